@@ -0,0 +1,441 @@
+use serde_json::{Value, json};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    process::{Child, Stdio},
+    sync::{Mutex, Once, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
+use yansi::Paint;
+
+use crate::{
+    runner::CommandRunner,
+    toolchain::Toolchain,
+    ui,
+    utils::{AnvilConfig, ProjectConfig},
+};
+
+/// Longest a node may take to fork its endpoint and start listening.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Line printed by `anvil` once it accepts requests.
+const LISTENING: &str = "Listening on";
+
+/// Number of transactions sent to a node by every run of [`bench`].
+pub const BENCH_TXS: usize = 100;
+
+/// Longest a JSON-RPC request of [`bench`] may take.
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval between two requests while waiting for a node to answer, or for a receipt.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The first two dev accounts of `anvil`, unlocked: the sender and recipient of the transactions
+/// of [`bench`].
+const SENDER: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+const RECIPIENT: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+
+/// Nodes still running, killed on Ctrl-C so that an interrupted run doesn't leave them behind.
+static NODES: Mutex<Vec<Child>> = Mutex::new(Vec::new());
+
+/// A local `anvil` node forking the RPC endpoint of a project's fork tests, so that the timings of
+/// its test runs don't depend on a remote endpoint, or a node benchmarked by [`bench`]. The node is
+/// killed when dropped.
+#[derive(Debug)]
+pub struct Anvil {
+    pid: u32,
+    /// Variable set to the node's endpoint in the env of `forge test`, empty for a benchmarked node.
+    env_var: String,
+    endpoint: String,
+    /// Stderr of a node started by [`bench`], read until it exits.
+    stderr: Option<thread::JoinHandle<Vec<u8>>>,
+}
+
+impl Anvil {
+    /// Starts the node of `project` with the `anvil` of `toolchain`, forking `fork_url`, and waits
+    /// until it accepts requests.
+    ///
+    /// Fails with the output of `anvil` if it exits or doesn't listen within a minute.
+    pub fn start(
+        project: &ProjectConfig,
+        config: &AnvilConfig,
+        fork_url: &str,
+        toolchain: &Toolchain,
+        runner: &dyn CommandRunner,
+    ) -> Result<Self, String> {
+        let port = match config.port {
+            0 => free_port().map_err(|e| format!("Failed to find a free port for anvil: {e}"))?,
+            port => port,
+        };
+
+        kill_on_interrupt();
+        tracing::info!(
+            "Starting anvil on port {port}, forking ${}",
+            config.fork_url_env
+        );
+        tracing::debug!("$ anvil --fork-url ${} --port {port}", config.fork_url_env);
+        let mut child = runner
+            .spawn(
+                toolchain
+                    .anvil()
+                    .args(["--fork-url", fork_url, "--port", &port.to_string()])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped()),
+            )
+            .map_err(|e| {
+                format!(
+                    "Failed to execute 'anvil' for {}. Error: {e:?}",
+                    project.name
+                )
+            })?;
+
+        // The output is drained for as long as the node runs, so that it never blocks on a full
+        // pipe. Only stderr is kept, to report a failed startup.
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let (lines, listening) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let _ = lines.send(line);
+            }
+        });
+        let stderr = thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = stderr.read_to_end(&mut output);
+            output
+        });
+
+        let started = loop {
+            match listening.recv_timeout(STARTUP_TIMEOUT) {
+                Ok(line) if line.contains(LISTENING) => break Ok(()),
+                Ok(_) => {}
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    break Err(format!(
+                        "'anvil' for {} didn't listen within {}s",
+                        project.name,
+                        STARTUP_TIMEOUT.as_secs()
+                    ));
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    break Err(format!("'anvil' for {} exited on startup", project.name));
+                }
+            }
+        };
+        if let Err(error) = started {
+            let _ = child.kill();
+            let status = child.wait().ok();
+            let stderr = stderr.join().unwrap_or_default();
+            ui::log_cmd_error(
+                &stderr,
+                status.and_then(|s| s.code()),
+                &format!("{} {error}", Paint::red("ERROR:").bold()),
+            );
+            let stderr = String::from_utf8_lossy(&stderr);
+            return Err(match stderr.trim() {
+                "" => error,
+                stderr => format!("{error}: {stderr}"),
+            });
+        }
+
+        let pid = child.id();
+        NODES.lock().unwrap().push(child);
+        tracing::info!("anvil is listening on port {port}");
+        Ok(Self {
+            pid,
+            env_var: config.fork_url_env.clone(),
+            endpoint: format!("http://127.0.0.1:{port}"),
+            stderr: None,
+        })
+    }
+
+    /// Spawns a node of `toolchain` without a fork on a free port, without waiting for it to
+    /// accept requests. Its stdout is discarded.
+    fn spawn_local(toolchain: &Toolchain, runner: &dyn CommandRunner) -> Result<Self, String> {
+        let port = free_port().map_err(|e| format!("Failed to find a free port for anvil: {e}"))?;
+        kill_on_interrupt();
+        tracing::debug!("$ anvil --port {port}");
+        let mut child = runner
+            .spawn(
+                toolchain
+                    .anvil()
+                    .args(["--port", &port.to_string()])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped()),
+            )
+            .map_err(|e| format!("Failed to execute 'anvil'. Error: {e:?}"))?;
+        let stderr = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut output = Vec::new();
+                let _ = stderr.read_to_end(&mut output);
+                output
+            })
+        });
+        let pid = child.id();
+        NODES.lock().unwrap().push(child);
+        Ok(Self {
+            pid,
+            env_var: String::new(),
+            endpoint: format!("http://127.0.0.1:{port}"),
+            stderr,
+        })
+    }
+
+    /// Why the node exited, with its stderr, if it did.
+    fn exit_error(&mut self) -> Option<String> {
+        let status = {
+            let mut nodes = NODES.lock().unwrap();
+            let child = nodes.iter_mut().find(|child| child.id() == self.pid)?;
+            child.try_wait().ok().flatten()?
+        };
+        let error = format!("'anvil' exited with status code: {:?}", status.code());
+        let stderr = self.stderr.take()?.join().unwrap_or_default();
+        Some(match String::from_utf8_lossy(&stderr).trim() {
+            "" => error,
+            stderr => format!("{error}: {stderr}"),
+        })
+    }
+
+    /// The variable replaced with the node's endpoint in the env of `forge test`.
+    pub fn env_var(&self) -> &str {
+        &self.env_var
+    }
+
+    /// The RPC endpoint of the node, e.g. `http://127.0.0.1:8545`.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+}
+
+impl Drop for Anvil {
+    fn drop(&mut self) {
+        let mut nodes = NODES.lock().unwrap();
+        if let Some(i) = nodes.iter().position(|child| child.id() == self.pid) {
+            let mut child = nodes.swap_remove(i);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Times of the `anvil` of a toolchain, measured by [`bench`], in seconds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnvilBench {
+    /// Time from spawning a node until it answers `eth_chainId`, for every run.
+    pub startup: Vec<f64>,
+    /// Time to mine [`BENCH_TXS`] transactions sent one after the other, for every run.
+    pub txs: Vec<f64>,
+}
+
+impl AnvilBench {
+    pub fn avg_startup(&self) -> f64 {
+        average(&self.startup)
+    }
+
+    pub fn avg_txs(&self) -> f64 {
+        average(&self.txs)
+    }
+}
+
+fn average(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len().max(1) as f64
+}
+
+/// Benchmarks the `anvil` of `toolchain` with `runs` fresh nodes: the time each one takes to
+/// answer its first request, then to mine [`BENCH_TXS`] transfers.
+///
+/// Every node is killed once its run is over, including when it fails.
+pub fn bench(
+    toolchain: &Toolchain,
+    runs: usize,
+    runner: &dyn CommandRunner,
+) -> Result<AnvilBench, String> {
+    let mut bench = AnvilBench::default();
+    for run in 1..=runs {
+        tracing::info!("anvil run {run}/{runs}");
+        let start_time = Instant::now();
+        let mut node = Anvil::spawn_local(toolchain, runner)?;
+        wait_until_ready(&mut node, start_time)?;
+        bench.startup.push(start_time.elapsed().as_secs_f64());
+
+        let start_time = Instant::now();
+        send_transfers(node.endpoint())?;
+        bench.txs.push(start_time.elapsed().as_secs_f64());
+    }
+    Ok(bench)
+}
+
+/// Waits until `node` answers `eth_chainId`, failing if it exits or doesn't answer within
+/// [`STARTUP_TIMEOUT`] of `start_time`.
+fn wait_until_ready(node: &mut Anvil, start_time: Instant) -> Result<(), String> {
+    loop {
+        if rpc(node.endpoint(), "eth_chainId", json!([])).is_ok() {
+            return Ok(());
+        }
+        if let Some(error) = node.exit_error() {
+            return Err(error);
+        }
+        if start_time.elapsed() > STARTUP_TIMEOUT {
+            return Err(format!(
+                "'anvil' didn't answer within {}s",
+                STARTUP_TIMEOUT.as_secs()
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Sends [`BENCH_TXS`] transfers to the node at `endpoint`, and waits for the receipt of the last.
+fn send_transfers(endpoint: &str) -> Result<(), String> {
+    let mut hash = Value::Null;
+    for _ in 0..BENCH_TXS {
+        hash = rpc(
+            endpoint,
+            "eth_sendTransaction",
+            json!([{ "from": SENDER, "to": RECIPIENT, "value": "0x1" }]),
+        )?;
+    }
+    let start_time = Instant::now();
+    while rpc(endpoint, "eth_getTransactionReceipt", json!([hash]))?.is_null() {
+        if start_time.elapsed() > RPC_TIMEOUT {
+            return Err(format!("transaction {hash} wasn't mined"));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Sends a JSON-RPC request to the node at `endpoint`, over a new connection, returning its
+/// result.
+fn rpc(endpoint: &str, method: &str, params: Value) -> Result<Value, String> {
+    let address = endpoint.trim_start_matches("http://");
+    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }).to_string();
+    let mut stream = TcpStream::connect(address).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(RPC_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    write!(
+        stream,
+        "POST / HTTP/1.1\r\nHost: {address}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .map_err(|e| e.to_string())?;
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|e| e.to_string())?;
+    let (_, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| format!("invalid response to {method}"))?;
+    let mut response: Value =
+        serde_json::from_str(body).map_err(|e| format!("invalid response to {method}: {e}"))?;
+    match response.get("error") {
+        Some(error) => Err(format!("{method} failed: {error}")),
+        None => Ok(response["result"].take()),
+    }
+}
+
+/// Kills every running node, before the process exits.
+pub fn kill_all() {
+    for mut child in NODES.lock().unwrap().drain(..) {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Kills the running nodes and exits on Ctrl-C, unless a handler is already installed: the
+/// commands handling Ctrl-C themselves (`diff --watch` and `bisect`) let the pipeline finish, or
+/// call [`kill_all`] before exiting.
+fn kill_on_interrupt() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let _ = ctrlc::try_set_handler(|| {
+            kill_all();
+            std::process::exit(130);
+        });
+    });
+}
+
+/// A port of the loopback interface that is free, as far as the OS knows.
+fn free_port() -> std::io::Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{
+        rpc_cache::read_request,
+        runner::{Response, ScriptedRunner},
+    };
+    use std::sync::Arc;
+
+    /// Starts a fake node answering the requests of [`send_transfers`], recording their methods.
+    /// Receipts are only found from the second request for one.
+    fn fake_node() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let methods = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&methods);
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                while let Ok(Some(http)) = read_request(&mut reader) {
+                    let request: Value = serde_json::from_slice(&http.body).unwrap();
+                    let method = request["method"].as_str().unwrap().to_string();
+                    let mut methods = recorded.lock().unwrap();
+                    let result = match method.as_str() {
+                        "eth_getTransactionReceipt" if !methods.contains(&method) => Value::Null,
+                        "eth_getTransactionReceipt" => json!({ "status": "0x1" }),
+                        _ => json!("0xabc"),
+                    };
+                    methods.push(method);
+                    let body = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+                        .to_string();
+                    let _ = write!(
+                        writer,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+                        body.len()
+                    );
+                    if http.close {
+                        break;
+                    }
+                }
+            }
+        });
+        (endpoint, methods)
+    }
+
+    #[test]
+    fn test_send_transfers() {
+        let (endpoint, methods) = fake_node();
+        send_transfers(&endpoint).unwrap();
+        let methods = methods.lock().unwrap();
+        assert_eq!(methods.len(), BENCH_TXS + 2);
+        assert!(
+            methods[..BENCH_TXS]
+                .iter()
+                .all(|method| method == "eth_sendTransaction")
+        );
+        assert_eq!(methods[BENCH_TXS..], ["eth_getTransactionReceipt"; 2]);
+    }
+
+    #[test]
+    fn test_bench_failed_startup() {
+        let runner = ScriptedRunner::default().on(
+            "anvil",
+            [Response::fail(2, "", "error: unexpected argument '--port'")],
+        );
+        let error = bench(&Toolchain::default(), 3, &runner).unwrap_err();
+        assert_eq!(
+            error,
+            "'anvil' exited with status code: Some(2): error: unexpected argument '--port'"
+        );
+        // The first failed run stops the benchmark.
+        assert_eq!(runner.calls().len(), 1);
+        assert!(runner.calls()[0].starts_with("anvil --port "));
+    }
+}
@@ -0,0 +1,2239 @@
+use crate::{
+    anvil,
+    benchmark::{self, Benchmarks, Failure, Measured, Source, SourceResults, Stage, TimingSource},
+    bisect, canary, clean,
+    cmd::{
+        self, CleanConfig, Cli, CompareConfig, DiffConfig, DiffSources, HistoryCommand,
+        MatrixConfig, MergeConfig, ProjectDiffConfig, ReportConfig, RerunConfig, TrendConfig,
+    },
+    config, discover, doctor,
+    flamegraph::{self, Flamegraphs},
+    gate::{self, BudgetCheck, GateReport},
+    history, interactive,
+    keep::{self, Keep},
+    logging, matrix, openmetrics, plan, provenance, redact, report,
+    reporter::ConsoleReporter,
+    repro::{self, Reproduction},
+    results::{self, RunResults},
+    runner::SystemRunner,
+    schema,
+    toolchain::{self, ForgeVersion, Toolchain},
+    ui,
+    utils::{self, ConfigOrigin, ProjectConfig},
+    validate, watch,
+};
+use clap::{ArgMatches, ValueEnum};
+use eyre::{Context, Result, eyre};
+use std::{fs, num::NonZeroUsize, process::ExitCode, time::Duration};
+use yansi::Paint;
+
+/// Runs the command of `cli`, parsed into `matches`, and returns the exit code of the binary.
+pub fn run(cli: Cli, matches: &ArgMatches) -> Result<ExitCode> {
+    if cli.keep || cli.keep_failed {
+        let keep = if cli.keep { Keep::All } else { Keep::Failed };
+        let root = utils::work_dir_path().join(format!(
+            "{}{}",
+            keep::KEPT_PREFIX,
+            utils::unix_timestamp()
+        ));
+        keep::configure(keep, root);
+    }
+    let code = run_command(cli, matches);
+    log_kept_dirs();
+    code
+}
+
+/// Prints the project directories kept by `--keep` or `--keep-failed`, or removes the directory
+/// of the run if none was kept, e.g. since every project passed.
+fn log_kept_dirs() {
+    let Some(root) = keep::root() else {
+        return;
+    };
+    let kept = keep::kept();
+    if kept.is_empty() {
+        let _ = fs::remove_dir(&root);
+        return;
+    }
+    println!(
+        "\nKept the project directories in {} (removed by `clean`):",
+        root.display()
+    );
+    for (name, dir) in kept {
+        println!(" - {name}: {}", dir.display());
+    }
+}
+
+/// Runs the command of `cli`, see [`run`].
+fn run_command(mut cli: Cli, matches: &ArgMatches) -> Result<ExitCode> {
+    ui::set_log_level(cli.log_level);
+    ui::set_full_errors(cli.full_errors);
+    ui::set_error_lines(cli.error_lines);
+    logging::init(cli.log_level, cli.log_format);
+    cli.read_repo_lists(std::io::stdin())?;
+    if let Some(schema) = cli.schema_config() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schema::render(schema.kind))?
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(init) = cli.init_config() {
+        let path = cli.config.as_deref().unwrap_or(config::DEFAULT_CONFIG);
+        config::init(path, init.force, init.minimal)?;
+        println!("Wrote {path}");
+        return Ok(ExitCode::SUCCESS);
+    }
+    if cli.config.is_none() {
+        let cwd = std::env::current_dir()?;
+        if let Some(path) = config::discover(&cwd, config::config_home().as_deref()) {
+            // Printed to stderr, to keep the output of e.g. `list --json` parseable.
+            eprintln!("Using config file {}", path.display());
+            cli.config = Some(path.display().to_string());
+        }
+    }
+    // Unreadable config files are reported by the commands that need them.
+    let (secrets, github_base_url) = cli
+        .config
+        .as_deref()
+        .and_then(|path| config::ConfigFile::load_lenient(path).ok())
+        .map(|(file, _)| (file.secrets, file.defaults.github_base_url))
+        .unwrap_or_default();
+    redact::configure(&secrets);
+    utils::set_github_url(cli.github_url.clone().or(github_base_url));
+    if let Some(name) = cli.preset.clone() {
+        let path = cli.config.as_deref().ok_or_else(|| {
+            eyre!(
+                "{} --preset {name} needs a config file, none was found",
+                Paint::red("ERROR:").bold()
+            )
+        })?;
+        let preset = config::ConfigFile::load(path, cli.lenient_config)?
+            .preset(&name)?
+            .clone();
+        cli.apply_preset(preset, |id| cmd::is_explicit(matches, id));
+        eprintln!("Using preset {name}");
+    }
+    cli.check_stop_after()?;
+    cli.check_dry_run()?;
+    if let Some(config) = cli.clean_config() {
+        return run_clean(&cli, config);
+    }
+    if let Some(config) = cli.bisect_config() {
+        check_network_isolation(&cli)?;
+        let foundryup = toolchain::resolve_foundryup(config.foundryup_bin.as_deref())?;
+        // The project keeps its configuration, if any, but isn't required to be configured.
+        let project = cli
+            .get_repos()
+            .ok()
+            .and_then(|repos| repos.into_iter().find(|repo| repo.name == config.project))
+            .unwrap_or_else(|| ProjectConfig::new(&config.project));
+        bisect::run(config, project, cli.verbosity, &foundryup, cli.keep)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(config) = cli.project_diff_config() {
+        check_network_isolation(&cli)?;
+        return run_project_diff(&cli, config);
+    }
+    if let Some(rerun) = cli.rerun_config() {
+        check_network_isolation(&cli)?;
+        return run_rerun(&cli, rerun);
+    }
+    if let Some(history) = cli.history_config() {
+        let path = history_path(&cli)?;
+        if let Some(HistoryCommand::Trend(trend)) = &history.command {
+            run_trend(&path, trend)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        let entries = history::filter(
+            history::load(&path)?,
+            history.since,
+            &history.projects,
+            history.last,
+        );
+        if entries.is_empty() {
+            println!("No recorded runs in {}", path.display());
+        } else {
+            ui::log_history(&entries);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(doctor) = cli.doctor_config() {
+        let config_path = cli.config.as_deref().unwrap_or(config::DEFAULT_CONFIG);
+        return Ok(run_doctor(
+            config_path,
+            cli.lenient_config,
+            doctor.foundryup_bin.as_deref(),
+        ));
+    }
+    if cli.is_validate() {
+        return run_validate(cli.config.as_deref().unwrap_or(config::DEFAULT_CONFIG));
+    }
+    if let Some(discover) = cli.discover_config() {
+        let config_path = cli.config.as_deref().unwrap_or(config::DEFAULT_CONFIG);
+        discover::run(discover, config_path, &SystemRunner)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(list) = cli.list_config() {
+        let (repos, _) = cli.exclude_repos(cli.get_repos()?);
+        let repos: Vec<_> = repos.iter().map(|p| p.masked()).collect();
+        if list.json {
+            println!("{}", serde_json::to_string_pretty(&repos)?);
+        } else {
+            ui::log_project_configs(&repos);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(compare) = cli.compare_config() {
+        run_compare(compare, cli.noise_threshold, cli.noisy_threshold)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(merge) = cli.merge_config() {
+        run_merge(merge)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(report) = cli.report_config() {
+        run_report(report, cli.noise_threshold, cli.noisy_threshold)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let (repos, skipped) = selected_repos(&mut cli)?;
+    if let Some(matrix) = cli.matrix_config() {
+        check_network_isolation(&cli)?;
+        return run_matrix(&cli, matrix, (repos, skipped));
+    }
+
+    if cli.dry_run {
+        print!(
+            "{}",
+            plan::render(
+                &repos,
+                &skipped,
+                cli.num_runs,
+                cli.verbosity,
+                cli.stop_after,
+                cli.get_cmd()?.as_ref(),
+            )
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    if cli.stop_after == Stage::Test {
+        check_network_isolation(&cli)?;
+    }
+    match cli.get_cmd()? {
+        None => {
+            let toolchain = Toolchain::default();
+            let pipeline = benchmark::run_pipeline(
+                &repos,
+                cli.num_runs,
+                cli.verbosity,
+                cli.stop_after,
+                &toolchain,
+                &SystemRunner,
+                &ConsoleReporter,
+            )?;
+            ui::banner(Some("BENCHMARK SUMMARY"));
+
+            if cli.stop_after == Stage::Clone {
+                for project in repos
+                    .iter()
+                    .filter(|p| !pipeline.failures.iter().any(|f| f.project == p.name))
+                {
+                    println!(
+                        " * {} ({}) cloned",
+                        Paint::primary(&project.name).bold(),
+                        Paint::cyan(&project.url())
+                    );
+                }
+            }
+            let budgets: Vec<Vec<BudgetCheck>> = pipeline
+                .tested
+                .iter()
+                .map(|project| gate::check_budget(&repos, project))
+                .collect();
+            for (project, checks) in pipeline.tested.iter().zip(&budgets) {
+                println!(
+                    " * {} ({})",
+                    Paint::primary(&project.name).bold(),
+                    Paint::cyan(&project.url)
+                );
+                if let Some(commit) = &project.commit {
+                    println!("   - commit:     {commit}");
+                }
+                let budget = |metric: &str| {
+                    ui::budget_suffix(checks.iter().find(|check| check.metric == metric))
+                };
+                if project.command == Measured::Fmt {
+                    if project.runs > 0 {
+                        println!(
+                            "   - fmt time:   {:.2}s (avg for {} runs){}",
+                            project.avg_test_time,
+                            project.runs,
+                            budget("fmt")
+                        );
+                    }
+                } else {
+                    println!(
+                        "   - build time: {:.2}s{}",
+                        project.build_time,
+                        budget("build")
+                    );
+                    if cli.measure_discovery {
+                        match project.discovery_time {
+                            Some(time) => println!("   - discovery:  {time:.2}s"),
+                            None => println!("   - discovery:  n/a"),
+                        }
+                    }
+                    if cli.incremental.is_some() {
+                        match project.incremental_build_time {
+                            Some(time) => println!(
+                                "   - incremental: {time:.2}s (avg for {} runs)",
+                                project.incremental_samples.len()
+                            ),
+                            None => println!("   - incremental: n/a"),
+                        }
+                    }
+                    if project.build_only {
+                        println!("   - test time:  tests skipped (build only)");
+                    } else if project.runs > 0 {
+                        let cv = ui::coefficient_of_variation(&project.samples);
+                        println!(
+                            "   - test time:  {:.2}s (avg for {} runs{}{}){}{}",
+                            project.avg_test_time,
+                            project.runs,
+                            if project.timing_source == TimingSource::Forge {
+                                ", reported by forge"
+                            } else {
+                                ""
+                            },
+                            cv.map(|cv| format!(", CV {cv:.1}%")).unwrap_or_default(),
+                            budget("test"),
+                            if cv.is_some_and(|cv| cv > cli.noisy_threshold) {
+                                format!(" {}", Paint::yellow(ui::NOISY_MARKER).bold())
+                            } else {
+                                String::new()
+                            }
+                        );
+                    }
+                }
+                if let Some(script_time) = project.avg_script_time {
+                    println!(
+                        "   - script time: {script_time:.2}s (avg for {} runs)",
+                        project.script_samples.len()
+                    );
+                }
+            }
+            let noisy = pipeline
+                .tested
+                .iter()
+                .filter(|project| {
+                    ui::coefficient_of_variation(&project.samples)
+                        .is_some_and(|cv| cv > cli.noisy_threshold)
+                })
+                .count();
+            if noisy > 0 {
+                println!(
+                    "\n{noisy} of {} projects {}: their test runs vary by more than {}% (--noisy-threshold)",
+                    pipeline.tested.len(),
+                    ui::NOISY_MARKER,
+                    cli.noisy_threshold
+                );
+            }
+            ui::banner(None);
+            benchmark::log_failed_projects(&pipeline.failures);
+            benchmark::log_missing_env_examples(&pipeline.missing_env_examples);
+            benchmark::log_skipped_projects(&skipped);
+            let over_budget: Vec<&BudgetCheck> = budgets
+                .iter()
+                .flatten()
+                .filter(|check| check.exceeded)
+                .collect();
+            ui::log_over_budget(&over_budget, cli.enforce_budgets);
+            let budgets_failed = cli.enforce_budgets && !over_budget.is_empty();
+
+            let failed = !pipeline.failures.is_empty();
+            if cli.save.is_some() || cli.exports_results() {
+                let results = plain_results(&cli, &repos, &toolchain, pipeline);
+                save_results(&cli, cli.save.as_deref(), results)?;
+            }
+            Ok(run_exit_code(&cli, failed, !budgets_failed))
+        }
+        Some(sources) => {
+            let code = run_diff(&cli, &repos, sources)?;
+            benchmark::log_skipped_projects(&skipped);
+            Ok(code)
+        }
+    }
+}
+
+/// Notes below the test tables about the settings every run was timed with: the number of test
+/// threads (`--forge-threads`) and the clean environment (`--clean-env`).
+fn run_notes(cli: &Cli) -> Vec<String> {
+    cli.forge_threads
+        .map(|threads| results::threads_note(threads.get()))
+        .into_iter()
+        .chain(cli.clean_env.then(|| results::CLEAN_ENV_NOTE.to_string()))
+        .collect()
+}
+
+/// Checks that the timed test runs can be isolated from the network if `--no-network` is set,
+/// before running any of them.
+fn check_network_isolation(cli: &Cli) -> Result<()> {
+    if cli.no_network {
+        toolchain::check_network_isolation(&SystemRunner)?;
+    }
+    Ok(())
+}
+
+/// Resolves the projects to benchmark, narrowed down with a prompt if `--interactive` is set.
+///
+/// The projects missing some of their required environment variables fail the run, or are
+/// returned apart with `--skip-missing-env`. A `--limit` that doesn't leave any project out is
+/// cleared from `cli`, so that the results don't record it.
+fn selected_repos(cli: &mut Cli) -> Result<(Vec<ProjectConfig>, Vec<benchmark::Skipped>)> {
+    let (repos, section) = cli.get_repos_with_section()?;
+    match &cli.config {
+        Some(config) => println!(
+            "Using the [{}] section of {config} (--config-profile {})",
+            match section {
+                ConfigOrigin::Custom => "custom",
+                _ => "defaults",
+            },
+            cli.config_profile
+        ),
+        None => println!("No config file found, using the built-in defaults"),
+    }
+    let (mut repos, excluded) = cli.exclude_repos(repos);
+    for pattern in &excluded.unmatched {
+        eprintln!(
+            "{} --exclude {pattern} matches none of the projects",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+    if !excluded.names.is_empty() {
+        println!("Excluding {}", excluded.names.join(", "));
+    }
+    if repos.is_empty() {
+        return Err(eyre!(
+            "{} All the projects are excluded by --exclude",
+            Paint::red("ERROR:").bold()
+        ));
+    }
+    match cli.limit.map(NonZeroUsize::get) {
+        Some(limit) if limit < repos.len() => {
+            println!(
+                "Limiting the run to the first {limit} of {} projects (--limit)",
+                repos.len()
+            );
+            repos.truncate(limit);
+        }
+        Some(_) => cli.limit = None,
+        None => {}
+    }
+    let repos = if cli.interactive {
+        interactive::pick_projects(cli, repos)?
+    } else {
+        repos
+    };
+    benchmark::check_required_env(repos, cli.skip_missing_env)
+}
+
+/// Describes the run of `repos` against the sources of `results`, see [`Reproduction`].
+fn reproduction(cli: &Cli, repos: &[ProjectConfig], results: &[SourceResults]) -> Reproduction {
+    let sources: Vec<String> = results
+        .iter()
+        .map(|r| repro::source_key(&r.source))
+        .collect();
+    Reproduction::new(cli, repos, &sources)
+}
+
+/// Makes the test times of `results` comparable, warning about the projects timed with different
+/// timing sources, see [`benchmark::align_timing_sources`].
+fn align_timing_sources(results: &mut [SourceResults]) {
+    for name in benchmark::align_timing_sources(results) {
+        eprintln!(
+            "{} {name} was timed with different timing sources, comparing the process times",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+}
+
+/// Results of a plain run, to be saved with `--save` or recorded with `--record`.
+fn plain_results(
+    cli: &Cli,
+    repos: &[ProjectConfig],
+    toolchain: &Toolchain,
+    pipeline: benchmark::PipelineResults,
+) -> RunResults {
+    let forge_version = match toolchain.forge_version() {
+        Ok(version) => Some(version),
+        Err(e) => {
+            eprintln!(
+                "{} Unknown forge version, the results won't record it. {e}",
+                Paint::yellow("WARNING:").bold()
+            );
+            None
+        }
+    };
+    let projects = repos.iter().map(|repo| repo.name.clone()).collect();
+    let mut results = RunResults::new(
+        forge_version,
+        cli.num_runs,
+        cli.verbosity,
+        projects,
+        pipeline.tested,
+        pipeline.failures,
+    );
+    // The `forge` of a plain run is what `compare` compares, so it's left out of the fingerprint.
+    results.reproduction = Some(Reproduction::new(cli, repos, &[]));
+    results
+}
+
+/// Runs the `clean` subcommand: reports the size of every managed location, and removes the
+/// selected ones.
+///
+/// Locations that fail to be removed are reported, and make the command fail once all the others
+/// have been removed.
+fn run_clean(cli: &Cli, config: &CleanConfig) -> Result<ExitCode> {
+    // Without a user data directory, there is no default history file to report.
+    let history = history_path(cli).ok();
+    let locations = clean::locations(&utils::work_dir_path(), history.as_deref());
+    if locations.is_empty() {
+        println!("Nothing to clean.");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let selected = |kind: clean::Kind| match kind {
+        clean::Kind::Cache => !config.work_only,
+        clean::Kind::Work => !config.cache_only,
+        clean::Kind::History => config.history,
+    };
+    let (mut freed, mut failed) = (0, 0);
+    for location in &locations {
+        let size = clean::format_size(location.size);
+        let status = if !selected(location.kind) {
+            "kept"
+        } else if config.dry_run {
+            freed += location.size;
+            "would remove"
+        } else {
+            match location.remove() {
+                Ok(()) => {
+                    freed += location.size;
+                    "removed"
+                }
+                Err(e) => {
+                    failed += 1;
+                    eprintln!(
+                        "{} Failed to remove {}: {e}",
+                        Paint::red("ERROR:").bold(),
+                        location.path.display()
+                    );
+                    "failed"
+                }
+            }
+        };
+        println!(
+            "{:<8}{size:>10}  {}  ({status})",
+            location.kind.to_string(),
+            location.path.display()
+        );
+    }
+
+    let verb = if config.dry_run {
+        "Would free"
+    } else {
+        "Freed"
+    };
+    println!("\n{verb} {}.", clean::format_size(freed));
+    if locations
+        .iter()
+        .any(|l| l.kind == clean::Kind::History && !config.history)
+    {
+        println!("note: the history file is kept, pass --history to remove it.");
+    }
+    Ok(if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Runs the `rerun` subcommand: benchmarks the projects that failed in a saved plain run, with the
+/// settings recorded in it, and merges their results back into it.
+///
+/// Exits with [`benchmark::FAILED_PROJECTS_EXIT_CODE`] if some projects still fail.
+fn run_rerun(cli: &Cli, rerun: &RerunConfig) -> Result<ExitCode> {
+    let mut recorded = RunResults::load(&rerun.from)?;
+    if recorded.sources.len() > 1 {
+        return Err(eyre!(
+            "{} {} holds the results of a diff, but rerun only supports the results of a plain run",
+            Paint::red("ERROR:").bold(),
+            rerun.from
+        ));
+    }
+
+    let failed = recorded.failed_projects();
+    if failed.is_empty() {
+        println!("No failed projects in {}", rerun.from);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let toolchain = Toolchain::default();
+    let current = toolchain.forge_version().ok();
+    let same_forge = match (&recorded.baseline().forge_version, &current) {
+        (Some(recorded), Some(current)) => recorded.same_build(current),
+        _ => false,
+    };
+    if !same_forge {
+        let describe = |forge: Option<&ForgeVersion>| {
+            forge.map_or_else(|| "unknown".to_string(), ToString::to_string)
+        };
+        let msg = format!(
+            "The results were recorded with forge {}, but the active forge is {}",
+            describe(recorded.baseline().forge_version.as_ref()),
+            describe(current.as_ref())
+        );
+        if !rerun.force {
+            return Err(eyre!(
+                "{} {msg}. Pass --force to merge the results anyway.",
+                Paint::red("ERROR:").bold()
+            ));
+        }
+        eprintln!("{} {msg}", Paint::yellow("WARNING:").bold());
+    }
+
+    // Resolve the configuration of the failed projects, which may have been fixed since.
+    let configured = cli.get_repos()?;
+    let repos: Vec<ProjectConfig> = failed
+        .iter()
+        .map(|name| {
+            let mut repo = configured
+                .iter()
+                .find(|repo| &repo.name == name)
+                .cloned()
+                .unwrap_or_else(|| ProjectConfig::new(name));
+            // Timed like the recorded projects, whatever the flags of this run.
+            recorded.apply_run_settings(&mut repo);
+            repo
+        })
+        .collect();
+    let threads = cli.forge_threads.map(NonZeroUsize::get);
+    if threads.is_some() && threads != recorded.forge_threads {
+        eprintln!(
+            "{} The recorded results were tested on {} forge threads, the rerun ignores --forge-threads to match them",
+            Paint::yellow("WARNING:").bold(),
+            results::describe_threads(recorded.forge_threads)
+        );
+    }
+    if recorded.clean_env != cli.clean_env {
+        eprintln!(
+            "{} The recorded results ran forge with the {} environment, the rerun ignores --clean-env to match them",
+            Paint::yellow("WARNING:").bold(),
+            results::describe_env(recorded.clean_env)
+        );
+    }
+    if let Some(timing_source) = recorded.timing_source()
+        && cli.timing_source != timing_source
+    {
+        eprintln!(
+            "{} The recorded results were timed with the {} clock, the rerun ignores --timing-source to match them",
+            Paint::yellow("WARNING:").bold(),
+            timing_source
+                .to_possible_value()
+                .expect("no skipped value")
+                .get_name()
+        );
+    }
+
+    println!("Rerunning the failed projects of {}:", rerun.from);
+    for name in &failed {
+        match recorded
+            .baseline()
+            .failures
+            .iter()
+            .find(|f| &f.project == name)
+        {
+            Some(failure) => println!(" - {name} (failed at stage '{}')", failure.stage),
+            None => println!(" - {name}"),
+        }
+    }
+
+    let pipeline = benchmark::run_pipeline(
+        &repos,
+        recorded.num_runs,
+        recorded.verbosity,
+        Stage::Test,
+        &toolchain,
+        &SystemRunner,
+        &ConsoleReporter,
+    )?;
+    benchmark::log_failed_projects(&pipeline.failures);
+    benchmark::log_missing_env_examples(&pipeline.missing_env_examples);
+    let (recovered, still_failing) = (pipeline.tested.len(), pipeline.failures.len());
+    recorded.merge_rerun(pipeline);
+
+    let out = rerun.out.as_deref().unwrap_or(&rerun.from);
+    recorded.save(out)?;
+    println!(
+        "\nMerged {recovered} recovered project(s) into {out} ({still_failing} still failing)"
+    );
+    Ok(run_exit_code(cli, still_failing > 0, true))
+}
+
+/// Saves the results of a run to `path` (if any), so that they can be reused as the baseline of a
+/// diff, appends them to the history file if `--record` is set, and exports them as metrics with
+/// `--export-openmetrics` and `--push-gateway`.
+fn save_results(cli: &Cli, path: Option<&str>, mut results: RunResults) -> Result<()> {
+    results.preset = cli.preset.clone();
+    results.limit = cli.limit.map(NonZeroUsize::get);
+    results.forge_threads = cli.forge_threads.map(NonZeroUsize::get);
+    results.clean_env = cli.clean_env;
+    if let Some(path) = path {
+        results.save(path)?;
+        println!("\nSaved results to {path}");
+    }
+    if cli.record {
+        let history = history_path(cli)?;
+        history::record(&history, &results)?;
+        println!("\nRecorded results to {}", history.display());
+    }
+    if let Some(path) = &cli.export_openmetrics {
+        openmetrics::export(&results, path)?;
+        println!("\nExported metrics to {path}");
+    }
+    // The results are already saved, a failed push doesn't fail the run.
+    if let Some(url) = &cli.push_gateway {
+        match openmetrics::push(&results, url) {
+            Ok(()) => println!("\nPushed metrics to {url}"),
+            Err(e) => eprintln!(
+                "{} Failed to push the metrics to {url}: {e}",
+                Paint::yellow("WARNING:").bold()
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// The history file of `--record` and `history`.
+fn history_path(cli: &Cli) -> Result<std::path::PathBuf> {
+    match &cli.history_file {
+        Some(path) => Ok(path.into()),
+        None => history::default_path(),
+    }
+}
+
+/// Loads the recorded baseline of a diff, ensuring that it is comparable with the current run.
+fn load_baseline(cli: &Cli, repos: &[ProjectConfig], path: &str) -> Result<RunResults> {
+    let recorded = RunResults::load(path)?;
+    let projects: Vec<String> = repos.iter().map(|repo| repo.name.clone()).collect();
+    recorded
+        .check_compatible(&projects, cli.num_runs)
+        .map_err(|e| eyre!("{} {e}", Paint::red("ERROR:").bold()))?;
+    // Every side of a diff must run its tests on as many threads.
+    let threads = cli.forge_threads.map(NonZeroUsize::get);
+    if recorded.forge_threads != threads {
+        return Err(eyre!(
+            "{} The recorded results were tested on {} forge threads, but this run uses {} (--forge-threads)",
+            Paint::red("ERROR:").bold(),
+            results::describe_threads(recorded.forge_threads),
+            results::describe_threads(threads)
+        ));
+    }
+    if recorded.clean_env != cli.clean_env {
+        eprintln!(
+            "{} The recorded results ran forge with the {} environment, but this run uses the {} one (--clean-env)",
+            Paint::yellow("WARNING:").bold(),
+            results::describe_env(recorded.clean_env),
+            results::describe_env(cli.clean_env)
+        );
+    }
+    if recorded.verbosity != cli.verbosity {
+        eprintln!(
+            "{} The recorded results were tested with verbosity {}, but this run uses {}",
+            Paint::yellow("WARNING:").bold(),
+            recorded.verbosity,
+            cli.verbosity
+        );
+    }
+    let limit = cli.limit.map(NonZeroUsize::get);
+    if recorded.limit != limit {
+        let describe =
+            |limit: Option<usize>| limit.map_or_else(|| "no".to_string(), |n| n.to_string());
+        eprintln!(
+            "{} The recorded results were benchmarked with {} --limit, but this run uses {}",
+            Paint::yellow("WARNING:").bold(),
+            describe(recorded.limit),
+            describe(limit)
+        );
+    }
+    let differing: Vec<&str> = recorded
+        .baseline()
+        .results
+        .iter()
+        .filter(|tested| {
+            repos.iter().any(|repo| {
+                repo.name == tested.name
+                    && (&repo.prepare, &repo.conclude) != (&tested.prepare, &tested.conclude)
+            })
+        })
+        .map(|tested| tested.name.as_str())
+        .collect();
+    if !differing.is_empty() {
+        eprintln!(
+            "{} The recorded results ran other --prepare/--conclude hooks than this run for: {}",
+            Paint::yellow("WARNING:").bold(),
+            differing.join(", ")
+        );
+    }
+    Ok(recorded)
+}
+
+/// Runs `history trend`: plots the test time of every project over the most recent runs recorded
+/// in the history file at `path`.
+///
+/// Runs recorded on other machines are left out unless `--all-hosts` is set, as their times
+/// aren't comparable.
+fn run_trend(path: &std::path::Path, config: &TrendConfig) -> Result<()> {
+    let mut entries = history::load(path)?;
+    let mut excluded = 0;
+    if !config.all_hosts {
+        let fingerprint = history::current_fingerprint();
+        let total = entries.len();
+        entries.retain(|entry| entry.host_fingerprint == fingerprint);
+        excluded = total - entries.len();
+    }
+    let entries = history::filter(entries, None, &config.projects, config.last);
+
+    if entries.is_empty() {
+        let machine = if excluded > 0 { " on this machine" } else { "" };
+        println!("No runs recorded{machine} in {}", path.display());
+    } else {
+        ui::log_trends(&history::trends(&entries), entries.len());
+    }
+    if excluded > 0 {
+        println!(
+            "\nnote: {excluded} run(s) recorded on other machines were left out, pass --all-hosts to include them."
+        );
+    }
+    Ok(())
+}
+
+/// Prints the outcome of every environment check, failing if any required check failed.
+fn run_doctor(config_path: &str, lenient_config: bool, foundryup_bin: Option<&str>) -> ExitCode {
+    let checks = doctor::run_checks(config_path, lenient_config, foundryup_bin);
+    for check in &checks {
+        check.log();
+    }
+    if checks.iter().any(|c| c.is_fatal()) {
+        println!(
+            "\n{} some required checks failed.",
+            Paint::red("ERROR:").bold()
+        );
+        ExitCode::FAILURE
+    } else {
+        println!("\nThe environment is ready to run benchmarks.");
+        ExitCode::SUCCESS
+    }
+}
+
+/// Reports the findings of [`validate::validate`], failing only if any of them is an error.
+fn run_validate(path: &str) -> Result<ExitCode> {
+    let report = validate::validate(path)?;
+    for finding in &report.findings {
+        println!("{finding}");
+    }
+    if report.findings.is_empty() {
+        println!("{path} is valid.");
+    } else {
+        println!(
+            "\n{path}: {} error(s), {} warning(s).",
+            report.errors(),
+            report.warnings()
+        );
+    }
+    Ok(if report.errors() > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Runs the `matrix` subcommand: benchmarks the selected projects (see [`selected_repos`]) once
+/// per variant of the Foundry settings, with the `forge` in the user's `PATH`, and compares every
+/// variant against the first.
+///
+/// The projects that fail with a variant are recorded as its failures, without affecting the
+/// results of the other variants.
+fn run_matrix(
+    cli: &Cli,
+    config: &MatrixConfig,
+    (repos, skipped): (Vec<ProjectConfig>, Vec<benchmark::Skipped>),
+) -> Result<ExitCode> {
+    let variants = matrix::resolve(&config.variants, cli.config.as_deref(), cli.lenient_config)?;
+    let forge = Toolchain::default().forge_version()?;
+
+    ui::big_banner("FOUNDRY BENCHMARKS");
+    println!("Forge version          {forge}");
+    for (i, variant) in variants.iter().enumerate() {
+        let role = if i == 0 {
+            "Baseline variant"
+        } else {
+            "Variant"
+        };
+        println!("{role:<23}{}: {}", variant.name, variant.describe());
+    }
+    println!("Number of test runs    {}", cli.num_runs);
+    println!("Test verbosity         {}", cli.verbosity);
+
+    let toolchains: Vec<(&str, Toolchain)> = variants
+        .iter()
+        .map(|variant| {
+            (
+                variant.name.as_str(),
+                Toolchain::default().with_env(variant.env.clone()),
+            )
+        })
+        .collect();
+    let pipelines = benchmark::run_matrix_pipeline(
+        &repos,
+        cli.num_runs,
+        cli.verbosity,
+        &toolchains,
+        &SystemRunner,
+        &ConsoleReporter,
+    )?;
+
+    let mut notes = vec![format!("every variant ran forge {forge}.")];
+    notes.extend(run_notes(cli));
+    let mut failures = Vec::new();
+    let mut missing_env_examples = Vec::new();
+    let mut failed_variants = Vec::new();
+    let mut results = Vec::new();
+    for (variant, pipeline) in variants.into_iter().zip(pipelines) {
+        notes.push(format!("variant {}: {}.", variant.name, variant.describe()));
+        if !pipeline.failures.is_empty() {
+            let failed: Vec<&str> = pipeline
+                .failures
+                .iter()
+                .map(|f| f.project.as_str())
+                .collect();
+            notes.push(format!(
+                "failed with {}: {}.",
+                variant.name,
+                failed.join(", ")
+            ));
+        }
+        if pipeline.tested.is_empty() && !repos.is_empty() {
+            failed_variants.push(variant.name.clone());
+        }
+        failures.push(pipeline.failures);
+        missing_env_examples.extend(pipeline.missing_env_examples);
+        results.push(SourceResults {
+            source: Source::Env {
+                label: variant.name,
+                vars: variant.env,
+            },
+            // Every variant runs the `forge` in `PATH`, whose repository isn't known.
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: Some(forge.clone()),
+            tests: pipeline.tested,
+        });
+    }
+
+    align_timing_sources(&mut results);
+    let benchmarks = Benchmarks {
+        verbosity: benchmark::verbosity_flag(cli.verbosity),
+        num_runs: cli.num_runs,
+        reproduction: Some(reproduction(cli, &repos, &results)),
+        results,
+        notes,
+        noise_threshold: cli.noise_threshold,
+        noisy_threshold: cli.noisy_threshold,
+    };
+    ui::log_test_table(&benchmarks);
+    benchmark::log_failed_projects(failures.iter().flatten());
+    benchmark::log_missing_env_examples(&missing_env_examples);
+    benchmark::log_skipped_projects(&skipped);
+    let failed = failures.iter().any(|f| !f.is_empty());
+
+    if config.save.is_some() || cli.exports_results() {
+        let projects = repos.iter().map(|repo| repo.name.clone()).collect();
+        let mut results = RunResults::from_benchmarks(&benchmarks, cli.verbosity, projects, None);
+        for (source, failures) in results.sources.iter_mut().zip(failures) {
+            source.failures = failures;
+        }
+        save_results(cli, config.save.as_deref(), results)?;
+    }
+
+    if !failed_variants.is_empty() {
+        return Err(eyre!(
+            "{} No project could be benchmarked with the following variants: {}",
+            Paint::red("ERROR:").bold(),
+            failed_variants.join(", ")
+        ));
+    }
+    Ok(run_exit_code(cli, failed, true))
+}
+
+/// Runs the `project-diff` subcommand: benchmarks two revisions of a project with the `forge` in
+/// the user's `PATH`, and compares the second against the first.
+fn run_project_diff(cli: &Cli, config: &ProjectDiffConfig) -> Result<ExitCode> {
+    // The project keeps its configuration, if any, but isn't required to be configured.
+    let project = cli
+        .get_repos()
+        .ok()
+        .and_then(|repos| repos.into_iter().find(|repo| repo.name == config.project))
+        .unwrap_or_else(|| ProjectConfig::new(&config.project));
+    let toolchain = Toolchain::default();
+    let forge = toolchain.forge_version()?;
+
+    ui::big_banner("FOUNDRY BENCHMARKS");
+    println!("Forge version          {forge}");
+    println!("Project                {}", project.name);
+    println!("Before revision        {}", config.before);
+    println!("After revision         {}", config.after);
+    println!("Number of test runs    {}", cli.num_runs);
+    println!("Test verbosity         {}", cli.verbosity);
+
+    let mut results = Vec::new();
+    for (side, rev) in [("before", &config.before), ("after", &config.after)] {
+        let repos = [ProjectConfig {
+            rev: Some(rev.clone()),
+            ..project.clone()
+        }];
+        ui::big_banner(&format!("BENCHMARK --> {side} ({rev})"));
+        let pipeline = benchmark::run_pipeline(
+            &repos,
+            cli.num_runs,
+            cli.verbosity,
+            Stage::Test,
+            &toolchain,
+            &SystemRunner,
+            &ConsoleReporter,
+        )?;
+        benchmark::log_failed_projects(&pipeline.failures);
+        benchmark::log_missing_env_examples(&pipeline.missing_env_examples);
+        if pipeline.tested.is_empty() {
+            return Err(eyre!(
+                "{} {} could not be benchmarked at {rev}, there is nothing to compare",
+                Paint::red("ERROR:").bold(),
+                project.name
+            ));
+        }
+        results.push(SourceResults {
+            source: Source::Current {
+                forge: Some(forge.clone()),
+            },
+            // Both revisions run the `forge` in `PATH`, whose repository isn't known.
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: Some(rev.clone()),
+            forge: Some(forge.clone()),
+            tests: pipeline.tested,
+        });
+    }
+
+    align_timing_sources(&mut results);
+    let mut notes = vec![format!(
+        "both revisions of {} ran forge {forge}.",
+        project.name
+    )];
+    notes.extend(run_notes(cli));
+    notes.extend(ui::test_count_note(&results));
+    // The revisions, rather than the (identical) sources, tell the sides apart.
+    let sources = [&config.before, &config.after].map(|rev| format!("rev:{rev}"));
+    let benchmarks = Benchmarks {
+        verbosity: benchmark::verbosity_flag(cli.verbosity),
+        num_runs: cli.num_runs,
+        reproduction: Some(Reproduction::new(
+            cli,
+            std::slice::from_ref(&project),
+            &sources,
+        )),
+        results,
+        notes,
+        noise_threshold: cli.noise_threshold,
+        noisy_threshold: cli.noisy_threshold,
+    };
+    ui::log_test_table(&benchmarks);
+
+    if config.save.is_some() || cli.exports_results() {
+        let results = RunResults::from_benchmarks(
+            &benchmarks,
+            cli.verbosity,
+            vec![project.name.clone()],
+            None,
+        );
+        save_results(cli, config.save.as_deref(), results)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs the `report` subcommand: renders a saved results file, to stdout or to `--out`.
+fn run_report(
+    config: &ReportConfig,
+    noise_threshold: Option<f64>,
+    noisy_threshold: f64,
+) -> Result<()> {
+    let results = RunResults::load(&config.file)?;
+    let mut benchmarks = results.to_benchmarks(&config.foundry_repo);
+    benchmarks.noise_threshold = noise_threshold;
+    benchmarks.noisy_threshold = noisy_threshold;
+    let report = report::render(&benchmarks, results.flamegraphs.as_ref(), config.format)?;
+    match &config.out {
+        Some(path) => {
+            fs::write(path, report)
+                .wrap_err_with(|| format!("Failed to write report file: {path}"))?;
+            println!("Saved report to {path}");
+        }
+        None => print!("{report}"),
+    }
+    Ok(())
+}
+
+/// Runs the `merge` subcommand: combines result files benchmarking different projects into one,
+/// saved to `--out`.
+fn run_merge(config: &MergeConfig) -> Result<()> {
+    let inputs = config
+        .files
+        .iter()
+        .map(|path| Ok((path.clone(), RunResults::load(path)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (first_path, first) = &inputs[0];
+    for (path, results) in &inputs[1..] {
+        for (i, (a, b)) in first.sources.iter().zip(&results.sources).enumerate() {
+            if a.forge_version != b.forge_version {
+                eprintln!(
+                    "{} {first_path} and {path} were benchmarked with different forge builds for {}: {} vs {}",
+                    Paint::yellow("WARNING:").bold(),
+                    if i == 0 { "the baseline" } else { &a.name },
+                    a.forge_version
+                        .as_ref()
+                        .map_or_else(|| "unknown".to_string(), |forge| forge.to_string()),
+                    b.forge_version
+                        .as_ref()
+                        .map_or_else(|| "unknown".to_string(), |forge| forge.to_string())
+                );
+            }
+        }
+    }
+    for (path, results) in &inputs[1..] {
+        if first.forge_threads != results.forge_threads {
+            eprintln!(
+                "{} {first_path} and {path} were tested on different numbers of forge threads: {} vs {}, the merged results don't record it",
+                Paint::yellow("WARNING:").bold(),
+                results::describe_threads(first.forge_threads),
+                results::describe_threads(results.forge_threads)
+            );
+        }
+    }
+    let gated = inputs.iter().any(|(_, results)| results.gate.is_some());
+
+    let merged =
+        RunResults::merge(inputs).map_err(|e| eyre!("{} {e}", Paint::red("ERROR:").bold()))?;
+    if gated && merged.gate.is_none() {
+        eprintln!(
+            "{} The inputs weren't all gated with the same settings, the merged results have no gate outcome",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+
+    for input in &merged.merged_from {
+        println!(
+            "{}: {} project(s){}",
+            input.path,
+            input.projects.len(),
+            input
+                .host
+                .hostname
+                .as_ref()
+                .map_or_else(String::new, |hostname| format!(" from {hostname}"))
+        );
+    }
+    merged.save(&config.out)?;
+    println!(
+        "\nSaved {} project(s) to {}",
+        merged.projects.len(),
+        config.out
+    );
+    Ok(())
+}
+
+/// Runs the `compare` subcommand: prints the comparison table of two saved result files.
+fn run_compare(
+    config: &CompareConfig,
+    noise_threshold: Option<f64>,
+    noisy_threshold: f64,
+) -> Result<()> {
+    let old = RunResults::load(&config.old)?;
+    let new = RunResults::load(&config.new)?;
+
+    for mismatch in old.settings_mismatches(&new) {
+        eprintln!(
+            "{} The results may not be comparable: {mismatch}",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+    if let (Some(a), Some(b)) = (&old.reproduction, &new.reproduction)
+        && a.fingerprint != b.fingerprint
+    {
+        eprintln!(
+            "{} The results were benchmarked with different settings (config fingerprints {} and {}):\n - {}: {}\n - {}: {}",
+            Paint::yellow("WARNING:").bold(),
+            a.fingerprint,
+            b.fingerprint,
+            config.old,
+            a.command,
+            config.new,
+            b.command
+        );
+    }
+
+    let mut notes = Vec::new();
+    // Projects that failed (or weren't benchmarked) on one side are still listed in the table.
+    for (path, missing) in [
+        (&config.old, old.missing_from(&new)),
+        (&config.new, new.missing_from(&old)),
+    ] {
+        if !missing.is_empty() {
+            notes.push(format!(
+                "only {path} has results for: {}.",
+                missing.join(", ")
+            ));
+        }
+    }
+    for (path, results) in [(&config.old, &old), (&config.new, &new)] {
+        notes.push(format!(
+            "{path} was recorded on {} with forge {}.",
+            utils::format_timestamp(results.timestamp),
+            results
+                .baseline()
+                .forge_version
+                .as_ref()
+                .map_or_else(|| "unknown".to_string(), |forge| forge.to_string())
+        ));
+    }
+
+    let (verbosity, num_runs) = (old.verbosity, old.num_runs);
+    let mut results: Vec<SourceResults> = [(&config.old, old), (&config.new, new)]
+        .into_iter()
+        .map(|(path, results)| {
+            let baseline = results.into_baseline();
+            SourceResults {
+                source: Source::Recorded {
+                    path: path.clone(),
+                    forge: baseline.forge_version.clone(),
+                },
+                foundry_repo: baseline
+                    .foundry_repo
+                    .unwrap_or_else(|| config.foundry_repo.clone()),
+                label: baseline.label,
+                forge: baseline.forge_version,
+                tests: baseline.results,
+            }
+        })
+        .collect();
+    align_timing_sources(&mut results);
+    let benchmarks = Benchmarks {
+        verbosity: benchmark::verbosity_flag(verbosity),
+        num_runs,
+        results,
+        notes,
+        reproduction: None,
+        noise_threshold,
+        noisy_threshold,
+    };
+    ui::log_test_table(&benchmarks);
+
+    Ok(())
+}
+
+/// Runs `diff --watch`: benchmarks the reference once, and then the head of the comparison branch
+/// every time it moves, printing its diff against the reference, until stopped with Ctrl-C.
+///
+/// Every iteration installs the exact commit it measures, and is saved (to `--save`, suffixed with
+/// the commit) and recorded on its own. Ctrl-C stops the watch between iterations, and an
+/// iteration interrupted midway is discarded.
+fn run_watch(
+    cli: &Cli,
+    repos: &[ProjectConfig],
+    sources: DiffSources,
+    interval: Duration,
+) -> Result<ExitCode> {
+    let DiffSources {
+        ref_repo,
+        baseline,
+        vs_repo,
+        comparisons,
+    } = sources;
+    let [Source::Branch(branch)] = comparisons.as_slice() else {
+        unreachable!("--watch is validated to compare a single branch");
+    };
+    let diff = cli.diff_config().expect("--watch is a diff flag");
+    let foundryup = toolchain::resolve_foundryup(diff.foundryup_bin.as_deref())?;
+    let interrupt = watch::Interrupt::install();
+
+    ui::big_banner("FOUNDRY BENCHMARKS");
+    println!("Reference Repo URL     {ref_repo}");
+    println!("Comparison Repo URL    {vs_repo}");
+    println!("Foundryup binary       {}", foundryup.display());
+    println!(
+        "Baseline source        {}: {}",
+        baseline.ty(),
+        baseline.name()
+    );
+    println!("Watched branch         {branch}");
+    println!("Polling interval       {}s", interval.as_secs());
+    println!("Number of test runs    {}", cli.num_runs);
+    println!("Test verbosity         {}", cli.verbosity);
+    if let Some(threads) = cli.forge_threads {
+        println!("Forge test threads     {threads}");
+    }
+
+    let work_dir = utils::work_dir()?;
+    let prefixes = tempfile::Builder::new()
+        .prefix("toolchains-")
+        .tempdir_in(&work_dir)?;
+
+    // The reference is measured (or loaded) once, and compared against every iteration.
+    let reference = match baseline {
+        Source::Recorded { path, .. } => {
+            let recorded = load_baseline(cli, repos, &path)?.into_baseline();
+            SourceResults {
+                source: Source::Recorded {
+                    path,
+                    forge: recorded.forge_version.clone(),
+                },
+                foundry_repo: recorded
+                    .foundry_repo
+                    .unwrap_or_else(|| ref_repo.to_string()),
+                label: diff.ref_label.clone(),
+                forge: recorded.forge_version,
+                tests: recorded.results,
+            }
+        }
+        Source::Current { .. } => {
+            let toolchain = Toolchain::default();
+            let forge = toolchain.forge_version()?;
+            println!("Using the installed forge {forge}");
+            ui::big_banner("BENCHMARK --> baseline (installed forge)");
+            let pipeline = benchmark::run_pipeline(
+                repos,
+                cli.num_runs,
+                cli.verbosity,
+                cli.stop_after,
+                &toolchain,
+                &SystemRunner,
+                &ConsoleReporter,
+            )?;
+            benchmark::log_failed_projects(&pipeline.failures);
+            benchmark::log_missing_env_examples(&pipeline.missing_env_examples);
+            SourceResults {
+                source: Source::Current {
+                    forge: Some(forge.clone()),
+                },
+                foundry_repo: ref_repo.to_string(),
+                label: diff.ref_label.clone(),
+                forge: Some(forge),
+                tests: pipeline.tested,
+            }
+        }
+        source => {
+            let toolchain = isolated_toolchain(
+                &work_dir,
+                prefixes.path().join("baseline"),
+                &ref_repo,
+                &source,
+            );
+            ui::big_banner(&format!(
+                "FOUNDRYUP --> baseline ({}: {})",
+                source.ty(),
+                source.name()
+            ));
+            let reused = toolchain.install_or_reuse(
+                &foundryup,
+                &ref_repo,
+                "baseline",
+                &source,
+                diff.refresh_toolchains,
+                &SystemRunner,
+            )?;
+            let forge = toolchain.forge_version()?;
+            print_installed(&forge, reused);
+            ui::big_banner(&format!(
+                "BENCHMARK --> baseline ({}: {})",
+                source.ty(),
+                source.name()
+            ));
+            let pipeline = benchmark::run_pipeline(
+                repos,
+                cli.num_runs,
+                cli.verbosity,
+                cli.stop_after,
+                &toolchain,
+                &SystemRunner,
+                &ConsoleReporter,
+            )?;
+            benchmark::log_failed_projects(&pipeline.failures);
+            benchmark::log_missing_env_examples(&pipeline.missing_env_examples);
+            SourceResults {
+                source,
+                foundry_repo: ref_repo.to_string(),
+                label: diff.ref_label.clone(),
+                forge: Some(forge),
+                tests: pipeline.tested,
+            }
+        }
+    };
+
+    let mut measured: Option<String> = None;
+    let mut iterations = 0;
+    while !interrupt.is_set() {
+        match watch::head_sha(&vs_repo, branch) {
+            Ok(sha) if measured.as_ref() != Some(&sha) => {
+                iterations += 1;
+                let outcome = watch_iteration(
+                    cli,
+                    repos,
+                    &reference,
+                    (&vs_repo, branch, &sha),
+                    &work_dir,
+                    &foundryup,
+                    &interrupt,
+                );
+                if let Err(e) = outcome {
+                    eprintln!("{e}");
+                }
+                // A failed commit isn't retried, only the next push is benchmarked.
+                measured = Some(sha);
+            }
+            Ok(_) => {}
+            // Without a first commit to measure, there is nothing to watch.
+            Err(e) if measured.is_none() => return Err(e),
+            Err(e) => eprintln!(
+                "{} Failed to check {branch} for new commits, retrying later. {e}",
+                Paint::yellow("WARNING:").bold()
+            ),
+        }
+        if interrupt.is_set() {
+            break;
+        }
+        println!(
+            "\nWaiting for new commits on {branch} (checking every {}s, Ctrl-C to stop)",
+            interval.as_secs()
+        );
+        if !interrupt.sleep(interval) {
+            break;
+        }
+    }
+
+    println!("\nStopped watching {branch} after {iterations} iteration(s).");
+    if cli.keep {
+        println!("Kept toolchain prefixes at {}", prefixes.keep().display());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Installs and benchmarks a single commit of the watched branch, and prints (and saves) its diff
+/// against the `reference` results. Nothing is reported if the watch was interrupted meanwhile.
+fn watch_iteration(
+    cli: &Cli,
+    repos: &[ProjectConfig],
+    reference: &SourceResults,
+    (vs_repo, branch, sha): (&str, &str, &String),
+    work_dir: &std::path::Path,
+    foundryup: &std::path::Path,
+    interrupt: &watch::Interrupt,
+) -> Result<()> {
+    let diff = cli.diff_config().expect("--watch is a diff flag");
+    let short = &sha[..sha.len().min(7)];
+    let label = format!(
+        "{}@{short}",
+        diff.vs_label.first().map_or(branch, String::as_str)
+    );
+    let source = Source::Commit(sha.clone());
+
+    // Every commit gets its own cached toolchain, so that a restarted watch reuses them.
+    let toolchain = &Toolchain::cached(work_dir, sha);
+    ui::big_banner(&format!("FOUNDRYUP --> comparison ({branch} at {sha})"));
+    let reused = toolchain.install_or_reuse(
+        foundryup,
+        vs_repo,
+        "comparison",
+        &source,
+        diff.refresh_toolchains,
+        &SystemRunner,
+    )?;
+    let forge = toolchain.forge_version()?;
+    print_installed(&forge, reused);
+    if let Some(reference_forge) = &reference.forge
+        && forge.same_build(reference_forge)
+    {
+        eprintln!(
+            "{} {branch} at {short} resolves to the same forge build as the baseline: {forge}",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+
+    ui::big_banner(&format!("BENCHMARK --> comparison ({label})"));
+    let pipeline = benchmark::run_pipeline(
+        repos,
+        cli.num_runs,
+        cli.verbosity,
+        Stage::Test,
+        toolchain,
+        &SystemRunner,
+        &ConsoleReporter,
+    )?;
+    benchmark::log_failed_projects(&pipeline.failures);
+    benchmark::log_missing_env_examples(&pipeline.missing_env_examples);
+    if interrupt.is_set() {
+        println!("\nDiscarding the results of {label}, as the iteration was interrupted.");
+        return Ok(());
+    }
+
+    let mut results = vec![
+        reference.clone(),
+        SourceResults {
+            source,
+            foundry_repo: vs_repo.to_string(),
+            label: Some(label),
+            forge: Some(forge),
+            tests: pipeline.tested,
+        },
+    ];
+    align_timing_sources(&mut results);
+    let mismatches = provenance::mismatches(&results);
+    provenance::log_mismatches(&results, &mismatches);
+    let provenance_note = provenance::note(&mismatches);
+    let mismatched: Vec<String> = mismatches.iter().map(|m| m.project.to_string()).collect();
+    let benchmarks = Benchmarks {
+        verbosity: benchmark::verbosity_flag(cli.verbosity),
+        num_runs: cli.num_runs,
+        reproduction: Some(reproduction(cli, repos, &results)),
+        results,
+        notes: std::iter::once(format!("{branch} was benchmarked at {sha}."))
+            .chain(run_notes(cli))
+            .chain(provenance_note)
+            .collect(),
+        noise_threshold: cli.noise_threshold,
+        noisy_threshold: cli.noisy_threshold,
+    };
+    ui::log_test_table(&benchmarks);
+
+    let gate = diff.fail_on_regression.map(|threshold| {
+        let report =
+            GateReport::evaluate(&benchmarks, threshold, diff.gate_metric, &diff.gate_exclude);
+        ui::log_gate_report(&report);
+        report
+    });
+    annotate_regressions(diff, &benchmarks);
+    if diff.save.is_some() || cli.exports_results() {
+        let projects = repos.iter().map(|repo| repo.name.clone()).collect();
+        let results = RunResults::from_benchmarks(&benchmarks, cli.verbosity, projects, gate);
+        let path = diff
+            .save
+            .as_deref()
+            .map(|path| watch::iteration_path(path, sha));
+        save_results(cli, path.as_deref(), results)?;
+    }
+    if !mismatched.is_empty() && diff.strict_provenance {
+        return Err(provenance_error(&mismatched));
+    }
+    Ok(())
+}
+
+/// Prints the GitHub Actions annotations of the regressions of `benchmarks` to stdout, when running
+/// in a workflow or with `--gh-annotations`.
+fn annotate_regressions(diff: &DiffConfig, benchmarks: &Benchmarks) {
+    if !gate::annotations_enabled(diff.gh_annotations) {
+        return;
+    }
+    for annotation in gate::annotations(
+        benchmarks,
+        diff.warn_on_regression,
+        diff.fail_on_regression,
+        diff.gate_metric,
+        &diff.gate_exclude,
+    ) {
+        println!("{annotation}");
+    }
+}
+
+/// Profiles the `n` projects of `benchmarks` that regressed the most with the toolchain of every
+/// source of `sides` (see [`flamegraph::profile_regressions`]), and prints the table linking their
+/// flamegraphs, returned to be saved with the results. Without `perf`, or without any
+/// regression, only tells why nothing is profiled.
+fn print_flamegraphs(
+    cli: &Cli,
+    diff: &DiffConfig,
+    repos: &[ProjectConfig],
+    benchmarks: &Benchmarks,
+    n: usize,
+    sides: &[(String, String, Option<Toolchain>)],
+) -> Option<Flamegraphs> {
+    let regressed = flamegraph::worst_regressed(benchmarks, n);
+    if regressed.is_empty() {
+        println!("\nNo project regressed, nothing to profile (--flamegraph)");
+        return None;
+    }
+    if let Err(e) = toolchain::check_perf(&SystemRunner) {
+        eprintln!(
+            "\n{} Skipping the flamegraphs (--flamegraph): {e}",
+            Paint::yellow("WARNING:").bold()
+        );
+        return None;
+    }
+    let profiled: Vec<(String, Option<Toolchain>)> = sides
+        .iter()
+        .map(|(name, _, toolchain)| (name.clone(), toolchain.clone()))
+        .collect();
+    let profiles = flamegraph::profile_regressions(
+        &regressed,
+        repos,
+        &profiled,
+        cli.verbosity,
+        std::path::Path::new(&diff.flamegraph_dir),
+        &SystemRunner,
+        &ConsoleReporter,
+    );
+    let flamegraphs = Flamegraphs {
+        sources: sides
+            .iter()
+            .map(|(_, display_name, _)| display_name.clone())
+            .collect(),
+        profiles,
+    };
+    print!("{}", flamegraph::render(&flamegraphs));
+    (!flamegraphs.is_empty()).then_some(flamegraphs)
+}
+
+/// The isolated toolchain installing `source` from `foundry_repo`: cached in `work_dir` by the
+/// commit it's built from, if any, and otherwise in the `prefix` of this run.
+fn isolated_toolchain(
+    work_dir: &std::path::Path,
+    prefix: std::path::PathBuf,
+    foundry_repo: &str,
+    source: &Source,
+) -> Toolchain {
+    match toolchain::source_commit(foundry_repo, source) {
+        Ok(Some(commit)) => Toolchain::cached(work_dir, &commit),
+        Ok(None) => Toolchain::isolated(prefix),
+        Err(e) => {
+            eprintln!(
+                "{} Failed to resolve the commit of the {} {}, its toolchain isn't cached. {e}",
+                Paint::yellow("WARNING:").bold(),
+                source.ty(),
+                source.name()
+            );
+            Toolchain::isolated(prefix)
+        }
+    }
+}
+
+/// Prints the version of the `forge` installed for a source, or reused from the toolchain cache.
+fn print_installed(version: &ForgeVersion, reused: bool) {
+    if reused {
+        println!("Reusing the cached forge {version} (--refresh-toolchains to install it again)");
+    } else {
+        println!("Installed forge {version}");
+    }
+}
+
+/// The error of `--strict-provenance`, failing a diff whose sources cloned the `mismatched`
+/// projects at different commits.
+fn provenance_error(mismatched: &[String]) -> eyre::Report {
+    eyre!(
+        "{} The sources benchmarked different commits of: {} (--strict-provenance)",
+        Paint::red("ERROR:").bold(),
+        mismatched.join(", ")
+    )
+}
+
+/// A Foundry source benchmarked by the `diff` subcommand, along with the toolchain it is
+/// installed into.
+struct DiffSide {
+    /// Role of the source in the diff (e.g. "baseline").
+    name: String,
+    source: Source,
+    /// Git repository the source is installed from.
+    foundry_repo: String,
+    /// Display name overriding the source's name, if any.
+    label: Option<String>,
+    toolchain: Toolchain,
+}
+
+impl DiffSide {
+    /// Describes the source, e.g. "branch: master", or its label if provided.
+    fn describe(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => format!("{}: {}", self.source.ty(), self.source.name()),
+        }
+    }
+}
+
+/// Runs the `diff` subcommand: installs every source into its own toolchain, benchmarks the
+/// projects with each of them, and prints the comparison table.
+///
+/// A comparison source that fails to install or benchmark doesn't discard the results collected
+/// for the other sources, but the run still errors once the table has been printed.
+///
+/// Exits with [`gate::REGRESSION_EXIT_CODE`] if the regression gate is enabled and fails, or else
+/// with [`benchmark::FAILED_PROJECTS_EXIT_CODE`] if some projects failed with any source.
+fn run_diff(cli: &Cli, repos: &[ProjectConfig], sources: DiffSources) -> Result<ExitCode> {
+    if let Some(interval) = cli.diff_config().and_then(|diff| diff.watch) {
+        return run_watch(cli, repos, sources, interval);
+    }
+    let DiffSources {
+        mut ref_repo,
+        baseline,
+        vs_repo,
+        comparisons,
+    } = sources;
+
+    // A recorded baseline is loaded (and validated) upfront, instead of installed and benchmarked.
+    let mut recorded = None;
+    let baseline = match baseline {
+        Source::Recorded { path, .. } => {
+            let results = load_baseline(cli, repos, &path)?;
+            let forge = results.baseline().forge_version.clone();
+            if let Some(repo) = &results.baseline().foundry_repo {
+                ref_repo = repo.clone();
+            }
+            recorded = Some(results);
+            Source::Recorded { path, forge }
+        }
+        source => source,
+    };
+
+    // Resolve `foundryup` upfront, so that a missing binary doesn't fail the run halfway through.
+    let foundryup = std::iter::once(&baseline)
+        .chain(&comparisons)
+        .any(|source| source.short().is_some())
+        .then(|| {
+            toolchain::resolve_foundryup(
+                cli.diff_config()
+                    .and_then(|diff| diff.foundryup_bin.as_deref()),
+            )
+        })
+        .transpose()?;
+
+    ui::big_banner("FOUNDRY BENCHMARKS");
+
+    if ref_repo == vs_repo {
+        println!("Foundry Repo URL       {ref_repo}");
+    } else {
+        println!("Reference Repo URL     {ref_repo}");
+        println!("Comparison Repo URL    {vs_repo}");
+    }
+    if let Some(foundryup) = &foundryup {
+        println!("Foundryup binary       {}", foundryup.display());
+    }
+    let (ref_label, vs_labels) = cli
+        .diff_config()
+        .map(|diff| (diff.ref_label.clone(), diff.vs_label.clone()))
+        .unwrap_or_default();
+    let label_suffix = |label: &Option<String>| {
+        label
+            .as_ref()
+            .map_or_else(String::new, |label| format!(" (label: {label})"))
+    };
+    let mut notes = Vec::new();
+    match (&baseline, &recorded) {
+        (Source::Recorded { path, forge }, Some(recorded)) => {
+            let forge = forge
+                .as_ref()
+                .map_or_else(|| "unknown".to_string(), |forge| forge.to_string());
+            let date = utils::format_timestamp(recorded.timestamp);
+            println!(
+                "Baseline source        recorded: {path} (forge {forge}, {date}){}",
+                label_suffix(&ref_label)
+            );
+            notes.push(format!(
+                "the baseline was not benchmarked in this run. Its results were loaded from {path}, recorded on {date} with forge {forge}."
+            ));
+        }
+        _ => println!(
+            "Baseline source        {}: {}{}",
+            baseline.ty(),
+            baseline.name(),
+            label_suffix(&ref_label)
+        ),
+    }
+    for (i, comparison) in comparisons.iter().enumerate() {
+        println!(
+            "Comparison source      {}: {}{}",
+            comparison.ty(),
+            comparison.name(),
+            label_suffix(&vs_labels.get(i).cloned())
+        );
+    }
+    println!("Number of test runs    {}", cli.num_runs);
+    println!("Test verbosity         {}", cli.verbosity);
+    if let Some(threads) = cli.forge_threads {
+        println!("Forge test threads     {threads}");
+    }
+    notes.extend(run_notes(cli));
+    if cli.clean_env {
+        println!("Forge environment      clean");
+    } else {
+        // Stray variables change what forge does on every side, e.g. `FOUNDRY_PROFILE`.
+        let vars = toolchain::foundry_vars(std::env::vars_os().map(|(key, _)| key));
+        if !vars.is_empty() {
+            println!(
+                "Forge environment      inherited, with {} (pass --clean-env to leave them out)",
+                vars.join(", ")
+            );
+        }
+    }
+    let interleave = cli.diff_config().is_some_and(|diff| diff.interleave);
+    let refresh_toolchains = cli
+        .diff_config()
+        .is_some_and(|diff| diff.refresh_toolchains);
+    if interleave {
+        println!("Scheduling             interleaved");
+    }
+    let canary = match cli.diff_config().and_then(|diff| diff.canary.as_ref()) {
+        Some(name) => canary::pick(repos, name.as_deref())?,
+        None => None,
+    };
+    if let Some(canary) = canary {
+        println!("Canary project         {}", canary.name);
+    }
+
+    // Each source is installed into its own prefix so that the user's global install is left
+    // untouched, and all toolchains are ready before any benchmark starts.
+    let work_dir = utils::work_dir()?;
+    let prefixes = tempfile::Builder::new()
+        .prefix("toolchains-")
+        .tempdir_in(&work_dir)?;
+    let num_comparisons = comparisons.len();
+    let sides: Vec<DiffSide> =
+        std::iter::once(("baseline".to_string(), baseline, ref_repo, ref_label))
+            .chain(comparisons.into_iter().enumerate().map(|(i, source)| {
+                let name = if num_comparisons == 1 {
+                    "comparison".to_string()
+                } else {
+                    format!("comparison #{}", i + 1)
+                };
+                (name, source, vs_repo.to_string(), vs_labels.get(i).cloned())
+            }))
+            .map(|(name, source, foundry_repo, label)| {
+                let toolchain = match &source {
+                    // Environment sources share the `forge` in the user's `PATH`.
+                    Source::Env { vars, .. } => Toolchain::default().with_env(vars.clone()),
+                    Source::Current { .. } | Source::Recorded { .. } => Toolchain::default(),
+                    Source::Version(_) | Source::Branch(_) | Source::Commit(_) => {
+                        let prefix = prefixes.path().join(name.replace(" #", "-"));
+                        let toolchain =
+                            isolated_toolchain(&work_dir, prefix, &foundry_repo, &source);
+                        if let Some(prefix) = toolchain.prefix() {
+                            println!("{:<23}{}", format!("Prefix ({name})"), prefix.display());
+                        }
+                        toolchain
+                    }
+                };
+                DiffSide {
+                    name,
+                    source,
+                    foundry_repo,
+                    label,
+                    toolchain,
+                }
+            })
+            .collect();
+
+    let mut failed_sides = Vec::new();
+    let mut installed: Vec<(DiffSide, Option<ForgeVersion>)> = Vec::new();
+    for mut side in sides {
+        match &side.source {
+            Source::Recorded { forge, .. } => {
+                let forge = forge.clone();
+                installed.push((side, forge));
+                continue;
+            }
+            // Nothing to install, but make sure that `forge` is available.
+            Source::Env { .. } => {
+                let version = side.toolchain.forge_version()?;
+                println!("Using forge {version} ({}: {})", side.name, side.describe());
+                installed.push((side, Some(version)));
+                continue;
+            }
+            // Used as is: the global install is neither reinstalled nor switched away from.
+            Source::Current { .. } => {
+                let version = side.toolchain.forge_version()?;
+                println!("Using the installed forge {version} ({})", side.name);
+                side.source = Source::Current {
+                    forge: Some(version.clone()),
+                };
+                installed.push((side, Some(version)));
+                continue;
+            }
+            Source::Version(_) | Source::Branch(_) | Source::Commit(_) => {}
+        }
+        let foundryup = foundryup
+            .as_deref()
+            .expect("foundryup is resolved when a source needs installing");
+
+        ui::big_banner(&format!(
+            "FOUNDRYUP --> {} ({})",
+            side.name,
+            side.describe()
+        ));
+        let version = side
+            .toolchain
+            .install_or_reuse(
+                foundryup,
+                &side.foundry_repo,
+                &side.name,
+                &side.source,
+                refresh_toolchains,
+                &SystemRunner,
+            )
+            .and_then(|reused| Ok((reused, side.toolchain.forge_version()?)));
+        match version {
+            Ok((reused, version)) => {
+                print_installed(&version, reused);
+                installed.push((side, Some(version)));
+            }
+            // Without a baseline there is nothing to compare against.
+            Err(e) if installed.is_empty() => return Err(e),
+            Err(e) => {
+                eprintln!("{e}");
+                failed_sides.push(side.name);
+            }
+        }
+    }
+
+    // Guard against burning hours benchmarking the same build twice, e.g. because of a typo in the
+    // sources, or because a branch is identical to the baseline. Environment sources run the same
+    // build by design.
+    let allow_identical = cli.diff_config().is_some_and(|diff| diff.allow_identical);
+    for (i, (side, version)) in installed.iter().enumerate() {
+        for (other, other_version) in &installed[..i] {
+            if let (Some(version), Some(other_version)) = (version, other_version)
+                && !matches!(side.source, Source::Env { .. })
+                && version.same_build(other_version)
+            {
+                let msg = format!(
+                    "The {} and {} sources resolve to the same forge build: {version}",
+                    other.name, side.name
+                );
+                if !allow_identical {
+                    return Err(eyre!(
+                        "{} {msg}. Pass --allow-identical to benchmark them anyway.",
+                        Paint::red("ERROR:").bold()
+                    ));
+                }
+                eprintln!("{} {msg}", Paint::yellow("WARNING:").bold());
+            }
+        }
+    }
+
+    // The canary runs with the toolchains installed above, which the benchmark then reuses.
+    if let Some(canary) = canary {
+        let sides: Vec<(&str, &Toolchain)> = installed
+            .iter()
+            .filter(|(side, _)| !matches!(side.source, Source::Recorded { .. }))
+            .map(|(side, _)| (side.name.as_str(), &side.toolchain))
+            .collect();
+        canary::run(
+            canary,
+            &sides,
+            cli.verbosity,
+            cli.stop_after,
+            &SystemRunner,
+            &ConsoleReporter,
+        )?;
+        println!(
+            "\nThe canary run of {} passed with every source (its times are discarded)",
+            canary.name
+        );
+    }
+
+    // The toolchain of every source, with its name in the tables, for the `anvil` benchmarks and
+    // the flamegraphs after the projects. A recorded baseline has none.
+    let side_toolchains: Vec<(String, String, Option<Toolchain>)> = installed
+        .iter()
+        .map(|(side, _)| {
+            let display_name = side
+                .label
+                .clone()
+                .unwrap_or_else(|| side.source.name().to_string());
+            let toolchain =
+                (!matches!(side.source, Source::Recorded { .. })).then(|| side.toolchain.clone());
+            (side.name.clone(), display_name, toolchain)
+        })
+        .collect();
+    let anvil_sides: &[_] = if cli.diff_config().is_some_and(|diff| diff.bench_anvil) {
+        &side_toolchains
+    } else {
+        &[]
+    };
+
+    let mut results = Vec::new();
+    // Failures of every source, in the order of `results`.
+    let mut failures: Vec<Vec<Failure>> = Vec::new();
+    let mut missing_env_examples = Vec::new();
+    if interleave {
+        ui::big_banner(&format!(
+            "BENCHMARK --> {} (interleaved)",
+            installed
+                .iter()
+                .map(|(side, _)| side.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" + ")
+        ));
+        let toolchains: Vec<Toolchain> = installed
+            .iter()
+            .map(|(side, _)| side.toolchain.clone())
+            .collect();
+        let pipelines = benchmark::run_interleaved_pipeline(
+            repos,
+            cli.num_runs,
+            cli.verbosity,
+            &toolchains,
+            &SystemRunner,
+            &ConsoleReporter,
+        )?;
+        for ((side, forge), pipeline) in installed.into_iter().zip(pipelines) {
+            results.push(SourceResults {
+                source: side.source,
+                foundry_repo: side.foundry_repo,
+                label: side.label,
+                forge,
+                tests: pipeline.tested,
+            });
+            failures.push(pipeline.failures);
+            missing_env_examples.extend(pipeline.missing_env_examples);
+        }
+    } else {
+        for (side, forge) in installed {
+            if let Some(recorded) =
+                recorded.take_if(|_| matches!(side.source, Source::Recorded { .. }))
+            {
+                let baseline = recorded.into_baseline();
+                results.push(SourceResults {
+                    source: side.source,
+                    foundry_repo: side.foundry_repo,
+                    label: side.label,
+                    forge,
+                    tests: baseline.results,
+                });
+                failures.push(baseline.failures);
+                continue;
+            }
+
+            ui::big_banner(&format!(
+                "BENCHMARK --> {} ({})",
+                side.name,
+                side.describe()
+            ));
+            match benchmark::run_pipeline(
+                repos,
+                cli.num_runs,
+                cli.verbosity,
+                cli.stop_after,
+                &side.toolchain,
+                &SystemRunner,
+                &ConsoleReporter,
+            ) {
+                Ok(pipeline) => {
+                    results.push(SourceResults {
+                        source: side.source,
+                        foundry_repo: side.foundry_repo,
+                        label: side.label,
+                        forge,
+                        tests: pipeline.tested,
+                    });
+                    failures.push(pipeline.failures);
+                    missing_env_examples.extend(pipeline.missing_env_examples);
+                }
+                Err(e) if results.is_empty() => return Err(e),
+                Err(e) => {
+                    eprintln!("{} {}: {e}", Paint::red("ERROR:").bold(), side.name);
+                    failed_sides.push(side.name);
+                }
+            }
+        }
+    }
+
+    let mut anvil_benches = Vec::new();
+    for (name, display_name, toolchain) in anvil_sides {
+        // A recorded baseline has no `anvil` to run.
+        let Some(toolchain) = toolchain else {
+            anvil_benches.push((display_name.clone(), None));
+            continue;
+        };
+        ui::big_banner(&format!("ANVIL --> {name}"));
+        match anvil::bench(toolchain, cli.num_runs, &SystemRunner) {
+            Ok(bench) => anvil_benches.push((display_name.clone(), Some(bench))),
+            Err(e) => {
+                eprintln!("{} anvil ({name}): {e}", Paint::red("ERROR:").bold());
+                failed_sides.push(format!("{name} (anvil)"));
+                anvil_benches.push((display_name.clone(), None));
+            }
+        }
+    }
+
+    benchmark::log_failed_projects(failures.iter().flatten());
+    benchmark::log_missing_env_examples(&missing_env_examples);
+    let failed = failures.iter().any(|f| !f.is_empty());
+
+    let mut gate_passed = true;
+    let mut mismatched = Vec::new();
+    if results.len() > 1 {
+        align_timing_sources(&mut results);
+        let mismatches = provenance::mismatches(&results);
+        provenance::log_mismatches(&results, &mismatches);
+        notes.extend(provenance::note(&mismatches));
+        mismatched = mismatches.iter().map(|m| m.project.to_string()).collect();
+        let benchmarks = Benchmarks {
+            verbosity: benchmark::verbosity_flag(cli.verbosity),
+            num_runs: cli.num_runs,
+            reproduction: Some(reproduction(cli, repos, &results)),
+            results,
+            notes,
+            noise_threshold: cli.noise_threshold,
+            noisy_threshold: cli.noisy_threshold,
+        };
+
+        ui::log_test_table(&benchmarks);
+        if !anvil_benches.is_empty() {
+            print!("{}", ui::render_anvil_table(&anvil_benches, cli.num_runs));
+        }
+
+        let diff = cli.diff_config();
+        let gate = diff.and_then(|diff| {
+            let threshold = diff.fail_on_regression?;
+            let report =
+                GateReport::evaluate(&benchmarks, threshold, diff.gate_metric, &diff.gate_exclude);
+            ui::log_gate_report(&report);
+            Some(report)
+        });
+        gate_passed = gate.as_ref().is_none_or(GateReport::passed);
+        if let Some(diff) = diff {
+            annotate_regressions(diff, &benchmarks);
+        }
+
+        let flamegraphs = diff.and_then(|diff| {
+            let n = diff.flamegraph?;
+            print_flamegraphs(cli, diff, repos, &benchmarks, n.get(), &side_toolchains)
+        });
+
+        let save = diff.and_then(|diff| diff.save.as_deref());
+        if save.is_some() || cli.exports_results() {
+            let projects = repos.iter().map(|repo| repo.name.clone()).collect();
+            let mut results =
+                RunResults::from_benchmarks(&benchmarks, cli.verbosity, projects, gate);
+            for (source, failures) in results.sources.iter_mut().zip(failures) {
+                source.failures = failures;
+            }
+            results.flamegraphs = flamegraphs;
+            save_results(cli, save, results)?;
+        }
+    }
+
+    if cli.keep {
+        println!("\nKept toolchain prefixes at {}", prefixes.keep().display());
+    }
+
+    if !mismatched.is_empty() && cli.diff_config().is_some_and(|diff| diff.strict_provenance) {
+        return Err(provenance_error(&mismatched));
+    }
+
+    if !failed_sides.is_empty() {
+        return Err(eyre!(
+            "{} Failed to benchmark the following sources: {}",
+            Paint::red("ERROR:").bold(),
+            failed_sides.join(", ")
+        ));
+    }
+
+    Ok(run_exit_code(cli, failed, gate_passed))
+}
+
+/// Exit code of a run that went through, see [`exit_code`].
+fn run_exit_code(cli: &Cli, failed: bool, gate_passed: bool) -> ExitCode {
+    ExitCode::from(exit_code(failed, cli.allow_failures, gate_passed))
+}
+
+/// Exit code of a run that went through, by order of precedence:
+///
+/// - [`gate::REGRESSION_EXIT_CODE`] if its gate didn't pass: `--fail-on-regression`, or the time
+///   budgets with `--enforce-budgets`.
+/// - [`benchmark::FAILED_PROJECTS_EXIT_CODE`] if some projects `failed` a stage, unless
+///   `--allow-failures` is set.
+/// - `0` otherwise.
+///
+/// The errors stopping a run (e.g. an invalid config, or a failed install) exit with `1` when
+/// returned by `main`, and invalid arguments with `2`, like any clap error.
+fn exit_code(failed: bool, allow_failures: bool, gate_passed: bool) -> u8 {
+    if !gate_passed {
+        gate::REGRESSION_EXIT_CODE
+    } else if failed && !allow_failures {
+        benchmark::FAILED_PROJECTS_EXIT_CODE
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{Response, ScriptedRunner};
+    use clap::{CommandFactory, FromArgMatches};
+
+    /// The exit code of a plain run of the `a/a` project with `runner`.
+    fn pipeline_exit_code(runner: &ScriptedRunner, allow_failures: bool, gate_passed: bool) -> u8 {
+        let pipeline = benchmark::run_pipeline(
+            &[ProjectConfig::new("a/a")],
+            1,
+            0,
+            Stage::Test,
+            &Toolchain::default(),
+            runner,
+            &ConsoleReporter,
+        )
+        .unwrap();
+        exit_code(!pipeline.failures.is_empty(), allow_failures, gate_passed)
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let passing = ScriptedRunner::default();
+        assert_eq!(pipeline_exit_code(&passing, false, true), 0);
+        assert_eq!(pipeline_exit_code(&passing, false, false), 3);
+
+        let failing = || {
+            ScriptedRunner::default().on(
+                "forge build",
+                [Response::fail(1, "", "Error: Compiler run failed")],
+            )
+        };
+        assert_eq!(pipeline_exit_code(&failing(), false, true), 4);
+        assert_eq!(pipeline_exit_code(&failing(), true, true), 0);
+        // A failed gate takes precedence over the failed projects.
+        assert_eq!(pipeline_exit_code(&failing(), false, false), 3);
+    }
+
+    #[test]
+    fn test_error_exit_codes() {
+        // Invalid arguments exit with 2, like any clap error.
+        let err = Cli::command()
+            .try_get_matches_from(["foundry-benchmarks", "--num-runs", "many"])
+            .unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+
+        // An invalid config is returned as an error, i.e. exits with 1.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        fs::write(&path, "[[custom.projects]\n").unwrap();
+        let matches = Cli::command()
+            .try_get_matches_from(["foundry-benchmarks", "--config", path.to_str().unwrap()])
+            .unwrap();
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        assert!(run(cli, &matches).is_err());
+    }
+}
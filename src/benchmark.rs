@@ -1,26 +1,115 @@
-use eyre::{Context, Result};
+use clap::ValueEnum;
+use eyre::{Context, Result, eyre};
 use rayon::prelude::*;
-use std::path::PathBuf;
-use std::{fs, io::Write, process::Command, time::Instant};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use std::{fmt, fs, io, io::Write, process::Command, time::Instant};
 use tempfile::TempDir;
+use tracing::Span;
 use yansi::Paint;
 
 use crate::cmd::Verbosity;
-use crate::ui;
-use crate::utils::{GITHUB_URL, ProjectConfig};
+use crate::toolchain::{self, ForgeVersion, Platform, Toolchain};
+use crate::utils::{self, ProjectConfig, RemappingsMode, SyntheticConfig};
+use crate::{
+    anvil::Anvil,
+    env_example, fingerprint, fork, keep,
+    logging::strip_ansi,
+    output_size::OutputSize,
+    redact,
+    reporter::Reporter,
+    repro::Reproduction,
+    rpc_cache,
+    runner::CommandRunner,
+    synthetic,
+    ui::{self, LogLevel},
+    warnings,
+};
 
-/// Foundry source. Either a tagged version, or a branch.
-#[derive(Debug, Clone)]
-pub enum Source<'url> {
-    Version(&'url String),
-    Branch(&'url String),
+/// Foundry source. Either a tagged version, a branch, a commit, a set of environment variables
+/// applied to the `forge` in the user's `PATH`, that `forge` as is, or results recorded by a
+/// previous run.
+///
+/// Sources are written as `<type>:<name>` (see [`Source::ty`]), e.g. `branch:master`,
+/// `version:v1.2.0` or `env:FOUNDRY_FUZZ_RUNS=512,FOUNDRY_OPTIMIZER=false`:
+///
+/// ```
+/// use foundry_benchmarks::Source;
+///
+/// let source: Source = "branch:master".parse()?;
+/// assert_eq!(source, Source::Branch("master".to_string()));
+/// assert_eq!(source.to_string(), "branch:master");
+/// # Ok::<(), eyre::Report>(())
+/// ```
+///
+/// In JSON, the type is the key of the source, e.g. `{"branch":"master"}`. The values of the
+/// secret env vars are redacted from both forms.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Version(String),
+    Branch(String),
+    /// A single commit, e.g. a candidate of `bisect`.
+    Commit(String),
+    /// Environment variables set on every `forge` command, to compare Foundry settings on a single
+    /// build.
+    Env {
+        label: String,
+        #[serde(serialize_with = "serialize_redacted")]
+        vars: BTreeMap<String, String>,
+    },
+    /// The `forge` installed in the user's `PATH`, used as is rather than installed.
+    Current {
+        /// Build information of the binary, captured before the run.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        forge: Option<ForgeVersion>,
+    },
+    /// Results loaded from a file, rather than benchmarked with an installed toolchain.
+    Recorded {
+        path: String,
+        /// Build information of the `forge` binary the results were recorded with, if known.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        forge: Option<ForgeVersion>,
+    },
+}
+
+/// Serializes env `vars` with the values of the secrets redacted.
+fn serialize_redacted<S: Serializer>(
+    vars: &BTreeMap<String, String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    redact::vars(vars).serialize(serializer)
 }
 
-impl<'url> Source<'url> {
-    pub fn short(&self) -> &'static str {
+impl Source {
+    /// Creates an environment source, labelled after its variables (with the secrets redacted).
+    pub fn env(vars: BTreeMap<String, String>) -> Self {
+        redact::register(&vars);
+        let label = if vars.is_empty() {
+            "default env".to_string()
+        } else {
+            redact::vars(&vars)
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        Self::Env { label, vars }
+    }
+
+    /// The `foundryup` flag that selects the source, if it can be installed.
+    pub fn short(&self) -> Option<&'static str> {
         match self {
-            Self::Version(_) => "-v",
-            Self::Branch(_) => "-b",
+            Self::Version(_) => Some("-v"),
+            Self::Branch(_) => Some("-b"),
+            Self::Commit(_) => Some("-C"),
+            Self::Env { .. } | Self::Current { .. } | Self::Recorded { .. } => None,
         }
     }
 
@@ -28,20 +117,118 @@ impl<'url> Source<'url> {
         match self {
             Self::Branch(_) => "branch",
             Self::Version(_) => "version",
+            Self::Commit(_) => "commit",
+            Self::Env { .. } => "env",
+            Self::Current { .. } => "current",
+            Self::Recorded { .. } => "recorded",
         }
     }
 
-    pub fn name(&self) -> &'url str {
+    pub fn name(&self) -> &str {
         match self {
             Self::Branch(b) => b,
             Self::Version(v) => v,
+            Self::Commit(c) => c,
+            Self::Env { label, .. } => label,
+            Self::Current { .. } => "installed",
+            Self::Recorded {
+                forge: Some(forge), ..
+            } => &forge.version,
+            Self::Recorded { path, .. } => path,
+        }
+    }
+
+    /// Whether the source doesn't name a fixed build: a release channel (`stable` or `nightly`),
+    /// which moves with every release, or the current install. The version it ran is only known
+    /// from its `forge` binary.
+    pub fn is_unpinned(&self) -> bool {
+        match self {
+            Self::Version(v) => v == "stable" || v == "nightly",
+            Self::Current { .. } => true,
+            _ => false,
         }
     }
 
+    /// Page of the source in `foundry_repo`, on the GitHub instance of the run (see
+    /// [`utils::github_url`]).
     pub fn github_url(&self, foundry_repo: &str) -> String {
+        let base = utils::github_url();
+        match self {
+            Self::Branch(b) => format!("{base}/{foundry_repo}/tree/{b}"),
+            Self::Version(v) => format!("{base}/{foundry_repo}/releases/tag/{v}"),
+            Self::Commit(c) => format!("{base}/{foundry_repo}/commit/{c}"),
+            Self::Env { .. } => format!("{base}/{foundry_repo}"),
+            Self::Current { forge } | Self::Recorded { forge, .. } => {
+                match forge.as_ref().and_then(|f| f.commit.as_ref()) {
+                    Some(commit) => format!("{base}/{foundry_repo}/commit/{commit}"),
+                    None => format!("{base}/{foundry_repo}"),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Branch(b) => format!("{GITHUB_URL}/{foundry_repo}/tree/{b}"),
-            Self::Version(v) => format!("{GITHUB_URL}/{foundry_repo}/releases/tag/{v}"),
+            Self::Env { vars, .. } => write!(
+                f,
+                "env:{}",
+                redact::vars(vars)
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            Self::Current { .. } => f.write_str("current:"),
+            Self::Recorded { path, .. } => write!(f, "recorded:{path}"),
+            source => write!(f, "{}:{}", source.ty(), source.name()),
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (ty, name) = s.split_once(':').ok_or_else(|| {
+            eyre!("Invalid source '{s}', expected <type>:<name> (e.g. branch:master)")
+        })?;
+        if name.is_empty() && ty != "env" && ty != "current" {
+            return Err(eyre!("Invalid source '{s}', the {ty} name is empty"));
+        }
+        let name = name.to_string();
+        match ty {
+            "version" => Ok(Self::Version(name)),
+            "branch" => Ok(Self::Branch(name)),
+            "commit" => Ok(Self::Commit(name)),
+            "current" if name.is_empty() => Ok(Self::Current { forge: None }),
+            "current" => Err(eyre!(
+                "Invalid source '{s}', the current install is written 'current:'"
+            )),
+            "recorded" => Ok(Self::Recorded {
+                path: name,
+                forge: None,
+            }),
+            "env" => {
+                let vars = name
+                    .split(',')
+                    .filter(|pair| !pair.is_empty())
+                    .map(|pair| {
+                        pair.split_once('=')
+                            .map(|(key, value)| (key.to_string(), value.to_string()))
+                            .ok_or_else(|| {
+                                eyre!(
+                                    "Invalid env var '{pair}' in source '{s}', expected KEY=VALUE"
+                                )
+                            })
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(Self::env(vars))
+            }
+            _ => Err(eyre!(
+                "Unknown source type '{ty}' in '{s}', expected version, branch, commit, env, current or recorded"
+            )),
         }
     }
 }
@@ -51,6 +238,12 @@ impl<'url> Source<'url> {
 pub struct Ready<'url> {
     pub config: &'url ProjectConfig,
     pub path: PathBuf,
+    /// Whether its fork tests fork the latest block, see [`Tested::unpinned_fork`].
+    pub unpinned_fork: bool,
+    /// Variables documented by its `.env.example` that aren't set, if any.
+    pub missing_env_example: Option<MissingEnvExample>,
+    /// Commit the project was cloned at, see [`Tested::commit`].
+    pub commit: Option<String>,
     pub _temp_dir: TempDir,
 }
 
@@ -58,19 +251,108 @@ pub struct Ready<'url> {
 pub struct Built<'url> {
     pub state: Ready<'url>,
     pub build_time: f64,
+    /// Average time of `forge test --list` after the build, see [`Tested::discovery_time`].
+    pub discovery_time: Option<f64>,
+    /// The warnings of the build, see [`Tested::build_warnings`].
+    pub build_warnings: Option<Vec<String>>,
+    /// Time of every incremental build, see [`Tested::incremental_samples`].
+    pub incremental_samples: Vec<f64>,
+    /// Its config after the build, see [`Tested::config_fingerprint`].
+    pub config_fingerprint: Option<BTreeMap<String, String>>,
 }
 
 /// Final state of a project after successful testing.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Tested {
+    /// Name of the project, see [`ProjectConfig::name`].
     pub name: String,
+    /// URL the project was cloned from.
     pub url: String,
+    /// Time of the `forge build` of the project, in seconds.
     pub build_time: f64,
+    /// Average time of the test runs, in seconds.
     pub avg_test_time: f64,
+    /// Number of test runs, 0 for a project whose pipeline stopped after its build (see
+    /// [`Stage`]) or that is only built (see [`Tested::build_only`]).
     pub runs: usize,
+    /// Time of every test run, in seconds.
+    #[serde(default)]
+    pub samples: Vec<f64>,
+    /// Clock the test runs were timed with.
+    #[serde(default, skip_serializing_if = "TimingSource::is_process")]
+    pub timing_source: TimingSource,
+    /// Wall time of the `forge` process of every test run, in seconds, kept when `samples` are the
+    /// times reported by forge so that the project can be compared with process-timed results.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub process_samples: Vec<f64>,
+    /// Whether its fork tests fork the latest block rather than a pinned one, so that their
+    /// workload may differ between runs.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unpinned_fork: bool,
+    /// Command timed by its test runs, `forge test` unless its config measures another one. Such
+    /// projects aren't built, and their build time is 0.
+    #[serde(default, skip_serializing_if = "Measured::is_test")]
+    pub command: Measured,
+    /// Parameters of the project, if it was generated instead of cloned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synthetic: Option<SyntheticConfig>,
+    /// Average time of its test discovery with `forge test --list` after the build, in seconds, if
+    /// measured (see [`ProjectConfig::measure_discovery`]) and supported by `forge`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery_time: Option<f64>,
+    /// Average time of the `forge script` runs of its script (see [`ProjectConfig::script`]), in
+    /// seconds, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_script_time: Option<f64>,
+    /// Time of every script run, in seconds.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub script_samples: Vec<f64>,
+    /// Whether the project was only built (see [`ProjectConfig::build_only`]), so that it has no
+    /// test metrics to compare.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub build_only: bool,
+    /// The warnings printed by its `forge build`, normalized to compare between the sources of a
+    /// diff, e.g. `Warning (2072): Unused local variable. --> src/A.sol:12:9`. `None` if it wasn't
+    /// built, or for results recorded without them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_warnings: Option<Vec<String>>,
+    /// Average output of its `forge test` runs, if it ran any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_size: Option<OutputSize>,
+    /// Commit the project was cloned at (`git rev-parse HEAD`), so that the sides of a diff can be
+    /// checked to benchmark the same code. `None` for a generated project, or for results recorded
+    /// without it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Number of tests its `forge test` runs ran, as reported by forge, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_tests: Option<usize>,
+    /// Average time of its incremental builds, in seconds, if measured (see
+    /// [`ProjectConfig::incremental_runs`]): a `forge build` after a trivial edit of one of its
+    /// sources, see [`ProjectConfig::incremental_file`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incremental_build_time: Option<f64>,
+    /// Time of every incremental build, in seconds.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub incremental_samples: Vec<f64>,
+    /// Shell command run before each of its timed test runs, see [`ProjectConfig::prepare`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prepare: Option<String>,
+    /// Shell command run after each of its timed test runs, see [`ProjectConfig::conclude`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conclude: Option<String>,
+    /// The settings of its `forge config --json` that most often explain a diff, e.g. `fuzz.runs`,
+    /// see `fingerprint::KEYS`. `None` if forge didn't print its config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_fingerprint: Option<BTreeMap<String, String>>,
+    /// Network access of its timed runs, see [`ProjectConfig::network`].
+    #[serde(default, skip_serializing_if = "NetworkAccess::is_allowed")]
+    pub network: NetworkAccess,
 }
 
 impl Tested {
-    fn new(built_state: Built<'_>, tests_times: Vec<f64>, runs: usize) -> Self {
+    fn new(mut built_state: Built<'_>, tests_times: Vec<f64>, runs: usize) -> Self {
+        keep::passed(&mut built_state.state._temp_dir);
         Tested {
             name: built_state.state.config.name.clone(),
             url: built_state.state.config.url(),
@@ -81,74 +363,641 @@ impl Tested {
                 0.0
             },
             runs,
+            samples: tests_times,
+            timing_source: TimingSource::Process,
+            process_samples: Vec::new(),
+            unpinned_fork: built_state.state.unpinned_fork,
+            command: built_state.state.config.command,
+            discovery_time: built_state.discovery_time,
+            synthetic: built_state.state.config.synthetic.clone(),
+            avg_script_time: None,
+            script_samples: Vec::new(),
+            build_only: built_state.state.config.build_only,
+            build_warnings: built_state.build_warnings,
+            output_size: None,
+            commit: built_state.state.commit.clone(),
+            num_tests: None,
+            incremental_build_time: (!built_state.incremental_samples.is_empty()).then(|| {
+                built_state.incremental_samples.iter().sum::<f64>()
+                    / built_state.incremental_samples.len() as f64
+            }),
+            incremental_samples: built_state.incremental_samples,
+            prepare: built_state.state.config.prepare.clone(),
+            conclude: built_state.state.config.conclude.clone(),
+            config_fingerprint: built_state.config_fingerprint,
+            network: built_state.state.config.network(),
+        }
+    }
+
+    /// Average time of the test runs, `None` without any, e.g. for a project that was only built,
+    /// rather than a time of 0.
+    pub fn test_time(&self) -> Option<f64> {
+        (self.runs > 0).then_some(self.avg_test_time)
+    }
+
+    /// A project tested once in `avg_test_time` seconds after a 1s build, for the tests to set the
+    /// fields they check on with struct update syntax.
+    #[cfg(test)]
+    pub(crate) fn fixture(name: &str, avg_test_time: f64) -> Self {
+        Tested {
+            name: name.to_string(),
+            url: format!("https://github.com/{name}"),
+            build_time: 1.0,
+            avg_test_time,
+            runs: 1,
+            samples: vec![avg_test_time],
+            timing_source: TimingSource::Process,
+            process_samples: Vec::new(),
+            unpinned_fork: false,
+            command: Measured::Test,
+            discovery_time: None,
+            synthetic: None,
+            avg_script_time: None,
+            script_samples: Vec::new(),
+            build_only: false,
+            build_warnings: None,
+            output_size: None,
+            commit: None,
+            num_tests: None,
+            incremental_build_time: None,
+            incremental_samples: Vec::new(),
+            prepare: None,
+            conclude: None,
+            config_fingerprint: None,
+            network: NetworkAccess::Allowed,
+        }
+    }
+
+    /// The results of the test runs of a built project at `verbosity`, timed with its timing
+    /// source (see [`TimingSource`]). Without a time reported by forge for every run, the process
+    /// times are used instead, with a warning.
+    fn from_runs(
+        built_state: Built<'_>,
+        run_times: Vec<RunTime>,
+        runs: usize,
+        verbosity: Verbosity,
+    ) -> Self {
+        let config = built_state.state.config;
+        let process_times = run_times.iter().map(|time| time.process).collect();
+        let forge_times: Option<Vec<f64>> = run_times.iter().map(|time| time.forge).collect();
+        let outputs: Vec<(usize, usize)> = run_times.iter().map(|time| time.output).collect();
+        let num_tests = run_times.iter().rev().find_map(|time| time.tests);
+        let mut tested = Self::new(built_state, process_times, runs);
+        if config.command.is_test() {
+            tested.output_size = OutputSize::average(&outputs, verbosity);
+            tested.num_tests = num_tests;
+        }
+        if config.timing_source == TimingSource::Forge && config.command.is_test() && runs > 0 {
+            match forge_times {
+                Some(forge_times) => {
+                    tested.process_samples = std::mem::replace(&mut tested.samples, forge_times);
+                    tested.avg_test_time = tested.samples.iter().sum::<f64>() / runs as f64;
+                    tested.timing_source = TimingSource::Forge;
+                }
+                None => tracing::warn!(
+                    "{} Couldn't find the test time reported by forge for {}, falling back to the process time",
+                    Paint::yellow("WARNING:").bold(),
+                    config.name
+                ),
+            }
+        }
+        tested
+    }
+
+    /// Switches the test times timed by forge back to the process times, if they were kept.
+    /// Returns whether the test times are now process times.
+    fn use_process_times(&mut self) -> bool {
+        if self.timing_source == TimingSource::Process {
+            return true;
+        }
+        if self.process_samples.len() != self.samples.len() || self.samples.is_empty() {
+            return false;
+        }
+        self.samples = std::mem::take(&mut self.process_samples);
+        self.avg_test_time = self.samples.iter().sum::<f64>() / self.samples.len() as f64;
+        self.timing_source = TimingSource::Process;
+        true
+    }
+
+    /// Records the times of the script runs, if any.
+    fn with_script_times(mut self, script_times: Vec<f64>) -> Self {
+        if !script_times.is_empty() {
+            self.avg_script_time =
+                Some(script_times.iter().sum::<f64>() / script_times.len() as f64);
         }
+        self.script_samples = script_times;
+        self
     }
 }
 
+/// Results of running the benchmark pipeline with a single Foundry source.
+#[derive(Clone)]
+pub struct SourceResults {
+    pub source: Source,
+    /// Git repository the source was installed from.
+    pub foundry_repo: String,
+    /// Display name overriding the source's name, if any.
+    pub label: Option<String>,
+    /// Build information of the `forge` binary the source resolved to, if known.
+    pub forge: Option<ForgeVersion>,
+    pub tests: Vec<Tested>,
+}
+
 /// Helper struct to aggregate all the requires data to compute benchmark diffs.
-pub struct Benchmarks<'url> {
-    pub foundry_repo: &'url str,
+pub struct Benchmarks {
     pub verbosity: String,
-    pub ref_source: Source<'url>,
-    pub ref_tests: Vec<Tested>,
-    pub vs_source: Source<'url>,
-    pub vs_tests: Vec<Tested>,
+    pub num_runs: usize,
+    /// Results per source, in order. The first entry is the baseline the others are compared to.
+    pub results: Vec<SourceResults>,
+    /// Additional notes printed below the results table.
+    pub notes: Vec<String>,
+    /// How the run was invoked, printed in the footer of the tables if known.
+    pub reproduction: Option<Reproduction>,
+    /// Relative diff (in %) below which a diff is reported as noise, set with `--noise-threshold`.
+    /// Defaults to the noise floor of each project, twice the standard error of its diff.
+    pub noise_threshold: Option<f64>,
+    /// Coefficient of variation (in %) of the runs of a project above which it's reported as noisy,
+    /// set with `--noisy-threshold`.
+    pub noisy_threshold: f64,
+}
+
+impl SourceResults {
+    /// Name under which the source is reported: its label, or its name along with the version a
+    /// release channel or the current install resolved to, e.g. `stable (v1.2.3, a1b2c3d)`.
+    pub fn display_name(&self) -> Cow<'_, str> {
+        match (&self.label, &self.forge) {
+            (Some(label), _) => Cow::Borrowed(label),
+            (None, Some(forge)) if self.source.is_unpinned() => {
+                Cow::Owned(format!("{} ({})", self.source.name(), forge.resolved()))
+            }
+            (None, _) => Cow::Borrowed(self.source.name()),
+        }
+    }
+
+    /// Page of the source in its Foundry repository (see [`Source::github_url`]). An unpinned
+    /// source links to the release it resolved to, or else to the commit of its build, rather than
+    /// to the channel.
+    pub fn github_url(&self) -> String {
+        let base = utils::github_url();
+        let repo = &self.foundry_repo;
+        match &self.forge {
+            Some(forge) if self.source.is_unpinned() => {
+                match (forge.release_tag(), &forge.commit) {
+                    (Some(tag), _) => format!("{base}/{repo}/releases/tag/{tag}"),
+                    (None, Some(commit)) => format!("{base}/{repo}/commit/{commit}"),
+                    (None, None) => self.source.github_url(repo),
+                }
+            }
+            _ => self.source.github_url(repo),
+        }
+    }
+}
+
+impl Benchmarks {
+    pub fn baseline(&self) -> &SourceResults {
+        &self.results[0]
+    }
+
+    pub fn comparisons(&self) -> &[SourceResults] {
+        &self.results[1..]
+    }
+
+    /// Note stating that the test times are the ones reported by forge, if any project was timed by
+    /// forge (see [`TimingSource::Forge`]).
+    pub fn timing_source_note(&self) -> Option<String> {
+        self.results
+            .iter()
+            .flat_map(|results| &results.tests)
+            .any(|tested| tested.timing_source == TimingSource::Forge)
+            .then(|| {
+                "timing source: the test times are the durations reported by forge for running the test suites (--timing-source forge), without its startup.".to_string()
+            })
+    }
+
+    /// Notes the projects whose test runs ran between `--prepare` and `--conclude` hooks, whose
+    /// commands affect what the times compare.
+    pub fn hooks_note(&self) -> Option<String> {
+        let mut hooks: BTreeMap<(Option<&str>, Option<&str>), BTreeSet<&str>> = BTreeMap::new();
+        for tested in self.results.iter().flat_map(|results| &results.tests) {
+            let key = (tested.prepare.as_deref(), tested.conclude.as_deref());
+            if key != (None, None) {
+                hooks.entry(key).or_default().insert(&tested.name);
+            }
+        }
+        let described: Vec<String> = hooks
+            .into_iter()
+            .map(|((prepare, conclude), names)| {
+                let commands: Vec<String> = [
+                    prepare.map(|cmd| format!("`{}` before (--prepare)", redact::text(cmd))),
+                    conclude.map(|cmd| format!("`{}` after (--conclude)", redact::text(cmd))),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                format!(
+                    "{} ran {} every test run",
+                    names.into_iter().collect::<Vec<_>>().join(", "),
+                    commands.join(" and ")
+                )
+            })
+            .collect();
+        (!described.is_empty())
+            .then(|| format!("{}, excluded from the times.", described.join("; ")))
+    }
+
+    /// Notes the test runs isolated from the network by `--no-network`, and the projects exempted
+    /// from it since they need the network.
+    pub fn network_note(&self) -> Option<String> {
+        let mut isolated = false;
+        let mut exempted = BTreeSet::new();
+        for tested in self.results.iter().flat_map(|results| &results.tests) {
+            match tested.network {
+                NetworkAccess::Allowed => {}
+                NetworkAccess::Isolated => isolated = true,
+                NetworkAccess::Exempted => {
+                    exempted.insert(tested.name.as_str());
+                }
+            }
+        }
+        if exempted.is_empty() {
+            return isolated
+                .then(|| "the timed test runs had no network access (--no-network).".to_string());
+        }
+        Some(format!(
+            "the timed test runs had no network access (--no-network), except those of {}, which need the network.",
+            exempted.into_iter().collect::<Vec<_>>().join(", ")
+        ))
+    }
+
+    /// Note naming the projects that forked the latest block with any source (see
+    /// [`Tested::unpinned_fork`]), if any.
+    pub fn unpinned_fork_note(&self) -> Option<String> {
+        let names: BTreeSet<&str> = self
+            .results
+            .iter()
+            .flat_map(|results| &results.tests)
+            .filter(|tested| tested.unpinned_fork)
+            .map(|tested| tested.name.as_str())
+            .collect();
+        (!names.is_empty()).then(|| {
+            format!(
+                "unpinned fork: {} forked the latest block, so the compared workloads may differ.",
+                names.into_iter().collect::<Vec<_>>().join(", ")
+            )
+        })
+    }
 }
 
 /// Represents the state of a project during the benchmark pipeline.
 pub enum ProjectState<'url> {
+    /// Cloned into a temporary directory.
     Cloned(Ready<'url>),
+    /// Built with `forge build`, in the directory it was cloned into.
     Built(Built<'url>),
+    /// Every `forge test` run passed, the temporary directory is removed.
     Tested(Tested),
+    /// Failed at a stage, with the error of the failed step.
     Failed {
         name: &'url String,
+        /// `clone`, `build`, `prepare`, `test`, `conclude` or `script`.
         stage: &'static str,
         error: String,
+        /// Tests that failed, for a project failing at the test stage.
+        failed_tests: Vec<FailedTest>,
     },
 }
 
+/// Stage of the benchmark pipeline. The pipeline can stop after any of them, see [`run_pipeline`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Stage {
+    /// Clone the projects.
+    Clone,
+    /// Build the projects with `forge build`, timing it.
+    Build,
+    /// Run the tests of the projects with `forge test`, timing every run.
+    #[default]
+    Test,
+}
+
+/// Command timed by the test stage of a project, see [`ProjectConfig::command`].
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Measured {
+    /// `forge test`, after building the project.
+    #[default]
+    Test,
+    /// `forge fmt --check`, without installing the dependencies nor building the project.
+    Fmt,
+}
+
+impl Measured {
+    /// The command, e.g. `forge test`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Test => "forge test",
+            Self::Fmt => "forge fmt",
+        }
+    }
+
+    /// Whether the project is built before the runs.
+    pub fn builds(self) -> bool {
+        self == Self::Test
+    }
+
+    /// The `forge` arguments of a run with `verbosity`, followed by the `extra` ones of the
+    /// project, which only apply to `forge test`.
+    fn args(self, verbosity: Verbosity, extra: &[String]) -> Vec<String> {
+        match self {
+            Self::Test => test_args(verbosity, extra),
+            Self::Fmt => vec!["fmt".to_string(), "--check".to_string()],
+        }
+    }
+
+    /// Whether a run exiting with `code` succeeded. `forge fmt --check` exits with 1 when some
+    /// file isn't formatted, which still times a full run.
+    fn succeeded(self, code: Option<i32>) -> bool {
+        match self {
+            Self::Test => code == Some(0),
+            Self::Fmt => matches!(code, Some(0 | 1)),
+        }
+    }
+
+    pub(crate) fn is_test(&self) -> bool {
+        *self == Self::Test
+    }
+}
+
+/// Clock the test runs are timed with, see [`ProjectConfig::timing_source`].
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TimingSource {
+    /// The wall time of the whole `forge` process, including its startup, config loading and
+    /// output.
+    #[default]
+    Process,
+    /// The time `forge test` reports for running the test suites, in its `Ran N test suites in`
+    /// summary. Falls back to the process time if it can't be found.
+    Forge,
+}
+
+impl TimingSource {
+    pub(crate) fn is_process(&self) -> bool {
+        *self == Self::Process
+    }
+}
+
+/// Network access of the timed runs of a project, see [`ProjectConfig::network`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkAccess {
+    /// The runs had the network of the machine.
+    #[default]
+    Allowed,
+    /// The runs were isolated from the network by `--no-network`.
+    Isolated,
+    /// The project needs the network (see [`ProjectConfig::network`]), its runs were exempted
+    /// from `--no-network`.
+    Exempted,
+}
+
+impl NetworkAccess {
+    pub(crate) fn is_allowed(&self) -> bool {
+        *self == Self::Allowed
+    }
+}
+
+/// Times of a single test run, see [`try_test_run`].
+#[derive(Debug, Clone, Copy)]
+struct RunTime {
+    /// Wall time of the `forge` process, in seconds.
+    process: f64,
+    /// Time reported by forge for running the test suites, in seconds, if timed by forge.
+    forge: Option<f64>,
+    /// Number of bytes the run wrote to stdout and stderr.
+    output: (usize, usize),
+    /// Number of tests the run ran, if reported by forge.
+    tests: Option<usize>,
+}
+
+/// The time `forge test` reports for running the test suites in `output`, in seconds, from its
+/// summary, e.g. `Ran 2 test suites in 1.05s (1.89s CPU time): 8 tests passed, ...`.
+pub fn parse_reported_time(output: &str) -> Option<f64> {
+    strip_ansi(output).lines().rev().find_map(|line| {
+        let rest = line.trim().strip_prefix("Ran ")?;
+        let (suites, rest) = rest.split_once(" in ")?;
+        if !suites.contains("test suite") {
+            return None;
+        }
+        parse_duration(rest.split([' ', ':']).next()?)
+    })
+}
+
+/// The number of tests `forge test` reports running in `output`, from its summary, e.g.
+/// `Ran 2 test suites in 1.05s (1.89s CPU time): 8 tests passed, 0 failed, 0 skipped (8 total tests)`.
+pub fn parse_test_count(output: &str) -> Option<usize> {
+    strip_ansi(output).lines().rev().find_map(|line| {
+        let rest = line.trim().strip_suffix(" total tests)")?;
+        rest.rsplit_once('(')?.1.parse().ok()
+    })
+}
+
+/// Parses a duration printed by forge, e.g. `1.05s`, `226.80ms` or `850.00µs`, in seconds.
+fn parse_duration(duration: &str) -> Option<f64> {
+    let split = duration.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (value, unit) = duration.split_at(split);
+    let scale = match unit {
+        "s" => 1.0,
+        "ms" => 1e-3,
+        "µs" | "us" => 1e-6,
+        "ns" => 1e-9,
+        _ => return None,
+    };
+    Some(value.parse::<f64>().ok()? * scale)
+}
+
+/// Span of the events about `project`, in which the spans of its stages are nested.
+fn project_span(project: &ProjectConfig) -> Span {
+    tracing::info_span!("project", project = %project.name)
+}
+
+/// Creates the directory `project` is cloned into: a temporary directory for a
+/// [scratch](ProjectConfig::scratch) clone, and otherwise see [`keep::project_dir`].
+fn project_dir(project: &ProjectConfig) -> io::Result<TempDir> {
+    if project.scratch {
+        TempDir::new()
+    } else {
+        keep::project_dir(&project.name)
+    }
+}
+
 /// Attempts to clone a project.
-fn try_clone_project<'url>(repo: &'url ProjectConfig) -> ProjectState<'url> {
-    let temp_dir = match TempDir::new() {
+fn try_clone_project<'url>(
+    repo: &'url ProjectConfig,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> ProjectState<'url> {
+    let _stage =
+        tracing::info_span!(parent: &project_span(repo), "clone", stage = "clone").entered();
+    let temp_dir = match project_dir(repo) {
         Ok(td) => td,
         Err(e) => {
             let error_msg = format!(
                 "Failed to create temp directory for {}. Error: {:?}",
                 repo.name, e
             );
-            eprintln!(
-                "{} {} {}",
-                &repo.label(),
-                Paint::red("ERROR:").bold(),
-                error_msg
-            );
+            tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
             return ProjectState::Failed {
                 name: &repo.name,
                 stage: "clone",
                 error: error_msg,
+                failed_tests: Vec::new(),
             };
         }
     };
     let path = temp_dir.path().to_path_buf();
     let path_str = path.to_string_lossy();
 
-    println!(
-        "{} Cloning {} into {}",
-        &repo.label(),
-        Paint::cyan(&repo.url()),
-        Paint::yellow(&path_str)
-    );
+    reporter.project_stage_started(repo, "clone", &path);
+    let start_time = Instant::now();
+    let cloned = match &repo.synthetic {
+        Some(synthetic) => {
+            tracing::debug!("Generating the synthetic project ({synthetic}) in {path_str}");
+            synthetic::generate(synthetic, &path).map_err(|e| {
+                let error_msg = format!("Failed to generate {}. Error: {e:?}", repo.name);
+                tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+                error_msg
+            })
+        }
+        None => clone_repo(repo, &path, runner),
+    };
+    if let Err(error_msg) = cloned {
+        return ProjectState::Failed {
+            name: &repo.name,
+            stage: "clone",
+            error: error_msg,
+            failed_tests: Vec::new(),
+        };
+    }
+    let commit = match &repo.synthetic {
+        Some(_) => None,
+        None => head_commit(&path, runner),
+    };
+    reporter.project_stage_finished(repo, "clone", start_time.elapsed().as_secs_f64());
 
-    let clone_output = match Command::new("git")
-        .args([
-            "clone",
-            "--depth",
-            "1",
-            &repo.url(),
-            path.to_str().expect("Path should be valid UTF-8"),
-        ])
-        .output()
-    {
+    let unpinned_fork = fork::unpinned_fork(repo, &path);
+    if let Some(reason) = &unpinned_fork {
+        let message = format!("{} forks the latest block: {reason}", repo.name);
+        if repo.require_pinned_fork {
+            let error_msg = format!("{message} (--require-pinned-forks)");
+            tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+            return ProjectState::Failed {
+                name: &repo.name,
+                stage: "clone",
+                error: error_msg,
+                failed_tests: Vec::new(),
+            };
+        }
+        tracing::warn!(
+            "{} {message}. Its workload may differ between runs, pin the block to compare like \
+             with like",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+
+    let missing_env_example =
+        env_example::missing_vars(repo, &path).map(|(file, missing)| MissingEnvExample {
+            project: repo.name.clone(),
+            file: file.to_string(),
+            missing,
+        });
+    if let Some(example) = &missing_env_example {
+        let message = format!(
+            "{} documents variables in its {} that aren't set: {}",
+            repo.name,
+            example.file,
+            example.missing.join(", ")
+        );
+        if repo.require_env_example {
+            let error_msg = format!("{message} (--require-env-example)");
+            tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+            return ProjectState::Failed {
+                name: &repo.name,
+                stage: "clone",
+                error: error_msg,
+                failed_tests: Vec::new(),
+            };
+        }
+        tracing::warn!(
+            "{} {message}. Its tests may fail without them",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+    ProjectState::Cloned(Ready {
+        config: repo,
+        path,
+        unpinned_fork: unpinned_fork.is_some(),
+        missing_env_example,
+        commit,
+        _temp_dir: temp_dir,
+    })
+}
+
+/// Returns the commit checked out in the clone at `path`, if `git rev-parse HEAD` reports it.
+fn head_commit(path: &Path, runner: &dyn CommandRunner) -> Option<String> {
+    let output = runner
+        .output(
+            Command::new("git")
+                .args(["rev-parse", "HEAD"])
+                .current_dir(path),
+        )
+        .ok()?;
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || commit.is_empty() {
+        tracing::debug!(
+            "Failed to resolve the commit of the clone in {}",
+            path.display()
+        );
+        return None;
+    }
+    Some(commit)
+}
+
+/// Clones the repository of `repo` into `path`, at its revision if set (see
+/// [`ProjectConfig::rev`]), returning the error message of a failure.
+fn clone_repo(repo: &ProjectConfig, path: &Path, runner: &dyn CommandRunner) -> Result<(), String> {
+    // A revision is fetched on its own after the clone, as `--branch` doesn't take commits.
+    let no_checkout = if repo.rev.is_some() {
+        " --no-checkout"
+    } else {
+        ""
+    };
+    tracing::debug!(
+        "$ git clone --depth 1{no_checkout} {} {}",
+        repo.url(),
+        path.to_string_lossy()
+    );
+    let clone_output = match runner.output(
+        Command::new("git")
+            .args(["clone", "--depth", "1"])
+            .args(repo.rev.as_ref().map(|_| "--no-checkout"))
+            .arg(repo.url())
+            .arg(path),
+    ) {
         Ok(output) => output,
         Err(e) => {
             let error_msg = format!(
@@ -156,17 +1005,8 @@ fn try_clone_project<'url>(repo: &'url ProjectConfig) -> ProjectState<'url> {
                 repo.url(),
                 e
             );
-            eprintln!(
-                "{} {} {}",
-                &repo.label(),
-                Paint::red("ERROR:").bold(),
-                error_msg
-            );
-            return ProjectState::Failed {
-                name: &repo.name,
-                stage: "clone",
-                error: error_msg,
-            };
+            tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+            return Err(error_msg);
         }
     };
 
@@ -178,52 +1018,150 @@ fn try_clone_project<'url>(repo: &'url ProjectConfig) -> ProjectState<'url> {
         );
         ui::log_cmd_error(
             &clone_output.stderr,
-            &format!(
-                "{} {} {}",
-                &repo.label(),
-                Paint::red("ERROR:").bold(),
-                error_msg
-            ),
+            clone_output.status.code(),
+            &format!("{} {}", Paint::red("ERROR:").bold(), error_msg),
         );
-        return ProjectState::Failed {
-            name: &repo.name,
-            stage: "clone",
-            error: error_msg,
-        };
+        return Err(error_msg);
     }
-    println!("{} Cloned successfully.", &repo.label());
+    match &repo.rev {
+        Some(rev) => checkout_rev(repo, rev, path, runner),
+        None => Ok(()),
+    }
+}
 
-    ProjectState::Cloned(Ready {
-        config: repo,
-        path,
-        _temp_dir: temp_dir,
-    })
+/// Fetches `rev` of `repo` into its clone at `path` and checks it out, returning the error message
+/// of a failure.
+fn checkout_rev(
+    repo: &ProjectConfig,
+    rev: &str,
+    path: &Path,
+    runner: &dyn CommandRunner,
+) -> Result<(), String> {
+    for args in [
+        ["fetch", "--depth", "1", "origin", rev].as_slice(),
+        &["checkout", "--detach", "FETCH_HEAD"],
+    ] {
+        tracing::debug!("$ git {}", args.join(" "));
+        let output = runner
+            .output(Command::new("git").args(args).current_dir(path))
+            .map_err(|e| {
+                let error_msg = format!(
+                    "Failed to execute git {} for {}. Error: {e:?}",
+                    args[0],
+                    repo.url()
+                );
+                tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+                error_msg
+            })?;
+        if !output.status.success() {
+            let error_msg = format!(
+                "Failed to check out {rev} of {}. Git command exited with: {}.",
+                repo.url(),
+                output.status
+            );
+            ui::log_cmd_error(
+                &output.stderr,
+                output.status.code(),
+                &format!("{} {}", Paint::red("ERROR:").bold(), error_msg),
+            );
+            return Err(error_msg);
+        }
+    }
+    Ok(())
 }
 
-/// Attemp to run custom installations for projects that need it.
-fn try_handle_custom_setup(state: &Ready) -> Result<(), String> {
-    let repo_label = &state.config.label();
+/// The remappings of a cloned project, before the configured ones are merged into them: the lines
+/// of its `remappings.txt`, or else the ones `forge remappings` infers from its `foundry.toml` and
+/// libraries. Empty, with a warning, if they can't be read.
+fn existing_remappings(
+    state: &Ready<'_>,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+) -> Vec<String> {
+    let lines = |text: &str| text.lines().map(String::from).collect();
+    match fs::read_to_string(state.path.join("remappings.txt")) {
+        Ok(existing) => return lines(&existing),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => {
+            tracing::warn!(
+                "{} Failed to read the remappings.txt of {}, only writing the custom remappings: {e}",
+                Paint::yellow("WARNING:").bold(),
+                state.config.name
+            );
+            return Vec::new();
+        }
+    }
+    tracing::debug!("$ forge remappings");
+    match runner.output(
+        forge_command(toolchain, state.config)
+            .arg("remappings")
+            .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
+            .current_dir(&state.path),
+    ) {
+        Ok(output) if output.status.success() => lines(&String::from_utf8_lossy(&output.stdout)),
+        Ok(output) => {
+            tracing::warn!(
+                "{} 'forge remappings' failed for {} with exit code {:?}, only writing the custom remappings",
+                Paint::yellow("WARNING:").bold(),
+                state.config.name,
+                output.status.code()
+            );
+            Vec::new()
+        }
+        Err(e) => {
+            tracing::warn!(
+                "{} Failed to execute 'forge remappings' for {}, only writing the custom remappings: {e:?}",
+                Paint::yellow("WARNING:").bold(),
+                state.config.name
+            );
+            Vec::new()
+        }
+    }
+}
 
+/// Attemp to run custom installations for projects that need it.
+fn try_handle_custom_setup(
+    state: &Ready,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+) -> Result<(), String> {
     // Install dependencies if specified.
     if let Some(deps) = state.config.dependencies() {
-        println!("{repo_label} Running 'forge install' for custom dependencies");
-        let install_process = Command::new("forge")
-            .args(deps)
-            .current_dir(&state.path)
-            .output()
+        tracing::info!("Running 'forge install' for custom dependencies");
+        tracing::debug!("$ forge {}", deps.join(" "));
+        let install_process = runner
+            .output(
+                forge_command(toolchain, state.config)
+                    .args(deps)
+                    .current_dir(&state.path),
+            )
             .map_err(|e| format!("Failed to execute 'forge install': {e:?}"))?;
 
         if !install_process.status.success() {
             let error_msg = "'forge install' failed".to_string();
-            ui::log_cmd_error(&install_process.stderr, &error_msg);
+            ui::log_cmd_error(
+                &install_process.stderr,
+                install_process.status.code(),
+                &error_msg,
+            );
             return Err(error_msg);
         }
-        println!("{repo_label} Custom dependencies installed successfully.");
+        tracing::info!("Custom dependencies installed successfully.");
     }
 
-    // Create custom `remappings.txt` if specified.
+    // Write the configured remappings to `remappings.txt`, if any.
     if let Some(remappings) = state.config.remappings() {
-        println!("{repo_label} Creating custom 'remappings.txt'");
+        let remappings = match state.config.remappings_mode {
+            RemappingsMode::Merge => {
+                tracing::info!("Merging the custom remappings into 'remappings.txt'");
+                utils::merge_remappings(&existing_remappings(state, toolchain, runner), remappings)
+            }
+            RemappingsMode::Replace => {
+                tracing::info!("Creating custom 'remappings.txt'");
+                remappings.clone()
+            }
+        };
+        tracing::debug!("remappings.txt: {}", remappings.join(", "));
         let remappings_path = state.path.join("remappings.txt");
         let remappings_content = remappings.join("\n");
         fs::write(&remappings_path, remappings_content)
@@ -232,7 +1170,13 @@ fn try_handle_custom_setup(state: &Ready) -> Result<(), String> {
 
     // Create a `.env` file if environment variables are specified.
     if let Some(env_vars) = state.config.env_vars() {
-        println!("{repo_label} Creating '.env' file");
+        tracing::info!("Creating '.env' file");
+        let mut names: Vec<&String> = env_vars.keys().collect();
+        names.sort();
+        tracing::debug!(
+            ".env: {}",
+            names.into_iter().cloned().collect::<Vec<_>>().join(", ")
+        );
         let env_content = env_vars
             .iter()
             .map(|(key, value)| format!("{key}={value}"))
@@ -246,57 +1190,118 @@ fn try_handle_custom_setup(state: &Ready) -> Result<(), String> {
 }
 
 /// Attempts to build a cloned project.
-fn try_build_project<'url>(cloned_state: Ready<'url>) -> ProjectState<'url> {
+fn try_build_project<'url>(
+    cloned_state: Ready<'url>,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> ProjectState<'url> {
     let config = &cloned_state.config;
     let path_str = cloned_state.path.to_string_lossy();
+    let _stage =
+        tracing::info_span!(parent: &project_span(config), "build", stage = "build").entered();
+
+    if config.clean_env {
+        let vars: Vec<String> = forge_command(toolchain, config)
+            .get_envs()
+            .filter_map(|(key, value)| {
+                let key = key.to_string_lossy();
+                let value = redact::value(&key, &value?.to_string_lossy());
+                Some(format!("{key}={value}"))
+            })
+            .collect();
+        tracing::debug!(
+            "forge runs with a clean environment (--clean-env): {}",
+            vars.join(" ")
+        );
+    }
+
+    if !config.command.builds() {
+        tracing::info!(
+            "Skipping the build, '{}' doesn't need one",
+            config.command.name()
+        );
+        return ProjectState::Built(Built {
+            state: cloned_state,
+            build_time: 0.0,
+            discovery_time: None,
+            build_warnings: None,
+            incremental_samples: Vec::new(),
+            config_fingerprint: None,
+        });
+    }
 
-    if let Err(e) = try_handle_custom_setup(&cloned_state) {
+    if let Err(e) = try_handle_custom_setup(&cloned_state, toolchain, runner) {
         return ProjectState::Failed {
             name: &config.name,
             stage: "build",
             error: e,
+            failed_tests: Vec::new(),
         };
     }
 
-    println!("{} Running 'forge build'", &config.label());
+    reporter.project_stage_started(config, "build", &cloned_state.path);
+    tracing::debug!("$ forge build (in {path_str})");
     let start_time = Instant::now();
-    let build_process = match Command::new("forge")
-        .arg("build")
-        .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
-        .current_dir(&cloned_state.path)
-        .output()
-    {
+    let build_process = match runner.output(
+        forge_command(toolchain, config)
+            .arg("build")
+            .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
+            .current_dir(&cloned_state.path),
+    ) {
         Ok(output) => output,
         Err(e) => {
             let error_msg = format!(
                 "Failed to execute 'forge build' in {} for {}. Error: {:?}",
                 path_str, config.name, e
             );
-            eprintln!(
-                "{} {} {}",
-                &config.label(),
-                Paint::red("ERROR:").bold(),
-                error_msg
-            );
+            tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
             return ProjectState::Failed {
                 name: &config.name,
                 stage: "build",
                 error: error_msg,
+                failed_tests: Vec::new(),
             };
         }
     };
     let elapsed = start_time.elapsed().as_secs_f64();
 
     if build_process.status.success() {
-        println!(
-            "{} {} Elapsed time: {}",
-            &config.label(),
-            Paint::yellow("BUILT!").bold(),
-            Paint::yellow(format!("{elapsed:.2}s").as_str()).bold()
-        );
+        reporter.project_stage_finished(config, "build", elapsed);
+        let config_fingerprint = config_fingerprint(&cloned_state, toolchain, runner);
+        let discovery_time = config
+            .measure_discovery
+            .then(|| time_discovery(&cloned_state, toolchain, runner))
+            .flatten();
+        let incremental_samples = match config.incremental_runs {
+            Some(runs) => {
+                match time_incremental(config, &cloned_state.path, runs.get(), toolchain, runner) {
+                    Ok(samples) => samples,
+                    Err(error_msg) => {
+                        tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+                        return ProjectState::Failed {
+                            name: &config.name,
+                            stage: "build",
+                            error: error_msg,
+                            failed_tests: Vec::new(),
+                        };
+                    }
+                }
+            }
+            None => Vec::new(),
+        };
+        let output = [build_process.stdout, build_process.stderr].concat();
+        let build_warnings = warnings::parse(&String::from_utf8_lossy(&output), &cloned_state.path);
+        if !build_warnings.is_empty() {
+            tracing::debug!("'forge build' printed {} warning(s)", build_warnings.len());
+        }
         ProjectState::Built(Built {
             state: cloned_state,
             build_time: elapsed,
+            discovery_time,
+            build_warnings: Some(build_warnings),
+            incremental_samples,
+            config_fingerprint,
         })
     } else {
         let error_msg = format!(
@@ -306,105 +1311,662 @@ fn try_build_project<'url>(cloned_state: Ready<'url>) -> ProjectState<'url> {
         );
         ui::log_cmd_error(
             &build_process.stderr,
-            &format!(
-                "{} {} {}",
-                &config.label(),
-                Paint::red("ERROR:").bold(),
-                error_msg
-            ),
+            build_process.status.code(),
+            &format!("{} {}", Paint::red("ERROR:").bold(), error_msg),
         );
         ProjectState::Failed {
             name: &config.name,
             stage: "build",
             error: error_msg,
+            failed_tests: Vec::new(),
         }
     }
 }
 
-/// Attempts to run tests for a built project.
-fn try_test_project<'url>(
-    built_state: Built<'url>,
-    num_test_runs: usize,
-    verbosity: Verbosity,
-) -> ProjectState<'url> {
-    let config = &built_state.state.config;
-    let mut args = vec!["test"];
-    let verbosity_flag = format!("-{}", "v".repeat(verbosity as usize));
-    if verbosity != 0 {
-        args.push(verbosity_flag.as_str());
+/// Records the settings of a built project that most often explain a diff, from its
+/// `forge config --json` (see [`fingerprint::parse`]).
+///
+/// Returns `None` if forge fails to print its config, without failing the project: the diff is
+/// then only left without the config changes of the project.
+fn config_fingerprint(
+    state: &Ready<'_>,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+) -> Option<BTreeMap<String, String>> {
+    tracing::debug!("$ forge config --json");
+    let output = runner
+        .output(
+            forge_command(toolchain, state.config)
+                .args(["config", "--json"])
+                .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
+                .current_dir(&state.path),
+        )
+        .inspect_err(|e| tracing::debug!("Failed to run 'forge config --json': {e}"))
+        .ok()?;
+    if !output.status.success() {
+        tracing::debug!(
+            "'forge config --json' failed with exit code: {:?}",
+            output.status.code()
+        );
+        return None;
+    }
+    let fingerprint = fingerprint::parse(&String::from_utf8_lossy(&output.stdout));
+    if fingerprint.is_none() {
+        tracing::debug!("'forge config --json' didn't print a JSON object");
     }
+    fingerprint
+}
 
-    let mut test_times = Vec::with_capacity(num_test_runs);
-    for i in 0..num_test_runs {
-        println!(
-            "{} Running 'forge test' ({}/{}) for {}",
-            &config.label(),
-            i + 1,
-            num_test_runs,
-            config.name
+/// Returns a `forge` command of `toolchain` for the project of `config`, with a clean environment
+/// with `--clean-env` (see [`ProjectConfig::clean_env`]).
+fn forge_command(toolchain: &Toolchain, config: &ProjectConfig) -> Command {
+    with_project_env(toolchain.forge(), config)
+}
+
+/// Clears the environment of `cmd` with `--clean-env`, keeping the env vars of the project of
+/// `config` besides the allowed ones (see [`toolchain::clear_env`]).
+fn with_project_env(mut cmd: Command, config: &ProjectConfig) -> Command {
+    if config.clean_env {
+        toolchain::clear_env(&mut cmd, config.env_vars().into_iter().flatten());
+    }
+    cmd
+}
+
+/// Number of timed `forge test --list` runs of a project, which are fast.
+pub(crate) const DISCOVERY_RUNS: usize = 3;
+
+/// Times the test discovery of a built project with `forge test --list`, [`DISCOVERY_RUNS`] times.
+///
+/// Returns the average time in seconds, or `None` if a run fails, e.g. with a `forge` that doesn't
+/// support `--list`: the metric is then reported as "n/a", without failing the project.
+fn time_discovery(
+    state: &Ready<'_>,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+) -> Option<f64> {
+    let name = &state.config.name;
+    let mut total = 0.0;
+    for run in 0..DISCOVERY_RUNS {
+        tracing::debug!("$ forge test --list ({}/{DISCOVERY_RUNS})", run + 1);
+        let start_time = Instant::now();
+        let output = runner.output(
+            forge_command(toolchain, state.config)
+                .args(["test", "--list"])
+                .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
+                .current_dir(&state.path),
+        );
+        let elapsed = start_time.elapsed().as_secs_f64();
+        let reason = match output {
+            Ok(output) if output.status.success() => {
+                total += elapsed;
+                continue;
+            }
+            Ok(output) if String::from_utf8_lossy(&output.stderr).contains("--list") => {
+                "this forge doesn't support 'forge test --list'".to_string()
+            }
+            Ok(output) => format!(
+                "'forge test --list' failed with exit code: {:?}",
+                output.status.code()
+            ),
+            Err(e) => format!("failed to execute 'forge test --list'. Error: {e:?}"),
+        };
+        tracing::warn!(
+            "{} The discovery time of {name} is n/a: {reason}",
+            Paint::yellow("WARNING:").bold()
         );
+        return None;
+    }
+    let avg = total / DISCOVERY_RUNS as f64;
+    tracing::info!(
+        duration_secs = avg,
+        "Discovery time: {avg:.2}s (avg for {DISCOVERY_RUNS} runs)"
+    );
+    Some(avg)
+}
 
-        let start_at = Instant::now();
-        let test_process = match Command::new("forge")
-            .args(&args)
-            .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
-            .current_dir(&built_state.state.path)
-            .output()
-        {
-            Ok(output) => output,
+/// Appended to the edited source of every incremental build, with the number of the run, so that
+/// every run recompiles it while the edits stay deterministic.
+pub(crate) const INCREMENTAL_MARKER: &str = "// foundry-benchmarks: incremental build";
+
+/// The source of the project in `dir` edited by its incremental builds, relative to `dir`: its
+/// [`ProjectConfig::incremental_file`], or else the first Solidity file of `src` in path order.
+pub(crate) fn incremental_file(config: &ProjectConfig, dir: &Path) -> Option<PathBuf> {
+    if let Some(file) = &config.incremental_file {
+        return Some(PathBuf::from(file));
+    }
+    let mut dirs = vec![PathBuf::from("src")];
+    let mut files = Vec::new();
+    while let Some(rel) = dirs.pop() {
+        for entry in fs::read_dir(dir.join(&rel)).into_iter().flatten().flatten() {
+            let rel = rel.join(entry.file_name());
+            match entry.file_type() {
+                Ok(ty) if ty.is_dir() => dirs.push(rel),
+                Ok(ty) if ty.is_file() && rel.extension().is_some_and(|ext| ext == "sol") => {
+                    files.push(rel)
+                }
+                _ => {}
+            }
+        }
+    }
+    files.into_iter().min()
+}
+
+/// Times `runs` incremental builds of the built project in `dir`: before every `forge build`, a
+/// comment is appended to its [`incremental_file`], and the source is restored right after the
+/// build. A last, untimed, `forge build` then brings the artifacts back to the pristine sources,
+/// so that the test runs don't recompile them.
+///
+/// Returns the time of every build in seconds, empty if the file can't be read or a build fails:
+/// the metric is then reported as "n/a", without failing the project. Fails if the source can't be
+/// restored, or the pristine sources don't build anymore.
+fn time_incremental(
+    config: &ProjectConfig,
+    dir: &Path,
+    runs: usize,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+) -> Result<Vec<f64>, String> {
+    let name = &config.name;
+    let warn = |reason: String| {
+        tracing::warn!(
+            "{} The incremental build time of {name} is n/a: {reason}",
+            Paint::yellow("WARNING:").bold()
+        );
+    };
+    let Some(file) = incremental_file(config, dir) else {
+        warn("it has no Solidity file in src, set `incremental_file`".to_string());
+        return Ok(Vec::new());
+    };
+    let path = dir.join(&file);
+    let original = match fs::read(&path) {
+        Ok(original) => original,
+        Err(e) => {
+            warn(format!("failed to read {}: {e}", file.display()));
+            return Ok(Vec::new());
+        }
+    };
+    let forge_build = || {
+        runner.output(
+            forge_command(toolchain, config)
+                .arg("build")
+                .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
+                .current_dir(dir),
+        )
+    };
+
+    let restore = || {
+        fs::write(&path, &original)
+            .map_err(|e| format!("Failed to restore {} of {name}: {e}", file.display()))
+    };
+
+    let mut samples = Vec::with_capacity(runs);
+    for run in 1..=runs {
+        let mut edited = original.clone();
+        edited.extend_from_slice(format!("\n{INCREMENTAL_MARKER} {run}\n").as_bytes());
+        if let Err(e) = fs::write(&path, edited) {
+            // A failed write may have truncated the source.
+            restore()?;
+            warn(format!("failed to edit {}: {e}", file.display()));
+            break;
+        }
+        tracing::debug!("$ forge build ({run}/{runs}, {} edited)", file.display());
+        let start_time = Instant::now();
+        let output = forge_build();
+        let elapsed = start_time.elapsed().as_secs_f64();
+        restore()?;
+        match output {
+            Ok(output) if output.status.success() => samples.push(elapsed),
+            Ok(output) => {
+                warn(format!(
+                    "the incremental 'forge build' failed with exit code: {:?}",
+                    output.status.code()
+                ));
+                break;
+            }
             Err(e) => {
-                let error_msg = format!(
-                    "Failed to execute 'forge test' for {}. Error: {:?}",
-                    config.name, e
-                );
-                eprintln!(
-                    "{} {} {}",
-                    &config.label(),
-                    Paint::red("ERROR:").bold(),
-                    error_msg
-                );
-                return ProjectState::Failed {
-                    name: &config.name,
-                    stage: "test",
-                    error: error_msg,
-                };
+                warn(format!("failed to execute 'forge build'. Error: {e:?}"));
+                break;
             }
-        };
-        let elapsed = start_at.elapsed().as_secs_f64();
+        }
+    }
 
-        if test_process.status.success() {
-            println!(
-                "{} {} Elapsed time: {}",
-                &config.label(),
-                Paint::green("PASSED!").bold(),
-                Paint::green(format!("{elapsed:.2}s").as_str()).bold()
-            );
-            test_times.push(elapsed);
-        } else {
+    tracing::debug!("$ forge build (restored sources)");
+    match forge_build() {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            return Err(format!(
+                "'forge build' of the restored sources of {name} failed with exit code: {:?}.",
+                output.status.code()
+            ));
+        }
+        Err(e) => {
+            return Err(format!(
+                "Failed to execute 'forge build' of the restored sources of {name}. Error: {e:?}"
+            ));
+        }
+    }
+    if samples.len() < runs {
+        return Ok(Vec::new());
+    }
+    let avg = samples.iter().sum::<f64>() / runs as f64;
+    tracing::info!(
+        duration_secs = avg,
+        "Incremental build time: {avg:.2}s (avg for {runs} runs)"
+    );
+    Ok(samples)
+}
+
+/// Returns the `forge` arguments of the timed runs of `config` with `verbosity`, pinning the number
+/// of test threads with `--forge-threads` (see [`ProjectConfig::forge_threads`]).
+pub(crate) fn run_args(config: &ProjectConfig, verbosity: Verbosity) -> Vec<String> {
+    let mut args = config.command.args(verbosity, &config.test_args);
+    if config.command.is_test()
+        && let Some(threads) = config.forge_threads
+    {
+        args.extend(["--threads".to_string(), threads.to_string()]);
+    }
+    args
+}
+
+/// Returns the `forge test` arguments for the given verbosity, followed by the `extra` ones of
+/// the project.
+fn test_args(verbosity: Verbosity, extra: &[String]) -> Vec<String> {
+    let mut args = vec!["test".to_string()];
+    if verbosity != 0 {
+        args.push(verbosity_flag(verbosity));
+    }
+    args.extend(extra.iter().cloned());
+    args
+}
+
+/// Returns the `forge script` arguments of the script of `project`, followed by its extra ones, or
+/// `None` if it has no script.
+pub(crate) fn script_args(project: &ProjectConfig) -> Option<Vec<String>> {
+    let path = project.script.as_ref()?;
+    let mut args = vec!["script".to_string(), path.clone()];
+    args.extend(project.script_args.iter().cloned());
+    Some(args)
+}
+
+/// Returns the `forge` flag for the given verbosity level (e.g. `-vvv`), or an empty string.
+pub fn verbosity_flag(verbosity: Verbosity) -> String {
+    if verbosity != 0 {
+        format!("-{}", "v".repeat(verbosity as usize))
+    } else {
+        String::new()
+    }
+}
+
+/// Attempts a single timed `forge test` run (the `run`-th out of `num_test_runs`) of a built project,
+/// or of the command it measures instead (see [`Measured`]). Returns the elapsed time in seconds,
+/// along with the time reported by forge when timed by forge, or an error message if the run failed.
+fn try_test_run(
+    built_state: &Built<'_>,
+    args: &[String],
+    toolchain: &Toolchain,
+    run: usize,
+    num_test_runs: usize,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Result<RunTime, (String, Vec<FailedTest>)> {
+    let config = &built_state.state.config;
+    let _stage = tracing::info_span!(
+        parent: &project_span(config),
+        "test",
+        stage = "test",
+        run = run + 1
+    )
+    .entered();
+    reporter.test_run_started(config, run, num_test_runs);
+
+    let mut forge = match config.network() {
+        NetworkAccess::Isolated => {
+            tracing::debug!("$ unshare --map-root-user --net forge {}", args.join(" "));
+            with_project_env(toolchain.forge_without_network(), config)
+        }
+        NetworkAccess::Allowed | NetworkAccess::Exempted => {
+            tracing::debug!("$ forge {}", args.join(" "));
+            forge_command(toolchain, config)
+        }
+    };
+    let start_at = Instant::now();
+    let test_process = match runner.output(
+        forge
+            .args(args)
+            .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
+            .current_dir(&built_state.state.path),
+    ) {
+        Ok(output) => output,
+        Err(e) => {
             let error_msg = format!(
-                "'forge test' for {} FAILED with status code: {:?}",
+                "Failed to execute '{}' for {}. Error: {:?}",
+                config.command.name(),
                 config.name,
-                test_process.status.code()
+                e
             );
-            ui::log_cmd_error(
-                &test_process.stdout,
-                &format!(
-                    "{} {} {}",
-                    &config.label(),
-                    Paint::red("FAILED:").bold(),
-                    error_msg
-                ),
+            tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+            return Err((error_msg, Vec::new()));
+        }
+    };
+    let elapsed = start_at.elapsed().as_secs_f64();
+
+    if config.command.succeeded(test_process.status.code()) {
+        let forge = (config.timing_source == TimingSource::Forge && config.command.is_test())
+            .then(|| parse_reported_time(&String::from_utf8_lossy(&test_process.stdout)))
+            .flatten();
+        if let Some(reported) = forge {
+            tracing::debug!("forge reported {reported:.3}s for running the test suites");
+        }
+        reporter.test_run_completed(config, run, forge.unwrap_or(elapsed));
+        let tests = config
+            .command
+            .is_test()
+            .then(|| parse_test_count(&String::from_utf8_lossy(&test_process.stdout)))
+            .flatten();
+        Ok(RunTime {
+            process: elapsed,
+            forge,
+            output: (test_process.stdout.len(), test_process.stderr.len()),
+            tests,
+        })
+    } else {
+        let error_msg = format!(
+            "'{}' for {} FAILED with status code: {:?}",
+            config.command.name(),
+            config.name,
+            test_process.status.code()
+        );
+        let failed_tests = parse_failed_tests(&String::from_utf8_lossy(&test_process.stdout));
+        let msg = format!("{} {}", Paint::red("FAILED:").bold(), error_msg);
+        if failed_tests.is_empty() || ui::full_errors() {
+            ui::log_cmd_error(&test_process.stdout, test_process.status.code(), &msg);
+        } else {
+            tracing::error!(
+                exit_code = test_process.status.code(),
+                failed_tests = failed_tests.len(),
+                "{}\n{}",
+                redact::text(&msg),
+                redact::text(&failed_tests_summary(&failed_tests))
+            );
+        }
+        Err((error_msg, failed_tests))
+    }
+}
+
+/// Runs the `kind` hook (`prepare` or `conclude`) of a timed test run of a built project: `cmd`,
+/// through the shell of the platform (see [`Platform::shell`]) in the project directory. Returns an
+/// error message if it fails.
+fn run_hook(
+    built_state: &Built<'_>,
+    kind: &str,
+    cmd: &str,
+    runner: &dyn CommandRunner,
+) -> Result<(), String> {
+    let config = &built_state.state.config;
+    tracing::debug!("$ {cmd} (--{kind})");
+    let output = runner
+        .output(
+            Platform::default()
+                .shell(cmd)
+                .current_dir(&built_state.state.path),
+        )
+        .map_err(|e| {
+            format!(
+                "Failed to execute the {kind} hook '{}' for {}. Error: {e:?}",
+                redact::text(cmd),
+                config.name
+            )
+        })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let error_msg = format!(
+        "The {kind} hook '{}' for {} failed with exit code: {:?}.",
+        redact::text(cmd),
+        config.name,
+        output.status.code()
+    );
+    ui::log_cmd_error(
+        &[output.stdout, output.stderr].concat(),
+        output.status.code(),
+        &format!("{} {}", Paint::red("ERROR:").bold(), error_msg),
+    );
+    Err(error_msg)
+}
+
+/// Attempts a single timed `forge script` run (the `run`-th out of `num_runs`) of the script of a
+/// built project. Returns the elapsed time in seconds, or an error message if the run failed.
+fn try_script_run(
+    built_state: &Built<'_>,
+    args: &[String],
+    toolchain: &Toolchain,
+    run: usize,
+    num_runs: usize,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Result<f64, String> {
+    let config = &built_state.state.config;
+    let _stage = tracing::info_span!(
+        parent: &project_span(config),
+        "script",
+        stage = "script",
+        run = run + 1
+    )
+    .entered();
+    reporter.script_run_started(config, run, num_runs);
+
+    tracing::debug!("$ forge {}", args.join(" "));
+    let start_at = Instant::now();
+    let script_process = match runner.output(
+        forge_command(toolchain, config)
+            .args(args)
+            .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
+            .current_dir(&built_state.state.path),
+    ) {
+        Ok(output) => output,
+        Err(e) => {
+            let error_msg = format!(
+                "Failed to execute 'forge script' for {}. Error: {:?}",
+                config.name, e
             );
+            tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+            return Err(error_msg);
+        }
+    };
+    let elapsed = start_at.elapsed().as_secs_f64();
+
+    if script_process.status.success() {
+        reporter.script_run_completed(config, run, elapsed);
+        Ok(elapsed)
+    } else {
+        let error_msg = format!(
+            "'forge script' for {} FAILED with status code: {:?}",
+            config.name,
+            script_process.status.code()
+        );
+        // Scripts report their errors on stderr, after the logs of the script on stdout.
+        let output = [script_process.stdout, script_process.stderr].concat();
+        ui::log_cmd_error(
+            &output,
+            script_process.status.code(),
+            &format!("{} {}", Paint::red("FAILED:").bold(), error_msg),
+        );
+        Err(error_msg)
+    }
+}
+
+/// Starts the local services of the fork tests of `project`, if any, within the span of its test
+/// stage: the proxy caching its RPC requests, and its `anvil` node, forking through the proxy if
+/// both use the same endpoint.
+///
+/// Returns the node, to be dropped after the test runs, and the toolchain to run them with: one
+/// setting the endpoints of the services on `forge test`, over the `.env` file of the project.
+fn start_test_services(
+    project: &ProjectConfig,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+) -> Result<(Option<Anvil>, Toolchain), String> {
+    if project.anvil.is_none() && project.rpc_cache.is_none() {
+        return Ok((None, toolchain.clone()));
+    }
+    let _stage =
+        tracing::info_span!(parent: &project_span(project), "test", stage = "test").entered();
+    let mut test_toolchain = toolchain.clone();
+    let mut cached = None;
+    if let Some(config) = &project.rpc_cache {
+        let upstream = project.env_value(&config.url_env).ok_or_else(|| {
+            format!(
+                "{} is not set, there is no endpoint to cache",
+                config.url_env
+            )
+        })?;
+        let endpoint = rpc_cache::endpoint(&upstream).map_err(|e| {
+            format!(
+                "Failed to start the RPC cache for {}. Error: {e}",
+                project.name
+            )
+        })?;
+        tracing::info!(
+            "Caching the RPC requests to ${} through {endpoint}",
+            config.url_env
+        );
+        test_toolchain = test_toolchain.with_var(&config.url_env, &endpoint);
+        cached = Some((&config.url_env, endpoint));
+    }
+    let Some(config) = &project.anvil else {
+        return Ok((None, test_toolchain));
+    };
+    let fork_url = match &cached {
+        Some((key, endpoint)) if **key == config.fork_url_env => Some(endpoint.clone()),
+        _ => project.env_value(&config.fork_url_env),
+    }
+    .ok_or_else(|| {
+        format!(
+            "{} is not set, anvil has no endpoint to fork",
+            config.fork_url_env
+        )
+    })?;
+    let anvil = Anvil::start(project, config, &fork_url, toolchain, runner)?;
+    let test_toolchain = test_toolchain.with_var(anvil.env_var(), anvil.endpoint());
+    Ok((Some(anvil), test_toolchain))
+}
+
+/// Number of test runs of `project` in a test stage running `num_test_runs` per project: none if
+/// it's only built.
+fn planned_runs(project: &ProjectConfig, num_test_runs: usize) -> usize {
+    if project.build_only { 0 } else { num_test_runs }
+}
+
+/// Attempts to run tests for a built project, or only records its build if it's build-only.
+fn try_test_project<'url>(
+    built_state: Built<'url>,
+    num_test_runs: usize,
+    verbosity: Verbosity,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> ProjectState<'url> {
+    let config = built_state.state.config;
+    if config.build_only {
+        let _span = project_span(config).entered();
+        tracing::info!("Skipping the test runs of {} (build only)", config.name);
+        let tested = Tested::new(built_state, Vec::new(), 0);
+        reporter.project_tested(&tested);
+        return ProjectState::Tested(tested);
+    }
+    let args = run_args(config, verbosity);
+    // The node, if any, is killed once dropped after the runs.
+    let (_anvil, toolchain) = match start_test_services(config, toolchain, runner) {
+        Ok(started) => started,
+        Err(error) => {
             return ProjectState::Failed {
                 name: &config.name,
                 stage: "test",
-                error: error_msg,
+                error,
+                failed_tests: Vec::new(),
+            };
+        }
+    };
+
+    let mut test_times = Vec::with_capacity(num_test_runs);
+    for i in 0..num_test_runs {
+        if let Some(cmd) = &config.prepare
+            && let Err(error) = run_hook(&built_state, "prepare", cmd, runner)
+        {
+            return ProjectState::Failed {
+                name: &config.name,
+                stage: "prepare",
+                error,
+                failed_tests: Vec::new(),
             };
         }
+        let run = try_test_run(
+            &built_state,
+            &args,
+            &toolchain,
+            i,
+            num_test_runs,
+            runner,
+            reporter,
+        );
+        // The hook concludes the failed runs too, e.g. to clean up after them.
+        let concluded = match &config.conclude {
+            Some(cmd) => run_hook(&built_state, "conclude", cmd, runner),
+            None => Ok(()),
+        };
+        match (run, concluded) {
+            (Ok(time), Ok(())) => test_times.push(time),
+            (Err((error, failed_tests)), _) => {
+                return ProjectState::Failed {
+                    name: &config.name,
+                    stage: "test",
+                    error,
+                    failed_tests,
+                };
+            }
+            (Ok(_), Err(error)) => {
+                return ProjectState::Failed {
+                    name: &config.name,
+                    stage: "conclude",
+                    error,
+                    failed_tests: Vec::new(),
+                };
+            }
+        }
+    }
+
+    // The script runs as many times as the tests, once they all passed.
+    let mut script_times = Vec::new();
+    if let Some(args) = script_args(config) {
+        for i in 0..num_test_runs {
+            match try_script_run(
+                &built_state,
+                &args,
+                &toolchain,
+                i,
+                num_test_runs,
+                runner,
+                reporter,
+            ) {
+                Ok(elapsed) => script_times.push(elapsed),
+                Err(error) => {
+                    return ProjectState::Failed {
+                        name: &config.name,
+                        stage: "script",
+                        error,
+                        failed_tests: Vec::new(),
+                    };
+                }
+            }
+        }
     }
 
     if test_times.len() == num_test_runs {
-        ProjectState::Tested(Tested::new(built_state, test_times, num_test_runs))
+        let _span = project_span(config).entered();
+        let tested = Tested::from_runs(built_state, test_times, num_test_runs, verbosity)
+            .with_script_times(script_times);
+        reporter.project_tested(&tested);
+        ProjectState::Tested(tested)
     } else {
         let error_msg = format!(
             "Incomplete test runs for {} (expected {}, got {}).",
@@ -416,124 +1978,1348 @@ fn try_test_project<'url>(
             name: &config.name,
             stage: "test",
             error: error_msg,
+            failed_tests: Vec::new(),
         }
     }
 }
 
-/// Orchestrates the benchmark pipeline for a list of repository URLs.
+/// Runs the timed command of a built project once more with `toolchain`, profiled with
+/// `perf record -g` into `data` (see [`Toolchain::forge_under_perf`]), along with the local
+/// services of its fork tests. The run isn't timed, nor its hooks run.
 ///
-/// Steps:
-///  1. Clone repositories from github (in parallel).
-///  2. Run `forge build` (in parallel).
-///  3. Run `forge test` (sequentially).
-pub fn run_pipeline(
-    projects: &[ProjectConfig],
-    num_test_runs: usize,
+/// Returns an error message if the run failed.
+pub(crate) fn profile_test_run(
+    built_state: &Built<'_>,
     verbosity: Verbosity,
-) -> Result<Vec<Tested>> {
-    if projects.is_empty() {
-        println!("No repository URLs provided to benchmark.");
-        return Ok(Vec::new());
+    toolchain: &Toolchain,
+    data: &Path,
+    runner: &dyn CommandRunner,
+) -> Result<(), String> {
+    let config = built_state.state.config;
+    let args = run_args(config, verbosity);
+    let (_anvil, toolchain) = start_test_services(config, toolchain, runner)?;
+    tracing::debug!(
+        "$ perf record -g -o {} -- forge {}",
+        data.display(),
+        args.join(" ")
+    );
+    let output = runner
+        .output(
+            with_project_env(toolchain.forge_under_perf(data), config)
+                .args(&args)
+                .env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true")
+                .current_dir(&built_state.state.path),
+        )
+        .map_err(|e| {
+            format!(
+                "Failed to execute 'perf record' for {}. Error: {e:?}",
+                config.name
+            )
+        })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(format!(
+        "The profiled '{}' of {} failed with status code: {:?}. {}",
+        config.command.name(),
+        config.name,
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+/// Makes the test times of every project comparable between the sources of `results`: a project
+/// timed by forge with some sources but not with others (e.g. because forge's summary couldn't be
+/// parsed, or against a baseline recorded with another timing source) is compared with the
+/// process times of all of them. A result without any process time left to compare is removed.
+///
+/// Returns the names of the projects switched to the process times.
+pub fn align_timing_sources(results: &mut [SourceResults]) -> Vec<String> {
+    let names: BTreeSet<String> = results
+        .iter()
+        .flat_map(|results| &results.tests)
+        .map(|tested| tested.name.clone())
+        .collect();
+    let mut aligned = Vec::new();
+    for name in names {
+        let sources: BTreeSet<TimingSource> = results
+            .iter()
+            .flat_map(|results| &results.tests)
+            .filter(|tested| tested.name == name)
+            .map(|tested| tested.timing_source)
+            .collect();
+        if sources.len() < 2 {
+            continue;
+        }
+        for results in results.iter_mut() {
+            results
+                .tests
+                .retain_mut(|tested| tested.name != name || tested.use_process_times());
+        }
+        aligned.push(name);
     }
+    aligned
+}
 
-    ui::banner(Some("CLONE PROJECTS (in parallel)"));
-    let cloned_outcomes: Vec<ProjectState> = projects.par_iter().map(try_clone_project).collect();
+/// Exit code of a run in which some projects failed, unless `--allow-failures` is set.
+pub const FAILED_PROJECTS_EXIT_CODE: u8 = 4;
 
-    let mut successfully_cloned: Vec<Ready> = Vec::new();
-    let mut failed_project_names: Vec<&String> = Vec::new();
+/// A project that failed at some stage of the pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Failure {
+    pub project: String,
+    /// Stage the project failed at (e.g. "build").
+    pub stage: String,
+    pub error: String,
+    /// Tests that failed, for a project failing at the test stage.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_tests: Vec<FailedTest>,
+}
 
-    for outcome in cloned_outcomes {
-        match outcome {
-            ProjectState::Cloned(cloned) => successfully_cloned.push(cloned),
-            ProjectState::Failed {
-                name, stage, error, ..
-            } => {
-                eprintln!("Project '{name}' failed at stage '{stage}': {error}");
-                failed_project_names.push(name);
-            }
-            _ => unreachable!("Unexpected outcome after cloning stage"),
+impl Failure {
+    fn new(project: &str, stage: &str, error: String, failed_tests: Vec<FailedTest>) -> Self {
+        Self {
+            project: project.to_string(),
+            stage: stage.to_string(),
+            error: redact::text(&error),
+            failed_tests: failed_tests
+                .into_iter()
+                .map(|test| FailedTest {
+                    reason: test.reason.map(|reason| redact::text(&reason)),
+                    ..test
+                })
+                .collect(),
         }
     }
+}
 
-    ui::banner(Some("BUILD PROJECTS (in parallel)"));
-    let built_outcomes: Vec<ProjectState> = successfully_cloned
-        .into_par_iter()
-        .map(try_build_project)
+/// A test that failed in a `forge test` run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FailedTest {
+    /// Name of the test, prefixed with its contract (e.g. `CounterTest::test_increment`).
+    pub name: String,
+    /// Revert reason or failed assertion, if reported.
+    pub reason: Option<String>,
+}
+
+impl fmt::Display for FailedTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "{} ({reason})", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Most failing tests named by [`failed_tests_summary`].
+const MAX_LISTED_FAILED_TESTS: usize = 5;
+
+/// Compact summary of the `tests` that failed in a run, e.g.
+/// `2 tests failed: CounterTest::test_a (revert: boom), CounterTest::test_b`.
+pub fn failed_tests_summary(tests: &[FailedTest]) -> String {
+    let mut listed: Vec<String> = tests
+        .iter()
+        .take(MAX_LISTED_FAILED_TESTS)
+        .map(ToString::to_string)
         .collect();
+    if tests.len() > MAX_LISTED_FAILED_TESTS {
+        listed.push("…".to_string());
+    }
+    let noun = if tests.len() == 1 { "test" } else { "tests" };
+    format!("{} {noun} failed: {}", tests.len(), listed.join(", "))
+}
 
-    let mut successfully_built: Vec<Built> = Vec::new();
-    for outcome in built_outcomes {
-        match outcome {
-            ProjectState::Built(built) => successfully_built.push(built),
-            ProjectState::Failed {
-                name, stage, error, ..
-            } => {
-                eprintln!("Project '{name}' failed at stage '{stage}': {error}");
-                failed_project_names.push(name);
+/// Extracts the failing tests from the output of `forge test`, along with their revert reasons.
+///
+/// Failing tests are listed as `[FAIL: <reason>] test_name() (gas: ...)` (or
+/// `[FAIL. Reason: <reason>]` with older versions) below the `Ran N tests for <path>:<contract>`
+/// line of their suite, and again in the `Failing tests:` section at the end.
+pub fn parse_failed_tests(output: &str) -> Vec<FailedTest> {
+    let mut failed: Vec<FailedTest> = Vec::new();
+    let mut contract = None;
+    for line in output.lines().map(strip_ansi) {
+        let line = line.trim();
+        if (line.starts_with("Ran ") && line.contains(" for "))
+            || (line.starts_with("Encountered ") && line.contains(" in "))
+        {
+            contract = line.rsplit_once(':').map(|(_, name)| name.to_string());
+            continue;
+        }
+        let Some(rest) = line.strip_prefix("[FAIL") else {
+            continue;
+        };
+        // The reason may contain brackets, e.g. the args of a counterexample.
+        let mut depth = 1;
+        let Some(end) = rest.find(|c| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => {}
             }
-            _ => unreachable!("Unexpected outcome after building stage"),
+            depth == 0
+        }) else {
+            continue;
+        };
+        let reason = rest[..end]
+            .trim_start_matches(". Reason:")
+            .trim_start_matches(':');
+        let reason = reason
+            .split_once("; counterexample:")
+            .or_else(|| reason.split_once(". Counterexample:"))
+            .map_or(reason, |(reason, _)| reason)
+            .trim();
+        let test = rest[end + 1..].trim();
+        let test = test.split_once('(').map_or(test, |(name, _)| name);
+        let name = match &contract {
+            Some(contract) => format!("{contract}::{test}"),
+            None => test.to_string(),
+        };
+        if !failed.iter().any(|f| f.name == name) {
+            failed.push(FailedTest {
+                name,
+                reason: (!reason.is_empty()).then(|| reason.to_string()),
+            });
         }
     }
+    failed
+}
 
-    ui::banner(Some("TEST PROJECTS (sequentially per project)"));
-    std::io::stdout()
-        .flush()
-        .wrap_err("Failed to flush stdout")?;
+/// A project left out of a run because some of its `required_env` variables aren't set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    pub name: String,
+    pub missing: Vec<String>,
+}
 
-    let mut final_results: Vec<Tested> = Vec::new();
-    // `TempDir` is dropped when it goes out of scope at the end of each iteration, or when consumed by `try_test_project`.
-    for built_project in successfully_built {
-        match try_test_project(built_project, num_test_runs, verbosity) {
-            ProjectState::Tested(tested) => final_results.push(tested),
-            ProjectState::Failed {
-                name, stage, error, ..
-            } => {
-                eprintln!("Project '{name}' failed at stage '{stage}': {error}");
-                failed_project_names.push(name);
-            }
-            _ => unreachable!("Unexpected outcome after testing stage"),
+/// Required environment variables of `project`, along with the endpoints forked by its `anvil`
+/// node and cached by its `rpc_cache`, that are set neither in its resolved env vars nor in the
+/// process environment. Empty values count as missing.
+pub fn missing_env(project: &ProjectConfig) -> Vec<String> {
+    let mut required: Vec<&String> = project.required_env.iter().collect();
+    for key in project
+        .anvil
+        .iter()
+        .map(|anvil| &anvil.fork_url_env)
+        .chain(project.rpc_cache.iter().map(|cache| &cache.url_env))
+    {
+        if !required.contains(&key) {
+            required.push(key);
         }
     }
+    required
+        .into_iter()
+        .filter(|key| project.env_value(key).is_none())
+        .cloned()
+        .collect()
+}
 
-    if !failed_project_names.is_empty() {
-        println!(
-            "\n{}",
-            Paint::yellow("Summary of projects that failed at some stage:").bold()
-        );
-        let unique_failed_names: std::collections::HashSet<&String> =
-            failed_project_names.into_iter().collect();
-        for name in unique_failed_names {
-            println!(" - {name}");
+/// Checks the required environment variables of `projects` before anything is cloned.
+///
+/// Errors with the missing variables of every project, unless `skip` is set, in which case the
+/// projects missing some are returned apart from the ones to benchmark.
+pub fn check_required_env(
+    projects: Vec<ProjectConfig>,
+    skip: bool,
+) -> Result<(Vec<ProjectConfig>, Vec<Skipped>)> {
+    let mut runnable = Vec::with_capacity(projects.len());
+    let mut skipped = Vec::new();
+    for project in projects {
+        let missing = missing_env(&project);
+        if missing.is_empty() {
+            runnable.push(project);
+        } else {
+            skipped.push(Skipped {
+                name: project.name,
+                missing,
+            });
         }
     }
+    if !skip && !skipped.is_empty() {
+        let list: Vec<String> = skipped
+            .iter()
+            .map(|s| format!(" - {}: {}", s.name, s.missing.join(", ")))
+            .collect();
+        return Err(eyre!(
+            "{} Missing required environment variables:\n{}\nSet them, or pass --skip-missing-env to skip these projects.",
+            Paint::red("ERROR:").bold(),
+            list.join("\n")
+        ));
+    }
+    Ok((runnable, skipped))
+}
 
-    Ok(final_results)
+/// A cloned project whose `.env.example` (or `.env.sample`) documents variables that aren't set,
+/// see [`ProjectConfig::require_env_example`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingEnvExample {
+    pub project: String,
+    /// Name of the file documenting the variables, e.g. `.env.example`.
+    pub file: String,
+    pub missing: Vec<String>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// The variables documented by the `.env.example` of the `cloned` projects that aren't set.
+fn missing_env_examples<'a>(
+    cloned: impl IntoIterator<Item = &'a Ready<'a>>,
+) -> Vec<MissingEnvExample> {
+    cloned
+        .into_iter()
+        .filter_map(|ready| ready.missing_env_example.clone())
+        .collect()
+}
+
+/// Outcome of [`run_pipeline`]: the results of the successfully tested projects, and the
+/// failures of the others.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PipelineResults {
+    pub tested: Vec<Tested>,
+    pub failures: Vec<Failure>,
+    /// Cloned projects documenting variables in their `.env.example` that aren't set, which
+    /// only warned (without `--require-env-example`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_env_examples: Vec<MissingEnvExample>,
+}
+
+/// Orchestrates the benchmark pipeline for a list of repository URLs.
+///
+/// Steps:
+///  1. Clone repositories from github (in parallel), see [`clone_projects`].
+///  2. Run `forge build` (in parallel), see [`build_projects`].
+///  3. Run `forge test` (sequentially), see [`test_projects`].
+///
+/// All `forge` invocations resolve to the binaries of the given `toolchain`, and `forge test`
+/// runs `num_test_runs` times per project with `verbosity` (the number of `-v`s). The progress is
+/// presented by `reporter`.
+///
+/// The pipeline stops after the `stop_after` stage. After the build, the built projects are
+/// returned without any test run (see [`Tested::runs`]), and after the clone no project is
+/// returned, only the failures.
+///
+/// A project failing at a stage is recorded in [`PipelineResults::failures`] and skips the next
+/// stages, while the others carry on. Errors are only returned if the pipeline itself fails.
+pub fn run_pipeline(
+    projects: &[ProjectConfig],
+    num_test_runs: usize,
+    verbosity: Verbosity,
+    stop_after: Stage,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Result<PipelineResults> {
+    if projects.is_empty() {
+        tracing::info!("No repository URLs provided to benchmark.");
+        return Ok(PipelineResults::default());
+    }
+
+    let (cloned, mut failures) = clone_projects(projects, runner, reporter);
+    let missing_env_examples = missing_env_examples(&cloned);
+    if stop_after == Stage::Clone {
+        for mut ready in cloned {
+            keep::passed(&mut ready._temp_dir);
+        }
+        return Ok(PipelineResults {
+            tested: Vec::new(),
+            failures,
+            missing_env_examples,
+        });
+    }
+
+    let (built, build_failures) = build_projects(cloned, toolchain, runner, reporter);
+    failures.extend(build_failures);
+    if stop_after == Stage::Build {
+        return Ok(PipelineResults {
+            tested: built
+                .into_iter()
+                .map(|built| Tested::new(built, Vec::new(), 0))
+                .collect(),
+            failures,
+            missing_env_examples,
+        });
+    }
+
+    let (tested, test_failures) =
+        test_projects(built, num_test_runs, verbosity, toolchain, runner, reporter)?;
+    failures.extend(test_failures);
+    Ok(PipelineResults {
+        tested,
+        failures,
+        missing_env_examples,
+    })
+}
+
+/// Clones every project into a temporary directory (in parallel).
+///
+/// Returns the cloned projects, and the failures of the others.
+pub fn clone_projects<'url>(
+    projects: &'url [ProjectConfig],
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> (Vec<Ready<'url>>, Vec<Failure>) {
+    reporter.step_started("CLONE PROJECTS (in parallel)");
+    let outcomes: Vec<ProjectState> = projects
+        .par_iter()
+        .map(|project| try_clone_project(project, runner, reporter))
+        .collect();
+
+    let mut cloned = Vec::new();
+    let mut failures = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            ProjectState::Cloned(ready) => cloned.push(ready),
+            ProjectState::Failed {
+                name, stage, error, ..
+            } => {
+                let failure = Failure::new(name, stage, error, Vec::new());
+                reporter.project_failed(&failure, None);
+                failures.push(failure);
+            }
+            _ => unreachable!("Unexpected outcome after cloning stage"),
+        }
+    }
+    (cloned, failures)
+}
+
+/// Sets up and runs `forge build` in every cloned project (in parallel), with `toolchain`.
+///
+/// Returns the built projects along with their build times, and the failures of the others.
+pub fn build_projects<'url>(
+    cloned: Vec<Ready<'url>>,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> (Vec<Built<'url>>, Vec<Failure>) {
+    reporter.step_started("BUILD PROJECTS (in parallel)");
+    let outcomes: Vec<ProjectState> = cloned
+        .into_par_iter()
+        .map(|ready| try_build_project(ready, toolchain, runner, reporter))
+        .collect();
+
+    let mut built = Vec::new();
+    let mut failures = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            ProjectState::Built(project) => built.push(project),
+            ProjectState::Failed {
+                name, stage, error, ..
+            } => {
+                let failure = Failure::new(name, stage, error, Vec::new());
+                reporter.project_failed(&failure, None);
+                failures.push(failure);
+            }
+            _ => unreachable!("Unexpected outcome after building stage"),
+        }
+    }
+    (built, failures)
+}
+
+/// Runs `forge test` `num_test_runs` times in every built project, one project after the other,
+/// with `toolchain`. The temporary directory of a project is removed once it's tested, unless it's
+/// kept with `--keep`.
+///
+/// Returns the tested projects, and the failures of the others.
+pub fn test_projects(
+    built: Vec<Built<'_>>,
+    num_test_runs: usize,
+    verbosity: Verbosity,
+    toolchain: &Toolchain,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Result<(Vec<Tested>, Vec<Failure>)> {
+    reporter.step_started("TEST PROJECTS (sequentially per project)");
+    std::io::stdout()
+        .flush()
+        .wrap_err("Failed to flush stdout")?;
+
+    let plan: Vec<(&ProjectConfig, usize)> = built
+        .iter()
+        .map(|built| {
+            (
+                built.state.config,
+                planned_runs(built.state.config, num_test_runs),
+            )
+        })
+        .collect();
+    reporter.tests_planned(&plan);
+
+    let mut tested = Vec::new();
+    let mut failures = Vec::new();
+    // `TempDir` is dropped when it goes out of scope at the end of each iteration, or when consumed by `try_test_project`.
+    for built_project in built {
+        match try_test_project(
+            built_project,
+            num_test_runs,
+            verbosity,
+            toolchain,
+            runner,
+            reporter,
+        ) {
+            ProjectState::Tested(project) => tested.push(project),
+            ProjectState::Failed {
+                name,
+                stage,
+                error,
+                failed_tests,
+            } => {
+                let failure = Failure::new(name, stage, error, failed_tests);
+                reporter.project_failed(&failure, None);
+                failures.push(failure);
+            }
+            _ => unreachable!("Unexpected outcome after testing stage"),
+        }
+    }
+    Ok((tested, failures))
+}
+
+/// Orchestrates the benchmark pipeline for several toolchains at once, interleaving their test runs.
+///
+/// Every project is cloned and built once per toolchain, and then its test runs alternate between
+/// the toolchains (ABAB...), so that slow drifts of the machine's performance (thermal throttling,
+/// background load, network caches) affect all of them alike.
+///
+/// Returns the outcome of each toolchain, in the same order as `toolchains`. Since the samples of a
+/// project are only meaningful when paired, a project that fails with any of the toolchains is
+/// dropped from all the results, and recorded as a failure of the toolchains it failed with.
+pub fn run_interleaved_pipeline(
+    projects: &[ProjectConfig],
+    num_test_runs: usize,
+    verbosity: Verbosity,
+    toolchains: &[Toolchain],
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Result<Vec<PipelineResults>> {
+    let mut final_results: Vec<PipelineResults> = toolchains
+        .iter()
+        .map(|_| PipelineResults::default())
+        .collect();
+    if projects.is_empty() {
+        tracing::info!("No repository URLs provided to benchmark.");
+        return Ok(final_results);
+    }
+
+    reporter.step_started("CLONE PROJECTS (in parallel, once per toolchain)");
+    let cloned_outcomes: Vec<Vec<ProjectState>> = projects
+        .par_iter()
+        .map(|project| {
+            toolchains
+                .iter()
+                .map(|_| try_clone_project(project, runner, reporter))
+                .collect()
+        })
+        .collect();
+
+    let mut successfully_cloned: Vec<Vec<Ready>> = Vec::new();
+    for outcomes in cloned_outcomes {
+        let mut cloned = Vec::with_capacity(toolchains.len());
+        for (side, outcome) in outcomes.into_iter().enumerate() {
+            match outcome {
+                ProjectState::Cloned(ready) => {
+                    final_results[side]
+                        .missing_env_examples
+                        .extend(ready.missing_env_example.clone());
+                    cloned.push(ready);
+                }
+                ProjectState::Failed {
+                    name, stage, error, ..
+                } => {
+                    let failure = Failure::new(name, stage, error, Vec::new());
+                    reporter.project_failed(&failure, None);
+                    final_results[side].failures.push(failure);
+                }
+                _ => unreachable!("Unexpected outcome after cloning stage"),
+            }
+        }
+        if cloned.len() == toolchains.len() {
+            successfully_cloned.push(cloned);
+        }
+    }
+
+    reporter.step_started("BUILD PROJECTS (in parallel, once per toolchain)");
+    let built_outcomes: Vec<Vec<ProjectState>> = successfully_cloned
+        .into_par_iter()
+        .map(|cloned| {
+            cloned
+                .into_iter()
+                .zip(toolchains)
+                .map(|(ready, toolchain)| try_build_project(ready, toolchain, runner, reporter))
+                .collect()
+        })
+        .collect();
+
+    let mut successfully_built: Vec<Vec<Built>> = Vec::new();
+    for outcomes in built_outcomes {
+        let mut built = Vec::with_capacity(toolchains.len());
+        for (side, outcome) in outcomes.into_iter().enumerate() {
+            match outcome {
+                ProjectState::Built(b) => built.push(b),
+                ProjectState::Failed {
+                    name, stage, error, ..
+                } => {
+                    let failure = Failure::new(name, stage, error, Vec::new());
+                    reporter.project_failed(&failure, None);
+                    final_results[side].failures.push(failure);
+                }
+                _ => unreachable!("Unexpected outcome after building stage"),
+            }
+        }
+        if built.len() == toolchains.len() {
+            successfully_built.push(built);
+        }
+    }
+
+    reporter.step_started("TEST PROJECTS (interleaving toolchains per run)");
+    std::io::stdout()
+        .flush()
+        .wrap_err("Failed to flush stdout")?;
+    let plan: Vec<(&ProjectConfig, usize)> = successfully_built
+        .iter()
+        .map(|built| {
+            let config = built[0].state.config;
+            (
+                config,
+                planned_runs(config, num_test_runs) * toolchains.len(),
+            )
+        })
+        .collect();
+    reporter.tests_planned(&plan);
+
+    'projects: for built in successfully_built {
+        let config = built[0].state.config;
+        if config.build_only {
+            tracing::info!("Skipping the test runs of {} (build only)", config.name);
+            for (results, built_state) in final_results.iter_mut().zip(built) {
+                results.tested.push(Tested::new(built_state, Vec::new(), 0));
+            }
+            continue;
+        }
+        let args = run_args(config, verbosity);
+        // Every toolchain runs the tests against its own node.
+        let mut test_toolchains = Vec::with_capacity(toolchains.len());
+        for (side, toolchain) in toolchains.iter().enumerate() {
+            match start_test_services(config, toolchain, runner) {
+                Ok(started) => test_toolchains.push(started),
+                Err(error) => {
+                    let failure = Failure::new(&config.name, "test", error, Vec::new());
+                    reporter.project_failed(&failure, None);
+                    final_results[side].failures.push(failure);
+                    continue 'projects;
+                }
+            }
+        }
+        let mut samples: Vec<Vec<RunTime>> = toolchains
+            .iter()
+            .map(|_| Vec::with_capacity(num_test_runs))
+            .collect();
+        for i in 0..num_test_runs {
+            for (side, (built_state, (_, toolchain))) in
+                built.iter().zip(&test_toolchains).enumerate()
+            {
+                if let Some(cmd) = &config.prepare
+                    && let Err(error) = run_hook(built_state, "prepare", cmd, runner)
+                {
+                    let failure = Failure::new(&config.name, "prepare", error, Vec::new());
+                    reporter.project_failed(&failure, None);
+                    final_results[side].failures.push(failure);
+                    continue 'projects;
+                }
+                let run = try_test_run(
+                    built_state,
+                    &args,
+                    toolchain,
+                    i,
+                    num_test_runs,
+                    runner,
+                    reporter,
+                );
+                let concluded = match &config.conclude {
+                    Some(cmd) => run_hook(built_state, "conclude", cmd, runner),
+                    None => Ok(()),
+                };
+                let failure = match (run, concluded) {
+                    (Ok(time), Ok(())) => {
+                        samples[side].push(time);
+                        continue;
+                    }
+                    (Err((error, failed_tests)), _) => {
+                        Failure::new(&config.name, "test", error, failed_tests)
+                    }
+                    (Ok(_), Err(error)) => {
+                        Failure::new(&config.name, "conclude", error, Vec::new())
+                    }
+                };
+                reporter.project_failed(&failure, None);
+                final_results[side].failures.push(failure);
+                continue 'projects;
+            }
+        }
+
+        // The script runs alternate between the toolchains too, once every test run passed.
+        let mut script_samples: Vec<Vec<f64>> = toolchains.iter().map(|_| Vec::new()).collect();
+        if let Some(args) = script_args(config) {
+            for i in 0..num_test_runs {
+                for (side, (built_state, (_, toolchain))) in
+                    built.iter().zip(&test_toolchains).enumerate()
+                {
+                    match try_script_run(
+                        built_state,
+                        &args,
+                        toolchain,
+                        i,
+                        num_test_runs,
+                        runner,
+                        reporter,
+                    ) {
+                        Ok(elapsed) => script_samples[side].push(elapsed),
+                        Err(error) => {
+                            let failure = Failure::new(&config.name, "script", error, Vec::new());
+                            reporter.project_failed(&failure, None);
+                            final_results[side].failures.push(failure);
+                            continue 'projects;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (((results, built_state), test_times), script_times) in final_results
+            .iter_mut()
+            .zip(built)
+            .zip(samples)
+            .zip(script_samples)
+        {
+            results.tested.push(
+                Tested::from_runs(built_state, test_times, num_test_runs, verbosity)
+                    .with_script_times(script_times),
+            );
+        }
+    }
+
+    Ok(final_results)
+}
+
+/// Orchestrates the benchmark pipeline for several variants of the same project setup, e.g. the
+/// `forge` in the user's `PATH` with different settings.
+///
+/// Every project is cloned once, and each variant builds and tests a fresh copy of the clone, so
+/// that the variants run the exact same sources without re-cloning them.
+///
+/// Returns the outcome of each variant, in the same order as `variants`. Unlike
+/// [`run_interleaved_pipeline`], a project that fails with a variant is still reported with the
+/// others.
+pub fn run_matrix_pipeline(
+    projects: &[ProjectConfig],
+    num_test_runs: usize,
+    verbosity: Verbosity,
+    variants: &[(&str, Toolchain)],
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Result<Vec<PipelineResults>> {
+    let mut results: Vec<PipelineResults> = variants
+        .iter()
+        .map(|_| PipelineResults::default())
+        .collect();
+    if projects.is_empty() {
+        tracing::info!("No repository URLs provided to benchmark.");
+        return Ok(results);
+    }
+
+    reporter.step_started("CLONE PROJECTS (in parallel, once for all variants)");
+    let cloned_outcomes: Vec<ProjectState> = projects
+        .par_iter()
+        .map(|project| try_clone_project(project, runner, reporter))
+        .collect();
+
+    let mut clones: Vec<Ready> = Vec::new();
+    for outcome in cloned_outcomes {
+        match outcome {
+            ProjectState::Cloned(cloned) => clones.push(cloned),
+            ProjectState::Failed {
+                name, stage, error, ..
+            } => {
+                let failure = Failure::new(name, stage, error, Vec::new());
+                reporter.project_failed(&failure, None);
+                for variant in &mut results {
+                    variant.failures.push(failure.clone());
+                }
+            }
+            _ => unreachable!("Unexpected outcome after cloning stage"),
+        }
+    }
+    let missing = missing_env_examples(&clones);
+    for variant in &mut results {
+        variant.missing_env_examples = missing.clone();
+    }
+
+    for ((variant, toolchain), variant_results) in variants.iter().zip(&mut results) {
+        reporter.step_started(&format!("BUILD PROJECTS (in parallel, variant {variant})"));
+        let built_outcomes: Vec<ProjectState> = clones
+            .par_iter()
+            .map(|clone| match try_copy_clone(clone) {
+                ProjectState::Cloned(ready) => {
+                    try_build_project(ready, toolchain, runner, reporter)
+                }
+                failed => failed,
+            })
+            .collect();
+
+        let mut successfully_built: Vec<Built> = Vec::new();
+        for outcome in built_outcomes {
+            match outcome {
+                ProjectState::Built(built) => successfully_built.push(built),
+                ProjectState::Failed {
+                    name, stage, error, ..
+                } => {
+                    let failure = Failure::new(name, stage, error, Vec::new());
+                    reporter.project_failed(&failure, Some(variant));
+                    variant_results.failures.push(failure);
+                }
+                _ => unreachable!("Unexpected outcome after building stage"),
+            }
+        }
+
+        reporter.step_started(&format!(
+            "TEST PROJECTS (sequentially per project, variant {variant})"
+        ));
+        std::io::stdout()
+            .flush()
+            .wrap_err("Failed to flush stdout")?;
+        let plan: Vec<(&ProjectConfig, usize)> = successfully_built
+            .iter()
+            .map(|built| {
+                (
+                    built.state.config,
+                    planned_runs(built.state.config, num_test_runs),
+                )
+            })
+            .collect();
+        reporter.tests_planned(&plan);
+
+        for built_project in successfully_built {
+            match try_test_project(
+                built_project,
+                num_test_runs,
+                verbosity,
+                toolchain,
+                runner,
+                reporter,
+            ) {
+                ProjectState::Tested(tested) => variant_results.tested.push(tested),
+                ProjectState::Failed {
+                    name,
+                    stage,
+                    error,
+                    failed_tests,
+                } => {
+                    let failure = Failure::new(name, stage, error, failed_tests);
+                    reporter.project_failed(&failure, Some(variant));
+                    variant_results.failures.push(failure);
+                }
+                _ => unreachable!("Unexpected outcome after testing stage"),
+            }
+        }
+    }
+    for clone in &mut clones {
+        keep::passed(&mut clone._temp_dir);
+    }
+
+    Ok(results)
+}
+
+/// Attempts to copy a cloned project into a new temporary directory, to be built from scratch.
+fn try_copy_clone<'url>(clone: &Ready<'url>) -> ProjectState<'url> {
+    let config = clone.config;
+    let copied = project_dir(config).and_then(|temp_dir| {
+        copy_dir(&clone.path, temp_dir.path())?;
+        Ok(temp_dir)
+    });
+    match copied {
+        Ok(temp_dir) => ProjectState::Cloned(Ready {
+            config,
+            path: temp_dir.path().to_path_buf(),
+            unpinned_fork: clone.unpinned_fork,
+            missing_env_example: clone.missing_env_example.clone(),
+            commit: clone.commit.clone(),
+            _temp_dir: temp_dir,
+        }),
+        Err(e) => {
+            let error_msg = format!("Failed to copy the clone of {}. Error: {e:?}", config.name);
+            let _stage =
+                tracing::info_span!(parent: &project_span(config), "clone", stage = "clone")
+                    .entered();
+            tracing::error!("{} {}", Paint::red("ERROR:").bold(), error_msg);
+            ProjectState::Failed {
+                name: &config.name,
+                stage: "clone",
+                error: error_msg,
+                failed_tests: Vec::new(),
+            }
+        }
+    }
+}
+
+/// Recursively copies the contents of `from` into the existing directory `to`, preserving
+/// symlinks.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&entry.path(), file_type, &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(link: &Path, _: fs::FileType, target: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(link)?, target)
+}
+
+#[cfg(windows)]
+fn copy_symlink(link: &Path, file_type: fs::FileType, target: &Path) -> std::io::Result<()> {
+    use std::os::windows::fs::{FileTypeExt, symlink_dir, symlink_file};
+    if file_type.is_symlink_dir() {
+        symlink_dir(fs::read_link(link)?, target)
+    } else {
+        symlink_file(fs::read_link(link)?, target)
+    }
+}
+
+/// Prints the projects skipped with `--skip-missing-env`, along with their missing variables.
+pub fn log_skipped_projects(skipped: &[Skipped]) {
+    if !skipped.is_empty() && ui::logs(LogLevel::Warn) {
+        println!(
+            "\n{}",
+            Paint::yellow("Summary of projects skipped for missing environment variables:").bold()
+        );
+        for project in skipped {
+            println!(
+                " - {} (missing {})",
+                project.name,
+                project.missing.join(", ")
+            );
+        }
+    }
+}
+
+/// Prints the projects documenting variables in their `.env.example` that aren't set, along with
+/// the variables. A project found several times (e.g. with several sources) is listed once.
+pub fn log_missing_env_examples<'a>(findings: impl IntoIterator<Item = &'a MissingEnvExample>) {
+    let mut projects: BTreeMap<&str, &MissingEnvExample> = BTreeMap::new();
+    for finding in findings {
+        projects.entry(&finding.project).or_insert(finding);
+    }
+    if !projects.is_empty() && ui::logs(LogLevel::Warn) {
+        println!(
+            "\n{}",
+            Paint::yellow("Summary of projects missing variables of their .env.example:").bold()
+        );
+        for (name, finding) in projects {
+            println!(
+                " - {name} (missing {} from {})",
+                finding.missing.join(", "),
+                finding.file
+            );
+        }
+    }
+}
+
+/// Prints the projects that failed at some stage, along with the stage and a summary of their
+/// failing tests. A project failing several times (e.g. with several sources) is listed once,
+/// with its first failure.
+pub fn log_failed_projects<'a>(failures: impl IntoIterator<Item = &'a Failure>) {
+    let mut projects: BTreeMap<&str, &Failure> = BTreeMap::new();
+    for failure in failures {
+        projects.entry(&failure.project).or_insert(failure);
+    }
+    if !projects.is_empty() && ui::logs(LogLevel::Warn) {
+        println!(
+            "\n{}",
+            Paint::yellow("Summary of projects that failed at some stage:").bold()
+        );
+        for (name, failure) in projects {
+            if failure.failed_tests.is_empty() {
+                println!(" - {name} ({})", failure.stage);
+            } else {
+                println!(
+                    " - {name} ({}): {}",
+                    failure.stage,
+                    failed_tests_summary(&failure.failed_tests)
+                );
+            }
+            for dir in keep::kept_dirs(name) {
+                println!("   kept in {}", dir.display());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroUsize;
+
+    use crate::{
+        runner::{Response, ScriptedRunner, SystemRunner},
+        utils::{AnvilConfig, RpcCacheConfig},
+    };
+
+    #[test]
+    fn test_check_required_env() {
+        // SAFETY: the variable is only used by this test.
+        unsafe { std::env::set_var("FOUNDRY_BENCHMARKS_TEST_REQUIRED_SET", "1") };
+        let project = |name: &str, required: &[&str]| {
+            let mut project = ProjectConfig::new(name)
+                .with_env_vars(vec!["CONFIGURED", "EMPTY"], vec!["value", ""]);
+            project.required_env = required.iter().map(|key| key.to_string()).collect();
+            project
+        };
+        let projects = vec![
+            project(
+                "a/a",
+                &["CONFIGURED", "FOUNDRY_BENCHMARKS_TEST_REQUIRED_SET"],
+            ),
+            project(
+                "b/b",
+                &[
+                    "EMPTY",
+                    "CONFIGURED",
+                    "FOUNDRY_BENCHMARKS_TEST_REQUIRED_UNSET",
+                ],
+            ),
+            project("c/c", &[]),
+        ];
+        assert_eq!(
+            missing_env(&projects[1]),
+            ["EMPTY", "FOUNDRY_BENCHMARKS_TEST_REQUIRED_UNSET"]
+        );
+        // The endpoint forked by anvil is required too.
+        let forking = projects[2].clone().with_anvil(AnvilConfig {
+            fork_url_env: "FOUNDRY_BENCHMARKS_TEST_REQUIRED_UNSET".to_string(),
+            port: 0,
+        });
+        assert_eq!(
+            missing_env(&forking),
+            ["FOUNDRY_BENCHMARKS_TEST_REQUIRED_UNSET"]
+        );
+        // So is the endpoint cached by the proxy, listed once if anvil forks it.
+        let cached = forking.with_rpc_cache(RpcCacheConfig {
+            url_env: "FOUNDRY_BENCHMARKS_TEST_REQUIRED_UNSET".to_string(),
+        });
+        assert_eq!(
+            missing_env(&cached),
+            ["FOUNDRY_BENCHMARKS_TEST_REQUIRED_UNSET"]
+        );
+
+        let err = check_required_env(projects.clone(), false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(" - b/b: EMPTY, FOUNDRY_BENCHMARKS_TEST_REQUIRED_UNSET\n")
+        );
+
+        let (runnable, skipped) = check_required_env(projects, true).unwrap();
+        let names: Vec<_> = runnable.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["a/a", "c/c"]);
+        assert_eq!(
+            skipped,
+            [Skipped {
+                name: "b/b".to_string(),
+                missing: vec![
+                    "EMPTY".to_string(),
+                    "FOUNDRY_BENCHMARKS_TEST_REQUIRED_UNSET".to_string()
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_source_branch() {
+        let branch_name = String::from("feature-branch");
+        let source = Source::Branch(branch_name.clone());
+
+        assert_eq!(source.short(), Some("-b"));
+        assert_eq!(source.ty(), "branch");
+        assert_eq!(source.name(), "feature-branch");
+        assert_eq!(
+            source.github_url("owner/repo"),
+            "https://github.com/owner/repo/tree/feature-branch"
+        );
+    }
+
+    #[test]
+    fn test_source_commit() {
+        let sha = String::from("a1b2c3d");
+        let source = Source::Commit(sha.clone());
+
+        assert_eq!(source.short(), Some("-C"));
+        assert_eq!(source.ty(), "commit");
+        assert_eq!(source.name(), "a1b2c3d");
+        assert_eq!(
+            source.github_url("owner/repo"),
+            "https://github.com/owner/repo/commit/a1b2c3d"
+        );
+    }
+
+    #[test]
+    fn test_source_version() {
+        let version_name = String::from("v1.2.3");
+        let source = Source::Version(version_name.clone());
+
+        assert_eq!(source.short(), Some("-v"));
+        assert_eq!(source.ty(), "version");
+        assert_eq!(source.name(), "v1.2.3");
+        assert_eq!(
+            source.github_url("owner/repo"),
+            "https://github.com/owner/repo/releases/tag/v1.2.3"
+        );
+    }
+
+    #[test]
+    fn test_source_env() {
+        let source = Source::env(BTreeMap::from([
+            ("FOUNDRY_FUZZ_RUNS".to_string(), "1024".to_string()),
+            ("FOUNDRY_FFI".to_string(), "true".to_string()),
+        ]));
+
+        assert_eq!(source.short(), None);
+        assert_eq!(source.ty(), "env");
+        assert_eq!(source.name(), "FOUNDRY_FFI=true FOUNDRY_FUZZ_RUNS=1024");
+        assert_eq!(
+            source.github_url("owner/repo"),
+            "https://github.com/owner/repo"
+        );
+
+        assert_eq!(Source::env(BTreeMap::new()).name(), "default env");
+    }
+
+    #[test]
+    fn test_source_recorded() {
+        let path = String::from("baseline.json");
+        let source = Source::Recorded {
+            path: path.clone(),
+            forge: None,
+        };
+
+        assert_eq!(source.short(), None);
+        assert_eq!(source.ty(), "recorded");
+        assert_eq!(source.name(), "baseline.json");
+        assert_eq!(
+            source.github_url("owner/repo"),
+            "https://github.com/owner/repo"
+        );
+
+        let source = Source::Recorded {
+            path: path.clone(),
+            forge: Some(ForgeVersion {
+                version: "1.2.3-stable".to_string(),
+                commit: Some("abcdef".to_string()),
+            }),
+        };
+        assert_eq!(source.name(), "1.2.3-stable");
+        assert_eq!(
+            source.github_url("owner/repo"),
+            "https://github.com/owner/repo/commit/abcdef"
+        );
+    }
+
+    #[test]
+    fn test_channel_source_results() {
+        let sha = "a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2";
+        let results = |source: Source, version: &str, label: Option<&str>| SourceResults {
+            source,
+            foundry_repo: "owner/repo".to_string(),
+            label: label.map(String::from),
+            forge: Some(ForgeVersion {
+                version: version.to_string(),
+                commit: Some(sha.to_string()),
+            }),
+            tests: Vec::new(),
+        };
+
+        let stable = results(Source::Version("stable".to_string()), "1.2.3-stable", None);
+        assert_eq!(stable.display_name(), "stable (v1.2.3, a1b2c3d)");
+        assert_eq!(
+            stable.github_url(),
+            "https://github.com/owner/repo/releases/tag/v1.2.3"
+        );
+        let nightly = results(
+            Source::Version("nightly".to_string()),
+            "1.3.0-nightly",
+            None,
+        );
+        assert_eq!(nightly.display_name(), "nightly (1.3.0-nightly, a1b2c3d)");
+        assert_eq!(
+            nightly.github_url(),
+            format!("https://github.com/owner/repo/releases/tag/nightly-{sha}")
+        );
+        let dev = results(Source::Version("nightly".to_string()), "1.3.0-dev", None);
+        assert_eq!(
+            dev.github_url(),
+            format!("https://github.com/owner/repo/commit/{sha}")
+        );
+
+        // Labels, and the sources that don't move, are reported as is.
+        let labelled = results(
+            Source::Version("stable".to_string()),
+            "1.2.3-stable",
+            Some("Old"),
+        );
+        assert_eq!(labelled.display_name(), "Old");
+        // So is the current install, whatever it is.
+        let current = results(Source::Current { forge: None }, "1.2.3-stable", None);
+        assert_eq!(current.display_name(), "installed (v1.2.3, a1b2c3d)");
+
+        let pinned = results(Source::Version("v1.2.3".to_string()), "1.2.3-stable", None);
+        assert_eq!(pinned.display_name(), "v1.2.3");
+        assert_eq!(
+            pinned.github_url(),
+            "https://github.com/owner/repo/releases/tag/v1.2.3"
+        );
+    }
+
+    #[test]
+    fn test_source_from_str() {
+        for s in [
+            "version:v1.2.0",
+            "branch:feat/faster-fuzz",
+            "commit:abcdef",
+            "recorded:out/baseline.json",
+            "env:FOUNDRY_FUZZ_RUNS=512,FOUNDRY_OPTIMIZER=false",
+            "current:",
+        ] {
+            let source: Source = s.parse().unwrap();
+            assert_eq!(source.to_string(), s);
+        }
+        assert_eq!(
+            "branch:master".parse::<Source>().unwrap(),
+            Source::Branch("master".to_string())
+        );
+        let Source::Env { label, vars } = "env:FOUNDRY_FUZZ_RUNS=512".parse().unwrap() else {
+            panic!("Expected env source");
+        };
+        assert_eq!(label, "FOUNDRY_FUZZ_RUNS=512");
+        assert_eq!(vars["FOUNDRY_FUZZ_RUNS"], "512");
+        assert_eq!("env:".parse::<Source>().unwrap().name(), "default env");
+
+        for invalid in [
+            "master",
+            "branch:",
+            "tag:v1",
+            "env:FOUNDRY_FUZZ_RUNS",
+            "current:stable",
+        ] {
+            assert!(invalid.parse::<Source>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_source_serde() {
+        let sources = [
+            Source::Version("v1.2.0".to_string()),
+            Source::Commit("abcdef".to_string()),
+            Source::env(BTreeMap::from([(
+                "FOUNDRY_FUZZ_RUNS".to_string(),
+                "512".to_string(),
+            )])),
+            Source::Recorded {
+                path: "baseline.json".to_string(),
+                forge: Some(ForgeVersion {
+                    version: "1.2.3-stable".to_string(),
+                    commit: None,
+                }),
+            },
+        ];
+        for source in sources {
+            let json = serde_json::to_string(&source).unwrap();
+            assert_eq!(serde_json::from_str::<Source>(&json).unwrap(), source);
+        }
+        assert_eq!(
+            serde_json::to_string(&Source::Branch("master".to_string())).unwrap(),
+            r#"{"branch":"master"}"#
+        );
+
+        // Secrets are redacted, like in the saved results.
+        let vars = BTreeMap::from([(
+            "FOUNDRY_BENCHMARKS_TEST_API_KEY".to_string(),
+            "0123456789abcdef".to_string(),
+        )]);
+        let source = Source::env(vars);
+        assert!(
+            !serde_json::to_string(&source)
+                .unwrap()
+                .contains("0123456789abcdef")
+        );
+        assert!(!source.to_string().contains("0123456789abcdef"));
+    }
 
     #[test]
-    fn test_source_branch() {
-        let branch_name = String::from("feature-branch");
-        let source = Source::Branch(&branch_name);
-        
-        assert_eq!(source.short(), "-b");
-        assert_eq!(source.ty(), "branch");
-        assert_eq!(source.name(), "feature-branch");
-        assert_eq!(source.github_url("owner/repo"), "https://github.com/owner/repo/tree/feature-branch");
+    fn test_test_args() {
+        assert_eq!(test_args(0, &[]), ["test"]);
+        assert_eq!(test_args(3, &[]), ["test", "-vvv"]);
+        assert_eq!(
+            test_args(2, &["--fuzz-runs".to_string(), "1024".to_string()]),
+            ["test", "-vv", "--fuzz-runs", "1024"]
+        );
     }
 
     #[test]
-    fn test_source_version() {
-        let version_name = String::from("v1.2.3");
-        let source = Source::Version(&version_name);
-        
-        assert_eq!(source.short(), "-v");
-        assert_eq!(source.ty(), "version");
-        assert_eq!(source.name(), "v1.2.3");
-        assert_eq!(source.github_url("owner/repo"), "https://github.com/owner/repo/releases/tag/v1.2.3");
+    fn test_parse_failed_tests() {
+        let output = "\
+Ran 3 tests for test/Counter.t.sol:CounterTest
+[PASS] test_set() (gas: 31303)
+[FAIL: revert: boom] test_increment() (gas: 8351)
+[FAIL: assertion failed; counterexample: calldata=0x01 args=[1]] testFuzz_add(uint256) (runs: 3, \u{3bc}: 0, ~: 0)
+Suite result: FAILED. 1 passed; 2 failed; 0 skipped; finished in 1.20ms
+
+Ran 1 test for test/Vault.t.sol:VaultTest
+[FAIL. Reason: EvmError: Revert] setUp() (gas: 0)
+
+Failing tests:
+Encountered 2 failing tests in test/Counter.t.sol:CounterTest
+[FAIL: revert: boom] test_increment() (gas: 8351)
+[FAIL: assertion failed; counterexample: calldata=0x01 args=[1]] testFuzz_add(uint256) (runs: 3, \u{3bc}: 0, ~: 0)
+Encountered 1 failing test in test/Vault.t.sol:VaultTest
+[FAIL] test_withdraw() (gas: 1234)
+
+Encountered a total of 3 failing tests, 1 tests succeeded
+";
+        let failed = |name: &str, reason: Option<&str>| FailedTest {
+            name: name.to_string(),
+            reason: reason.map(str::to_string),
+        };
+        assert_eq!(
+            parse_failed_tests(output),
+            [
+                failed("CounterTest::test_increment", Some("revert: boom")),
+                failed("CounterTest::testFuzz_add", Some("assertion failed")),
+                failed("VaultTest::setUp", Some("EvmError: Revert")),
+                failed("VaultTest::test_withdraw", None),
+            ]
+        );
+        assert!(parse_failed_tests("Error: Compiler run failed").is_empty());
+    }
+
+    #[test]
+    fn test_failed_tests_summary() {
+        let tests: Vec<FailedTest> = (0..7)
+            .map(|i| FailedTest {
+                name: format!("C::test_{i}"),
+                reason: (i == 0).then(|| "revert: boom".to_string()),
+            })
+            .collect();
+        assert_eq!(
+            failed_tests_summary(&tests[..1]),
+            "1 test failed: C::test_0 (revert: boom)"
+        );
+        assert_eq!(
+            failed_tests_summary(&tests),
+            "7 tests failed: C::test_0 (revert: boom), C::test_1, C::test_2, C::test_3, C::test_4, …"
+        );
     }
 
     #[test]
@@ -542,21 +3328,29 @@ mod tests {
         let ready = Ready {
             config: &config,
             path: PathBuf::from("/tmp/test"),
+            unpinned_fork: false,
+            missing_env_example: None,
+            commit: None,
             _temp_dir: tempfile::tempdir().unwrap(),
         };
         let built = Built {
             state: ready,
             build_time: 5.5,
+            discovery_time: None,
+            build_warnings: None,
+            incremental_samples: Vec::new(),
+            config_fingerprint: None,
         };
-        
+
         let test_times = vec![1.0, 2.0, 3.0];
         let tested = Tested::new(built, test_times, 3);
-        
+
         assert_eq!(tested.name, "test/repo");
         assert_eq!(tested.url, "https://github.com/test/repo");
         assert_eq!(tested.build_time, 5.5);
         assert_eq!(tested.avg_test_time, 2.0); // (1.0 + 2.0 + 3.0) / 3
         assert_eq!(tested.runs, 3);
+        assert_eq!(tested.samples, [1.0, 2.0, 3.0]);
     }
 
     #[test]
@@ -565,17 +3359,1152 @@ mod tests {
         let ready = Ready {
             config: &config,
             path: PathBuf::from("/tmp/test"),
+            unpinned_fork: false,
+            missing_env_example: None,
+            commit: None,
             _temp_dir: tempfile::tempdir().unwrap(),
         };
         let built = Built {
             state: ready,
             build_time: 5.5,
+            discovery_time: None,
+            build_warnings: None,
+            incremental_samples: Vec::new(),
+            config_fingerprint: None,
         };
-        
+
         let test_times = vec![];
         let tested = Tested::new(built, test_times, 0);
-        
+
         assert_eq!(tested.avg_test_time, 0.0);
         assert_eq!(tested.runs, 0);
     }
+
+    /// Records the events of a pipeline, e.g. `a/a: build finished`.
+    #[derive(Default)]
+    struct RecordingReporter(std::sync::Mutex<Vec<String>>);
+
+    impl RecordingReporter {
+        fn record(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+
+        /// The events about `project`, in order.
+        fn of(&self, project: &str) -> Vec<String> {
+            let prefix = format!("{project}: ");
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|event| event.strip_prefix(&prefix).map(str::to_string))
+                .collect()
+        }
+    }
+
+    impl Reporter for RecordingReporter {
+        fn step_started(&self, title: &str) {
+            self.record(format!("step: {title}"));
+        }
+
+        fn project_stage_started(&self, project: &ProjectConfig, stage: &str, _path: &Path) {
+            self.record(format!("{}: {stage} started", project.name));
+        }
+
+        fn project_stage_finished(&self, project: &ProjectConfig, stage: &str, _duration: f64) {
+            self.record(format!("{}: {stage} finished", project.name));
+        }
+
+        fn tests_planned(&self, plan: &[(&ProjectConfig, usize)]) {
+            for (project, runs) in plan {
+                self.record(format!("{}: {runs} runs planned", project.name));
+            }
+        }
+
+        fn test_run_started(&self, project: &ProjectConfig, run: usize, num_runs: usize) {
+            self.record(format!(
+                "{}: test run {}/{num_runs} started",
+                project.name,
+                run + 1
+            ));
+        }
+
+        fn test_run_completed(&self, project: &ProjectConfig, run: usize, _duration: f64) {
+            self.record(format!("{}: test run {} completed", project.name, run + 1));
+        }
+
+        fn script_run_started(&self, project: &ProjectConfig, run: usize, num_runs: usize) {
+            self.record(format!(
+                "{}: script run {}/{num_runs} started",
+                project.name,
+                run + 1
+            ));
+        }
+
+        fn script_run_completed(&self, project: &ProjectConfig, run: usize, _duration: f64) {
+            self.record(format!(
+                "{}: script run {} completed",
+                project.name,
+                run + 1
+            ));
+        }
+
+        fn project_tested(&self, tested: &Tested) {
+            self.record(format!("{}: tested", tested.name));
+        }
+
+        fn project_failed(&self, failure: &Failure, _variant: Option<&str>) {
+            self.record(format!("{}: failed at {}", failure.project, failure.stage));
+        }
+    }
+
+    #[test]
+    fn test_pipeline_events() {
+        let dir = TempDir::new().unwrap();
+        // A `forge` whose tests fail in the projects holding a `fail` file.
+        let bin = dir.path().join("toolchain/bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(
+            bin.join("forge"),
+            "#!/bin/sh\nif [ \"$1\" = test ] && [ -f fail ]; then\n  echo 'Ran 1 test for test/A.t.sol:ATest'\n  echo '[FAIL: revert: boom] test_a() (gas: 1)'\n  exit 1\nfi\n",
+        )
+        .unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(bin.join("forge"), fs::Permissions::from_mode(0o755)).unwrap();
+        let toolchain = Toolchain::isolated(dir.path().join("toolchain"));
+
+        let project = |name: &str, fail: bool| {
+            let repo = dir.path().join(name.replace('/', "-"));
+            fs::create_dir_all(&repo).unwrap();
+            if fail {
+                fs::write(repo.join("fail"), "").unwrap();
+            }
+            for args in [
+                &["init", "--quiet"][..],
+                &["add", "."],
+                &["commit", "--quiet", "--allow-empty", "-m", "init"],
+            ] {
+                let status = Command::new("git")
+                    .arg("-C")
+                    .arg(&repo)
+                    .args(["-c", "user.name=test", "-c", "user.email=test@test"])
+                    .args(args)
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            }
+            let mut project = ProjectConfig::new(name);
+            project.config.url = Some(repo.to_string_lossy().into_owned());
+            project
+        };
+        let projects = [
+            project("a/a", false),
+            project("b/b", true),
+            ProjectConfig::new("/does/not/exist"),
+        ];
+
+        let reporter = RecordingReporter::default();
+        let pipeline = run_pipeline(
+            &projects,
+            2,
+            0,
+            Stage::Test,
+            &toolchain,
+            &SystemRunner,
+            &reporter,
+        )
+        .unwrap();
+        assert_eq!(pipeline.tested.len(), 1);
+        assert_eq!(pipeline.tested[0].samples.len(), 2);
+        assert_eq!(
+            reporter.of("step"),
+            [
+                "CLONE PROJECTS (in parallel)",
+                "BUILD PROJECTS (in parallel)",
+                "TEST PROJECTS (sequentially per project)"
+            ]
+        );
+        assert_eq!(
+            reporter.of("a/a"),
+            [
+                "clone started",
+                "clone finished",
+                "build started",
+                "build finished",
+                "2 runs planned",
+                "test run 1/2 started",
+                "test run 1 completed",
+                "test run 2/2 started",
+                "test run 2 completed",
+                "tested",
+            ]
+        );
+        assert_eq!(
+            reporter.of("b/b"),
+            [
+                "clone started",
+                "clone finished",
+                "build started",
+                "build finished",
+                "2 runs planned",
+                "test run 1/2 started",
+                "failed at test",
+            ]
+        );
+        assert_eq!(
+            reporter.of("/does/not/exist"),
+            ["clone started", "failed at clone"]
+        );
+        assert_eq!(
+            pipeline.failures[1].failed_tests,
+            [FailedTest {
+                name: "ATest::test_a".to_string(),
+                reason: Some("revert: boom".to_string()),
+            }]
+        );
+    }
+
+    /// The commands run by `runner`, with the temporary directory of `git clone` elided.
+    fn commands(runner: &ScriptedRunner) -> Vec<String> {
+        runner
+            .calls()
+            .into_iter()
+            .map(|call| match call.strip_prefix("git clone ") {
+                Some(args) => format!("git clone {} <dir>", args.rsplit_once(' ').unwrap().0),
+                None => call,
+            })
+            .collect()
+    }
+
+    /// Runs the pipeline of the `a/a` project with `runner` until `stop_after`, returning its
+    /// results and events.
+    fn run_scripted(
+        project: ProjectConfig,
+        num_runs: usize,
+        stop_after: Stage,
+        runner: &ScriptedRunner,
+    ) -> (PipelineResults, Vec<String>) {
+        let reporter = RecordingReporter::default();
+        let pipeline = run_pipeline(
+            &[project],
+            num_runs,
+            3,
+            stop_after,
+            &Toolchain::default(),
+            runner,
+            &reporter,
+        )
+        .unwrap();
+        (pipeline, reporter.of("a/a"))
+    }
+
+    #[test]
+    fn test_scripted_pipeline() {
+        let runner = ScriptedRunner::default()
+            .on(
+                "git rev-parse HEAD",
+                [Response::ok("0123abc0123abc0123abc0123abc0123abc0123a\n")],
+            )
+            .on(
+                "forge config --json",
+                [Response::ok(r#"{"optimizer":false,"fuzz":{"runs":1000}}"#)],
+            );
+        let project = ProjectConfig::new("a/a").with_deps(vec!["install", "foundry-rs/forge-std"]);
+        let (pipeline, events) = run_scripted(project, 2, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        assert_eq!(pipeline.tested.len(), 1);
+        assert_eq!(pipeline.tested[0].url, "https://github.com/a/a");
+        assert_eq!(pipeline.tested[0].samples.len(), 2);
+        assert_eq!(
+            pipeline.tested[0].commit.as_deref(),
+            Some("0123abc0123abc0123abc0123abc0123abc0123a")
+        );
+        let fingerprint = pipeline.tested[0].config_fingerprint.as_ref().unwrap();
+        assert_eq!(
+            fingerprint.iter().collect::<Vec<_>>(),
+            [
+                (&"fuzz.runs".to_string(), &"1000".to_string()),
+                (&"optimizer".to_string(), &"false".to_string()),
+            ]
+        );
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 https://github.com/a/a <dir>",
+                "git rev-parse HEAD",
+                "forge install foundry-rs/forge-std",
+                "forge build",
+                "forge config --json",
+                "forge test -vvv",
+                "forge test -vvv",
+            ]
+        );
+        assert_eq!(events.last().unwrap(), "tested");
+    }
+
+    #[test]
+    fn test_scripted_stop_after() {
+        let runner = ScriptedRunner::default();
+        let (pipeline, events) = run_scripted(ProjectConfig::new("a/a"), 2, Stage::Clone, &runner);
+        assert!(pipeline.tested.is_empty() && pipeline.failures.is_empty());
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 https://github.com/a/a <dir>",
+                "git rev-parse HEAD"
+            ]
+        );
+        assert_eq!(events, ["clone started", "clone finished"]);
+
+        let runner = ScriptedRunner::default();
+        let (pipeline, events) = run_scripted(ProjectConfig::new("a/a"), 2, Stage::Build, &runner);
+        assert!(pipeline.failures.is_empty());
+        let tested = &pipeline.tested[0];
+        assert_eq!((tested.runs, tested.avg_test_time), (0, 0.0));
+        assert!(tested.samples.is_empty());
+        // Without a config printed by forge, the project is only left without a fingerprint.
+        assert_eq!(tested.config_fingerprint, None);
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 https://github.com/a/a <dir>",
+                "git rev-parse HEAD",
+                "forge build",
+                "forge config --json"
+            ]
+        );
+        assert_eq!(events.last().unwrap(), "build finished");
+    }
+
+    #[test]
+    fn test_scripted_build_only() {
+        let mut project = ProjectConfig::new("a/a");
+        project.build_only = true;
+        project.script = Some("script/Deploy.s.sol".to_string());
+        let runner = ScriptedRunner::default();
+        let (pipeline, events) = run_scripted(project, 2, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        let tested = &pipeline.tested[0];
+        assert!(tested.build_only);
+        assert_eq!((tested.runs, tested.test_time()), (0, None));
+        assert_eq!(tested.avg_script_time, None);
+        // Neither the tests nor the script run.
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 https://github.com/a/a <dir>",
+                "git rev-parse HEAD",
+                "forge build",
+                "forge config --json"
+            ]
+        );
+        assert_eq!(events[4..], ["0 runs planned", "tested"]);
+    }
+
+    #[test]
+    fn test_scripted_output_size() {
+        let runner = ScriptedRunner::default().on(
+            "forge test",
+            [
+                Response::ok(&"x".repeat(100)),
+                Response::fail(0, &"x".repeat(300), "warn"),
+            ],
+        );
+        let (pipeline, _) = run_scripted(ProjectConfig::new("a/a"), 2, Stage::Test, &runner);
+        assert_eq!(
+            pipeline.tested[0].output_size,
+            Some(OutputSize {
+                stdout: 200,
+                stderr: 2,
+                verbosity: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_scripted_build_warnings() {
+        let runner = ScriptedRunner::default().on(
+            "forge build",
+            [Response::ok(
+                "Compiler run successful with warnings:\nWarning (2072): Unused local variable.\n  --> src/A.sol:12:9:\n",
+            )],
+        );
+        let (pipeline, _) = run_scripted(ProjectConfig::new("a/a"), 1, Stage::Test, &runner);
+        assert_eq!(
+            pipeline.tested[0].build_warnings.as_deref(),
+            Some(&["Warning (2072): Unused local variable. --> src/A.sol:12:9".to_string()][..])
+        );
+    }
+
+    #[test]
+    fn test_scripted_script() {
+        let project =
+            || ProjectConfig::new("a/a").with_script("script/Deploy.s.sol", vec!["--sig", "run()"]);
+        let runner = ScriptedRunner::default();
+        let (pipeline, events) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        let tested = &pipeline.tested[0];
+        assert_eq!(tested.script_samples.len(), 2);
+        assert!(tested.avg_script_time.is_some());
+        assert_eq!(
+            commands(&runner)[4..],
+            [
+                "forge test -vvv",
+                "forge test -vvv",
+                "forge script script/Deploy.s.sol --sig run()",
+                "forge script script/Deploy.s.sol --sig run()",
+            ]
+        );
+        assert_eq!(
+            events[events.len() - 3..],
+            ["script run 2/2 started", "script run 2 completed", "tested"]
+        );
+
+        let runner = ScriptedRunner::default().on(
+            "forge script",
+            [Response::fail(1, "", "Error: script failed: revert")],
+        );
+        let (pipeline, events) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert!(pipeline.tested.is_empty());
+        assert_eq!(pipeline.failures[0].stage, "script");
+        assert_eq!(
+            pipeline.failures[0].error,
+            "'forge script' for a/a FAILED with status code: Some(1)"
+        );
+        assert_eq!(events.last().unwrap(), "failed at script");
+
+        // The pipeline stopping after the build runs no script.
+        let runner = ScriptedRunner::default();
+        let (pipeline, _) = run_scripted(project(), 2, Stage::Build, &runner);
+        assert_eq!(pipeline.tested[0].avg_script_time, None);
+    }
+
+    #[test]
+    fn test_custom_remappings() {
+        let setup = |config: &ProjectConfig, existing: Option<&str>, runner: &ScriptedRunner| {
+            let dir = tempfile::tempdir().unwrap();
+            if let Some(existing) = existing {
+                fs::write(dir.path().join("remappings.txt"), existing).unwrap();
+            }
+            let ready = Ready {
+                config,
+                path: dir.path().to_path_buf(),
+                unpinned_fork: false,
+                missing_env_example: None,
+                commit: None,
+                _temp_dir: dir,
+            };
+            try_handle_custom_setup(&ready, &Toolchain::default(), runner).unwrap();
+            fs::read_to_string(ready.path.join("remappings.txt")).unwrap()
+        };
+        let mut config =
+            ProjectConfig::new("a/a").with_remappings(vec!["@oz/=lib/oz/", "@new/=lib/new/"]);
+
+        // The project's file is merged, without running `forge remappings`.
+        let runner = ScriptedRunner::default();
+        let existing = "forge-std/=lib/forge-std/src/\n@oz/=lib/openzeppelin/\n";
+        assert_eq!(
+            setup(&config, Some(existing), &runner),
+            "forge-std/=lib/forge-std/src/\n@oz/=lib/oz/\n@new/=lib/new/"
+        );
+        assert!(runner.calls().is_empty());
+
+        // Without one, the remappings forge infers are.
+        let runner = ScriptedRunner::default().on(
+            "forge remappings",
+            [Response::ok("ds-test/=lib/ds-test/src/\n")],
+        );
+        assert_eq!(
+            setup(&config, None, &runner),
+            "ds-test/=lib/ds-test/src/\n@oz/=lib/oz/\n@new/=lib/new/"
+        );
+        assert_eq!(runner.calls(), ["forge remappings"]);
+
+        config.remappings_mode = RemappingsMode::Replace;
+        let runner = ScriptedRunner::default();
+        assert_eq!(
+            setup(&config, Some(existing), &runner),
+            "@oz/=lib/oz/\n@new/=lib/new/"
+        );
+    }
+
+    #[test]
+    fn test_scripted_no_network() {
+        let project = |needs_network| {
+            let mut project = ProjectConfig::new("a/a");
+            project.no_network = true;
+            project.needs_network = needs_network;
+            project
+        };
+        let runner = ScriptedRunner::default();
+        let (pipeline, _) = run_scripted(project(false), 1, Stage::Test, &runner);
+        assert_eq!(pipeline.tested[0].network, NetworkAccess::Isolated);
+        // Only the timed runs are isolated.
+        assert_eq!(
+            commands(&runner)[2..],
+            [
+                "forge build",
+                "forge config --json",
+                "unshare --map-root-user --net forge test -vvv",
+            ]
+        );
+        assert_eq!(
+            runner.env(4, "FOUNDRY_DISABLE_NIGHTLY_WARNING").as_deref(),
+            Some("true")
+        );
+
+        let runner = ScriptedRunner::default();
+        let (pipeline, _) = run_scripted(project(true), 1, Stage::Test, &runner);
+        assert_eq!(pipeline.tested[0].network, NetworkAccess::Exempted);
+        assert_eq!(commands(&runner)[4], "forge test -vvv");
+    }
+
+    #[test]
+    fn test_scripted_clean_env() {
+        let project = |clean_env| {
+            let mut project = ProjectConfig::new("a/a")
+                .with_env_vars(vec!["MAINNET_RPC_URL"], vec!["https://rpc.example"]);
+            project.clean_env = clean_env;
+            project
+        };
+        // Without --clean-env, the env vars of the project only reach forge through its `.env`.
+        let runner = ScriptedRunner::default();
+        run_scripted(project(false), 1, Stage::Test, &runner);
+        assert_eq!(commands(&runner)[2], "forge build");
+        assert_eq!(runner.env(2, "MAINNET_RPC_URL"), None);
+        assert_eq!(runner.env(2, "PATH"), None);
+
+        let runner = ScriptedRunner::default();
+        let (pipeline, _) = run_scripted(project(true), 1, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        for call in 2..5 {
+            assert_eq!(
+                runner.env(call, "MAINNET_RPC_URL").as_deref(),
+                Some("https://rpc.example")
+            );
+            assert_eq!(runner.env(call, "PATH"), std::env::var("PATH").ok());
+        }
+        assert_eq!(
+            runner.env(4, "FOUNDRY_DISABLE_NIGHTLY_WARNING").as_deref(),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn test_scripted_hooks() {
+        let project = || {
+            let mut project = ProjectConfig::new("a/a");
+            project.prepare = Some("sync".to_string());
+            project.conclude = Some("rm -rf cache".to_string());
+            project
+        };
+        let runner = ScriptedRunner::default();
+        let (pipeline, _) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        let tested = &pipeline.tested[0];
+        assert_eq!(tested.samples.len(), 2);
+        assert_eq!(tested.prepare.as_deref(), Some("sync"));
+        assert_eq!(
+            commands(&runner)[4..],
+            [
+                "sh -c sync",
+                "forge test -vvv",
+                "sh -c rm -rf cache",
+                "sh -c sync",
+                "forge test -vvv",
+                "sh -c rm -rf cache",
+            ]
+        );
+
+        // A failed hook fails the project at its stage, without any more run.
+        let runner = ScriptedRunner::default()
+            .on("sh -c sync", [Response::ok(""), Response::fail(1, "", "")]);
+        let (pipeline, _) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert!(pipeline.tested.is_empty());
+        assert_eq!(pipeline.failures[0].stage, "prepare");
+        assert_eq!(
+            pipeline.failures[0].error,
+            "The prepare hook 'sync' for a/a failed with exit code: Some(1)."
+        );
+        assert_eq!(commands(&runner).len(), 8);
+
+        // A failed run is concluded too, and fails the project at the test stage.
+        let runner = ScriptedRunner::default()
+            .on("forge test", [Response::fail(1, "", "")])
+            .on("sh -c rm", [Response::fail(1, "", "")]);
+        let (pipeline, _) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert_eq!(pipeline.failures[0].stage, "test");
+        assert_eq!(commands(&runner).last().unwrap(), "sh -c rm -rf cache");
+    }
+
+    #[test]
+    fn test_scripted_interleaved_hooks() {
+        let mut project = ProjectConfig::new("a/a");
+        project.prepare = Some("sync".to_string());
+        project.conclude = Some("rm -rf cache".to_string());
+        let toolchains = [Toolchain::default(), Toolchain::default()];
+        let run = |runner: &ScriptedRunner| {
+            let reporter = RecordingReporter::default();
+            run_interleaved_pipeline(&[project.clone()], 1, 3, &toolchains, runner, &reporter)
+                .unwrap()
+        };
+        let runner = ScriptedRunner::default();
+        let pipelines = run(&runner);
+        assert!(pipelines.iter().all(|p| p.failures.is_empty()));
+        let calls = commands(&runner);
+        let timed: Vec<_> = calls
+            .iter()
+            .filter(|c| c.starts_with("sh -c") || c.starts_with("forge test"))
+            .collect();
+        assert_eq!(
+            timed,
+            [
+                "sh -c sync",
+                "forge test -vvv",
+                "sh -c rm -rf cache",
+                "sh -c sync",
+                "forge test -vvv",
+                "sh -c rm -rf cache",
+            ]
+        );
+
+        // A failed hook fails the project at its stage, with the toolchain it failed with.
+        let runner =
+            ScriptedRunner::default().on("sh -c rm", [Response::ok(""), Response::fail(1, "", "")]);
+        let pipelines = run(&runner);
+        assert!(pipelines.iter().all(|p| p.tested.is_empty()));
+        assert!(pipelines[0].failures.is_empty());
+        assert_eq!(pipelines[1].failures[0].stage, "conclude");
+        let runner = ScriptedRunner::default().on("sh -c sync", [Response::fail(1, "", "")]);
+        let pipelines = run(&runner);
+        assert_eq!(pipelines[0].failures[0].stage, "prepare");
+        assert!(
+            !commands(&runner)
+                .iter()
+                .any(|c| c.starts_with("forge test"))
+        );
+    }
+
+    #[test]
+    fn test_time_incremental() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/b")).unwrap();
+        fs::write(dir.path().join("src/b/A.sol"), "contract A {}\n").unwrap();
+        fs::write(dir.path().join("src/C.sol"), "contract C {}\n").unwrap();
+        fs::write(dir.path().join("src/README.md"), "").unwrap();
+        let mut config = ProjectConfig::new("a/a");
+        assert_eq!(
+            incremental_file(&config, dir.path()),
+            Some(PathBuf::from("src/C.sol"))
+        );
+
+        config.incremental_file = Some("src/b/A.sol".to_string());
+        let runner = ScriptedRunner::default();
+        let samples =
+            time_incremental(&config, dir.path(), 2, &Toolchain::default(), &runner).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(runner.calls(), ["forge build"; 3]);
+        // The edited source is restored.
+        assert_eq!(
+            fs::read_to_string(dir.path().join("src/b/A.sol")).unwrap(),
+            "contract A {}\n"
+        );
+
+        // A failed incremental build is reported as n/a, once the sources build again.
+        let runner = ScriptedRunner::default().on("forge build", [Response::fail(1, "", "")]);
+        let samples =
+            time_incremental(&config, dir.path(), 2, &Toolchain::default(), &runner).unwrap();
+        assert!(samples.is_empty());
+        assert_eq!(runner.calls().len(), 2);
+        let runner = ScriptedRunner::default()
+            .on("forge build", [Response::ok(""), Response::fail(1, "", "")]);
+        assert!(time_incremental(&config, dir.path(), 1, &Toolchain::default(), &runner).is_err());
+
+        // A missing source too, without building anything.
+        config.incremental_file = Some("src/Missing.sol".to_string());
+        let runner = ScriptedRunner::default();
+        let samples =
+            time_incremental(&config, dir.path(), 2, &Toolchain::default(), &runner).unwrap();
+        assert!(samples.is_empty() && runner.calls().is_empty());
+        assert_eq!(
+            incremental_file(&ProjectConfig::new("a/a"), &dir.path().join("src/b")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_scripted_discovery() {
+        let project = || {
+            let mut project = ProjectConfig::new("a/a");
+            project.measure_discovery = true;
+            project
+        };
+        let runner = ScriptedRunner::default();
+        let (pipeline, _) = run_scripted(project(), 1, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        assert!(pipeline.tested[0].discovery_time.is_some());
+        assert_eq!(
+            commands(&runner)[2..],
+            [
+                "forge build",
+                "forge config --json",
+                "forge test --list",
+                "forge test --list",
+                "forge test --list",
+                "forge test -vvv",
+            ]
+        );
+
+        // A forge without `--list` reports the discovery time as n/a, but still runs the tests.
+        let runner = ScriptedRunner::default().on(
+            "forge test --list",
+            [Response::fail(
+                2,
+                "",
+                "error: unexpected argument '--list' found",
+            )],
+        );
+        let (pipeline, _) = run_scripted(project(), 1, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        assert_eq!(pipeline.tested[0].discovery_time, None);
+        assert_eq!(
+            commands(&runner)[4..],
+            ["forge test --list", "forge test -vvv"]
+        );
+
+        // Discovery isn't timed unless asked for.
+        let runner = ScriptedRunner::default();
+        let (pipeline, _) = run_scripted(ProjectConfig::new("a/a"), 1, Stage::Test, &runner);
+        assert_eq!(pipeline.tested[0].discovery_time, None);
+        assert!(!commands(&runner).contains(&"forge test --list".to_string()));
+    }
+
+    #[test]
+    fn test_parse_reported_time() {
+        let output = "Ran 3 tests for test/A.t.sol:ATest\n\
+            Suite result: ok. 3 passed; 0 failed; 0 skipped; finished in 2.10ms (1.50ms CPU time)\n\n\
+            \x1b[1mRan 2 test suites in 1.05s (1.89s CPU time)\x1b[0m: 8 tests passed, 0 failed, 0 skipped (8 total tests)\n";
+        assert_eq!(parse_reported_time(output), Some(1.05));
+        let time = |duration: &str| {
+            parse_reported_time(&format!(
+                "Ran 1 test suite in {duration} (1.00ms CPU time): 1 tests passed"
+            ))
+        };
+        for (duration, seconds) in [
+            ("226.80ms", 0.2268),
+            ("850.00µs", 8.5e-4),
+            ("12.5us", 1.25e-5),
+        ] {
+            assert!(
+                (time(duration).unwrap() - seconds).abs() < 1e-12,
+                "{duration}"
+            );
+        }
+        assert_eq!(time("1.2m"), None);
+        // The per-suite summaries aren't the total time.
+        assert_eq!(
+            parse_reported_time("Ran 3 tests for test/A.t.sol:ATest\n"),
+            None
+        );
+        assert_eq!(parse_reported_time(""), None);
+
+        assert_eq!(parse_test_count(output), Some(8));
+        assert_eq!(
+            parse_test_count("Ran 3 tests for test/A.t.sol:ATest\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_scripted_timing_source() {
+        let summary = "Ran 1 test suite in 5.00ms (4.00ms CPU time): 1 tests passed, 0 failed, 0 skipped (1 total tests)\n";
+        let project = |timing_source| {
+            let mut project = ProjectConfig::new("a/a");
+            project.timing_source = timing_source;
+            project
+        };
+        let runner = ScriptedRunner::default()
+            .on("forge test", [Response::ok(summary), Response::ok(summary)]);
+        let (pipeline, _) = run_scripted(project(TimingSource::Forge), 2, Stage::Test, &runner);
+        let forge_timed = pipeline.tested[0].clone();
+        assert_eq!(forge_timed.timing_source, TimingSource::Forge);
+        assert_eq!(forge_timed.samples, [0.005, 0.005]);
+        assert!((forge_timed.avg_test_time - 0.005).abs() < 1e-9);
+        assert_eq!(forge_timed.process_samples.len(), 2);
+
+        // Without forge's summary in every run, the process times are used.
+        let runner = ScriptedRunner::default().on("forge test", [Response::ok(summary)]);
+        let (pipeline, _) = run_scripted(project(TimingSource::Forge), 2, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        let process_timed = pipeline.tested[0].clone();
+        assert_eq!(process_timed.timing_source, TimingSource::Process);
+        assert!(process_timed.process_samples.is_empty());
+        assert_eq!(process_timed.samples.len(), 2);
+
+        // A project timed with different sources on each side is compared with the process times.
+        let side = |tests: Vec<Tested>| SourceResults {
+            source: Source::Version("stable".to_string()),
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        };
+        let mut results = vec![
+            side(vec![forge_timed.clone()]),
+            side(vec![process_timed.clone()]),
+        ];
+        assert_eq!(align_timing_sources(&mut results), ["a/a"]);
+        assert_eq!(results[0].tests[0].timing_source, TimingSource::Process);
+        assert_eq!(results[0].tests[0].samples, forge_timed.process_samples);
+
+        // Results timed by forge on every side are left alone.
+        let mut results = vec![
+            side(vec![forge_timed.clone()]),
+            side(vec![forge_timed.clone()]),
+        ];
+        assert!(align_timing_sources(&mut results).is_empty());
+        assert_eq!(results[1].tests[0].samples, [0.005, 0.005]);
+
+        // A result without its process times can't be compared.
+        let recorded = Tested {
+            process_samples: Vec::new(),
+            ..forge_timed
+        };
+        let mut results = vec![side(vec![recorded]), side(vec![process_timed])];
+        assert_eq!(align_timing_sources(&mut results), ["a/a"]);
+        assert!(results[0].tests.is_empty());
+        assert_eq!(results[1].tests.len(), 1);
+    }
+
+    #[test]
+    fn test_scripted_forge_threads() {
+        let mut project = ProjectConfig::new("a/a");
+        project.forge_threads = NonZeroUsize::new(4);
+        project.script = Some("script/Deploy.s.sol".to_string());
+        let runner = ScriptedRunner::default();
+        let (pipeline, _) = run_scripted(project, 1, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        // Only the test runs are pinned to the threads.
+        assert_eq!(
+            commands(&runner)[2..],
+            [
+                "forge build",
+                "forge config --json",
+                "forge test -vvv --threads 4",
+                "forge script script/Deploy.s.sol",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scripted_synthetic() {
+        let synthetic = SyntheticConfig {
+            contracts: 2,
+            seed: 42,
+            ..Default::default()
+        };
+        let project = ProjectConfig::new("a/a").with_synthetic(synthetic.clone());
+        let runner = ScriptedRunner::default();
+        let (pipeline, events) = run_scripted(project, 1, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        // The project is generated instead of cloned, and its parameters recorded.
+        assert_eq!(
+            commands(&runner),
+            ["forge build", "forge config --json", "forge test -vvv"]
+        );
+        assert_eq!(events[..2], ["clone started", "clone finished"]);
+        assert_eq!(pipeline.tested[0].synthetic, Some(synthetic));
+        assert_eq!(
+            pipeline.tested[0].url,
+            "synthetic:contracts=2,tests_per_contract=5,inheritance_depth=1,fuzz=false,seed=42"
+        );
+    }
+
+    #[test]
+    fn test_scripted_fmt() {
+        let project = || {
+            ProjectConfig::new("a/a")
+                .with_deps(vec!["install", "foundry-rs/forge-std"])
+                .with_command(Measured::Fmt)
+        };
+        // Unformatted files make `forge fmt --check` exit with 1, which still times a run.
+        let runner = ScriptedRunner::default().on(
+            "forge fmt",
+            [Response::fail(1, "", "Diff in src/A.sol"), Response::ok("")],
+        );
+        let (pipeline, events) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        let tested = &pipeline.tested[0];
+        assert_eq!((tested.command, tested.build_time), (Measured::Fmt, 0.0));
+        assert_eq!(tested.samples.len(), 2);
+        // Neither the dependencies nor the build are needed.
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 https://github.com/a/a <dir>",
+                "git rev-parse HEAD",
+                "forge fmt --check",
+                "forge fmt --check",
+            ]
+        );
+        assert!(!events.iter().any(|event| event.starts_with("build")));
+
+        let runner = ScriptedRunner::default().on(
+            "forge fmt",
+            [Response::fail(2, "", "Error: failed to parse src/A.sol")],
+        );
+        let (pipeline, _) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert!(pipeline.tested.is_empty());
+        assert_eq!(pipeline.failures[0].stage, "test");
+        assert_eq!(
+            pipeline.failures[0].error,
+            "'forge fmt' for a/a FAILED with status code: Some(2)"
+        );
+    }
+
+    #[test]
+    fn test_scripted_anvil() {
+        let project = || {
+            ProjectConfig::new("a/a")
+                .with_env_vars(vec!["MAINNET_RPC_URL"], vec!["https://rpc.example"])
+                .with_anvil(AnvilConfig {
+                    fork_url_env: "MAINNET_RPC_URL".to_string(),
+                    port: 8545,
+                })
+        };
+        let runner = ScriptedRunner::default().on(
+            "anvil",
+            [Response::ok(
+                "Fetching the fork block\nListening on 127.0.0.1:8545\n",
+            )],
+        );
+        let (pipeline, _) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 https://github.com/a/a <dir>",
+                "git rev-parse HEAD",
+                "forge build",
+                "forge config --json",
+                "anvil --fork-url https://rpc.example --port 8545",
+                "forge test -vvv",
+                "forge test -vvv",
+            ]
+        );
+        // The tests run against the node, instead of the configured endpoint.
+        assert_eq!(
+            runner.env(5, "MAINNET_RPC_URL").as_deref(),
+            Some("http://127.0.0.1:8545")
+        );
+        assert_eq!(runner.env(2, "MAINNET_RPC_URL"), None);
+
+        let runner = ScriptedRunner::default().on(
+            "anvil",
+            [Response::fail(
+                1,
+                "",
+                "Error: failed to get fork block number",
+            )],
+        );
+        let (pipeline, events) = run_scripted(project(), 2, Stage::Test, &runner);
+        assert!(pipeline.tested.is_empty());
+        assert_eq!(pipeline.failures[0].stage, "test");
+        assert_eq!(
+            pipeline.failures[0].error,
+            "'anvil' for a/a exited on startup: Error: failed to get fork block number"
+        );
+        assert!(
+            !commands(&runner)
+                .iter()
+                .any(|c| c.starts_with("forge test"))
+        );
+        assert_eq!(events.last().unwrap(), "failed at test");
+    }
+
+    #[test]
+    fn test_scripted_rpc_cache() {
+        let project = ProjectConfig::new("a/a")
+            .with_env_vars(vec!["MAINNET_RPC_URL"], vec!["https://rpc.example"])
+            .with_anvil(AnvilConfig {
+                fork_url_env: "MAINNET_RPC_URL".to_string(),
+                port: 8545,
+            })
+            .with_rpc_cache(RpcCacheConfig {
+                url_env: "MAINNET_RPC_URL".to_string(),
+            });
+        let runner =
+            ScriptedRunner::default().on("anvil", [Response::ok("Listening on 127.0.0.1:8545\n")]);
+        let (pipeline, _) = run_scripted(project, 1, Stage::Test, &runner);
+        assert!(pipeline.failures.is_empty());
+        // The node forks through the proxy, and the tests run against the node.
+        let proxy = rpc_cache::endpoint("https://rpc.example").unwrap();
+        assert!(proxy.starts_with("http://127.0.0.1:"));
+        assert_eq!(
+            commands(&runner)[4],
+            format!("anvil --fork-url {proxy} --port 8545")
+        );
+        assert_eq!(
+            runner.env(5, "MAINNET_RPC_URL").as_deref(),
+            Some("http://127.0.0.1:8545")
+        );
+    }
+
+    #[test]
+    fn test_scripted_rev() {
+        let mut project = ProjectConfig::new("a/a");
+        project.rev = Some("v1.0".to_string());
+        let runner = ScriptedRunner::default().on(
+            "forge test",
+            [Response::ok(
+                "Ran 1 test suite in 1.05s (1.89s CPU time): 12 tests passed, 0 failed, 0 skipped (12 total tests)\n",
+            )],
+        );
+        let (pipeline, _) = run_scripted(project.clone(), 1, Stage::Test, &runner);
+        assert_eq!(pipeline.tested[0].num_tests, Some(12));
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 --no-checkout https://github.com/a/a <dir>",
+                "git fetch --depth 1 origin v1.0",
+                "git checkout --detach FETCH_HEAD",
+                "git rev-parse HEAD",
+                "forge build",
+                "forge config --json",
+                "forge test -vvv",
+            ]
+        );
+
+        let runner = ScriptedRunner::default().on(
+            "git fetch",
+            [Response::fail(
+                128,
+                "",
+                "fatal: couldn't find remote ref v1.0",
+            )],
+        );
+        let (pipeline, events) = run_scripted(project, 1, Stage::Test, &runner);
+        assert_eq!(
+            pipeline.failures[0].error,
+            "Failed to check out v1.0 of https://github.com/a/a. Git command exited with: exit status: 128."
+        );
+        assert_eq!(events, ["clone started", "failed at clone"]);
+    }
+
+    #[test]
+    fn test_scripted_clone_failure() {
+        let runner = ScriptedRunner::default().on(
+            "git clone",
+            [Response::fail(128, "", "fatal: repository not found")],
+        );
+        let (pipeline, events) = run_scripted(ProjectConfig::new("a/a"), 2, Stage::Test, &runner);
+        assert!(pipeline.tested.is_empty());
+        assert_eq!(pipeline.failures[0].stage, "clone");
+        assert_eq!(
+            pipeline.failures[0].error,
+            "Failed to clone https://github.com/a/a. Git command exited with: exit status: 128."
+        );
+        assert_eq!(
+            commands(&runner),
+            ["git clone --depth 1 https://github.com/a/a <dir>"]
+        );
+        assert_eq!(events, ["clone started", "failed at clone"]);
+    }
+
+    #[test]
+    fn test_scripted_build_failure() {
+        let runner = ScriptedRunner::default().on(
+            "forge build",
+            [Response::fail(1, "", "Error: Compiler run failed")],
+        );
+        let (pipeline, events) = run_scripted(ProjectConfig::new("a/a"), 2, Stage::Test, &runner);
+        assert!(pipeline.tested.is_empty());
+        assert_eq!(pipeline.failures[0].stage, "build");
+        assert_eq!(
+            pipeline.failures[0].error,
+            "'forge build' for a/a failed with exit code: Some(1)."
+        );
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 https://github.com/a/a <dir>",
+                "git rev-parse HEAD",
+                "forge build"
+            ]
+        );
+        assert_eq!(events.last().unwrap(), "failed at build");
+    }
+
+    #[test]
+    fn test_scripted_test_failure() {
+        // The second of three runs fails, the last one isn't run.
+        let runner = ScriptedRunner::default().on(
+            "forge test",
+            [
+                Response::ok("Suite result: ok. 1 passed"),
+                Response::fail(
+                    1,
+                    "Ran 1 test for test/A.t.sol:ATest\n[FAIL: revert: boom] test_a() (gas: 1)\n",
+                    "",
+                ),
+            ],
+        );
+        let (pipeline, events) = run_scripted(ProjectConfig::new("a/a"), 3, Stage::Test, &runner);
+        assert!(pipeline.tested.is_empty());
+        let failure = &pipeline.failures[0];
+        assert_eq!(failure.stage, "test");
+        assert_eq!(
+            failure.error,
+            "'forge test' for a/a FAILED with status code: Some(1)"
+        );
+        assert_eq!(failure.failed_tests[0].name, "ATest::test_a");
+        assert_eq!(
+            commands(&runner),
+            [
+                "git clone --depth 1 https://github.com/a/a <dir>",
+                "git rev-parse HEAD",
+                "forge build",
+                "forge config --json",
+                "forge test -vvv",
+                "forge test -vvv",
+            ]
+        );
+        assert_eq!(
+            events[5..],
+            [
+                "test run 1/3 started",
+                "test run 1 completed",
+                "test run 2/3 started",
+                "failed at test",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_copy_dir() {
+        let from = TempDir::new().unwrap();
+        fs::create_dir_all(from.path().join("lib/forge-std/src")).unwrap();
+        fs::write(
+            from.path().join("lib/forge-std/src/Test.sol"),
+            "contract Test {}",
+        )
+        .unwrap();
+        fs::write(from.path().join("foundry.toml"), "[profile.default]").unwrap();
+        std::os::unix::fs::symlink("lib/forge-std", from.path().join("std")).unwrap();
+
+        let to = TempDir::new().unwrap();
+        copy_dir(from.path(), to.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(to.path().join("lib/forge-std/src/Test.sol")).unwrap(),
+            "contract Test {}"
+        );
+        assert_eq!(
+            fs::read_to_string(to.path().join("foundry.toml")).unwrap(),
+            "[profile.default]"
+        );
+        assert_eq!(
+            fs::read_link(to.path().join("std")).unwrap(),
+            Path::new("lib/forge-std")
+        );
+    }
 }
@@ -0,0 +1,485 @@
+use eyre::{Result, eyre};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+use yansi::Paint;
+
+use crate::{
+    benchmark::{self, Source, Stage},
+    cmd::{BisectConfig, Verbosity},
+    reporter::ConsoleReporter,
+    runner::SystemRunner,
+    toolchain::Toolchain,
+    ui,
+    utils::{self, ProjectConfig},
+};
+
+/// A commit of the Foundry repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    pub sha: String,
+    pub subject: String,
+}
+
+impl Commit {
+    fn short_sha(&self) -> &str {
+        &self.sha[..self.sha.len().min(10)]
+    }
+}
+
+/// Binary search for the first bad commit of a range.
+///
+/// The candidates are the commits after the good one, oldest first, the last one being the known
+/// bad commit.
+#[derive(Debug)]
+pub struct Bisection {
+    commits: Vec<Commit>,
+    /// Number of candidates known to be good (all of them come before `bad`).
+    good: usize,
+    /// Index of the oldest candidate known to be bad.
+    bad: usize,
+}
+
+impl Bisection {
+    /// # Panics
+    /// Panics if `commits` is empty.
+    pub fn new(commits: Vec<Commit>) -> Self {
+        assert!(
+            !commits.is_empty(),
+            "a bisection needs at least the bad commit"
+        );
+        let bad = commits.len() - 1;
+        Self {
+            commits,
+            good: 0,
+            bad,
+        }
+    }
+
+    /// The next candidate to test, or `None` once the culprit is found.
+    pub fn next(&self) -> Option<usize> {
+        (self.good < self.bad).then(|| self.good + (self.bad - self.good) / 2)
+    }
+
+    /// Records whether the candidate at `index` regressed.
+    pub fn mark(&mut self, index: usize, regressed: bool) {
+        if regressed {
+            self.bad = index;
+        } else {
+            self.good = index + 1;
+        }
+    }
+
+    /// The first bad commit, once the bisection is done.
+    pub fn culprit(&self) -> Option<&Commit> {
+        (self.good == self.bad).then(|| &self.commits[self.bad])
+    }
+
+    /// The commit at `index`.
+    pub fn commit(&self, index: usize) -> &Commit {
+        &self.commits[index]
+    }
+
+    /// The last known good candidate (or `None` for the good commit itself), and the first known
+    /// bad one.
+    pub fn range(&self) -> (Option<&Commit>, &Commit) {
+        let last_good = self.good.checked_sub(1).map(|i| &self.commits[i]);
+        (last_good, &self.commits[self.bad])
+    }
+
+    /// Number of candidates that may still be the culprit.
+    pub fn remaining(&self) -> usize {
+        self.bad - self.good + 1
+    }
+
+    /// Number of steps left to find the culprit.
+    pub fn steps_left(&self) -> u32 {
+        self.remaining().next_power_of_two().trailing_zeros()
+    }
+}
+
+/// Runs the `bisect` subcommand: finds the first commit between `--good` and `--bad` whose test
+/// time of `--project` exceeds the one of `--good` by more than `--threshold`.
+///
+/// Every candidate is installed into its own isolated prefix, removed once benchmarked (unless
+/// `keep` is set). If the bisection stops early (on an error or a Ctrl-C), the narrowed range is
+/// reported.
+pub fn run(
+    config: &BisectConfig,
+    project: ProjectConfig,
+    verbosity: Verbosity,
+    foundryup: &Path,
+    keep: bool,
+) -> Result<()> {
+    let repo_dir = sync_repo(&config.foundry_repo)?;
+    let good = Commit {
+        sha: rev_parse(&repo_dir, &config.good)?,
+        subject: config.good.clone(),
+    };
+    let commits = commit_range(&repo_dir, &good.sha, &rev_parse(&repo_dir, &config.bad)?)?;
+    if commits.is_empty() {
+        return Err(eyre!(
+            "{} {} is not a descendant of {}, there is nothing to bisect",
+            Paint::red("ERROR:").bold(),
+            config.bad,
+            config.good
+        ));
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&interrupted);
+    if let Err(e) = ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst)) {
+        eprintln!(
+            "{} Failed to handle Ctrl-C, an interrupted bisect won't report its range. {e}",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+
+    let prefixes = tempfile::Builder::new()
+        .prefix("bisect-")
+        .tempdir_in(utils::work_dir()?)?;
+    let mut bisector = Bisector {
+        config,
+        project: [project],
+        verbosity,
+        foundryup,
+        prefixes: prefixes.path(),
+        keep,
+        step: 0,
+    };
+
+    println!(
+        "Bisecting {} commits of {} for a regression of {} above {}%",
+        commits.len(),
+        config.foundry_repo,
+        config.project,
+        config.threshold
+    );
+
+    let baseline = bisector.measure(&good, "good")?;
+    let mut bisection = Bisection::new(commits);
+
+    // The bad commit must regress, otherwise there is no culprit to find.
+    let bad = bisection.commit(bisection.remaining() - 1).clone();
+    let outcome = bisector
+        .measure(&bad, "bad")
+        .and_then(|time| check_interrupted(&interrupted).map(|_| time));
+    let bad_time = outcome?;
+    let diff = ui::relative_diff(baseline, bad_time);
+    if diff <= config.threshold {
+        return Err(eyre!(
+            "{} {} is only {diff:.1}% slower than {}, below the {}% threshold: nothing to bisect",
+            Paint::red("ERROR:").bold(),
+            config.bad,
+            config.good,
+            config.threshold
+        ));
+    }
+
+    while let Some(index) = bisection.next() {
+        println!(
+            "\n{} {} candidates left (~{} steps)",
+            Paint::cyan("BISECT:").bold(),
+            bisection.remaining(),
+            bisection.steps_left()
+        );
+        let candidate = bisection.commit(index).clone();
+        let outcome = bisector
+            .measure(&candidate, "candidate")
+            .and_then(|time| check_interrupted(&interrupted).map(|_| time));
+        let time = match outcome {
+            Ok(time) => time,
+            Err(e) => {
+                log_range(&bisection, &good, &config.foundry_repo);
+                return Err(e);
+            }
+        };
+
+        let diff = ui::relative_diff(baseline, time);
+        let regressed = diff > config.threshold;
+        println!(
+            "{} {} {} ({diff:+.1}%)",
+            candidate.short_sha(),
+            if regressed {
+                Paint::red("BAD").bold()
+            } else {
+                Paint::green("GOOD").bold()
+            },
+            candidate.subject
+        );
+        bisection.mark(index, regressed);
+    }
+
+    let culprit = bisection.culprit().expect("bisection is done");
+    ui::banner(Some("BISECT RESULT"));
+    println!(
+        "First bad commit: {} {}\n{}",
+        Paint::primary(&culprit.sha).bold(),
+        culprit.subject,
+        Paint::cyan(&Source::Commit(culprit.sha.clone()).github_url(&config.foundry_repo))
+    );
+    ui::banner(None);
+
+    if keep {
+        println!("\nKept toolchain prefixes at {}", prefixes.keep().display());
+    }
+    Ok(())
+}
+
+/// Installs and benchmarks the candidates of a bisection.
+struct Bisector<'a> {
+    config: &'a BisectConfig,
+    project: [ProjectConfig; 1],
+    verbosity: Verbosity,
+    foundryup: &'a Path,
+    prefixes: &'a Path,
+    keep: bool,
+    step: usize,
+}
+
+impl Bisector<'_> {
+    /// Returns the average test time of the project with `commit`.
+    fn measure(&mut self, commit: &Commit, role: &str) -> Result<f64> {
+        self.step += 1;
+        let start = Instant::now();
+        println!(
+            "\n{} step {}: installing the {role} commit {} ({})",
+            Paint::cyan("BISECT:").bold(),
+            self.step,
+            commit.short_sha(),
+            commit.subject
+        );
+
+        let prefix = self.prefixes.join(&commit.sha);
+        let toolchain = Toolchain::isolated(&prefix);
+        toolchain.install(
+            self.foundryup,
+            &self.config.foundry_repo,
+            role,
+            &Source::Commit(commit.sha.clone()),
+            &SystemRunner,
+        )?;
+        let pipeline = benchmark::run_pipeline(
+            &self.project,
+            self.config.runs,
+            self.verbosity,
+            Stage::Test,
+            &toolchain,
+            &SystemRunner,
+            &ConsoleReporter,
+        )?;
+        if !self.keep {
+            let _ = fs::remove_dir_all(&prefix);
+        }
+
+        let tested = pipeline.tested.first().ok_or_else(|| {
+            let reason = pipeline
+                .failures
+                .first()
+                .map_or_else(String::new, |f| format!(" at stage '{}'", f.stage));
+            eyre!(
+                "{} {} failed{reason} with the {role} commit {}",
+                Paint::red("ERROR:").bold(),
+                self.config.project,
+                commit.sha
+            )
+        })?;
+        println!(
+            "{} step {} took {:.0}s: {:.2}s (avg for {} runs)",
+            Paint::cyan("BISECT:").bold(),
+            self.step,
+            start.elapsed().as_secs_f64(),
+            tested.avg_test_time,
+            tested.runs
+        );
+        Ok(tested.avg_test_time)
+    }
+}
+
+fn check_interrupted(interrupted: &AtomicBool) -> Result<()> {
+    if interrupted.load(Ordering::SeqCst) {
+        return Err(eyre!("{} Bisect interrupted", Paint::red("ERROR:").bold()));
+    }
+    Ok(())
+}
+
+/// Prints the range the culprit is known to be in, so that a stopped bisection isn't wasted.
+fn log_range(bisection: &Bisection, good: &Commit, foundry_repo: &str) {
+    let (last_good, first_bad) = bisection.range();
+    let last_good = last_good.unwrap_or(good);
+    println!(
+        "\n{} the regression was narrowed down to {} commit(s), after {} and up to {}:\n{}/{foundry_repo}/compare/{}...{}",
+        Paint::yellow("BISECT STOPPED:").bold(),
+        bisection.remaining(),
+        last_good.short_sha(),
+        first_bad.short_sha(),
+        utils::github_url(),
+        last_good.sha,
+        first_bad.sha
+    );
+}
+
+/// Returns a local mirror of `foundry_repo` (without file contents), cloning or updating it.
+fn sync_repo(foundry_repo: &str) -> Result<PathBuf> {
+    let dir = utils::work_dir()?
+        .join("repos")
+        .join(format!("{}.git", foundry_repo.replace('/', "-")));
+    let mut cmd = Command::new("git");
+    if dir.exists() {
+        println!("Fetching {foundry_repo} into {}", dir.display());
+        cmd.arg("-C")
+            .arg(&dir)
+            .args(["fetch", "--quiet", "--prune"]);
+    } else {
+        println!("Cloning {foundry_repo} into {}", dir.display());
+        cmd.args(["clone", "--quiet", "--mirror", "--filter=blob:none"])
+            .arg(format!("{}/{foundry_repo}", utils::github_url()))
+            .arg(&dir);
+    }
+    git(&mut cmd)?;
+    Ok(dir)
+}
+
+/// Resolves `rev` (a tag, branch or commit) to a commit hash.
+fn rev_parse(repo_dir: &Path, rev: &str) -> Result<String> {
+    git(Command::new("git").arg("-C").arg(repo_dir).args([
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("{rev}^{{commit}}"),
+    ]))
+    .map(|out| out.trim().to_string())
+    .map_err(|_| eyre!("{} Unknown revision: {rev}", Paint::red("ERROR:").bold()))
+}
+
+/// Returns the commits on the ancestry path from `good` (excluded) to `bad`, oldest first.
+fn commit_range(repo_dir: &Path, good: &str, bad: &str) -> Result<Vec<Commit>> {
+    let out = git(Command::new("git").arg("-C").arg(repo_dir).args([
+        "log",
+        "--reverse",
+        "--ancestry-path",
+        "--format=%H %s",
+        &format!("{good}..{bad}"),
+    ]))?;
+    Ok(out
+        .lines()
+        .filter_map(|line| {
+            let (sha, subject) = line.split_once(' ').unwrap_or((line, ""));
+            (!sha.is_empty()).then(|| Commit {
+                sha: sha.to_string(),
+                subject: subject.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Runs a git command, returning its stdout.
+fn git(cmd: &mut Command) -> Result<String> {
+    let output = cmd
+        .output()
+        .map_err(|e| eyre!("Failed to run git. Error: {e}"))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{} git failed: {}",
+            Paint::red("ERROR:").bold(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn commits(n: usize) -> Vec<Commit> {
+        (0..n)
+            .map(|i| Commit {
+                sha: format!("{i:040}"),
+                subject: format!("commit {i}"),
+            })
+            .collect()
+    }
+
+    /// Bisects `n` commits where every commit from `first_bad` on regresses.
+    fn bisect(n: usize, first_bad: usize) -> (String, usize) {
+        let mut bisection = Bisection::new(commits(n));
+        let mut steps = 0;
+        while let Some(index) = bisection.next() {
+            bisection.mark(index, index >= first_bad);
+            steps += 1;
+        }
+        (bisection.culprit().unwrap().subject.clone(), steps)
+    }
+
+    #[test]
+    fn test_bisection() {
+        for n in 1..20 {
+            for first_bad in 0..n {
+                let (culprit, steps) = bisect(n, first_bad);
+                assert_eq!(culprit, format!("commit {first_bad}"));
+                assert!(steps as u32 <= Bisection::new(commits(n)).steps_left());
+            }
+        }
+    }
+
+    #[test]
+    fn test_bisection_range() {
+        let mut bisection = Bisection::new(commits(8));
+        assert_eq!(bisection.remaining(), 8);
+        assert_eq!(bisection.steps_left(), 3);
+        assert_eq!(bisection.range().0, None);
+
+        bisection.mark(3, false);
+        bisection.mark(5, true);
+        let (last_good, first_bad) = bisection.range();
+        assert_eq!(last_good.unwrap().subject, "commit 3");
+        assert_eq!(first_bad.subject, "commit 5");
+        assert_eq!(bisection.remaining(), 2);
+        assert!(bisection.culprit().is_none());
+    }
+
+    #[test]
+    fn test_commit_range() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path();
+        let run = |args: &[&str]| {
+            git(Command::new("git")
+                .arg("-C")
+                .arg(repo)
+                .args(["-c", "user.name=test", "-c", "user.email=test@test"])
+                .args(args))
+            .unwrap()
+        };
+        run(&["init", "--quiet"]);
+        for i in 0..4 {
+            run(&[
+                "commit",
+                "--quiet",
+                "--allow-empty",
+                "-m",
+                &format!("commit {i}"),
+            ]);
+        }
+        run(&["tag", "good", "HEAD~3"]);
+
+        let good = rev_parse(repo, "good").unwrap();
+        let bad = rev_parse(repo, "HEAD").unwrap();
+        assert_eq!(good.len(), 40);
+        assert!(rev_parse(repo, "missing").is_err());
+
+        let range = commit_range(repo, &good, &bad).unwrap();
+        let subjects: Vec<_> = range.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(subjects, ["commit 1", "commit 2", "commit 3"]);
+        assert_eq!(range[2].sha, bad);
+        assert!(commit_range(repo, &bad, &good).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use eyre::{Result, eyre};
+use yansi::Paint;
+
+use crate::{
+    benchmark::{self, Stage},
+    cmd::Verbosity,
+    reporter::Reporter,
+    runner::CommandRunner,
+    toolchain::Toolchain,
+    ui,
+    utils::ProjectConfig,
+};
+
+/// The project of `repos` the canary of a diff runs: the one `name`d by `--canary=PROJECT`, or
+/// else the one expected to be the fastest by its time budget, or else the first one.
+///
+/// `None` without any project to benchmark.
+pub fn pick<'a>(
+    repos: &'a [ProjectConfig],
+    name: Option<&str>,
+) -> Result<Option<&'a ProjectConfig>> {
+    if let Some(name) = name {
+        return repos
+            .iter()
+            .find(|repo| repo.name == name)
+            .map(Some)
+            .ok_or_else(|| {
+                eyre!(
+                    "{} The canary project {name} is not benchmarked by this run",
+                    Paint::red("ERROR:").bold()
+                )
+            });
+    }
+    let expected = |repo: &ProjectConfig| {
+        let budget = repo.budget.as_ref()?;
+        (budget.build_time.is_some() || budget.test_time.is_some())
+            .then(|| budget.build_time.unwrap_or(0.0) + budget.test_time.unwrap_or(0.0))
+    };
+    let fastest = repos
+        .iter()
+        .filter_map(|repo| Some((repo, expected(repo)?)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(repo, _)| repo);
+    Ok(fastest.or(repos.first()))
+}
+
+/// Builds and tests `project` once with the toolchain of every source of `sides` (named by their
+/// role in the diff, e.g. "baseline"), in order, stopping at the first source it fails with.
+///
+/// Only checks that every source can run the benchmark before committing to it: the times aren't
+/// kept, neither the test discovery nor the incremental builds are measured, and the project is
+/// never kept by `--keep` (see [`ProjectConfig::scratch`]).
+pub fn run(
+    project: &ProjectConfig,
+    sides: &[(&str, &Toolchain)],
+    verbosity: Verbosity,
+    stop_after: Stage,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let mut project = project.clone();
+    project.scratch = true;
+    project.measure_discovery = false;
+    project.incremental_runs = None;
+    for (name, toolchain) in sides {
+        ui::big_banner(&format!("CANARY --> {name} ({})", project.name));
+        let pipeline = benchmark::run_pipeline(
+            std::slice::from_ref(&project),
+            1,
+            verbosity,
+            stop_after,
+            toolchain,
+            runner,
+            reporter,
+        )
+        .map_err(|e| {
+            eyre!(
+                "{} The canary run of {} failed with the {name} source: {e}",
+                Paint::red("ERROR:").bold(),
+                project.name
+            )
+        })?;
+        if let Some(failure) = pipeline.failures.first() {
+            return Err(eyre!(
+                "{} The canary run of {} failed with the {name} source at the {} stage: {}",
+                Paint::red("ERROR:").bold(),
+                project.name,
+                failure.stage,
+                failure.error
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{
+        reporter::ConsoleReporter,
+        runner::{Response, ScriptedRunner},
+        utils::Budget,
+    };
+
+    fn with_budget(name: &str, build_time: Option<f64>, test_time: Option<f64>) -> ProjectConfig {
+        let mut project = ProjectConfig::new(name);
+        project.budget = Some(Budget {
+            build_time,
+            test_time,
+            tolerance: 10.0,
+        });
+        project
+    }
+
+    #[test]
+    fn test_pick() {
+        let repos = [
+            ProjectConfig::new("a/a"),
+            with_budget("b/b", Some(60.0), Some(30.0)),
+            with_budget("c/c", None, Some(20.0)),
+            with_budget("d/d", None, None),
+        ];
+        let pick = |name| pick(&repos, name).unwrap().map(|repo| repo.name.as_str());
+        assert_eq!(pick(None), Some("c/c"));
+        assert_eq!(pick(Some("b/b")), Some("b/b"));
+        assert!(super::pick(&repos, Some("e/e")).is_err());
+        // Without any budget, the first project.
+        assert_eq!(super::pick(&repos[..1], None).unwrap().unwrap().name, "a/a");
+        assert!(super::pick(&[], None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut project = ProjectConfig::new("a/a");
+        project.measure_discovery = true;
+        let baseline = Toolchain::default();
+        let comparison = Toolchain::default();
+        let sides = [("baseline", &baseline), ("comparison", &comparison)];
+
+        let runner = ScriptedRunner::default();
+        run(&project, &sides, 0, Stage::Test, &runner, &ConsoleReporter).unwrap();
+        let calls = runner.calls();
+        // A single build and test run with every source, without the test discovery.
+        assert_eq!(calls.iter().filter(|c| *c == "forge build").count(), 2);
+        assert_eq!(
+            calls.iter().filter(|c| c.starts_with("forge test")).count(),
+            2
+        );
+        assert!(!calls.iter().any(|c| c.contains("--list")));
+
+        // The comparison fails its test run: the canary stops there.
+        let runner = ScriptedRunner::default().on(
+            "forge test",
+            [Response::ok(""), Response::fail(1, "", "boom")],
+        );
+        let err = run(&project, &sides, 0, Stage::Test, &runner, &ConsoleReporter)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(
+                "The canary run of a/a failed with the comparison source at the test stage"
+            )
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{keep, rpc_cache, toolchain};
+
+/// Directories of the work dir reused across runs: the git mirrors cloned by `bisect`, and the
+/// responses cached by the RPC proxy of fork tests.
+const CACHE_DIRS: &[&str] = &["repos", rpc_cache::CACHE_DIR];
+/// Prefixes of the work dir entries created by runs: the toolchain prefixes of `diff` (left
+/// behind by `--keep`), those of `bisect`, and the project directories kept by `--keep` and
+/// `--keep-failed`.
+const WORK_PREFIXES: &[&str] = &["toolchains-", "bisect-", keep::KEPT_PREFIX];
+
+/// Kind of location managed by the tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Cached clones, RPC responses and toolchains, reused across runs.
+    Cache,
+    /// Leftovers of previous runs, e.g. isolated toolchain prefixes.
+    Work,
+    /// The history file of `--record`.
+    History,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Cache => "cache",
+            Self::Work => "work",
+            Self::History => "history",
+        })
+    }
+}
+
+/// A file or directory managed by the tool, and its size on disk.
+#[derive(Debug)]
+pub struct Location {
+    pub kind: Kind,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+impl Location {
+    fn new(kind: Kind, path: PathBuf) -> Self {
+        let size = disk_size(&path);
+        Self { kind, path, size }
+    }
+
+    /// Removes the location. Entries that were already removed are ignored.
+    pub fn remove(&self) -> io::Result<()> {
+        let result = match fs::symlink_metadata(&self.path) {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(&self.path),
+            Ok(_) => fs::remove_file(&self.path),
+            Err(e) => Err(e),
+        };
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Lists the locations managed by the tool: the entries of `work_dir` created by previous runs,
+/// and the `history` file, if they exist. The toolchains cached by `diff` are listed one by one,
+/// by the commit they were built from.
+///
+/// Any other entry of `work_dir` is left out, so that cleaning never touches files the tool didn't
+/// create.
+pub fn locations(work_dir: &Path, history: Option<&Path>) -> Vec<Location> {
+    let mut locations = Vec::new();
+
+    for path in sorted_entries(work_dir) {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name == toolchain::CACHE_DIR {
+            let prefixes = sorted_entries(&path);
+            if prefixes.is_empty() {
+                locations.push(Location::new(Kind::Cache, path));
+            }
+            for prefix in prefixes {
+                locations.push(Location::new(Kind::Cache, prefix));
+            }
+        } else if CACHE_DIRS.contains(&name) {
+            locations.push(Location::new(Kind::Cache, path));
+        } else if WORK_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+            locations.push(Location::new(Kind::Work, path));
+        }
+    }
+
+    if let Some(history) = history
+        && history.is_file()
+    {
+        locations.push(Location::new(Kind::History, history.to_path_buf()));
+    }
+    locations
+}
+
+/// The paths of the entries of the directory `dir`, sorted. Empty if it can't be read.
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// Size on disk of `path`, recursively for directories. Symlinks aren't followed, and entries
+/// that can't be read are skipped.
+fn disk_size(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| disk_size(&entry.path()))
+        .sum()
+}
+
+/// Formats a size in bytes with a binary unit (e.g. `1.5 GiB`).
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_locations() {
+        let dir = tempdir().unwrap();
+        let work_dir = dir.path().join("work");
+        fs::create_dir_all(work_dir.join("repos/foundry-rs-foundry.git")).unwrap();
+        fs::write(work_dir.join("repos/foundry-rs-foundry.git/HEAD"), [0; 100]).unwrap();
+        fs::create_dir_all(work_dir.join("toolchains-abc/baseline/bin")).unwrap();
+        fs::write(work_dir.join("toolchains-abc/baseline/bin/forge"), [0; 50]).unwrap();
+        fs::create_dir_all(work_dir.join("bisect-def")).unwrap();
+        fs::create_dir_all(work_dir.join("kept-1700000000/a__a")).unwrap();
+        fs::write(work_dir.join("kept-1700000000/a__a/foundry.toml"), [0; 10]).unwrap();
+        fs::create_dir_all(work_dir.join("rpc-cache")).unwrap();
+        fs::write(work_dir.join("rpc-cache/0123456789abcdef.json"), [0; 20]).unwrap();
+        fs::create_dir_all(work_dir.join("toolchain-cache/0123abc/bin")).unwrap();
+        fs::write(work_dir.join("toolchain-cache/0123abc/bin/forge"), [0; 30]).unwrap();
+        fs::create_dir_all(work_dir.join("toolchain-cache/4567def")).unwrap();
+        fs::create_dir_all(work_dir.join("unrelated")).unwrap();
+        let history = dir.path().join("history.jsonl");
+        fs::write(&history, "{}\n").unwrap();
+
+        let found = locations(&work_dir, Some(&history));
+        let summary: Vec<_> = found
+            .iter()
+            .map(|l| {
+                (
+                    l.kind,
+                    l.path.file_name().unwrap().to_str().unwrap(),
+                    l.size,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Kind::Work, "bisect-def", 0),
+                (Kind::Work, "kept-1700000000", 10),
+                (Kind::Cache, "repos", 100),
+                (Kind::Cache, "rpc-cache", 20),
+                (Kind::Cache, "0123abc", 30),
+                (Kind::Cache, "4567def", 0),
+                (Kind::Work, "toolchains-abc", 50),
+                (Kind::History, "history.jsonl", 3),
+            ]
+        );
+
+        for location in &found {
+            location.remove().unwrap();
+            // Removing an already removed location is a no-op.
+            location.remove().unwrap();
+        }
+        assert!(work_dir.join("unrelated").exists());
+        // The emptied toolchain cache is removed with the next clean.
+        let found = locations(&work_dir, Some(&history));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, work_dir.join("toolchain-cache"));
+        found[0].remove().unwrap();
+        assert!(locations(&work_dir, Some(&history)).is_empty());
+        assert!(locations(&dir.path().join("missing"), None).is_empty());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+}
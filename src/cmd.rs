@@ -1,29 +1,75 @@
 pub use clap::{ArgAction, Parser};
-use clap::{Args, Subcommand};
+use clap::{
+    ArgMatches, Args, CommandFactory, FromArgMatches, Subcommand, ValueEnum, parser::ValueSource,
+};
 use eyre::{Result, eyre};
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::Read,
+    num::NonZeroUsize,
+    time::Duration,
+};
 
 use crate::{
     Source,
-    config::ConfigFile,
-    utils::{JsonProjectConfig, ProjectConfig},
+    benchmark::{Measured, Stage, TimingSource},
+    config::{ConfigFile, ConfigProfile, PresetConfig},
+    gate::GateMetric,
+    logging::LogFormat,
+    matrix::Variant,
+    redact,
+    report::ReportFormat,
+    schema::SchemaKind,
+    ui::{self, LogLevel},
+    utils::{self, ConfigOrigin, ConfigOrigins, JsonProjectConfig, ProjectConfig},
 };
 
 pub type Verbosity = u8;
 
 #[derive(Parser, Debug)]
+#[cfg_attr(test, derive(Default))]
 #[clap(author, version, about = "A CLI tool to benchmark Foundry projects.")]
 pub struct Cli {
     /// Specifies the list of repository URLs to benchmark.
     /// Can be provided multiple times or as a comma-separated list (e.g., --repos url1,url2 or --repos url1 --repos url2).
+    /// Pass `-` to read the list from stdin, one repository per line.
     /// If not provided, a default list of projects will be used.
     #[clap(short, long, env = "BENCHMARK_REPOS", num_args = 1.., value_delimiter = ',', global = true)]
     pub repos: Option<Vec<String>>,
 
-    /// Path to TOML configuration file for custom project settings
+    /// Reads the repositories to benchmark from a file, one per line (`#` starts a comment), as if
+    /// passed to --repos.
+    #[clap(long, value_name = "PATH", conflicts_with_all = ["repo", "group"], global = true)]
+    pub repos_file: Option<String>,
+
+    /// Path to the configuration file for custom project settings (TOML, or YAML and JSON with a
+    /// `.yaml`/`.yml` or `.json` extension). By default, `benchmarks.toml` is looked up in the
+    /// current directory and its parents, then in `$XDG_CONFIG_HOME/foundry-benchmarks`
     #[clap(short = 'c', long, global = true)]
     pub config: Option<String>,
 
+    /// Section of the configuration file providing the global settings. `auto` uses `[custom]` if
+    /// it has any settings and no CLI override (--repos, --repo, --deps, --remappings, --env) is
+    /// passed, and `[defaults]` otherwise.
+    #[clap(long, value_enum, default_value_t = ConfigProfile::Auto, global = true)]
+    pub config_profile: ConfigProfile,
+
+    /// Preset of the configuration file (`[preset.<name>]`) providing the number of runs, the
+    /// verbosity, the projects, and the `forge test` args and env vars of the run. Flags passed
+    /// explicitly override its values.
+    #[clap(long, global = true)]
+    pub preset: Option<String>,
+
+    /// The settings of `--preset`, once loaded with [`Cli::apply_preset`].
+    #[clap(skip)]
+    pub preset_config: Option<PresetConfig>,
+
+    /// Ignore unknown keys in the configuration file with a warning, rather than failing. Useful
+    /// for configs written for a newer version of the tool.
+    #[clap(long, global = true)]
+    pub lenient_config: bool,
+
     /// Per-project configuration in format "repo:json" or just "repo"
     /// Example: --repo 'owner/repo:{"dependencies":["forge-std"],"remappings":["@std/=lib/forge-std/"]}'
     #[clap(long, conflicts_with = "repos", global = true)]
@@ -41,6 +87,33 @@ pub struct Cli {
     #[clap(long, value_delimiter = ',', global = true)]
     pub env: Option<Vec<String>>,
 
+    /// Only benchmark the projects of these groups of the config file (comma-separated or
+    /// repeated).
+    #[clap(long, value_delimiter = ',', conflicts_with_all = ["repos", "repo"], global = true)]
+    pub group: Vec<String>,
+
+    /// Only benchmark the projects with any of these tags (comma-separated or repeated).
+    #[clap(long, value_delimiter = ',', global = true)]
+    pub tag: Vec<String>,
+
+    /// Skip the projects with any of these tags (comma-separated or repeated).
+    #[clap(long, value_delimiter = ',', global = true)]
+    pub exclude_tag: Vec<String>,
+
+    /// Skip these projects (comma-separated or repeated), by name or glob pattern (e.g.
+    /// `vectorized/*`).
+    #[clap(long, value_delimiter = ',', global = true)]
+    pub exclude: Vec<String>,
+
+    /// Only benchmark the first N of the selected projects, e.g. for a quick smoke run. The limit
+    /// is recorded in the saved results if it leaves projects out.
+    #[clap(long, value_name = "N", global = true)]
+    pub limit: Option<NonZeroUsize>,
+
+    /// Skip the projects whose `required_env` variables aren't set, instead of failing the run.
+    #[clap(long, global = true)]
+    pub skip_missing_env: bool,
+
     /// Optional: Number of test runs for each project to average results. 10 by default.
     #[clap(
         long,
@@ -71,6 +144,219 @@ pub struct Cli {
     )]
     pub verbosity: Verbosity,
 
+    /// Level of the tool's own log messages, independent of the `forge test` verbosity (-v).
+    #[arg(
+        help_heading = "Display options",
+        long,
+        value_enum,
+        default_value_t = LogLevel::Info,
+        global = true
+    )]
+    pub log_level: LogLevel,
+
+    /// Format of the tool's own log messages. `json` prints one event per line on stderr.
+    #[arg(
+        help_heading = "Display options",
+        long,
+        value_enum,
+        default_value_t = LogFormat::Pretty,
+        global = true
+    )]
+    pub log_format: LogFormat,
+
+    /// Report the relative diffs within this percentage as ~0%, instead of within the noise floor
+    /// estimated from the spread of the test runs of each project.
+    #[arg(
+        help_heading = "Display options",
+        long,
+        value_name = "PERCENT",
+        global = true
+    )]
+    pub noise_threshold: Option<f64>,
+
+    /// Mark the projects whose runs vary by more than this percentage (their coefficient of
+    /// variation) with some source as noisy, in the tables and the summary.
+    #[arg(
+        help_heading = "Display options",
+        long,
+        value_name = "PERCENT",
+        default_value_t = ui::DEFAULT_NOISY_THRESHOLD,
+        global = true
+    )]
+    pub noisy_threshold: f64,
+
+    /// Print the full output of the failed commands: of a failed `forge test`, instead of the names
+    /// of its failing tests, and of the others, instead of its last --error-lines lines.
+    #[arg(help_heading = "Display options", long, global = true)]
+    pub full_errors: bool,
+
+    /// Number of trailing lines of the output of a failed command printed without --full-errors.
+    #[arg(
+        help_heading = "Display options",
+        long,
+        value_name = "N",
+        default_value_t = ui::DEFAULT_ERROR_LINES,
+        global = true
+    )]
+    pub error_lines: usize,
+
+    /// Keep the isolated toolchain prefixes and the project directories instead of removing them
+    /// once the run finishes. The projects are kept in a `kept-<timestamp>` directory of the work
+    /// dir, removed by `clean`.
+    #[clap(long, global = true)]
+    pub keep: bool,
+
+    /// Keep only the directories of the projects that failed, to reproduce their failure by hand.
+    #[clap(long, global = true, conflicts_with = "keep")]
+    pub keep_failed: bool,
+
+    /// Save the results of the run to a JSON file, to be reused with `compare` or
+    /// `diff --baseline-file`.
+    #[clap(long, value_name = "PATH")]
+    pub save: Option<String>,
+
+    /// Append the results of the run to the history file, to be inspected with `history`.
+    #[clap(long, global = true)]
+    pub record: bool,
+
+    /// Export the results of the run as OpenMetrics gauges to this file, e.g. for Prometheus to
+    /// scrape.
+    #[clap(long, value_name = "PATH", global = true)]
+    pub export_openmetrics: Option<String>,
+
+    /// Push the results of the run as metrics to this Prometheus Pushgateway. A failed push only
+    /// warns.
+    #[clap(long, value_name = "URL", global = true)]
+    pub push_gateway: Option<String>,
+
+    /// Exit with code 0 even if some projects failed, instead of 4.
+    #[clap(long, global = true)]
+    pub allow_failures: bool,
+
+    /// Exit with code 3 if a project is slower than the expected times of its config entry
+    /// (`expected_build_time` and `expected_test_time`), beyond their tolerance. Otherwise they're
+    /// only highlighted in the summary. Only checked by plain runs.
+    #[clap(long, global = true)]
+    pub enforce_budgets: bool,
+
+    /// Stop every project after this stage: `clone` only checks that the projects clone, and
+    /// `build` reports their build times without running `forge test`.
+    #[clap(long, value_enum, value_name = "STAGE", default_value_t, global = true)]
+    pub stop_after: Stage,
+
+    /// Only build every project, without running its tests, like the projects configuring
+    /// `build_only = true`. Unlike `--stop-after build`, the build-only projects are left out of the
+    /// test time tables, and marked as such in the summary.
+    #[clap(long, global = true)]
+    pub build_only: bool,
+
+    /// Command to time instead of `forge test` in every project, over the `command` of their
+    /// config: `fmt` times `forge fmt --check`, without building the projects.
+    #[clap(long = "command", value_enum, value_name = "COMMAND", global = true)]
+    pub measure: Option<Measured>,
+
+    /// Send the RPC requests of fork tests straight to their endpoint, instead of through the
+    /// local cache of the projects configuring `rpc_cache`.
+    #[clap(long, global = true)]
+    pub no_rpc_cache: bool,
+
+    /// Fail the projects whose fork tests don't pin the block they fork, instead of only warning
+    /// and marking their results.
+    #[clap(long, global = true)]
+    pub require_pinned_forks: bool,
+
+    /// Fail the projects whose `.env.example` (or `.env.sample`) documents variables that aren't
+    /// set, instead of only warning and listing them at the end of the run.
+    #[clap(long, global = true)]
+    pub require_env_example: bool,
+
+    /// Run the timed `forge test` commands without any network access, in a network namespace of
+    /// their own (`unshare --net`), so that fork tests or FFI calls reaching the network fail fast.
+    /// The projects configured with `needs_network = true`, or reaching a local `anvil` node or
+    /// `rpc_cache` proxy, are exempted, and listed in the report.
+    /// Linux only.
+    #[clap(long, global = true)]
+    pub no_network: bool,
+
+    /// Run the `forge` commands with a clean environment instead of inheriting this one, so that
+    /// stray variables like `FOUNDRY_PROFILE` or `RUST_LOG` don't change what's measured: only
+    /// `PATH`, `HOME` and the env vars configured for the project are kept. Recorded in the saved
+    /// results.
+    #[clap(long, global = true)]
+    pub clean_env: bool,
+
+    /// Also time the test discovery of every project with `forge test --list`, a few runs after
+    /// its build. Reported as n/a with a `forge` that doesn't support it.
+    #[clap(long, global = true)]
+    pub measure_discovery: bool,
+
+    /// Also time the incremental builds of every project after its build, 3 runs unless given: a
+    /// `forge build` after appending a comment to one of its sources (see `incremental_file`),
+    /// which is restored after every run.
+    #[clap(
+        long,
+        value_name = "RUNS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "3",
+        global = true
+    )]
+    pub incremental: Option<NonZeroUsize>,
+
+    /// Shell command run in the directory of every project before each of its timed test runs, e.g.
+    /// to drop the OS caches. Its time isn't measured, and a failure fails the project. Overrides
+    /// the `prepare` of the config entries.
+    #[clap(long, value_name = "CMD", global = true)]
+    pub prepare: Option<String>,
+
+    /// Shell command run in the directory of every project after each of its timed test runs. Its
+    /// time isn't measured, and a failure fails the project. Overrides the `conclude` of the config
+    /// entries.
+    #[clap(long, value_name = "CMD", global = true)]
+    pub conclude: Option<String>,
+
+    /// Clock the test runs are timed with: the wall time of the `forge` process, or the time forge
+    /// reports for running the test suites, without its startup. Falls back to the process time,
+    /// with a warning, when forge's summary can't be parsed.
+    #[clap(long, value_enum, default_value_t, global = true)]
+    pub timing_source: TimingSource,
+
+    /// Base URL of the GitHub instance the `owner/repo` names are cloned from and linked to, e.g.
+    /// a GitHub Enterprise server. Overrides `github_base_url` in `[defaults]`.
+    #[clap(
+        long,
+        env = "FOUNDRY_BENCHMARKS_GITHUB_URL",
+        value_name = "URL",
+        value_parser = utils::parse_github_url,
+        global = true
+    )]
+    pub github_url: Option<String>,
+
+    /// Run the tests on N threads (`forge test --threads N`), instead of one per core, so that
+    /// machines with different core counts can be compared. Recorded in the saved results.
+    #[clap(long, value_name = "N", global = true)]
+    pub forge_threads: Option<NonZeroUsize>,
+
+    /// Print the execution plan of the run (the toolchains installed, then the commands run and
+    /// the files written for every project) and exit without running anything.
+    #[clap(long, global = true)]
+    pub dry_run: bool,
+
+    /// Pick the projects to benchmark among the configured ones with an interactive prompt.
+    /// Requires a terminal.
+    #[clap(long, global = true)]
+    pub interactive: bool,
+
+    /// History file of the runs recorded with --record. Defaults to a file in the user data
+    /// directory.
+    #[clap(
+        long,
+        env = "FOUNDRY_BENCHMARKS_HISTORY",
+        value_name = "PATH",
+        global = true
+    )]
+    pub history_file: Option<String>,
+
     #[clap(subcommand)]
     command: Option<Commands>,
 }
@@ -79,11 +365,60 @@ pub struct Cli {
 enum Commands {
     /// Benchmark a diff between two Foundry versions built from specified branches.
     #[clap(name = "diff")]
-    Diff(DiffConfig),
+    Diff(Box<DiffConfig>),
+    /// Compare two result files saved with `--save`, without running any benchmark.
+    #[clap(name = "compare")]
+    Compare(CompareConfig),
+    /// Benchmark the projects once per variant of the Foundry settings, with the forge in PATH.
+    #[clap(name = "matrix")]
+    Matrix(MatrixConfig),
+    /// Render a results file saved with `--save` (standard or `diff` run) in another format.
+    #[clap(name = "report")]
+    Report(ReportConfig),
+    /// Combine result files saved with `--save` on several machines, each benchmarking different
+    /// projects, into a single one.
+    #[clap(name = "merge")]
+    Merge(MergeConfig),
+    /// Report the disk usage of the caches and work files of the tool, and remove them.
+    #[clap(name = "clean")]
+    Clean(CleanConfig),
+    /// Find the first Foundry commit between two revisions whose test time of a project regressed.
+    #[clap(name = "bisect")]
+    Bisect(BisectConfig),
+    /// Compare two revisions of a project, built and tested with the forge in PATH.
+    #[clap(name = "project-diff")]
+    ProjectDiff(ProjectDiffConfig),
+    /// Benchmark the projects that failed in a run saved with `--save`, and merge their results.
+    #[clap(name = "rerun")]
+    Rerun(RerunConfig),
+    /// Print the runs recorded with --record, and the test time progression of every project.
+    #[clap(name = "history")]
+    History(HistoryConfig),
+    /// Print the resolved configuration of every project, without cloning them.
+    #[clap(name = "list")]
+    List(ListConfig),
+    /// Check that the environment can run a benchmark (binaries, network, disk space and config).
+    #[clap(name = "doctor")]
+    Doctor(DoctorConfig),
+    /// Check the configuration file (the one passed to --config, or the discovered one) for
+    /// mistakes.
+    #[clap(name = "validate")]
+    Validate,
+    /// Write a commented configuration file (`benchmarks.toml`, or the path passed to --config).
+    #[clap(name = "init")]
+    Init(InitConfig),
+    /// Print the JSON Schema of the results files saved with `--save`.
+    #[clap(name = "schema")]
+    Schema(SchemaConfig),
+    /// Search GitHub for Foundry projects, and print their `[[project]]` entries or append them to
+    /// the configuration file.
+    #[clap(name = "discover")]
+    Discover(DiscoverConfig),
 }
 
 /// Struct for reference Foundry source choice (version or branch)
 #[derive(Args, Debug)]
+#[cfg_attr(test, derive(Default))]
 #[group(id = "reference_source_group", required = true, multiple = false)]
 struct ReferenceSource {
     #[clap(
@@ -101,31 +436,118 @@ struct ReferenceSource {
         help = "Reference Foundry branch (e.g., 'master')"
     )]
     ref_branch: Option<String>,
+
+    #[clap(
+        long = "baseline-file",
+        value_name = "RESULTS_FILE",
+        conflicts_with = "interleave",
+        help = "Results file (saved with --save) to use as the reference instead of benchmarking it"
+    )]
+    baseline_file: Option<String>,
+
+    #[clap(
+        long = "reference-env",
+        visible_alias = "ref-env",
+        value_name = "KEY=VALUE",
+        num_args = 0..,
+        conflicts_with_all = ["vs_version", "vs_branch"],
+        help = "Environment variables to run the forge in PATH with, instead of installing a reference source"
+    )]
+    ref_env: Option<Vec<String>>,
+
+    #[clap(
+        long = "reference-current",
+        visible_alias = "ref-current",
+        help = "Use the forge in PATH as the reference, as installed, instead of installing one with foundryup"
+    )]
+    ref_current: bool,
 }
 
-/// Struct for comparison Foundry source choice (version or branch)
-#[derive(Args, Debug)]
-#[group(id = "comparison_source_group", required = true, multiple = false)]
+/// Comparison Foundry source choices (versions and/or branches).
+///
+/// Both flags can be repeated to compare several sources against the reference in a single run.
+/// The sources are benchmarked (and listed in the results table) in the order they are passed.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Default))]
 struct ComparisonSource {
+    /// The `--vs-version` and `--vs-branch` sources, in command line order.
+    sources: Vec<Source>,
+    vs_env: Option<Vec<String>>,
+}
+
+impl FromArgMatches for ComparisonSource {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let flags = ComparisonFlags::from_arg_matches(matches)?;
+        // The values of both flags, tagged with their position on the command line.
+        let positioned = |id, values: Vec<String>, source: fn(String) -> Source| {
+            let indices: Vec<usize> = matches.indices_of(id).into_iter().flatten().collect();
+            indices.into_iter().zip(values.into_iter().map(source))
+        };
+        let mut sources: Vec<(usize, Source)> =
+            positioned("vs_version", flags.vs_version, Source::Version)
+                .chain(positioned("vs_branch", flags.vs_branch, Source::Branch))
+                .collect();
+        sources.sort_by_key(|(index, _)| *index);
+        Ok(Self {
+            sources: sources.into_iter().map(|(_, source)| source).collect(),
+            vs_env: flags.vs_env,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for ComparisonSource {
+    fn group_id() -> Option<clap::Id> {
+        ComparisonFlags::group_id()
+    }
+
+    fn augment_args(cmd: clap::Command) -> clap::Command {
+        ComparisonFlags::augment_args(cmd)
+    }
+
+    fn augment_args_for_update(cmd: clap::Command) -> clap::Command {
+        ComparisonFlags::augment_args_for_update(cmd)
+    }
+}
+
+/// Flags of [`ComparisonSource`], as parsed by clap.
+#[derive(Args, Debug)]
+#[group(id = "comparison_source_group", required = true, multiple = true)]
+struct ComparisonFlags {
     #[clap(
         long = "comparison-version",
         visible_alias = "vs-version",
         value_name = "VS_VERSION",
-        help = "Comparison Foundry version to test (e.g., 'v1.2.0-rc')"
+        help = "Comparison Foundry version to test (e.g., 'v1.2.0-rc'). Can be repeated"
     )]
-    vs_version: Option<String>,
+    vs_version: Vec<String>,
 
     #[clap(
         long = "comparison-branch",
         visible_alias = "vs-branch",
         value_name = "VS_BRANCH",
-        help = "Comparison Foundry branch to test (e.g., 'my-perf-optimization')"
+        help = "Comparison Foundry branch to test (e.g., 'my-perf-optimization'). Can be repeated"
+    )]
+    vs_branch: Vec<String>,
+
+    #[clap(
+        long = "comparison-env",
+        visible_alias = "vs-env",
+        value_name = "KEY=VALUE",
+        num_args = 0..,
+        conflicts_with_all = ["ref_version", "ref_branch", "baseline_file"],
+        help = "Environment variables to run the forge in PATH with, to compare against --ref-env"
     )]
-    vs_branch: Option<String>,
+    vs_env: Option<Vec<String>>,
 }
 
 #[derive(Args, Debug)]
-struct DiffConfig {
+#[cfg_attr(test, derive(Default))]
+pub struct DiffConfig {
     #[clap(flatten)]
     reference_source: ReferenceSource,
 
@@ -141,9 +563,467 @@ struct DiffConfig {
         help = "Git repository for building Foundry from source"
     )]
     foundry_repo: String,
+
+    /// Git repository of the reference source. Defaults to --foundry-repo.
+    #[clap(long, value_name = "FOUNDRY_REPOSITORY")]
+    ref_repo: Option<String>,
+
+    /// Git repository of the comparison sources. Defaults to --foundry-repo.
+    #[clap(long, value_name = "FOUNDRY_REPOSITORY")]
+    vs_repo: Option<String>,
+
+    /// Path to the `foundryup` binary used to install the sources. Defaults to the one in `PATH`.
+    #[clap(long, env = "FOUNDRYUP_BIN", value_name = "PATH")]
+    pub foundryup_bin: Option<String>,
+
+    /// Install the branches and commits again, instead of reusing the toolchains that previous
+    /// runs built from the same commits (cached in the work dir, removed by `clean`).
+    #[clap(long)]
+    pub refresh_toolchains: bool,
+
+    /// Alternate the test runs of all sources per project (ABAB...) instead of benchmarking one
+    /// source after the other, so that slow drifts in the machine's performance (thermal
+    /// throttling, background load) affect all sources alike.
+    #[clap(long)]
+    pub interleave: bool,
+
+    /// Before the benchmark, build and test a single project once with every source, and abort
+    /// the run if any of them fails. Runs the given project, or else the fastest by its time
+    /// budget, or else the first one. The times of the canary are discarded.
+    #[clap(
+        long,
+        value_name = "PROJECT",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with = "watch"
+    )]
+    pub canary: Option<Option<String>>,
+
+    /// Benchmark the sources even if several of them resolve to the same forge build.
+    #[clap(long)]
+    pub allow_identical: bool,
+
+    /// Fail the run if the sources cloned a project at different commits, e.g. because it was
+    /// pushed to between their runs, instead of only warning about it.
+    #[clap(long)]
+    pub strict_provenance: bool,
+
+    /// Also benchmark the `anvil` of every source, in a separate table: the time a fresh node
+    /// takes to answer its first request, and then to mine a batch of transactions, averaged over
+    /// --num-runs nodes.
+    #[clap(long, conflicts_with = "watch")]
+    pub bench_anvil: bool,
+
+    /// Keep watching the comparison branch, and benchmark its head again every time it moves,
+    /// checking every INTERVAL (e.g. `30s`, `5m` or `1h`). The reference is only benchmarked once.
+    #[clap(
+        long,
+        value_name = "INTERVAL",
+        value_parser = parse_interval,
+        conflicts_with = "interleave"
+    )]
+    pub watch: Option<Duration>,
+
+    /// After the comparison, profile the N projects whose test time regressed the most (3 by
+    /// default): run their tests once more with every source under `perf record -g`, untimed, and
+    /// render the flamegraphs to --flamegraph-dir. Linux only, needs `perf`: without it, the run
+    /// only warns.
+    #[clap(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "3",
+        conflicts_with = "watch"
+    )]
+    pub flamegraph: Option<NonZeroUsize>,
+
+    /// Directory the flamegraphs of --flamegraph are written to.
+    #[clap(
+        long,
+        value_name = "PATH",
+        default_value = "flamegraphs",
+        requires = "flamegraph"
+    )]
+    pub flamegraph_dir: String,
+
+    /// Exit with a non-zero code if a comparison source is slower than the baseline by more than
+    /// this percentage.
+    #[clap(long, value_name = "PERCENT")]
+    pub fail_on_regression: Option<f64>,
+
+    /// Metric checked by --fail-on-regression.
+    #[clap(long, value_enum, default_value_t, requires = "fail_on_regression")]
+    pub gate_metric: GateMetric,
+
+    /// Project to exclude from --fail-on-regression (e.g. because it is known to be noisy).
+    /// Can be repeated.
+    #[clap(long, value_name = "PROJECT", requires = "fail_on_regression")]
+    pub gate_exclude: Vec<String>,
+
+    /// Annotate the comparison sources slower than the baseline by more than this percentage with
+    /// a GitHub Actions warning, without failing the run. Slower than --fail-on-regression is
+    /// annotated with an error.
+    #[clap(long, value_name = "PERCENT")]
+    pub warn_on_regression: Option<f64>,
+
+    /// Print GitHub Actions annotations for the regressions even outside of a workflow. They're
+    /// printed by default when GITHUB_ACTIONS is set.
+    #[clap(long)]
+    pub gh_annotations: bool,
+
+    /// Display name of the reference source in the results, instead of its version or branch.
+    #[clap(long, value_name = "LABEL")]
+    pub ref_label: Option<String>,
+
+    /// Display name of a comparison source in the results. Can be repeated, in the order the
+    /// comparison sources are passed.
+    #[clap(long, value_name = "LABEL")]
+    pub vs_label: Vec<String>,
+
+    /// Save the results of every source, along with the run's metadata, to a JSON file.
+    #[clap(long, value_name = "PATH")]
+    pub save: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct CompareConfig {
+    /// Results file to use as the baseline.
+    #[clap(value_name = "OLD")]
+    pub old: String,
+
+    /// Results file to compare against the baseline.
+    #[clap(value_name = "NEW")]
+    pub new: String,
+
+    /// Git repository the results were benchmarked from, used to link the forge builds.
+    #[clap(
+        long,
+        value_name = "FOUNDRY_REPOSITORY",
+        default_value = "foundry-rs/foundry",
+        help = "Git repository the results were benchmarked from"
+    )]
+    pub foundry_repo: String,
+}
+
+#[derive(Args, Debug)]
+pub struct MatrixConfig {
+    /// A variant of the Foundry settings, as `NAME:KEY=VALUE,...`. Pass it once per variant, the
+    /// first one being the baseline. Defaults to the `[[variant]]` entries of the config file.
+    #[clap(long = "variant", value_name = "NAME:KEY=VALUE,...", value_parser = Variant::parse)]
+    pub variants: Vec<Variant>,
+
+    /// Save the results of every variant, along with the run's metadata, to a JSON file.
+    #[clap(long, value_name = "PATH")]
+    pub save: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportConfig {
+    /// Results file to render.
+    #[clap(value_name = "RESULTS")]
+    pub file: String,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = ReportFormat::Markdown)]
+    pub format: ReportFormat,
+
+    /// Write the report to this file, rather than to stdout.
+    #[clap(long, value_name = "PATH")]
+    pub out: Option<String>,
+
+    /// Git repository the results were benchmarked from, used to link the forge builds when the
+    /// file doesn't record it.
+    #[clap(
+        long,
+        value_name = "FOUNDRY_REPOSITORY",
+        default_value = "foundry-rs/foundry",
+        help = "Git repository the results were benchmarked from"
+    )]
+    pub foundry_repo: String,
+}
+
+#[derive(Args, Debug)]
+pub struct MergeConfig {
+    /// Result files to merge, benchmarked with the same settings and sources.
+    #[clap(value_name = "RESULTS", num_args = 2.., required = true)]
+    pub files: Vec<String>,
+
+    /// File to save the merged results to.
+    #[clap(long, value_name = "PATH")]
+    pub out: String,
+}
+
+#[derive(Args, Debug)]
+pub struct CleanConfig {
+    /// Only remove the cached clones, RPC responses and toolchains.
+    #[clap(long, conflicts_with = "work_only")]
+    pub cache_only: bool,
+
+    /// Only remove the leftovers of previous runs (e.g. toolchain prefixes kept with --keep).
+    #[clap(long)]
+    pub work_only: bool,
+
+    /// Also remove the history file of --record.
+    #[clap(long, conflicts_with_all = ["cache_only", "work_only"])]
+    pub history: bool,
+
+    /// Only report what would be removed.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ProjectDiffConfig {
+    /// Project to benchmark at both revisions (e.g. `owner/repo`). Its configuration applies to
+    /// both of them.
+    #[clap(long)]
+    pub project: String,
+
+    /// Revision (branch, tag or full commit hash) of the project to compare against.
+    #[clap(long, value_name = "REV")]
+    pub before: String,
+
+    /// Revision (branch, tag or full commit hash) of the project to compare.
+    #[clap(long, value_name = "REV")]
+    pub after: String,
+
+    /// Save the results of both revisions, along with the run's metadata, to a JSON file.
+    #[clap(long, value_name = "PATH")]
+    pub save: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct BisectConfig {
+    /// Revision (tag, branch or commit) without the regression.
+    #[clap(long, value_name = "REF")]
+    pub good: String,
+
+    /// Revision (tag, branch or commit) with the regression.
+    #[clap(long, value_name = "REF")]
+    pub bad: String,
+
+    /// Project to benchmark every candidate with (e.g. `owner/repo`).
+    #[clap(long)]
+    pub project: String,
+
+    /// Relative test time increase (in %) over the good revision above which a commit is bad.
+    #[clap(long, value_name = "PERCENT", default_value_t = 5.0)]
+    pub threshold: f64,
+
+    /// Number of test runs per candidate, lower than a regular run to keep the bisect short.
+    #[clap(long, default_value_t = 3)]
+    pub runs: usize,
+
+    #[clap(
+        long,
+        value_name = "FOUNDRY_REPOSITORY",
+        default_value = "foundry-rs/foundry",
+        help = "Git repository to bisect and build Foundry from"
+    )]
+    pub foundry_repo: String,
+
+    /// Path to the `foundryup` binary used to install the candidates. Defaults to the one in `PATH`.
+    #[clap(long, env = "FOUNDRYUP_BIN", value_name = "PATH")]
+    pub foundryup_bin: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct RerunConfig {
+    /// Results file of a plain run, saved with --save.
+    #[clap(long, value_name = "PATH")]
+    pub from: String,
+
+    /// File to write the merged results to. Defaults to overwriting --from.
+    #[clap(long, value_name = "PATH")]
+    pub out: Option<String>,
+
+    /// Merge the results even if the active forge differs from the recorded one.
+    #[clap(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+#[clap(args_conflicts_with_subcommands = true)]
+pub struct HistoryConfig {
+    #[clap(subcommand)]
+    pub command: Option<HistoryCommand>,
+
+    /// Number of most recent runs to print.
+    #[clap(short = 'n', long, default_value_t = 10)]
+    pub last: usize,
+
+    /// Only print the given projects (e.g. `owner/repo`). Can be repeated.
+    #[clap(long = "project", value_name = "PROJECT")]
+    pub projects: Vec<String>,
+
+    /// Only print the runs recorded since this date (`YYYY-MM-DD`, UTC).
+    #[clap(long, value_name = "DATE", value_parser = parse_since)]
+    pub since: Option<u64>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HistoryCommand {
+    /// Print a sparkline of the test time of every project over the most recent runs.
+    #[clap(name = "trend")]
+    Trend(TrendConfig),
+}
+
+#[derive(Args, Debug)]
+pub struct TrendConfig {
+    /// Number of most recent runs to plot.
+    #[clap(short = 'n', long, default_value_t = 20)]
+    pub last: usize,
+
+    /// Only plot the given projects (e.g. `owner/repo`). Can be repeated.
+    #[clap(long = "project", value_name = "PROJECT")]
+    pub projects: Vec<String>,
+
+    /// Include the runs recorded on other machines, whose times aren't directly comparable.
+    #[clap(long)]
+    pub all_hosts: bool,
+}
+
+fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let (value, unit) = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((interval, "s"), |i| interval.split_at(i));
+    let secs = match (value.parse::<u64>(), unit) {
+        (Ok(value), "s") => value,
+        (Ok(value), "m") => value * 60,
+        (Ok(value), "h") => value * 3600,
+        _ => {
+            return Err(format!(
+                "invalid interval '{interval}', expected e.g. 30s, 5m or 1h"
+            ));
+        }
+    };
+    if secs == 0 {
+        return Err("the interval must be positive".to_string());
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+fn parse_since(date: &str) -> Result<u64, String> {
+    crate::utils::parse_date(date)
+        .ok_or_else(|| format!("invalid date '{date}', expected YYYY-MM-DD"))
+}
+
+#[derive(Args, Debug)]
+pub struct DoctorConfig {
+    /// Path to the `foundryup` binary to check. Defaults to the one in `PATH`.
+    #[clap(long, env = "FOUNDRYUP_BIN", value_name = "PATH")]
+    pub foundryup_bin: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ListConfig {
+    /// Print the resolved project configurations as JSON.
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct InitConfig {
+    /// Overwrite the configuration file if it already exists.
+    #[clap(long)]
+    pub force: bool,
+
+    /// Only write the skeleton of the configuration, without example projects.
+    #[clap(long)]
+    pub minimal: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DiscoverConfig {
+    /// Search the repositories of this GitHub organization or user.
+    #[clap(long, required_unless_present = "topic")]
+    pub org: Option<String>,
+
+    /// Search the repositories with this topic (e.g. `foundry`).
+    #[clap(long)]
+    pub topic: Option<String>,
+
+    /// Only keep the repositories with at least this many stars.
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    pub min_stars: u64,
+
+    /// Maximum number of repositories to check, by descending stars.
+    #[clap(long, value_name = "N", default_value_t = 30)]
+    pub max_repos: usize,
+
+    /// Append the entries to the configuration file (the one passed to --config, the discovered
+    /// one, or `benchmarks.toml`) instead of printing them.
+    #[clap(long)]
+    pub write: bool,
+
+    /// Token of the GitHub API, for its higher rate limits.
+    #[clap(
+        long,
+        env = "GITHUB_TOKEN",
+        hide_env_values = true,
+        value_name = "TOKEN"
+    )]
+    pub github_token: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct SchemaConfig {
+    /// Kind of results file to print the schema of.
+    #[clap(long, value_enum, default_value_t)]
+    pub kind: SchemaKind,
+}
+
+/// Foundry sources of the `diff` subcommand, along with the repository they are installed from.
+#[derive(Debug)]
+pub struct DiffSources {
+    pub ref_repo: String,
+    pub baseline: Source,
+    pub vs_repo: String,
+    pub comparisons: Vec<Source>,
 }
 
 impl Cli {
+    /// Parses the command line, along with its matches, to tell the arguments that were passed
+    /// from the defaulted ones (see [`is_explicit`]).
+    pub fn parse_with_matches() -> (Self, ArgMatches) {
+        let matches = Self::command().get_matches();
+        let cli = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        (cli, matches)
+    }
+
+    /// Applies the settings of `preset` that the matching flags don't override, `explicit` telling
+    /// whether an argument (by id) was passed.
+    ///
+    /// The projects of the preset are only used if no projects or groups are passed, and its env
+    /// vars and `forge test` args are applied to every project by [`Cli::get_repos`].
+    pub fn apply_preset(&mut self, preset: PresetConfig, explicit: impl Fn(&str) -> bool) {
+        if let Some(num_runs) = preset.num_runs
+            && !explicit("num_runs")
+        {
+            self.num_runs = num_runs;
+        }
+        if let Some(verbosity) = preset.verbosity
+            && !explicit("verbosity")
+        {
+            self.verbosity = verbosity;
+        }
+        if self.tag.is_empty() {
+            self.tag = preset.tags.clone();
+        }
+        if self.repos.is_none() && self.repo.is_none() && self.group.is_empty() {
+            self.group = preset.groups.clone();
+        }
+        self.preset_config = Some(preset);
+    }
+
+    /// Projects of the preset, if it selects any.
+    fn preset_projects(&self) -> Option<&Vec<String>> {
+        self.preset_config
+            .as_ref()
+            .map(|preset| &preset.projects)
+            .filter(|projects| !projects.is_empty())
+    }
+
     /// Returns the list of projects to benchmark.
     ///
     /// Priority order:
@@ -151,32 +1031,199 @@ impl Cli {
     /// 2. --repos flag with global config flags
     /// 3. TOML config file (custom)
     /// 4. TOML config file (default)
+    ///
+    /// The projects of the config file can be restricted to those of `--group`, and all the
+    /// projects are then filtered by `--tag` and `--exclude-tag`.
     pub fn get_repos(&self) -> Result<Vec<ProjectConfig>> {
+        self.get_repos_with_section().map(|(repos, _)| repos)
+    }
+
+    /// Same as [`Cli::get_repos`], along with the section of the config file the global settings
+    /// were taken from ([`ConfigOrigin::Custom`] or [`ConfigOrigin::Defaults`]), as selected by
+    /// `--config-profile`.
+    ///
+    /// The secrets among the env vars of the projects are registered for redaction.
+    pub fn get_repos_with_section(&self) -> Result<(Vec<ProjectConfig>, ConfigOrigin)> {
+        let (mut repos, section) = self.resolve_repos()?;
+        if let Some(preset) = &self.preset_config {
+            for repo in &mut repos {
+                apply_preset_settings(repo, preset);
+            }
+        }
+        for repo in &mut repos {
+            if self.no_rpc_cache {
+                repo.rpc_cache = None;
+            }
+            repo.require_pinned_fork = self.require_pinned_forks;
+            repo.require_env_example = self.require_env_example;
+            repo.no_network = self.no_network;
+            repo.clean_env = self.clean_env;
+            repo.build_only |= self.build_only;
+            repo.measure_discovery = self.measure_discovery;
+            repo.incremental_runs = self.incremental;
+            if let Some(prepare) = &self.prepare {
+                repo.prepare = Some(prepare.clone());
+            }
+            if let Some(conclude) = &self.conclude {
+                repo.conclude = Some(conclude.clone());
+            }
+            repo.timing_source = self.timing_source;
+            repo.forge_threads = self.forge_threads;
+            if let Some(command) = self.measure {
+                repo.command = command;
+            }
+        }
+        for env_vars in repos.iter().filter_map(ProjectConfig::env_vars) {
+            redact::register(env_vars);
+        }
+        if self.tag.is_empty() && self.exclude_tag.is_empty() {
+            return Ok((repos, section));
+        }
+
+        let has_any =
+            |repo: &ProjectConfig, tags: &[String]| repo.tags.iter().any(|tag| tags.contains(tag));
+        let selected: Vec<ProjectConfig> = repos
+            .iter()
+            .filter(|repo| self.tag.is_empty() || has_any(repo, &self.tag))
+            .filter(|repo| !has_any(repo, &self.exclude_tag))
+            .cloned()
+            .collect();
+        if selected.is_empty() {
+            let available: BTreeSet<&str> = repos
+                .iter()
+                .flat_map(|repo| repo.tags.iter().map(String::as_str))
+                .collect();
+            return Err(eyre!(
+                "No project matches the tag filters. Available tags: {}",
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.into_iter().collect::<Vec<_>>().join(", ")
+                }
+            ));
+        }
+        Ok((selected, section))
+    }
+
+    /// Reads the repository lists of `--repos-file` and `--repos -` (from `stdin`) into
+    /// `--repos`, after the repositories passed directly.
+    ///
+    /// Errors if the file can't be read.
+    pub fn read_repo_lists(&mut self, mut stdin: impl Read) -> Result<()> {
+        if self.repos_file.is_none() && !self.repos.iter().flatten().any(|name| name == "-") {
+            return Ok(());
+        }
+        let mut repos = Vec::new();
+        for name in self.repos.take().into_iter().flatten() {
+            if name == "-" {
+                let mut list = String::new();
+                stdin
+                    .read_to_string(&mut list)
+                    .map_err(|e| eyre!("Failed to read the repositories from stdin: {e}"))?;
+                repos.extend(parse_repo_list(&list));
+            } else {
+                repos.push(name);
+            }
+        }
+        if let Some(path) = &self.repos_file {
+            let list = fs::read_to_string(path)
+                .map_err(|e| eyre!("Failed to read the repositories from {path}: {e}"))?;
+            repos.extend(parse_repo_list(&list));
+        }
+        self.repos = Some(repos);
+        Ok(())
+    }
+
+    /// Removes the projects matching `--exclude` from `repos`.
+    pub fn exclude_repos(&self, repos: Vec<ProjectConfig>) -> (Vec<ProjectConfig>, Excluded) {
+        let is_excluded = |name: &str| {
+            self.exclude
+                .iter()
+                .any(|pattern| utils::name_matches(pattern, name))
+        };
+        let unmatched = self
+            .exclude
+            .iter()
+            .filter(|pattern| {
+                !repos
+                    .iter()
+                    .any(|repo| utils::name_matches(pattern, &repo.name))
+            })
+            .cloned()
+            .collect();
+        let (excluded, repos): (Vec<_>, Vec<_>) =
+            repos.into_iter().partition(|repo| is_excluded(&repo.name));
+        let excluded = Excluded {
+            names: excluded.into_iter().map(|repo| repo.name).collect(),
+            unmatched,
+        };
+        (repos, excluded)
+    }
+
+    /// Loads the config file passed to `--config` (or discovered in its place). Without any, the
+    /// projects passed to `--repos` or `--repo` run with the built-in defaults.
+    fn load_config_file(&self) -> Result<ConfigFile> {
+        match &self.config {
+            Some(path) => ConfigFile::load(path, self.lenient_config),
+            None if self.repos.is_some() || self.repo.is_some() => Ok(ConfigFile::default()),
+            None => Err(eyre!(
+                "No config file found in the current directory, its parents, or \
+                 $XDG_CONFIG_HOME/foundry-benchmarks. Run `foundry-benchmarks init` to write one, \
+                 or pass the projects to benchmark with --repos or --repo"
+            )),
+        }
+    }
+
+    fn resolve_repos(&self) -> Result<(Vec<ProjectConfig>, ConfigOrigin)> {
         if let Some(config) = &self.repo {
             return self.parse_project_config(config);
         }
 
         let mut configs: HashMap<String, ProjectConfig> = HashMap::new();
 
-        let config_path = self.config.as_deref().unwrap_or("benchmarks.toml");
-        let file_config = ConfigFile::load(config_path)?;
+        let file_config = self.load_config_file()?;
 
         let has_cli_overrides = self.repos.is_some()
             || self.deps.is_some()
             || self.remappings.is_some()
             || self.env.is_some();
 
-        let use_custom = file_config.has_custom_config() && !has_cli_overrides;
+        let use_custom = file_config.use_custom(self.config_profile, has_cli_overrides)?;
+        let section = if use_custom {
+            ConfigOrigin::Custom
+        } else {
+            ConfigOrigin::Defaults
+        };
+        let group_projects = file_config.group_projects(&self.group)?;
 
+        // Names of the configured projects, in the order of the config file
+        let mut configured = Vec::new();
         for project_config in file_config.into_project_configs(use_custom) {
+            configured.push(project_config.name.clone());
             configs.insert(project_config.name.clone(), project_config);
         }
 
-        // Handle --repos flag with global overrides
-        if let Some(repo_names) = &self.repos {
-            let mut selected_configs = Vec::new();
+        // Handle --group, whose projects are all configured
+        if !self.group.is_empty() {
+            let repos = group_projects
+                .iter()
+                .filter_map(|name| configs.remove(name))
+                .collect();
+            return Ok((repos, section));
+        }
 
-            for repo_name in repo_names {
+        // Handle --repos flag (or the projects of the preset) with global overrides
+        if let Some(repo_names) = self.repos.as_ref().or(self.preset_projects()) {
+            let mut selected_configs: Vec<ProjectConfig> = Vec::new();
+
+            for repo_name in &expand_repo_patterns(repo_names, &configured)? {
+                // A project listed more than once is benchmarked once
+                if selected_configs
+                    .iter()
+                    .any(|config| &config.name == repo_name)
+                {
+                    continue;
+                }
                 let mut config = configs
                     .remove(repo_name)
                     .unwrap_or_else(|| ProjectConfig::new(repo_name));
@@ -184,32 +1231,47 @@ impl Cli {
                 // Apply global CLI overrides
                 if let Some(deps) = &self.deps {
                     config.config.dependencies = Some(deps.clone());
+                    config.origins.dependencies = Some(ConfigOrigin::Cli);
                 }
                 if let Some(remappings) = &self.remappings {
                     config.config.remappings = Some(remappings.clone());
+                    config.origins.remappings = Some(ConfigOrigin::Cli);
                 }
                 if let Some(env_pairs) = &self.env {
-                    config.config.env_vars = Some(parse_env_pairs(env_pairs)?);
+                    let env_vars = parse_env_pairs(env_pairs)?;
+                    config.origins.env_vars = env_vars
+                        .keys()
+                        .map(|key| (key.clone(), ConfigOrigin::Cli))
+                        .collect();
+                    config.config.env_vars = Some(env_vars);
                 }
 
                 selected_configs.push(config);
             }
 
-            return Ok(selected_configs);
+            return Ok((selected_configs, section));
         }
 
-        Ok(configs.into_values().collect())
+        let repos = configured
+            .iter()
+            .filter_map(|name| configs.remove(name))
+            .collect();
+        Ok((repos, section))
     }
 
     /// Parse project specifications in format "repo" or "repo:json"
-    fn parse_project_config(&self, specs: &[String]) -> Result<Vec<ProjectConfig>> {
+    fn parse_project_config(&self, specs: &[String]) -> Result<(Vec<ProjectConfig>, ConfigOrigin)> {
         let mut file_configs: HashMap<String, ProjectConfig> = HashMap::new();
 
-        let config_path = self.config.as_deref().unwrap_or("benchmarks.toml");
-        let file_config = ConfigFile::load(config_path)?;
+        let file_config = self.load_config_file()?;
 
-        // For --repo flag, we always use defaults since it's an explicit CLI override
-        let use_custom = false;
+        // The --repo flag is an explicit CLI override, so `auto` uses the defaults
+        let use_custom = file_config.use_custom(self.config_profile, true)?;
+        let section = if use_custom {
+            ConfigOrigin::Custom
+        } else {
+            ConfigOrigin::Defaults
+        };
 
         for project_config in file_config.into_project_configs(use_custom) {
             file_configs.insert(project_config.name.clone(), project_config);
@@ -218,28 +1280,45 @@ impl Cli {
         let mut result = Vec::new();
 
         for spec in specs {
-            let config = if let Some(colon_pos) = spec.find(':') {
-                let repo_name = &spec[..colon_pos];
-                let json_str = &spec[colon_pos + 1..];
-
+            let config = if let (repo_name, Some(json_str)) = split_repo_spec(spec) {
                 // Parse JSON config first
-                let json_config: JsonProjectConfig = serde_json::from_str(json_str)
+                let mut json_config: JsonProjectConfig = serde_json::from_str(json_str)
                     .map_err(|e| eyre!("Failed to parse JSON config for '{}': {}", repo_name, e))?;
+                json_config.expand_env_vars();
 
                 // Start with existing config or create new
                 let mut base_config = file_configs
                     .remove(repo_name)
                     .unwrap_or_else(|| ProjectConfig::new(repo_name));
 
+                if json_config.url.is_some() && utils::looks_like_url(repo_name) {
+                    return Err(eyre!(
+                        "'{repo_name}' is already a URL, pass an `owner/repo` name along with the \
+                         JSON url, or the URL alone"
+                    ));
+                }
+
                 // Merge configs: JSON overrides base
+                let json_origins = ConfigOrigins::of(&json_config, ConfigOrigin::Json);
+                if json_config.url.is_some() {
+                    base_config.config.url = json_config.url;
+                }
                 if json_config.dependencies.is_some() {
                     base_config.config.dependencies = json_config.dependencies;
+                    base_config.origins.dependencies = json_origins.dependencies;
                 }
                 if json_config.remappings.is_some() {
                     base_config.config.remappings = json_config.remappings;
+                    base_config.origins.remappings = json_origins.remappings;
                 }
-                if json_config.env_vars.is_some() {
-                    base_config.config.env_vars = json_config.env_vars;
+                // Env vars are merged key by key, the JSON values winning on conflicts
+                if let Some(json_env_vars) = json_config.env_vars {
+                    base_config
+                        .config
+                        .env_vars
+                        .get_or_insert_with(HashMap::new)
+                        .extend(json_env_vars);
+                    base_config.origins.env_vars.extend(json_origins.env_vars);
                 }
 
                 base_config
@@ -252,42 +1331,405 @@ impl Cli {
             result.push(config);
         }
 
-        Ok(result)
+        Ok((result, section))
+    }
+
+    /// Whether the results of the run are recorded or exported, besides being saved with --save.
+    pub fn exports_results(&self) -> bool {
+        self.record || self.export_openmetrics.is_some() || self.push_gateway.is_some()
+    }
+
+    /// Checks that the invoked command supports `--stop-after`. Only the standard run and `diff`
+    /// stop early, and `diff` needs the build times at least to compare the sources.
+    pub fn check_stop_after(&self) -> Result<()> {
+        if self.stop_after == Stage::Test {
+            return Ok(());
+        }
+        let stage = self
+            .stop_after
+            .to_possible_value()
+            .expect("no skipped stage");
+        let stage = stage.get_name();
+        let command = match &self.command {
+            None => None,
+            Some(Commands::Diff(config)) => {
+                if self.stop_after == Stage::Clone {
+                    return Err(eyre!(
+                        "diff can't --stop-after clone, there would be nothing to compare"
+                    ));
+                }
+                let flag = [
+                    (config.interleave, "--interleave"),
+                    (config.watch.is_some(), "--watch"),
+                    (config.flamegraph.is_some(), "--flamegraph"),
+                    (config.fail_on_regression.is_some(), "--fail-on-regression"),
+                    (config.warn_on_regression.is_some(), "--warn-on-regression"),
+                ]
+                .into_iter()
+                .find_map(|(set, flag)| set.then_some(flag));
+                if let Some(flag) = flag {
+                    return Err(eyre!(
+                        "{flag} needs the test times, it can't be used with --stop-after {stage}"
+                    ));
+                }
+                None
+            }
+            Some(Commands::Matrix(_)) => Some("matrix"),
+            Some(Commands::ProjectDiff(_)) => Some("project-diff"),
+            Some(Commands::Bisect(_)) => Some("bisect"),
+            Some(Commands::Rerun(_)) => Some("rerun"),
+            // The other commands don't run the pipeline.
+            Some(_) => return Ok(()),
+        };
+        if let Some(command) = command {
+            return Err(eyre!("{command} doesn't support --stop-after {stage}"));
+        }
+        if self.record {
+            // The history trends the test times, which an early stop doesn't measure.
+            return Err(eyre!("--record can't be used with --stop-after {stage}"));
+        }
+        Ok(())
+    }
+
+    /// Checks that the invoked command supports `--dry-run`, which only plans the standard run and
+    /// `diff`. `clean` has a `--dry-run` of its own, listing what it would remove.
+    pub fn check_dry_run(&self) -> Result<()> {
+        match &self.command {
+            _ if !self.dry_run => Ok(()),
+            None | Some(Commands::Diff(_) | Commands::Clean(_)) => Ok(()),
+            Some(_) => Err(eyre!(
+                "--dry-run only plans the standard run and diff, not this command"
+            )),
+        }
+    }
+
+    /// Returns the arguments of the `diff` subcommand, if invoked.
+    pub fn diff_config(&self) -> Option<&DiffConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Diff(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `list` subcommand, if invoked.
+    pub fn list_config(&self) -> Option<&ListConfig> {
+        match self.command.as_ref() {
+            Some(Commands::List(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `clean` subcommand, if invoked.
+    pub fn clean_config(&self) -> Option<&CleanConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Clean(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `project-diff` subcommand, if invoked.
+    pub fn project_diff_config(&self) -> Option<&ProjectDiffConfig> {
+        match self.command.as_ref() {
+            Some(Commands::ProjectDiff(config)) => Some(config),
+            _ => None,
+        }
     }
 
-    pub fn get_cmd(&self) -> Result<Option<(&String, Source, Source)>> {
-        if let Some(Commands::Diff(config)) = self.command.as_ref() {
+    /// Returns the arguments of the `bisect` subcommand, if invoked.
+    pub fn bisect_config(&self) -> Option<&BisectConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Bisect(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `rerun` subcommand, if invoked.
+    pub fn rerun_config(&self) -> Option<&RerunConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Rerun(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `history` subcommand, if invoked.
+    pub fn history_config(&self) -> Option<&HistoryConfig> {
+        match self.command.as_ref() {
+            Some(Commands::History(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `doctor` subcommand, if invoked.
+    pub fn doctor_config(&self) -> Option<&DoctorConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Doctor(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Whether the `validate` subcommand was invoked.
+    pub fn is_validate(&self) -> bool {
+        matches!(self.command, Some(Commands::Validate))
+    }
+
+    /// Returns the arguments of the `init` subcommand, if invoked.
+    pub fn init_config(&self) -> Option<&InitConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Init(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `discover` subcommand, if invoked.
+    pub fn discover_config(&self) -> Option<&DiscoverConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Discover(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `schema` subcommand, if invoked.
+    pub fn schema_config(&self) -> Option<&SchemaConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Schema(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `compare` subcommand, if invoked.
+    pub fn compare_config(&self) -> Option<&CompareConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Compare(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `matrix` subcommand, if invoked.
+    pub fn matrix_config(&self) -> Option<&MatrixConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Matrix(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of the `report` subcommand, if invoked.
+    pub fn report_config(&self) -> Option<&ReportConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Report(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    pub fn merge_config(&self) -> Option<&MergeConfig> {
+        match self.command.as_ref() {
+            Some(Commands::Merge(config)) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Returns the reference and comparison sources of the `diff` subcommand (and their
+    /// repositories), if invoked.
+    pub fn get_cmd(&self) -> Result<Option<DiffSources>> {
+        if let Some(config) = self.diff_config() {
             let baseline = match (
                 &config.reference_source.ref_version,
                 &config.reference_source.ref_branch,
+                &config.reference_source.baseline_file,
+                &config.reference_source.ref_env,
+                config.reference_source.ref_current,
             ) {
-                (Some(version), None) => Source::Version(version),
-                (None, Some(branch)) => Source::Branch(branch),
+                (Some(version), None, None, None, false) => Source::Version(version.clone()),
+                (None, Some(branch), None, None, false) => Source::Branch(branch.clone()),
+                (None, None, Some(path), None, false) => Source::Recorded {
+                    path: path.clone(),
+                    forge: None,
+                },
+                (None, None, None, Some(pairs), false) => env_source(pairs)?,
+                (None, None, None, None, true) => Source::Current { forge: None },
                 _ => {
                     return Err(eyre!("(single) Foundry reference source is required"));
                 }
             };
 
-            let comparison = match (
-                &config.comparison_source.vs_version,
-                &config.comparison_source.vs_branch,
-            ) {
-                (Some(version), None) => Source::Version(version),
-                (None, Some(branch)) => Source::Branch(branch),
-                _ => {
-                    return Err(eyre!("(single) Foundry comparison source is required"));
-                }
-            };
+            let mut comparisons = config.comparison_source.sources.clone();
+            if let Some(pairs) = &config.comparison_source.vs_env {
+                comparisons.push(env_source(pairs)?);
+            }
+            if comparisons.is_empty() {
+                return Err(eyre!("At least one Foundry comparison source is required"));
+            }
+            if config.watch.is_some() && !matches!(comparisons.as_slice(), [Source::Branch(_)]) {
+                return Err(eyre!(
+                    "--watch requires a single comparison branch (--vs-branch)"
+                ));
+            }
+            if config.vs_label.len() > comparisons.len() {
+                return Err(eyre!(
+                    "Got {} --vs-label values for {} comparison source(s)",
+                    config.vs_label.len(),
+                    comparisons.len()
+                ));
+            }
+
+            // Comparing a set of environment variables against itself would measure nothing.
+            if let (Source::Env { vars, label }, [Source::Env { vars: vs_vars, .. }]) =
+                (&baseline, comparisons.as_slice())
+                && vars == vs_vars
+            {
+                return Err(eyre!(
+                    "The reference and comparison environments are identical: {label}"
+                ));
+            }
 
-            return Ok(Some((&config.foundry_repo, baseline, comparison)));
+            return Ok(Some(DiffSources {
+                ref_repo: config
+                    .ref_repo
+                    .as_ref()
+                    .unwrap_or(&config.foundry_repo)
+                    .clone(),
+                baseline,
+                vs_repo: config
+                    .vs_repo
+                    .as_ref()
+                    .unwrap_or(&config.foundry_repo)
+                    .clone(),
+                comparisons,
+            }));
         }
 
         Ok(None)
     }
 }
 
+/// Whether the argument `id` was passed on the command line (or with its env var), rather than
+/// left to its default.
+pub fn is_explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches
+        .value_source(id)
+        .is_some_and(|source| source != ValueSource::DefaultValue)
+}
+
+/// Sets the `forge test` args and env vars of `preset` on `repo`. The env vars passed on the
+/// command line (with `--env` or `--repo`) win over the preset ones.
+fn apply_preset_settings(repo: &mut ProjectConfig, preset: &PresetConfig) {
+    repo.test_args = preset.test_args.clone();
+    for (key, value) in preset.env_vars.iter().flatten() {
+        if matches!(
+            repo.origins.env_vars.get(key),
+            Some(ConfigOrigin::Cli | ConfigOrigin::Json)
+        ) {
+            continue;
+        }
+        repo.config
+            .env_vars
+            .get_or_insert_with(HashMap::new)
+            .insert(key.clone(), value.clone());
+        repo.origins
+            .env_vars
+            .insert(key.clone(), ConfigOrigin::Preset);
+    }
+}
+
+/// Expands the glob patterns among `names` (see [`utils::is_glob`]) into the configured projects
+/// they match, in the order of `configured`. Other names are kept verbatim, configured or not.
+///
+/// Errors if a pattern matches none of the configured projects.
+fn expand_repo_patterns(names: &[String], configured: &[String]) -> Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for name in names {
+        if !utils::is_glob(name) {
+            expanded.push(name.clone());
+            continue;
+        }
+        let matches: Vec<&String> = configured
+            .iter()
+            .filter(|project| utils::name_matches(name, project))
+            .collect();
+        if matches.is_empty() {
+            let near_misses = near_misses(name, configured);
+            return Err(eyre!(
+                "No configured project matches '{name}'{}",
+                if near_misses.is_empty() {
+                    String::new()
+                } else {
+                    format!(", did you mean one of: {}?", near_misses.join(", "))
+                }
+            ));
+        }
+        expanded.extend(matches.into_iter().cloned());
+    }
+    Ok(expanded)
+}
+
+/// The configured projects sharing the longest prefix (of at least 3 characters, ignoring case)
+/// with the literal part of the glob `pattern`, at most 3 of them.
+fn near_misses<'a>(pattern: &str, configured: &'a [String]) -> Vec<&'a str> {
+    let prefix = pattern
+        .split(['*', '?'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let shared = |name: &str| {
+        prefix
+            .chars()
+            .zip(name.to_lowercase().chars())
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    let Some(longest) = configured.iter().map(|name| shared(name)).max() else {
+        return Vec::new();
+    };
+    if longest < 3 {
+        return Vec::new();
+    }
+    configured
+        .iter()
+        .filter(|name| shared(name) == longest)
+        .take(3)
+        .map(String::as_str)
+        .collect()
+}
+
+/// Parses a list of repositories, one per line. Blank lines and `#` comments are ignored.
+fn parse_repo_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(|line| line.split_once('#').map_or(line, |(repo, _)| repo).trim())
+        .filter(|repo| !repo.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Projects removed from a run by `--exclude`, see [`Cli::exclude_repos`].
+#[derive(Debug, Default)]
+pub struct Excluded {
+    pub names: Vec<String>,
+    /// Patterns that matched none of the projects.
+    pub unmatched: Vec<String>,
+}
+
+/// Splits a `--repo` spec into the name of the project and its JSON config, if any.
+///
+/// The JSON config starts at the first colon followed by `{`, so that URLs (e.g.
+/// `https://host/owner/repo` or `git@host:owner/repo`) can be passed as names. Other names are
+/// split on their first colon, so that a malformed config is reported rather than taken as part of
+/// the name.
+pub fn split_repo_spec(spec: &str) -> (&str, Option<&str>) {
+    if let Some(pos) = spec.find(":{") {
+        return (&spec[..pos], Some(&spec[pos + 1..]));
+    }
+    match spec.split_once(':') {
+        Some((name, json)) if !utils::looks_like_url(spec) => (name, Some(json)),
+        _ => (spec, None),
+    }
+}
+
+/// Builds an environment source from `KEY=VALUE` pairs.
+fn env_source(pairs: &[String]) -> Result<Source> {
+    Ok(Source::env(parse_env_pairs(pairs)?.into_iter().collect()))
+}
+
 /// Parse environment variable pairs
-fn parse_env_pairs(pairs: &[String]) -> Result<HashMap<String, String>> {
+pub fn parse_env_pairs(pairs: &[String]) -> Result<HashMap<String, String>> {
     let mut env_vars = HashMap::new();
 
     for pair in pairs {
@@ -307,7 +1749,7 @@ fn parse_env_pairs(pairs: &[String]) -> Result<HashMap<String, String>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
+    use std::{collections::BTreeMap, fs};
     use tempfile::tempdir;
 
     #[test]
@@ -358,6 +1800,7 @@ name = "default/project"
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
         };
 
         let repos = cli.get_repos().unwrap();
@@ -366,6 +1809,21 @@ name = "default/project"
         assert_eq!(repos[1].name, "owner/repo2");
     }
 
+    #[test]
+    fn test_log_level_and_verbosity() {
+        let cli = Cli::parse_from(["foundry-benchmarks", "-vvv"]);
+        assert_eq!((cli.verbosity, cli.log_level), (3, LogLevel::Info));
+
+        // The log level doesn't change the verbosity of `forge test`, and vice versa.
+        let cli = Cli::parse_from(["foundry-benchmarks", "--log-level", "debug"]);
+        assert_eq!((cli.verbosity, cli.log_level), (0, LogLevel::Debug));
+        let cli = Cli::parse_from(["foundry-benchmarks", "-vv", "--log-level", "warn"]);
+        assert_eq!((cli.verbosity, cli.log_level), (2, LogLevel::Warn));
+
+        assert!(LogLevel::Error < LogLevel::Warn && LogLevel::Info < LogLevel::Debug);
+        assert!(Cli::try_parse_from(["foundry-benchmarks", "--log-level", "trace"]).is_err());
+    }
+
     #[test]
     fn test_cli_with_global_flags() {
         let dir = tempdir().unwrap();
@@ -382,62 +1840,510 @@ name = "default/project"
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
+        };
+
+        let repos = cli.get_repos().unwrap();
+        assert_eq!(repos.len(), 1);
+
+        let repo = &repos[0];
+        assert_eq!(repo.name, "test/repo");
+        assert_eq!(
+            repo.dependencies().unwrap(),
+            &vec!["forge-std", "openzeppelin"]
+        );
+        assert_eq!(repo.remappings().unwrap(), &vec!["@std/=lib/"]);
+        assert_eq!(
+            repo.env_vars().unwrap().get("RPC_URL"),
+            Some(&"https://test.rpc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cli_with_repo_json_config() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("empty.toml");
+        fs::write(&config_path, "[defaults]\n").unwrap();
+
+        let cli = Cli {
+            repos: None,
+            config: Some(config_path.to_str().unwrap().to_string()),
+            repo: Some(vec![
+                r#"test/repo1:{"dependencies":["dep1","dep2"]}"#.to_string(),
+                r#"test/repo2:{"remappings":["@lib/=lib/"],"env_vars":{"KEY":"value"}}"#
+                    .to_string(),
+                "test/repo3".to_string(),
+            ]),
+            deps: None,
+            remappings: None,
+            env: None,
+            num_runs: 10,
+            verbosity: 0,
+            command: None,
+            ..Default::default()
         };
 
-        let repos = cli.get_repos().unwrap();
-        assert_eq!(repos.len(), 1);
+        let repos = cli.get_repos().unwrap();
+        assert_eq!(repos.len(), 3);
+
+        assert_eq!(repos[0].name, "test/repo1");
+        assert_eq!(repos[0].dependencies().unwrap(), &vec!["dep1", "dep2"]);
+
+        assert_eq!(repos[1].name, "test/repo2");
+        assert_eq!(repos[1].remappings().unwrap(), &vec!["@lib/=lib/"]);
+        assert_eq!(
+            repos[1].env_vars().unwrap().get("KEY"),
+            Some(&"value".to_string())
+        );
+
+        assert_eq!(repos[2].name, "test/repo3");
+        assert!(repos[2].dependencies().is_none());
+    }
+
+    #[test]
+    fn test_cli_tag_filters() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test.toml");
+        fs::write(
+            &config_path,
+            r#"
+[[project]]
+name = "a/fuzz"
+tags = ["fuzz", "large"]
+
+[[project]]
+name = "b/fork"
+tags = ["fork"]
+
+[[project]]
+name = "c/plain"
+"#,
+        )
+        .unwrap();
+        let config = config_path.to_str().unwrap();
+        let names = |args: &[&str]| -> Result<Vec<String>> {
+            let cli = Cli::parse_from(
+                ["foundry-benchmarks", "--config", config]
+                    .iter()
+                    .chain(args),
+            );
+            let mut names: Vec<_> = cli.get_repos()?.into_iter().map(|r| r.name).collect();
+            names.sort();
+            Ok(names)
+        };
+
+        assert_eq!(names(&[]).unwrap().len(), 3);
+        // Multiple tags are OR'd.
+        assert_eq!(
+            names(&["--tag", "fuzz", "--tag", "fork"]).unwrap(),
+            ["a/fuzz", "b/fork"]
+        );
+        assert_eq!(
+            names(&["--tag", "fork,large"]).unwrap(),
+            ["a/fuzz", "b/fork"]
+        );
+        assert_eq!(
+            names(&["--exclude-tag", "large"]).unwrap(),
+            ["b/fork", "c/plain"]
+        );
+        assert_eq!(
+            names(&["--tag", "fuzz", "--exclude-tag", "large"])
+                .unwrap_err()
+                .to_string(),
+            "No project matches the tag filters. Available tags: fork, fuzz, large"
+        );
+
+        let cli = Cli::parse_from(["foundry-benchmarks", "--config", config, "--tag", "fork"]);
+        assert_eq!(cli.get_repos().unwrap()[0].tags, ["fork"]);
+    }
+
+    #[test]
+    fn test_read_repo_lists() {
+        let dir = tempdir().unwrap();
+        let list_path = dir.path().join("repos.txt");
+        fs::write(
+            &list_path,
+            "# corpus\nvectorized/solady\n\n  morpho-org/morpho-blue  # fork tests\na/a\n",
+        )
+        .unwrap();
+        let list = list_path.to_str().unwrap();
+        let config_path = dir.path().join("test.toml");
+        fs::write(
+            &config_path,
+            "[[project]]\nname = \"a/a\"\nremappings = [\"x=y\"]\n",
+        )
+        .unwrap();
+        let config = config_path.to_str().unwrap();
+        let read = |args: &[&str], stdin: &str| {
+            let mut cli = Cli::parse_from(
+                ["foundry-benchmarks", "--config", config]
+                    .iter()
+                    .chain(args),
+            );
+            cli.read_repo_lists(stdin.as_bytes()).map(|_| cli)
+        };
+
+        let cli = read(
+            &["--repos", "a/a,-", "--repos-file", list, "--deps", "d/d"],
+            "b/b\n# c/c\n",
+        )
+        .unwrap();
+        assert_eq!(
+            cli.repos.as_ref().unwrap(),
+            &[
+                "a/a",
+                "b/b",
+                "vectorized/solady",
+                "morpho-org/morpho-blue",
+                "a/a"
+            ]
+        );
+        // The entries resolve like --repos, and duplicates are benchmarked once.
+        let repos = cli.get_repos().unwrap();
+        let names: Vec<_> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["a/a", "b/b", "vectorized/solady", "morpho-org/morpho-blue"]
+        );
+        assert_eq!(repos[0].remappings().unwrap(), &["x=y"]);
+        assert!(repos.iter().all(|r| r.dependencies().unwrap() == &["d/d"]));
+
+        // Without a list, --repos is left alone.
+        let cli = read(&["--repos", "a/a"], "b/b").unwrap();
+        assert_eq!(cli.repos.unwrap(), ["a/a"]);
+        assert!(read(&[], "b/b").unwrap().repos.is_none());
+
+        let missing = dir.path().join("missing.txt");
+        let err = read(&["--repos-file", missing.to_str().unwrap()], "").unwrap_err();
+        assert!(err.to_string().contains("missing.txt"), "{err}");
+        assert!(
+            Cli::try_parse_from([
+                "foundry-benchmarks",
+                "--repos-file",
+                list,
+                "--group",
+                "smoke"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_repos_glob_patterns() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test.toml");
+        fs::write(
+            &config_path,
+            r#"
+[[project]]
+name = "uniswap/v4-periphery"
+remappings = ["x=y"]
+
+[[project]]
+name = "vectorized/solady"
+
+[[project]]
+name = "uniswap/v4-core"
+
+[[project]]
+name = "uniswap/permit2"
+"#,
+        )
+        .unwrap();
+        let config = config_path.to_str().unwrap();
+        let repos = |repos: &str| {
+            Cli::parse_from(["foundry-benchmarks", "--config", config, "--repos", repos])
+                .get_repos()
+                .map(|repos| repos.into_iter().map(|repo| repo.name).collect::<Vec<_>>())
+        };
+
+        // Patterns expand in the order of the config file, and match names case-insensitively.
+        assert_eq!(
+            repos("Uniswap/*").unwrap(),
+            ["uniswap/v4-periphery", "uniswap/v4-core", "uniswap/permit2"]
+        );
+        assert_eq!(
+            repos("a/a,uniswap/v4-?ore,vectorized/*").unwrap(),
+            ["a/a", "uniswap/v4-core", "vectorized/solady"]
+        );
+        // Plain names are used verbatim, and projects matched twice are benchmarked once.
+        assert_eq!(
+            repos("uniswap/v4-core,uniswap/v4-*").unwrap(),
+            ["uniswap/v4-core", "uniswap/v4-periphery"]
+        );
+
+        let err = repos("uniswap/v5-*").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "No configured project matches 'uniswap/v5-*', did you mean one of: uniswap/v4-periphery, uniswap/v4-core?"
+        );
+        let err = repos("*/nope").unwrap_err().to_string();
+        assert_eq!(err, "No configured project matches '*/nope'");
+    }
+
+    #[test]
+    fn test_config_file_order() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test.toml");
+        let names = ["c/c", "a/a", "d/d", "b/b", "e/e"];
+        let projects: String = names
+            .iter()
+            .map(|name| format!("[[project]]\nname = \"{name}\"\n"))
+            .collect();
+        fs::write(&config_path, projects).unwrap();
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "--config",
+            config_path.to_str().unwrap(),
+            "--limit",
+            "2",
+        ]);
+        assert_eq!(cli.limit.map(NonZeroUsize::get), Some(2));
+
+        // The projects keep the order of the config file, that --limit truncates.
+        let repos: Vec<_> = cli
+            .get_repos()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.name)
+            .collect();
+        assert_eq!(repos, names);
+
+        assert!(Cli::try_parse_from(["foundry-benchmarks", "--limit", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_exclude_repos() {
+        let repos = [
+            "vectorized/solady",
+            "vectorized/solmate",
+            "morpho-org/morpho-blue",
+        ]
+        .map(ProjectConfig::new)
+        .to_vec();
+        let exclude = |args: &[&str]| {
+            let cli = Cli::parse_from(["foundry-benchmarks"].iter().chain(args));
+            let (repos, excluded) = cli.exclude_repos(repos.clone());
+            let names: Vec<_> = repos.into_iter().map(|r| r.name).collect();
+            (names, excluded)
+        };
+
+        let (names, excluded) = exclude(&[]);
+        assert_eq!(names.len(), 3);
+        assert!(excluded.names.is_empty() && excluded.unmatched.is_empty());
+
+        let (names, excluded) = exclude(&["--exclude", "vectorized/solady,nope/nope"]);
+        assert_eq!(names, ["vectorized/solmate", "morpho-org/morpho-blue"]);
+        assert_eq!(excluded.names, ["vectorized/solady"]);
+        assert_eq!(excluded.unmatched, ["nope/nope"]);
+
+        let (names, excluded) = exclude(&["--exclude", "vectorized/*", "--exclude", "morpho"]);
+        assert_eq!(names, ["morpho-org/morpho-blue"]);
+        assert_eq!(excluded.names, ["vectorized/solady", "vectorized/solmate"]);
+        // Names match exactly, unless the pattern has a `*`.
+        assert_eq!(excluded.unmatched, ["morpho"]);
+    }
+
+    #[test]
+    fn test_no_config_file() {
+        // Projects passed explicitly run with the built-in defaults.
+        let cli = Cli::parse_from(["foundry-benchmarks", "--repos", "a/a"]);
+        let repos = cli.get_repos().unwrap();
+        assert_eq!(repos[0].name, "a/a");
+        assert!(repos[0].dependencies().is_none());
+        assert_eq!(repos[0].command, Measured::Test);
+        let cli = Cli::parse_from(["foundry-benchmarks", "--repos", "a/a", "--command", "fmt"]);
+        assert_eq!(cli.get_repos().unwrap()[0].command, Measured::Fmt);
+        assert!(!repos[0].build_only);
+        let cli = Cli::parse_from(["foundry-benchmarks", "--repos", "a/a", "--build-only"]);
+        assert!(cli.get_repos().unwrap()[0].build_only);
+        assert_eq!(repos[0].incremental_runs, None);
+        let cli = Cli::parse_from(["foundry-benchmarks", "--repos", "a/a", "--incremental"]);
+        assert_eq!(
+            cli.get_repos().unwrap()[0].incremental_runs,
+            NonZeroUsize::new(3)
+        );
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "--repos",
+            "a/a",
+            "--prepare",
+            "sync",
+            "--conclude",
+            "rm -rf cache",
+        ]);
+        let repo = &cli.get_repos().unwrap()[0];
+        assert_eq!(repo.prepare.as_deref(), Some("sync"));
+        assert_eq!(repo.conclude.as_deref(), Some("rm -rf cache"));
+        assert!(!repo.no_network);
+        let cli = Cli::parse_from(["foundry-benchmarks", "--repos", "a/a", "--no-network"]);
+        assert!(cli.get_repos().unwrap()[0].no_network);
+        assert!(!repo.clean_env);
+        let cli = Cli::parse_from(["foundry-benchmarks", "--repos", "a/a", "--clean-env"]);
+        assert!(cli.get_repos().unwrap()[0].clean_env);
+        let cli = Cli::parse_from(["foundry-benchmarks", "--repos", "a/a", "--incremental=5"]);
+        assert_eq!(
+            cli.get_repos().unwrap()[0].incremental_runs,
+            NonZeroUsize::new(5)
+        );
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "--repo",
+            "a/a:{\"remappings\":[\"x=y\"]}",
+        ]);
+        assert_eq!(
+            cli.get_repos().unwrap()[0].remappings().unwrap(),
+            &vec!["x=y"]
+        );
+
+        let err = Cli::parse_from(["foundry-benchmarks"])
+            .get_repos()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Run `foundry-benchmarks init`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_cli_group() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test.toml");
+        fs::write(
+            &config_path,
+            r#"
+[defaults]
+env_vars = { DEFAULT_VAR = "default_value" }
+
+[[project]]
+name = "a/a"
+tags = ["fuzz"]
 
-        let repo = &repos[0];
-        assert_eq!(repo.name, "test/repo");
+[[project]]
+name = "b/b"
+
+[[project]]
+name = "c/c"
+tags = ["fuzz"]
+
+[groups]
+smoke = ["b/b", "a/a"]
+"#,
+        )
+        .unwrap();
+        let config = config_path.to_str().unwrap();
+        let repos = |args: &[&str]| {
+            Cli::parse_from(
+                ["foundry-benchmarks", "--config", config]
+                    .iter()
+                    .chain(args),
+            )
+            .get_repos()
+        };
+
+        let smoke = repos(&["--group", "smoke"]).unwrap();
+        let names: Vec<_> = smoke.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["b/b", "a/a"]);
         assert_eq!(
-            repo.dependencies().unwrap(),
-            &vec!["forge-std", "openzeppelin"]
+            smoke[0].env_vars().unwrap().get("DEFAULT_VAR"),
+            Some(&"default_value".to_string())
         );
-        assert_eq!(repo.remappings().unwrap(), &vec!["@std/=lib/"]);
-        assert_eq!(
-            repo.env_vars().unwrap().get("RPC_URL"),
-            Some(&"https://test.rpc".to_string())
+
+        // Groups and tags intersect.
+        let fuzz = repos(&["--group", "smoke", "--tag", "fuzz"]).unwrap();
+        assert_eq!(fuzz.len(), 1);
+        assert_eq!(fuzz[0].name, "a/a");
+
+        assert!(repos(&["--group", "nightly"]).is_err());
+        assert!(
+            Cli::try_parse_from(["foundry-benchmarks", "--group", "smoke", "--repos", "a/a"])
+                .is_err()
         );
     }
 
     #[test]
-    fn test_cli_with_repo_json_config() {
+    fn test_cli_preset() {
         let dir = tempdir().unwrap();
-        let config_path = dir.path().join("empty.toml");
-        fs::write(&config_path, "[defaults]\n").unwrap();
+        let config_path = dir.path().join("test.toml");
+        fs::write(
+            &config_path,
+            r#"
+[[project]]
+name = "a/a"
+env_vars = { FOUNDRY_FUZZ_RUNS = "256", KEEP = "1" }
 
-        let cli = Cli {
-            repos: None,
-            config: Some(config_path.to_str().unwrap().to_string()),
-            repo: Some(vec![
-                r#"test/repo1:{"dependencies":["dep1","dep2"]}"#.to_string(),
-                r#"test/repo2:{"remappings":["@lib/=lib/"],"env_vars":{"KEY":"value"}}"#
-                    .to_string(),
-                "test/repo3".to_string(),
-            ]),
-            deps: None,
-            remappings: None,
-            env: None,
-            num_runs: 10,
-            verbosity: 0,
-            command: None,
+[[project]]
+name = "b/b"
+
+[groups]
+smoke = ["b/b"]
+
+[preset.quick]
+num_runs = 1
+verbosity = 3
+groups = ["smoke"]
+
+[preset.fuzz]
+projects = ["a/a"]
+test_args = ["--fuzz-runs", "32"]
+env_vars = { FOUNDRY_FUZZ_RUNS = "32" }
+"#,
+        )
+        .unwrap();
+        let config = config_path.to_str().unwrap();
+        let cli = |preset: &str, args: &[&str]| {
+            let matches = Cli::command().get_matches_from(
+                ["foundry-benchmarks", "--config", config]
+                    .iter()
+                    .chain(args),
+            );
+            let mut cli = Cli::from_arg_matches(&matches).unwrap();
+            let preset = ConfigFile::load(config, false)
+                .unwrap()
+                .preset(preset)
+                .unwrap()
+                .clone();
+            cli.apply_preset(preset, |id| is_explicit(&matches, id));
+            cli
         };
 
-        let repos = cli.get_repos().unwrap();
-        assert_eq!(repos.len(), 3);
-
-        assert_eq!(repos[0].name, "test/repo1");
-        assert_eq!(repos[0].dependencies().unwrap(), &vec!["dep1", "dep2"]);
-
-        assert_eq!(repos[1].name, "test/repo2");
-        assert_eq!(repos[1].remappings().unwrap(), &vec!["@lib/=lib/"]);
+        let quick = cli("quick", &[]);
+        assert_eq!((quick.num_runs, quick.verbosity), (1, 3));
+        let names: Vec<_> = quick
+            .get_repos()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.name)
+            .collect();
+        assert_eq!(names, ["b/b"]);
+
+        // Explicit flags override the preset, even after a subcommand or set to their default.
+        let quick = cli("quick", &["-v", "--num-runs", "10", "--repos", "a/a"]);
+        assert_eq!((quick.num_runs, quick.verbosity), (10, 1));
+        assert_eq!(quick.get_repos().unwrap()[0].name, "a/a");
+        let quick = cli("quick", &["report", "results.json", "--num-runs", "5"]);
+        assert_eq!((quick.num_runs, quick.verbosity), (5, 3));
+
+        let fuzz = cli("fuzz", &[]).get_repos().unwrap();
+        assert_eq!(fuzz.len(), 1);
+        assert_eq!(fuzz[0].test_args, ["--fuzz-runs", "32"]);
+        let env = fuzz[0].env_vars().unwrap();
         assert_eq!(
-            repos[1].env_vars().unwrap().get("KEY"),
-            Some(&"value".to_string())
+            (env["FOUNDRY_FUZZ_RUNS"].as_str(), env["KEEP"].as_str()),
+            ("32", "1")
+        );
+        assert_eq!(
+            fuzz[0].origins.env_vars["FOUNDRY_FUZZ_RUNS"],
+            ConfigOrigin::Preset
         );
 
-        assert_eq!(repos[2].name, "test/repo3");
-        assert!(repos[2].dependencies().is_none());
+        // Per-project JSON settings take precedence over the preset.
+        let fuzz = cli(
+            "fuzz",
+            &["--repo", r#"a/a:{"env_vars":{"FOUNDRY_FUZZ_RUNS":"8"}}"#],
+        );
+        let repos = fuzz.get_repos().unwrap();
+        assert_eq!(repos[0].env_vars().unwrap()["FOUNDRY_FUZZ_RUNS"], "8");
     }
 
     #[test]
@@ -466,6 +2372,7 @@ dependencies = ["forge-std"]
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
         };
 
         let repos = cli.get_repos().unwrap();
@@ -494,6 +2401,7 @@ dependencies = ["forge-std"]
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
         };
 
         let repos = cli.get_repos().unwrap();
@@ -529,6 +2437,7 @@ name = "test/repo"
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
         };
 
         let repos = cli.get_repos().unwrap();
@@ -569,6 +2478,7 @@ name = "test/repo"
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
         };
 
         let repos = cli.get_repos().unwrap();
@@ -593,36 +2503,872 @@ name = "test/repo"
             env: None,
             num_runs: 10,
             verbosity: 0,
-            command: Some(Commands::Diff(DiffConfig {
+            command: Some(Commands::Diff(Box::new(DiffConfig {
                 reference_source: ReferenceSource {
                     ref_version: None,
                     ref_branch: Some("master".to_string()),
+                    ..Default::default()
                 },
                 comparison_source: ComparisonSource {
-                    vs_version: Some("v1.0.0".to_string()),
-                    vs_branch: None,
+                    sources: vec![Source::Version("v1.0.0".to_string())],
+                    ..Default::default()
                 },
                 foundry_repo: "foundry-rs/foundry".to_string(),
-            })),
+                ..Default::default()
+            }))),
+            ..Default::default()
         };
 
         let result = cli.get_cmd().unwrap();
         assert!(result.is_some());
 
-        let (repo, ref_source, vs_source) = result.unwrap();
-        assert_eq!(repo, "foundry-rs/foundry");
+        let DiffSources {
+            ref_repo,
+            baseline: ref_source,
+            vs_repo,
+            comparisons: vs_sources,
+        } = result.unwrap();
+        assert_eq!(ref_repo, "foundry-rs/foundry");
+        assert_eq!(vs_repo, "foundry-rs/foundry");
 
         match ref_source {
             Source::Branch(b) => assert_eq!(b, "master"),
             _ => panic!("Expected branch source"),
         }
 
-        match vs_source {
+        assert_eq!(vs_sources.len(), 1);
+        match &vs_sources[0] {
             Source::Version(v) => assert_eq!(v, "v1.0.0"),
             _ => panic!("Expected version source"),
         }
     }
 
+    #[test]
+    fn test_get_cmd_split_repos() {
+        let args = [
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "my-branch",
+        ];
+
+        // Both sides default to --foundry-repo.
+        let cli = Cli::parse_from(args.into_iter().chain(["--foundry-repo", "fork/foundry"]));
+        let sources = cli.get_cmd().unwrap().unwrap();
+        assert_eq!(sources.ref_repo, "fork/foundry");
+        assert_eq!(sources.vs_repo, "fork/foundry");
+
+        let cli = Cli::parse_from(args.into_iter().chain(["--vs-repo", "myuser/foundry"]));
+        let sources = cli.get_cmd().unwrap().unwrap();
+        assert_eq!(sources.ref_repo, "foundry-rs/foundry");
+        assert_eq!(sources.vs_repo, "myuser/foundry");
+
+        let cli = Cli::parse_from(args.into_iter().chain([
+            "--ref-repo",
+            "a/foundry",
+            "--vs-repo",
+            "b/foundry",
+        ]));
+        let sources = cli.get_cmd().unwrap().unwrap();
+        assert_eq!(sources.ref_repo, "a/foundry");
+        assert_eq!(sources.vs_repo, "b/foundry");
+    }
+
+    #[test]
+    fn test_get_cmd_multiple_comparison_sources() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "my-branch",
+            "--vs-version",
+            "v1.1.0",
+            "--vs-version",
+            "nightly",
+        ]);
+
+        let sources = cli.get_cmd().unwrap().unwrap();
+        assert_eq!(sources.baseline.name(), "stable");
+
+        // In command line order, whatever the flag.
+        let names: Vec<_> = (sources.comparisons.iter())
+            .map(|s| (s.ty(), s.name()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("branch", "my-branch"),
+                ("version", "v1.1.0"),
+                ("version", "nightly")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_cmd_baseline_file() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--baseline-file",
+            "stable.json",
+            "--vs-branch",
+            "my-branch",
+        ]);
+
+        let sources = cli.get_cmd().unwrap().unwrap();
+        assert!(matches!(sources.baseline, Source::Recorded { path, .. } if path == "stable.json"));
+
+        // A recorded baseline is a single source, and can't be interleaved.
+        let args = ["--ref-version", "stable", "--interleave"];
+        for extra in [&args[..2], &args[2..]] {
+            let res = Cli::try_parse_from(
+                [
+                    "foundry-benchmarks",
+                    "diff",
+                    "--baseline-file",
+                    "stable.json",
+                ]
+                .into_iter()
+                .chain(extra.iter().copied())
+                .chain(["--vs-branch", "my-branch"]),
+            );
+            assert!(res.is_err());
+        }
+    }
+
+    #[test]
+    fn test_get_cmd_current_baseline() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-current",
+            "--vs-version",
+            "nightly",
+        ]);
+        let sources = cli.get_cmd().unwrap().unwrap();
+        assert_eq!(sources.baseline, Source::Current { forge: None });
+        assert_eq!(
+            sources.comparisons,
+            [Source::Version("nightly".to_string())]
+        );
+
+        // The current install is a single reference source.
+        let res = Cli::try_parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-current",
+            "--ref-version",
+            "stable",
+            "--vs-version",
+            "nightly",
+        ]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_get_cmd_env_sources() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-env",
+            "FOUNDRY_FUZZ_RUNS=256",
+            "--vs-env",
+            "FOUNDRY_FUZZ_RUNS=1024",
+            "FOUNDRY_FFI=true",
+        ]);
+
+        let sources = cli.get_cmd().unwrap().unwrap();
+        assert_eq!(sources.baseline.ty(), "env");
+        assert_eq!(sources.baseline.name(), "FOUNDRY_FUZZ_RUNS=256");
+        assert_eq!(sources.comparisons.len(), 1);
+        assert_eq!(
+            sources.comparisons[0].name(),
+            "FOUNDRY_FFI=true FOUNDRY_FUZZ_RUNS=1024"
+        );
+
+        // An empty set runs with the default settings.
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-env",
+            "--vs-env",
+            "FOUNDRY_FUZZ_RUNS=1024",
+        ]);
+        let sources = cli.get_cmd().unwrap().unwrap();
+        assert_eq!(sources.baseline.name(), "default env");
+    }
+
+    #[test]
+    fn test_get_cmd_identical_env_sources() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-env",
+            "A=1",
+            "B=2",
+            "--vs-env",
+            "B=2",
+            "A=1",
+        ]);
+
+        let err = cli.get_cmd().unwrap_err();
+        assert!(err.to_string().contains("environments are identical"));
+
+        // Env sources can't be mixed with installed sources.
+        let res = Cli::try_parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-env",
+            "A=1",
+            "--vs-branch",
+            "master",
+        ]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_stop_after() {
+        let cli = Cli::parse_from(["foundry-benchmarks"]);
+        assert_eq!(cli.stop_after, Stage::Test);
+        let cli = Cli::parse_from(["foundry-benchmarks", "--stop-after", "clone"]);
+        assert_eq!(cli.stop_after, Stage::Clone);
+        cli.check_stop_after().unwrap();
+
+        let check = |args: &[&str]| {
+            Cli::parse_from(["foundry-benchmarks"].iter().chain(args))
+                .check_stop_after()
+                .map_err(|e| e.to_string())
+        };
+        let diff = ["diff", "--ref-version", "stable", "--vs-version", "nightly"];
+        assert!(check(&[&diff[..], &["--stop-after", "build"]].concat()).is_ok());
+        assert!(
+            check(&[&diff[..], &["--stop-after", "clone"]].concat())
+                .unwrap_err()
+                .contains("nothing to compare")
+        );
+        assert!(
+            check(&[&diff[..], &["--stop-after", "build", "--interleave"]].concat())
+                .unwrap_err()
+                .contains("--interleave")
+        );
+        assert!(check(&["--stop-after", "build", "--record"]).is_err());
+        assert!(check(&["rerun", "--from", "out.json", "--stop-after", "build"]).is_err());
+        assert!(check(&["list", "--stop-after", "clone"]).is_ok());
+    }
+
+    #[test]
+    fn test_keep_failed() {
+        let cli = Cli::parse_from(["foundry-benchmarks", "--keep-failed"]);
+        assert!(cli.keep_failed && !cli.keep);
+        assert!(Cli::try_parse_from(["foundry-benchmarks", "--keep", "--keep-failed"]).is_err());
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let check = |args: &[&str]| {
+            Cli::parse_from(["foundry-benchmarks"].iter().chain(args))
+                .check_dry_run()
+                .is_ok()
+        };
+        assert!(check(&["--dry-run"]));
+        assert!(check(&[
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-version",
+            "nightly",
+            "--dry-run"
+        ]));
+        assert!(check(&["clean", "--dry-run"]));
+        assert!(!check(&["matrix", "--dry-run"]));
+        assert!(check(&["matrix"]));
+    }
+
+    #[test]
+    fn test_regression_gate_args() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "my-branch",
+            "--fail-on-regression",
+            "5",
+            "--gate-metric",
+            "geomean",
+            "--gate-exclude",
+            "noisy/project",
+            "--warn-on-regression",
+            "2",
+            "--gh-annotations",
+        ]);
+
+        let diff = cli.diff_config().unwrap();
+        assert_eq!(diff.fail_on_regression, Some(5.0));
+        assert_eq!(diff.warn_on_regression, Some(2.0));
+        assert!(diff.gh_annotations);
+        assert_eq!(diff.gate_metric, GateMetric::Geomean);
+        assert_eq!(diff.gate_exclude, ["noisy/project"]);
+
+        // The gate settings are meaningless without a threshold.
+        let res = Cli::try_parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "my-branch",
+            "--gate-metric",
+            "geomean",
+        ]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_source_labels() {
+        let args = [
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "my-branch",
+            "--ref-label",
+            "Stable",
+            "--vs-label",
+            "Optimized",
+        ];
+        let cli = Cli::parse_from(args);
+        let diff = cli.diff_config().unwrap();
+        assert_eq!(diff.ref_label.as_deref(), Some("Stable"));
+        assert_eq!(diff.vs_label, ["Optimized"]);
+        assert!(cli.get_cmd().is_ok());
+
+        // More labels than comparison sources.
+        let cli = Cli::parse_from(args.into_iter().chain(["--vs-label", "Extra"]));
+        let err = cli.get_cmd().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("2 --vs-label values for 1 comparison source")
+        );
+    }
+
+    #[test]
+    fn test_diff_watch() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "my-branch",
+            "--watch",
+            "5m",
+        ]);
+        assert_eq!(
+            cli.diff_config().unwrap().watch,
+            Some(Duration::from_secs(300))
+        );
+        assert!(cli.get_cmd().is_ok());
+
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-version",
+            "nightly",
+            "--watch",
+            "30",
+        ]);
+        assert!(cli.get_cmd().is_err());
+
+        assert!(
+            Cli::try_parse_from([
+                "foundry-benchmarks",
+                "diff",
+                "--ref-version",
+                "stable",
+                "--vs-branch",
+                "my-branch",
+                "--watch",
+                "5m",
+                "--interleave",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_diff_canary() {
+        let diff = [
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "feat",
+        ];
+        let canary = |args: &[&str]| {
+            Cli::parse_from([&diff[..], args].concat())
+                .diff_config()
+                .unwrap()
+                .canary
+                .clone()
+        };
+        assert_eq!(canary(&[]), None);
+        assert_eq!(canary(&["--canary"]), Some(None));
+        assert_eq!(canary(&["--canary=a/a"]), Some(Some("a/a".to_string())));
+        // The project must be attached, so that the flag doesn't swallow the next argument.
+        assert_eq!(canary(&["--canary", "--ref-label", "old"]), Some(None));
+        assert!(Cli::try_parse_from([&diff[..], &["--watch", "5m", "--canary"]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_diff_flamegraph() {
+        let diff = [
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "feat",
+        ];
+        let parse = |args: &[&str]| Cli::try_parse_from([&diff[..], args].concat());
+        let flamegraph = |args: &[&str]| {
+            let cli = parse(args).unwrap();
+            let diff = cli.diff_config().unwrap();
+            (
+                diff.flamegraph.map(NonZeroUsize::get),
+                diff.flamegraph_dir.clone(),
+            )
+        };
+        assert_eq!(flamegraph(&[]), (None, "flamegraphs".to_string()));
+        assert_eq!(
+            flamegraph(&["--flamegraph"]),
+            (Some(3), "flamegraphs".to_string())
+        );
+        assert_eq!(
+            flamegraph(&["--flamegraph", "5", "--flamegraph-dir", "out"]),
+            (Some(5), "out".to_string())
+        );
+        assert!(parse(&["--flamegraph", "0"]).is_err());
+        assert!(parse(&["--flamegraph-dir", "out"]).is_err());
+        assert!(parse(&["--flamegraph", "--watch", "5m"]).is_err());
+
+        // The profiled projects are picked by their test times.
+        let cli = parse(&["--flamegraph", "--stop-after", "build"]).unwrap();
+        let err = cli.check_stop_after().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("--flamegraph needs the test times")
+        );
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("45"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_interval("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_interval("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_interval("2h"), Ok(Duration::from_secs(7200)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("5d").is_err());
+        assert!(parse_interval("m").is_err());
+    }
+
+    #[test]
+    fn test_interactive_flag() {
+        assert!(!Cli::parse_from(["foundry-benchmarks"]).interactive);
+        assert!(Cli::parse_from(["foundry-benchmarks", "--interactive"]).interactive);
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "master",
+            "--interactive",
+        ]);
+        assert!(cli.interactive && cli.diff_config().is_some());
+    }
+
+    #[test]
+    fn test_discover_args() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "discover",
+            "--topic",
+            "foundry",
+            "--min-stars",
+            "100",
+            "--write",
+        ]);
+        let config = cli.discover_config().unwrap();
+        assert_eq!(config.topic.as_deref(), Some("foundry"));
+        assert_eq!((config.min_stars, config.max_repos), (100, 30));
+        assert!(config.write && config.org.is_none());
+        // Either an organization or a topic is needed.
+        assert!(
+            Cli::try_parse_from(["foundry-benchmarks", "discover", "--min-stars", "1"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_failure_flags() {
+        let cli = Cli::parse_from(["foundry-benchmarks"]);
+        assert!(!cli.allow_failures && !cli.full_errors);
+        assert_eq!(cli.error_lines, ui::DEFAULT_ERROR_LINES);
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "matrix",
+            "--variant",
+            "a:FOUNDRY_FUZZ_RUNS=1",
+            "--allow-failures",
+            "--full-errors",
+            "--error-lines",
+            "10",
+        ]);
+        assert!(cli.allow_failures && cli.full_errors && cli.matrix_config().is_some());
+        assert_eq!(cli.error_lines, 10);
+    }
+
+    #[test]
+    fn test_matrix_config() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "matrix",
+            "--variant",
+            "fuzz-256:FOUNDRY_FUZZ_RUNS=256",
+            "--variant",
+            "fuzz-1024:FOUNDRY_FUZZ_RUNS=1024,FOUNDRY_FUZZ_SEED=0x1",
+            "--save",
+            "matrix.json",
+        ]);
+        let matrix = cli.matrix_config().unwrap();
+        assert_eq!(matrix.variants.len(), 2);
+        assert_eq!(matrix.variants[0].name, "fuzz-256");
+        assert_eq!(matrix.variants[1].env.len(), 2);
+        assert_eq!(matrix.save.as_deref(), Some("matrix.json"));
+
+        assert!(
+            Cli::parse_from(["foundry-benchmarks", "matrix"])
+                .matrix_config()
+                .unwrap()
+                .variants
+                .is_empty()
+        );
+        assert!(
+            Cli::try_parse_from(["foundry-benchmarks", "matrix", "--variant", "a:NOVALUE"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_report_config() {
+        let cli = Cli::parse_from(["foundry-benchmarks", "report", "results.json"]);
+        let report = cli.report_config().unwrap();
+        assert_eq!(report.file, "results.json");
+        assert_eq!(report.format, ReportFormat::Markdown);
+        assert!(report.out.is_none());
+
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "report",
+            "results.json",
+            "--format",
+            "csv",
+            "--out",
+            "results.csv",
+        ]);
+        let report = cli.report_config().unwrap();
+        assert_eq!(report.format, ReportFormat::Csv);
+        assert_eq!(report.out.as_deref(), Some("results.csv"));
+
+        assert!(
+            Cli::try_parse_from([
+                "foundry-benchmarks",
+                "report",
+                "results.json",
+                "--format",
+                "pdf"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_merge_config() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "merge",
+            "a.json",
+            "b.json",
+            "c.json",
+            "--out",
+            "merged.json",
+        ]);
+        let merge = cli.merge_config().unwrap();
+        assert_eq!(merge.files, ["a.json", "b.json", "c.json"]);
+        assert_eq!(merge.out, "merged.json");
+
+        // At least two files and an output path are required.
+        assert!(
+            Cli::try_parse_from(["foundry-benchmarks", "merge", "a.json", "--out", "m.json"])
+                .is_err()
+        );
+        assert!(Cli::try_parse_from(["foundry-benchmarks", "merge", "a.json", "b.json"]).is_err());
+    }
+
+    #[test]
+    fn test_clean_config() {
+        let cli = Cli::parse_from(["foundry-benchmarks", "clean", "--cache-only", "--dry-run"]);
+        let clean = cli.clean_config().unwrap();
+        assert!(clean.cache_only && clean.dry_run);
+        assert!(!clean.work_only && !clean.history);
+
+        assert!(
+            Cli::try_parse_from(["foundry-benchmarks", "clean", "--cache-only", "--work-only"])
+                .is_err()
+        );
+        assert!(
+            Cli::try_parse_from(["foundry-benchmarks", "clean", "--work-only", "--history"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_bisect_config() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "bisect",
+            "--good",
+            "v1.2.0",
+            "--bad",
+            "master",
+            "--project",
+            "vectorized/solady",
+        ]);
+        let bisect = cli.bisect_config().unwrap();
+        assert_eq!(bisect.good, "v1.2.0");
+        assert_eq!(bisect.bad, "master");
+        assert_eq!(bisect.project, "vectorized/solady");
+        assert_eq!(bisect.threshold, 5.0);
+        assert_eq!(bisect.runs, 3);
+        assert_eq!(bisect.foundry_repo, "foundry-rs/foundry");
+        assert!(cli.get_cmd().unwrap().is_none());
+
+        assert!(Cli::try_parse_from(["foundry-benchmarks", "bisect", "--good", "v1.2.0"]).is_err());
+    }
+
+    #[test]
+    fn test_project_diff_config() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "project-diff",
+            "--project",
+            "vectorized/solady",
+            "--before",
+            "v0.1.0",
+            "--after",
+            "main",
+        ]);
+        let config = cli.project_diff_config().unwrap();
+        assert_eq!(config.project, "vectorized/solady");
+        assert_eq!(
+            (config.before.as_str(), config.after.as_str()),
+            ("v0.1.0", "main")
+        );
+        assert!(config.save.is_none());
+        assert!(cli.get_cmd().unwrap().is_none());
+
+        assert!(
+            Cli::try_parse_from([
+                "foundry-benchmarks",
+                "project-diff",
+                "--project",
+                "a/a",
+                "--before",
+                "v1"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_rerun_config() {
+        let cli = Cli::parse_from(["foundry-benchmarks", "rerun", "--from", "results.json"]);
+        let rerun = cli.rerun_config().unwrap();
+        assert_eq!(rerun.from, "results.json");
+        assert!(rerun.out.is_none());
+        assert!(!rerun.force);
+        assert!(cli.get_cmd().unwrap().is_none());
+
+        assert!(Cli::try_parse_from(["foundry-benchmarks", "rerun"]).is_err());
+    }
+
+    #[test]
+    fn test_history_trend_config() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "history",
+            "trend",
+            "--project",
+            "a/a",
+            "--last",
+            "8",
+            "--all-hosts",
+        ]);
+        let history = cli.history_config().unwrap();
+        let Some(HistoryCommand::Trend(trend)) = &history.command else {
+            panic!("expected the trend subcommand");
+        };
+        assert_eq!(trend.last, 8);
+        assert_eq!(trend.projects, ["a/a"]);
+        assert!(trend.all_hosts);
+
+        let cli = Cli::parse_from(["foundry-benchmarks", "history", "trend"]);
+        let Some(HistoryCommand::Trend(trend)) = &cli.history_config().unwrap().command else {
+            panic!("expected the trend subcommand");
+        };
+        assert_eq!(trend.last, 20);
+        assert!(!trend.all_hosts);
+
+        // The options of `history` don't apply to `trend`.
+        assert!(
+            Cli::try_parse_from(["foundry-benchmarks", "history", "-n", "5", "trend"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_history_config() {
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "history",
+            "-n",
+            "5",
+            "--project",
+            "a/a",
+            "--project",
+            "b/b",
+            "--since",
+            "2025-06-16",
+        ]);
+        let history = cli.history_config().unwrap();
+        assert_eq!(history.last, 5);
+        assert_eq!(history.projects, ["a/a", "b/b"]);
+        assert_eq!(history.since, Some(1_750_032_000));
+        assert!(cli.get_cmd().unwrap().is_none());
+
+        assert!(
+            Cli::try_parse_from(["foundry-benchmarks", "history", "--since", "last week"]).is_err()
+        );
+
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "diff",
+            "--ref-version",
+            "stable",
+            "--vs-branch",
+            "master",
+            "--record",
+        ]);
+        assert!(cli.record);
+    }
+
+    #[test]
+    fn test_compare_config() {
+        let cli = Cli::parse_from(["foundry-benchmarks", "compare", "old.json", "new.json"]);
+
+        let compare = cli.compare_config().unwrap();
+        assert_eq!(compare.old, "old.json");
+        assert_eq!(compare.new, "new.json");
+        assert_eq!(compare.foundry_repo, "foundry-rs/foundry");
+        assert!(cli.get_cmd().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_repo_json_url() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test.toml");
+        fs::write(
+            &config_path,
+            "[[project]]\nname = \"a/a\"\nurl = \"me/a-fork\"\n",
+        )
+        .unwrap();
+        let config_path = config_path.to_str().unwrap();
+        let repos = |spec: &str| {
+            Cli::parse_from([
+                "foundry-benchmarks",
+                "--config",
+                config_path,
+                "--repo",
+                spec,
+            ])
+            .get_repos()
+        };
+
+        assert_eq!(
+            repos("a/a").unwrap()[0].url(),
+            "https://github.com/me/a-fork"
+        );
+        let repo = &repos(r#"a/a:{"url":"https://mirror.example/a.git"}"#).unwrap()[0];
+        assert_eq!(repo.name, "a/a");
+        assert_eq!(repo.url(), "https://mirror.example/a.git");
+
+        let err = repos(r#"git@github.com:a/a.git:{"url":"me/a"}"#).unwrap_err();
+        assert!(err.to_string().contains("is already a URL"), "{err}");
+    }
+
+    #[test]
+    fn test_repo_spec_urls() {
+        assert_eq!(split_repo_spec("a/a"), ("a/a", None));
+        assert_eq!(
+            split_repo_spec(r#"a/a:{"x":1}"#),
+            ("a/a", Some(r#"{"x":1}"#))
+        );
+        assert_eq!(split_repo_spec("a/a:oops"), ("a/a", Some("oops")));
+        let url = "https://github.com/owner/repo";
+        assert_eq!(split_repo_spec(url), (url, None));
+        assert_eq!(
+            split_repo_spec(r#"git@github.com:owner/repo.git:{"x":{"y":1}}"#),
+            ("git@github.com:owner/repo.git", Some(r#"{"x":{"y":1}}"#))
+        );
+
+        let repos = |specs: &[&str]| {
+            let mut args = vec!["foundry-benchmarks"];
+            for spec in specs {
+                args.extend(["--repo", spec]);
+            }
+            Cli::parse_from(args).get_repos().unwrap()
+        };
+        let repos = repos(&[
+            url,
+            r#"https://gitlab.com/group/repo.git:{"remappings":["x=y"]}"#,
+            "git@github.com:owner/ssh",
+            r#"git@github.com:owner/ssh-json:{"env_vars":{"A":"1"}}"#,
+            r#"owner/short:{"dependencies":["a/b"]}"#,
+        ]);
+        let urls: Vec<_> = repos.iter().map(ProjectConfig::url).collect();
+        assert_eq!(
+            urls,
+            [
+                url,
+                "https://gitlab.com/group/repo.git",
+                "git@github.com:owner/ssh",
+                "git@github.com:owner/ssh-json",
+                "https://github.com/owner/short",
+            ]
+        );
+        assert_eq!(repos[1].remappings().unwrap(), &["x=y"]);
+        assert_eq!(repos[3].env_vars().unwrap()["A"], "1");
+        assert_eq!(repos[4].dependencies().unwrap(), &["a/b"]);
+    }
+
     #[test]
     fn test_repo_json_merge_with_config() {
         let dir = tempdir().unwrap();
@@ -645,7 +3391,7 @@ env_vars = { CONFIG_VAR = "config_value" }
             repos: None,
             config: Some(config_path.to_str().unwrap().to_string()),
             repo: Some(vec![
-                r#"test/repo1:{"dependencies":["override-dep"],"env_vars":{"JSON_VAR":"json_value"}}"#.to_string(),
+                r#"test/repo1:{"dependencies":["override-dep"],"env_vars":{"JSON_VAR":"json_value","CONFIG_VAR":"json_override"}}"#.to_string(),
             ]),
             deps: None,
             remappings: None,
@@ -653,6 +3399,7 @@ env_vars = { CONFIG_VAR = "config_value" }
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
         };
 
         let repos = cli.get_repos().unwrap();
@@ -661,14 +3408,189 @@ env_vars = { CONFIG_VAR = "config_value" }
         let repo = &repos[0];
         // JSON dependencies override config
         assert_eq!(repo.dependencies().unwrap(), &vec!["override-dep"]);
-        // JSON env_vars override config env_vars
+        // JSON env_vars are merged with the config env_vars, winning on conflicts
+        let env_vars = repo.env_vars().unwrap();
+        assert_eq!(env_vars.get("JSON_VAR"), Some(&"json_value".to_string()));
+        assert_eq!(
+            env_vars.get("CONFIG_VAR"),
+            Some(&"json_override".to_string())
+        );
+        assert_eq!(env_vars.get("BASE_VAR"), Some(&"base_value".to_string()));
+        assert_eq!(env_vars.len(), 3);
+    }
+
+    #[test]
+    fn test_repo_json_expands_env_vars() {
+        unsafe { std::env::set_var("FOUNDRY_BENCHMARKS_TEST_JSON_DIR", "/opt/libs") };
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "--repo",
+            r#"a/a:{"dependencies":["org/lib@${FOUNDRY_BENCHMARKS_TEST_JSON_UNSET}"],"remappings":["@x/=${FOUNDRY_BENCHMARKS_TEST_JSON_DIR}/x/"],"env_vars":{"LIBS":"$FOUNDRY_BENCHMARKS_TEST_JSON_DIR"}}"#,
+        ]);
+        let repo = &cli.get_repos().unwrap()[0];
+        assert_eq!(
+            repo.dependencies().unwrap(),
+            &vec!["org/lib@${FOUNDRY_BENCHMARKS_TEST_JSON_UNSET}"]
+        );
+        assert_eq!(repo.remappings().unwrap(), &vec!["@x/=/opt/libs/x/"]);
+        assert_eq!(repo.env_vars().unwrap()["LIBS"], "/opt/libs");
+    }
+
+    #[test]
+    fn test_config_profile() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test.toml");
+        fs::write(
+            &config_path,
+            r#"
+[custom]
+env_vars = { RPC = "custom" }
+
+[defaults]
+env_vars = { RPC = "default" }
+
+[[project]]
+name = "a/a"
+"#,
+        )
+        .unwrap();
+        let config = config_path.to_str().unwrap();
+        let rpc = |args: &[&str]| {
+            let cli = Cli::parse_from(
+                ["foundry-benchmarks", "--config", config]
+                    .iter()
+                    .chain(args),
+            );
+            let (repos, section) = cli.get_repos_with_section().unwrap();
+            (repos[0].env_vars().unwrap()["RPC"].clone(), section)
+        };
+
+        // `auto` switches to the defaults with CLI overrides.
+        assert_eq!(rpc(&[]), ("custom".to_string(), ConfigOrigin::Custom));
+        assert_eq!(
+            rpc(&["--repos", "a/a"]),
+            ("default".to_string(), ConfigOrigin::Defaults)
+        );
+        assert_eq!(
+            rpc(&["--repo", "a/a"]),
+            ("default".to_string(), ConfigOrigin::Defaults)
+        );
+
+        // An explicit profile applies regardless of the other flags.
+        assert_eq!(
+            rpc(&["--config-profile", "custom", "--repos", "a/a"]),
+            ("custom".to_string(), ConfigOrigin::Custom)
+        );
+        assert_eq!(
+            rpc(&["--config-profile", "custom", "--repo", "a/a"]),
+            ("custom".to_string(), ConfigOrigin::Custom)
+        );
+        assert_eq!(
+            rpc(&["--config-profile", "defaults"]),
+            ("default".to_string(), ConfigOrigin::Defaults)
+        );
+
+        fs::write(&config_path, "[[project]]\nname = \"a/a\"\n").unwrap();
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "--config",
+            config,
+            "--config-profile",
+            "custom",
+        ]);
+        assert!(cli.get_repos().is_err());
+    }
+
+    #[test]
+    fn test_config_origins() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("test.toml");
+        fs::write(
+            &config_path,
+            r#"
+[custom]
+env_vars = { CUSTOM_VAR = "custom" }
+
+[defaults]
+env_vars = { BASE_VAR = "base" }
+
+[[project]]
+name = "test/repo1"
+dependencies = ["base-dep"]
+env_vars = { CONFIG_VAR = "config" }
+"#,
+        )
+        .unwrap();
+        let config = config_path.to_str().unwrap();
+
+        let cli = Cli::parse_from(["foundry-benchmarks", "--config", config, "list"]);
+        assert!(cli.list_config().is_some());
+        let repo = &cli.get_repos().unwrap()[0];
+        assert_eq!(repo.origins.dependencies, Some(ConfigOrigin::Project));
+        assert_eq!(repo.origins.remappings, None);
+        assert_eq!(
+            repo.origins.env_vars,
+            BTreeMap::from([
+                ("CONFIG_VAR".to_string(), ConfigOrigin::Project),
+                ("CUSTOM_VAR".to_string(), ConfigOrigin::Custom),
+            ])
+        );
+
+        // CLI overrides fall back to the defaults section.
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "--config",
+            config,
+            "--repos",
+            "test/repo1",
+            "--remappings",
+            "@std/=lib/",
+            "list",
+            "--json",
+        ]);
+        assert!(cli.list_config().unwrap().json);
+        let repo = &cli.get_repos().unwrap()[0];
+        assert_eq!(repo.origins.dependencies, Some(ConfigOrigin::Project));
+        assert_eq!(repo.origins.remappings, Some(ConfigOrigin::Cli));
+        assert_eq!(
+            repo.origins.env_vars.get("BASE_VAR"),
+            Some(&ConfigOrigin::Defaults)
+        );
+
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "--config",
+            config,
+            "--repo",
+            r#"test/repo1:{"env_vars":{"JSON_VAR":"json"}}"#,
+            "list",
+        ]);
+        let repo = &cli.get_repos().unwrap()[0];
+        assert_eq!(repo.origins.dependencies, Some(ConfigOrigin::Project));
+        assert_eq!(
+            repo.origins.env_vars,
+            BTreeMap::from([
+                ("BASE_VAR".to_string(), ConfigOrigin::Defaults),
+                ("CONFIG_VAR".to_string(), ConfigOrigin::Project),
+                ("JSON_VAR".to_string(), ConfigOrigin::Json),
+            ])
+        );
+
+        // Conflicting keys take the JSON value.
+        let cli = Cli::parse_from([
+            "foundry-benchmarks",
+            "--config",
+            config,
+            "--repo",
+            r#"test/repo1:{"env_vars":{"CONFIG_VAR":"json"}}"#,
+            "list",
+        ]);
+        let repo = &cli.get_repos().unwrap()[0];
+        assert_eq!(repo.env_vars().unwrap()["CONFIG_VAR"], "json");
         assert_eq!(
-            repo.env_vars().unwrap().get("JSON_VAR"),
-            Some(&"json_value".to_string())
+            repo.origins.env_vars.get("CONFIG_VAR"),
+            Some(&ConfigOrigin::Json)
         );
-        // Config env_vars that weren't in JSON are lost (JSON overrides completely)
-        assert_eq!(repo.env_vars().unwrap().get("CONFIG_VAR"), None);
-        assert_eq!(repo.env_vars().unwrap().get("BASE_VAR"), None);
     }
 
     #[test]
@@ -687,6 +3609,7 @@ env_vars = { CONFIG_VAR = "config_value" }
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
         };
 
         let repos = cli.get_repos().unwrap();
@@ -709,6 +3632,7 @@ env_vars = { CONFIG_VAR = "config_value" }
             num_runs: 10,
             verbosity: 0,
             command: None,
+            ..Default::default()
         };
 
         let result = cli.get_repos();
@@ -1,106 +1,669 @@
-use eyre::{Context, Result};
+use clap::ValueEnum;
+use eyre::{Context, Result, eyre};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+use yansi::Paint;
 
-use crate::utils::{JsonProjectConfig, ProjectConfig};
+use crate::{
+    benchmark::{Measured, TimingSource},
+    cmd::Verbosity,
+    utils::{
+        self, AnvilConfig, Budget, ConfigOrigin, ConfigOrigins, DEFAULT_BUDGET_TOLERANCE,
+        JsonProjectConfig, ProjectConfig, RemappingsMode, RpcCacheConfig, SyntheticConfig,
+    },
+};
 
+/// Config file written by `init`, and looked up when `--config` isn't passed.
+pub const DEFAULT_CONFIG: &str = "benchmarks.toml";
+/// Names of the config files looked up by [`discover`], by order of preference.
+const CONFIG_NAMES: &[&str] = &[
+    DEFAULT_CONFIG,
+    "benchmarks.yaml",
+    "benchmarks.yml",
+    "benchmarks.json",
+];
+
+/// Keys accepted at the top level of the config file.
+const ROOT_KEYS: &[&str] = &[
+    "include", "custom", "defaults", "project", "variant", "groups", "secrets", "preset",
+];
+/// Keys accepted by the `[custom]` section.
+const GLOBAL_KEYS: &[&str] = &["env_vars", "dependencies", "remappings"];
+/// Keys accepted by the `[defaults]` section.
+const DEFAULTS_KEYS: &[&str] = &["env_vars", "dependencies", "remappings", "github_base_url"];
+/// Keys accepted by a `[[project]]` entry.
+const PROJECT_KEYS: &[&str] = &[
+    "name",
+    "url",
+    "dependencies",
+    "remappings",
+    "remappings_mode",
+    "env_vars",
+    "tags",
+    "required_env",
+    "anvil",
+    "rpc_cache",
+    "command",
+    "script",
+    "script_args",
+    "synthetic",
+    "build_only",
+    "incremental_file",
+    "prepare",
+    "conclude",
+    "needs_network",
+    "expected_build_time",
+    "expected_test_time",
+    "budget_tolerance",
+];
+/// Keys accepted by a `[[variant]]` entry.
+const VARIANT_KEYS: &[&str] = &["name", "env_vars"];
+/// Keys accepted by a `[preset.<name>]` table.
+const PRESET_KEYS: &[&str] = &[
+    "num_runs",
+    "verbosity",
+    "projects",
+    "groups",
+    "tags",
+    "test_args",
+    "env_vars",
+];
+/// Keys accepted by the `[secrets]` section.
+const SECRETS_KEYS: &[&str] = &["patterns", "names"];
+
+/// A config file (`benchmarks.toml`, or its YAML and JSON equivalents): the settings of the
+/// projects, and the groups, variants, presets and secrets of the runs.
+///
+/// ```no_run
+/// use foundry_benchmarks::ConfigFile;
+///
+/// // Resolves the projects on top of the `[defaults]` section.
+/// let projects = ConfigFile::load("benchmarks.toml", false)?.into_project_configs(false);
+/// # Ok::<(), eyre::Report>(())
+/// ```
 #[derive(Debug, Deserialize, Serialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ConfigFile {
+    /// Other config files merged into this one, relative to it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
     #[serde(default)]
     pub custom: CustomConfig,
     #[serde(default)]
     pub defaults: DefaultsConfig,
     #[serde(default)]
     pub project: Vec<ProjectConfigToml>,
+    /// Settings swept by the `matrix` subcommand.
+    #[serde(default)]
+    pub variant: Vec<VariantToml>,
+    /// Named lists of projects, selected with `--group`.
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Env vars whose values are redacted from the output.
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    /// Named settings of a run, selected with `--preset`.
+    #[serde(default)]
+    pub preset: BTreeMap<String, PresetConfig>,
+}
+
+/// A `[preset.<name>]` table: settings of a run, used unless the matching flags are passed.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PresetConfig {
+    pub num_runs: Option<usize>,
+    pub verbosity: Option<Verbosity>,
+    /// Projects to benchmark, like `--repos`.
+    #[serde(default)]
+    pub projects: Vec<String>,
+    /// Groups of projects to benchmark, like `--group`.
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Tags selecting the projects, like `--tag`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Extra arguments of every `forge test` command.
+    #[serde(default)]
+    pub test_args: Vec<String>,
+    /// Env vars set on every project, over the configured ones.
+    pub env_vars: Option<HashMap<String, String>>,
+}
+
+/// The `[secrets]` section: the env vars whose values are replaced by `***` wherever the tool
+/// prints or saves them.
+#[derive(Debug, Deserialize, Serialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SecretsConfig {
+    /// Glob patterns of the names of the secrets, replacing the default ones (see
+    /// `redact::DEFAULT_PATTERNS`).
+    pub patterns: Option<Vec<String>>,
+    /// Names of the secrets, in addition to the ones matching the patterns.
+    #[serde(default)]
+    pub names: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct CustomConfig {
     pub env_vars: Option<HashMap<String, String>>,
+    pub dependencies: Option<Vec<String>>,
+    pub remappings: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct DefaultsConfig {
     pub env_vars: Option<HashMap<String, String>>,
+    pub dependencies: Option<Vec<String>>,
+    pub remappings: Option<Vec<String>>,
+    /// GitHub instance the `owner/repo` names refer to, e.g. a GitHub Enterprise server.
+    pub github_base_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct ProjectConfigToml {
     pub name: String,
+    /// Repository to clone instead of the one of `name`, which stays the label of the project.
+    pub url: Option<String>,
     pub dependencies: Option<Vec<String>>,
     pub remappings: Option<Vec<String>>,
+    /// Whether `remappings` are merged into the project's `remappings.txt`, or replace it.
+    #[serde(default)]
+    pub remappings_mode: RemappingsMode,
+    pub env_vars: Option<HashMap<String, String>>,
+    /// Tags selecting the project with `--tag`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Environment variables that must be set to benchmark the project, e.g. the RPC URL of
+    /// fork tests.
+    #[serde(default)]
+    pub required_env: Vec<String>,
+    /// Local `anvil` node the fork tests run against.
+    pub anvil: Option<AnvilConfig>,
+    /// Local proxy caching the RPC requests of the fork tests.
+    pub rpc_cache: Option<RpcCacheConfig>,
+    /// Command timed instead of `forge test`, e.g. `fmt`.
+    #[serde(default)]
+    pub command: Measured,
+    /// Solidity script timed with `forge script` after the test runs.
+    pub script: Option<String>,
+    /// Extra arguments of the `forge script` commands.
+    #[serde(default)]
+    pub script_args: Vec<String>,
+    /// Parameters of the project generated instead of cloned.
+    pub synthetic: Option<SyntheticConfig>,
+    /// Only build the project, without running its tests.
+    #[serde(default)]
+    pub build_only: bool,
+    /// Source edited before every incremental build (with `--incremental`), relative to the
+    /// project.
+    pub incremental_file: Option<String>,
+    /// Shell command run before every timed test run.
+    pub prepare: Option<String>,
+    /// Shell command run after every timed test run.
+    pub conclude: Option<String>,
+    /// The timed runs need the network, and aren't isolated from it by `--no-network`.
+    #[serde(default)]
+    pub needs_network: bool,
+    /// Expected build time, in seconds.
+    pub expected_build_time: Option<f64>,
+    /// Expected average test time, in seconds.
+    pub expected_test_time: Option<f64>,
+    /// How much slower (in %) than expected the project may be, 10 by default.
+    pub budget_tolerance: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VariantToml {
+    pub name: String,
     pub env_vars: Option<HashMap<String, String>>,
 }
 
+/// Format of a config file, picked from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// `.toml`, and any other extension.
+    Toml,
+    /// `.yaml` or `.yml`.
+    Yaml,
+    /// `.json`.
+    Json,
+}
+
+impl ConfigFormat {
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+}
+
+impl fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Toml => "TOML",
+            Self::Yaml => "YAML",
+            Self::Json => "JSON",
+        })
+    }
+}
+
+/// Reads the config file at `path` (without its includes) in the format of its extension, as a
+/// TOML table.
+pub fn read_raw(path: &Path) -> Result<toml::Table> {
+    let display = path.display();
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read config file: {display}"))?;
+    let format = ConfigFormat::of(path);
+    let message = || format!("Failed to parse {format} config file: {display}");
+    match format {
+        ConfigFormat::Toml => toml::from_str(&contents).wrap_err_with(message),
+        ConfigFormat::Yaml => serde_yaml::from_str(&contents).wrap_err_with(message),
+        ConfigFormat::Json => serde_json::from_str(&contents).wrap_err_with(message),
+    }
+}
+
+/// Table of the config file holding an unknown key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigTable {
+    Root,
+    /// The `[custom]`, `[defaults]` or `[secrets]` section.
+    Global(&'static str),
+    /// A `[[project]]` entry, and its name if it has one.
+    Project(Option<String>),
+    /// A `[[variant]]` entry, and its name if it has one.
+    Variant(Option<String>),
+    /// A `[preset.<name>]` table.
+    Preset(String),
+}
+
+impl fmt::Display for ConfigTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Root => f.write_str("the top level"),
+            Self::Global(section) => write!(f, "[{section}]"),
+            Self::Project(name) => write!(f, "[[project]] {}", name.as_deref().unwrap_or("?")),
+            Self::Variant(name) => write!(f, "[[variant]] {}", name.as_deref().unwrap_or("?")),
+            Self::Preset(name) => write!(f, "[preset.{name}]"),
+        }
+    }
+}
+
+/// A key of the config file that isn't read by [`ConfigFile`], e.g. a misspelled field.
+#[derive(Debug)]
+pub struct UnknownKey {
+    pub table: ConfigTable,
+    pub key: String,
+    /// Closest known key of the table, if any is close enough to be a typo.
+    pub suggestion: Option<&'static str>,
+    /// Config file holding the key, which may be included by the loaded one.
+    pub path: String,
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown key `{}` in {}", self.key, self.table)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, ", did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Section of the config file providing the global settings, selected with `--config-profile`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ConfigProfile {
+    /// `[custom]` if it has any settings and no CLI override is passed, `[defaults]` otherwise.
+    #[default]
+    Auto,
+    Custom,
+    Defaults,
+}
+
+impl fmt::Display for ConfigProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Custom => "custom",
+            Self::Defaults => "defaults",
+        })
+    }
+}
+
 impl ConfigFile {
-    pub fn load(path: &str) -> Result<Self> {
-        let contents = std::fs::read_to_string(path)
-            .wrap_err_with(|| format!("Failed to read config file: {path}"))?;
-        let mut config: ConfigFile = toml::from_str(&contents)
-            .wrap_err_with(|| format!("Failed to parse TOML config file: {path}"))?;
+    /// Loads the config file at `path`.
+    ///
+    /// Unknown keys are an error, unless `lenient` is set, in which case they are ignored with a
+    /// warning.
+    pub fn load(path: &str, lenient: bool) -> Result<Self> {
+        let (config, unknown) = Self::load_lenient(path)?;
+        if unknown.is_empty() {
+            return Ok(config);
+        }
+        if !lenient {
+            let keys: String = unknown
+                .iter()
+                .map(|key| {
+                    if key.path == path {
+                        format!("\n - {key}")
+                    } else {
+                        format!("\n - {key}, in {}", key.path)
+                    }
+                })
+                .collect();
+            return Err(eyre!(
+                "{} Unknown keys in config file {path}:{keys}\nFix them, or pass --lenient-config to ignore them",
+                Paint::red("ERROR:").bold()
+            ));
+        }
+        for key in &unknown {
+            eprintln!(
+                "{} {}: {key}, ignoring it",
+                key.path,
+                Paint::yellow("WARNING:").bold()
+            );
+        }
+        Ok(config)
+    }
+
+    /// Loads the config file at `path` along with the files it includes, ignoring unknown keys.
+    /// Returns them along with the config.
+    pub fn load_lenient(path: &str) -> Result<(Self, Vec<UnknownKey>)> {
+        let mut unknown = Vec::new();
+        let mut config = Self::load_file(Path::new(path), &mut Vec::new(), &mut unknown)?;
 
-        // Expand environment variables in config
+        config
+            .check_groups()
+            .wrap_err_with(|| format!("Invalid config file: {path}"))?;
+        if let Some(url) = &config.defaults.github_base_url {
+            utils::parse_github_url(url)
+                .map_err(|e| eyre!("Invalid github_base_url in [defaults]: {e}"))
+                .wrap_err_with(|| format!("Invalid config file: {path}"))?;
+        }
+
+        // Expand environment variables once all the files are merged, so that every file expands
+        // them the same way.
         config.expand_env_vars();
-        Ok(config)
+        Ok((config, unknown))
     }
 
-    fn expand_env_vars(&mut self) {
-        // Expand custom env vars
-        if let Some(env_vars) = &mut self.custom.env_vars {
-            for (_, value) in env_vars.iter_mut() {
-                // shellexpand::env returns the original string if expansion fails
-                *value = shellexpand::env(value)
-                    .unwrap_or_else(|_| value.as_str().into())
-                    .into_owned();
+    /// Loads the config file at `path`, merged with the files it includes (recursively).
+    ///
+    /// `stack` holds the files being included, to detect cycles.
+    fn load_file(
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        unknown: &mut Vec<UnknownKey>,
+    ) -> Result<Self> {
+        let display = path.display().to_string();
+        let canonical = fs::canonicalize(path)
+            .wrap_err_with(|| format!("Failed to read config file: {display}"))?;
+        if let Some(start) = stack.iter().position(|p| *p == canonical) {
+            let cycle: Vec<String> = stack[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(eyre!(
+                "Config files include each other: {}",
+                cycle.join(" -> ")
+            ));
+        }
+
+        let format = ConfigFormat::of(path);
+        let mut raw = read_raw(path)?;
+        unknown.extend(
+            strip_unknown_keys(&mut raw)
+                .into_iter()
+                .map(|key| UnknownKey {
+                    path: display.clone(),
+                    ..key
+                }),
+        );
+        let mut config: ConfigFile = raw
+            .try_into()
+            .wrap_err_with(|| format!("Failed to parse {format} config file: {display}"))?;
+        if config.include.is_empty() {
+            return Ok(config);
+        }
+
+        stack.push(canonical);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut merged = ConfigFile::default();
+        for include in std::mem::take(&mut config.include) {
+            let included = Self::load_file(&dir.join(&include), stack, unknown)
+                .wrap_err_with(|| format!("Failed to include {include} from {display}"))?;
+            merged.merge(included);
+        }
+        stack.pop();
+        merged.merge(config);
+        Ok(merged)
+    }
+
+    /// Merges `other` into this config, `other` taking precedence: its global settings, groups,
+    /// presets and secret patterns override these ones, its secret names are added to these ones, and its
+    /// projects and variants come after these ones (so that its entries win over duplicates, like
+    /// later entries of a single file).
+    fn merge(&mut self, other: Self) {
+        fn merge_env(
+            base: &mut Option<HashMap<String, String>>,
+            other: Option<HashMap<String, String>>,
+        ) {
+            if let Some(other) = other {
+                base.get_or_insert_with(HashMap::new).extend(other);
             }
         }
+        merge_env(&mut self.custom.env_vars, other.custom.env_vars);
+        merge_env(&mut self.defaults.env_vars, other.defaults.env_vars);
+        for (base, other) in [
+            (&mut self.custom.dependencies, other.custom.dependencies),
+            (&mut self.custom.remappings, other.custom.remappings),
+            (&mut self.defaults.dependencies, other.defaults.dependencies),
+            (&mut self.defaults.remappings, other.defaults.remappings),
+        ] {
+            if other.is_some() {
+                *base = other;
+            }
+        }
+        if other.defaults.github_base_url.is_some() {
+            self.defaults.github_base_url = other.defaults.github_base_url;
+        }
+        self.project.extend(other.project);
+        self.variant.extend(other.variant);
+        self.groups.extend(other.groups);
+        if other.secrets.patterns.is_some() {
+            self.secrets.patterns = other.secrets.patterns;
+        }
+        self.secrets.names.extend(other.secrets.names);
+        self.preset.extend(other.preset);
+    }
 
-        // Expand defaults env vars
-        if let Some(env_vars) = &mut self.defaults.env_vars {
-            for (_, value) in env_vars.iter_mut() {
-                *value = shellexpand::env(value)
-                    .unwrap_or_else(|_| value.as_str().into())
-                    .into_owned();
+    /// Ensures that every project referenced by a group is configured.
+    fn check_groups(&self) -> Result<()> {
+        for (group, projects) in &self.groups {
+            let missing: Vec<&str> = projects
+                .iter()
+                .filter(|name| !self.project.iter().any(|p| &p.name == *name))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                return Err(eyre!(
+                    "Group {group} references projects without a [[project]] entry: {}",
+                    missing.join(", ")
+                ));
             }
         }
+        Ok(())
+    }
 
-        // Expand project env vars
-        for project in &mut self.project {
-            if let Some(env_vars) = &mut project.env_vars {
-                for (_, value) in env_vars.iter_mut() {
-                    *value = shellexpand::env(value)
-                        .unwrap_or_else(|_| value.as_str().into())
-                        .into_owned();
+    /// Names of the projects of `groups`, in the order they are listed, without duplicates.
+    ///
+    /// Errors if any of the groups isn't defined.
+    pub fn group_projects(&self, groups: &[String]) -> Result<Vec<String>> {
+        let mut names: Vec<String> = Vec::new();
+        for group in groups {
+            let Some(projects) = self.groups.get(group) else {
+                return Err(eyre!(
+                    "Unknown group: {group}. Available groups: {}",
+                    if self.groups.is_empty() {
+                        "none".to_string()
+                    } else {
+                        self.groups.keys().cloned().collect::<Vec<_>>().join(", ")
+                    }
+                ));
+            };
+            for name in projects {
+                if !names.contains(name) {
+                    names.push(name.clone());
                 }
             }
         }
+        Ok(names)
+    }
+
+    /// The preset `name`.
+    ///
+    /// Errors if there is no such preset, or if it selects both projects and groups.
+    pub fn preset(&self, name: &str) -> Result<&PresetConfig> {
+        let Some(preset) = self.preset.get(name) else {
+            return Err(eyre!(
+                "Unknown preset: {name}. Available presets: {}",
+                if self.preset.is_empty() {
+                    "none".to_string()
+                } else {
+                    self.preset.keys().cloned().collect::<Vec<_>>().join(", ")
+                }
+            ));
+        };
+        if !preset.projects.is_empty() && !preset.groups.is_empty() {
+            return Err(eyre!(
+                "Preset {name} sets both projects and groups, keep only one of them"
+            ));
+        }
+        Ok(preset)
+    }
+
+    /// Expands the env vars, dependencies and remappings of every section, see
+    /// [`expand_env_value`].
+    fn expand_env_vars(&mut self) {
+        let sections = [&mut self.custom.env_vars, &mut self.defaults.env_vars]
+            .into_iter()
+            .chain(self.project.iter_mut().map(|project| &mut project.env_vars))
+            .chain(self.variant.iter_mut().map(|variant| &mut variant.env_vars))
+            .chain(self.preset.values_mut().map(|preset| &mut preset.env_vars));
+        for value in sections
+            .flatten()
+            .flat_map(|env_vars| env_vars.values_mut())
+        {
+            *value = expand_env_value(value);
+        }
+        let lists = [
+            &mut self.custom.dependencies,
+            &mut self.custom.remappings,
+            &mut self.defaults.dependencies,
+            &mut self.defaults.remappings,
+        ]
+        .into_iter()
+        .chain(
+            self.project
+                .iter_mut()
+                .flat_map(|project| [&mut project.dependencies, &mut project.remappings]),
+        );
+        for value in lists.flatten().flatten() {
+            *value = expand_env_value(value);
+        }
+    }
+
+    /// Whether the global settings come from `[custom]` rather than `[defaults]` for `profile`,
+    /// `cli_overrides` being whether any project settings are passed on the command line.
+    ///
+    /// Errors if `[custom]` is forced, but has no settings.
+    pub fn use_custom(&self, profile: ConfigProfile, cli_overrides: bool) -> Result<bool> {
+        match profile {
+            ConfigProfile::Auto => Ok(self.has_custom_config() && !cli_overrides),
+            ConfigProfile::Custom if !self.has_custom_config() => Err(eyre!(
+                "--config-profile custom was passed, but the [custom] section has no settings"
+            )),
+            ConfigProfile::Custom => Ok(true),
+            ConfigProfile::Defaults => Ok(false),
+        }
     }
 
     /// Check if the custom section has any configuration
     pub fn has_custom_config(&self) -> bool {
         self.custom.env_vars.is_some()
+            || self.custom.dependencies.is_some()
+            || self.custom.remappings.is_some()
     }
 
+    /// Resolves the configuration of every project, on top of the global settings: the `[custom]`
+    /// section if `use_custom` is set and it has any configuration, `[defaults]` otherwise.
+    ///
+    /// Project env vars are merged with the global ones, overriding them key by key, whereas the
+    /// project's `dependencies` and `remappings` replace the global ones.
     pub fn into_project_configs(self, use_custom: bool) -> Vec<ProjectConfig> {
-        let global_env_vars = if use_custom && self.custom.env_vars.is_some() {
-            self.custom.env_vars.clone()
-        } else {
-            self.defaults.env_vars.clone()
-        }
-        .unwrap_or_default();
+        let (global_env_vars, global_deps, global_remappings, global_origin) =
+            if use_custom && self.has_custom_config() {
+                let custom = self.custom;
+                (
+                    custom.env_vars,
+                    custom.dependencies,
+                    custom.remappings,
+                    ConfigOrigin::Custom,
+                )
+            } else {
+                let defaults = self.defaults;
+                (
+                    defaults.env_vars,
+                    defaults.dependencies,
+                    defaults.remappings,
+                    ConfigOrigin::Defaults,
+                )
+            };
+        let global_env_vars = global_env_vars.unwrap_or_default();
+        let origin = |project: &Option<Vec<String>>, global: &Option<Vec<String>>| {
+            if project.is_some() {
+                Some(ConfigOrigin::Project)
+            } else {
+                global.as_ref().map(|_| global_origin)
+            }
+        };
 
         self.project
             .into_iter()
             .map(|proj| {
                 // Apply env vars (merge with global)
                 let mut env_vars = global_env_vars.clone();
+                let mut env_origins: BTreeMap<_, _> = env_vars
+                    .keys()
+                    .map(|key| (key.clone(), global_origin))
+                    .collect();
                 if let Some(proj_env_vars) = proj.env_vars {
+                    env_origins.extend(
+                        proj_env_vars
+                            .keys()
+                            .map(|key| (key.clone(), ConfigOrigin::Project)),
+                    );
                     env_vars.extend(proj_env_vars);
                 }
+                let origins = ConfigOrigins {
+                    dependencies: origin(&proj.dependencies, &global_deps),
+                    remappings: origin(&proj.remappings, &global_remappings),
+                    env_vars: env_origins,
+                };
 
                 let json_config = JsonProjectConfig {
-                    dependencies: proj.dependencies,
-                    remappings: proj.remappings,
+                    url: proj.url,
+                    dependencies: proj.dependencies.or_else(|| global_deps.clone()),
+                    remappings: proj.remappings.or_else(|| global_remappings.clone()),
                     env_vars: if env_vars.is_empty() {
                         None
                     } else {
@@ -111,18 +674,857 @@ impl ConfigFile {
                 ProjectConfig {
                     name: proj.name.clone(),
                     config: json_config,
+                    origins,
+                    tags: proj.tags,
+                    required_env: proj.required_env,
+                    test_args: Vec::new(),
+                    anvil: proj.anvil,
+                    rpc_cache: proj.rpc_cache,
+                    command: proj.command,
+                    script: proj.script,
+                    script_args: proj.script_args,
+                    synthetic: proj.synthetic,
+                    budget: (proj.expected_build_time.is_some()
+                        || proj.expected_test_time.is_some())
+                    .then(|| Budget {
+                        build_time: proj.expected_build_time,
+                        test_time: proj.expected_test_time,
+                        tolerance: proj.budget_tolerance.unwrap_or(DEFAULT_BUDGET_TOLERANCE),
+                    }),
+                    build_only: proj.build_only,
+                    measure_discovery: false,
+                    incremental_runs: None,
+                    incremental_file: proj.incremental_file,
+                    prepare: proj.prepare,
+                    conclude: proj.conclude,
+                    remappings_mode: proj.remappings_mode,
+                    timing_source: TimingSource::Process,
+                    forge_threads: None,
+                    require_pinned_fork: false,
+                    require_env_example: false,
+                    needs_network: proj.needs_network,
+                    no_network: false,
+                    clean_env: false,
+                    rev: None,
+                    scratch: false,
                 }
             })
             .collect()
     }
 }
 
+/// Expands a leading `~` to the home directory, and the `$VAR`, `${VAR}` and `${VAR:-default}`
+/// references of `value` to the values of the environment variables.
+///
+/// References to unset variables without a default are kept as is, like a `~` anywhere but at
+/// the start.
+pub fn expand_env_value(value: &str) -> String {
+    shellexpand::full_with_context_no_errors(value, home_dir, |var| env::var(var).ok()).into_owned()
+}
+
+/// The first variable referenced by `value` that is unset and has no default, if any.
+pub fn unset_env_var(value: &str) -> Option<String> {
+    shellexpand::full_with_context(value, home_dir, |var| env::var(var).map(Some))
+        .err()
+        .map(|e| e.var_name)
+}
+
+fn home_dir() -> Option<String> {
+    env::home_dir().map(|path| path.display().to_string())
+}
+
+/// Finds the config file to use when `--config` isn't passed: the first one of `dir` and its
+/// parents up to the filesystem root, or else the one of `config_home` (see [`config_home`]),
+/// under `foundry-benchmarks/`.
+pub fn discover(dir: &Path, config_home: Option<&Path>) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(Path::to_path_buf)
+        .chain(config_home.map(|home| home.join("foundry-benchmarks")))
+        .flat_map(|dir| CONFIG_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// The user's config directory: `$XDG_CONFIG_HOME`, or `~/.config` if unset.
+pub fn config_home() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::home_dir().map(|home| home.join(".config")))
+}
+
+/// Removes the keys of `raw` that aren't read by [`ConfigFile`], and returns them.
+fn strip_unknown_keys(raw: &mut toml::Table) -> Vec<UnknownKey> {
+    let mut unknown = strip_table(raw, ConfigTable::Root, ROOT_KEYS);
+    for (key, value) in raw.iter_mut() {
+        match (key.as_str(), value) {
+            ("custom", toml::Value::Table(table)) => {
+                unknown.extend(strip_table(
+                    table,
+                    ConfigTable::Global("custom"),
+                    GLOBAL_KEYS,
+                ));
+            }
+            ("defaults", toml::Value::Table(table)) => {
+                unknown.extend(strip_table(
+                    table,
+                    ConfigTable::Global("defaults"),
+                    DEFAULTS_KEYS,
+                ));
+            }
+            ("secrets", toml::Value::Table(table)) => {
+                unknown.extend(strip_table(
+                    table,
+                    ConfigTable::Global("secrets"),
+                    SECRETS_KEYS,
+                ));
+            }
+            ("preset", toml::Value::Table(presets)) => {
+                for (name, preset) in presets.iter_mut() {
+                    if let toml::Value::Table(table) = preset {
+                        unknown.extend(strip_table(
+                            table,
+                            ConfigTable::Preset(name.clone()),
+                            PRESET_KEYS,
+                        ));
+                    }
+                }
+            }
+            (key @ ("project" | "variant"), toml::Value::Array(entries)) => {
+                for table in entries.iter_mut().filter_map(toml::Value::as_table_mut) {
+                    let name = table
+                        .get("name")
+                        .and_then(|n| n.as_str())
+                        .map(str::to_string);
+                    unknown.extend(if key == "project" {
+                        strip_table(table, ConfigTable::Project(name), PROJECT_KEYS)
+                    } else {
+                        strip_table(table, ConfigTable::Variant(name), VARIANT_KEYS)
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    unknown
+}
+
+/// Removes the keys of `table` that aren't in `known`.
+fn strip_table(
+    table: &mut toml::Table,
+    location: ConfigTable,
+    known: &'static [&'static str],
+) -> Vec<UnknownKey> {
+    let keys: Vec<String> = table
+        .keys()
+        .filter(|key| !known.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    keys.into_iter()
+        .map(|key| {
+            table.remove(&key);
+            UnknownKey {
+                table: location.clone(),
+                suggestion: suggest(&key, known),
+                key,
+                path: String::new(),
+            }
+        })
+        .collect()
+}
+
+/// The known key closest to `key`, if it's close enough for `key` to be a typo of it.
+fn suggest(key: &str, known: &[&'static str]) -> Option<&'static str> {
+    let max_distance = (key.chars().count() / 3).max(2);
+    known
+        .iter()
+        .map(|candidate| (edit_distance(key, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Configuration scaffolded by `init`, with example projects.
+const INIT_TEMPLATE: &str = r#"# Configuration of the projects benchmarked by foundry-benchmarks.
+# CLI args (--repos, --repo, --deps, --remappings, --env) always take precedence over this file.
+
+# -------------------------------------------------------------------------------------------------
+# -- CUSTOM ---------------------------------------------------------------------------------------
+# -------------------------------------------------------------------------------------------------
+
+# Custom global settings. When set (and not overridden by CLI args), they replace `[defaults]`.
+[custom]
+# env_vars = { MAINNET_RPC_URL = "${MY_PRIVATE_RPC_URL}" }
+
+# -------------------------------------------------------------------------------------------------
+# -- DEFAULTS -------------------------------------------------------------------------------------
+# -------------------------------------------------------------------------------------------------
+
+# Global settings that apply to all projects, unless overridden.
+[defaults]
+
+# Environment variables can use ${VAR_NAME} syntax to read from your environment.
+env_vars = { MAINNET_RPC_URL = "${MAINNET_RPC_URL}" }
+
+# Dependencies and remappings shared by all projects. A project's own lists replace them.
+# dependencies = ["install", "foundry-rs/forge-std"]
+# remappings = ["forge-std/=lib/forge-std/src/"]
+
+# GitHub instance the `owner/repo` names refer to, e.g. a GitHub Enterprise server.
+# github_base_url = "https://github.example.com"
+
+# -------------------------------------------------------------------------------------------------
+# -- PROJECTS -------------------------------------------------------------------------------------
+# -------------------------------------------------------------------------------------------------
+
+# Each [[project]] entry is a GitHub repository (`owner/repo`) to benchmark.
+
+# Projects that build and test out of the box only need a name.
+[[project]]
+name = "vectorized/solady"
+
+[[project]]
+name = "morpho-org/morpho-blue"
+
+# Projects whose dependencies aren't vendored can install them with `forge install`, and add their
+# own remappings to `remappings.txt` (to the output of `forge remappings` without one), replacing the
+# project's remappings of the same prefixes. `remappings_mode = "replace"` overwrites the file
+# instead.
+[[project]]
+name = "sablier-labs/lockup"
+dependencies = [
+    "install",
+    "foundry-rs/forge-std",
+    "OpenZeppelin/openzeppelin-contracts@v5.0.2",
+    "PaulRBerg/prb-math@v4.1.0",
+    "vectorized/solady",
+    "evmcheb/solarray",
+]
+remappings = [
+    "forge-std/src/=lib/forge-std/src/",
+    "solarray/src/=lib/solarray/src/",
+    "solady/src/=lib/solady/src/",
+    "@openzeppelin/contracts/=lib/openzeppelin-contracts/contracts/",
+    "@prb/math/=lib/prb-math/",
+    "node_modules/=lib/",
+]
+
+# Project-specific environment variables are merged with the global ones, and written to the
+# project's `.env` file.
+[[project]]
+name = "uniswap/v4-core"
+env_vars = { FOUNDRY_FUZZ_RUNS = "256" }
+# Tags select a subset of the projects with --tag (and --exclude-tag).
+tags = ["fuzz"]
+# Variables that must be set (here or in the environment) before anything is cloned. The run fails
+# if one is missing, unless --skip-missing-env is passed.
+# required_env = ["MAINNET_RPC_URL"]
+# Fork tests can run against a local anvil node forking the endpoint of a variable, which is set to
+# the node's endpoint for `forge test`. A port of 0 picks a free one.
+# anvil = { fork_url_env = "MAINNET_RPC_URL", port = 0 }
+# The deterministic RPC requests (at pinned blocks) to the endpoint of a variable can be cached on
+# disk by a local proxy, shared by every run. Disabled with --no-rpc-cache.
+# rpc_cache = { url_env = "MAINNET_RPC_URL" }
+# `command = "fmt"` times `forge fmt --check` instead of `forge test`, without building the project.
+# command = "fmt"
+# A deployment script can be timed with `forge script` (as many times as the tests) after the test
+# runs, reported as a separate metric.
+# script = "script/Deploy.s.sol"
+# script_args = ["--sig", "run()"]
+# `build_only = true` only benchmarks the build of the project, without running its tests.
+# build_only = true
+# With --incremental, the incremental builds edit this source, instead of the first Solidity file
+# of `src`.
+# incremental_file = "src/PoolManager.sol"
+# Shell commands run in the project directory before and after every timed test run (overridden
+# by --prepare and --conclude), e.g. to drop the OS caches. Their time isn't measured, and a failure
+# fails the project.
+# prepare = "sync"
+# conclude = "rm -rf cache/fuzz"
+# With --no-network, the timed runs are isolated from the network (Linux only), except those of
+# the projects that need it, e.g. for fork tests, listed in the report.
+# needs_network = true
+
+# Synthetic projects are generated (deterministically, from a seed) instead of cloned, to benchmark
+# a controlled workload. The name is only a label.
+# [[project]]
+# name = "synthetic/large"
+# synthetic = { contracts = 200, tests_per_contract = 10, inheritance_depth = 3, fuzz = true, seed = 1 }
+
+# Known-good times (in seconds) turn a plain run into a regression tripwire: the projects slower
+# than expected (beyond the tolerance, in %) are highlighted, and fail the run with
+# --enforce-budgets.
+# [[project]]
+# name = "vectorized/solady"
+# expected_build_time = 60.0
+# expected_test_time = 25.0
+# budget_tolerance = 10.0
+
+# -------------------------------------------------------------------------------------------------
+# -- GROUPS ---------------------------------------------------------------------------------------
+# -------------------------------------------------------------------------------------------------
+
+# Named lists of projects, benchmarked with --group. Every project must have a [[project]] entry.
+[groups]
+smoke = ["vectorized/solady", "morpho-org/morpho-blue"]
+
+# -------------------------------------------------------------------------------------------------
+# -- PRESETS --------------------------------------------------------------------------------------
+# -------------------------------------------------------------------------------------------------
+
+# Named settings of a run, selected with --preset. The flags passed explicitly take precedence.
+# A preset selects its projects with `projects`, `groups` and/or `tags`, and can set extra
+# `forge test` args and env vars on every project.
+[preset.quick]
+num_runs = 1
+groups = ["smoke"]
+test_args = ["--fuzz-runs", "32"]
+# env_vars = { FOUNDRY_FUZZ_SEED = "0x1" }
+
+# -------------------------------------------------------------------------------------------------
+# -- VARIANTS -------------------------------------------------------------------------------------
+# -------------------------------------------------------------------------------------------------
+
+# Each [[variant]] entry is a set of Foundry settings swept by `matrix`, unless --variant is passed.
+# [[variant]]
+# name = "fuzz-256"
+# env_vars = { FOUNDRY_FUZZ_RUNS = "256" }
+#
+# [[variant]]
+# name = "fuzz-1024"
+# env_vars = { FOUNDRY_FUZZ_RUNS = "1024" }
+
+# -------------------------------------------------------------------------------------------------
+# -- SECRETS --------------------------------------------------------------------------------------
+# -------------------------------------------------------------------------------------------------
+
+# The values of the env vars matching these patterns, or listed in `names`, are replaced by `***`
+# wherever they are printed or saved. `patterns` replaces the defaults shown here.
+[secrets]
+# patterns = ["*TOKEN*", "*KEY*", "*SECRET*"]
+names = ["MAINNET_RPC_URL"]
+"#;
+
+/// Configuration scaffolded by `init --minimal`.
+const MINIMAL_TEMPLATE: &str = r#"# Configuration of the projects benchmarked by foundry-benchmarks.
+# CLI args (--repos, --repo, --deps, --remappings, --env) always take precedence over this file.
+
+# Custom global settings. When set (and not overridden by CLI args), they replace `[defaults]`.
+[custom]
+
+# Global settings that apply to all projects, unless overridden.
+[defaults]
+
+# Each [[project]] entry is a GitHub repository (`owner/repo`) to benchmark, e.g.:
+# [[project]]
+# name = "owner/repo"
+# dependencies = ["install", "foundry-rs/forge-std"]
+# remappings = ["forge-std/=lib/forge-std/src/"]
+# env_vars = { MAINNET_RPC_URL = "${MAINNET_RPC_URL}" }
+"#;
+
+/// Writes a commented configuration file to `path`.
+///
+/// Refuses to overwrite an existing file, unless `force` is set.
+pub fn init(path: &str, force: bool, minimal: bool) -> Result<()> {
+    let format = ConfigFormat::of(Path::new(path));
+    if format != ConfigFormat::Toml {
+        return Err(eyre!(
+            "init only writes TOML config files, {path} would be read as {format}"
+        ));
+    }
+    if !force && std::path::Path::new(path).exists() {
+        return Err(eyre!(
+            "Config file {path} already exists. Pass --force to overwrite it."
+        ));
+    }
+    let template = if minimal {
+        MINIMAL_TEMPLATE
+    } else {
+        INIT_TEMPLATE
+    };
+    std::fs::write(path, template).wrap_err_with(|| format!("Failed to write config file: {path}"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_init_templates_parse() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        let path = path.to_str().unwrap();
+
+        init(path, false, false).unwrap();
+        let config = ConfigFile::load(path, false).unwrap();
+        assert!(!config.has_custom_config());
+        assert_eq!(config.groups["smoke"].len(), 2);
+        assert_eq!(config.preset("quick").unwrap().num_runs, Some(1));
+        let projects = config.into_project_configs(false);
+        assert_eq!(projects.len(), 4);
+        let lockup = projects
+            .iter()
+            .find(|p| p.name == "sablier-labs/lockup")
+            .unwrap();
+        assert!(lockup.dependencies().is_some());
+        assert!(lockup.remappings().is_some());
+        let v4 = projects
+            .iter()
+            .find(|p| p.name == "uniswap/v4-core")
+            .unwrap();
+        assert!(v4.env_vars().unwrap().contains_key("FOUNDRY_FUZZ_RUNS"));
+        assert_eq!(v4.tags, ["fuzz"]);
+
+        init(path, true, true).unwrap();
+        let config = ConfigFile::load(path, false).unwrap();
+        assert!(config.project.is_empty());
+    }
+
+    #[test]
+    fn test_config_file_github_base_url() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        let write = |url: &str| {
+            fs::write(
+                &path,
+                format!("[defaults]\ngithub_base_url = \"{url}\"\n\n[[project]]\nname = \"a/a\"\n"),
+            )
+            .unwrap();
+            path.to_str().unwrap()
+        };
+        let config = ConfigFile::load(write("https://github.example.com"), false).unwrap();
+        assert_eq!(
+            config.defaults.github_base_url.as_deref(),
+            Some("https://github.example.com")
+        );
+
+        let err = format!(
+            "{:#}",
+            ConfigFile::load(write("https://github.example.com/"), false).unwrap_err()
+        );
+        assert!(err.contains(
+            "Invalid github_base_url in [defaults]: https://github.example.com/ must not end with a slash"
+        ));
+
+        // Only `[defaults]` sets the base URL.
+        fs::write(
+            &path,
+            "[custom]\ngithub_base_url = \"https://github.example.com\"\n",
+        )
+        .unwrap();
+        let err = format!(
+            "{:#}",
+            ConfigFile::load(path.to_str().unwrap(), false).unwrap_err()
+        );
+        assert!(err.contains("unknown key `github_base_url` in [custom]"));
+    }
+
+    #[test]
+    fn test_config_file_unknown_keys() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        fs::write(
+            &path,
+            r#"
+[defaults]
+env_var = { A = "b" }
+
+[[project]]
+name = "owner/repo"
+remmapings = ["a=b"]
+homepage = "https://example.com"
+"#,
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let err = format!("{:#}", ConfigFile::load(path, false).unwrap_err());
+        assert!(err.contains("unknown key `env_var` in [defaults], did you mean `env_vars`?"));
+        assert!(err.contains(
+            "unknown key `remmapings` in [[project]] owner/repo, did you mean `remappings`?"
+        ));
+        assert!(err.contains("unknown key `homepage` in [[project]] owner/repo\n"));
+
+        // Lenient loading ignores the unknown keys.
+        let config = ConfigFile::load(path, true).unwrap();
+        assert_eq!(config.project.len(), 1);
+        assert!(config.project[0].remappings.is_none());
+        let (_, unknown) = ConfigFile::load_lenient(path).unwrap();
+        let keys: Vec<_> = unknown.iter().map(|key| key.key.as_str()).collect();
+        assert_eq!(keys, ["env_var", "homepage", "remmapings"]);
+    }
+
+    #[test]
+    fn test_config_file_presets() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        let path = path.to_str().unwrap();
+        let write = |presets: &str| {
+            fs::write(path, format!("[[project]]\nname = \"a/a\"\n{presets}")).unwrap();
+        };
+
+        write(
+            r#"
+[preset.quick]
+num_runs = 1
+verbosity = 2
+tags = ["fuzz"]
+test_args = ["--fuzz-runs", "32"]
+env_vars = { FOUNDRY_FUZZ_SEED = "0x1" }
+
+[preset.nightly]
+projects = ["a/a"]
+"#,
+        );
+        let config = ConfigFile::load(path, false).unwrap();
+        let quick = config.preset("quick").unwrap();
+        assert_eq!((quick.num_runs, quick.verbosity), (Some(1), Some(2)));
+        assert_eq!(quick.test_args, ["--fuzz-runs", "32"]);
+        assert_eq!(quick.env_vars.as_ref().unwrap()["FOUNDRY_FUZZ_SEED"], "0x1");
+        assert_eq!(config.preset("nightly").unwrap().projects, ["a/a"]);
+
+        let err = config.preset("smoke").unwrap_err().to_string();
+        assert!(err.contains("Unknown preset: smoke. Available presets: nightly, quick"));
+
+        write("[preset.both]\nprojects = [\"a/a\"]\ngroups = [\"smoke\"]\n");
+        let config = ConfigFile::load(path, false).unwrap();
+        assert!(config.preset("both").is_err());
+
+        write("[preset.typo]\nnum_run = 1\n");
+        let err = format!("{:#}", ConfigFile::load(path, false).unwrap_err());
+        assert!(err.contains("unknown key `num_run` in [preset.typo], did you mean `num_runs`?"));
+    }
+
+    #[test]
+    fn test_config_file_groups() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        let path_str = path.to_str().unwrap();
+        let projects = r#"
+[[project]]
+name = "a/a"
+
+[[project]]
+name = "b/b"
+
+[[project]]
+name = "c/c"
+"#;
+        fs::write(
+            &path,
+            format!(
+                "{projects}\n[groups]\nsmoke = [\"b/b\", \"a/a\"]\nfull = [\"a/a\", \"c/c\"]\n"
+            ),
+        )
+        .unwrap();
+        let config = ConfigFile::load(path_str, false).unwrap();
+        assert_eq!(
+            config
+                .group_projects(&["smoke".to_string(), "full".to_string()])
+                .unwrap(),
+            ["b/b", "a/a", "c/c"]
+        );
+        assert_eq!(
+            config
+                .group_projects(&["nightly".to_string()])
+                .unwrap_err()
+                .to_string(),
+            "Unknown group: nightly. Available groups: full, smoke"
+        );
+
+        // Groups can only reference configured projects.
+        fs::write(
+            &path,
+            format!("{projects}\n[groups]\nsmoke = [\"a/a\", \"x/x\", \"y/y\"]\n"),
+        )
+        .unwrap();
+        let err = format!("{:#}", ConfigFile::load(path_str, false).unwrap_err());
+        assert!(
+            err.contains("Group smoke references projects without a [[project]] entry: x/x, y/y")
+        );
+    }
+
+    #[test]
+    fn test_config_file_include() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("base/shared")).unwrap();
+        fs::write(
+            dir.path().join("base/shared/corpus.toml"),
+            r#"
+[defaults]
+env_vars = { RPC = "${FOUNDRY_BENCHMARKS_TEST_INCLUDE_RPC}", FUZZ_RUNS = "256" }
+
+[[project]]
+name = "a/a"
+
+[[project]]
+name = "b/b"
+remappings = ["old=old"]
+"#,
+        )
+        .unwrap();
+        // Includes are relative to the including file.
+        fs::write(
+            dir.path().join("base/base.toml"),
+            "include = [\"shared/corpus.toml\"]\n\n[groups]\nsmoke = [\"a/a\"]\n\n\
+             [secrets]\npatterns = [\"*KEY*\"]\nnames = [\"RPC\"]\n",
+        )
+        .unwrap();
+        let path = dir.path().join("team.toml");
+        fs::write(
+            &path,
+            r#"
+include = ["base/base.toml"]
+
+[defaults]
+env_vars = { FUZZ_RUNS = "1024" }
+
+[[project]]
+name = "b/b"
+remappings = ["new=new"]
+
+[groups]
+smoke = ["a/a", "b/b"]
+
+[secrets]
+names = ["ETH_RPC_URL"]
+"#,
+        )
+        .unwrap();
+
+        unsafe { std::env::set_var("FOUNDRY_BENCHMARKS_TEST_INCLUDE_RPC", "http://rpc") };
+        let config = ConfigFile::load(path.to_str().unwrap(), false).unwrap();
+        assert!(config.include.is_empty());
+        let defaults = config.defaults.env_vars.as_ref().unwrap();
+        assert_eq!(defaults["FUZZ_RUNS"], "1024");
+        assert_eq!(defaults["RPC"], "http://rpc");
+        assert_eq!(config.groups["smoke"], ["a/a", "b/b"]);
+        // Secret names add up, while the patterns are kept unless overridden.
+        assert_eq!(config.secrets.patterns.as_deref().unwrap(), ["*KEY*"]);
+        assert_eq!(config.secrets.names, ["RPC", "ETH_RPC_URL"]);
+
+        // The including file's entry of a duplicate project wins, as the last one.
+        let names: Vec<_> = config.project.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["a/a", "b/b", "b/b"]);
+        let projects = config.into_project_configs(false);
+        let b = projects.iter().rfind(|p| p.name == "b/b").unwrap();
+        assert_eq!(b.remappings().unwrap(), &vec!["new=new"]);
+    }
+
+    #[test]
+    fn test_config_file_expands_lists() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        fs::write(
+            &path,
+            r#"
+[custom]
+dependencies = ["install", "foundry-rs/forge-std@${FOUNDRY_BENCHMARKS_TEST_LISTS_REF}"]
+
+[defaults]
+remappings = ["shared/=${FOUNDRY_BENCHMARKS_TEST_LISTS_DIR}/shared-libs/"]
+
+[[project]]
+name = "a/a"
+dependencies = ["install", "org/lib@${FOUNDRY_BENCHMARKS_TEST_LISTS_UNSET}"]
+remappings = ["@lib/=${FOUNDRY_BENCHMARKS_TEST_LISTS_DIR}/lib/", "~/x=y"]
+"#,
+        )
+        .unwrap();
+        unsafe {
+            env::set_var("FOUNDRY_BENCHMARKS_TEST_LISTS_REF", "v1.9.0");
+            env::set_var("FOUNDRY_BENCHMARKS_TEST_LISTS_DIR", "/home/ci");
+            env::remove_var("FOUNDRY_BENCHMARKS_TEST_LISTS_UNSET");
+        }
+        let home = env::home_dir().unwrap().display().to_string();
+
+        let config = ConfigFile::load(path.to_str().unwrap(), false).unwrap();
+        assert_eq!(
+            config.custom.dependencies.as_ref().unwrap(),
+            &["install", "foundry-rs/forge-std@v1.9.0"]
+        );
+        assert_eq!(
+            config.defaults.remappings.as_ref().unwrap(),
+            &["shared/=/home/ci/shared-libs/"]
+        );
+        let project = &config.project[0];
+        // Unset variables are kept as is.
+        assert_eq!(
+            project.dependencies.as_ref().unwrap(),
+            &["install", "org/lib@${FOUNDRY_BENCHMARKS_TEST_LISTS_UNSET}"]
+        );
+        assert_eq!(
+            project.remappings.as_ref().unwrap(),
+            &["@lib/=/home/ci/lib/".to_string(), format!("{home}/x=y")]
+        );
+    }
+
+    #[test]
+    fn test_config_file_include_errors() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.toml");
+        fs::write(&a, "include = [\"b.toml\"]\n").unwrap();
+        fs::write(
+            dir.path().join("b.toml"),
+            "include = [\"a.toml\"]\n\n[defaults]\nenv_var = {}\n",
+        )
+        .unwrap();
+        let err = format!(
+            "{:#}",
+            ConfigFile::load(a.to_str().unwrap(), false).unwrap_err()
+        );
+        assert!(err.contains("Config files include each other: "), "{err}");
+        assert!(
+            err.contains("a.toml -> ") && err.ends_with("a.toml"),
+            "{err}"
+        );
+
+        fs::write(dir.path().join("b.toml"), "[defaults]\nenv_var = {}\n").unwrap();
+        let err = format!(
+            "{:#}",
+            ConfigFile::load(a.to_str().unwrap(), false).unwrap_err()
+        );
+        assert!(
+            err.contains("unknown key `env_var` in [defaults], did you mean `env_vars`?, in "),
+            "{err}"
+        );
+
+        fs::write(&a, "include = [\"missing.toml\"]\n").unwrap();
+        let err = format!(
+            "{:#}",
+            ConfigFile::load(a.to_str().unwrap(), false).unwrap_err()
+        );
+        assert!(
+            err.contains("Failed to include missing.toml from "),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_config_formats() {
+        let fixture = |name: &str| format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        let load = |name: &str| {
+            let config = ConfigFile::load(&fixture(name), false).unwrap();
+            assert_eq!(config.groups["smoke"], ["vectorized/solady"]);
+            assert_eq!(config.secrets.names, ["RPC_URL"]);
+            serde_json::to_value(config.into_project_configs(false)).unwrap()
+        };
+
+        let toml = load("config.toml");
+        assert_eq!(toml[0]["env_vars"]["RPC_URL"], "http://localhost:8545");
+        assert_eq!(toml[1]["required_env"][0], "MAINNET_RPC_URL");
+        assert_eq!(load("config.yaml"), toml);
+        assert_eq!(load("config.json"), toml);
+    }
+
+    #[test]
+    fn test_config_format_errors() {
+        let dir = tempdir().unwrap();
+        let error = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            format!(
+                "{:#}",
+                ConfigFile::load(path.to_str().unwrap(), false).unwrap_err()
+            )
+        };
+
+        let err = error("bad.yml", "project:\n  - name: a/a\n   tags: [x]\n");
+        assert!(err.contains("Failed to parse YAML config file"), "{err}");
+        assert!(err.contains("line 3"), "{err}");
+        let err = error("bad.json", "{\n  \"project\": [\n}");
+        assert!(err.contains("Failed to parse JSON config file"), "{err}");
+        assert!(err.contains("line 3 column 1"), "{err}");
+        let err = error("bad.toml", "[defaults\n");
+        assert!(err.contains("Failed to parse TOML config file"), "{err}");
+        assert!(err.contains("line 1"), "{err}");
+
+        // Unknown keys are reported the same way in every format.
+        let err = error(
+            "typo.json",
+            r#"{"project": [{"name": "a/a", "tag": ["x"]}]}"#,
+        );
+        assert!(
+            err.contains("unknown key `tag` in [[project]] a/a"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("a/b/c");
+        let home = dir.path().join("home");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(home.join("foundry-benchmarks")).unwrap();
+        let found = |home: Option<&Path>| discover(&nested, home);
+
+        assert_eq!(found(None), None);
+        fs::write(home.join("foundry-benchmarks/benchmarks.toml"), "").unwrap();
+        assert_eq!(
+            found(Some(&home)),
+            Some(home.join("foundry-benchmarks/benchmarks.toml"))
+        );
+
+        // The closest parent wins over the config home, and TOML over the other formats.
+        fs::write(dir.path().join("a/benchmarks.toml"), "").unwrap();
+        fs::write(dir.path().join("a/b/benchmarks.json"), "").unwrap();
+        assert_eq!(
+            found(Some(&home)),
+            Some(dir.path().join("a/b/benchmarks.json"))
+        );
+        fs::write(dir.path().join("a/b/benchmarks.toml"), "").unwrap();
+        assert_eq!(found(None), Some(dir.path().join("a/b/benchmarks.toml")));
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(edit_distance("remmapings", "remappings"), 2);
+        assert_eq!(edit_distance("", "name"), 4);
+        assert_eq!(suggest("dependecies", PROJECT_KEYS), Some("dependencies"));
+        assert_eq!(suggest("projects", ROOT_KEYS), Some("project"));
+        assert_eq!(suggest("nmae", PROJECT_KEYS), Some("name"));
+        assert_eq!(suggest("uri", PROJECT_KEYS), Some("url"));
+        assert_eq!(suggest("homepage", PROJECT_KEYS), None);
+    }
+
+    #[test]
+    fn test_init_refuses_to_overwrite() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        fs::write(&path, "# my config").unwrap();
+        let path = path.to_str().unwrap();
+
+        let err = init(path, false, false).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        assert_eq!(fs::read_to_string(path).unwrap(), "# my config");
+
+        init(path, true, false).unwrap();
+        assert!(ConfigFile::load(path, false).is_ok());
+
+        let yaml = dir.path().join("benchmarks.yaml");
+        assert!(init(yaml.to_str().unwrap(), false, false).is_err());
+        assert!(!yaml.exists());
+    }
+
     #[test]
     fn test_shellexpand_behavior() {
         // Test basic shellexpand behavior
@@ -143,6 +1545,53 @@ mod tests {
         assert_eq!(expanded.as_ref(), "$MISSING_VAR_UNIQUE_12345");
     }
 
+    #[test]
+    fn test_expand_env_value() {
+        unsafe {
+            env::set_var("FOUNDRY_BENCHMARKS_TEST_EXPAND_SET", "value");
+            env::remove_var("FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET");
+        }
+        let home = env::home_dir().unwrap().display().to_string();
+
+        // Defaults only apply to unset variables.
+        assert_eq!(
+            expand_env_value("${FOUNDRY_BENCHMARKS_TEST_EXPAND_SET:-https://fallback.rpc}"),
+            "value"
+        );
+        assert_eq!(
+            expand_env_value("${FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET:-https://fallback.rpc}"),
+            "https://fallback.rpc"
+        );
+
+        // Only a leading tilde expands to the home directory.
+        assert_eq!(expand_env_value("~/keys/foo"), format!("{home}/keys/foo"));
+        assert_eq!(expand_env_value("a/~/keys"), "a/~/keys");
+
+        // Unset variables without a default are kept, without preventing the other expansions.
+        assert_eq!(
+            expand_env_value("$FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET"),
+            "$FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET"
+        );
+        assert_eq!(
+            expand_env_value(
+                "${FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET}/$FOUNDRY_BENCHMARKS_TEST_EXPAND_SET"
+            ),
+            "${FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET}/value"
+        );
+
+        assert_eq!(
+            unset_env_var("${FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET:-x}/~"),
+            None
+        );
+        assert_eq!(
+            unset_env_var(
+                "$FOUNDRY_BENCHMARKS_TEST_EXPAND_SET/$FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET"
+            )
+            .as_deref(),
+            Some("FOUNDRY_BENCHMARKS_TEST_EXPAND_UNSET")
+        );
+    }
+
     #[test]
     fn test_config_file_load() {
         let dir = tempdir().unwrap();
@@ -167,7 +1616,7 @@ name = "test/project2"
 
         fs::write(&config_path, config_content).unwrap();
 
-        let config = ConfigFile::load(config_path.to_str().unwrap()).unwrap();
+        let config = ConfigFile::load(config_path.to_str().unwrap(), false).unwrap();
 
         assert!(config.has_custom_config());
         assert_eq!(
@@ -211,7 +1660,7 @@ env_vars = { PROJECT_RPC = "https://project.rpc/v1" }
 
         fs::write(&config_path, config_content).unwrap();
 
-        let config = ConfigFile::load(config_path.to_str().unwrap()).unwrap();
+        let config = ConfigFile::load(config_path.to_str().unwrap(), false).unwrap();
 
         assert_eq!(
             config.custom.env_vars.as_ref().unwrap().get("RPC_URL"),
@@ -256,7 +1705,7 @@ env_vars = { PROJECT_RPC = "${NONEXISTENT_RPC_URL}/v1" }
 
         fs::write(&config_path, config_content).unwrap();
 
-        let config = ConfigFile::load(config_path.to_str().unwrap()).unwrap();
+        let config = ConfigFile::load(config_path.to_str().unwrap(), false).unwrap();
 
         // Verify that non-existent env vars are preserved as-is
         assert_eq!(
@@ -298,12 +1747,30 @@ env_vars = { PROJECT_RPC = "${NONEXISTENT_RPC_URL}/v1" }
 
         config.project.push(ProjectConfigToml {
             name: "test/project".to_string(),
+            url: None,
             dependencies: Some(vec!["dep1".to_string()]),
             remappings: None,
+            remappings_mode: RemappingsMode::Merge,
             env_vars: Some(HashMap::from([(
                 "PROJECT_VAR".to_string(),
                 "project_value".to_string(),
             )])),
+            tags: Vec::new(),
+            required_env: Vec::new(),
+            anvil: None,
+            rpc_cache: None,
+            command: Measured::Test,
+            script: None,
+            script_args: Vec::new(),
+            synthetic: None,
+            build_only: false,
+            incremental_file: None,
+            prepare: None,
+            conclude: None,
+            needs_network: false,
+            expected_build_time: None,
+            expected_test_time: None,
+            budget_tolerance: None,
         });
 
         let projects = config.into_project_configs(true);
@@ -342,9 +1809,27 @@ env_vars = { PROJECT_RPC = "${NONEXISTENT_RPC_URL}/v1" }
 
         config.project.push(ProjectConfigToml {
             name: "test/project".to_string(),
+            url: None,
             dependencies: None,
             remappings: Some(vec!["@std/=lib/".to_string()]),
+            remappings_mode: RemappingsMode::Merge,
             env_vars: None,
+            tags: Vec::new(),
+            required_env: Vec::new(),
+            anvil: None,
+            rpc_cache: None,
+            command: Measured::Test,
+            script: None,
+            script_args: Vec::new(),
+            synthetic: None,
+            build_only: false,
+            incremental_file: None,
+            prepare: None,
+            conclude: None,
+            needs_network: false,
+            expected_build_time: None,
+            expected_test_time: None,
+            budget_tolerance: None,
         });
 
         let projects = config.into_project_configs(false);
@@ -369,5 +1854,132 @@ env_vars = { PROJECT_RPC = "${NONEXISTENT_RPC_URL}/v1" }
 
         config.custom.env_vars = Some(HashMap::new());
         assert!(config.has_custom_config());
+
+        let mut config = ConfigFile::default();
+        config.custom.remappings = Some(vec!["@std/=lib/forge-std/src/".to_string()]);
+        assert!(config.has_custom_config());
+    }
+
+    #[test]
+    fn test_to_project_configs_global_lists() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        fs::write(
+            &path,
+            r#"
+[custom]
+remappings = ["@custom/=lib/custom/"]
+
+[defaults]
+dependencies = ["install", "foundry-rs/forge-std"]
+remappings = ["@std/=lib/forge-std/src/"]
+
+[[project]]
+name = "a/a"
+
+[[project]]
+name = "b/b"
+dependencies = ["install", "vectorized/solady"]
+required_env = ["MAINNET_RPC_URL"]
+anvil = { fork_url_env = "MAINNET_RPC_URL" }
+rpc_cache = { url_env = "MAINNET_RPC_URL" }
+script = "script/Deploy.s.sol"
+script_args = ["--sig", "run()"]
+build_only = true
+incremental_file = "src/B.sol"
+remappings_mode = "replace"
+prepare = "sync"
+needs_network = true
+
+[[project]]
+name = "c/c"
+command = "fmt"
+synthetic = { contracts = 20, fuzz = true }
+expected_test_time = 2.5
+"#,
+        )
+        .unwrap();
+        let load = || ConfigFile::load(path.to_str().unwrap(), false).unwrap();
+
+        // Project lists replace the global ones.
+        let projects = load().into_project_configs(false);
+        assert_eq!(
+            projects[0].dependencies().unwrap(),
+            &vec!["install", "foundry-rs/forge-std"]
+        );
+        assert_eq!(
+            projects[0].origins.dependencies,
+            Some(ConfigOrigin::Defaults)
+        );
+        assert_eq!(
+            projects[1].dependencies().unwrap(),
+            &vec!["install", "vectorized/solady"]
+        );
+        assert_eq!(
+            projects[1].origins.dependencies,
+            Some(ConfigOrigin::Project)
+        );
+        assert_eq!(
+            projects[1].remappings().unwrap(),
+            &vec!["@std/=lib/forge-std/src/"]
+        );
+        assert!(projects[0].required_env.is_empty());
+        assert_eq!(projects[1].required_env, ["MAINNET_RPC_URL"]);
+        assert!(projects[0].anvil.is_none());
+        assert_eq!(
+            projects[1].rpc_cache.as_ref().unwrap().url_env,
+            "MAINNET_RPC_URL"
+        );
+        assert_eq!(projects[1].script.as_deref(), Some("script/Deploy.s.sol"));
+        assert_eq!(projects[1].script_args, ["--sig", "run()"]);
+        assert!(projects[0].script.is_none());
+        assert!(!projects[0].build_only && projects[1].build_only);
+        assert_eq!(projects[1].incremental_file.as_deref(), Some("src/B.sol"));
+        assert!(projects[0].incremental_file.is_none());
+        assert_eq!(projects[1].prepare.as_deref(), Some("sync"));
+        assert_eq!(projects[0].remappings_mode, RemappingsMode::Merge);
+        assert_eq!(projects[1].remappings_mode, RemappingsMode::Replace);
+        assert!(projects[0].prepare.is_none() && projects[1].conclude.is_none());
+        assert!(!projects[0].needs_network && projects[1].needs_network);
+        assert_eq!(projects[0].command, Measured::Test);
+        assert_eq!(projects[2].command, Measured::Fmt);
+        assert!(projects[0].synthetic.is_none());
+        assert!(projects[0].budget.is_none());
+        assert_eq!(
+            projects[2].budget,
+            Some(Budget {
+                build_time: None,
+                test_time: Some(2.5),
+                tolerance: DEFAULT_BUDGET_TOLERANCE,
+            })
+        );
+        assert_eq!(
+            projects[2].synthetic,
+            Some(SyntheticConfig {
+                contracts: 20,
+                fuzz: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            projects[2].url(),
+            "synthetic:contracts=20,tests_per_contract=5,inheritance_depth=1,fuzz=true,seed=0"
+        );
+        assert_eq!(
+            projects[1].anvil,
+            Some(AnvilConfig {
+                fork_url_env: "MAINNET_RPC_URL".to_string(),
+                port: 0,
+            })
+        );
+
+        // The custom section replaces the defaults altogether.
+        let projects = load().into_project_configs(true);
+        assert!(projects[0].dependencies().is_none());
+        assert_eq!(
+            projects[0].remappings().unwrap(),
+            &vec!["@custom/=lib/custom/"]
+        );
+        assert_eq!(projects[0].origins.remappings, Some(ConfigOrigin::Custom));
     }
 }
@@ -0,0 +1,511 @@
+use std::{
+    fs,
+    io::Write as _,
+    path::Path,
+    process::Command,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Result, WrapErr, eyre};
+use serde::Deserialize;
+use tempfile::NamedTempFile;
+use yansi::Paint;
+
+use crate::{
+    cmd::DiscoverConfig,
+    config::{ConfigFile, ConfigFormat},
+    runner::CommandRunner,
+    utils,
+};
+
+/// Largest page of the GitHub search API.
+const MAX_PER_PAGE: usize = 100;
+/// Longest wait for a rate limit to reset before giving up, e.g. the per-minute limit of the search
+/// API, but not the hourly one of the other endpoints.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(90);
+/// Number of times a rate limited request is retried.
+const MAX_RETRIES: usize = 3;
+
+/// A repository found by the GitHub search API.
+#[derive(Debug, Deserialize)]
+struct Repository {
+    full_name: String,
+    stargazers_count: u64,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResults {
+    items: Vec<Repository>,
+}
+
+/// Response of the GitHub API.
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    /// The headers, with their names in lowercase.
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// How long to wait before retrying, if the request was rate limited: the `retry-after` of a
+    /// secondary rate limit, or until the `x-ratelimit-reset` of an exhausted primary one.
+    fn rate_limit_wait(&self, now: u64) -> Option<Duration> {
+        if !matches!(self.status, 403 | 429) {
+            return None;
+        }
+        if let Some(secs) = self.header("retry-after").and_then(|s| s.parse().ok()) {
+            return Some(Duration::from_secs(secs));
+        }
+        if self.header("x-ratelimit-remaining") != Some("0") {
+            return None;
+        }
+        let reset: u64 = self.header("x-ratelimit-reset")?.parse().ok()?;
+        Some(Duration::from_secs(reset.saturating_sub(now) + 1))
+    }
+
+    /// The `message` of an error response, or else its body.
+    fn message(&self) -> String {
+        serde_json::from_str::<serde_json::Value>(&self.body)
+            .ok()
+            .and_then(|body| body.get("message")?.as_str().map(str::to_string))
+            .unwrap_or_else(|| self.body.trim().to_string())
+    }
+}
+
+/// Client of the REST API of the GitHub instance of the run (see [`utils::github_url`]), running
+/// `curl`. The token is passed in a header file, so that it doesn't show in the process list.
+struct Api<'a> {
+    base: String,
+    token_header: Option<NamedTempFile>,
+    runner: &'a dyn CommandRunner,
+}
+
+impl<'a> Api<'a> {
+    fn new(token: Option<&str>, runner: &'a dyn CommandRunner) -> Result<Self> {
+        let base = match utils::github_url() {
+            url if url == utils::GITHUB_URL => "https://api.github.com".to_string(),
+            url => format!("{url}/api/v3"),
+        };
+        let token_header = token
+            .map(|token| -> Result<NamedTempFile> {
+                let mut file = NamedTempFile::new()?;
+                writeln!(file, "Authorization: Bearer {token}")?;
+                Ok(file)
+            })
+            .transpose()
+            .wrap_err("Failed to write the GitHub token header")?;
+        Ok(Self {
+            base,
+            token_header,
+            runner,
+        })
+    }
+
+    /// `GET`s `path`, waiting for the rate limit to reset and retrying if it's exceeded, unless it
+    /// resets too late.
+    fn get(&self, path: &str) -> Result<Response> {
+        let url = format!("{}{path}", self.base);
+        for attempt in 0.. {
+            let response = self.request(&url)?;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let Some(wait) = response.rate_limit_wait(now) else {
+                return Ok(response);
+            };
+            if attempt == MAX_RETRIES || wait > MAX_RATE_LIMIT_WAIT {
+                let hint = if self.token_header.is_none() {
+                    ", set GITHUB_TOKEN for a higher limit"
+                } else {
+                    ""
+                };
+                return Err(eyre!(
+                    "GitHub API rate limit exceeded, it resets in {}s{hint}",
+                    wait.as_secs()
+                ));
+            }
+            eprintln!(
+                "{} GitHub API rate limit exceeded, retrying in {}s",
+                Paint::yellow("WARNING:").bold(),
+                wait.as_secs()
+            );
+            thread::sleep(wait);
+        }
+        unreachable!("the retries are bounded")
+    }
+
+    fn request(&self, url: &str) -> Result<Response> {
+        let mut cmd = Command::new("curl");
+        cmd.args([
+            url,
+            "--silent",
+            "--show-error",
+            "--location",
+            "--include",
+            "--max-time",
+            "30",
+            "--header",
+            "Accept: application/vnd.github+json",
+            "--header",
+            "X-GitHub-Api-Version: 2022-11-28",
+        ]);
+        if let Some(file) = &self.token_header {
+            cmd.arg("--header")
+                .arg(format!("@{}", file.path().display()));
+        }
+        let output = self
+            .runner
+            .output(&mut cmd)
+            .map_err(|e| eyre!("Failed to run curl. Error: {e}"))?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "Request to {url} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        parse_response(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| eyre!("Unexpected response from {url}"))
+    }
+}
+
+/// Parses the output of `curl --include`: the status line and headers of every response (of the
+/// redirects too), each followed by an empty line, and the body of the last one.
+fn parse_response(output: &str) -> Option<Response> {
+    let mut rest = output;
+    loop {
+        let (head, body) = rest
+            .split_once("\r\n\r\n")
+            .or_else(|| rest.split_once("\n\n"))
+            .unwrap_or((rest, ""));
+        if body.starts_with("HTTP/") {
+            rest = body;
+            continue;
+        }
+        let mut lines = head.lines();
+        let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+        let headers = lines
+            .filter_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                Some((key.trim().to_ascii_lowercase(), value.trim().to_string()))
+            })
+            .collect();
+        return Some(Response {
+            status,
+            headers,
+            body: body.to_string(),
+        });
+    }
+}
+
+/// The GitHub search query of `config`, e.g. `org:foundry-rs stars:>=100 archived:false`.
+fn query(config: &DiscoverConfig) -> String {
+    let mut terms = Vec::new();
+    if let Some(org) = &config.org {
+        terms.push(format!("org:{org}"));
+    }
+    if let Some(topic) = &config.topic {
+        terms.push(format!("topic:{topic}"));
+    }
+    if config.min_stars > 0 {
+        terms.push(format!("stars:>={}", config.min_stars));
+    }
+    terms.push("archived:false".to_string());
+    terms.join(" ")
+}
+
+/// Percent-encodes `text` for a URL query string.
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The repositories matching `config`, by descending stars, up to its limit.
+fn search(api: &Api, config: &DiscoverConfig) -> Result<Vec<Repository>> {
+    let per_page = config.max_repos.clamp(1, MAX_PER_PAGE);
+    let mut repos: Vec<Repository> = Vec::new();
+    for page in 1.. {
+        let response = api.get(&format!(
+            "/search/repositories?q={}&sort=stars&order=desc&per_page={per_page}&page={page}",
+            encode(&query(config))
+        ))?;
+        if response.status != 200 {
+            return Err(eyre!(
+                "GitHub search failed ({}): {}",
+                response.status,
+                response.message()
+            ));
+        }
+        let results: SearchResults = serde_json::from_str(&response.body)
+            .wrap_err("Failed to parse the GitHub search results")?;
+        let last = results.items.len() < per_page;
+        repos.extend(results.items);
+        if last || repos.len() >= config.max_repos {
+            break;
+        }
+    }
+    repos.truncate(config.max_repos);
+    repos.sort_by_key(|repo| std::cmp::Reverse(repo.stargazers_count));
+    Ok(repos)
+}
+
+/// Whether `repo` has a `foundry.toml` at its root.
+fn is_foundry_project(api: &Api, repo: &str) -> Result<bool> {
+    let response = api.get(&format!("/repos/{repo}/contents/foundry.toml"))?;
+    match response.status {
+        200 => Ok(true),
+        404 => Ok(false),
+        status => Err(eyre!(
+            "Failed to look for the foundry.toml of {repo} ({status}): {}",
+            response.message()
+        )),
+    }
+}
+
+/// The `[[project]]` entry of `repo`, with its stars and description in a comment.
+fn project_entry(repo: &Repository) -> String {
+    let mut comment = format!("# {} stars", repo.stargazers_count);
+    if let Some(description) = repo.description.as_deref().filter(|d| !d.is_empty()) {
+        comment.push_str(": ");
+        comment.push_str(&description.replace(['\r', '\n'], " "));
+    }
+    format!(
+        "{comment}\n[[project]]\nname = {}\n",
+        toml::Value::String(repo.full_name.clone())
+    )
+}
+
+/// Names of the projects of the config file at `path`, if it exists.
+fn existing_projects(path: &Path) -> Result<Vec<String>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let (file, _) = ConfigFile::load_lenient(&path.display().to_string())?;
+    Ok(file
+        .project
+        .into_iter()
+        .map(|project| project.name)
+        .collect())
+}
+
+/// Runs the `discover` subcommand: searches GitHub for the repositories matching `config`, and
+/// prints a `[[project]]` entry for every one with a `foundry.toml` at its root, by descending
+/// stars, or appends them to the config file at `config_path` with `--write`. The repositories
+/// already in that config file are skipped.
+pub fn run(config: &DiscoverConfig, config_path: &str, runner: &dyn CommandRunner) -> Result<()> {
+    let path = Path::new(config_path);
+    if config.write && ConfigFormat::of(path) != ConfigFormat::Toml {
+        return Err(eyre!(
+            "{} --write only appends to TOML config files, not {config_path}",
+            Paint::red("ERROR:").bold()
+        ));
+    }
+    let existing = existing_projects(path)?;
+    let token = config.github_token.as_deref().filter(|t| !t.is_empty());
+    if token.is_none() {
+        eprintln!(
+            "{} GITHUB_TOKEN isn't set, the GitHub API only allows a few unauthenticated requests",
+            Paint::yellow("WARNING:").bold()
+        );
+    }
+    let api = Api::new(token, runner)?;
+
+    let repos = search(&api, config)?;
+    eprintln!(
+        "Found {} repositories matching {}",
+        repos.len(),
+        query(config)
+    );
+    let mut entries = Vec::new();
+    for repo in &repos {
+        if existing
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(&repo.full_name))
+        {
+            eprintln!("Skipping {}: already in {config_path}", repo.full_name);
+            continue;
+        }
+        if !is_foundry_project(&api, &repo.full_name)? {
+            eprintln!("Skipping {}: no foundry.toml at its root", repo.full_name);
+            continue;
+        }
+        entries.push(project_entry(repo));
+    }
+
+    let entries = entries.join("\n");
+    if !config.write {
+        print!("{entries}");
+    } else if !entries.is_empty() {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .wrap_err_with(|| format!("Failed to open config file: {config_path}"))?;
+        write!(file, "\n{entries}")
+            .wrap_err_with(|| format!("Failed to write config file: {config_path}"))?;
+        eprintln!(
+            "Appended {} project(s) to {config_path}",
+            entries.matches("[[project]]").count()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{Response as Scripted, ScriptedRunner};
+
+    fn config(org: Option<&str>, topic: Option<&str>, min_stars: u64) -> DiscoverConfig {
+        DiscoverConfig {
+            org: org.map(str::to_string),
+            topic: topic.map(str::to_string),
+            min_stars,
+            max_repos: 30,
+            write: false,
+            github_token: None,
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        assert_eq!(
+            query(&config(Some("foundry-rs"), None, 0)),
+            "org:foundry-rs archived:false"
+        );
+        let query = query(&config(None, Some("foundry"), 100));
+        assert_eq!(query, "topic:foundry stars:>=100 archived:false");
+        assert_eq!(
+            encode(&query),
+            "topic%3Afoundry%20stars%3A%3E%3D100%20archived%3Afalse"
+        );
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let output = "HTTP/1.1 301 Moved Permanently\r\nLocation: /x\r\n\r\nHTTP/2 403\r\nX-RateLimit-Remaining: 0\r\nx-ratelimit-reset: 1000\r\n\r\n{\"message\": \"API rate limit exceeded\"}";
+        let response = parse_response(output).unwrap();
+        assert_eq!(response.status, 403);
+        assert_eq!(response.header("x-ratelimit-remaining"), Some("0"));
+        assert_eq!(response.message(), "API rate limit exceeded");
+        assert_eq!(response.rate_limit_wait(990), Some(Duration::from_secs(11)));
+
+        let response = parse_response("HTTP/2 403\nretry-after: 5\n\n").unwrap();
+        assert_eq!(response.rate_limit_wait(0), Some(Duration::from_secs(5)));
+        // A 403 that isn't a rate limit, e.g. a missing permission.
+        let response = parse_response("HTTP/2 403\nx-ratelimit-remaining: 42\n\n{}").unwrap();
+        assert_eq!(response.rate_limit_wait(0), None);
+        assert!(parse_response("garbage").is_none());
+    }
+
+    fn search_page(repos: &[(&str, u64)]) -> Scripted {
+        let items: Vec<String> = repos
+            .iter()
+            .map(|(name, stars)| {
+                format!(r#"{{"full_name": "{name}", "stargazers_count": {stars}, "description": "{name}\nrepo"}}"#)
+            })
+            .collect();
+        Scripted::ok(&format!(
+            "HTTP/2 200\r\n\r\n{{\"items\": [{}]}}",
+            items.join(",")
+        ))
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        fs::write(&path, "[[project]]\nname = \"Org/Known\"\n").unwrap();
+
+        let runner = ScriptedRunner::default()
+            .on(
+                "curl https://api.github.com/search/repositories",
+                [
+                    // Rate limited: retried after its retry-after.
+                    Scripted::ok("HTTP/2 429\r\nretry-after: 0\r\n\r\n{}"),
+                    search_page(&[("org/b", 50), ("org/known", 900), ("org/a", 100)]),
+                ],
+            )
+            .on(
+                "curl https://api.github.com/repos/org/a/contents/foundry.toml",
+                [Scripted::ok("HTTP/2 200\r\n\r\n{}")],
+            )
+            .on(
+                "curl https://api.github.com/repos/org/b/contents/foundry.toml",
+                [Scripted::ok(
+                    "HTTP/2 404\r\n\r\n{\"message\": \"Not Found\"}",
+                )],
+            );
+        let mut config = config(Some("org"), None, 0);
+        config.write = true;
+        config.github_token = Some("discover-test-token".to_string());
+        run(&config, path.to_str().unwrap(), &runner).unwrap();
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 4);
+        assert!(calls[0].starts_with(
+            "curl https://api.github.com/search/repositories?q=org%3Aorg%20archived%3Afalse&sort=stars&order=desc&per_page=30&page=1 "
+        ));
+        // The token is passed in a file, not on the command line.
+        assert!(calls[0].contains("--header @"));
+        assert!(
+            calls
+                .iter()
+                .all(|call| !call.contains("discover-test-token"))
+        );
+        // Sorted by stars, the known project isn't checked.
+        assert!(
+            calls[2].starts_with("curl https://api.github.com/repos/org/a/contents/foundry.toml")
+        );
+        assert!(
+            calls[3].starts_with("curl https://api.github.com/repos/org/b/contents/foundry.toml")
+        );
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            written,
+            "[[project]]\nname = \"Org/Known\"\n\n# 100 stars: org/a repo\n[[project]]\nname = \"org/a\"\n"
+        );
+        let file = ConfigFile::load(path.to_str().unwrap(), false).unwrap();
+        assert_eq!(file.project.len(), 2);
+    }
+
+    #[test]
+    fn test_discover_errors() {
+        let runner = ScriptedRunner::default().on(
+            "curl",
+            [Scripted::ok(
+                "HTTP/2 422\r\n\r\n{\"message\": \"Validation Failed\"}",
+            )],
+        );
+        let err = run(&config(Some("nope"), None, 0), "missing.toml", &runner).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "GitHub search failed (422): Validation Failed"
+        );
+
+        let mut config = config(Some("org"), None, 0);
+        config.write = true;
+        let err = run(&config, "benchmarks.yaml", &ScriptedRunner::default()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("--write only appends to TOML config files")
+        );
+    }
+}
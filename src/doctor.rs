@@ -0,0 +1,231 @@
+use std::{path::Path, process::Command};
+use yansi::Paint;
+
+use crate::{
+    config::ConfigFile,
+    toolchain::{Platform, Toolchain, resolve_foundryup},
+    utils::{self, work_dir},
+};
+
+/// Free space (in MiB) below which the work directory check fails.
+const MIN_FREE_SPACE_MIB: u64 = 1024;
+/// Free space (in MiB) below which the work directory check warns.
+const LOW_FREE_SPACE_MIB: u64 = 5 * 1024;
+
+/// Outcome of a single environment check.
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    /// Whether a failure of this check should fail the whole `doctor` run.
+    pub required: bool,
+    /// Details of the check: a version, a path or the reason it failed.
+    pub outcome: Result<String, String>,
+    /// How to fix a failed check.
+    pub hint: &'static str,
+}
+
+impl Check {
+    fn new(
+        name: &'static str,
+        required: bool,
+        hint: &'static str,
+        outcome: Result<String, String>,
+    ) -> Self {
+        Self {
+            name,
+            required,
+            outcome,
+            hint,
+        }
+    }
+
+    /// Whether this check makes the `doctor` run fail.
+    pub fn is_fatal(&self) -> bool {
+        self.required && self.outcome.is_err()
+    }
+
+    /// Prints the pass/fail line of the check, followed by its hint if it failed.
+    pub fn log(&self) {
+        match &self.outcome {
+            Ok(details) => println!("{} {}: {details}", Paint::green("PASS").bold(), self.name),
+            Err(details) => {
+                let status = if self.required {
+                    Paint::red("FAIL").bold()
+                } else {
+                    Paint::yellow("WARN").bold()
+                };
+                println!("{status} {}: {details}", self.name);
+                println!("     hint: {}", self.hint);
+            }
+        }
+    }
+}
+
+/// Checks that the environment can run a benchmark: the required binaries, the connectivity to
+/// GitHub, the free space of the work directory and the configuration file (see
+/// [`ConfigFile::load`] for `lenient_config`).
+pub fn run_checks(
+    config_path: &str,
+    lenient_config: bool,
+    foundryup_bin: Option<&str>,
+) -> Vec<Check> {
+    vec![
+        Check::new(
+            "git",
+            true,
+            "install git, projects are cloned with it",
+            command_version(Command::new("git").arg("--version")),
+        ),
+        Check::new(
+            "forge",
+            true,
+            "install Foundry (see https://getfoundry.sh) and add forge to PATH",
+            Toolchain::default()
+                .forge_version()
+                .map(|v| v.to_string())
+                .map_err(|e| e.to_string()),
+        ),
+        Check::new(
+            "foundryup",
+            false,
+            "install foundryup (see https://getfoundry.sh) or pass its location with --foundryup-bin; it is only needed by `diff`",
+            resolve_foundryup(foundryup_bin)
+                .map_err(|_| "foundryup binary not found".to_string())
+                .map(|path| {
+                    let version =
+                        command_version(Platform::default().command(&path).arg("--version"))
+                            .unwrap_or_else(|_| "unknown version".to_string());
+                    format!("{version} ({})", path.display())
+                }),
+        ),
+        Check::new(
+            "github.com",
+            true,
+            "check the network access (and proxy settings) to github.com",
+            check_github(),
+        ),
+        Check::new(
+            "disk space",
+            true,
+            "free up space in the temp directory, or point TMPDIR to a larger partition",
+            check_disk_space(),
+        ),
+        Check::new(
+            "config",
+            true,
+            "fix the configuration file, see `foundry-benchmarks validate` or create one with `foundry-benchmarks init`",
+            ConfigFile::load(config_path, lenient_config)
+                .map(|config| format!("{config_path} ({} projects)", config.project.len()))
+                .map_err(|e| format!("{e:#}")),
+        ),
+    ]
+}
+
+/// Runs a `--version` command, returning the first line of its output.
+fn command_version(cmd: &mut Command) -> Result<String, String> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let output = cmd
+        .output()
+        .map_err(|e| format!("failed to run '{program}': {e}"))?;
+    if !output.status.success() {
+        return Err(format!("'{program}' exited with {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string())
+}
+
+/// Checks that a repository of the configured GitHub instance can be reached, the same way
+/// projects are cloned.
+fn check_github() -> Result<String, String> {
+    let url = format!("{}/foundry-rs/forge-std", utils::github_url());
+    let output = Command::new("git")
+        .args(["ls-remote", "--exit-code", &url, "HEAD"])
+        // Give up on stalled connections instead of hanging.
+        .env("GIT_HTTP_LOW_SPEED_LIMIT", "1")
+        .env("GIT_HTTP_LOW_SPEED_TIME", "15")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map_err(|e| format!("failed to run 'git ls-remote': {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("failed to reach {url}: {}", stderr.trim()));
+    }
+    Ok(format!("reached {url}"))
+}
+
+/// Checks the free space of the work directory, where toolchains and projects are installed.
+fn check_disk_space() -> Result<String, String> {
+    let dir = work_dir().map_err(|e| format!("failed to create the work directory: {e}"))?;
+    let free = free_space_mib(&dir)?;
+    let details = format!("{free} MiB free in {}", dir.display());
+    if free < MIN_FREE_SPACE_MIB {
+        return Err(format!(
+            "only {details}, at least {MIN_FREE_SPACE_MIB} MiB are needed"
+        ));
+    }
+    if free < LOW_FREE_SPACE_MIB {
+        return Ok(format!("{details} (low)"));
+    }
+    Ok(details)
+}
+
+/// Returns the free space (in MiB) of the partition of `dir`, using `df`.
+fn free_space_mib(dir: &Path) -> Result<u64, String> {
+    let output = Command::new("df")
+        .arg("-Pk")
+        .arg(dir)
+        .output()
+        .map_err(|e| format!("failed to run 'df': {e}"))?;
+    parse_df_available(&String::from_utf8_lossy(&output.stdout))
+        .map(|kib| kib / 1024)
+        .ok_or_else(|| "failed to parse the output of 'df'".to_string())
+}
+
+/// Parses the available KiB from the output of `df -Pk` for a single path.
+fn parse_df_available(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(3)?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df_available() {
+        let output = "\
+Filesystem     1024-blocks     Used Available Capacity Mounted on
+/dev/vda         264212084 17483136  83319140      18% /
+";
+        assert_eq!(parse_df_available(output), Some(83_319_140));
+        assert_eq!(parse_df_available("Filesystem 1024-blocks\n"), None);
+        assert_eq!(parse_df_available(""), None);
+    }
+
+    #[test]
+    fn test_command_version() {
+        assert!(command_version(&mut Command::new("foundry-benchmarks-missing-bin")).is_err());
+        assert!(command_version(&mut Command::new("false")).is_err());
+        assert_eq!(
+            command_version(Command::new("echo").args(["v1.0.0\nmore"])),
+            Ok("v1.0.0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_required_checks_fail_the_run() {
+        let check = |required, outcome| Check::new("check", required, "", outcome);
+        assert!(check(true, Err(String::new())).is_fatal());
+        assert!(!check(false, Err(String::new())).is_fatal());
+        assert!(!check(true, Ok(String::new())).is_fatal());
+    }
+}
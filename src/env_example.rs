@@ -0,0 +1,91 @@
+use std::{fs, path::Path};
+
+use crate::utils::ProjectConfig;
+
+/// Files documenting the environment variables of a project, by order of preference.
+const ENV_EXAMPLE_FILES: &[&str] = &[".env.example", ".env.sample"];
+
+/// The variables documented by the `.env.example` (or `.env.sample`) of `project`, cloned in
+/// `dir`, that are set neither in its resolved env vars nor in the process environment, along
+/// with the name of the file. Empty values count as missing, like for `required_env`.
+///
+/// Returns `None` without such a file, or if every variable it documents is set.
+pub fn missing_vars(project: &ProjectConfig, dir: &Path) -> Option<(&'static str, Vec<String>)> {
+    let (file, contents) = ENV_EXAMPLE_FILES
+        .iter()
+        .find_map(|file| Some((*file, fs::read_to_string(dir.join(file)).ok()?)))?;
+    let mut missing: Vec<String> = Vec::new();
+    for key in keys(&contents) {
+        if project.env_value(key).is_none() && !missing.iter().any(|k| k == key) {
+            missing.push(key.to_string());
+        }
+    }
+    (!missing.is_empty()).then_some((file, missing))
+}
+
+/// The keys assigned by the lines of a dotenv file, in order, e.g. `RPC_URL` for
+/// `export RPC_URL=https://...`. Comments and lines that aren't assignments are ignored.
+fn keys(contents: &str) -> impl Iterator<Item = &str> {
+    contents.lines().filter_map(|line| {
+        let line = line.trim();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, _) = line.split_once('=')?;
+        let key = key.trim();
+        let valid = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        valid.then_some(key)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_keys() {
+        let contents = "# RPC endpoints\nMAINNET_RPC_URL=\nexport ETHERSCAN_API_KEY = \"abc\"\n\n  PRIVATE_KEY=0x01 # dev key\n#COMMENTED=1\nnot an assignment\n1INVALID=1\n";
+        assert_eq!(
+            keys(contents).collect::<Vec<_>>(),
+            ["MAINNET_RPC_URL", "ETHERSCAN_API_KEY", "PRIVATE_KEY"]
+        );
+    }
+
+    #[test]
+    fn test_missing_vars() {
+        let dir = tempdir().unwrap();
+        let project = ProjectConfig::new("a/a").with_env_vars(
+            vec![
+                "FOUNDRY_BENCHMARKS_TEST_EXAMPLE_SET",
+                "FOUNDRY_BENCHMARKS_TEST_EXAMPLE_EMPTY",
+            ],
+            vec!["1", ""],
+        );
+        assert_eq!(missing_vars(&project, dir.path()), None);
+
+        fs::write(
+            dir.path().join(".env.sample"),
+            "FOUNDRY_BENCHMARKS_TEST_EXAMPLE_SAMPLE=\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(".env.example"),
+            "FOUNDRY_BENCHMARKS_TEST_EXAMPLE_SET=\nFOUNDRY_BENCHMARKS_TEST_EXAMPLE_EMPTY=\nFOUNDRY_BENCHMARKS_TEST_EXAMPLE_UNSET=\nFOUNDRY_BENCHMARKS_TEST_EXAMPLE_UNSET=\n",
+        )
+        .unwrap();
+        // `.env.example` is preferred over `.env.sample`.
+        assert_eq!(
+            missing_vars(&project, dir.path()),
+            Some((
+                ".env.example",
+                vec![
+                    "FOUNDRY_BENCHMARKS_TEST_EXAMPLE_EMPTY".to_string(),
+                    "FOUNDRY_BENCHMARKS_TEST_EXAMPLE_UNSET".to_string()
+                ]
+            ))
+        );
+
+        fs::remove_file(dir.path().join(".env.example")).unwrap();
+        assert_eq!(missing_vars(&project, dir.path()).unwrap().0, ".env.sample");
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+
+/// Kind of the timed runs of a project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RunKind {
+    Test,
+    Script,
+}
+
+/// The runs of one kind planned for a project in the current test stage.
+#[derive(Debug)]
+struct Work {
+    project: String,
+    kind: RunKind,
+    /// Runs not completed yet.
+    left: usize,
+    /// Runs completed, and their total time in seconds.
+    done: usize,
+    total: f64,
+}
+
+impl Work {
+    fn average(&self) -> Option<f64> {
+        (self.done > 0).then(|| self.total / self.done as f64)
+    }
+}
+
+/// Estimates the time left in the test stage from the runs completed so far.
+///
+/// The time left of a project is its runs left times its average run time, measured in this stage
+/// or else in a previous one (e.g. with the baseline of a diff). The projects without any
+/// measurement yet are estimated with the mean of the averages of the others.
+#[derive(Debug, Default)]
+pub struct Estimator {
+    /// The runs of the current test stage, in order.
+    work: Vec<Work>,
+    /// Average run times measured in the previous test stages.
+    seeds: BTreeMap<(String, RunKind), f64>,
+}
+
+impl Estimator {
+    pub const fn new() -> Self {
+        Self {
+            work: Vec::new(),
+            seeds: BTreeMap::new(),
+        }
+    }
+
+    /// Starts a test stage running the given `(project, test runs, script runs)`, keeping the
+    /// averages measured in the previous stage as the estimates of the same runs.
+    pub fn plan<'a>(&mut self, runs: impl IntoIterator<Item = (&'a str, usize, usize)>) {
+        for work in self.work.drain(..) {
+            if let Some(average) = work.average() {
+                self.seeds.insert((work.project, work.kind), average);
+            }
+        }
+        for (project, test_runs, script_runs) in runs {
+            for (kind, left) in [(RunKind::Test, test_runs), (RunKind::Script, script_runs)] {
+                if left > 0 {
+                    self.work.push(Work {
+                        project: project.to_string(),
+                        kind,
+                        left,
+                        done: 0,
+                        total: 0.0,
+                    });
+                }
+            }
+        }
+    }
+
+    /// A `kind` run of `project` completed after `duration` seconds.
+    pub fn completed(&mut self, project: &str, kind: RunKind, duration: f64) {
+        if let Some(work) = self
+            .work
+            .iter_mut()
+            .find(|work| work.project == project && work.kind == kind)
+        {
+            work.left = work.left.saturating_sub(1);
+            work.done += 1;
+            work.total += duration;
+        }
+    }
+
+    /// `project` failed: none of its runs left will run.
+    pub fn failed(&mut self, project: &str) {
+        for work in self.work.iter_mut().filter(|work| work.project == project) {
+            work.left = 0;
+        }
+    }
+
+    /// Seconds left until the runs of `project` complete, if there is anything to estimate from.
+    pub fn project_left(&self, project: &str) -> Option<f64> {
+        self.left(|work| work.project == project)
+    }
+
+    /// Seconds left until every run of the stage completes, if there is anything to estimate from.
+    pub fn total_left(&self) -> Option<f64> {
+        self.left(|_| true)
+    }
+
+    fn left(&self, filter: impl Fn(&Work) -> bool) -> Option<f64> {
+        let averages: Vec<Option<f64>> = self.work.iter().map(|work| self.average(work)).collect();
+        let known: Vec<f64> = averages.iter().flatten().copied().collect();
+        let fallback = (!known.is_empty()).then(|| known.iter().sum::<f64>() / known.len() as f64);
+        self.work
+            .iter()
+            .zip(averages)
+            .filter(|(work, _)| filter(work) && work.left > 0)
+            .map(|(work, average)| Some(work.left as f64 * average.or(fallback)?))
+            .sum()
+    }
+
+    /// Average run time of `work`, measured in this stage or else in a previous one.
+    fn average(&self, work: &Work) -> Option<f64> {
+        work.average()
+            .or_else(|| self.seeds.get(&(work.project.clone(), work.kind)).copied())
+    }
+}
+
+/// Formats a number of seconds left, e.g. `42s`, `3m05s` or `1h02m`.
+pub fn format_left(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimator() {
+        let mut eta = Estimator::new();
+        eta.plan([("a/a", 3, 0), ("b/b", 2, 2)]);
+        assert_eq!(eta.total_left(), None);
+
+        eta.completed("a/a", RunKind::Test, 2.0);
+        eta.completed("a/a", RunKind::Test, 4.0);
+        // a/a: 1 run of 3s left. b/b: 4 runs, estimated with the average of a/a.
+        assert_eq!(eta.project_left("a/a"), Some(3.0));
+        assert_eq!(eta.project_left("b/b"), Some(12.0));
+        assert_eq!(eta.total_left(), Some(15.0));
+
+        eta.completed("a/a", RunKind::Test, 3.0);
+        eta.completed("b/b", RunKind::Test, 1.0);
+        // b/b: 1 test run of 1s, and its 2 script runs with the mean of the test averages (2s).
+        assert_eq!(eta.project_left("a/a"), Some(0.0));
+        assert_eq!(eta.project_left("b/b"), Some(5.0));
+
+        eta.failed("b/b");
+        assert_eq!(eta.total_left(), Some(0.0));
+    }
+
+    #[test]
+    fn test_estimator_seeds() {
+        let mut eta = Estimator::new();
+        eta.plan([("a/a", 2, 0), ("b/b", 2, 0)]);
+        eta.completed("a/a", RunKind::Test, 10.0);
+        eta.completed("a/a", RunKind::Test, 10.0);
+        eta.completed("b/b", RunKind::Test, 1.0);
+
+        // The next stage, e.g. the comparison of a diff, is estimated from the previous one.
+        eta.plan([("a/a", 2, 0), ("b/b", 4, 0), ("c/c", 1, 0)]);
+        assert_eq!(eta.project_left("a/a"), Some(20.0));
+        assert_eq!(eta.project_left("b/b"), Some(4.0));
+        assert_eq!(eta.project_left("c/c"), Some(5.5));
+
+        // The runs of this stage take over the seeds.
+        eta.completed("a/a", RunKind::Test, 6.0);
+        assert_eq!(eta.project_left("a/a"), Some(6.0));
+    }
+
+    #[test]
+    fn test_format_left() {
+        assert_eq!(format_left(0.2), "0s");
+        assert_eq!(format_left(42.4), "42s");
+        assert_eq!(format_left(185.0), "3m05s");
+        assert_eq!(format_left(3720.0), "1h02m");
+    }
+}
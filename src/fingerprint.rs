@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::benchmark::Benchmarks;
+
+/// Keys of `forge config --json` recorded for every project, as dotted paths into its output:
+/// the settings that most often explain a diff, when a new version of forge changes a default.
+pub const KEYS: &[&str] = &[
+    "optimizer",
+    "optimizer_runs",
+    "evm_version",
+    "fuzz.runs",
+    "invariant.runs",
+    "via_ir",
+];
+
+/// The [`KEYS`] of the output of `forge config --json`, rendered as text, e.g. `fuzz.runs: 256`.
+///
+/// The keys that the `forge` doesn't know, e.g. an older version without a setting, are left out.
+/// `None` if the output isn't a JSON object.
+pub fn parse(output: &str) -> Option<BTreeMap<String, String>> {
+    let config: Value = serde_json::from_str(output).ok()?;
+    config.is_object().then(|| {
+        KEYS.iter()
+            .filter_map(|key| {
+                let value = key
+                    .split('.')
+                    .try_fold(&config, |value, segment| value.get(segment))?;
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    Value::Null => return None,
+                    value => value.to_string(),
+                };
+                Some((key.to_string(), value))
+            })
+            .collect()
+    })
+}
+
+/// The changes of the config of a project between the baseline of a diff and a comparison source,
+/// e.g. `fuzz.runs 256→1000`.
+///
+/// Only the keys recorded on both sides are compared, so that a key that only one version of forge
+/// knows doesn't count as a change.
+fn changes(before: &BTreeMap<String, String>, after: &BTreeMap<String, String>) -> Vec<String> {
+    before
+        .iter()
+        .filter_map(|(key, before)| {
+            let after = after.get(key)?;
+            (before != after).then(|| format!("{key} {before}→{after}"))
+        })
+        .collect()
+}
+
+/// The notes of the tables about the projects whose config differs between the baseline of `b` and
+/// a comparison source, one per project and comparison source, e.g.
+/// `a/a with feat: config changed: fuzz.runs 256→1000.`
+///
+/// The projects without a fingerprint on either side, e.g. with a baseline recorded without them,
+/// aren't compared.
+pub fn notes(b: &Benchmarks) -> Vec<String> {
+    let Some((baseline, comparisons)) = b.results.split_first() else {
+        return Vec::new();
+    };
+    let mut notes = Vec::new();
+    for tested in &baseline.tests {
+        let Some(before) = &tested.config_fingerprint else {
+            continue;
+        };
+        for vs in comparisons {
+            let Some(after) = vs
+                .tests
+                .iter()
+                .find(|t| t.name == tested.name)
+                .and_then(|t| t.config_fingerprint.as_ref())
+            else {
+                continue;
+            };
+            let changes = changes(before, after);
+            if !changes.is_empty() {
+                notes.push(format!(
+                    "{} with {}: config changed: {}.",
+                    tested.name,
+                    vs.display_name(),
+                    changes.join(", ")
+                ));
+            }
+        }
+    }
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        benchmark::{Source, SourceResults, Tested},
+        ui,
+    };
+
+    #[test]
+    fn test_parse() {
+        let output = r#"{
+            "src": "src",
+            "optimizer": true,
+            "optimizer_runs": 200,
+            "evm_version": "cancun",
+            "fuzz": { "runs": 256, "seed": null },
+            "invariant": { "depth": 500 },
+            "via_ir": null
+        }"#;
+        let fingerprint = parse(output).unwrap();
+        // The unknown keys (`invariant.runs`) and the unset ones (`via_ir`) are left out.
+        assert_eq!(
+            fingerprint.into_iter().collect::<Vec<_>>(),
+            [
+                ("evm_version".to_string(), "cancun".to_string()),
+                ("fuzz.runs".to_string(), "256".to_string()),
+                ("optimizer".to_string(), "true".to_string()),
+                ("optimizer_runs".to_string(), "200".to_string()),
+            ]
+        );
+        assert_eq!(parse("{}"), Some(BTreeMap::new()));
+        assert_eq!(parse("Error: failed to extract foundry config"), None);
+        assert_eq!(parse("[]"), None);
+    }
+
+    fn tested(name: &str, fingerprint: Option<&[(&str, &str)]>) -> Tested {
+        Tested {
+            config_fingerprint: fingerprint.map(|fingerprint| {
+                fingerprint
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect()
+            }),
+            ..Tested::fixture(name, 1.0)
+        }
+    }
+
+    #[test]
+    fn test_notes() {
+        let source = |name: &str, tests| SourceResults {
+            source: Source::Branch(name.to_string()),
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        };
+        let base = [("fuzz.runs", "256"), ("optimizer", "true")];
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 1,
+            results: vec![
+                source(
+                    "master",
+                    vec![
+                        tested("a/a", Some(&base)),
+                        tested("b/b", Some(&base)),
+                        tested("c/c", None),
+                        tested("d/d", Some(&base)),
+                    ],
+                ),
+                source(
+                    "feat",
+                    vec![
+                        tested(
+                            "a/a",
+                            Some(&[("fuzz.runs", "1000"), ("optimizer", "false")]),
+                        ),
+                        tested("b/b", Some(&base)),
+                        tested("c/c", Some(&base)),
+                        // A key only known by one side isn't a change.
+                        tested(
+                            "d/d",
+                            Some(&[("fuzz.runs", "256"), ("invariant.runs", "256")]),
+                        ),
+                    ],
+                ),
+            ],
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        };
+        assert_eq!(
+            notes(&b),
+            ["a/a with feat: config changed: fuzz.runs 256→1000, optimizer true→false."]
+        );
+    }
+}
@@ -0,0 +1,436 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use inferno::collapse::{Collapse, perf::Folder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use yansi::Paint;
+
+use crate::{
+    benchmark::{self, Benchmarks},
+    cmd::Verbosity,
+    keep,
+    reporter::Reporter,
+    runner::CommandRunner,
+    toolchain::Toolchain,
+    ui,
+    utils::ProjectConfig,
+};
+
+/// The flamegraphs of the worst regressed projects of a diff, saved along with its results.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Flamegraphs {
+    /// Name of every source in the tables, in the order of the SVGs of every profile.
+    pub sources: Vec<String>,
+    pub profiles: Vec<Profile>,
+}
+
+/// The flamegraphs of a regressed project, one per source of a diff.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Profile {
+    pub project: String,
+    /// Worst relative test time diff (in %) of a comparison source against the baseline.
+    pub regression: f64,
+    /// The SVG of every source, `None` if it couldn't be profiled with it.
+    pub svgs: Vec<Option<PathBuf>>,
+}
+
+/// The `n` projects of `b` whose test time regressed the most against the baseline with any of the
+/// comparison sources, worst first, along with that regression (in %).
+pub fn worst_regressed(b: &Benchmarks, n: usize) -> Vec<(&str, f64)> {
+    let mut regressed: Vec<(&str, f64)> = b
+        .baseline()
+        .tests
+        .iter()
+        .filter_map(|before| {
+            let before_time = before.test_time()?;
+            let worst = b
+                .comparisons()
+                .iter()
+                .filter_map(|vs| {
+                    let after = vs.tests.iter().find(|t| t.name == before.name)?;
+                    Some(ui::relative_diff(before_time, after.test_time()?))
+                })
+                .max_by(f64::total_cmp)?;
+            (worst > 0.0).then_some((before.name.as_str(), worst))
+        })
+        .collect();
+    regressed.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    regressed.truncate(n);
+    regressed
+}
+
+/// Profiles the `regressed` projects of `repos` (see [`worst_regressed`]) with the toolchain of
+/// every source of `sides`, named by their role in the diff (e.g. "baseline"): every project is
+/// cloned again (never kept by `--keep`) and built, and its timed command runs once more under
+/// `perf record -g`. The
+/// flamegraph of every run is written to `dir`, e.g. `a__a.baseline.svg`, along with its folded
+/// stacks (`.folded`).
+///
+/// The profiled runs are never part of the timings. A run failing to be profiled only warns, and
+/// leaves its flamegraph out. The sources without a toolchain (a recorded baseline) aren't
+/// profiled.
+pub fn profile_regressions(
+    regressed: &[(&str, f64)],
+    repos: &[ProjectConfig],
+    sides: &[(String, Option<Toolchain>)],
+    verbosity: Verbosity,
+    dir: &Path,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Vec<Profile> {
+    if let Err(e) = fs::create_dir_all(dir) {
+        eprintln!(
+            "{} Failed to create {}, skipping the flamegraphs. Error: {e}",
+            Paint::yellow("WARNING:").bold(),
+            dir.display()
+        );
+        return Vec::new();
+    }
+    let mut profiles = Vec::new();
+    for &(name, regression) in regressed {
+        let Some(project) = repos.iter().find(|repo| repo.name == name) else {
+            continue;
+        };
+        let svgs = sides
+            .iter()
+            .map(|(side, toolchain)| {
+                let toolchain = toolchain.as_ref()?;
+                ui::big_banner(&format!("FLAMEGRAPH --> {side} ({name})"));
+                let svg = dir.join(format!(
+                    "{}.{}.svg",
+                    keep::dir_name(name),
+                    side.replace(" #", "-")
+                ));
+                let title = format!("{} {name} ({side})", project.command.name());
+                match profile(
+                    project, toolchain, verbosity, &title, &svg, runner, reporter,
+                ) {
+                    Ok(()) => {
+                        println!(
+                            "Wrote the flamegraph of {name} ({side}) to {}",
+                            svg.display()
+                        );
+                        Some(svg)
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "{} Failed to profile {name} with the {side} source: {e}",
+                            Paint::yellow("WARNING:").bold()
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+        profiles.push(Profile {
+            project: name.to_string(),
+            regression,
+            svgs,
+        });
+    }
+    profiles
+}
+
+/// Clones and builds `project` with `toolchain`, profiles a run of its timed command (see
+/// [`benchmark::profile_test_run`]), and renders it as the flamegraph `svg`, titled `title`.
+fn profile(
+    project: &ProjectConfig,
+    toolchain: &Toolchain,
+    verbosity: Verbosity,
+    title: &str,
+    svg: &Path,
+    runner: &dyn CommandRunner,
+    reporter: &dyn Reporter,
+) -> Result<(), String> {
+    // Only the profiled run matters, the build is just its setup.
+    let mut project = project.clone();
+    project.scratch = true;
+    project.measure_discovery = false;
+    project.incremental_runs = None;
+    let projects = [project];
+    let (cloned, mut failures) = benchmark::clone_projects(&projects, runner, reporter);
+    let (built, build_failures) = benchmark::build_projects(cloned, toolchain, runner, reporter);
+    failures.extend(build_failures);
+    let Some(built) = built.first() else {
+        return Err(failures
+            .pop()
+            .map_or_else(|| "the project wasn't built".to_string(), |f| f.error));
+    };
+
+    // Recorded in the project directory, removed along with it.
+    let data = built.state.path.join("perf.data");
+    benchmark::profile_test_run(built, verbosity, toolchain, &data, runner)?;
+    tracing::debug!("$ perf script -i {}", data.display());
+    let output = runner
+        .output(Command::new("perf").arg("script").arg("-i").arg(&data))
+        .map_err(|e| format!("Failed to execute 'perf script'. Error: {e:?}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "'perf script' failed with status code: {:?}. {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    render_svg(&output.stdout, title, svg)
+}
+
+/// Folds the stacks of the `perf script` output, and renders them as the flamegraph `svg`, with
+/// the folded stacks next to it.
+fn render_svg(perf_script: &[u8], title: &str, svg: &Path) -> Result<(), String> {
+    let mut folded = Vec::new();
+    Folder::default()
+        .collapse(perf_script, &mut folded)
+        .map_err(|e| format!("Failed to fold the stacks recorded by perf. Error: {e}"))?;
+    if folded.is_empty() {
+        return Err("perf recorded no samples".to_string());
+    }
+    let path = svg.with_extension("folded");
+    fs::write(&path, &folded)
+        .map_err(|e| format!("Failed to write {}. Error: {e}", path.display()))?;
+
+    let file = fs::File::create(svg)
+        .map_err(|e| format!("Failed to create {}. Error: {e}", svg.display()))?;
+    let mut options = inferno::flamegraph::Options::default();
+    options.title = title.to_string();
+    inferno::flamegraph::from_reader(&mut options, &folded[..], BufWriter::new(file))
+        .map_err(|e| format!("Failed to render {}. Error: {e}", svg.display()))
+}
+
+impl Flamegraphs {
+    /// Whether no project could be profiled with any source.
+    pub fn is_empty(&self) -> bool {
+        self.profiles
+            .iter()
+            .all(|p| p.svgs.iter().all(Option::is_none))
+    }
+}
+
+/// Renders the `flamegraphs` as a markdown table linking them, with a column per source. The
+/// sources without a flamegraph show "n/a".
+///
+/// Empty without any flamegraph.
+pub fn render(flamegraphs: &Flamegraphs) -> String {
+    if flamegraphs.is_empty() {
+        return String::new();
+    }
+    let Flamegraphs { sources, profiles } = flamegraphs;
+    let mut out =
+        String::from("\n## flamegraphs of the worst regressions\n\n| Project | Regression |");
+    for source in sources {
+        let _ = write!(out, " {} |", ui::escape_markdown(source));
+    }
+    out.push_str("\n|---------|------------|");
+    out.push_str(&"---|".repeat(sources.len()));
+    out.push('\n');
+    for profile in profiles {
+        let _ = write!(
+            out,
+            "| {} | {:+.1}% |",
+            ui::escape_markdown(&profile.project),
+            profile.regression
+        );
+        for svg in &profile.svgs {
+            match svg {
+                Some(svg) => {
+                    let name = svg.file_name().unwrap_or_default().to_string_lossy();
+                    let _ = write!(out, " [{name}]({}) |", svg.display());
+                }
+                None => out.push_str(" n/a |"),
+            }
+        }
+        out.push('\n');
+    }
+    let _ = writeln!(out, "\nnote: {NOTE}");
+    out
+}
+
+/// Note below the table of the flamegraphs.
+pub const NOTE: &str = "a single run of every project and source profiled with `perf record -g`, after the benchmark and without timing it. The folded stacks are next to every SVG (`.folded`).";
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{
+        benchmark::{Source, SourceResults, Tested},
+        reporter::ConsoleReporter,
+        runner::{Response, ScriptedRunner},
+    };
+
+    const PERF_SCRIPT: &str = "\
+forge 4242 [001] 1000.000001:     250000 cpu-clock:u:
+\t    55d5a2b1c3d4 revm::interpreter::run+0x24 (/usr/bin/forge)
+\t    55d5a2b1c000 forge::main+0x10 (/usr/bin/forge)
+
+forge 4242 [001] 1000.000251:     250000 cpu-clock:u:
+\t    55d5a2b1c000 forge::main+0x10 (/usr/bin/forge)
+
+";
+
+    #[test]
+    fn test_worst_regressed() {
+        let source = |name: &str, tests| SourceResults {
+            source: Source::Branch(name.to_string()),
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        };
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 1,
+            results: vec![
+                source(
+                    "master",
+                    vec![
+                        Tested::fixture("a/a", 10.0),
+                        Tested::fixture("b/b", 10.0),
+                        Tested::fixture("c/c", 10.0),
+                        Tested::fixture("d/d", 10.0),
+                    ],
+                ),
+                source(
+                    "feat",
+                    vec![
+                        Tested::fixture("a/a", 11.0),
+                        Tested::fixture("b/b", 8.0),
+                        Tested::fixture("c/c", 15.0),
+                    ],
+                ),
+                source(
+                    "other",
+                    vec![
+                        Tested::fixture("a/a", 13.0),
+                        Tested::fixture("b/b", 9.0),
+                        Tested::fixture("c/c", 10.0),
+                        Tested::fixture("d/d", 10.5),
+                    ],
+                ),
+            ],
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        };
+        let names = |n| {
+            worst_regressed(&b, n)
+                .into_iter()
+                .map(|(name, diff)| (name, diff.round()))
+                .collect::<Vec<_>>()
+        };
+        // The worst comparison counts, and b/b, faster with both, isn't a regression.
+        assert_eq!(names(5), [("c/c", 50.0), ("a/a", 30.0), ("d/d", 5.0)]);
+        assert_eq!(names(1), [("c/c", 50.0)]);
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg = dir.path().join("a__a.baseline.svg");
+        render_svg(PERF_SCRIPT.as_bytes(), "forge test a/a (baseline)", &svg).unwrap();
+        let folded = fs::read_to_string(svg.with_extension("folded")).unwrap();
+        assert!(folded.contains("forge;forge::main;revm::interpreter::run 250000\n"));
+        let svg = fs::read_to_string(&svg).unwrap();
+        assert!(svg.contains("forge test a/a (baseline)"));
+        assert!(svg.contains("revm::interpreter::run"));
+
+        let err = render_svg(b"", "empty", &dir.path().join("empty.svg")).unwrap_err();
+        assert_eq!(err, "perf recorded no samples");
+    }
+
+    #[test]
+    fn test_profile_regressions() {
+        let dir = tempfile::tempdir().unwrap();
+        let repos = [ProjectConfig::new("a/a"), ProjectConfig::new("b/b")];
+        let sides = [
+            ("baseline".to_string(), Some(Toolchain::default())),
+            ("comparison".to_string(), Some(Toolchain::default())),
+            // A recorded source has nothing to profile.
+            ("comparison #2".to_string(), None),
+        ];
+        // The comparison run fails, and only warns.
+        let runner = ScriptedRunner::default()
+            .on(
+                "perf record",
+                [Response::ok(""), Response::fail(1, "", "boom")],
+            )
+            .on("perf script", [Response::ok(PERF_SCRIPT)]);
+        let profiles = profile_regressions(
+            &[("a/a", 25.0)],
+            &repos,
+            &sides,
+            2,
+            dir.path(),
+            &runner,
+            &ConsoleReporter,
+        );
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].project, "a/a");
+        assert_eq!(
+            profiles[0].svgs,
+            [Some(dir.path().join("a__a.baseline.svg")), None, None]
+        );
+        assert!(dir.path().join("a__a.baseline.svg").exists());
+
+        let calls = runner.calls();
+        // Profiled once per source, with the timed command.
+        let profiled: Vec<&String> = calls
+            .iter()
+            .filter(|c| c.starts_with("perf record"))
+            .collect();
+        assert_eq!(profiled.len(), 2);
+        assert!(profiled[0].ends_with(" -- forge test -vv"));
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|c| c.starts_with("perf script"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let profiles = vec![
+            Profile {
+                project: "a/a".to_string(),
+                regression: 25.0,
+                svgs: vec![
+                    Some(PathBuf::from("flamegraphs/a__a.baseline.svg")),
+                    Some(PathBuf::from("flamegraphs/a__a.comparison.svg")),
+                ],
+            },
+            Profile {
+                project: "b/b".to_string(),
+                regression: 4.0,
+                svgs: vec![None, Some(PathBuf::from("flamegraphs/b__b.comparison.svg"))],
+            },
+        ];
+        let sources = vec!["stable".to_string(), "feat".to_string()];
+        let mut flamegraphs = Flamegraphs { sources, profiles };
+        let markdown = render(&flamegraphs);
+        assert!(markdown.contains(
+            "| Project | Regression | stable | feat |\n|---------|------------|---|---|\n"
+        ));
+        assert!(markdown.contains(
+            "| a/a | +25.0% | [a__a.baseline.svg](flamegraphs/a__a.baseline.svg) | [a__a.comparison.svg](flamegraphs/a__a.comparison.svg) |\n"
+        ));
+        assert!(markdown.contains(
+            "| b/b | +4.0% | n/a | [b__b.comparison.svg](flamegraphs/b__b.comparison.svg) |\n"
+        ));
+
+        assert!(markdown.ends_with(&format!("\nnote: {NOTE}\n")));
+
+        flamegraphs.profiles = vec![Profile {
+            project: "a/a".to_string(),
+            regression: 25.0,
+            svgs: vec![None, None],
+        }];
+        assert_eq!(render(&flamegraphs), "");
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::ProjectConfig;
+
+/// Cheatcodes creating a fork, pinned to a block (or a transaction) by their second argument.
+const FORK_CHEATCODES: &[&str] = &["createFork", "createSelectFork"];
+
+/// Cheatcode moving a fork to a block, pinning the forks created without one in the same file.
+const ROLL_CHEATCODE: &str = "rollFork";
+
+/// Why the fork tests of `project`, cloned in `dir`, seem to fork the latest block, if they do.
+///
+/// The workload of such tests changes with the chain, so that runs at different times (e.g. the
+/// sides of a `diff`) don't benchmark the same thing. Forking is detected from:
+/// - the `eth_rpc_url` of its `foundry.toml`, or a fork URL set by its env vars or `test_args`,
+///   pinned by `fork_block_number` (or a `@<block>` suffix of the URL);
+/// - the fork cheatcodes of its test sources, pinned by their block argument, or by a `rollFork`
+///   in the same file.
+pub fn unpinned_fork(project: &ProjectConfig, dir: &Path) -> Option<String> {
+    let config = fs::read_to_string(dir.join("foundry.toml"))
+        .ok()
+        .and_then(|config| config.parse::<toml::Table>().ok())
+        .unwrap_or_default();
+    let profiles: Vec<&toml::Table> = config
+        .get("profile")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(|profiles| profiles.values().filter_map(toml::Value::as_table))
+        .collect();
+
+    let fork_url = profiles
+        .iter()
+        .find_map(|profile| profile.get("eth_rpc_url").and_then(toml::Value::as_str))
+        .map(str::to_string)
+        .or_else(|| project.env_value("FOUNDRY_ETH_RPC_URL"))
+        .or_else(|| project.env_value("ETH_RPC_URL"))
+        .or_else(|| {
+            has_arg(&project.test_args, &["--fork-url", "-f", "--rpc-url"]).then(String::new)
+        });
+    if let Some(url) = fork_url {
+        let pinned = profiles
+            .iter()
+            .any(|profile| profile.contains_key("fork_block_number"))
+            || project.env_value("FOUNDRY_FORK_BLOCK_NUMBER").is_some()
+            || has_arg(&project.test_args, &["--fork-block-number"])
+            || url.rsplit_once('@').is_some_and(|(_, block)| {
+                !block.is_empty() && block.bytes().all(|b| b.is_ascii_digit())
+            });
+        if !pinned {
+            return Some("its fork URL is set without a fork_block_number".to_string());
+        }
+    }
+
+    let test_dir = profiles
+        .iter()
+        .find_map(|profile| profile.get("test").and_then(toml::Value::as_str))
+        .unwrap_or("test");
+    let mut sources = Vec::new();
+    solidity_files(&dir.join(test_dir), &mut sources);
+    sources.sort();
+    sources.into_iter().find_map(|path| {
+        let source = strip_comments(&fs::read_to_string(&path).ok()?);
+        let cheatcode = unpinned_cheatcode(&source)?;
+        let path = path.strip_prefix(dir).unwrap_or(&path);
+        Some(format!(
+            "{} calls {cheatcode} without a block number",
+            path.display()
+        ))
+    })
+}
+
+/// Whether `args` contain any of the `flags`, alone or as `--flag=value`.
+fn has_arg(args: &[String], flags: &[&str]) -> bool {
+    args.iter().any(|arg| {
+        let flag = arg.split_once('=').map_or(arg.as_str(), |(flag, _)| flag);
+        flags.contains(&flag)
+    })
+}
+
+/// Collects the Solidity files under `dir`, recursively. Unreadable entries are skipped.
+fn solidity_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => solidity_files(&path, files),
+            Ok(_) if path.extension().is_some_and(|ext| ext == "sol") => files.push(path),
+            _ => {}
+        }
+    }
+}
+
+/// The first fork cheatcode of `source` called without a block argument, unless the fork is
+/// rolled to a block afterwards.
+fn unpinned_cheatcode(source: &str) -> Option<&'static str> {
+    if source.contains(&format!("{ROLL_CHEATCODE}(")) {
+        return None;
+    }
+    FORK_CHEATCODES.iter().copied().find(|cheatcode| {
+        let call = format!("{cheatcode}(");
+        source.match_indices(&call).any(|(start, _)| {
+            let standalone = !source[..start]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_');
+            standalone && argument_count(&source[start + call.len()..]) == Some(1)
+        })
+    })
+}
+
+/// Number of arguments of the call whose arguments start `rest`, up to its closing parenthesis.
+fn argument_count(rest: &str) -> Option<usize> {
+    let (mut depth, mut count, mut empty) = (0usize, 1, true);
+    let mut quote = None;
+    for c in rest.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')') if depth == 0 => return Some(if empty { 0 } else { count }),
+            (None, ')' | ']' | '}') => depth = depth.saturating_sub(1),
+            (None, ',') if depth == 0 => count += 1,
+            _ => {}
+        }
+        empty &= c.is_whitespace();
+    }
+    None
+}
+
+/// `source` without its `//` and `/* */` comments, so that commented out forks are ignored.
+fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find('/') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start..];
+        if after.starts_with("//") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+        } else if let Some(comment) = after.strip_prefix("/*") {
+            rest = comment.find("*/").map_or("", |end| &comment[end + 2..]);
+        } else {
+            out.push('/');
+            rest = &after[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_argument_count() {
+        assert_eq!(argument_count("\"mainnet\");"), Some(1));
+        assert_eq!(
+            argument_count("vm.envString(\"RPC\"), 19_000_000);"),
+            Some(2)
+        );
+        assert_eq!(argument_count("\"a,b\", f(1, 2));"), Some(2));
+        assert_eq!(argument_count(");"), Some(0));
+        assert_eq!(argument_count("\"mainnet\""), None);
+    }
+
+    #[test]
+    fn test_unpinned_fork() {
+        let dir = tempdir().unwrap();
+        let project = ProjectConfig::new("a/a");
+        fs::create_dir_all(dir.path().join("test/fork")).unwrap();
+        fs::write(
+            dir.path().join("test/Unit.t.sol"),
+            "// vm.createSelectFork(\"mainnet\");\nfunction _createFork(string memory) {}",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("test/fork/Pinned.t.sol"),
+            "uint256 fork = vm.createFork(vm.rpcUrl(\"mainnet\"), 19_000_000);",
+        )
+        .unwrap();
+        assert_eq!(unpinned_fork(&project, dir.path()), None);
+
+        fs::write(
+            dir.path().join("test/fork/Latest.t.sol"),
+            "vm.createSelectFork(vm.rpcUrl(\"mainnet\"));",
+        )
+        .unwrap();
+        assert_eq!(
+            unpinned_fork(&project, dir.path()).unwrap(),
+            format!(
+                "{} calls createSelectFork without a block number",
+                Path::new("test/fork/Latest.t.sol").display()
+            )
+        );
+        // Rolling the fork to a block pins it.
+        fs::write(
+            dir.path().join("test/fork/Latest.t.sol"),
+            "vm.createSelectFork(\"mainnet\");\nvm.rollFork(19_000_000);",
+        )
+        .unwrap();
+        assert_eq!(unpinned_fork(&project, dir.path()), None);
+
+        fs::write(
+            dir.path().join("foundry.toml"),
+            "[profile.default]\neth_rpc_url = \"https://rpc.example\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            unpinned_fork(&project, dir.path()).unwrap(),
+            "its fork URL is set without a fork_block_number"
+        );
+        let pinned = project
+            .clone()
+            .with_env_vars(vec!["FOUNDRY_FORK_BLOCK_NUMBER"], vec!["19000000"]);
+        assert_eq!(unpinned_fork(&pinned, dir.path()), None);
+        fs::write(
+            dir.path().join("foundry.toml"),
+            "[profile.ci]\neth_rpc_url = \"https://rpc.example\"\nfork_block_number = 19000000\n",
+        )
+        .unwrap();
+        assert_eq!(unpinned_fork(&project, dir.path()), None);
+    }
+}
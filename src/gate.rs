@@ -0,0 +1,463 @@
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{env, fmt};
+
+use crate::{
+    benchmark::{Benchmarks, Measured, SourceResults, Tested},
+    ui,
+    utils::ProjectConfig,
+};
+
+/// Exit code of a diff run that failed the regression gate.
+pub const REGRESSION_EXIT_CODE: u8 = 3;
+
+/// Metric checked by the regression gate.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum GateMetric {
+    /// The relative test time diff of every project.
+    #[default]
+    Project,
+    /// The geometric mean of the test time ratios of all projects.
+    Geomean,
+}
+
+/// A comparison source whose test times regressed beyond the gate's threshold.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Violation {
+    /// Name of the comparison source.
+    pub source: String,
+    /// Regressed project, or `None` if the aggregate of all projects regressed.
+    pub project: Option<String>,
+    /// Relative test time diff (in %) against the baseline.
+    pub relative_diff: f64,
+}
+
+/// Outcome of the regression gate.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GateReport {
+    /// Maximum allowed relative diff (in %).
+    pub threshold: f64,
+    pub metric: GateMetric,
+    /// Projects that are not checked by the gate.
+    pub excluded: Vec<String>,
+    pub violations: Vec<Violation>,
+}
+
+impl GateReport {
+    /// Checks the test times of every comparison source of `b` against the baseline.
+    ///
+    /// Only projects with results for both sources are checked.
+    pub fn evaluate(
+        b: &Benchmarks,
+        threshold: f64,
+        metric: GateMetric,
+        excluded: &[String],
+    ) -> Self {
+        let baseline = b.baseline();
+        let mut violations = Vec::new();
+
+        for vs in b.comparisons() {
+            let pairs = test_time_pairs(baseline, vs, excluded);
+            match metric {
+                GateMetric::Project => {
+                    for (project, before, after) in pairs {
+                        let diff = ui::relative_diff(before, after);
+                        if diff > threshold {
+                            violations.push(Violation {
+                                source: vs.display_name().to_string(),
+                                project: Some(project.to_string()),
+                                relative_diff: diff,
+                            });
+                        }
+                    }
+                }
+                GateMetric::Geomean => {
+                    if let Some(ratio) = geometric_mean_ratio(&pairs) {
+                        let diff = (ratio - 1.0) * 100.0;
+                        if diff > threshold {
+                            violations.push(Violation {
+                                source: vs.display_name().to_string(),
+                                project: None,
+                                relative_diff: diff,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            threshold,
+            metric,
+            excluded: excluded.to_vec(),
+            violations,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// The `(project, before, after)` test times of the projects tested with both `baseline` and `vs`,
+/// except the `excluded` ones.
+fn test_time_pairs<'a>(
+    baseline: &'a SourceResults,
+    vs: &SourceResults,
+    excluded: &[String],
+) -> Vec<(&'a str, f64, f64)> {
+    baseline
+        .tests
+        .iter()
+        .filter(|before| !excluded.contains(&before.name))
+        .filter_map(|before| {
+            let after = vs.tests.iter().find(|t| t.name == before.name)?;
+            Some((
+                before.name.as_str(),
+                before.test_time()?,
+                after.test_time()?,
+            ))
+        })
+        .collect()
+}
+
+/// Geometric mean of the `after / before` ratios, ignoring projects with a zero baseline.
+fn geometric_mean_ratio(pairs: &[(&str, f64, f64)]) -> Option<f64> {
+    let logs: Vec<f64> = pairs
+        .iter()
+        .filter(|(_, before, after)| *before > 0.0 && *after > 0.0)
+        .map(|(_, before, after)| (after / before).ln())
+        .collect();
+    if logs.is_empty() {
+        return None;
+    }
+    Some((logs.iter().sum::<f64>() / logs.len() as f64).exp())
+}
+
+/// A time of a project checked against its [`Budget`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetCheck {
+    pub project: String,
+    /// The checked time: `build`, `test`, or `fmt` for the projects measuring it.
+    pub metric: &'static str,
+    pub time: f64,
+    pub expected: f64,
+    /// Whether `time` exceeds `expected` beyond the tolerance of the budget.
+    pub exceeded: bool,
+}
+
+impl BudgetCheck {
+    /// How much slower (in %) than expected the project was.
+    pub fn relative_diff(&self) -> f64 {
+        ui::relative_diff(self.expected, self.time)
+    }
+}
+
+/// Checks the times of `tested` against the budget of its project in `repos`, if it has one: its
+/// build time (unless it measures `forge fmt`), and its test time if the tests ran.
+pub fn check_budget(repos: &[ProjectConfig], tested: &Tested) -> Vec<BudgetCheck> {
+    let Some(budget) = repos
+        .iter()
+        .find(|repo| repo.name == tested.name)
+        .and_then(|repo| repo.budget)
+    else {
+        return Vec::new();
+    };
+    let check = |metric, time, expected| BudgetCheck {
+        project: tested.name.clone(),
+        metric,
+        time,
+        expected,
+        exceeded: budget.exceeded(time, expected),
+    };
+    let mut checks = Vec::new();
+    if let Some(expected) = budget.build_time
+        && tested.command.builds()
+    {
+        checks.push(check("build", tested.build_time, expected));
+    }
+    if let Some(expected) = budget.test_time
+        && tested.runs > 0
+    {
+        let metric = match tested.command {
+            Measured::Test => "test",
+            Measured::Fmt => "fmt",
+        };
+        checks.push(check(metric, tested.avg_test_time, expected));
+    }
+    checks
+}
+
+/// Whether to print GitHub Actions annotations: when running in a workflow (`GITHUB_ACTIONS` is
+/// set), or if forced with `--gh-annotations`.
+pub fn annotations_enabled(forced: bool) -> bool {
+    forced || env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true")
+}
+
+/// Level of a GitHub Actions annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationLevel {
+    Warning,
+    Error,
+}
+
+/// A regression reported as a GitHub Actions annotation, printed as a workflow command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub level: AnnotationLevel,
+    pub message: String,
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Error => "error",
+        };
+        write!(
+            f,
+            "::{level} title={}::{}",
+            escape_property("Performance regression"),
+            escape_data(&self.message)
+        )
+    }
+}
+
+/// Annotates the test time regressions of every comparison source of `b`: as errors beyond the
+/// `fail` threshold (in %), and as warnings beyond the `warn` one.
+///
+/// Like [`GateReport::evaluate`], the thresholds apply to each project or to their geometric
+/// mean depending on `metric`, and the `excluded` projects are left out.
+pub fn annotations(
+    b: &Benchmarks,
+    warn: Option<f64>,
+    fail: Option<f64>,
+    metric: GateMetric,
+    excluded: &[String],
+) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+    for vs in b.comparisons() {
+        let pairs = test_time_pairs(b.baseline(), vs, excluded);
+        let regressions: Vec<(&str, f64, f64)> = match metric {
+            GateMetric::Project => pairs,
+            GateMetric::Geomean => {
+                // The ratio of the geometric means is the geometric mean of the ratios.
+                let positive: Vec<_> = pairs
+                    .iter()
+                    .filter(|(_, before, after)| *before > 0.0 && *after > 0.0)
+                    .collect();
+                if positive.is_empty() {
+                    continue;
+                }
+                let mean = |time: fn(&&(&str, f64, f64)) -> f64| {
+                    (positive.iter().map(|p| time(p).ln()).sum::<f64>() / positive.len() as f64)
+                        .exp()
+                };
+                vec![(
+                    "the geometric mean of the projects",
+                    mean(|(_, before, _)| *before),
+                    mean(|(_, _, after)| *after),
+                )]
+            }
+        };
+        for (project, before, after) in regressions {
+            let diff = ui::relative_diff(before, after);
+            let level = if fail.is_some_and(|fail| diff > fail) {
+                AnnotationLevel::Error
+            } else if warn.is_some_and(|warn| diff > warn) {
+                AnnotationLevel::Warning
+            } else {
+                continue;
+            };
+            annotations.push(Annotation {
+                level,
+                message: format!(
+                    "{project} is {diff:.1}% slower with {}: {before:.2}s before, {after:.2}s after",
+                    vs.display_name()
+                ),
+            });
+        }
+    }
+    annotations
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property (e.g. the title) of a workflow command.
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{benchmark::Source, utils::Budget};
+
+    fn benchmarks(before: &str, after: &str, times: &[(&str, f64, f64)]) -> Benchmarks {
+        Benchmarks {
+            verbosity: String::new(),
+            num_runs: 1,
+            results: vec![
+                SourceResults {
+                    source: Source::Version(before.to_string()),
+                    foundry_repo: "foundry-rs/foundry".to_string(),
+                    label: None,
+                    forge: None,
+                    tests: times
+                        .iter()
+                        .map(|(n, b, _)| Tested::fixture(n, *b))
+                        .collect(),
+                },
+                SourceResults {
+                    source: Source::Branch(after.to_string()),
+                    foundry_repo: "foundry-rs/foundry".to_string(),
+                    label: None,
+                    forge: None,
+                    tests: times
+                        .iter()
+                        .map(|(n, _, a)| Tested::fixture(n, *a))
+                        .collect(),
+                },
+            ],
+            notes: vec![],
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        }
+    }
+
+    #[test]
+    fn test_gate_per_project() {
+        let (before, after) = ("stable".to_string(), "my-branch".to_string());
+        let b = benchmarks(&before, &after, &[("a/a", 10.0, 11.0), ("b/b", 10.0, 10.2)]);
+
+        let report = GateReport::evaluate(&b, 5.0, GateMetric::Project, &[]);
+        assert!(!report.passed());
+        assert_eq!(report.violations.len(), 1);
+        assert_eq!(report.violations[0].source, "my-branch");
+        assert_eq!(report.violations[0].project.as_deref(), Some("a/a"));
+
+        let report = GateReport::evaluate(&b, 5.0, GateMetric::Project, &["a/a".to_string()]);
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_gate_geomean() {
+        let (before, after) = ("stable".to_string(), "my-branch".to_string());
+        // One project regresses by 21%, the other improves by ~17%: the aggregate is flat.
+        let b = benchmarks(&before, &after, &[("a/a", 10.0, 12.1), ("b/b", 12.1, 10.0)]);
+
+        assert!(GateReport::evaluate(&b, 5.0, GateMetric::Geomean, &[]).passed());
+        assert!(!GateReport::evaluate(&b, 5.0, GateMetric::Project, &[]).passed());
+
+        let b = benchmarks(&before, &after, &[("a/a", 10.0, 12.1), ("b/b", 10.0, 12.1)]);
+        let report = GateReport::evaluate(&b, 5.0, GateMetric::Geomean, &[]);
+        assert_eq!(report.violations.len(), 1);
+        assert!(report.violations[0].project.is_none());
+        assert!((report.violations[0].relative_diff - 21.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let (before, after) = ("stable".to_string(), "my-branch".to_string());
+        let b = benchmarks(
+            &before,
+            &after,
+            &[
+                ("a/a", 10.0, 12.1),
+                ("b/b", 10.0, 10.6),
+                ("c/c", 10.0, 10.1),
+            ],
+        );
+
+        let annotations = annotations(&b, Some(5.0), Some(10.0), GateMetric::Project, &[]);
+        let lines: Vec<String> = annotations.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "::error title=Performance regression::a/a is 21.0%25 slower with my-branch: 10.00s before, 12.10s after",
+                "::warning title=Performance regression::b/b is 6.0%25 slower with my-branch: 10.00s before, 10.60s after",
+            ]
+        );
+
+        let excluded = ["a/a".to_string()];
+        let annotations = super::annotations(&b, None, Some(5.0), GateMetric::Project, &excluded);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].level, AnnotationLevel::Error);
+        assert!(annotations[0].message.starts_with("b/b"));
+
+        let annotations = super::annotations(&b, Some(5.0), None, GateMetric::Geomean, &[]);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].level, AnnotationLevel::Warning);
+        assert!(
+            annotations[0]
+                .message
+                .starts_with("the geometric mean of the projects is")
+        );
+        assert!(super::annotations(&b, None, None, GateMetric::Project, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_escape_workflow_command() {
+        assert_eq!(escape_data("50%\r\ndone"), "50%25%0D%0Adone");
+        assert_eq!(escape_property("a: b, c"), "a%3A b%2C c");
+    }
+
+    #[test]
+    fn test_check_budget() {
+        let budget = Budget {
+            build_time: Some(1.0),
+            test_time: Some(10.0),
+            tolerance: 10.0,
+        };
+        let repos = [
+            ProjectConfig::new("a/a"),
+            ProjectConfig {
+                budget: Some(budget),
+                ..ProjectConfig::new("b/b")
+            },
+        ];
+        // Projects without a budget aren't checked.
+        assert!(check_budget(&repos, &Tested::fixture("a/a", 100.0)).is_empty());
+
+        let checks = check_budget(&repos, &Tested::fixture("b/b", 11.5));
+        let outcome: Vec<_> = checks
+            .iter()
+            .map(|check| (check.metric, check.time, check.expected, check.exceeded))
+            .collect();
+        assert_eq!(
+            outcome,
+            [("build", 1.0, 1.0, false), ("test", 11.5, 10.0, true)]
+        );
+        assert!((checks[1].relative_diff() - 15.0).abs() < 1e-9);
+        // Within the tolerance.
+        assert!(!check_budget(&repos, &Tested::fixture("b/b", 11.0))[1].exceeded);
+
+        // Without test runs (--stop-after build), only the build time is checked.
+        let built = Tested {
+            runs: 0,
+            ..Tested::fixture("b/b", 0.0)
+        };
+        assert_eq!(check_budget(&repos, &built).len(), 1);
+        // Projects measuring `forge fmt` don't build.
+        let fmt = Tested {
+            command: Measured::Fmt,
+            build_time: 0.0,
+            ..Tested::fixture("b/b", 12.0)
+        };
+        let checks = check_budget(&repos, &fmt);
+        assert_eq!(checks.len(), 1);
+        assert_eq!((checks[0].metric, checks[0].exceeded), ("fmt", true));
+    }
+}
@@ -0,0 +1,381 @@
+use eyre::{Context, Result, eyre};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use yansi::Paint;
+
+use crate::{
+    cmd::Verbosity,
+    results::{HostInfo, RunResults},
+    toolchain::ForgeVersion,
+};
+
+/// Version of the history entries format. Bumped on breaking changes.
+pub const HISTORY_VERSION: u32 = 1;
+
+/// A benchmark run recorded with `--record`, stored as a line of the history file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub version: u32,
+    /// Unix timestamp (in seconds) of when the run was recorded.
+    pub timestamp: u64,
+    /// Short hash of the host metadata, to tell apart runs recorded on different machines.
+    pub host_fingerprint: String,
+    pub hostname: Option<String>,
+    pub num_runs: usize,
+    pub verbosity: Verbosity,
+    /// Every benchmarked source, the first one being the baseline.
+    pub sources: Vec<HistorySource>,
+}
+
+/// Per-project averages of a single source of a recorded run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySource {
+    /// Display name of the source.
+    pub name: String,
+    pub forge_version: Option<ForgeVersion>,
+    pub projects: Vec<ProjectTiming>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTiming {
+    pub name: String,
+    pub build_time: f64,
+    /// `None` for a project that was only built.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_test_time: Option<f64>,
+}
+
+impl HistoryEntry {
+    pub fn from_results(results: &RunResults) -> Self {
+        Self {
+            version: HISTORY_VERSION,
+            timestamp: results.timestamp,
+            host_fingerprint: host_fingerprint(&results.host),
+            hostname: results.host.hostname.clone(),
+            num_runs: results.num_runs,
+            verbosity: results.verbosity,
+            sources: results
+                .sources
+                .iter()
+                .map(|source| HistorySource {
+                    name: source.label.clone().unwrap_or_else(|| source.name.clone()),
+                    forge_version: source.forge_version.clone(),
+                    projects: source
+                        .results
+                        .iter()
+                        .map(|t| ProjectTiming {
+                            name: t.name.clone(),
+                            build_time: t.build_time,
+                            avg_test_time: t.test_time(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the default location of the history file, under the user data directory.
+pub fn default_path() -> Result<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .ok_or_else(|| {
+            eyre!("Failed to locate the user data directory, pass the history file with --history-file")
+        })?;
+    Ok(data_dir.join("foundry-benchmarks").join("history.jsonl"))
+}
+
+/// Appends the results of a run to the history file at `path`, creating it if needed.
+pub fn record(path: &Path, results: &RunResults) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("Failed to create directory: {}", dir.display()))?;
+    }
+    let line = serde_json::to_string(&HistoryEntry::from_results(results))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .wrap_err_with(|| format!("Failed to open history file: {}", path.display()))?;
+    writeln!(file, "{line}")
+        .wrap_err_with(|| format!("Failed to write history file: {}", path.display()))
+}
+
+/// Loads every entry of the history file at `path`, oldest first.
+///
+/// A missing file is an empty history. Entries that can't be parsed (e.g. written by a newer
+/// version of the tool) are skipped with a warning.
+pub fn load(path: &Path) -> Result<Vec<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read history file: {}", path.display()))?;
+
+    let mut entries = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str::<serde_json::Value>(line)
+            .map_err(|e| e.to_string())
+            .and_then(
+                |value| match value.get("version").and_then(|v| v.as_u64()) {
+                    Some(version) if version == HISTORY_VERSION as u64 => {
+                        serde_json::from_value::<HistoryEntry>(value).map_err(|e| e.to_string())
+                    }
+                    Some(version) => Err(format!("unsupported version {version}")),
+                    None => Err("missing 'version'".to_string()),
+                },
+            );
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!(
+                "{} Skipping line {} of {}: {e}",
+                Paint::yellow("WARNING:").bold(),
+                i + 1,
+                path.display()
+            ),
+        }
+    }
+    entries.sort_by_key(|entry| entry.timestamp);
+    Ok(entries)
+}
+
+/// Narrows down `entries` to the last `last` ones recorded at or after `since`, keeping only the
+/// results of `projects` (if not empty).
+pub fn filter(
+    entries: Vec<HistoryEntry>,
+    since: Option<u64>,
+    projects: &[String],
+    last: usize,
+) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = entries
+        .into_iter()
+        .filter(|entry| since.is_none_or(|since| entry.timestamp >= since))
+        .map(|mut entry| {
+            if !projects.is_empty() {
+                for source in &mut entry.sources {
+                    source.projects.retain(|p| projects.contains(&p.name));
+                }
+            }
+            entry
+        })
+        .filter(|entry| entry.sources.iter().any(|s| !s.projects.is_empty()))
+        .collect();
+    let skip = entries.len().saturating_sub(last);
+    entries.drain(..skip);
+    entries
+}
+
+/// Test times of a project across a window of recorded runs, as plotted by `history trend`.
+#[derive(Debug)]
+pub struct Trend<'a> {
+    pub project: &'a str,
+    /// Baseline test time of the project in every run of the window, if benchmarked in it.
+    pub points: Vec<Option<f64>>,
+    pub oldest: TrendPoint<'a>,
+    pub newest: TrendPoint<'a>,
+}
+
+/// A measured point of a [`Trend`], along with the forge build it was measured with.
+#[derive(Debug)]
+pub struct TrendPoint<'a> {
+    pub time: f64,
+    pub forge: Option<&'a ForgeVersion>,
+}
+
+/// Computes the trend of every project benchmarked in `entries`, sorted by project name.
+///
+/// Only the baseline (i.e. the first source) of each run is plotted, so that the points of a
+/// project are measured with the same kind of source.
+pub fn trends(entries: &[HistoryEntry]) -> Vec<Trend<'_>> {
+    let mut projects: Vec<&str> = entries
+        .iter()
+        .filter_map(|entry| entry.sources.first())
+        .flat_map(|source| &source.projects)
+        .map(|p| p.name.as_str())
+        .collect();
+    projects.sort_unstable();
+    projects.dedup();
+
+    projects
+        .into_iter()
+        .filter_map(|project| {
+            let measured: Vec<Option<TrendPoint>> = entries
+                .iter()
+                .map(|entry| {
+                    let source = entry.sources.first()?;
+                    let timing = source.projects.iter().find(|p| p.name == project)?;
+                    Some(TrendPoint {
+                        time: timing.avg_test_time?,
+                        forge: source.forge_version.as_ref(),
+                    })
+                })
+                .collect();
+            let points = measured
+                .iter()
+                .map(|p| p.as_ref().map(|p| p.time))
+                .collect();
+            let mut measured = measured.into_iter().flatten();
+            let oldest = measured.next()?;
+            let newest = measured.last().unwrap_or(TrendPoint {
+                time: oldest.time,
+                forge: oldest.forge,
+            });
+            Some(Trend {
+                project,
+                points,
+                oldest,
+                newest,
+            })
+        })
+        .collect()
+}
+
+/// Fingerprint of the current machine, to tell apart the runs recorded on it.
+pub fn current_fingerprint() -> String {
+    host_fingerprint(&HostInfo::current())
+}
+
+/// Hashes the host metadata with FNV-1a, which (unlike the std hasher) is stable across builds.
+pub fn host_fingerprint(host: &HostInfo) -> String {
+    let key = format!(
+        "{}|{}|{}|{}|{}",
+        host.hostname.as_deref().unwrap_or_default(),
+        host.os,
+        host.arch,
+        host.cpus.unwrap_or_default(),
+        host.cpu_model.as_deref().unwrap_or_default()
+    );
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:08x}", hash >> 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::Tested;
+    use tempfile::tempdir;
+
+    fn results(timestamp: u64, times: &[(&str, f64)]) -> RunResults {
+        let mut results = RunResults::new(
+            Some(ForgeVersion {
+                version: "1.2.3-stable".to_string(),
+                commit: Some("abcdef".to_string()),
+            }),
+            3,
+            0,
+            times.iter().map(|(name, _)| name.to_string()).collect(),
+            times
+                .iter()
+                .map(|(name, time)| Tested {
+                    runs: 3,
+                    samples: vec![*time; 3],
+                    ..Tested::fixture(name, *time)
+                })
+                .collect(),
+            Vec::new(),
+        );
+        results.timestamp = timestamp;
+        results
+    }
+
+    #[test]
+    fn test_history_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested").join("history.jsonl");
+        assert!(load(&path).unwrap().is_empty());
+
+        record(&path, &results(200, &[("a/a", 2.0)])).unwrap();
+        record(&path, &results(100, &[("a/a", 1.0), ("b/b", 3.0)])).unwrap();
+        // Entries of newer formats are skipped.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, r#"{{"version":99,"timestamp":300}}"#).unwrap();
+
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].timestamp, 100);
+        assert_eq!(entries[1].timestamp, 200);
+        assert_eq!(entries[0].sources[0].name, "1.2.3-stable");
+        assert_eq!(entries[0].sources[0].projects.len(), 2);
+        assert_eq!(entries[0].host_fingerprint.len(), 8);
+        assert_eq!(entries[0].host_fingerprint, entries[1].host_fingerprint);
+    }
+
+    #[test]
+    fn test_history_filter() {
+        let entries: Vec<_> = [
+            results(100, &[("a/a", 1.0), ("b/b", 3.0)]),
+            results(200, &[("a/a", 2.0)]),
+            results(300, &[("a/a", 3.0), ("b/b", 3.0)]),
+        ]
+        .iter()
+        .map(HistoryEntry::from_results)
+        .collect();
+
+        let filtered = filter(entries.clone(), None, &[], 2);
+        assert_eq!(
+            filtered.iter().map(|e| e.timestamp).collect::<Vec<_>>(),
+            [200, 300]
+        );
+
+        let filtered = filter(entries.clone(), Some(150), &["b/b".to_string()], 10);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].timestamp, 300);
+        assert_eq!(filtered[0].sources[0].projects.len(), 1);
+    }
+
+    #[test]
+    fn test_trends() {
+        let entries: Vec<_> = [
+            results(100, &[("a/a", 1.0), ("b/b", 3.0)]),
+            results(200, &[("a/a", 2.0)]),
+            results(300, &[("a/a", 4.0)]),
+        ]
+        .iter()
+        .map(HistoryEntry::from_results)
+        .collect();
+
+        let trends = trends(&entries);
+        assert_eq!(trends.len(), 2);
+        assert_eq!(trends[0].project, "a/a");
+        assert_eq!(trends[0].points, [Some(1.0), Some(2.0), Some(4.0)]);
+        assert_eq!(trends[0].oldest.time, 1.0);
+        assert_eq!(trends[0].newest.time, 4.0);
+        assert_eq!(
+            trends[0].newest.forge.map(|f| f.version.as_str()),
+            Some("1.2.3-stable")
+        );
+        // A project measured once is flat, and keeps its place in the window.
+        assert_eq!(trends[1].project, "b/b");
+        assert_eq!(trends[1].points, [Some(3.0), None, None]);
+        assert_eq!(trends[1].newest.time, 3.0);
+    }
+
+    #[test]
+    fn test_host_fingerprint() {
+        let host = HostInfo {
+            hostname: Some("bench-1".to_string()),
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            cpus: Some(16),
+            cpu_model: None,
+        };
+        let other = HostInfo {
+            hostname: Some("bench-2".to_string()),
+            ..host.clone()
+        };
+        assert_eq!(host_fingerprint(&host), host_fingerprint(&host.clone()));
+        assert_ne!(host_fingerprint(&host), host_fingerprint(&other));
+    }
+}
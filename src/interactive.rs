@@ -0,0 +1,94 @@
+use dialoguer::{MultiSelect, theme::ColorfulTheme};
+use eyre::{Result, eyre};
+use std::io::{self, IsTerminal};
+use yansi::Paint;
+
+use crate::{
+    cmd::{self, Cli},
+    utils::{ProjectConfig, shell_quote},
+};
+
+/// Prompts the user to select the projects to benchmark among `repos`, and prints the arguments
+/// that repeat the selection non-interactively.
+///
+/// Errors if stdin is not a terminal, or if no project is selected.
+pub fn pick_projects(cli: &Cli, repos: Vec<ProjectConfig>) -> Result<Vec<ProjectConfig>> {
+    if !io::stdin().is_terminal() {
+        return Err(eyre!(
+            "{} --interactive requires a terminal, pass the projects with --repos instead",
+            Paint::red("ERROR:").bold()
+        ));
+    }
+    if repos.is_empty() {
+        return Err(eyre!(
+            "{} No projects are configured to pick from",
+            Paint::red("ERROR:").bold()
+        ));
+    }
+
+    let names: Vec<&str> = repos.iter().map(|repo| repo.name.as_str()).collect();
+    let selected = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Projects to benchmark (space to select, enter to confirm)")
+        .items(&names)
+        .interact()?;
+    if selected.is_empty() {
+        return Err(eyre!("{} No project selected", Paint::red("ERROR:").bold()));
+    }
+
+    let repos: Vec<ProjectConfig> = repos
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| selected.contains(i))
+        .map(|(_, repo)| repo)
+        .collect();
+    println!(
+        "To repeat this selection, pass: {}",
+        selection_args(cli, &repos)
+    );
+    Ok(repos)
+}
+
+/// Arguments selecting `repos` without the prompt: the matching `--repo` args if the projects were
+/// configured with `--repo`, or `--repos` otherwise.
+fn selection_args(cli: &Cli, repos: &[ProjectConfig]) -> String {
+    let is_selected = |name: &str| repos.iter().any(|repo| repo.name == name);
+    match &cli.repo {
+        Some(args) => args
+            .iter()
+            .filter(|arg| is_selected(cmd::split_repo_spec(arg).0))
+            .map(|arg| format!("--repo {}", shell_quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => format!(
+            "--repos {}",
+            repos
+                .iter()
+                .map(|repo| repo.name.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_args() {
+        let repos = [ProjectConfig::new("a/a"), ProjectConfig::new("c/c")];
+        let cli = Cli::default();
+        assert_eq!(selection_args(&cli, &repos), "--repos a/a,c/c");
+
+        let mut cli = Cli::default();
+        cli.repo = Some(vec![
+            r#"a/a:{"remappings":["x=y"]}"#.to_string(),
+            "b/b".to_string(),
+            "c/c".to_string(),
+        ]);
+        assert_eq!(
+            selection_args(&cli, &repos),
+            r#"--repo 'a/a:{"remappings":["x=y"]}' --repo c/c"#
+        );
+    }
+}
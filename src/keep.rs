@@ -0,0 +1,152 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use tempfile::TempDir;
+
+/// Prefix of the work dir entries holding the project directories kept by a run.
+pub const KEPT_PREFIX: &str = "kept-";
+
+/// Project directories that outlive the run, instead of being removed with their `TempDir`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    /// Every project directory, with `--keep`.
+    All,
+    /// Only the directories of the projects that failed, with `--keep-failed`.
+    Failed,
+}
+
+/// What the run keeps, and the directory it keeps the projects in, see [`configure`].
+static KEPT: RwLock<Option<(Keep, PathBuf)>> = RwLock::new(None);
+/// Projects and the directories they were kept in, in creation order.
+static DIRS: Mutex<Vec<(String, PathBuf)>> = Mutex::new(Vec::new());
+
+/// Keeps the project directories selected by `keep` in `root`, instead of temporary directories.
+/// The directory is only created once a project is cloned.
+pub fn configure(keep: Keep, root: PathBuf) {
+    *KEPT.write().unwrap() = Some((keep, root));
+}
+
+/// Directory the projects of the run are kept in, if any.
+pub fn root() -> Option<PathBuf> {
+    KEPT.read().unwrap().as_ref().map(|(_, root)| root.clone())
+}
+
+/// Creates the directory `project` is cloned into: a temporary directory, or with `--keep` and
+/// `--keep-failed` a directory of [`root`] named after the project that isn't removed on drop, e.g.
+/// `owner__repo` (`owner__repo-2` for its next clone, e.g. with another source of a diff).
+pub fn project_dir(project: &str) -> io::Result<TempDir> {
+    let Some(root) = root() else {
+        return TempDir::new();
+    };
+    let dir = create_in(&root, project)?;
+    DIRS.lock()
+        .unwrap()
+        .push((project.to_string(), dir.path().to_path_buf()));
+    Ok(dir)
+}
+
+/// Creates the first free directory of `root` named after `project`, which isn't removed on drop.
+fn create_in(root: &Path, project: &str) -> io::Result<TempDir> {
+    fs::create_dir_all(root)?;
+    let name = dir_name(project);
+    let mut n = 1;
+    let mut dir = loop {
+        let suffix = if n == 1 {
+            String::new()
+        } else {
+            format!("-{n}")
+        };
+        match tempfile::Builder::new()
+            .prefix(&name)
+            .suffix(&suffix)
+            .rand_bytes(0)
+            .tempdir_in(root)
+        {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            dir => break dir?,
+        }
+    };
+    dir.disable_cleanup(true);
+    Ok(dir)
+}
+
+/// Marks the directory of a project as passed: with `--keep-failed`, it's removed on drop again.
+pub fn passed(dir: &mut TempDir) {
+    if matches!(*KEPT.read().unwrap(), Some((Keep::Failed, _))) {
+        dir.disable_cleanup(false);
+    }
+}
+
+/// The projects and the directories they were kept in, that still exist.
+pub fn kept() -> Vec<(String, PathBuf)> {
+    DIRS.lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, dir)| dir.exists())
+        .cloned()
+        .collect()
+}
+
+/// The directories `project` was kept in, that still exist.
+pub fn kept_dirs(project: &str) -> Vec<PathBuf> {
+    kept()
+        .into_iter()
+        .filter_map(|(name, dir)| (name == project).then_some(dir))
+        .collect()
+}
+
+/// Name of the kept directory of `project`, e.g. `owner__repo` for `owner/repo` and
+/// `gitlab.com__owner__repo` for `https://gitlab.com/owner/repo.git`.
+pub(crate) fn dir_name(project: &str) -> String {
+    let name = project
+        .split_once("://")
+        .map_or(project, |(_, rest)| rest)
+        .trim_end_matches('/');
+    name.strip_suffix(".git")
+        .unwrap_or(name)
+        .replace('/', "__")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_name() {
+        assert_eq!(dir_name("vectorized/solady"), "vectorized__solady");
+        assert_eq!(
+            dir_name("https://gitlab.com/owner/repo.git"),
+            "gitlab.com__owner__repo"
+        );
+        assert_eq!(dir_name("git@host:owner/repo"), "git_host_owner__repo");
+    }
+
+    #[test]
+    fn test_create_in() {
+        let root = tempfile::tempdir().unwrap();
+        let first = create_in(root.path(), "a/a").unwrap().path().to_path_buf();
+        let second = create_in(root.path(), "a/a").unwrap().path().to_path_buf();
+        // The directories outlive their `TempDir`.
+        assert_eq!(first, root.path().join("a__a"));
+        assert_eq!(second, root.path().join("a__a-2"));
+        assert!(first.is_dir() && second.is_dir());
+
+        let mut third = create_in(root.path(), "a/a").unwrap();
+        third.disable_cleanup(false);
+        let third_path = third.path().to_path_buf();
+        drop(third);
+        assert!(!third_path.exists());
+    }
+}
@@ -0,0 +1,91 @@
+//! Benchmarks the `forge build` and `forge test` times of Foundry projects.
+//!
+//! The `foundry-benchmarks` binary is a thin CLI over this library, which can also be embedded to
+//! schedule runs or report their results differently. A run resolves the [`ProjectConfig`] of
+//! every project, either built in code or loaded from a [`ConfigFile`], and hands them to
+//! [`run_pipeline`] along with the [`Toolchain`](toolchain::Toolchain) providing `forge`, the
+//! [`CommandRunner`](runner::CommandRunner) running the processes and the
+//! [`Reporter`](reporter::Reporter) presenting the progress:
+//!
+//! ```no_run
+//! use foundry_benchmarks::{
+//!     ProjectConfig, Stage, reporter::ConsoleReporter, run_pipeline, runner::SystemRunner,
+//!     toolchain::Toolchain,
+//! };
+//!
+//! let projects = [ProjectConfig::new("vectorized/solady")
+//!     .with_env_vars(vec!["FOUNDRY_FUZZ_RUNS"], vec!["256"])];
+//! // Clones and builds every project, then runs `forge test` 3 times with verbosity 0, using the
+//! // `forge` of the `PATH`.
+//! let results = run_pipeline(
+//!     &projects,
+//!     3,
+//!     0,
+//!     Stage::Test,
+//!     &Toolchain::default(),
+//!     &SystemRunner,
+//!     &ConsoleReporter,
+//! )?;
+//! for tested in &results.tested {
+//!     println!("{}: {:.2}s", tested.name, tested.avg_test_time);
+//! }
+//! for failure in &results.failures {
+//!     eprintln!("{} failed at stage '{}'", failure.project, failure.stage);
+//! }
+//! # Ok::<(), eyre::Report>(())
+//! ```
+//!
+//! The stages can also be driven one at a time with [`clone_projects`](benchmark::clone_projects),
+//! [`build_projects`](benchmark::build_projects) and [`test_projects`](benchmark::test_projects).
+//!
+//! Only the [`benchmark`], [`config`], [`reporter`], [`runner`], [`toolchain`] and [`utils`]
+//! modules are part of the library's API, the others back the commands of the binary.
+
+pub mod benchmark;
+pub mod config;
+pub mod reporter;
+pub mod runner;
+pub mod toolchain;
+pub mod utils;
+
+pub use benchmark::{Benchmarks, ProjectState, Source, Stage, Tested, run_pipeline};
+pub use config::ConfigFile;
+pub use utils::ProjectConfig;
+
+#[doc(hidden)]
+pub mod app;
+#[doc(hidden)]
+pub mod cmd;
+
+mod anvil;
+mod bisect;
+mod canary;
+mod clean;
+mod discover;
+mod doctor;
+mod env_example;
+mod eta;
+mod fingerprint;
+mod flamegraph;
+mod fork;
+mod gate;
+mod history;
+mod interactive;
+mod keep;
+mod logging;
+mod matrix;
+mod openmetrics;
+mod output_size;
+mod plan;
+mod provenance;
+mod redact;
+mod report;
+mod repro;
+mod results;
+mod rpc_cache;
+mod schema;
+mod synthetic;
+mod ui;
+mod validate;
+mod warnings;
+mod watch;
@@ -0,0 +1,310 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value, json};
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{
+    Event, Level, Metadata, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{
+    Layer,
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+};
+use yansi::Paint;
+
+use crate::ui::LogLevel;
+
+/// Format of the tool's log messages, set with `--log-format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable messages, prefixed with the project they're about.
+    #[default]
+    Pretty,
+    /// One JSON object per event and line on stderr, with the fields of the event and of its
+    /// spans (`project`, `stage`, `run`, `duration_secs`, `exit_code`, ...).
+    Json,
+}
+
+/// Installs the subscriber printing the events of the run at `level` and above, in `format`.
+pub fn init(level: LogLevel, format: LogFormat) {
+    let layer = LogLayer::new(level, format, move |level, line| {
+        if format == LogFormat::Json || level <= Level::WARN {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+    });
+    let subscriber = tracing_subscriber::registry().with(layer);
+    // Only fails if a subscriber is already installed, which then keeps printing the events.
+    let _ = tracing::subscriber::set_global_default(subscriber);
+}
+
+/// The `tracing` level of the events printed at `level`.
+fn max_level(level: LogLevel) -> Level {
+    match level {
+        LogLevel::Error => Level::ERROR,
+        LogLevel::Warn => Level::WARN,
+        LogLevel::Info => Level::INFO,
+        LogLevel::Debug => Level::DEBUG,
+    }
+}
+
+/// Fields recorded on a span or an event, in JSON.
+#[derive(Debug, Default)]
+struct Fields(Map<String, Value>);
+
+impl Fields {
+    fn insert(&mut self, field: &Field, value: Value) {
+        self.0.insert(field.name().to_string(), value);
+    }
+
+    fn str(&self, name: &str) -> Option<&str> {
+        self.0.get(name).and_then(Value::as_str)
+    }
+}
+
+impl Visit for Fields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, json!(format!("{value:?}")));
+    }
+}
+
+/// Layer printing every event with `write`, along with the fields of the spans it occurred in.
+///
+/// Spans are always recorded, so that an event printed at a lower log level still knows its
+/// project.
+struct LogLayer<W> {
+    level: Level,
+    format: LogFormat,
+    write: W,
+}
+
+impl<W: Fn(Level, &str)> LogLayer<W> {
+    fn new(level: LogLevel, format: LogFormat, write: W) -> Self {
+        Self {
+            level: max_level(level),
+            format,
+            write,
+        }
+    }
+
+    /// Formats an event of `level` with its `fields`, which occurred in `spans` (from the
+    /// outermost one).
+    fn format(&self, level: Level, spans: &[(&str, &Fields)], mut fields: Fields) -> String {
+        let message = match fields.0.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(message) => message.to_string(),
+            None => String::new(),
+        };
+        match self.format {
+            LogFormat::Pretty => {
+                let mut line = match spans.iter().rev().find_map(|(_, f)| f.str("project")) {
+                    Some(project) => format!("[{project}] {message}"),
+                    None => message,
+                };
+                for output in fields.str("output").into_iter().flat_map(str::lines) {
+                    line.push_str(&format!("\n{}", Paint::red(output).dim()));
+                }
+                line
+            }
+            LogFormat::Json => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                let mut object = Map::new();
+                object.insert("timestamp".to_string(), json!(timestamp));
+                object.insert("level".to_string(), json!(level.as_str().to_lowercase()));
+                object.insert("message".to_string(), json!(strip_ansi(&message)));
+                object.insert(
+                    "spans".to_string(),
+                    json!(spans.iter().map(|(name, _)| *name).collect::<Vec<_>>()),
+                );
+                // Inner spans and the event override the fields of the outer spans.
+                for fields in spans.iter().map(|(_, f)| &f.0).chain([&fields.0]) {
+                    for (name, value) in fields {
+                        let value = match value {
+                            Value::String(s) => json!(strip_ansi(s)),
+                            value => value.clone(),
+                        };
+                        object.insert(name.clone(), value);
+                    }
+                }
+                Value::Object(object).to_string()
+            }
+        }
+    }
+}
+
+impl<S, W> Layer<S> for LogLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: Fn(Level, &str) + 'static,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        metadata.is_span() || *metadata.level() <= self.level
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(fields) = span.extensions_mut().get_mut::<Fields>()
+        {
+            values.record(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let scope: Vec<_> = ctx
+            .event_scope(event)
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .collect();
+        let extensions: Vec<_> = scope.iter().map(|span| span.extensions()).collect();
+        let spans: Vec<(&str, &Fields)> = scope
+            .iter()
+            .zip(&extensions)
+            .filter_map(|(span, ext)| Some((span.name(), ext.get::<Fields>()?)))
+            .collect();
+        let level = *event.metadata().level();
+        (self.write)(level, &self.format(level, &spans, fields));
+    }
+}
+
+/// `text` without its ANSI escape sequences (e.g. colors).
+pub fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skips a CSI sequence up to its final byte, e.g. `\x1b[1;31m`.
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Runs `f` with a [`LogLayer`], returning the printed lines along with their level.
+    fn capture(level: LogLevel, format: LogFormat, f: impl FnOnce()) -> Vec<(Level, String)> {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let layer = LogLayer::new(level, format, move |level, line: &str| {
+            sink.lock().unwrap().push((level, line.to_string()));
+        });
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+        lines.lock().unwrap().clone()
+    }
+
+    fn build_events() {
+        let project = tracing::info_span!("project", project = "a/a");
+        let _stage = tracing::info_span!(parent: &project, "build", stage = "build").entered();
+        tracing::debug!("$ forge build");
+        tracing::info!(
+            duration_secs = 1.5,
+            "{} Elapsed time: 1.50s",
+            Paint::yellow("BUILT!")
+        );
+        tracing::error!(
+            exit_code = 1,
+            output = "line 1\nline 2",
+            "forge build failed"
+        );
+    }
+
+    #[test]
+    fn test_pretty_format() {
+        let lines = capture(LogLevel::Info, LogFormat::Pretty, build_events);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].0, Level::INFO);
+        assert_eq!(strip_ansi(&lines[0].1), "[a/a] BUILT! Elapsed time: 1.50s");
+        assert_eq!(lines[1].0, Level::ERROR);
+        assert_eq!(
+            strip_ansi(&lines[1].1),
+            "[a/a] forge build failed\nline 1\nline 2"
+        );
+
+        // Events outside of a project aren't prefixed, and the level filters the events.
+        let lines = capture(LogLevel::Debug, LogFormat::Pretty, || {
+            tracing::debug!("No repository URLs provided to benchmark.");
+        });
+        assert_eq!(lines[0].1, "No repository URLs provided to benchmark.");
+        assert_eq!(
+            capture(LogLevel::Error, LogFormat::Pretty, build_events).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_json_format() {
+        let lines = capture(LogLevel::Debug, LogFormat::Json, build_events);
+        assert_eq!(lines.len(), 3);
+        let events: Vec<Value> = lines
+            .iter()
+            .map(|(_, line)| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events[0]["level"], "debug");
+        assert_eq!(events[0]["spans"], json!(["project", "build"]));
+        assert_eq!(events[1]["message"], "BUILT! Elapsed time: 1.50s");
+        assert_eq!(events[1]["project"], "a/a");
+        assert_eq!(events[1]["stage"], "build");
+        assert_eq!(events[1]["duration_secs"], 1.5);
+        assert_eq!(events[2]["exit_code"], 1);
+        assert_eq!(events[2]["output"], "line 1\nline 2");
+        assert!(events[2]["timestamp"].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[1;31mERROR:\x1b[0m failed"),
+            "ERROR: failed"
+        );
+        assert_eq!(strip_ansi("[a/a] plain"), "[a/a] plain");
+    }
+}
@@ -0,0 +1,156 @@
+use eyre::{Result, eyre};
+use std::collections::{BTreeMap, HashSet};
+use yansi::Paint;
+
+use crate::{cmd, config::ConfigFile, redact};
+
+/// A named set of environment variables, set on every `forge` command of a `matrix` run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub name: String,
+    pub env: BTreeMap<String, String>,
+}
+
+impl Variant {
+    /// Parses a `NAME:KEY=VALUE,...` variant, as passed to `--variant`. A bare `NAME` runs with
+    /// the default settings.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (name, pairs) = s.split_once(':').unwrap_or((s, ""));
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format!(
+                "invalid variant '{s}', expected NAME:KEY=VALUE,..."
+            ));
+        }
+        let pairs: Vec<String> = pairs
+            .split(',')
+            .filter(|pair| !pair.is_empty())
+            .map(str::to_string)
+            .collect();
+        let env = cmd::parse_env_pairs(&pairs).map_err(|e| e.to_string())?;
+        Ok(Self {
+            name: name.to_string(),
+            env: env.into_iter().collect(),
+        })
+    }
+
+    /// The variables of the variant, as `KEY=VALUE` pairs with the secrets redacted.
+    pub fn describe(&self) -> String {
+        if self.env.is_empty() {
+            return "default settings".to_string();
+        }
+        redact::vars(&self.env)
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Resolves the variants of a `matrix` run: the ones passed with `--variant` if any, otherwise the
+/// `[[variant]]` entries of the config file at `config_path`, if any (see [`ConfigFile::load`] for
+/// `lenient`).
+///
+/// Errors if fewer than two variants are configured, or if their names aren't unique.
+pub fn resolve(
+    flags: &[Variant],
+    config_path: Option<&str>,
+    lenient: bool,
+) -> Result<Vec<Variant>> {
+    let variants = if let (true, Some(config_path)) = (flags.is_empty(), config_path) {
+        ConfigFile::load(config_path, lenient)?
+            .variant
+            .into_iter()
+            .map(|variant| Variant {
+                name: variant.name,
+                env: variant.env_vars.unwrap_or_default().into_iter().collect(),
+            })
+            .collect()
+    } else {
+        flags.to_vec()
+    };
+
+    if variants.len() < 2 {
+        return Err(eyre!(
+            "{} A matrix needs at least two variants, pass them with --variant or add [[variant]] entries to {}",
+            Paint::red("ERROR:").bold(),
+            config_path.unwrap_or("the config file")
+        ));
+    }
+    let mut names = HashSet::new();
+    for variant in &variants {
+        redact::register(&variant.env);
+        if !names.insert(&variant.name) {
+            return Err(eyre!(
+                "{} Duplicate variant name: {}",
+                Paint::red("ERROR:").bold(),
+                variant.name
+            ));
+        }
+    }
+    Ok(variants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_variant() {
+        let variant = Variant::parse("fuzz:FOUNDRY_FUZZ_RUNS=1024,FOUNDRY_FUZZ_SEED=0x1").unwrap();
+        assert_eq!(variant.name, "fuzz");
+        assert_eq!(
+            variant.describe(),
+            "FOUNDRY_FUZZ_RUNS=1024 FOUNDRY_FUZZ_SEED=0x1"
+        );
+
+        let variant = Variant::parse("baseline").unwrap();
+        assert!(variant.env.is_empty());
+        assert_eq!(variant.describe(), "default settings");
+
+        // Only the first colon separates the name, values may contain more.
+        let variant = Variant::parse("rpc:ETH_RPC_URL=http://localhost:8545").unwrap();
+        assert_eq!(variant.env["ETH_RPC_URL"], "http://localhost:8545");
+
+        assert!(Variant::parse(":A=1").is_err());
+        assert!(Variant::parse("bad:NOVALUE").is_err());
+    }
+
+    #[test]
+    fn test_resolve_variants() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        fs::write(
+            &path,
+            r#"
+[[variant]]
+name = "isolate-off"
+
+[[variant]]
+name = "isolate-on"
+env_vars = { FOUNDRY_ISOLATE = "true" }
+"#,
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let variants = resolve(&[], Some(path), false).unwrap();
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[1].env["FOUNDRY_ISOLATE"], "true");
+
+        // Flags take precedence over the config file.
+        let flags = [
+            Variant::parse("a:X=1").unwrap(),
+            Variant::parse("b:X=2").unwrap(),
+        ];
+        assert_eq!(resolve(&flags, Some(path), false).unwrap(), flags);
+
+        assert_eq!(resolve(&flags, None, false).unwrap(), flags);
+        assert!(resolve(&[], None, false).is_err());
+        assert!(resolve(&flags[..1], Some(path), false).is_err());
+        let duplicates = [flags[0].clone(), flags[0].clone()];
+        assert!(resolve(&duplicates, Some(path), false).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use eyre::{Result, WrapErr, eyre};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    history,
+    results::{RunResults, SourceRecord},
+};
+
+/// Job the metrics are grouped under in a Pushgateway.
+const PUSH_JOB: &str = "foundry_benchmarks";
+
+/// Text format the metrics are rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exposition {
+    /// OpenMetrics, as written by `--export-openmetrics`.
+    OpenMetrics,
+    /// The Prometheus text format, as accepted by a Pushgateway.
+    Prometheus,
+}
+
+/// Renders `results` as metrics: the test and build time of every project with every source, and
+/// the number of projects that failed at each stage.
+///
+/// Every series is labelled with the source, its forge version and commit, and the fingerprint of
+/// the host, so that the runs of different machines don't collide.
+pub fn render(results: &RunResults, exposition: Exposition) -> String {
+    let host = history::host_fingerprint(&results.host);
+    let mut test = Vec::new();
+    let mut build = Vec::new();
+    let mut failed = Vec::new();
+    for source in &results.sources {
+        let labels = source_labels(source, &host);
+        for tested in &source.results {
+            let labels = with_label(&labels, "project", &tested.name);
+            // The projects that were only built have no test time.
+            if let Some(time) = tested.test_time() {
+                test.push(format!("foundry_benchmark_test_seconds{labels} {time}"));
+            }
+            build.push(format!(
+                "foundry_benchmark_build_seconds{labels} {}",
+                tested.build_time
+            ));
+        }
+        let mut stages: BTreeMap<&str, usize> = BTreeMap::new();
+        for failure in &source.failures {
+            *stages.entry(failure.stage.as_str()).or_default() += 1;
+        }
+        for (stage, count) in stages {
+            let labels = with_label(&labels, "stage", stage);
+            failed.push(format!(
+                "foundry_benchmark_failed_projects_total{labels} {count}"
+            ));
+        }
+    }
+
+    // OpenMetrics names counters without their `_total` suffix.
+    let failed_name = match exposition {
+        Exposition::OpenMetrics => "foundry_benchmark_failed_projects",
+        Exposition::Prometheus => "foundry_benchmark_failed_projects_total",
+    };
+    let mut out = String::new();
+    for (name, ty, help, samples) in [
+        (
+            "foundry_benchmark_test_seconds",
+            "gauge",
+            "Average time of the `forge test` runs of a project.",
+            test,
+        ),
+        (
+            "foundry_benchmark_build_seconds",
+            "gauge",
+            "Time of a clean `forge build` of a project.",
+            build,
+        ),
+        (
+            failed_name,
+            "counter",
+            "Number of projects that failed at a stage of the benchmark.",
+            failed,
+        ),
+    ] {
+        out.push_str(&format!("# TYPE {name} {ty}\n# HELP {name} {help}\n"));
+        for sample in samples {
+            out.push_str(&sample);
+            out.push('\n');
+        }
+    }
+    if exposition == Exposition::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+/// Writes the metrics of `results` to `path`, in the OpenMetrics format.
+pub fn export(results: &RunResults, path: &str) -> Result<()> {
+    fs::write(path, render(results, Exposition::OpenMetrics))
+        .wrap_err_with(|| format!("Failed to write the metrics to {path}"))
+}
+
+/// Pushes the metrics of `results` to the Pushgateway at `url`, with `curl`. The metrics are
+/// grouped by host, so that the pushes of different machines don't replace each other.
+pub fn push(results: &RunResults, url: &str) -> Result<()> {
+    let endpoint = format!(
+        "{}/metrics/job/{PUSH_JOB}/instance/{}",
+        url.trim_end_matches('/'),
+        history::host_fingerprint(&results.host)
+    );
+    let mut curl = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "30",
+            "--header",
+            "Content-Type: text/plain; version=0.0.4",
+            "--data-binary",
+            "@-",
+            &endpoint,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| eyre!("Failed to run curl. Error: {e}"))?;
+    if let Some(mut stdin) = curl.stdin.take() {
+        stdin.write_all(render(results, Exposition::Prometheus).as_bytes())?;
+    }
+    let output = curl.wait_with_output()?;
+    if !output.status.success() {
+        return Err(eyre!("{}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// The labels shared by the series of `source`, recorded on the host with the `host` fingerprint.
+fn source_labels(source: &SourceRecord, host: &str) -> Vec<(&'static str, String)> {
+    let forge = source.forge_version.as_ref();
+    vec![
+        (
+            "source",
+            source.label.as_ref().unwrap_or(&source.name).clone(),
+        ),
+        (
+            "forge_version",
+            forge.map(|f| f.version.clone()).unwrap_or_default(),
+        ),
+        (
+            "forge_commit",
+            forge.and_then(|f| f.commit.clone()).unwrap_or_default(),
+        ),
+        ("host", host.to_string()),
+    ]
+}
+
+/// Formats `labels` with the `name` label first, e.g. `{project="a/a",source="stable"}`.
+fn with_label(labels: &[(&str, String)], name: &str, value: &str) -> String {
+    let labels: Vec<String> = std::iter::once((name, value))
+        .chain(labels.iter().map(|(name, value)| (*name, value.as_str())))
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+/// Escapes a label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        benchmark::{Benchmarks, Failure, Source, SourceResults, Tested},
+        results::HostInfo,
+        toolchain::ForgeVersion,
+    };
+
+    #[test]
+    fn test_render_openmetrics() {
+        let (stable, branch) = ("1.2.0-stable".to_string(), "my\"branch".to_string());
+        let tested = |name: &str| Tested {
+            build_time: 4.5,
+            runs: 2,
+            samples: vec![1.0, 1.5],
+            ..Tested::fixture(name, 1.25)
+        };
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 2,
+            results: vec![
+                SourceResults {
+                    source: Source::Version(stable.clone()),
+                    foundry_repo: "foundry-rs/foundry".to_string(),
+                    label: None,
+                    forge: Some(ForgeVersion {
+                        version: "1.2.0-stable".to_string(),
+                        commit: Some("abcdef".to_string()),
+                    }),
+                    tests: vec![tested("a/a")],
+                },
+                SourceResults {
+                    source: Source::Branch(branch.clone()),
+                    foundry_repo: "foundry-rs/foundry".to_string(),
+                    label: None,
+                    forge: None,
+                    tests: vec![],
+                },
+            ],
+            notes: vec![],
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: crate::ui::DEFAULT_NOISY_THRESHOLD,
+        };
+        let mut results = RunResults::from_benchmarks(&b, 0, vec!["a/a".to_string()], None);
+        results.host = HostInfo {
+            hostname: Some("runner".to_string()),
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            cpus: Some(8),
+            cpu_model: None,
+        };
+        results.sources[1].failures = ["build", "build", "test"]
+            .into_iter()
+            .map(|stage| Failure {
+                project: "a/a".to_string(),
+                stage: stage.to_string(),
+                error: String::new(),
+                failed_tests: vec![],
+            })
+            .collect();
+        let host = history::host_fingerprint(&results.host);
+
+        let metrics = render(&results, Exposition::OpenMetrics);
+        assert!(metrics.contains(&format!(
+            "foundry_benchmark_test_seconds{{project=\"a/a\",source=\"1.2.0-stable\",forge_version=\"1.2.0-stable\",forge_commit=\"abcdef\",host=\"{host}\"}} 1.25\n"
+        )));
+        assert!(metrics.contains(&format!(
+            "foundry_benchmark_build_seconds{{project=\"a/a\",source=\"1.2.0-stable\",forge_version=\"1.2.0-stable\",forge_commit=\"abcdef\",host=\"{host}\"}} 4.5\n"
+        )));
+        assert!(metrics.contains(&format!(
+            "foundry_benchmark_failed_projects_total{{stage=\"build\",source=\"my\\\"branch\",forge_version=\"\",forge_commit=\"\",host=\"{host}\"}} 2\n"
+        )));
+        assert!(metrics.contains("# TYPE foundry_benchmark_failed_projects counter\n"));
+        assert!(metrics.ends_with("# EOF\n"));
+
+        let metrics = render(&results, Exposition::Prometheus);
+        assert!(metrics.contains("# TYPE foundry_benchmark_failed_projects_total counter\n"));
+        assert!(!metrics.contains("# EOF"));
+    }
+}
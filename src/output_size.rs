@@ -0,0 +1,209 @@
+use std::fmt::Write as _;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{benchmark::Benchmarks, clean::format_size, cmd::Verbosity, ui};
+
+/// Relative growth (in %) of the output of a project above which a comparison source is flagged.
+const GROWTH_THRESHOLD: f64 = 50.0;
+/// Growth (in bytes) below which a comparison source is never flagged, so that a few more lines of
+/// a tiny output aren't.
+const MIN_GROWTH: u64 = 16 * 1024;
+
+/// Volume of the output of the test runs of a project, in bytes: extra traces or logs at the same
+/// verbosity cost CI logs, and perturb the timings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OutputSize {
+    /// Average number of bytes written to stdout by a run.
+    pub stdout: u64,
+    /// Average number of bytes written to stderr by a run.
+    pub stderr: u64,
+    /// Verbosity of the runs, their output only being comparable at the same one.
+    pub verbosity: Verbosity,
+}
+
+impl OutputSize {
+    /// The average of the `(stdout, stderr)` byte counts of the runs, `None` without any run.
+    pub fn average(runs: &[(usize, usize)], verbosity: Verbosity) -> Option<Self> {
+        let n = runs.len() as u64;
+        (n > 0).then(|| Self {
+            stdout: runs.iter().map(|(stdout, _)| *stdout as u64).sum::<u64>() / n,
+            stderr: runs.iter().map(|(_, stderr)| *stderr as u64).sum::<u64>() / n,
+            verbosity,
+        })
+    }
+
+    /// Average number of bytes written by a run to both stdout and stderr.
+    pub fn total(&self) -> u64 {
+        self.stdout + self.stderr
+    }
+
+    /// Whether `self` is significantly more output than `baseline`, at the same verbosity.
+    fn grew_from(&self, baseline: &Self) -> bool {
+        self.verbosity == baseline.verbosity
+            && self.total() >= baseline.total() + MIN_GROWTH
+            && ui::relative_diff(baseline.total() as f64, self.total() as f64) >= GROWTH_THRESHOLD
+    }
+}
+
+/// Renders the output size of the test runs of every project with every source of a diff, with
+/// the relative diff of each comparison source against the baseline. The comparison sources
+/// printing significantly more than the baseline at the same verbosity are flagged.
+///
+/// Empty without a comparison source, or when no output size was recorded.
+pub fn render(b: &Benchmarks) -> String {
+    let mut projects: Vec<&str> = Vec::new();
+    for tested in b.results.iter().flat_map(|r| &r.tests) {
+        if tested.output_size.is_some() && !projects.contains(&tested.name.as_str()) {
+            projects.push(&tested.name);
+        }
+    }
+    if b.results.len() < 2 || projects.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("\n## output size of `forge test`\n\n");
+    let _ = write!(
+        out,
+        "| Project | Before {} |",
+        ui::escape_markdown(&b.baseline().display_name())
+    );
+    for vs in b.comparisons() {
+        let _ = write!(
+            out,
+            " After {} | Relative Diff |",
+            ui::escape_markdown(&vs.display_name())
+        );
+    }
+    out.push_str("\n|--------|----------|");
+    out.push_str(&"------|-----------|".repeat(b.comparisons().len()));
+    out.push('\n');
+
+    let mut flagged = Vec::new();
+    for project in projects {
+        let sizes: Vec<Option<OutputSize>> = b
+            .results
+            .iter()
+            .map(|r| r.tests.iter().find(|t| t.name == project)?.output_size)
+            .collect();
+        let _ = write!(
+            out,
+            "| {} | {} |",
+            ui::escape_markdown(project),
+            fmt_size(sizes[0])
+        );
+        for after in &sizes[1..] {
+            let diff = match (sizes[0], after) {
+                (Some(before), Some(after)) => {
+                    let diff = ui::relative_diff(before.total() as f64, after.total() as f64);
+                    if after.grew_from(&before) {
+                        flagged.push(project);
+                        format!("{diff:+.1}% ⚠️")
+                    } else {
+                        format!("{diff:+.1}%")
+                    }
+                }
+                _ => "n/a".to_string(),
+            };
+            let _ = write!(out, " {} | {diff} |", fmt_size(*after));
+        }
+        out.push('\n');
+    }
+
+    out.push_str(
+        "\nnote: the average number of bytes written to stdout and stderr by a test run.\n",
+    );
+    flagged.dedup();
+    if !flagged.is_empty() {
+        let _ = writeln!(
+            out,
+            "note: more output at the same verbosity (⚠️, at least {GROWTH_THRESHOLD}% and {} more): {}.",
+            format_size(MIN_GROWTH),
+            flagged.join(", ")
+        );
+    }
+    out
+}
+
+fn fmt_size(size: Option<OutputSize>) -> String {
+    size.map_or_else(|| "n/a".to_string(), |size| format_size(size.total()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::{Source, SourceResults, Tested};
+
+    #[test]
+    fn test_average() {
+        assert_eq!(OutputSize::average(&[], 2), None);
+        let size = OutputSize::average(&[(100, 10), (300, 0)], 2).unwrap();
+        assert_eq!((size.stdout, size.stderr, size.total()), (200, 5, 205));
+    }
+
+    fn tested(name: &str, output_size: Option<(u64, Verbosity)>) -> Tested {
+        Tested {
+            output_size: output_size.map(|(stdout, verbosity)| OutputSize {
+                stdout,
+                stderr: 0,
+                verbosity,
+            }),
+            ..Tested::fixture(name, 1.0)
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let source = |source, tests| SourceResults {
+            source,
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        };
+        let mut b = Benchmarks {
+            verbosity: "-vv".to_string(),
+            num_runs: 1,
+            results: vec![source(
+                Source::Version("stable".to_string()),
+                vec![
+                    tested("a/a", Some((100_000, 2))),
+                    tested("b/b", Some((1_000, 2))),
+                    tested("c/c", Some((100_000, 2))),
+                    tested("d/d", None),
+                ],
+            )],
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        };
+        // Nothing to compare without a comparison source.
+        assert_eq!(render(&b), "");
+
+        b.results.push(source(
+            Source::Branch("feat".to_string()),
+            vec![
+                tested("a/a", Some((300_000, 2))),
+                // Tripled, but only by a few bytes.
+                tested("b/b", Some((3_000, 2))),
+                // At another verbosity, e.g. against a recorded baseline.
+                tested("c/c", Some((300_000, 3))),
+                tested("d/d", None),
+            ],
+        ));
+        let markdown = render(&b);
+        assert!(markdown.contains(
+            "| Project | Before stable | After feat | Relative Diff |\n|--------|----------|------|-----------|\n"
+        ));
+        assert!(markdown.contains("| a/a | 97.7 KiB | 293.0 KiB | +200.0% ⚠️ |\n"));
+        assert!(markdown.contains("| b/b | 1000 B | 2.9 KiB | +200.0% |\n"));
+        assert!(markdown.contains("| c/c | 97.7 KiB | 293.0 KiB | +200.0% |\n"));
+        // Projects without any output size are left out.
+        assert!(!markdown.contains("d/d"));
+        assert!(markdown.ends_with(
+            "note: more output at the same verbosity (⚠️, at least 50% and 16.0 KiB more): a/a.\n"
+        ));
+    }
+}
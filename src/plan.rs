@@ -0,0 +1,384 @@
+use std::fmt::Write as _;
+
+use crate::{
+    benchmark::{self, DISCOVERY_RUNS, NetworkAccess, Skipped, Source, Stage},
+    cmd::{DiffSources, Verbosity},
+    redact, toolchain,
+    utils::{ProjectConfig, RemappingsMode, shell_quote},
+};
+
+/// Renders the execution plan of `--dry-run`: the sources a diff installs, then the commands run
+/// and the files written for every project of `repos`, in order, without running anything.
+///
+/// The temporary directories are printed as placeholders (`<dir>`, `<prefixes>`) and the values of
+/// the secrets are redacted, so that the plan of a given config is stable.
+pub fn render(
+    repos: &[ProjectConfig],
+    skipped: &[Skipped],
+    num_runs: usize,
+    verbosity: Verbosity,
+    stop_after: Stage,
+    sources: Option<&DiffSources>,
+) -> String {
+    let mut plan = String::from("Execution plan (--dry-run, nothing is run)\n");
+    match sources {
+        None => plan.push_str("\nSource: the forge in PATH\n"),
+        Some(sources) => render_sources(&mut plan, sources),
+    }
+
+    let _ = writeln!(plan, "\nProjects ({})", repos.len());
+    for project in repos {
+        render_project(&mut plan, project, num_runs, verbosity, stop_after);
+    }
+    for project in skipped {
+        let _ = writeln!(
+            plan,
+            "  {} (skipped, missing {})",
+            project.name,
+            project.missing.join(", ")
+        );
+    }
+
+    plan.push_str(
+        "\nEvery forge command runs in the project directory, with FOUNDRY_DISABLE_NIGHTLY_WARNING=true.\n",
+    );
+    if let Some(sources) = sources {
+        let _ = writeln!(
+            plan,
+            "Every project is benchmarked with each of the {} sources.",
+            1 + sources.comparisons.len()
+        );
+    }
+    plan
+}
+
+/// Renders the sides of a diff, with the `foundryup` command installing each of them into its own
+/// prefix. The sources built from a commit are installed into the toolchain cache instead.
+fn render_sources(plan: &mut String, sources: &DiffSources) {
+    plan.push_str("\nSources\n");
+    let num_comparisons = sources.comparisons.len();
+    let sides = std::iter::once(("baseline".to_string(), &sources.baseline, &sources.ref_repo))
+        .chain(sources.comparisons.iter().enumerate().map(|(i, source)| {
+            let name = if num_comparisons == 1 {
+                "comparison".to_string()
+            } else {
+                format!("comparison #{}", i + 1)
+            };
+            (name, source, &sources.vs_repo)
+        }));
+    for (name, source, foundry_repo) in sides {
+        let _ = writeln!(plan, "  {name} ({}: {})", source.ty(), source.name());
+        if let Some(commit) = cached_commit(source) {
+            let _ = writeln!(
+                plan,
+                "    $ FOUNDRY_DIR=<toolchain-cache>/{commit} foundryup -r {foundry_repo} -C {}",
+                shell_quote(&commit)
+            );
+            plan.push_str(
+                "    reused if a previous run installed the same commit, unless --refresh-toolchains\n",
+            );
+            continue;
+        }
+        match source {
+            Source::Version(_) | Source::Branch(_) | Source::Commit(_) => {
+                let _ = writeln!(
+                    plan,
+                    "    $ FOUNDRY_DIR=<prefixes>/{} foundryup -r {foundry_repo} {} {}",
+                    name.replace(" #", "-"),
+                    source.short().expect("installable source"),
+                    shell_quote(source.name())
+                );
+            }
+            // Labelled after its variables, with the secrets redacted.
+            Source::Env { .. } => {
+                plan.push_str("    the forge in PATH, with these variables set on every command\n");
+            }
+            Source::Current { .. } => {
+                plan.push_str("    the forge in PATH, as installed\n");
+            }
+            Source::Recorded { path, .. } => {
+                let _ = writeln!(plan, "    results loaded from {path}, not benchmarked");
+            }
+        }
+    }
+}
+
+/// The commit the toolchain of `source` is cached by, as far as it's known without resolving
+/// anything (see [`toolchain::source_commit`]).
+fn cached_commit(source: &Source) -> Option<String> {
+    match source {
+        Source::Branch(branch) => Some(format!("<head of {branch}>")),
+        Source::Commit(commit) if toolchain::is_full_commit(commit) => Some(commit.to_lowercase()),
+        _ => None,
+    }
+}
+
+/// Renders the stages of `project` up to `stop_after`, mirroring [`benchmark::run_pipeline`].
+fn render_project(
+    plan: &mut String,
+    project: &ProjectConfig,
+    num_runs: usize,
+    verbosity: Verbosity,
+    stop_after: Stage,
+) {
+    let _ = writeln!(plan, "  {}", project.name);
+    match &project.synthetic {
+        Some(synthetic) => {
+            let _ = writeln!(
+                plan,
+                "    generate the synthetic project ({synthetic}) in <dir>"
+            );
+        }
+        None => {
+            let _ = writeln!(
+                plan,
+                "    $ git clone --depth 1 {} <dir>",
+                redact::text(&project.url())
+            );
+        }
+    }
+    if stop_after == Stage::Clone {
+        return;
+    }
+
+    if project.command.builds() {
+        if let Some(deps) = project.dependencies() {
+            let _ = writeln!(plan, "    $ forge {}", quote_args(deps));
+        }
+        if let Some(remappings) = project.remappings() {
+            let action = match project.remappings_mode {
+                RemappingsMode::Merge => "merge into remappings.txt",
+                RemappingsMode::Replace => "write remappings.txt",
+            };
+            let _ = writeln!(plan, "    {action}: {}", remappings.join(", "));
+        }
+        if let Some(env_vars) = project.env_vars() {
+            let mut vars: Vec<String> = env_vars
+                .iter()
+                .map(|(key, value)| format!("{key}={}", redact::value(key, value)))
+                .collect();
+            vars.sort();
+            let _ = writeln!(plan, "    write .env: {}", vars.join(", "));
+        }
+        plan.push_str("    $ forge build\n");
+        plan.push_str("    $ forge config --json (not timed)\n");
+        if project.measure_discovery {
+            let _ = writeln!(plan, "    $ forge test --list ({DISCOVERY_RUNS} runs)");
+        }
+        if let Some(runs) = project.incremental_runs {
+            let file = project
+                .incremental_file
+                .as_deref()
+                .unwrap_or("<first src/**/*.sol>");
+            let _ = writeln!(
+                plan,
+                "    $ forge build ({runs} runs, each after appending a comment to {file}, restored after the build)"
+            );
+            plan.push_str("    $ forge build (restored sources)\n");
+        }
+    }
+    if stop_after == Stage::Build {
+        return;
+    }
+    if project.build_only {
+        plan.push_str("    no test runs (build only)\n");
+        return;
+    }
+
+    if let Some(cache) = &project.rpc_cache {
+        let _ = writeln!(
+            plan,
+            "    start a local RPC cache for ${}, passed in its place",
+            cache.url_env
+        );
+    }
+    if let Some(anvil) = &project.anvil {
+        let port = match anvil.port {
+            0 => "<free port>".to_string(),
+            port => port.to_string(),
+        };
+        let _ = writeln!(
+            plan,
+            "    $ anvil --fork-url ${} --port {port} (its endpoint passed in ${} instead)",
+            anvil.fork_url_env, anvil.fork_url_env
+        );
+    }
+    let args = quote_args(&benchmark::run_args(project, verbosity));
+    let _ = match project.network() {
+        NetworkAccess::Allowed => writeln!(plan, "    $ forge {args} ({num_runs} runs)"),
+        NetworkAccess::Isolated => writeln!(
+            plan,
+            "    $ unshare --map-root-user --net forge {args} ({num_runs} runs, without network access)"
+        ),
+        NetworkAccess::Exempted => writeln!(
+            plan,
+            "    $ forge {args} ({num_runs} runs, exempted from --no-network)"
+        ),
+    };
+    for (kind, cmd, when) in [
+        ("prepare", &project.prepare, "before"),
+        ("conclude", &project.conclude, "after"),
+    ] {
+        if let Some(cmd) = cmd {
+            let _ = writeln!(
+                plan,
+                "      $ sh -c {} ({kind}, {when} every run, not timed)",
+                shell_quote(&redact::text(cmd))
+            );
+        }
+    }
+    if let Some(args) = benchmark::script_args(project) {
+        let _ = writeln!(plan, "    $ forge {} ({num_runs} runs)", quote_args(&args));
+    }
+}
+
+/// `args`, quoted for a shell and separated by spaces.
+fn quote_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::SyntheticConfig;
+    use std::{collections::BTreeMap, num::NonZeroUsize};
+
+    #[test]
+    fn test_render_plain() {
+        let mut project = ProjectConfig::new("a/a")
+            .with_deps(vec!["install", "foundry-rs/forge-std"])
+            .with_remappings(vec!["forge-std/=lib/forge-std/src/"])
+            .with_env_vars(
+                vec!["FOUNDRY_FUZZ_RUNS", "ETHERSCAN_API_KEY"],
+                vec!["256", "abc123"],
+            );
+        project.measure_discovery = true;
+        project.incremental_runs = NonZeroUsize::new(2);
+        project.incremental_file = Some("src/A.sol".to_string());
+        project.test_args = vec!["--match-test".to_string(), "test_a b".to_string()];
+        project.script = Some("script/Deploy.s.sol".to_string());
+        let synthetic = ProjectConfig::new("b/b").with_synthetic(SyntheticConfig {
+            contracts: 2,
+            ..Default::default()
+        });
+        let skipped = [Skipped {
+            name: "c/c".to_string(),
+            missing: vec!["MAINNET_RPC_URL".to_string()],
+        }];
+
+        let plan = render(&[project, synthetic], &skipped, 3, 2, Stage::Test, None);
+        assert_eq!(
+            plan,
+            "Execution plan (--dry-run, nothing is run)
+
+Source: the forge in PATH
+
+Projects (2)
+  a/a
+    $ git clone --depth 1 https://github.com/a/a <dir>
+    $ forge install foundry-rs/forge-std
+    merge into remappings.txt: forge-std/=lib/forge-std/src/
+    write .env: ETHERSCAN_API_KEY=***, FOUNDRY_FUZZ_RUNS=256
+    $ forge build
+    $ forge config --json (not timed)
+    $ forge test --list (3 runs)
+    $ forge build (2 runs, each after appending a comment to src/A.sol, restored after the build)
+    $ forge build (restored sources)
+    $ forge test -vv --match-test 'test_a b' (3 runs)
+    $ forge script script/Deploy.s.sol (3 runs)
+  b/b
+    generate the synthetic project (contracts=2,tests_per_contract=5,inheritance_depth=1,fuzz=false,seed=0) in <dir>
+    $ forge build
+    $ forge config --json (not timed)
+    $ forge test -vv (3 runs)
+  c/c (skipped, missing MAINNET_RPC_URL)
+
+Every forge command runs in the project directory, with FOUNDRY_DISABLE_NIGHTLY_WARNING=true.
+"
+        );
+
+        // A build-only project stops after its build.
+        let mut built = ProjectConfig::new("a/a").with_remappings(vec!["x/=y/"]);
+        built.build_only = true;
+        built.remappings_mode = RemappingsMode::Replace;
+        let plan = render(&[built], &[], 3, 0, Stage::Test, None);
+        assert!(plan.contains("    write remappings.txt: x/=y/\n"));
+        assert!(plan.contains("    $ forge build\n    $ forge config --json (not timed)\n    no test runs (build only)\n"));
+
+        // The hooks of the test runs are listed under them.
+        let mut hooked = ProjectConfig::new("a/a");
+        hooked.prepare = Some("sync".to_string());
+        hooked.conclude = Some("rm -rf cache".to_string());
+        let plan = render(&[hooked], &[], 3, 0, Stage::Test, None);
+        assert!(plan.contains(
+            "    $ forge test (3 runs)
+      $ sh -c sync (prepare, before every run, not timed)
+      $ sh -c 'rm -rf cache' (conclude, after every run, not timed)
+"
+        ));
+
+        // With --no-network, the runs are isolated unless the project needs the network.
+        let mut isolated = ProjectConfig::new("a/a");
+        isolated.no_network = true;
+        let mut exempted = isolated.clone();
+        exempted.name = "b/b".to_string();
+        exempted.needs_network = true;
+        let plan = render(&[isolated, exempted], &[], 3, 0, Stage::Test, None);
+        assert!(plan.contains(
+            "    $ unshare --map-root-user --net forge test (3 runs, without network access)\n"
+        ));
+        assert!(plan.contains("    $ forge test (3 runs, exempted from --no-network)\n"));
+
+        // An early stop leaves out the later stages.
+        let plan = render(&[ProjectConfig::new("a/a")], &[], 3, 0, Stage::Clone, None);
+        assert!(plan.contains("$ git clone --depth 1 https://github.com/a/a <dir>\n\n"));
+        assert!(!plan.contains("forge build"));
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let sources = DiffSources {
+            ref_repo: "foundry-rs/foundry".to_string(),
+            baseline: Source::Version("stable".to_string()),
+            vs_repo: "me/foundry".to_string(),
+            comparisons: vec![
+                Source::Branch("feat/x".to_string()),
+                Source::env(BTreeMap::from([
+                    ("FOUNDRY_FUZZ_RUNS".to_string(), "1".to_string()),
+                    ("RPC_TOKEN".to_string(), "plan-test-token".to_string()),
+                ])),
+                Source::Recorded {
+                    path: "base.json".to_string(),
+                    forge: None,
+                },
+            ],
+        };
+        let plan = render(
+            &[ProjectConfig::new("a/a")],
+            &[],
+            1,
+            0,
+            Stage::Test,
+            Some(&sources),
+        );
+        assert!(plan.contains(
+            "Sources
+  baseline (version: stable)
+    $ FOUNDRY_DIR=<prefixes>/baseline foundryup -r foundry-rs/foundry -v stable
+  comparison #1 (branch: feat/x)
+    $ FOUNDRY_DIR=<toolchain-cache>/<head of feat/x> foundryup -r me/foundry -C '<head of feat/x>'
+    reused if a previous run installed the same commit, unless --refresh-toolchains
+"
+        ));
+        assert!(plan.contains(
+            "  comparison #2 (env: FOUNDRY_FUZZ_RUNS=1 RPC_TOKEN=***)\n    the forge in PATH, with these variables set on every command\n"
+        ));
+        assert!(plan.contains("  comparison #3 (recorded: base.json)\n    results loaded from base.json, not benchmarked\n"));
+        assert!(plan.contains("    $ forge test (1 runs)\n"));
+        assert!(plan.ends_with("Every project is benchmarked with each of the 4 sources.\n"));
+        assert!(!plan.contains("plan-test-token"));
+    }
+}
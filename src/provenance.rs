@@ -0,0 +1,151 @@
+use yansi::Paint;
+
+use crate::benchmark::SourceResults;
+
+/// A project that the sources of a diff cloned at different commits, e.g. because it was pushed to
+/// between their runs, so that their results compare different code.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mismatch<'a> {
+    pub project: &'a str,
+    /// The commit of the project with every source, if known.
+    pub commits: Vec<Option<&'a str>>,
+}
+
+impl Mismatch<'_> {
+    /// Describes the mismatch with the short commits of every source, e.g.
+    /// `a/a (0123abc / 4567def)`.
+    fn describe(&self) -> String {
+        let commits: Vec<&str> = self
+            .commits
+            .iter()
+            .map(|commit| commit.map_or("n/a", |commit| &commit[..commit.len().min(7)]))
+            .collect();
+        format!("{} ({})", self.project, commits.join(" / "))
+    }
+}
+
+/// The projects of `results` that some sources cloned at different commits. The sources that
+/// don't know the commit of a project, e.g. a baseline recorded without it, aren't compared.
+pub fn mismatches(results: &[SourceResults]) -> Vec<Mismatch<'_>> {
+    let mut projects: Vec<&str> = Vec::new();
+    for tested in results.iter().flat_map(|r| &r.tests) {
+        if !projects.contains(&tested.name.as_str()) {
+            projects.push(&tested.name);
+        }
+    }
+    projects
+        .into_iter()
+        .filter_map(|project| {
+            let commits: Vec<Option<&str>> = results
+                .iter()
+                .map(|r| {
+                    r.tests
+                        .iter()
+                        .find(|t| t.name == project)?
+                        .commit
+                        .as_deref()
+                })
+                .collect();
+            let mut known = commits.iter().flatten();
+            let first = known.next()?;
+            known
+                .any(|commit| commit != first)
+                .then_some(Mismatch { project, commits })
+        })
+        .collect()
+}
+
+/// The note of the tables about the `mismatches`, if any.
+pub fn note(mismatches: &[Mismatch<'_>]) -> Option<String> {
+    (!mismatches.is_empty()).then(|| {
+        format!(
+            "the sources benchmarked different commits of {} project(s), pushed to between their runs: {}.",
+            mismatches.len(),
+            mismatches
+                .iter()
+                .map(Mismatch::describe)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// Warns about the `mismatches` of `results`, naming the commit of every source.
+pub fn log_mismatches(results: &[SourceResults], mismatches: &[Mismatch<'_>]) {
+    if mismatches.is_empty() {
+        return;
+    }
+    eprintln!(
+        "\n{} The sources benchmarked different commits of {} project(s), their results compare different code:",
+        Paint::yellow("WARNING:").bold(),
+        mismatches.len()
+    );
+    for mismatch in mismatches {
+        let commits: Vec<String> = results
+            .iter()
+            .zip(&mismatch.commits)
+            .map(|(r, commit)| format!("{} at {}", r.display_name(), commit.unwrap_or("n/a")))
+            .collect();
+        eprintln!("  - {}: {}", mismatch.project, commits.join(", "));
+    }
+    eprintln!("Pass --strict-provenance to fail the run instead.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::{Source, Tested};
+
+    fn tested(name: &str, commit: Option<&str>) -> Tested {
+        Tested {
+            commit: commit.map(String::from),
+            ..Tested::fixture(name, 1.0)
+        }
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let source = |name: &str, tests| SourceResults {
+            source: Source::Branch(name.to_string()),
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        };
+        let a = "0123abc0123abc0123abc0123abc0123abc0123a";
+        let b = "4567def4567def4567def4567def4567def4567d";
+        let results = [
+            source(
+                "master",
+                vec![
+                    tested("a/a", Some(a)),
+                    tested("b/b", Some(a)),
+                    tested("c/c", None),
+                ],
+            ),
+            source(
+                "feat",
+                vec![
+                    tested("a/a", Some(b)),
+                    tested("b/b", Some(a)),
+                    tested("c/c", Some(b)),
+                ],
+            ),
+        ];
+
+        // c/c has a single known commit, nothing to compare it with.
+        let found = mismatches(&results);
+        assert_eq!(
+            found,
+            [Mismatch {
+                project: "a/a",
+                commits: vec![Some(a), Some(b)],
+            }]
+        );
+        assert_eq!(
+            note(&found).unwrap(),
+            "the sources benchmarked different commits of 1 project(s), pushed to between their runs: a/a (0123abc / 4567def)."
+        );
+        assert_eq!(note(&[]), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    sync::{LazyLock, RwLock},
+};
+
+use crate::{config::SecretsConfig, utils::glob_match};
+
+/// Placeholder printed and saved instead of a secret value.
+pub const REDACTED: &str = "***";
+/// Patterns of the names of the env vars holding secrets, unless `[secrets]` sets its own.
+pub const DEFAULT_PATTERNS: &[&str] = &["*TOKEN*", "*KEY*", "*SECRET*"];
+/// Secret values shorter than this aren't redacted from free text, where they would mask
+/// unrelated output (e.g. a `1` in every number).
+const MIN_VALUE_LEN: usize = 4;
+
+/// The redactor of the run, see [`configure`].
+static REDACTOR: LazyLock<RwLock<Redactor>> = LazyLock::new(Default::default);
+
+/// Decides which env vars hold secrets, and masks their values in the text the tool prints or
+/// saves.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    /// Glob patterns (`*` matching any run of characters) of the names of the secrets, matched
+    /// case-insensitively.
+    patterns: Vec<String>,
+    /// Names of the secrets listed in `[secrets]`, regardless of the patterns.
+    names: Vec<String>,
+    /// Values of the secrets seen so far, longest first.
+    values: Vec<String>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new(&SecretsConfig::default())
+    }
+}
+
+impl Redactor {
+    pub fn new(secrets: &SecretsConfig) -> Self {
+        let patterns = match &secrets.patterns {
+            Some(patterns) => patterns.clone(),
+            None => DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        };
+        Self {
+            patterns,
+            names: secrets.names.clone(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Whether the env var `name` holds a secret.
+    pub fn is_secret(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name)
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| glob_match(&pattern.to_uppercase(), &name.to_uppercase()))
+    }
+
+    /// Remembers the values of the secrets among `vars`, so that [`Redactor::text`] masks them.
+    pub fn register<'a>(&mut self, vars: impl IntoIterator<Item = (&'a String, &'a String)>) {
+        for (name, value) in vars {
+            if value.len() >= MIN_VALUE_LEN && self.is_secret(name) && !self.values.contains(value)
+            {
+                self.values.push(value.clone());
+            }
+        }
+        self.values
+            .sort_by_key(|value| std::cmp::Reverse(value.len()));
+    }
+
+    /// `value`, or [`REDACTED`] if `name` holds a secret.
+    pub fn value(&self, name: &str, value: &str) -> String {
+        if self.is_secret(name) {
+            REDACTED.to_string()
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// `text`, with the registered secret values replaced by [`REDACTED`].
+    pub fn text(&self, text: &str) -> String {
+        self.values.iter().fold(text.to_string(), |text, value| {
+            text.replace(value, REDACTED)
+        })
+    }
+}
+
+/// Sets up the redactor of the run with the `[secrets]` of the config file, and registers the
+/// secrets of the process environment, which every `forge` command inherits.
+pub fn configure(secrets: &SecretsConfig) {
+    let mut redactor = Redactor::new(secrets);
+    let vars: Vec<(String, String)> = env::vars().collect();
+    redactor.register(vars.iter().map(|(name, value)| (name, value)));
+    *REDACTOR.write().unwrap() = redactor;
+}
+
+/// Registers the secrets among `vars`, e.g. the env vars of a project, see [`Redactor::register`].
+pub fn register<'a>(vars: impl IntoIterator<Item = (&'a String, &'a String)>) {
+    REDACTOR.write().unwrap().register(vars);
+}
+
+/// See [`Redactor::value`].
+pub fn value(name: &str, value: &str) -> String {
+    REDACTOR.read().unwrap().value(name, value)
+}
+
+/// See [`Redactor::text`].
+pub fn text(text: &str) -> String {
+    REDACTOR.read().unwrap().text(text)
+}
+
+/// `vars`, with the values of the secrets replaced by [`REDACTED`].
+pub fn vars(vars: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    vars.iter()
+        .map(|(name, v)| (name.clone(), value(name, v)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redactor() {
+        let rpc = "https://eth.example/v2/abcdef".to_string();
+        let key = "0123456789".to_string();
+        let vars: BTreeMap<String, String> = [
+            ("MAINNET_RPC_URL".to_string(), rpc.clone()),
+            ("etherscan_api_key".to_string(), key.clone()),
+            ("FOUNDRY_FUZZ_RUNS".to_string(), "1024".to_string()),
+            ("SHORT_TOKEN".to_string(), "1".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        // The default patterns match the names case-insensitively.
+        let mut redactor = Redactor::default();
+        assert!(redactor.is_secret("etherscan_api_key"));
+        assert!(!redactor.is_secret("MAINNET_RPC_URL"));
+        redactor.register(&vars);
+        assert_eq!(redactor.value("SHORT_TOKEN", "1"), REDACTED);
+        assert_eq!(
+            redactor.text(&format!(
+                "failed to fetch {rpc}?key={key}, runs 1024, 1 left"
+            )),
+            format!("failed to fetch {rpc}?key=***, runs 1024, 1 left")
+        );
+
+        // `[secrets]` adds names, and replaces the patterns.
+        let mut redactor = Redactor::new(&SecretsConfig {
+            patterns: Some(vec!["*_RUNS".to_string()]),
+            names: vec!["MAINNET_RPC_URL".to_string()],
+        });
+        assert!(!redactor.is_secret("etherscan_api_key"));
+        redactor.register(&vars);
+        assert_eq!(
+            redactor.text(&format!("failed to fetch {rpc}?key={key}, runs 1024")),
+            format!("failed to fetch ***?key={key}, runs ***")
+        );
+    }
+}
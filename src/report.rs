@@ -0,0 +1,472 @@
+use clap::ValueEnum;
+use eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    benchmark::Benchmarks,
+    fingerprint,
+    flamegraph::{self, Flamegraphs},
+    ui::{self, DiffTable, Metric},
+    warnings::{self, ProjectWarnings},
+};
+
+/// Output format of the `report` subcommand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// The tables printed at the end of a run.
+    #[default]
+    Markdown,
+    /// A row per project and metric, with a column per source time and relative diff.
+    Csv,
+    /// A standalone HTML page with the tables.
+    Html,
+    /// The tables as JSON, with the raw times and relative diffs.
+    Json,
+}
+
+/// Tables of a run, as written by the JSON format.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    verbosity: &'a str,
+    num_runs: usize,
+    notes: Vec<String>,
+    tables: Vec<DiffTable<'a>>,
+    /// The build warnings of the projects with every source, see [`warnings::compare`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<ProjectWarnings<'a>>,
+    /// The flamegraphs of the worst regressions, if profiled with `--flamegraph`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flamegraphs: Option<&'a Flamegraphs>,
+}
+
+/// Renders the comparison tables of `b` in `format`, followed by the table linking the
+/// `flamegraphs` of the run, if any (except in CSV).
+///
+/// Every format is rendered from the same [`DiffTable`]s as the tables printed at the end of a
+/// run, so the reported times and diffs always match.
+pub fn render(
+    b: &Benchmarks,
+    flamegraphs: Option<&Flamegraphs>,
+    format: ReportFormat,
+) -> Result<String> {
+    let tables = ui::diff_tables(b);
+    Ok(match format {
+        ReportFormat::Markdown => {
+            let mut out = ui::render_markdown(b);
+            out.extend(flamegraphs.map(flamegraph::render));
+            out
+        }
+        ReportFormat::Csv => render_csv(&tables),
+        ReportFormat::Html => render_html(b, &tables, flamegraphs),
+        ReportFormat::Json => {
+            let report = JsonReport {
+                verbosity: &b.verbosity,
+                num_runs: b.num_runs,
+                notes: b
+                    .notes
+                    .iter()
+                    .cloned()
+                    .chain(b.timing_source_note())
+                    .chain(b.unpinned_fork_note())
+                    .chain(b.hooks_note())
+                    .chain(b.network_note())
+                    .chain(fingerprint::notes(b))
+                    .collect(),
+                tables,
+                warnings: warnings::compare(b),
+                flamegraphs,
+            };
+            format!("{}\n", serde_json::to_string_pretty(&report)?)
+        }
+    })
+}
+
+fn metric_name(metric: Metric) -> &'static str {
+    match metric {
+        Metric::Test => "test",
+        Metric::Script => "script",
+        Metric::Discovery => "discovery",
+        Metric::Fmt => "fmt",
+        Metric::Build => "build",
+        Metric::Incremental => "incremental",
+    }
+}
+
+fn fmt_time(time: Option<f64>) -> String {
+    time.map(|t| format!("{t:.2}")).unwrap_or_default()
+}
+
+fn fmt_diff(diff: Option<f64>) -> String {
+    diff.map(|d| format!("{d:.1}")).unwrap_or_default()
+}
+
+fn render_csv(tables: &[DiffTable]) -> String {
+    let sources = &tables[0].sources;
+    let mut header = vec![
+        "metric".to_string(),
+        "project".to_string(),
+        "url".to_string(),
+        format!("{} (s)", sources[0].name),
+    ];
+    for source in &sources[1..] {
+        header.push(format!("{} (s)", source.name));
+        header.push(format!("{} diff (%)", source.name));
+    }
+    let mut out = csv_line(&header);
+
+    for table in tables {
+        for row in &table.rows {
+            let mut fields = vec![
+                metric_name(table.metric).to_string(),
+                row.project.to_string(),
+                row.url.to_string(),
+                fmt_time(row.times[0]),
+            ];
+            for (time, diff) in row.times[1..].iter().zip(&row.diffs) {
+                fields.push(fmt_time(*time));
+                fields.push(fmt_diff(*diff));
+            }
+            out.push_str(&csv_line(&fields));
+        }
+    }
+    out
+}
+
+/// Joins `fields` into a CSV line, quoting the fields that need it.
+fn csv_line(fields: &[String]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+fn render_html(b: &Benchmarks, tables: &[DiffTable], flamegraphs: Option<&Flamegraphs>) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>foundry benchmarks</title>\n</head>\n<body>\n",
+    );
+    for table in tables {
+        let title = match table.metric {
+            Metric::Test => format!("forge test {}", b.verbosity),
+            Metric::Script => "forge script".to_string(),
+            Metric::Discovery => "forge test --list".to_string(),
+            Metric::Fmt => "forge fmt --check".to_string(),
+            Metric::Build => "forge build".to_string(),
+            Metric::Incremental => "incremental forge build".to_string(),
+        };
+        out.push_str(&format!(
+            "<h2>benchmarks <code>{}</code></h2>\n<table>\n<tr><th>Project</th>",
+            escape_html(title.trim())
+        ));
+        for (i, source) in table.sources.iter().enumerate() {
+            out.push_str(&format!(
+                "<th>{} <a href=\"{}\">{}</a></th>",
+                if i == 0 { "Before" } else { "After" },
+                escape_html(&source.url),
+                escape_html(&source.name)
+            ));
+            if i > 0 {
+                out.push_str("<th>Relative Diff</th>");
+            }
+        }
+        out.push_str("</tr>\n");
+
+        for row in &table.rows {
+            out.push_str(&format!(
+                "<tr><td><a href=\"{}\">{}</a></td>",
+                escape_html(row.url),
+                escape_html(&row.project_name()),
+            ));
+            for cell in row.cells() {
+                out.push_str(&format!("<td>{cell}</td>"));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+
+        let notes = match table.metric {
+            Metric::Test => {
+                let mut notes = vec![format!(
+                    "the reported times are the average of {} runs.",
+                    b.num_runs
+                )];
+                notes.extend(
+                    b.notes
+                        .iter()
+                        .cloned()
+                        .chain(b.timing_source_note())
+                        .chain(b.unpinned_fork_note())
+                        .chain(b.hooks_note())
+                        .chain(b.network_note())
+                        .chain(fingerprint::notes(b)),
+                );
+                notes
+            }
+            Metric::Script => vec![format!(
+                "the reported times are the average of {} runs, after the test runs.",
+                b.num_runs
+            )],
+            Metric::Discovery => vec![
+                "the reported times are the average of a few runs, after the build; n/a if unsupported by forge."
+                    .to_string(),
+            ],
+            Metric::Fmt => vec![format!(
+                "the reported times are the average of {} runs, without building the projects.",
+                b.num_runs
+            )],
+            Metric::Build => vec!["the reported times are from a single clean build.".to_string()],
+            Metric::Incremental => vec![ui::INCREMENTAL_NOTE.to_string()],
+        };
+        let notes = notes
+            .into_iter()
+            .chain(table.noise_note(b.noise_threshold))
+            .chain(table.noisy_note(b.noisy_threshold));
+        for note in notes {
+            out.push_str(&format!("<p>note: {}</p>\n", escape_html(&note)));
+        }
+    }
+    if let Some(flamegraphs) = flamegraphs.filter(|f| !f.is_empty()) {
+        out.push_str(
+            "<h2>flamegraphs of the worst regressions</h2>\n<table>\n<tr><th>Project</th><th>Regression</th>",
+        );
+        for source in &flamegraphs.sources {
+            out.push_str(&format!("<th>{}</th>", escape_html(source)));
+        }
+        out.push_str("</tr>\n");
+        for profile in &flamegraphs.profiles {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{:+.1}%</td>",
+                escape_html(&profile.project),
+                profile.regression
+            ));
+            for svg in &profile.svgs {
+                match svg {
+                    Some(svg) => out.push_str(&format!(
+                        "<td><a href=\"{}\">{}</a></td>",
+                        escape_html(&svg.display().to_string()),
+                        escape_html(&svg.file_name().unwrap_or_default().to_string_lossy())
+                    )),
+                    None => out.push_str("<td>n/a</td>"),
+                }
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str(&format!(
+            "</table>\n<p>note: {}</p>\n",
+            escape_html(flamegraph::NOTE)
+        ));
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::{Source, SourceResults, Tested};
+
+    fn tested(name: &str, build_time: f64, avg_test_time: f64) -> Tested {
+        Tested {
+            build_time,
+            ..Tested::fixture(name, avg_test_time)
+        }
+    }
+
+    fn benchmarks(stable: &str, branch: &str) -> Benchmarks {
+        let results = [
+            (
+                Source::Version(stable.to_string()),
+                vec![tested("a/a", 4.0, 2.0), tested("b/b", 1.0, 1.0)],
+            ),
+            (
+                Source::Branch(branch.to_string()),
+                vec![tested("a/a", 5.0, 1.0)],
+            ),
+        ]
+        .into_iter()
+        .map(|(source, tests)| SourceResults {
+            source,
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        })
+        .collect();
+        Benchmarks {
+            verbosity: "-vv".to_string(),
+            num_runs: 1,
+            results,
+            notes: vec!["a, \"quoted\" <note>".to_string()],
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        }
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let (stable, branch) = ("stable".to_string(), "feat".to_string());
+        let csv = render(&benchmarks(&stable, &branch), None, ReportFormat::Csv).unwrap();
+        assert_eq!(
+            csv,
+            "metric,project,url,stable (s),feat (s),feat diff (%)\n\
+             test,a/a,https://github.com/a/a,2.00,1.00,-50.0\n\
+             test,b/b,https://github.com/b/b,1.00,,\n\
+             build,a/a,https://github.com/a/a,4.00,5.00,25.0\n\
+             build,b/b,https://github.com/b/b,1.00,,\n"
+        );
+        assert_eq!(
+            csv_line(&["a,b".to_string(), "say \"hi\"".to_string()]),
+            "\"a,b\",\"say \"\"hi\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_render_matches_markdown() {
+        let (stable, branch) = ("stable".to_string(), "feat".to_string());
+        let b = benchmarks(&stable, &branch);
+
+        let markdown = render(&b, None, ReportFormat::Markdown).unwrap();
+        assert!(markdown.contains("| [a/a](https://github.com/a/a) | 2.00s | 1.00s | -50.0% |"));
+        assert!(markdown.contains("| [b/b](https://github.com/b/b) | 1.00s | n/a | n/a |"));
+
+        let html = render(&b, None, ReportFormat::Html).unwrap();
+        assert!(html.contains("<td>2.00s</td><td>1.00s</td><td>-50.0%</td>"));
+        assert!(html.contains("note: a, &quot;quoted&quot; &lt;note&gt;"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&b, None, ReportFormat::Json).unwrap()).unwrap();
+        let test = &json["tables"][0];
+        assert_eq!(test["metric"], "test");
+        assert_eq!(test["sources"][1]["name"], "feat");
+        assert_eq!(test["rows"][0]["diffs"][0], -50.0);
+        assert!(test["rows"][1]["times"][1].is_null());
+        assert_eq!(json["tables"][1]["metric"], "build");
+    }
+
+    #[test]
+    fn test_render_flamegraphs() {
+        let (stable, branch) = ("stable".to_string(), "feat".to_string());
+        let b = benchmarks(&stable, &branch);
+        let flamegraphs = Flamegraphs {
+            sources: vec![stable.clone(), branch.clone()],
+            profiles: vec![flamegraph::Profile {
+                project: "a/a".to_string(),
+                regression: 25.0,
+                svgs: vec![
+                    None,
+                    Some(std::path::PathBuf::from("flamegraphs/a__a.comparison.svg")),
+                ],
+            }],
+        };
+
+        let markdown = render(&b, Some(&flamegraphs), ReportFormat::Markdown).unwrap();
+        assert!(markdown.contains(
+            "| a/a | +25.0% | n/a | [a__a.comparison.svg](flamegraphs/a__a.comparison.svg) |"
+        ));
+        let html = render(&b, Some(&flamegraphs), ReportFormat::Html).unwrap();
+        assert!(html.contains(
+            "<tr><td>a/a</td><td>+25.0%</td><td>n/a</td><td><a href=\"flamegraphs/a__a.comparison.svg\">a__a.comparison.svg</a></td></tr>"
+        ));
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&b, Some(&flamegraphs), ReportFormat::Json).unwrap())
+                .unwrap();
+        assert_eq!(
+            json["flamegraphs"]["profiles"][0]["svgs"][1],
+            "flamegraphs/a__a.comparison.svg"
+        );
+        // Without flamegraphs, the JSON report doesn't mention them.
+        let json = render(&b, None, ReportFormat::Json).unwrap();
+        assert!(!json.contains("flamegraphs"));
+    }
+
+    #[test]
+    fn test_render_script_table() {
+        let (stable, branch) = ("stable".to_string(), "feat".to_string());
+        let mut b = benchmarks(&stable, &branch);
+        for (results, time) in b.results.iter_mut().zip([3.0, 1.5]) {
+            results.tests[0].avg_script_time = Some(time);
+            results.tests[0].script_samples = vec![time];
+        }
+
+        // Only the projects with a script are listed, between the test and build times.
+        let csv = render(&b, None, ReportFormat::Csv).unwrap();
+        let metrics: Vec<_> = csv
+            .lines()
+            .skip(1)
+            .map(|line| &line[..line.find(',').unwrap()])
+            .collect();
+        assert_eq!(metrics, ["test", "test", "script", "build", "build"]);
+        assert!(csv.contains("script,a/a,https://github.com/a/a,3.00,1.50,-50.0\n"));
+
+        let markdown = render(&b, None, ReportFormat::Markdown).unwrap();
+        assert!(markdown.contains("## benchmarks `forge script`"));
+        assert!(markdown.contains("| [a/a](https://github.com/a/a) | 3.00s | 1.50s | -50.0% |"));
+        let html = render(&b, None, ReportFormat::Html).unwrap();
+        assert!(html.contains("<h2>benchmarks <code>forge script</code></h2>"));
+    }
+
+    #[test]
+    fn test_render_discovery_table() {
+        let (stable, branch) = ("stable".to_string(), "feat".to_string());
+        let mut b = benchmarks(&stable, &branch);
+        b.results[0].tests[0].discovery_time = Some(0.5);
+        b.results[0].tests[1].discovery_time = Some(0.2);
+
+        // A side whose forge doesn't support `--list` has no discovery time.
+        let csv = render(&b, None, ReportFormat::Csv).unwrap();
+        assert!(csv.contains("discovery,a/a,https://github.com/a/a,0.50,,\n"));
+        assert!(csv.contains("discovery,b/b,https://github.com/b/b,0.20,,\n"));
+
+        let markdown = render(&b, None, ReportFormat::Markdown).unwrap();
+        assert!(markdown.contains("## benchmarks `forge test --list`"));
+        assert!(markdown.contains("| [a/a](https://github.com/a/a) | 0.50s | n/a | n/a |"));
+        assert!(markdown.contains("n/a if unsupported by forge"));
+
+        b.results[1].tests[0].discovery_time = Some(0.25);
+        let csv = render(&b, None, ReportFormat::Csv).unwrap();
+        assert!(csv.contains("discovery,a/a,https://github.com/a/a,0.50,0.25,-50.0\n"));
+    }
+
+    #[test]
+    fn test_render_incremental_table() {
+        let (stable, branch) = ("stable".to_string(), "feat".to_string());
+        let mut b = benchmarks(&stable, &branch);
+        for (r, time) in [(0, 2.0), (1, 1.0)] {
+            let tested = &mut b.results[r].tests[0];
+            tested.incremental_build_time = Some(time);
+            tested.incremental_samples = vec![time; 3];
+        }
+
+        // Only the projects measuring it are listed, after the build times.
+        let csv = render(&b, None, ReportFormat::Csv).unwrap();
+        assert!(csv.ends_with("incremental,a/a,https://github.com/a/a,2.00,1.00,-50.0\n"));
+        let markdown = render(&b, None, ReportFormat::Markdown).unwrap();
+        assert!(markdown.contains("## benchmarks incremental `forge build`"));
+        assert!(markdown.contains("| [a/a](https://github.com/a/a) | 2.00s | 1.00s | -50.0% |"));
+        assert!(!markdown.contains("| [b/b](https://github.com/b/b) | n/a"));
+        let html = render(&b, None, ReportFormat::Html).unwrap();
+        assert!(html.contains("restored after every build"));
+    }
+}
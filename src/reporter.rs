@@ -0,0 +1,200 @@
+use std::{path::Path, sync::Mutex};
+use yansi::Paint;
+
+use crate::{
+    benchmark::{Failure, Tested},
+    eta::{self, Estimator, RunKind},
+    ui,
+    utils::ProjectConfig,
+};
+
+/// Presents the progress of the benchmark pipeline, which only measures and reports every step to
+/// a reporter, so that the output can change without touching the measurements.
+///
+/// The events about a project are reported within its `tracing` span, and its stage's. The
+/// diagnostics of the pipeline (the commands it runs, and the output of the failed ones) are
+/// logged with `tracing` directly.
+pub trait Reporter: Sync {
+    /// A step of the pipeline started for all the projects, e.g. `CLONE PROJECTS (in parallel)`.
+    fn step_started(&self, title: &str);
+
+    /// The `stage` (`clone` or `build`) of `project` started, in `path`.
+    fn project_stage_started(&self, project: &ProjectConfig, stage: &str, path: &Path);
+
+    /// The `stage` of `project` succeeded after `duration` seconds.
+    fn project_stage_finished(&self, project: &ProjectConfig, stage: &str, duration: f64);
+
+    /// The test stage is about to run each project of `plan` in order, `runs` times (and as many
+    /// script runs if it has a script). Does nothing by default.
+    fn tests_planned(&self, plan: &[(&ProjectConfig, usize)]) {
+        let _ = plan;
+    }
+
+    /// The `run`-th (from 0) of the `num_runs` test runs of `project` started.
+    fn test_run_started(&self, project: &ProjectConfig, run: usize, num_runs: usize);
+
+    /// The `run`-th test run of `project` passed after `duration` seconds.
+    fn test_run_completed(&self, project: &ProjectConfig, run: usize, duration: f64);
+
+    /// The `run`-th (from 0) of the `num_runs` script runs of `project` started, after its test
+    /// runs.
+    fn script_run_started(&self, project: &ProjectConfig, run: usize, num_runs: usize);
+
+    /// The `run`-th script run of `project` succeeded after `duration` seconds.
+    fn script_run_completed(&self, project: &ProjectConfig, run: usize, duration: f64);
+
+    /// Every test run (and script run) of a project passed.
+    fn project_tested(&self, tested: &Tested);
+
+    /// A project failed, with the `variant` of a `matrix` run if any.
+    fn project_failed(&self, failure: &Failure, variant: Option<&str>);
+}
+
+/// Prints the progress of the pipeline to the terminal, as log messages filtered by `--log-level`
+/// and formatted by `--log-format`.
+///
+/// After every test (or script) run, it estimates the time left for the project and for the test
+/// stage from the runs completed so far in the process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConsoleReporter;
+
+/// The estimates of the test stages of the process, shared so that a stage (e.g. the comparison of
+/// a diff) starts from the times of the previous one.
+static ETA: Mutex<Estimator> = Mutex::new(Estimator::new());
+
+/// Logs the time left for `project` and for the test stage, after one of its `kind` runs
+/// completed in `duration` seconds.
+fn log_eta(project: &ProjectConfig, kind: RunKind, duration: f64) {
+    let mut eta = ETA.lock().unwrap();
+    eta.completed(&project.name, kind, duration);
+    let Some(total) = eta.total_left().filter(|total| *total > 0.0) else {
+        return;
+    };
+    match eta.project_left(&project.name).filter(|left| *left > 0.0) {
+        Some(left) => tracing::info!(
+            eta_secs = total,
+            "ETA: {} for {}, {} for the test stage",
+            eta::format_left(left),
+            project.name,
+            eta::format_left(total)
+        ),
+        None => tracing::info!(
+            eta_secs = total,
+            "ETA: {} for the test stage",
+            eta::format_left(total)
+        ),
+    }
+}
+
+impl Reporter for ConsoleReporter {
+    fn step_started(&self, title: &str) {
+        ui::banner(Some(title));
+    }
+
+    fn project_stage_started(&self, project: &ProjectConfig, stage: &str, path: &Path) {
+        match stage {
+            "clone" => tracing::info!(
+                url = %project.url(),
+                "Cloning {} into {}",
+                Paint::cyan(&project.url()),
+                Paint::yellow(&path.to_string_lossy())
+            ),
+            stage => tracing::info!("Running 'forge {stage}'"),
+        }
+    }
+
+    fn project_stage_finished(&self, _project: &ProjectConfig, stage: &str, duration: f64) {
+        match stage {
+            "clone" => tracing::info!(duration_secs = duration, "Cloned successfully."),
+            _ => tracing::info!(
+                duration_secs = duration,
+                "{} Elapsed time: {}",
+                Paint::yellow("BUILT!").bold(),
+                Paint::yellow(format!("{duration:.2}s").as_str()).bold()
+            ),
+        }
+    }
+
+    fn tests_planned(&self, plan: &[(&ProjectConfig, usize)]) {
+        ETA.lock().unwrap().plan(plan.iter().map(|(project, runs)| {
+            let script_runs = if project.script.is_some() { *runs } else { 0 };
+            (project.name.as_str(), *runs, script_runs)
+        }));
+    }
+
+    fn test_run_started(&self, project: &ProjectConfig, run: usize, num_runs: usize) {
+        tracing::info!(
+            "Running '{}' ({}/{}) for {}",
+            project.command.name(),
+            run + 1,
+            num_runs,
+            project.name
+        );
+    }
+
+    fn test_run_completed(&self, project: &ProjectConfig, _run: usize, duration: f64) {
+        tracing::info!(
+            duration_secs = duration,
+            "{} Elapsed time: {}",
+            Paint::green("PASSED!").bold(),
+            Paint::green(format!("{duration:.2}s").as_str()).bold()
+        );
+        log_eta(project, RunKind::Test, duration);
+    }
+
+    fn script_run_started(&self, project: &ProjectConfig, run: usize, num_runs: usize) {
+        tracing::info!(
+            "Running 'forge script' ({}/{}) for {}",
+            run + 1,
+            num_runs,
+            project.name
+        );
+    }
+
+    fn script_run_completed(&self, project: &ProjectConfig, _run: usize, duration: f64) {
+        tracing::info!(
+            duration_secs = duration,
+            "{} Elapsed time: {}",
+            Paint::green("DONE!").bold(),
+            Paint::green(format!("{duration:.2}s").as_str()).bold()
+        );
+        log_eta(project, RunKind::Script, duration);
+    }
+
+    fn project_tested(&self, tested: &Tested) {
+        tracing::debug!(
+            "Test times: {}",
+            tested
+                .samples
+                .iter()
+                .map(|t| format!("{t:.2}s"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    fn project_failed(&self, failure: &Failure, variant: Option<&str>) {
+        ETA.lock().unwrap().failed(&failure.project);
+        let Failure {
+            project,
+            stage,
+            error,
+            ..
+        } = failure;
+        match variant {
+            Some(variant) => tracing::error!(
+                project = %project,
+                stage,
+                variant,
+                error = %error,
+                "Project '{project}' failed at stage '{stage}' ({variant}): {error}"
+            ),
+            None => tracing::error!(
+                project = %project,
+                stage,
+                error = %error,
+                "Project '{project}' failed at stage '{stage}': {error}"
+            ),
+        }
+    }
+}
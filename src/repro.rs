@@ -0,0 +1,180 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, env};
+
+use crate::{
+    benchmark::Source,
+    cmd::Cli,
+    redact,
+    utils::{self, ProjectConfig},
+};
+
+/// How a run was invoked, recorded along with its results so that it can be reproduced.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Reproduction {
+    /// Command line of the run, with the secrets redacted.
+    pub command: String,
+    /// Digest of the settings of the run, see [`fingerprint`].
+    pub fingerprint: String,
+    /// Version of foundry-benchmarks.
+    pub version: String,
+}
+
+impl Reproduction {
+    /// Describes the current run, of `repos` against `sources` (see [`source_key`]).
+    pub fn new(cli: &Cli, repos: &[ProjectConfig], sources: &[String]) -> Self {
+        Self {
+            command: command_line(env::args()),
+            fingerprint: fingerprint(cli, repos, sources),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// The markdown footer of the tables of the run.
+    pub fn footer(&self) -> String {
+        format!(
+            "\n## reproduction\n\n- command: `{}`\n- config fingerprint: `{}`\n- foundry-benchmarks version: {}\n",
+            self.command, self.fingerprint, self.version
+        )
+    }
+}
+
+/// Identifies a benchmarked source in a [`fingerprint`], e.g. `branch:master`.
+pub fn source_key(source: &Source) -> String {
+    match source {
+        Source::Env { label, vars } => format!(
+            "env:{label}:{}",
+            redact::vars(vars)
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        source => format!("{}:{}", source.ty(), source.name()),
+    }
+}
+
+/// `args`, quoted for a shell, with the registered secrets redacted (see [`redact::text`]).
+fn command_line(args: impl IntoIterator<Item = String>) -> String {
+    args.into_iter()
+        .map(|arg| utils::shell_quote(&redact::text(&arg)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Digest of the settings that determine the results of a run: the resolved configuration of
+/// every project, the number of runs and verbosity, the project filters, and the sources compared
+/// by `diff` and `matrix` runs.
+///
+/// Secret env vars only contribute their names, so the fingerprint can be shared.
+pub fn fingerprint(cli: &Cli, repos: &[ProjectConfig], sources: &[String]) -> String {
+    let projects: Vec<_> = repos
+        .iter()
+        .map(|repo| {
+            let env_vars: BTreeMap<String, String> = repo
+                .env_vars()
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            let mut project = json!({
+                "name": repo.name,
+                "url": repo.url(),
+                "dependencies": repo.dependencies(),
+                "remappings": repo.remappings(),
+                "env_vars": redact::vars(&env_vars),
+                "test_args": repo.test_args,
+            });
+            // Only set with a script, so that the fingerprints of the other runs don't change.
+            if let Some(script) = &repo.script {
+                project["script"] = json!(
+                    [script]
+                        .into_iter()
+                        .chain(&repo.script_args)
+                        .collect::<Vec<_>>()
+                );
+            }
+            project
+        })
+        .collect();
+    let settings = json!({
+        "projects": projects,
+        "num_runs": cli.num_runs,
+        "verbosity": cli.verbosity,
+        "groups": cli.group,
+        "tags": cli.tag,
+        "exclude_tags": cli.exclude_tag,
+        "exclude": cli.exclude,
+        "limit": cli.limit,
+        "preset": cli.preset,
+        "sources": sources,
+    });
+    format!("{:016x}", fnv1a(settings.to_string().as_bytes()))
+}
+
+/// 64-bit FNV-1a hash of `bytes`, stable across platforms and Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let repos = [ProjectConfig::new("a/a").with_env_vars(vec!["API_KEY"], vec!["secret-1"])];
+        let sources = ["branch:master".to_string()];
+        let mut cli = Cli::default();
+        cli.num_runs = 3;
+        let base = fingerprint(&cli, &repos, &sources);
+        assert_eq!(base.len(), 16);
+        assert_eq!(base, fingerprint(&cli, &repos, &sources));
+
+        // Secret values don't change the fingerprint, the settings do.
+        let rotated = [ProjectConfig::new("a/a").with_env_vars(vec!["API_KEY"], vec!["secret-2"])];
+        assert_eq!(base, fingerprint(&cli, &rotated, &sources));
+        cli.num_runs = 5;
+        assert_ne!(base, fingerprint(&cli, &repos, &sources));
+        cli.num_runs = 3;
+        assert_ne!(base, fingerprint(&cli, &repos, &["branch:dev".to_string()]));
+        let renamed = [ProjectConfig::new("b/b")];
+        assert_ne!(base, fingerprint(&cli, &renamed, &sources));
+    }
+
+    #[test]
+    fn test_source_key() {
+        let master = "master".to_string();
+        assert_eq!(source_key(&Source::Branch(master.clone())), "branch:master");
+        let vars = BTreeMap::from([
+            ("FOUNDRY_FUZZ_RUNS".to_string(), "256".to_string()),
+            ("ETHERSCAN_API_KEY".to_string(), "0123456789".to_string()),
+        ]);
+        let source = Source::Env {
+            label: "fuzz".to_string(),
+            vars,
+        };
+        assert_eq!(
+            source_key(&source),
+            "env:fuzz:ETHERSCAN_API_KEY=***,FOUNDRY_FUZZ_RUNS=256"
+        );
+    }
+
+    #[test]
+    fn test_command_line() {
+        let args = ["foundry-benchmarks", "--repos", "a/a", "--env", "A=b c"];
+        assert_eq!(
+            command_line(args.map(str::to_string)),
+            "foundry-benchmarks --repos a/a --env 'A=b c'"
+        );
+    }
+}
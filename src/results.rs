@@ -0,0 +1,1268 @@
+use eyre::{Context, Result, eyre};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    num::NonZeroUsize,
+    process::Command,
+};
+
+use crate::{
+    benchmark::{
+        self, Benchmarks, Failure, PipelineResults, Source, SourceResults, Tested, TimingSource,
+    },
+    cmd::Verbosity,
+    flamegraph::Flamegraphs,
+    gate::GateReport,
+    redact,
+    repro::Reproduction,
+    toolchain::ForgeVersion,
+    ui,
+    utils::{self, ProjectConfig},
+};
+
+/// Version of the results file format. Bumped on breaking changes.
+///
+/// - v1: results of a single forge build, at the top level.
+/// - v2: results of every benchmarked source, with host metadata and the regression gate outcome.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Results of a benchmark run, as saved to disk.
+///
+/// A standard run records a single source, whereas a `diff` run records all of them, the first
+/// one being the baseline.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RunResults {
+    pub schema_version: u32,
+    /// Unix timestamp (in seconds) of when the results were recorded.
+    pub timestamp: u64,
+    pub host: HostInfo,
+    /// Foundry repository the baseline was installed from, if any.
+    pub foundry_repo: Option<String>,
+    pub num_runs: usize,
+    pub verbosity: Verbosity,
+    /// Names of all the projects that were benchmarked, including the ones that failed.
+    pub projects: Vec<String>,
+    pub sources: Vec<SourceRecord>,
+    /// Outcome of the regression gate, if enabled.
+    pub gate: Option<GateReport>,
+    /// Result files these results were merged from with `merge`, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged_from: Vec<MergedInput>,
+    /// Preset the run was configured with, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Number of projects the run was limited to with `--limit`, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Number of threads the tests ran on with `--forge-threads`, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forge_threads: Option<usize>,
+    /// Whether forge ran with a clean environment, with `--clean-env`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clean_env: bool,
+    /// How the run was invoked, if recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reproduction: Option<Reproduction>,
+    /// Flamegraphs of the worst regressed projects of a diff, with `--flamegraph`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flamegraphs: Option<Flamegraphs>,
+}
+
+/// A result file merged into another one, e.g. the results of a shard of the projects benchmarked
+/// by one of several machines.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MergedInput {
+    pub path: String,
+    /// Unix timestamp (in seconds) of when the input was recorded.
+    pub timestamp: u64,
+    pub host: HostInfo,
+    /// Projects that come from this input.
+    pub projects: Vec<String>,
+    /// Build information of the `forge` binary of every source, in order.
+    pub forge_versions: Vec<Option<ForgeVersion>>,
+}
+
+/// Results of a single source.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SourceRecord {
+    /// Kind of source (e.g. "branch").
+    pub ty: String,
+    pub name: String,
+    /// Foundry repository the source was installed from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foundry_repo: Option<String>,
+    /// Display name of the source, if overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Environment variables set on every `forge` command, for environment sources (e.g. the
+    /// variants of a `matrix` run).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Build information of the `forge` binary used for the run, if known.
+    pub forge_version: Option<ForgeVersion>,
+    /// Results of the projects that were successfully tested.
+    pub results: Vec<Tested>,
+    /// Projects that failed at some stage, if recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<Failure>,
+}
+
+/// Machine the results were recorded on.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct HostInfo {
+    pub hostname: Option<String>,
+    pub os: String,
+    pub arch: String,
+    pub cpus: Option<usize>,
+    pub cpu_model: Option<String>,
+}
+
+impl HostInfo {
+    /// Collects the metadata of the current machine. Unavailable fields are left empty.
+    pub fn current() -> Self {
+        let hostname = Command::new("hostname")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .filter(|hostname| !hostname.is_empty());
+        let cpu_model = fs::read_to_string("/proc/cpuinfo")
+            .ok()
+            .and_then(|cpuinfo| {
+                cpuinfo
+                    .lines()
+                    .find_map(|line| line.strip_prefix("model name"))
+                    .and_then(|line| line.split_once(':'))
+                    .map(|(_, model)| model.trim().to_string())
+            });
+
+        Self {
+            hostname,
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            cpus: std::thread::available_parallelism().ok().map(|n| n.get()),
+            cpu_model,
+        }
+    }
+}
+
+/// Layout of the v1 results files, which only held the results of a single forge build.
+#[derive(Deserialize)]
+struct RunResultsV1 {
+    timestamp: u64,
+    forge_version: Option<ForgeVersion>,
+    num_runs: usize,
+    verbosity: Verbosity,
+    projects: Vec<String>,
+    results: Vec<Tested>,
+}
+
+impl From<RunResultsV1> for RunResults {
+    fn from(v1: RunResultsV1) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            timestamp: v1.timestamp,
+            host: HostInfo::default(),
+            foundry_repo: None,
+            num_runs: v1.num_runs,
+            verbosity: v1.verbosity,
+            projects: v1.projects,
+            sources: vec![SourceRecord::standard(
+                v1.forge_version,
+                v1.results,
+                Vec::new(),
+            )],
+            gate: None,
+            merged_from: Vec::new(),
+            preset: None,
+            limit: None,
+            forge_threads: None,
+            clean_env: false,
+            reproduction: None,
+            flamegraphs: None,
+        }
+    }
+}
+
+impl MergedInput {
+    fn new(path: String, results: &RunResults) -> Self {
+        Self {
+            path,
+            timestamp: results.timestamp,
+            host: results.host.clone(),
+            projects: results.projects.clone(),
+            forge_versions: results
+                .sources
+                .iter()
+                .map(|source| source.forge_version.clone())
+                .collect(),
+        }
+    }
+}
+
+impl SourceRecord {
+    /// Results of a standard run, with the `forge` binary in the user's `PATH`.
+    fn standard(
+        forge_version: Option<ForgeVersion>,
+        results: Vec<Tested>,
+        failures: Vec<Failure>,
+    ) -> Self {
+        Self {
+            ty: "version".to_string(),
+            name: forge_version
+                .as_ref()
+                .map_or_else(|| "unknown".to_string(), |forge| forge.version.clone()),
+            foundry_repo: None,
+            label: None,
+            env: BTreeMap::new(),
+            forge_version,
+            results,
+            failures,
+        }
+    }
+
+    /// The source the results were benchmarked with, as reported in the tables.
+    pub fn source(&self) -> Source {
+        match self.ty.as_str() {
+            "version" => Source::Version(self.name.clone()),
+            "branch" => Source::Branch(self.name.clone()),
+            "commit" => Source::Commit(self.name.clone()),
+            "env" => Source::Env {
+                label: self.name.clone(),
+                vars: self.env.clone(),
+            },
+            "current" => Source::Current {
+                forge: self.forge_version.clone(),
+            },
+            _ => Source::Recorded {
+                path: self.name.clone(),
+                forge: self.forge_version.clone(),
+            },
+        }
+    }
+}
+
+impl RunResults {
+    /// Results of a standard run, with the `forge` binary in the user's `PATH`.
+    pub fn new(
+        forge_version: Option<ForgeVersion>,
+        num_runs: usize,
+        verbosity: Verbosity,
+        projects: Vec<String>,
+        results: Vec<Tested>,
+        failures: Vec<Failure>,
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            timestamp: utils::unix_timestamp(),
+            host: HostInfo::current(),
+            foundry_repo: None,
+            num_runs,
+            verbosity,
+            projects,
+            sources: vec![SourceRecord::standard(forge_version, results, failures)],
+            gate: None,
+            merged_from: Vec::new(),
+            preset: None,
+            limit: None,
+            forge_threads: None,
+            clean_env: false,
+            reproduction: None,
+            flamegraphs: None,
+        }
+    }
+
+    /// Results of a `diff` run.
+    pub fn from_benchmarks(
+        b: &Benchmarks,
+        verbosity: Verbosity,
+        projects: Vec<String>,
+        gate: Option<GateReport>,
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            timestamp: utils::unix_timestamp(),
+            host: HostInfo::current(),
+            foundry_repo: Some(b.baseline().foundry_repo.clone()),
+            num_runs: b.num_runs,
+            verbosity,
+            projects,
+            sources: b
+                .results
+                .iter()
+                .map(|r| SourceRecord {
+                    ty: r.source.ty().to_string(),
+                    name: r.source.name().to_string(),
+                    foundry_repo: Some(r.foundry_repo.clone()),
+                    label: r.label.clone(),
+                    env: match &r.source {
+                        Source::Env { vars, .. } => redact::vars(vars),
+                        _ => BTreeMap::new(),
+                    },
+                    forge_version: r.forge.clone(),
+                    results: r.tests.clone(),
+                    failures: Vec::new(),
+                })
+                .collect(),
+            gate,
+            merged_from: Vec::new(),
+            preset: None,
+            limit: None,
+            forge_threads: None,
+            clean_env: false,
+            reproduction: b.reproduction.clone(),
+            flamegraphs: None,
+        }
+    }
+
+    /// Rebuilds the comparison tables of the recorded run, with a single source for standard runs.
+    ///
+    /// Sources without a recorded repository are linked to `foundry_repo`.
+    pub fn to_benchmarks(&self, foundry_repo: &str) -> Benchmarks {
+        let mut notes: Vec<String> = if self.merged_from.is_empty() {
+            vec![format!("{}.", recorded_on(self.timestamp, &self.host))]
+        } else {
+            self.merged_from
+                .iter()
+                .map(|input| {
+                    format!(
+                        "{} merged from {}, {}.",
+                        input.projects.join(", "),
+                        input.path,
+                        recorded_on(input.timestamp, &input.host)
+                    )
+                })
+                .collect()
+        };
+        if let Some(preset) = &self.preset {
+            notes.push(format!("run with the preset {preset}."));
+        }
+        if let Some(limit) = self.limit {
+            notes.push(format!(
+                "limited to the first {limit} projects with --limit."
+            ));
+        }
+        if let Some(threads) = self.forge_threads {
+            notes.push(threads_note(threads));
+        }
+        if self.clean_env {
+            notes.push(CLEAN_ENV_NOTE.to_string());
+        }
+        let failed = self.failed_projects();
+        if !failed.is_empty() {
+            notes.push(format!("failed with the baseline: {}.", failed.join(", ")));
+        }
+
+        let results = self
+            .sources
+            .iter()
+            .map(|record| SourceResults {
+                source: record.source(),
+                foundry_repo: record
+                    .foundry_repo
+                    .clone()
+                    .or_else(|| self.foundry_repo.clone())
+                    .unwrap_or_else(|| foundry_repo.to_string()),
+                label: record.label.clone(),
+                forge: record.forge_version.clone(),
+                tests: record.results.clone(),
+            })
+            .collect();
+        Benchmarks {
+            verbosity: benchmark::verbosity_flag(self.verbosity),
+            num_runs: self.num_runs,
+            results,
+            notes,
+            reproduction: self.reproduction.clone(),
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        }
+    }
+
+    /// The baseline results: those of the first (and for standard runs, only) source.
+    pub fn baseline(&self) -> &SourceRecord {
+        &self.sources[0]
+    }
+
+    /// Takes ownership of the baseline results.
+    pub fn into_baseline(mut self) -> SourceRecord {
+        self.sources.swap_remove(0)
+    }
+
+    /// Names of the benchmarked projects without baseline results, in the order they were
+    /// benchmarked.
+    pub fn failed_projects(&self) -> Vec<String> {
+        let tested = &self.baseline().results;
+        self.projects
+            .iter()
+            .filter(|name| !tested.iter().any(|t| &t.name == *name))
+            .cloned()
+            .collect()
+    }
+
+    /// Clock the test runs of the baseline were timed with: forge's if it timed any project, the
+    /// others having fallen back to the process time, or else the process. `None` without any
+    /// tested project.
+    pub fn timing_source(&self) -> Option<TimingSource> {
+        let results = &self.baseline().results;
+        if results.is_empty() {
+            return None;
+        }
+        Some(
+            if results
+                .iter()
+                .any(|t| t.timing_source == TimingSource::Forge)
+            {
+                TimingSource::Forge
+            } else {
+                TimingSource::Process
+            },
+        )
+    }
+
+    /// Reapplies the settings of the timed runs of the results to `project`, so that the results of
+    /// running it again (see [`Self::merge_rerun`]) are comparable with the recorded ones: the
+    /// number of test threads, the environment of forge, and the timing source if known.
+    pub fn apply_run_settings(&self, project: &mut ProjectConfig) {
+        project.forge_threads = self.forge_threads.and_then(NonZeroUsize::new);
+        project.clean_env = self.clean_env;
+        if let Some(timing_source) = self.timing_source() {
+            project.timing_source = timing_source;
+        }
+    }
+
+    /// Merges the outcome of rerunning failed projects into the baseline results, replacing the
+    /// failure records of the rerun projects.
+    pub fn merge_rerun(&mut self, rerun: PipelineResults) {
+        let baseline = &mut self.sources[0];
+        let rerun_projects: BTreeSet<&str> = rerun
+            .tested
+            .iter()
+            .map(|t| t.name.as_str())
+            .chain(rerun.failures.iter().map(|f| f.project.as_str()))
+            .collect();
+        baseline
+            .results
+            .retain(|t| !rerun_projects.contains(t.name.as_str()));
+        baseline
+            .failures
+            .retain(|f| !rerun_projects.contains(f.project.as_str()));
+        baseline.results.extend(rerun.tested);
+        baseline.failures.extend(rerun.failures);
+    }
+
+    /// Merges the results of runs benchmarking different projects with the same settings (e.g.
+    /// shards benchmarked on different machines) into a single run, remembering where every
+    /// project comes from.
+    ///
+    /// The results of a project cover all the sources of its input, so the baseline and
+    /// comparisons of `diff` runs stay paired. A regression gate is evaluated again on the merged
+    /// results if all the inputs were gated the same way, and dropped otherwise.
+    ///
+    /// Errors if the inputs differ in settings or sources, or if a project was benchmarked by more
+    /// than one input. The number of test threads is only kept if all the inputs agree on it.
+    pub fn merge(inputs: Vec<(String, Self)>) -> Result<Self> {
+        if inputs.len() < 2 {
+            return Err(eyre!("At least two result files are needed to merge"));
+        }
+        let (first_path, first) = &inputs[0];
+        for (path, results) in &inputs[1..] {
+            let mut mismatches: Vec<String> = first
+                .settings_mismatches(results)
+                .into_iter()
+                .filter(|mismatch| {
+                    !mismatch.starts_with("the set of benchmarked projects")
+                        && !mismatch.starts_with("forge test threads")
+                        && !mismatch.starts_with("forge environment")
+                })
+                .collect();
+            if first.sources.len() != results.sources.len()
+                || first.sources.iter().zip(&results.sources).any(|(a, b)| {
+                    (&a.ty, &a.name, &a.label, &a.env) != (&b.ty, &b.name, &b.label, &b.env)
+                })
+            {
+                mismatches.push("the benchmarked sources differ".to_string());
+            }
+            if !mismatches.is_empty() {
+                return Err(eyre!(
+                    "{first_path} and {path} can't be merged: {}",
+                    mismatches.join(", ")
+                ));
+            }
+        }
+        let mut seen: BTreeMap<&str, &str> = BTreeMap::new();
+        for (path, results) in &inputs {
+            for project in &results.projects {
+                if let Some(other) = seen.insert(project, path) {
+                    return Err(eyre!(
+                        "{project} was benchmarked in both {other} and {path}"
+                    ));
+                }
+            }
+        }
+
+        let gate = match inputs
+            .iter()
+            .map(|(_, results)| results.gate.as_ref())
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(gates)
+                if gates.iter().all(|gate| {
+                    (gate.threshold, gate.metric, &gate.excluded)
+                        == (gates[0].threshold, gates[0].metric, &gates[0].excluded)
+                }) =>
+            {
+                Some((
+                    gates[0].threshold,
+                    gates[0].metric,
+                    gates[0].excluded.clone(),
+                ))
+            }
+            _ => None,
+        };
+
+        let mut inputs = inputs.into_iter();
+        let (path, mut merged) = inputs.next().expect("checked above");
+        merged.gate = None;
+        merged.merged_from = vec![MergedInput::new(path, &merged)];
+        for (path, results) in inputs {
+            merged.merged_from.push(MergedInput::new(path, &results));
+            merged.timestamp = merged.timestamp.max(results.timestamp);
+            if merged.preset != results.preset {
+                merged.preset = None;
+            }
+            if merged.forge_threads != results.forge_threads {
+                merged.forge_threads = None;
+            }
+            merged.clean_env &= results.clean_env;
+            // The projects of every input differ, and so do their fingerprints.
+            merged.reproduction = None;
+            // The inputs benchmark the same sources, so their flamegraphs share the columns.
+            match (&mut merged.flamegraphs, results.flamegraphs) {
+                (Some(flamegraphs), Some(other)) => flamegraphs.profiles.extend(other.profiles),
+                (None, other) => merged.flamegraphs = other,
+                (Some(_), None) => {}
+            }
+            merged.projects.extend(results.projects);
+            for (source, other) in merged.sources.iter_mut().zip(results.sources) {
+                // Differing builds are kept in the merged inputs.
+                if source.forge_version != other.forge_version {
+                    source.forge_version = None;
+                }
+                if source.foundry_repo != other.foundry_repo {
+                    source.foundry_repo = None;
+                }
+                source.results.extend(other.results);
+                source.failures.extend(other.failures);
+            }
+        }
+
+        if let Some((threshold, metric, excluded)) = gate {
+            let report = {
+                let b = merged.to_benchmarks(merged.foundry_repo.as_deref().unwrap_or_default());
+                GateReport::evaluate(&b, threshold, metric, &excluded)
+            };
+            merged.gate = Some(report);
+        }
+        Ok(merged)
+    }
+
+    /// Writes the results to `path` as pretty-printed JSON.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).wrap_err_with(|| format!("Failed to write results file: {path}"))
+    }
+
+    /// Loads results previously written with [`RunResults::save`], upgrading older formats.
+    pub fn load(path: &str) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read results file: {path}"))?;
+
+        // Check the schema version first, to give a clear error on incompatible files.
+        let value: serde_json::Value = serde_json::from_str(&contents)
+            .wrap_err_with(|| format!("Failed to parse results file: {path}"))?;
+        let results = match value.get("schema_version").and_then(|v| v.as_u64()) {
+            Some(1) => serde_json::from_value::<RunResultsV1>(value).map(Self::from),
+            Some(version) if version == SCHEMA_VERSION as u64 => serde_json::from_value(value),
+            Some(version) => {
+                return Err(eyre!(
+                    "Unsupported schema version {version} in results file {path} (expected {SCHEMA_VERSION})"
+                ));
+            }
+            None => return Err(eyre!("Missing 'schema_version' in results file: {path}")),
+        }
+        .wrap_err_with(|| format!("Failed to parse results file: {path}"))?;
+
+        if results.sources.is_empty() {
+            return Err(eyre!("No results in results file: {path}"));
+        }
+        Ok(results)
+    }
+
+    /// Ensures that these results can be compared against a run of `projects` with `num_runs`.
+    pub fn check_compatible(&self, projects: &[String], num_runs: usize) -> Result<()> {
+        if self.num_runs != num_runs {
+            return Err(eyre!(
+                "The recorded results used {} test runs, but this run uses {num_runs}",
+                self.num_runs
+            ));
+        }
+
+        let recorded: BTreeSet<&String> = self.projects.iter().collect();
+        let current: BTreeSet<&String> = projects.iter().collect();
+        if recorded != current {
+            let mut msg =
+                String::from("The recorded results benchmarked a different set of projects:");
+            for name in current.difference(&recorded) {
+                msg.push_str(&format!("\n - {name}: not in the recorded results"));
+            }
+            for name in recorded.difference(&current) {
+                msg.push_str(&format!("\n - {name}: only in the recorded results"));
+            }
+            return Err(eyre!(msg));
+        }
+
+        Ok(())
+    }
+
+    /// Describes every setting that differs between these results and `other`, making them not
+    /// directly comparable.
+    pub fn settings_mismatches(&self, other: &Self) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if self.num_runs != other.num_runs {
+            mismatches.push(format!(
+                "number of test runs differs ({} vs {})",
+                self.num_runs, other.num_runs
+            ));
+        }
+        if self.verbosity != other.verbosity {
+            mismatches.push(format!(
+                "test verbosity differs ({} vs {})",
+                self.verbosity, other.verbosity
+            ));
+        }
+        if self.limit != other.limit {
+            let limit =
+                |limit: Option<usize>| limit.map_or_else(|| "none".to_string(), |n| n.to_string());
+            mismatches.push(format!(
+                "project limit differs ({} vs {})",
+                limit(self.limit),
+                limit(other.limit)
+            ));
+        }
+        if self.forge_threads != other.forge_threads {
+            mismatches.push(format!(
+                "forge test threads differ ({} vs {})",
+                describe_threads(self.forge_threads),
+                describe_threads(other.forge_threads)
+            ));
+        }
+        if self.clean_env != other.clean_env {
+            mismatches.push(format!(
+                "forge environment differs ({} vs {})",
+                describe_env(self.clean_env),
+                describe_env(other.clean_env)
+            ));
+        }
+        let differing = self.differing_hooks(other);
+        if !differing.is_empty() {
+            mismatches.push(format!(
+                "the --prepare/--conclude hooks of {} differ",
+                differing.join(", ")
+            ));
+        }
+        let projects: BTreeSet<&String> = self.projects.iter().collect();
+        if projects != other.projects.iter().collect() {
+            mismatches.push("the set of benchmarked projects differs".to_string());
+        }
+        mismatches
+    }
+
+    /// Names of the projects whose baseline results here and in `other` ran their test runs
+    /// between different hooks, see [`Tested::prepare`].
+    fn differing_hooks<'a>(&'a self, other: &Self) -> Vec<&'a str> {
+        let hooks = |tested: &Tested| (tested.prepare.clone(), tested.conclude.clone());
+        self.baseline()
+            .results
+            .iter()
+            .filter(|tested| {
+                other
+                    .baseline()
+                    .results
+                    .iter()
+                    .any(|t| t.name == tested.name && hooks(t) != hooks(tested))
+            })
+            .map(|tested| tested.name.as_str())
+            .collect()
+    }
+
+    /// Names of the projects with baseline results here, but not in `other`.
+    pub fn missing_from<'a>(&'a self, other: &Self) -> Vec<&'a str> {
+        let other = &other.baseline().results;
+        self.baseline()
+            .results
+            .iter()
+            .filter(|tested| !other.iter().any(|t| t.name == tested.name))
+            .map(|tested| tested.name.as_str())
+            .collect()
+    }
+}
+
+/// Notes that the tests ran on `threads` threads, in the reports.
+pub fn threads_note(threads: usize) -> String {
+    format!("forge test ran on {threads} threads (--forge-threads).")
+}
+
+/// Note of the results of a run whose forge commands ran with a clean environment.
+pub const CLEAN_ENV_NOTE: &str = "forge ran with a clean environment (--clean-env).";
+
+/// Describes the environment forge ran with, `clean` with `--clean-env`.
+pub fn describe_env(clean_env: bool) -> &'static str {
+    if clean_env { "clean" } else { "inherited" }
+}
+
+/// Describes the number of test threads of a run, `default` if not set with `--forge-threads`.
+pub fn describe_threads(threads: Option<usize>) -> String {
+    threads.map_or_else(|| "default".to_string(), |threads| threads.to_string())
+}
+
+/// Describes when and where results were recorded, e.g. `recorded on 2025-06-16 12:30:00 UTC on host`.
+fn recorded_on(timestamp: u64, host: &HostInfo) -> String {
+    format!(
+        "recorded on {}{}",
+        utils::format_timestamp(timestamp),
+        host.hostname
+            .as_ref()
+            .map_or_else(String::new, |hostname| format!(" on {hostname}"))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        benchmark::{Source, SourceResults, TimingSource},
+        gate::GateMetric,
+        ui,
+    };
+    use tempfile::tempdir;
+
+    fn tested(name: &str, avg_test_time: f64) -> Tested {
+        Tested {
+            runs: 3,
+            samples: vec![avg_test_time; 3],
+            ..Tested::fixture(name, avg_test_time)
+        }
+    }
+
+    fn forge(version: &str, commit: &str) -> ForgeVersion {
+        ForgeVersion {
+            version: version.to_string(),
+            commit: Some(commit.to_string()),
+        }
+    }
+
+    fn run_results() -> RunResults {
+        RunResults::new(
+            Some(forge("1.2.3-stable", "abcdef")),
+            3,
+            0,
+            vec!["a/a".to_string(), "b/b".to_string()],
+            vec![tested("a/a", 2.0)],
+            vec![failure("b/b", "build")],
+        )
+    }
+
+    fn failure(project: &str, stage: &str) -> Failure {
+        Failure {
+            project: project.to_string(),
+            stage: stage.to_string(),
+            error: "failed".to_string(),
+            failed_tests: vec![],
+        }
+    }
+
+    #[test]
+    fn test_run_results_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.json");
+        let path = path.to_str().unwrap();
+
+        let mut results = run_results();
+        results.preset = Some("quick".to_string());
+        let reproduction = Reproduction {
+            command: "foundry-benchmarks --num-runs 3".to_string(),
+            fingerprint: "0123456789abcdef".to_string(),
+            version: "0.1.0".to_string(),
+        };
+        results.reproduction = Some(reproduction.clone());
+        results.save(path).unwrap();
+        let loaded = RunResults::load(path).unwrap();
+
+        assert_eq!(loaded.schema_version, SCHEMA_VERSION);
+        assert_eq!(loaded.preset.as_deref(), Some("quick"));
+        assert_eq!(loaded.reproduction.as_ref(), Some(&reproduction));
+        // The markdown tables end with the reproduction footer.
+        let markdown = ui::render_markdown(&loaded.to_benchmarks("foundry-rs/foundry"));
+        assert!(markdown.ends_with(
+            "## reproduction\n\n- command: `foundry-benchmarks --num-runs 3`\n- config fingerprint: `0123456789abcdef`\n- foundry-benchmarks version: 0.1.0\n"
+        ));
+        assert_eq!(loaded.num_runs, 3);
+        assert_eq!(loaded.projects, ["a/a", "b/b"]);
+        assert_eq!(loaded.host.os, env::consts::OS);
+        let baseline = loaded.baseline();
+        assert_eq!(baseline.name, "1.2.3-stable");
+        assert_eq!(
+            baseline.forge_version.as_ref().unwrap().version,
+            "1.2.3-stable"
+        );
+        assert_eq!(baseline.results.len(), 1);
+        assert_eq!(baseline.results[0].name, "a/a");
+        assert_eq!(baseline.results[0].avg_test_time, 2.0);
+        assert_eq!(baseline.results[0].samples, [2.0, 2.0, 2.0]);
+        assert_eq!(baseline.failures.len(), 1);
+        assert_eq!(baseline.failures[0].project, "b/b");
+        assert_eq!(baseline.failures[0].stage, "build");
+    }
+
+    #[test]
+    fn test_merge_rerun() {
+        let mut results = run_results();
+        results.projects.push("c/c".to_string());
+        assert_eq!(results.failed_projects(), ["b/b", "c/c"]);
+
+        results.merge_rerun(PipelineResults {
+            tested: vec![tested("b/b", 4.0)],
+            failures: vec![failure("c/c", "test")],
+            missing_env_examples: Vec::new(),
+        });
+        assert_eq!(results.failed_projects(), ["c/c"]);
+        let baseline = results.baseline();
+        assert_eq!(baseline.results.len(), 2);
+        assert_eq!(baseline.results[1].avg_test_time, 4.0);
+        assert_eq!(baseline.failures.len(), 1);
+        assert_eq!(baseline.failures[0].project, "c/c");
+        assert_eq!(baseline.failures[0].stage, "test");
+    }
+
+    #[test]
+    fn test_apply_run_settings() {
+        let mut results = run_results();
+        results.forge_threads = Some(4);
+        results.clean_env = true;
+        let mut project = ProjectConfig::new("b/b");
+        project.forge_threads = NonZeroUsize::new(8);
+        results.apply_run_settings(&mut project);
+        assert_eq!(project.forge_threads, NonZeroUsize::new(4));
+        assert!(project.clean_env);
+        assert_eq!(project.timing_source, TimingSource::Process);
+
+        // A single project timed by forge means that the run was.
+        results.sources[0].results[0].timing_source = TimingSource::Forge;
+        results.forge_threads = None;
+        results.clean_env = false;
+        results.apply_run_settings(&mut project);
+        assert_eq!(project.forge_threads, None);
+        assert!(!project.clean_env);
+        assert_eq!(project.timing_source, TimingSource::Forge);
+
+        // Without any tested project, the timing source is unknown and left as is.
+        results.sources[0].results.clear();
+        project.timing_source = TimingSource::Process;
+        results.apply_run_settings(&mut project);
+        assert_eq!(project.timing_source, TimingSource::Process);
+    }
+
+    #[test]
+    fn test_diff_results_round_trip() {
+        let (stable, branch) = ("stable".to_string(), "my-branch".to_string());
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 3,
+            results: vec![
+                SourceResults {
+                    source: Source::Version(stable.clone()),
+                    foundry_repo: "foundry-rs/foundry".to_string(),
+                    label: None,
+                    forge: Some(forge("1.2.3-stable", "abcdef")),
+                    tests: vec![tested("a/a", 2.0)],
+                },
+                SourceResults {
+                    source: Source::Branch(branch.clone()),
+                    foundry_repo: "myuser/foundry".to_string(),
+                    label: Some("Optimized".to_string()),
+                    forge: Some(forge("1.2.3-nightly", "012345")),
+                    tests: vec![tested("a/a", 3.0)],
+                },
+            ],
+            notes: vec![],
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        };
+        let gate = GateReport::evaluate(&b, 5.0, GateMetric::Project, &[]);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("diff.json");
+        let path = path.to_str().unwrap();
+        RunResults::from_benchmarks(&b, 0, vec!["a/a".to_string()], Some(gate))
+            .save(path)
+            .unwrap();
+        let loaded = RunResults::load(path).unwrap();
+
+        assert_eq!(loaded.foundry_repo.as_deref(), Some("foundry-rs/foundry"));
+        assert_eq!(loaded.sources.len(), 2);
+        assert_eq!(loaded.baseline().ty, "version");
+        assert_eq!(loaded.baseline().name, "stable");
+        assert_eq!(loaded.sources[1].ty, "branch");
+        assert_eq!(loaded.sources[1].label.as_deref(), Some("Optimized"));
+        assert_eq!(
+            loaded.sources[1].foundry_repo.as_deref(),
+            Some("myuser/foundry")
+        );
+        assert_eq!(
+            loaded.sources[1]
+                .forge_version
+                .as_ref()
+                .unwrap()
+                .commit
+                .as_deref(),
+            Some("012345")
+        );
+        assert_eq!(loaded.sources[1].results[0].avg_test_time, 3.0);
+        let gate = loaded.gate.as_ref().unwrap();
+        assert!(!gate.passed());
+        assert_eq!(gate.violations[0].project.as_deref(), Some("a/a"));
+
+        // The tables of the recorded run can be rebuilt from the file.
+        let rebuilt = loaded.to_benchmarks("foundry-rs/foundry");
+        assert_eq!(rebuilt.results.len(), 2);
+        assert_eq!(rebuilt.baseline().source.name(), "stable");
+        assert_eq!(rebuilt.comparisons()[0].display_name(), "Optimized");
+        assert_eq!(
+            rebuilt.comparisons()[0]
+                .source
+                .github_url(&rebuilt.comparisons()[0].foundry_repo),
+            "https://github.com/myuser/foundry/tree/my-branch"
+        );
+    }
+
+    /// Results of a gated `diff` of `stable` against `my-branch`, with the baseline and comparison
+    /// test time of every project.
+    fn diff_results(projects: &[(&str, f64, f64)]) -> RunResults {
+        let (stable, branch) = ("stable".to_string(), "my-branch".to_string());
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 3,
+            results: [
+                (Source::Version(stable.clone()), 1),
+                (Source::Branch(branch.clone()), 2),
+            ]
+            .into_iter()
+            .map(|(source, i)| SourceResults {
+                source,
+                foundry_repo: "foundry-rs/foundry".to_string(),
+                label: None,
+                forge: None,
+                tests: projects
+                    .iter()
+                    .map(|&(name, before, after)| tested(name, if i == 1 { before } else { after }))
+                    .collect(),
+            })
+            .collect(),
+            notes: vec![],
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        };
+        let gate = GateReport::evaluate(&b, 5.0, GateMetric::Project, &[]);
+        let names = projects.iter().map(|p| p.0.to_string()).collect();
+        RunResults::from_benchmarks(&b, 0, names, Some(gate))
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = diff_results(&[("a/a", 2.0, 2.0), ("b/b", 1.0, 1.0)]);
+        a.host.hostname = Some("runner-1".to_string());
+        let mut b = diff_results(&[("c/c", 2.0, 4.0)]);
+        b.host.hostname = Some("runner-2".to_string());
+        b.timestamp = a.timestamp + 60;
+        assert!(a.gate.as_ref().unwrap().passed());
+
+        let merged =
+            RunResults::merge(vec![("a.json".to_string(), a), ("b.json".to_string(), b)]).unwrap();
+        assert_eq!(merged.projects, ["a/a", "b/b", "c/c"]);
+        assert_eq!(merged.sources.len(), 2);
+        // Every project keeps its baseline and comparison results.
+        for source in &merged.sources {
+            let names: Vec<_> = source.results.iter().map(|t| t.name.as_str()).collect();
+            assert_eq!(names, ["a/a", "b/b", "c/c"]);
+        }
+        assert_eq!(merged.sources[1].results[2].avg_test_time, 4.0);
+        assert_eq!(merged.merged_from.len(), 2);
+        assert_eq!(merged.merged_from[1].projects, ["c/c"]);
+        assert_eq!(
+            merged.merged_from[1].host.hostname.as_deref(),
+            Some("runner-2")
+        );
+        assert_eq!(merged.timestamp, merged.merged_from[1].timestamp);
+        // The gate is evaluated again, and now fails on the regression of the second input.
+        let gate = merged.gate.as_ref().unwrap();
+        assert!(!gate.passed());
+        assert_eq!(gate.violations[0].project.as_deref(), Some("c/c"));
+
+        let b = merged.to_benchmarks("foundry-rs/foundry");
+        assert!(b.notes[1].starts_with("c/c merged from b.json, recorded on "));
+        assert!(b.notes[1].ends_with(" on runner-2."));
+
+        // Each project can only come from a single input.
+        let duplicate = RunResults::merge(vec![
+            ("a.json".to_string(), diff_results(&[("a/a", 1.0, 1.0)])),
+            ("b.json".to_string(), diff_results(&[("a/a", 1.0, 1.0)])),
+        ]);
+        assert_eq!(
+            duplicate.unwrap_err().to_string(),
+            "a/a was benchmarked in both a.json and b.json"
+        );
+
+        // The inputs must share their settings and sources.
+        let mut other = diff_results(&[("c/c", 1.0, 1.0)]);
+        other.num_runs = 5;
+        other.sources.truncate(1);
+        let mismatch = RunResults::merge(vec![
+            ("a.json".to_string(), diff_results(&[("a/a", 1.0, 1.0)])),
+            ("b.json".to_string(), other),
+        ]);
+        assert_eq!(
+            mismatch.unwrap_err().to_string(),
+            "a.json and b.json can't be merged: number of test runs differs (3 vs 5), the benchmarked sources differ"
+        );
+
+        // Inputs tested on different numbers of threads merge, without recording any.
+        let threads = |projects: &[(&str, f64, f64)], forge_threads| {
+            let mut results = diff_results(projects);
+            results.forge_threads = forge_threads;
+            results
+        };
+        let merge = |a, b| {
+            RunResults::merge(vec![("a.json".to_string(), a), ("b.json".to_string(), b)]).unwrap()
+        };
+        let merged = merge(
+            threads(&[("a/a", 1.0, 1.0)], Some(4)),
+            threads(&[("c/c", 1.0, 1.0)], Some(4)),
+        );
+        assert_eq!(merged.forge_threads, Some(4));
+        assert!(
+            merged
+                .to_benchmarks("foundry-rs/foundry")
+                .notes
+                .contains(&"forge test ran on 4 threads (--forge-threads).".to_string())
+        );
+        let merged = merge(
+            threads(&[("a/a", 1.0, 1.0)], Some(4)),
+            threads(&[("c/c", 1.0, 1.0)], None),
+        );
+        assert_eq!(merged.forge_threads, None);
+
+        // The merged results only ran forge with a clean environment if every input did.
+        let clean = |projects: &[(&str, f64, f64)], clean_env| {
+            let mut results = diff_results(projects);
+            results.clean_env = clean_env;
+            results
+        };
+        let merged = merge(
+            clean(&[("a/a", 1.0, 1.0)], true),
+            clean(&[("c/c", 1.0, 1.0)], true),
+        );
+        assert!(merged.clean_env);
+        assert!(
+            merged
+                .to_benchmarks("foundry-rs/foundry")
+                .notes
+                .contains(&CLEAN_ENV_NOTE.to_string())
+        );
+        let merged = merge(
+            clean(&[("a/a", 1.0, 1.0)], true),
+            clean(&[("c/c", 1.0, 1.0)], false),
+        );
+        assert!(!merged.clean_env);
+    }
+
+    #[test]
+    fn test_to_benchmarks_standard_run() {
+        let results = run_results();
+        let b = results.to_benchmarks("myuser/foundry");
+        assert_eq!(b.results.len(), 1);
+        assert!(b.comparisons().is_empty());
+        assert_eq!(b.baseline().source.name(), "1.2.3-stable");
+        assert_eq!(b.baseline().foundry_repo, "myuser/foundry");
+        assert!(
+            b.notes
+                .iter()
+                .any(|note| note == "failed with the baseline: b/b.")
+        );
+
+        let mut results = run_results();
+        results.preset = Some("quick".to_string());
+        results.limit = Some(2);
+        let b = results.to_benchmarks("myuser/foundry");
+        assert!(
+            b.notes
+                .iter()
+                .any(|note| note == "limited to the first 2 projects with --limit.")
+        );
+        assert!(
+            b.notes
+                .iter()
+                .any(|note| note == "run with the preset quick.")
+        );
+    }
+
+    #[test]
+    fn test_env_results_round_trip() {
+        let env =
+            |runs: &str| BTreeMap::from([("FOUNDRY_FUZZ_RUNS".to_string(), runs.to_string())]);
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 3,
+            results: ["256", "1024"]
+                .into_iter()
+                .map(|runs| SourceResults {
+                    source: Source::Env {
+                        label: format!("fuzz-{runs}"),
+                        vars: env(runs),
+                    },
+                    foundry_repo: "foundry-rs/foundry".to_string(),
+                    label: None,
+                    forge: Some(forge("1.2.3-stable", "abcdef")),
+                    tests: vec![tested("a/a", 2.0)],
+                })
+                .collect(),
+            notes: vec![],
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        };
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("matrix.json");
+        let path = path.to_str().unwrap();
+        RunResults::from_benchmarks(&b, 0, vec!["a/a".to_string()], None)
+            .save(path)
+            .unwrap();
+        let loaded = RunResults::load(path).unwrap();
+
+        assert_eq!(loaded.sources[1].ty, "env");
+        assert_eq!(loaded.sources[1].name, "fuzz-1024");
+        assert_eq!(loaded.sources[1].env, env("1024"));
+        match loaded.sources[1].source() {
+            Source::Env { label, vars } => {
+                assert_eq!(label, "fuzz-1024");
+                assert_eq!(vars, env("1024"));
+            }
+            source => panic!("unexpected source: {source:?}"),
+        }
+    }
+
+    #[test]
+    fn test_run_results_load_v1() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.json");
+        fs::write(
+            &path,
+            r#"{"schema_version": 1, "timestamp": 0, "forge_version": null, "num_runs": 3,
+                "verbosity": 0, "projects": ["a/a"], "results": [{"name": "a/a",
+                "url": "https://github.com/a/a", "build_time": 1.0, "avg_test_time": 2.0,
+                "runs": 3}]}"#,
+        )
+        .unwrap();
+
+        let loaded = RunResults::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.schema_version, SCHEMA_VERSION);
+        assert_eq!(loaded.baseline().name, "unknown");
+        assert_eq!(loaded.baseline().results[0].avg_test_time, 2.0);
+        assert!(loaded.baseline().results[0].samples.is_empty());
+    }
+
+    #[test]
+    fn test_run_results_unsupported_schema() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.json");
+        fs::write(&path, r#"{"schema_version": 999}"#).unwrap();
+
+        let err = RunResults::load(path.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("Unsupported schema version 999"));
+    }
+
+    #[test]
+    fn test_check_compatible() {
+        let results = run_results();
+        let projects = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert!(
+            results
+                .check_compatible(&projects(&["b/b", "a/a"]), 3)
+                .is_ok()
+        );
+
+        let err = results
+            .check_compatible(&projects(&["a/a", "b/b"]), 10)
+            .unwrap_err();
+        assert!(err.to_string().contains("used 3 test runs"));
+
+        let err = results
+            .check_compatible(&projects(&["a/a", "c/c"]), 3)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("c/c: not in the recorded results"));
+        assert!(err.contains("b/b: only in the recorded results"));
+    }
+
+    #[test]
+    fn test_settings_mismatches() {
+        let old = run_results();
+        let mut new = run_results();
+        assert!(old.settings_mismatches(&new).is_empty());
+        assert!(old.missing_from(&new).is_empty());
+
+        new.num_runs = 5;
+        new.verbosity = 2;
+        new.limit = Some(1);
+        new.forge_threads = Some(8);
+        new.clean_env = true;
+        new.projects.pop();
+        new.sources[0].results = vec![tested("b/b", 1.0)];
+        assert_eq!(
+            old.settings_mismatches(&new),
+            [
+                "number of test runs differs (3 vs 5)",
+                "test verbosity differs (0 vs 2)",
+                "project limit differs (none vs 1)",
+                "forge test threads differ (default vs 8)",
+                "forge environment differs (inherited vs clean)",
+                "the set of benchmarked projects differs"
+            ]
+        );
+        assert_eq!(old.missing_from(&new), ["a/a"]);
+        assert_eq!(new.missing_from(&old), ["b/b"]);
+
+        // Only the hooks of the projects benchmarked by both are compared.
+        let mut new = run_results();
+        new.sources[0].results = vec![
+            Tested {
+                prepare: Some("sync".to_string()),
+                ..tested("a/a", 2.0)
+            },
+            Tested {
+                conclude: Some("sync".to_string()),
+                ..tested("b/b", 1.0)
+            },
+        ];
+        assert_eq!(
+            old.settings_mismatches(&new),
+            ["the --prepare/--conclude hooks of a/a differ"]
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use crate::utils;
+
+/// Directory of the work dir holding the cached RPC responses, shared by every run.
+pub const CACHE_DIR: &str = "rpc-cache";
+
+/// Methods whose response only depends on their parameters once the block is pinned, along with
+/// the position of their block parameter.
+const PINNED_METHODS: &[(&str, usize)] = &[
+    ("eth_getCode", 1),
+    ("eth_getStorageAt", 2),
+    ("eth_getBalance", 1),
+    ("eth_getTransactionCount", 1),
+    ("eth_call", 1),
+    ("eth_getProof", 2),
+    ("eth_getBlockByNumber", 0),
+];
+
+/// Methods whose response never changes, e.g. lookups by hash.
+const FIXED_METHODS: &[&str] = &[
+    "eth_chainId",
+    "net_version",
+    "eth_getBlockByHash",
+    "eth_getTransactionByHash",
+    "eth_getTransactionReceipt",
+];
+
+/// Endpoints of the proxies started so far, by upstream endpoint. A proxy serves every project
+/// and every source forking the same upstream until the process exits.
+static PROXIES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Returns the endpoint of the local proxy caching the requests to `upstream` in the work dir,
+/// starting it on first use.
+pub fn endpoint(upstream: &str) -> io::Result<String> {
+    let mut proxies = PROXIES.lock().unwrap();
+    if let Some(endpoint) = proxies.get(upstream) {
+        return Ok(endpoint.clone());
+    }
+    let endpoint = start(upstream, utils::work_dir()?.join(CACHE_DIR))?;
+    proxies.insert(upstream.to_string(), endpoint.clone());
+    Ok(endpoint)
+}
+
+/// Starts a JSON-RPC proxy forwarding the requests to `upstream` (with `curl`), and answering the
+/// deterministic ones from the responses cached in `cache_dir`, see [`cache_key`]. Returns its
+/// endpoint, e.g. `http://127.0.0.1:41234`.
+pub fn start(upstream: &str, cache_dir: PathBuf) -> io::Result<String> {
+    fs::create_dir_all(&cache_dir)?;
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let endpoint = format!("http://{}", listener.local_addr()?);
+    let upstream = upstream.to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let (upstream, cache_dir) = (upstream.clone(), cache_dir.clone());
+            thread::spawn(move || serve(stream, &upstream, &cache_dir));
+        }
+    });
+    Ok(endpoint)
+}
+
+/// Serves the HTTP requests of a connection until the client closes it.
+fn serve(stream: TcpStream, upstream: &str, cache_dir: &Path) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    while let Ok(Some(request)) = read_request(&mut reader) {
+        let response = match serde_json::from_slice::<Value>(&request.body) {
+            Ok(Value::Array(batch)) => Value::Array(
+                batch
+                    .iter()
+                    .map(|request| handle(request, upstream, cache_dir))
+                    .collect(),
+            ),
+            Ok(request) => handle(&request, upstream, cache_dir),
+            Err(e) => error_response(&Value::Null, -32700, &format!("Parse error: {e}")),
+        };
+        let body = response.to_string();
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        if writer
+            .write_all(head.as_bytes())
+            .and_then(|_| writer.write_all(body.as_bytes()))
+            .is_err()
+            || request.close
+        {
+            return;
+        }
+    }
+}
+
+/// An HTTP request read by [`read_request`].
+pub(crate) struct Request {
+    pub(crate) body: Vec<u8>,
+    /// Whether the client asked to close the connection after the response.
+    pub(crate) close: bool,
+}
+
+/// Reads the next HTTP request of a connection, `None` once it's closed.
+pub(crate) fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let mut content_length = 0;
+    let mut close = false;
+    let mut line = String::new();
+    let mut first = true;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            if first {
+                // Tolerates blank lines between requests.
+                continue;
+            }
+            break;
+        }
+        first = false;
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .trim()
+                .parse()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad Content-Length"))?;
+        } else if name.eq_ignore_ascii_case("connection") {
+            close = value.trim().eq_ignore_ascii_case("close");
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(Request { body, close }))
+}
+
+/// Answers a single JSON-RPC request, from the cache if possible.
+fn handle(request: &Value, upstream: &str, cache_dir: &Path) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let path = is_cacheable(method, &params)
+        .then(|| cache_dir.join(format!("{}.json", cache_key(upstream, method, &params))));
+
+    if let Some(path) = &path
+        && let Some(result) = read_cached(path, method, &params)
+    {
+        return json!({ "jsonrpc": "2.0", "id": id, "result": result });
+    }
+    let response = match forward(request, upstream) {
+        Ok(response) => response,
+        Err(e) => return error_response(&id, -32603, &format!("Upstream request failed: {e}")),
+    };
+    // Errors and empty results (e.g. a block that isn't mined yet) may change, and aren't cached.
+    if let Some(path) = &path
+        && let Some(result) = response.get("result").filter(|result| !result.is_null())
+    {
+        write_cached(path, method, &params, result);
+    }
+    response
+}
+
+/// Whether the response to `method` with `params` is deterministic: the method is
+/// [fixed](FIXED_METHODS), or [pinned](PINNED_METHODS) to a block number or hash rather than a tag
+/// like `latest`.
+pub fn is_cacheable(method: &str, params: &Value) -> bool {
+    if FIXED_METHODS.contains(&method) {
+        return true;
+    }
+    let Some((_, position)) = PINNED_METHODS.iter().find(|(name, _)| *name == method) else {
+        return false;
+    };
+    match params.get(position) {
+        Some(Value::String(block)) => block.starts_with("0x") || block == "earliest",
+        // EIP-1898 block parameters.
+        Some(Value::Object(block)) => {
+            block.contains_key("blockHash") || block.contains_key("blockNumber")
+        }
+        _ => false,
+    }
+}
+
+/// Key of the cached response to `method` with `params` from `upstream`: the FNV-1a hash of the
+/// request, ignoring its id.
+pub fn cache_key(upstream: &str, method: &str, params: &Value) -> String {
+    let request = format!("{upstream}\n{method}\n{params}");
+    let hash = request.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// The cached result of `method` with `params`, if any. The request is stored along with the
+/// result, so that a colliding key is a miss.
+fn read_cached(path: &Path, method: &str, params: &Value) -> Option<Value> {
+    let cached: Value = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+    (cached["method"] == method && cached["params"] == *params).then(|| cached["result"].clone())
+}
+
+/// Caches the `result` of `method` with `params`. Failing to write only loses the cache entry.
+fn write_cached(path: &Path, method: &str, params: &Value, result: &Value) {
+    let entry = json!({ "method": method, "params": params, "result": result });
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    // Written aside and renamed, so that concurrent readers never see a partial entry.
+    let partial = path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    if fs::write(&partial, entry.to_string()).is_ok() && fs::rename(&partial, path).is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+}
+
+/// Forwards `request` to `upstream` with `curl`, returning its response.
+fn forward(request: &Value, upstream: &str) -> Result<Value, String> {
+    let mut curl = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--max-time",
+            "120",
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            upstream,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run curl: {e}"))?;
+    if let Some(mut stdin) = curl.stdin.take() {
+        stdin
+            .write_all(request.to_string().as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    let output = curl.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("invalid response: {e}"))
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, sync::Arc};
+    use tempfile::tempdir;
+
+    /// Sends a JSON-RPC `request` to the proxy at `endpoint`, returning the response.
+    fn post(endpoint: &str, request: &Value) -> Value {
+        let mut stream = TcpStream::connect(endpoint.trim_start_matches("http://")).unwrap();
+        let body = request.to_string();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        serde_json::from_str(response.split_once("\r\n\r\n").unwrap().1).unwrap()
+    }
+
+    /// Starts an upstream answering every request with its method name, counting the requests.
+    fn fake_upstream() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                while let Ok(Some(request)) = read_request(&mut reader) {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let request: Value = serde_json::from_slice(&request.body).unwrap();
+                    let body = json!({ "jsonrpc": "2.0", "id": request["id"], "result": request["method"] })
+                        .to_string();
+                    let _ = write!(
+                        writer,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+                        body.len()
+                    );
+                }
+            }
+        });
+        (endpoint, count)
+    }
+
+    #[test]
+    fn test_is_cacheable() {
+        assert!(is_cacheable("eth_chainId", &json!([])));
+        assert!(is_cacheable("eth_getCode", &json!(["0xabc", "0x10"])));
+        assert!(is_cacheable(
+            "eth_call",
+            &json!([{ "to": "0xabc" }, { "blockHash": "0x01" }])
+        ));
+        assert!(!is_cacheable("eth_getCode", &json!(["0xabc", "latest"])));
+        assert!(!is_cacheable("eth_getStorageAt", &json!(["0xabc", "0x0"])));
+        assert!(!is_cacheable("eth_blockNumber", &json!([])));
+        assert!(!is_cacheable("eth_sendRawTransaction", &json!(["0x00"])));
+    }
+
+    #[test]
+    fn test_proxy_caches_pinned_requests() {
+        let (upstream, count) = fake_upstream();
+        let cache = tempdir().unwrap();
+        let proxy = start(&upstream, cache.path().to_path_buf()).unwrap();
+
+        let pinned = json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_getCode", "params": ["0xabc", "0x10"] });
+        assert_eq!(post(&proxy, &pinned)["result"], "eth_getCode");
+        let pinned = json!({ "jsonrpc": "2.0", "id": 2, "method": "eth_getCode", "params": ["0xabc", "0x10"] });
+        let response = post(&proxy, &pinned);
+        assert_eq!(response["result"], "eth_getCode");
+        assert_eq!(response["id"], 2);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // Requests at the latest block are always forwarded.
+        let latest = json!({ "jsonrpc": "2.0", "id": 3, "method": "eth_getCode", "params": ["0xabc", "latest"] });
+        post(&proxy, &latest);
+        post(&proxy, &latest);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        // The cache outlives the proxy, e.g. for the other side of a diff.
+        let proxy = start(&upstream, cache.path().to_path_buf()).unwrap();
+        let batch = post(&proxy, &json!([pinned, latest]));
+        assert_eq!(batch[0]["result"], "eth_getCode");
+        assert_eq!(batch[1]["id"], 3);
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+}
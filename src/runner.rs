@@ -0,0 +1,172 @@
+use std::{
+    io,
+    process::{Child, Command, ExitStatus, Output},
+};
+
+/// Runs the external processes of the pipeline (`git clone`, `forge` and `foundryup`), so that
+/// tests can script their outcome instead of requiring the real binaries.
+pub trait CommandRunner: Sync {
+    /// Runs `cmd` to completion, capturing its stdout and stderr.
+    fn output(&self, cmd: &mut Command) -> io::Result<Output>;
+
+    /// Runs `cmd` to completion, with the stdio of the current process.
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus>;
+
+    /// Starts `cmd` in the background, e.g. an `anvil` node, with the stdio configured on it.
+    fn spawn(&self, cmd: &mut Command) -> io::Result<Child>;
+}
+
+/// Spawns the commands as processes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        cmd.output()
+    }
+
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus> {
+        cmd.status()
+    }
+
+    fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+        cmd.spawn()
+    }
+}
+
+#[cfg(all(test, unix))]
+pub use scripted::{Response, ScriptedRunner, command_line};
+
+#[cfg(all(test, unix))]
+mod scripted {
+    use super::*;
+    use std::{
+        collections::VecDeque, os::unix::process::ExitStatusExt, process::Stdio, sync::Mutex,
+    };
+
+    /// Canned outcome of a command run by a [`ScriptedRunner`].
+    #[derive(Debug, Clone, Default)]
+    pub struct Response {
+        pub code: i32,
+        pub stdout: String,
+        pub stderr: String,
+    }
+
+    impl Response {
+        /// A successful run printing `stdout`.
+        pub fn ok(stdout: &str) -> Self {
+            Self {
+                stdout: stdout.to_string(),
+                ..Default::default()
+            }
+        }
+
+        /// A run exiting with `code`, printing `stdout` and `stderr`.
+        pub fn fail(code: i32, stdout: &str, stderr: &str) -> Self {
+            Self {
+                code,
+                stdout: stdout.to_string(),
+                stderr: stderr.to_string(),
+            }
+        }
+    }
+
+    /// The program and arguments of `cmd`, separated by spaces, e.g. `forge test -vv`.
+    pub fn command_line(cmd: &Command) -> String {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Fake runner answering the commands with scripted responses, and recording their command
+    /// lines (see [`command_line`]).
+    ///
+    /// A command gets the next response of the first script whose prefix starts its command line
+    /// and which has any left. The other commands succeed without output.
+    #[derive(Debug, Default)]
+    pub struct ScriptedRunner {
+        scripts: Mutex<Vec<(String, VecDeque<Response>)>>,
+        calls: Mutex<Vec<String>>,
+        /// Environment variables set on the command of every call.
+        envs: Mutex<Vec<Vec<(String, String)>>>,
+    }
+
+    impl ScriptedRunner {
+        /// Answers the commands starting with `prefix` with `responses`, in order.
+        pub fn on(self, prefix: &str, responses: impl IntoIterator<Item = Response>) -> Self {
+            self.scripts
+                .lock()
+                .unwrap()
+                .push((prefix.to_string(), responses.into_iter().collect()));
+            self
+        }
+
+        /// The command lines run so far, in order.
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        /// The value of `key` set on the command of the `call`-th (from 0) call, if any.
+        pub fn env(&self, call: usize, key: &str) -> Option<String> {
+            self.envs.lock().unwrap()[call]
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.clone())
+        }
+
+        fn respond(&self, cmd: &Command) -> Response {
+            let line = command_line(cmd);
+            self.calls.lock().unwrap().push(line.clone());
+            self.envs.lock().unwrap().push(
+                cmd.get_envs()
+                    .filter_map(|(key, value)| {
+                        Some((
+                            key.to_string_lossy().into_owned(),
+                            value?.to_string_lossy().into_owned(),
+                        ))
+                    })
+                    .collect(),
+            );
+            self.scripts
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .filter(|(prefix, _)| line.starts_with(prefix.as_str()))
+                .find_map(|(_, responses)| responses.pop_front())
+                .unwrap_or_default()
+        }
+    }
+
+    impl CommandRunner for ScriptedRunner {
+        fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+            let response = self.respond(cmd);
+            Ok(Output {
+                status: ExitStatus::from_raw(response.code << 8),
+                stdout: response.stdout.into_bytes(),
+                stderr: response.stderr.into_bytes(),
+            })
+        }
+
+        fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus> {
+            Ok(ExitStatus::from_raw(self.respond(cmd).code << 8))
+        }
+
+        /// Spawns a shell printing the response, which then exits with its code, or runs until
+        /// it's killed if the code is 0.
+        fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+            let response = self.respond(cmd);
+            Command::new("sh")
+                .arg("-c")
+                .arg(r#"printf '%s' "$OUT"; printf '%s' "$ERR" >&2; [ "$CODE" = 0 ] && exec sleep 600; exit "$CODE""#)
+                .env("OUT", response.stdout)
+                .env("ERR", response.stderr)
+                .env("CODE", response.code.to_string())
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+use crate::results::{RunResults, SCHEMA_VERSION};
+
+/// Kind of results file described by the schema printed by `schema`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SchemaKind {
+    /// The results of any run saved with `--save` (standard, `diff` or `matrix`).
+    #[default]
+    Run,
+    /// The results of a `diff` run, which record the baseline and at least one comparison source.
+    Diff,
+}
+
+/// The JSON Schema (draft 2020-12) of the results files of `kind`, at the current
+/// [`SCHEMA_VERSION`].
+pub fn render(kind: SchemaKind) -> Value {
+    let mut schema = schemars::schema_for!(RunResults).to_value();
+    // Files of another version are migrated on load, the schema only describes the current one.
+    schema["properties"]["schema_version"] = json!({
+        "description": "Version of the results file format.",
+        "type": "integer",
+        "const": SCHEMA_VERSION,
+    });
+    match kind {
+        SchemaKind::Run => {
+            schema["title"] = json!("foundry-benchmarks results");
+            schema["properties"]["sources"]["minItems"] = json!(1);
+        }
+        SchemaKind::Diff => {
+            schema["title"] = json!("foundry-benchmarks diff results");
+            schema["properties"]["sources"]["minItems"] = json!(2);
+        }
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        benchmark::{Benchmarks, FailedTest, Failure, Source, SourceResults, Tested},
+        gate::{GateMetric, GateReport, Violation},
+        repro::Reproduction,
+        results::MergedInput,
+        toolchain::ForgeVersion,
+    };
+    use std::collections::BTreeMap;
+
+    /// Checks `value` against `schema`, resolving the `$ref`s in `root`. Only supports the
+    /// keywords emitted by [`render`].
+    fn validate(root: &Value, schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+        let Some(schema) = schema.as_object() else {
+            return match schema {
+                Value::Bool(false) => Err(format!("{path}: not allowed")),
+                _ => Ok(()),
+            };
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let target = reference
+                .strip_prefix('#')
+                .and_then(|pointer| root.pointer(pointer))
+                .ok_or_else(|| format!("{path}: unresolved {reference}"))?;
+            validate(root, target, value, path)?;
+        }
+        if let Some(ty) = schema.get("type") {
+            let types: Vec<&str> = match ty {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                ty => ty.as_str().into_iter().collect(),
+            };
+            let matches = |ty: &str| match ty {
+                "null" => value.is_null(),
+                "boolean" => value.is_boolean(),
+                "integer" => value.is_u64() || value.is_i64(),
+                "number" => value.is_number(),
+                "string" => value.is_string(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                _ => false,
+            };
+            if !types.into_iter().any(matches) {
+                return Err(format!("{path}: expected {ty}, got {value}"));
+            }
+        }
+        if let Some(expected) = schema.get("const")
+            && expected != value
+        {
+            return Err(format!("{path}: expected {expected}, got {value}"));
+        }
+        if let Some(variants) = schema.get("enum").and_then(Value::as_array)
+            && !variants.contains(value)
+        {
+            return Err(format!("{path}: {value} is not one of {variants:?}"));
+        }
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+            && value.as_f64().is_some_and(|v| v < minimum)
+        {
+            return Err(format!("{path}: {value} is below {minimum}"));
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(options) = schema.get(keyword).and_then(Value::as_array) {
+                let valid = options
+                    .iter()
+                    .filter(|option| validate(root, option, value, path).is_ok())
+                    .count();
+                if valid == 0 || (keyword == "oneOf" && valid > 1) {
+                    return Err(format!("{path}: {valid} {keyword} options match {value}"));
+                }
+            }
+        }
+        if let Some(array) = value.as_array() {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+                && (array.len() as u64) < min
+            {
+                return Err(format!("{path}: expected at least {min} items"));
+            }
+            if let Some(items) = schema.get("items") {
+                for (i, item) in array.iter().enumerate() {
+                    validate(root, items, item, &format!("{path}/{i}"))?;
+                }
+            }
+        }
+        if let Some(object) = value.as_object() {
+            for required in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let required = required.as_str().unwrap_or_default();
+                if !object.contains_key(required) {
+                    return Err(format!("{path}: missing {required}"));
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, field) in object {
+                let path = format!("{path}/{key}");
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(property) => validate(root, property, field, &path)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(additional) => validate(root, additional, field, &path)?,
+                        None if properties.is_some() => {
+                            return Err(format!("{path}: unknown property"));
+                        }
+                        None => {}
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check(kind: SchemaKind, results: &RunResults) -> Result<(), String> {
+        let schema = render(kind);
+        validate(
+            &schema,
+            &schema,
+            &serde_json::to_value(results).unwrap(),
+            "",
+        )
+    }
+
+    #[test]
+    fn test_results_match_schema() {
+        let (stable, vars) = (
+            "stable".to_string(),
+            BTreeMap::from([("FOUNDRY_FUZZ_RUNS".to_string(), "512".to_string())]),
+        );
+        let tested = |name: &str| Tested {
+            build_time: 4.5,
+            runs: 2,
+            samples: vec![1.0, 1.5],
+            ..Tested::fixture(name, 1.25)
+        };
+        let source = |source, forge| SourceResults {
+            source,
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge,
+            tests: vec![tested("a/a")],
+        };
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 2,
+            results: vec![source(
+                Source::Version(stable.clone()),
+                Some(ForgeVersion {
+                    version: "1.2.0-stable".to_string(),
+                    commit: Some("abcdef".to_string()),
+                }),
+            )],
+            notes: vec![],
+            reproduction: Some(Reproduction {
+                command: "foundry-benchmarks --save out.json".to_string(),
+                fingerprint: "0123456789abcdef".to_string(),
+                version: "0.1.0".to_string(),
+            }),
+            noise_threshold: None,
+            noisy_threshold: crate::ui::DEFAULT_NOISY_THRESHOLD,
+        };
+        let projects = vec!["a/a".to_string(), "b/b".to_string()];
+        let mut results = RunResults::from_benchmarks(&b, 0, projects.clone(), None);
+        results.sources[0].failures.push(Failure {
+            project: "b/b".to_string(),
+            stage: "test".to_string(),
+            error: "'forge test' for b/b FAILED".to_string(),
+            failed_tests: vec![
+                FailedTest {
+                    name: "CounterTest::test_increment".to_string(),
+                    reason: Some("revert: boom".to_string()),
+                },
+                FailedTest {
+                    name: "CounterTest::test_decrement".to_string(),
+                    reason: None,
+                },
+            ],
+        });
+        results.limit = Some(2);
+        results.merged_from.push(MergedInput {
+            path: "shard-1.json".to_string(),
+            timestamp: results.timestamp,
+            host: results.host.clone(),
+            projects: projects.clone(),
+            forge_versions: vec![None],
+        });
+        check(SchemaKind::Run, &results).unwrap();
+        // A standard run records a single source.
+        assert!(check(SchemaKind::Diff, &results).is_err());
+
+        let mut b = b;
+        b.results.push(source(
+            Source::Env {
+                label: "fuzz".to_string(),
+                vars,
+            },
+            None,
+        ));
+        let gate = GateReport {
+            threshold: 5.0,
+            metric: GateMetric::Geomean,
+            excluded: vec!["b/b".to_string()],
+            violations: vec![Violation {
+                source: "fuzz".to_string(),
+                project: None,
+                relative_diff: 12.5,
+            }],
+        };
+        let mut results = RunResults::from_benchmarks(&b, 3, projects, Some(gate));
+        check(SchemaKind::Diff, &results).unwrap();
+        check(SchemaKind::Run, &results).unwrap();
+
+        results.schema_version = SCHEMA_VERSION + 1;
+        assert!(check(SchemaKind::Diff, &results).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use std::{fmt::Write as _, fs, io, path::Path};
+
+use crate::utils::SyntheticConfig;
+
+/// Solidity version of the generated sources.
+const PRAGMA: &str = "pragma solidity ^0.8.13;";
+
+/// Writes the Foundry project described by `config` into `dir`: a `foundry.toml`, the contracts in
+/// `src/` and their tests in `test/`.
+///
+/// The sources only depend on `config`, so that every side of a diff benchmarks byte-identical
+/// projects. They don't import any library, e.g. `forge-std`, so that no dependency is needed.
+pub fn generate(config: &SyntheticConfig, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir.join("src"))?;
+    fs::create_dir_all(dir.join("test"))?;
+    fs::write(dir.join("foundry.toml"), foundry_toml(config))?;
+    let mut rng = SplitMix64(config.seed);
+    for i in 0..config.contracts {
+        fs::write(
+            dir.join(format!("src/C{i}.sol")),
+            contract(config, i, &mut rng),
+        )?;
+        fs::write(
+            dir.join(format!("test/C{i}.t.sol")),
+            test_contract(config, i, &mut rng),
+        )?;
+    }
+    Ok(())
+}
+
+fn foundry_toml(config: &SyntheticConfig) -> String {
+    let mut toml = String::from(
+        "[profile.default]\nsrc = \"src\"\ntest = \"test\"\nout = \"out\"\nlibs = []\n",
+    );
+    // A fixed fuzz seed makes the fuzz tests run the same inputs on every side.
+    if config.fuzz {
+        let _ = write!(
+            toml,
+            "\n[profile.default.fuzz]\nseed = \"{:#x}\"\n",
+            config.seed
+        );
+    }
+    toml
+}
+
+/// The contract inherited by contract `i`, if any: the contracts form chains of
+/// `inheritance_depth` contracts, each inheriting from the previous one.
+fn parent(config: &SyntheticConfig, i: usize) -> Option<usize> {
+    (!i.is_multiple_of(config.inheritance_depth.max(1))).then(|| i - 1)
+}
+
+/// Contract `i`, with a state variable, a setter and a pure function chaining a few random
+/// arithmetic operations.
+fn contract(config: &SyntheticConfig, i: usize, rng: &mut SplitMix64) -> String {
+    let mut source = format!("// SPDX-License-Identifier: MIT\n{PRAGMA}\n\n");
+    let inherits = match parent(config, i) {
+        Some(parent) => {
+            let _ = writeln!(source, "import {{C{parent}}} from \"./C{parent}.sol\";\n");
+            format!(" is C{parent}")
+        }
+        None => String::new(),
+    };
+    let _ = write!(
+        source,
+        "contract C{i}{inherits} {{\n    uint256 public value{i};\n\n    function set{i}(uint256 x) public {{\n        value{i} = compute{i}(x);\n    }}\n\n    function compute{i}(uint256 x) public pure returns (uint256 y) {{\n        y = x;\n        unchecked {{\n"
+    );
+    for _ in 0..2 + rng.below(5) {
+        let op = ["+", "*", "^", "-"][rng.below(4) as usize];
+        let _ = writeln!(
+            source,
+            "            y = y {op} {};",
+            1 + rng.below(1_000_000)
+        );
+    }
+    source.push_str("        }\n    }\n}\n");
+    source
+}
+
+/// The tests of contract `i`: `tests_per_contract` of them, every other one being a fuzz test with
+/// `fuzz` set.
+fn test_contract(config: &SyntheticConfig, i: usize, rng: &mut SplitMix64) -> String {
+    let mut source = format!(
+        "// SPDX-License-Identifier: MIT\n{PRAGMA}\n\nimport {{C{i}}} from \"../src/C{i}.sol\";\n\ncontract C{i}Test {{\n    C{i} internal c;\n\n    function setUp() public {{\n        c = new C{i}();\n    }}\n"
+    );
+    for j in 0..config.tests_per_contract {
+        if config.fuzz && j % 2 == 1 {
+            let _ = write!(
+                source,
+                "\n    function testFuzz_{j}(uint256 x) public {{\n        c.set{i}(x);\n        require(c.value{i}() == c.compute{i}(x), \"value\");\n    }}\n"
+            );
+        } else {
+            let _ = write!(
+                source,
+                "\n    function test_{j}() public {{\n        c.set{i}({x});\n        require(c.value{i}() == c.compute{i}({x}), \"value\");\n    }}\n",
+                x = rng.below(u64::MAX)
+            );
+        }
+    }
+    source.push_str("}\n");
+    source
+}
+
+/// Small deterministic PRNG, see <https://prng.di.unimi.it/splitmix64.c>.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn read_all(dir: &Path) -> Vec<(String, String)> {
+        let mut files = Vec::new();
+        for sub in ["", "src", "test"] {
+            for entry in fs::read_dir(dir.join(sub)).unwrap().flatten() {
+                if entry.file_type().unwrap().is_file() {
+                    let name = format!("{sub}/{}", entry.file_name().to_string_lossy());
+                    files.push((name, fs::read_to_string(entry.path()).unwrap()));
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_generate() {
+        let config = SyntheticConfig {
+            contracts: 4,
+            tests_per_contract: 3,
+            inheritance_depth: 2,
+            fuzz: true,
+            seed: 7,
+        };
+        let (a, b) = (tempdir().unwrap(), tempdir().unwrap());
+        generate(&config, a.path()).unwrap();
+        generate(&config, b.path()).unwrap();
+        // The same config generates byte-identical projects.
+        let files = read_all(a.path());
+        assert_eq!(files, read_all(b.path()));
+        assert_eq!(files.len(), 9);
+
+        let source = |name: &str| &files.iter().find(|(file, _)| file == name).unwrap().1;
+        assert!(source("/foundry.toml").contains("seed = \"0x7\""));
+        assert!(source("src/C0.sol").contains("contract C0 {"));
+        assert!(source("src/C1.sol").contains("import {C0} from \"./C0.sol\";"));
+        assert!(source("src/C1.sol").contains("contract C1 is C0 {"));
+        assert!(source("src/C2.sol").contains("contract C2 {"));
+        let test = source("test/C3.t.sol");
+        assert!(test.contains("function test_0() public {"));
+        assert!(test.contains("function testFuzz_1(uint256 x) public {"));
+        assert!(test.contains("function test_2() public {"));
+
+        // Another seed generates other sources.
+        let c = tempdir().unwrap();
+        generate(&SyntheticConfig { seed: 8, ..config }, c.path()).unwrap();
+        assert_ne!(
+            fs::read_to_string(a.path().join("src/C0.sol")).unwrap(),
+            fs::read_to_string(c.path().join("src/C0.sol")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,1125 @@
+use eyre::{Result, eyre};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::{OsStr, OsString},
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use yansi::Paint;
+
+use crate::{benchmark::Source, runner::CommandRunner, watch};
+
+/// Directory of the work dir caching the toolchains built from source across runs, in one prefix
+/// per commit (e.g. `toolchain-cache/<sha>`).
+pub const CACHE_DIR: &str = "toolchain-cache";
+
+/// A Foundry toolchain that the benchmark pipeline runs `forge` from.
+#[derive(Debug, Clone, Default)]
+pub struct Toolchain {
+    /// Installation prefix (`FOUNDRY_DIR`) of the toolchain.
+    /// If `None`, the `forge` binary found in the user's `PATH` is used.
+    prefix: Option<PathBuf>,
+    /// Environment variables set on every `forge` command.
+    env: BTreeMap<String, String>,
+    /// Platform the `foundryup` command is built for.
+    platform: Platform,
+    /// Commit the toolchain is built from, if its prefix is cached across runs: the commit is
+    /// installed in place of the requested source, and a working install is reused.
+    commit: Option<String>,
+}
+
+impl Toolchain {
+    /// Creates a toolchain that is installed into (and run from) its own `prefix`, leaving the
+    /// user's global Foundry installation untouched.
+    pub fn isolated(prefix: impl Into<PathBuf>) -> Self {
+        Self {
+            prefix: Some(prefix.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Creates an isolated toolchain built from `commit`, in its prefix of the toolchain cache of
+    /// `work_dir`, where a previous run may already have installed it.
+    pub fn cached(work_dir: &Path, commit: &str) -> Self {
+        Self {
+            commit: Some(commit.to_string()),
+            ..Self::isolated(work_dir.join(CACHE_DIR).join(commit))
+        }
+    }
+
+    /// Sets environment variables on every `forge` command run with this toolchain.
+    pub fn with_env(mut self, env: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Sets the variable `key` to `value` on every `forge` command, besides the others.
+    pub fn with_var(mut self, key: &str, value: &str) -> Self {
+        self.env.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Builds the commands for `platform` instead of the current one, e.g. to check the Windows
+    /// commands on another platform.
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Installation prefix of the toolchain, if isolated.
+    pub fn prefix(&self) -> Option<&Path> {
+        self.prefix.as_deref()
+    }
+
+    /// Directory holding the toolchain binaries, if isolated.
+    pub fn bin_dir(&self) -> Option<PathBuf> {
+        self.prefix.as_ref().map(|prefix| prefix.join("bin"))
+    }
+
+    /// Returns a `forge` command that resolves to this toolchain's binary.
+    pub fn forge(&self) -> Command {
+        let mut cmd = Command::new("forge");
+        if let Some(path) = self.path_env() {
+            // Setting `PATH` on the command also makes it the search path for the program itself.
+            cmd.env("PATH", path);
+        }
+        cmd.envs(&self.env);
+        cmd
+    }
+
+    /// Returns a `forge` command like [`Self::forge`], run without any network access in a network
+    /// namespace of its own (`unshare --net`, as an unprivileged user mapped to root). Linux only,
+    /// see [`check_network_isolation`].
+    pub fn forge_without_network(&self) -> Command {
+        let mut cmd = Command::new("unshare");
+        cmd.args(["--map-root-user", "--net", "forge"]);
+        if let Some(path) = self.path_env() {
+            // `unshare` resolves `forge` from the `PATH` of the command too.
+            cmd.env("PATH", path);
+        }
+        cmd.envs(&self.env);
+        cmd
+    }
+
+    /// Returns a `forge` command like [`Self::forge`], profiled with `perf record -g` into the
+    /// `data` file. Linux only, see [`check_perf`].
+    pub fn forge_under_perf(&self, data: &Path) -> Command {
+        let mut cmd = Command::new("perf");
+        cmd.args(["record", "-q", "-g", "-o"])
+            .arg(data)
+            .args(["--", "forge"]);
+        if let Some(path) = self.path_env() {
+            // `perf` resolves `forge` from the `PATH` of the command too.
+            cmd.env("PATH", path);
+        }
+        cmd.envs(&self.env);
+        cmd
+    }
+
+    /// Returns an `anvil` command that resolves to this toolchain's binary, like [`Self::forge`].
+    pub fn anvil(&self) -> Command {
+        let mut cmd = Command::new("anvil");
+        if let Some(path) = self.path_env() {
+            cmd.env("PATH", path);
+        }
+        cmd
+    }
+
+    /// Builds the `foundryup` invocation (of the `foundryup` binary) that installs `source` from
+    /// `foundry_repo` into this toolchain. A cached toolchain installs its commit instead.
+    pub fn foundryup(&self, foundryup: &Path, foundry_repo: &str, source: &Source) -> Command {
+        let mut cmd = self.platform.command(foundryup);
+        cmd.arg("-r").arg(foundry_repo);
+        if let Some(commit) = &self.commit {
+            cmd.arg("-C").arg(commit);
+        } else if let Some(flag) = source.short() {
+            cmd.arg(flag).arg(source.name());
+        }
+        if let Some(prefix) = &self.prefix {
+            cmd.env("FOUNDRY_DIR", prefix);
+        }
+        cmd
+    }
+
+    /// Installs `source` with `foundryup`, run by `runner`.
+    ///
+    /// `side` names the role of the source in the diff (e.g. "baseline"), and is used to point
+    /// at the culprit when the installation fails.
+    pub fn install(
+        &self,
+        foundryup: &Path,
+        foundry_repo: &str,
+        side: &str,
+        source: &Source,
+        runner: &dyn CommandRunner,
+    ) -> Result<()> {
+        if source.short().is_none() {
+            return Err(eyre!(
+                "{} The {side} source ({}: {}) can't be installed with 'foundryup'",
+                Paint::red("ERROR:").bold(),
+                source.ty(),
+                source.name()
+            ));
+        }
+        run_foundryup(
+            self.foundryup(foundryup, foundry_repo, source),
+            side,
+            source,
+            runner,
+        )
+    }
+
+    /// Installs `source` like [`Self::install`], unless the toolchain is cached and its prefix
+    /// already holds a working `forge` (i.e. `forge --version` succeeds), which is reused instead.
+    /// With `refresh`, a cached toolchain is removed and installed again.
+    ///
+    /// Returns whether the cached toolchain was reused.
+    pub fn install_or_reuse(
+        &self,
+        foundryup: &Path,
+        foundry_repo: &str,
+        side: &str,
+        source: &Source,
+        refresh: bool,
+        runner: &dyn CommandRunner,
+    ) -> Result<bool> {
+        let Some(prefix) = self.prefix.as_ref().filter(|_| self.commit.is_some()) else {
+            self.install(foundryup, foundry_repo, side, source, runner)?;
+            return Ok(false);
+        };
+        if !refresh && self.forge_version().is_ok() {
+            return Ok(true);
+        }
+
+        remove_prefix(prefix)?;
+        let installed = self.install(foundryup, foundry_repo, side, source, runner);
+        // A failed install must not be mistaken for a cached toolchain by the next run.
+        if installed.is_err() {
+            let _ = remove_prefix(prefix);
+        }
+        installed.map(|()| false)
+    }
+
+    /// Returns the build information of the toolchain's `forge` binary.
+    ///
+    /// For isolated toolchains, this also ensures that `forge` was actually installed into the
+    /// prefix, rather than silently falling back to the one in the user's `PATH`.
+    pub fn forge_version(&self) -> Result<ForgeVersion> {
+        if let Some(bin_dir) = self.bin_dir()
+            && !bin_dir.join("forge").exists()
+            && !bin_dir.join("forge.exe").exists()
+        {
+            return Err(eyre!(
+                "{} 'forge' was not installed into {}",
+                Paint::red("ERROR:").bold(),
+                bin_dir.display()
+            ));
+        }
+
+        let output = self
+            .forge()
+            .arg("--version")
+            .output()
+            .map_err(|e| eyre!("Failed to run 'forge --version'. Error: {e}"))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        ForgeVersion::parse(&stdout)
+            .ok_or_else(|| eyre!("Failed to parse the output of 'forge --version': {stdout}"))
+    }
+
+    /// The `PATH` value with the toolchain's binary directory prepended, if isolated.
+    fn path_env(&self) -> Option<OsString> {
+        let bin_dir = self.bin_dir()?;
+        let current = env::var_os("PATH").unwrap_or_default();
+        let paths = std::iter::once(bin_dir).chain(env::split_paths(&current));
+        env::join_paths(paths).ok()
+    }
+}
+
+/// Removes the installation `prefix`, if it exists.
+fn remove_prefix(prefix: &Path) -> Result<()> {
+    match std::fs::remove_dir_all(prefix) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(eyre!(
+            "{} Failed to remove the toolchain prefix {}. Error: {e}",
+            Paint::red("ERROR:").bold(),
+            prefix.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the commit `source` builds forge from, if it's built from source and its toolchain can
+/// be cached: the head of a branch, resolved with `git ls-remote` against `foundry_repo` on the
+/// configured GitHub instance (see [`crate::utils::github_url`]), or a full commit hash.
+/// Abbreviated commits are ambiguous, and versions are downloaded rather than built.
+pub fn source_commit(foundry_repo: &str, source: &Source) -> Result<Option<String>> {
+    match source {
+        Source::Branch(branch) => watch::head_sha(foundry_repo, branch).map(Some),
+        Source::Commit(commit) => Ok(is_full_commit(commit).then(|| commit.to_lowercase())),
+        _ => Ok(None),
+    }
+}
+
+/// Whether `commit` is a full (40 characters) commit hash.
+pub fn is_full_commit(commit: &str) -> bool {
+    commit.len() == 40 && commit.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Platform the external commands are built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Linux and macOS.
+    Unix,
+    /// Windows resolves the programs with their extension (`.exe`, `.cmd` or `.bat`), and can
+    /// neither run batch files nor shell scripts (like `foundryup`) directly.
+    Windows,
+}
+
+impl Default for Platform {
+    /// The platform of the running binary.
+    fn default() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else {
+            Self::Unix
+        }
+    }
+}
+
+impl Platform {
+    /// A command running the program at `path`. On Windows, batch files run through `cmd /C`,
+    /// and the files without extension (shell scripts, like `foundryup`) through `bash`.
+    pub fn command(self, path: &Path) -> Command {
+        if self == Self::Unix {
+            return Command::new(path);
+        }
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("cmd" | "bat") => {
+                let mut cmd = Command::new("cmd");
+                cmd.arg("/C").arg(path);
+                cmd
+            }
+            None => {
+                let mut cmd = Command::new("bash");
+                cmd.arg(path);
+                cmd
+            }
+            Some(_) => Command::new(path),
+        }
+    }
+
+    /// A command running the shell command line `cmd`: through `sh -c`, or `cmd /C` on Windows.
+    pub fn shell(self, cmd: &str) -> Command {
+        let (program, flag) = match self {
+            Self::Unix => ("sh", "-c"),
+            Self::Windows => ("cmd", "/C"),
+        };
+        let mut command = Command::new(program);
+        command.arg(flag).arg(cmd);
+        command
+    }
+
+    /// The file names a program called `name` can have, in the order they are looked up.
+    fn executable_names(self, name: &str) -> Vec<String> {
+        match self {
+            Self::Unix => vec![name.to_string()],
+            Self::Windows => ["exe", "cmd", "bat"]
+                .iter()
+                .map(|ext| format!("{name}.{ext}"))
+                .chain(std::iter::once(name.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// Build information of a `forge` binary, as reported by `forge --version`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ForgeVersion {
+    /// Version string (e.g. `1.2.3-stable`).
+    pub version: String,
+    /// Commit hash the binary was built from, if reported.
+    pub commit: Option<String>,
+}
+
+impl ForgeVersion {
+    /// Parses the output of `forge --version`.
+    ///
+    /// Supports both the current multi-line format:
+    /// ```text
+    /// forge Version: 1.2.3-stable
+    /// Commit SHA: a1b2c3d4e5...
+    /// ```
+    /// and the legacy single-line format: `forge 0.2.0 (a1b2c3d 2024-01-01T00:00:00Z)`.
+    pub fn parse(output: &str) -> Option<Self> {
+        let mut version = None;
+        let mut commit = None;
+        for line in output.lines().map(str::trim) {
+            if let Some(v) = line.strip_prefix("forge Version:") {
+                version = Some(v.trim().to_string());
+            } else if let Some(c) = line.strip_prefix("Commit SHA:") {
+                commit = Some(c.trim().to_string());
+            } else if let Some(rest) = line.strip_prefix("forge ")
+                && version.is_none()
+            {
+                let (v, details) = rest.split_once(' ').unwrap_or((rest, ""));
+                version = Some(v.to_string());
+                commit = details
+                    .trim_start_matches('(')
+                    .split_whitespace()
+                    .next()
+                    .map(|c| c.trim_end_matches(')').to_string());
+            }
+        }
+
+        version.map(|version| Self { version, commit })
+    }
+
+    /// Tag of the Foundry release the binary was built for, if it can be told from its version:
+    /// `v1.2.3` for a `1.2.3-stable` build, and `nightly-<commit>` for a nightly build reporting
+    /// its full commit hash.
+    pub fn release_tag(&self) -> Option<String> {
+        if self.version.ends_with("-nightly") {
+            return self
+                .commit
+                .as_ref()
+                .filter(|commit| commit.len() == 40)
+                .map(|commit| format!("nightly-{commit}"));
+        }
+        let version = self
+            .version
+            .strip_suffix("-stable")
+            .unwrap_or(&self.version);
+        let is_semver = version.split('.').count() == 3
+            && version
+                .split('.')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+        is_semver.then(|| format!("v{version}"))
+    }
+
+    /// The concrete version of the binary, e.g. `v1.2.3, a1b2c3d` for a stable build and
+    /// `1.3.0-nightly, a1b2c3d` for a nightly one.
+    pub fn resolved(&self) -> String {
+        let version = self
+            .release_tag()
+            .filter(|tag| tag.starts_with('v'))
+            .unwrap_or_else(|| self.version.clone());
+        match &self.commit {
+            Some(commit) => format!("{version}, {}", &commit[..commit.len().min(7)]),
+            None => version,
+        }
+    }
+
+    /// Whether both versions were produced by the same build.
+    pub fn same_build(&self, other: &Self) -> bool {
+        match (&self.commit, &other.commit) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.version == other.version,
+        }
+    }
+}
+
+impl std::fmt::Display for ForgeVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.commit {
+            Some(commit) => write!(f, "{} ({})", self.version, &commit[..commit.len().min(7)]),
+            None => write!(f, "{}", self.version),
+        }
+    }
+}
+
+/// Resolves the `foundryup` binary: either the user-provided `bin`, or the one in the `PATH`.
+///
+/// Fails early, before any toolchain is installed, if the binary is missing or not executable.
+pub fn resolve_foundryup(bin: Option<&str>) -> Result<PathBuf> {
+    match bin {
+        Some(bin) => {
+            let path = PathBuf::from(bin);
+            if !path.is_file() {
+                return Err(eyre!(
+                    "{} The foundryup binary {bin} does not exist",
+                    Paint::red("ERROR:").bold()
+                ));
+            }
+            if !is_executable(&path) {
+                return Err(eyre!(
+                    "{} The foundryup binary {bin} is not executable",
+                    Paint::red("ERROR:").bold()
+                ));
+            }
+            Ok(path)
+        }
+        None => find_in_path(
+            "foundryup",
+            &env::var_os("PATH").unwrap_or_default(),
+            Platform::default(),
+        )
+        .ok_or_else(|| foundryup_not_found("'foundryup' was not found in PATH")),
+    }
+}
+
+/// Returns the first executable program called `name` in the directories of the `path` variable,
+/// with the file names of `platform`.
+fn find_in_path(name: &str, path: &OsStr, platform: Platform) -> Option<PathBuf> {
+    let names = platform.executable_names(name);
+    env::split_paths(path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Checks that the timed runs can be isolated from the network with `--no-network` (see
+/// [`Toolchain::forge_without_network`]): only on Linux, with `unshare` allowed to create user
+/// namespaces.
+pub fn check_network_isolation(runner: &dyn CommandRunner) -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        return Err(eyre!(
+            "{} --no-network isolates the test runs in a network namespace, which is only supported on Linux",
+            Paint::red("ERROR:").bold()
+        ));
+    }
+    let output = runner
+        .output(Command::new("unshare").args(["--map-root-user", "--net", "true"]))
+        .map_err(|e| {
+            eyre!(
+                "{} --no-network failed to run 'unshare' (from util-linux). Error: {e}",
+                Paint::red("ERROR:").bold()
+            )
+        })?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{} --no-network can't isolate the test runs, 'unshare --map-root-user --net' failed: {}. Unprivileged user namespaces may be disabled on this machine.",
+            Paint::red("ERROR:").bold(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the runs profiled by `--flamegraph` can be recorded (see
+/// [`Toolchain::forge_under_perf`]): only on Linux, with `perf` installed and allowed to sample the
+/// call stacks of the user's processes (`kernel.perf_event_paranoid`).
+pub fn check_perf(runner: &dyn CommandRunner) -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        return Err(eyre!("profiling with perf is only supported on Linux"));
+    }
+    let output = runner
+        .output(Command::new("perf").args(["record", "-q", "-g", "-o", "/dev/null", "--", "true"]))
+        .map_err(|e| eyre!("failed to run 'perf' (is it installed?). Error: {e}"))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "'perf record -g' failed: {}. The kernel.perf_event_paranoid setting may not allow it.",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Variables of the benchmark's environment that its `forge` commands keep with `--clean-env`,
+/// besides the ones set on them.
+pub const CLEAN_ENV_ALLOWLIST: &[&str] = &["PATH", "HOME"];
+
+/// Clears the environment `cmd` inherits from the benchmark (`--clean-env`), keeping the
+/// [`CLEAN_ENV_ALLOWLIST`] variables and the `kept` ones, e.g. the env vars of a project, under the
+/// variables already set on `cmd`, e.g. those of the toolchain (see [`Toolchain::with_env`]).
+pub fn clear_env<'a>(cmd: &mut Command, kept: impl IntoIterator<Item = (&'a String, &'a String)>) {
+    let set: Vec<(OsString, Option<OsString>)> = cmd
+        .get_envs()
+        .map(|(key, value)| (key.to_owned(), value.map(OsStr::to_owned)))
+        .collect();
+    cmd.env_clear();
+    for key in CLEAN_ENV_ALLOWLIST {
+        if let Some(value) = env::var_os(key) {
+            cmd.env(key, value);
+        }
+    }
+    cmd.envs(kept);
+    for (key, value) in set {
+        if let Some(value) = value {
+            cmd.env(key, value);
+        }
+    }
+}
+
+/// The variables among `keys` (e.g. of the benchmark's environment) that change what forge does,
+/// e.g. `FOUNDRY_PROFILE` or `RUST_LOG`, which its commands only inherit without `--clean-env`.
+/// The benchmark's own `FOUNDRY_BENCHMARKS_*` variables aren't.
+pub fn foundry_vars(keys: impl IntoIterator<Item = OsString>) -> Vec<String> {
+    let mut vars: Vec<String> = keys
+        .into_iter()
+        .map(|key| key.to_string_lossy().into_owned())
+        .filter(|key| {
+            (key.starts_with("FOUNDRY_") || key.starts_with("DAPP_"))
+                && !key.starts_with("FOUNDRY_BENCHMARKS_")
+                && key != "FOUNDRY_DISABLE_NIGHTLY_WARNING"
+                || key == "ETH_RPC_URL"
+                || key == "RUST_LOG"
+        })
+        .collect();
+    vars.sort();
+    vars
+}
+
+fn foundryup_not_found(msg: &str) -> eyre::Report {
+    eyre!(
+        "{} {msg}. Install it (see https://getfoundry.sh) or pass its location with --foundryup-bin.",
+        Paint::red("ERROR:").bold()
+    )
+}
+
+/// Runs a `foundryup` command, failing if it can't be spawned or if it exits unsuccessfully.
+fn run_foundryup(
+    mut cmd: Command,
+    side: &str,
+    source: &Source,
+    runner: &dyn CommandRunner,
+) -> Result<()> {
+    let status = runner.status(&mut cmd).map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            return foundryup_not_found(&format!(
+                "Failed to run {}: binary not found",
+                cmd.get_program().to_string_lossy()
+            ));
+        }
+        eyre!(
+            "{} Failed to run 'foundryup' to install the {side} source ({}: {}). Error: {e}",
+            Paint::red("ERROR:").bold(),
+            source.ty(),
+            source.name()
+        )
+    })?;
+
+    if !status.success() {
+        return Err(eyre!(
+            "{} 'foundryup' failed to install the {side} source ({}: {}). Exited with: {status}",
+            Paint::red("ERROR:").bold(),
+            source.ty(),
+            source.name()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::runner::{Response, ScriptedRunner, SystemRunner, command_line};
+    use std::{collections::HashMap, fs, os::unix::fs::PermissionsExt};
+    use tempfile::tempdir;
+
+    /// Writes an executable shell script named `name` into `dir`.
+    fn fake_bin(dir: &Path, name: &str, script: &str) {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_install_failure() {
+        let branch = String::from("master");
+        let runner = ScriptedRunner::default().on("foundryup", [Response::fail(1, "", "")]);
+        let err = Toolchain::default()
+            .install(
+                Path::new("foundryup"),
+                "foundry-rs/foundry",
+                "baseline",
+                &Source::Branch(branch.clone()),
+                &runner,
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'foundryup' failed to install the baseline source (branch: master)")
+        );
+        assert_eq!(
+            runner.calls(),
+            ["foundryup -r foundry-rs/foundry -b master"]
+        );
+    }
+
+    #[test]
+    fn test_foundryup_args() {
+        let branch = String::from("master");
+        let cmd = Toolchain::default().foundryup(
+            Path::new("foundryup"),
+            "foundry-rs/foundry",
+            &Source::Branch(branch.clone()),
+        );
+
+        assert_eq!(cmd.get_program(), "foundryup");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, ["-r", "foundry-rs/foundry", "-b", "master"]);
+        assert_eq!(cmd.get_envs().count(), 0);
+    }
+
+    #[test]
+    fn test_isolated_foundryup_sets_foundry_dir() {
+        let version = String::from("stable");
+        let toolchain = Toolchain::isolated("/tmp/prefix");
+        let cmd = toolchain.foundryup(
+            Path::new("foundryup"),
+            "foundry-rs/foundry",
+            &Source::Version(version.clone()),
+        );
+
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(
+            envs,
+            [("FOUNDRY_DIR".as_ref(), Some("/tmp/prefix".as_ref()))]
+        );
+    }
+
+    #[test]
+    fn test_isolated_forge_resolves_from_prefix() {
+        let dir = tempdir().unwrap();
+        let toolchain = Toolchain::isolated(dir.path());
+        fs::create_dir_all(toolchain.bin_dir().unwrap()).unwrap();
+        fake_bin(
+            &toolchain.bin_dir().unwrap(),
+            "forge",
+            "echo isolated-forge",
+        );
+
+        let output = toolchain.forge().output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "isolated-forge"
+        );
+    }
+
+    #[test]
+    fn test_forge_env() {
+        let env = BTreeMap::from([("FOUNDRY_FUZZ_RUNS".to_string(), "1024".to_string())]);
+        let cmd = Toolchain::default().with_env(env).forge();
+
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(
+            envs,
+            [("FOUNDRY_FUZZ_RUNS".as_ref(), Some("1024".as_ref()))]
+        );
+    }
+
+    #[test]
+    fn test_forge_without_network() {
+        let env = BTreeMap::from([("FOUNDRY_FUZZ_RUNS".to_string(), "1024".to_string())]);
+        let mut cmd = Toolchain::isolated("/tmp/prefix")
+            .with_env(env)
+            .forge_without_network();
+        cmd.arg("test");
+        assert_eq!(
+            command_line(&cmd),
+            "unshare --map-root-user --net forge test"
+        );
+        let path = cmd
+            .get_envs()
+            .find(|(key, _)| *key == "PATH")
+            .and_then(|(_, value)| value)
+            .unwrap();
+        assert!(path.to_string_lossy().starts_with("/tmp/prefix/bin"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_check_network_isolation() {
+        let runner = ScriptedRunner::default();
+        check_network_isolation(&runner).unwrap();
+        assert_eq!(runner.calls(), ["unshare --map-root-user --net true"]);
+
+        let runner = ScriptedRunner::default().on(
+            "unshare",
+            [Response::fail(
+                1,
+                "",
+                "unshare: write failed /proc/self/uid_map: Operation not permitted\n",
+            )],
+        );
+        let msg = check_network_isolation(&runner).unwrap_err().to_string();
+        assert!(msg.contains("'unshare --map-root-user --net' failed: unshare: write failed /proc/self/uid_map: Operation not permitted."));
+    }
+
+    #[test]
+    fn test_forge_under_perf() {
+        let mut cmd =
+            Toolchain::isolated("/tmp/prefix").forge_under_perf(Path::new("/tmp/perf.data"));
+        cmd.arg("test");
+        assert_eq!(
+            command_line(&cmd),
+            "perf record -q -g -o /tmp/perf.data -- forge test"
+        );
+        assert!(cmd.get_envs().any(|(key, _)| key == "PATH"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_check_perf() {
+        let runner = ScriptedRunner::default();
+        check_perf(&runner).unwrap();
+        assert_eq!(runner.calls(), ["perf record -q -g -o /dev/null -- true"]);
+
+        let runner = ScriptedRunner::default().on(
+            "perf",
+            [Response::fail(
+                255,
+                "",
+                "Error: Access to performance monitoring and observability operations is limited.\n",
+            )],
+        );
+        let msg = check_perf(&runner).unwrap_err().to_string();
+        assert!(msg.contains("'perf record -g' failed: Error: Access to performance monitoring"));
+    }
+
+    #[test]
+    fn test_clear_env() {
+        let dir = tempdir().unwrap();
+        let env = BTreeMap::from([("FOUNDRY_FUZZ_RUNS".to_string(), "1024".to_string())]);
+        let toolchain = Toolchain::isolated(dir.path()).with_env(env);
+        fs::create_dir_all(toolchain.bin_dir().unwrap()).unwrap();
+        fake_bin(&toolchain.bin_dir().unwrap(), "forge", "env");
+
+        let project = HashMap::from([
+            ("FOUNDRY_FUZZ_RUNS".to_string(), "256".to_string()),
+            (
+                "MAINNET_RPC_URL".to_string(),
+                "https://rpc.example".to_string(),
+            ),
+        ]);
+        let mut cmd = toolchain.forge();
+        clear_env(&mut cmd, &project);
+        cmd.env("FOUNDRY_DISABLE_NIGHTLY_WARNING", "true");
+        let output = cmd.output().unwrap();
+        let vars: Vec<&str> = std::str::from_utf8(&output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.split_once('=').map(|(key, _)| key))
+            .collect();
+        // Set by cargo for the tests, and not inherited.
+        assert!(!vars.contains(&"CARGO_PKG_NAME"));
+        assert!(vars.contains(&"PATH"));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // The variables of the toolchain win over the project's, like over its `.env`.
+        assert!(stdout.contains("FOUNDRY_FUZZ_RUNS=1024\n"));
+        assert!(stdout.contains("MAINNET_RPC_URL=https://rpc.example\n"));
+        assert!(stdout.contains("FOUNDRY_DISABLE_NIGHTLY_WARNING=true\n"));
+    }
+
+    #[test]
+    fn test_foundry_vars() {
+        let keys = [
+            "PATH",
+            "FOUNDRY_PROFILE",
+            "RUST_LOG",
+            "FOUNDRY_BENCHMARKS_HISTORY",
+            "FOUNDRY_DISABLE_NIGHTLY_WARNING",
+            "DAPP_SOLC_VERSION",
+            "ETH_RPC_URL",
+            "RUSTFLAGS",
+        ];
+        assert_eq!(
+            foundry_vars(keys.map(OsString::from)),
+            [
+                "DAPP_SOLC_VERSION",
+                "ETH_RPC_URL",
+                "FOUNDRY_PROFILE",
+                "RUST_LOG"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forge_version_parse() {
+        let output = "forge Version: 1.2.3-stable\nCommit SHA: a1b2c3d4e5f6\nBuild Timestamp: 2025-06-08T15:28:15.093Z (1749396495)\nBuild Profile: maxperf\n";
+        let version = ForgeVersion::parse(output).unwrap();
+        assert_eq!(version.version, "1.2.3-stable");
+        assert_eq!(version.commit.as_deref(), Some("a1b2c3d4e5f6"));
+        assert_eq!(version.to_string(), "1.2.3-stable (a1b2c3d)");
+
+        let legacy = ForgeVersion::parse("forge 0.2.0 (5be158b 2024-01-01T00:00:00.000Z)").unwrap();
+        assert_eq!(legacy.version, "0.2.0");
+        assert_eq!(legacy.commit.as_deref(), Some("5be158b"));
+
+        assert!(ForgeVersion::parse("command not found").is_none());
+    }
+
+    #[test]
+    fn test_forge_version_release_tag() {
+        let v = |version: &str, commit: Option<&str>| ForgeVersion {
+            version: version.to_string(),
+            commit: commit.map(String::from),
+        };
+        let sha = "a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2";
+
+        let stable = v("1.2.3-stable", Some(sha));
+        assert_eq!(stable.release_tag().as_deref(), Some("v1.2.3"));
+        assert_eq!(stable.resolved(), "v1.2.3, a1b2c3d");
+        assert_eq!(v("0.2.0", None).release_tag().as_deref(), Some("v0.2.0"));
+
+        let nightly = v("1.3.0-nightly", Some(sha));
+        assert_eq!(nightly.release_tag(), Some(format!("nightly-{sha}")));
+        assert_eq!(nightly.resolved(), "1.3.0-nightly, a1b2c3d");
+        // Nightly tags need the full commit hash.
+        assert_eq!(v("1.3.0-nightly", Some("a1b2c3d")).release_tag(), None);
+        assert_eq!(v("1.3.0-dev", None).release_tag(), None);
+        assert_eq!(v("1.3.0-dev", None).resolved(), "1.3.0-dev");
+    }
+
+    #[test]
+    fn test_forge_version_same_build() {
+        let v = |version: &str, commit: Option<&str>| ForgeVersion {
+            version: version.to_string(),
+            commit: commit.map(String::from),
+        };
+
+        assert!(v("1.2.3-stable", Some("abc")).same_build(&v("1.2.3-nightly", Some("abc"))));
+        assert!(!v("1.2.3-stable", Some("abc")).same_build(&v("1.2.3-stable", Some("def"))));
+        assert!(v("1.2.3", None).same_build(&v("1.2.3", Some("abc"))));
+        assert!(!v("1.2.3", None).same_build(&v("1.2.4", None)));
+    }
+
+    #[test]
+    fn test_forge_version_requires_isolated_install() {
+        let dir = tempdir().unwrap();
+        let toolchain = Toolchain::isolated(dir.path());
+
+        // No `forge` in the prefix: must not fall back to the one in `PATH`.
+        let err = toolchain.forge_version().unwrap_err();
+        assert!(err.to_string().contains("was not installed into"));
+
+        fs::create_dir_all(toolchain.bin_dir().unwrap()).unwrap();
+        fake_bin(
+            &toolchain.bin_dir().unwrap(),
+            "forge",
+            "echo 'forge Version: 1.0.0-stable'; echo 'Commit SHA: deadbeef'",
+        );
+        let version = toolchain.forge_version().unwrap();
+        assert_eq!(version.version, "1.0.0-stable");
+        assert_eq!(version.commit.as_deref(), Some("deadbeef"));
+    }
+
+    #[test]
+    fn test_install_or_reuse_cached() {
+        let dir = tempdir().unwrap();
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        let toolchain = Toolchain::cached(dir.path(), commit);
+        let bin_dir = toolchain.bin_dir().unwrap();
+        assert_eq!(bin_dir, dir.path().join(CACHE_DIR).join(commit).join("bin"));
+        let source = Source::Branch("feat".to_string());
+        let install = |refresh, runner: &ScriptedRunner| {
+            toolchain.install_or_reuse(
+                Path::new("foundryup"),
+                "me/foundry",
+                "comparison",
+                &source,
+                refresh,
+                runner,
+            )
+        };
+
+        // Nothing cached yet: the commit is installed in place of the branch.
+        let runner = ScriptedRunner::default();
+        assert!(!install(false, &runner).unwrap());
+        assert_eq!(
+            runner.calls(),
+            [format!("foundryup -r me/foundry -C {commit}")]
+        );
+
+        // A working forge is reused.
+        fs::create_dir_all(&bin_dir).unwrap();
+        fake_bin(&bin_dir, "forge", "echo 'forge Version: 1.3.0-dev'");
+        let runner = ScriptedRunner::default();
+        assert!(install(false, &runner).unwrap());
+        assert!(runner.calls().is_empty());
+
+        // Unless refreshed, which starts over from an empty prefix.
+        let runner = ScriptedRunner::default();
+        assert!(!install(true, &runner).unwrap());
+        assert_eq!(runner.calls().len(), 1);
+        assert!(!bin_dir.exists());
+
+        // A broken forge is installed again, and a failed install leaves nothing behind.
+        fs::create_dir_all(&bin_dir).unwrap();
+        fake_bin(&bin_dir, "forge", "exit 1");
+        let runner = ScriptedRunner::default().on("foundryup", [Response::fail(1, "", "")]);
+        let err = install(false, &runner).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("the comparison source (branch: feat)")
+        );
+        assert!(!dir.path().join(CACHE_DIR).join(commit).exists());
+
+        // Toolchains that aren't cached are always installed.
+        let runner = ScriptedRunner::default();
+        let installed = Toolchain::isolated(dir.path().join("baseline")).install_or_reuse(
+            Path::new("foundryup"),
+            "me/foundry",
+            "baseline",
+            &source,
+            false,
+            &runner,
+        );
+        assert!(!installed.unwrap());
+        assert_eq!(runner.calls(), ["foundryup -r me/foundry -b feat"]);
+    }
+
+    #[test]
+    fn test_source_commit() {
+        let commit = "0123456789ABCDEF0123456789abcdef01234567";
+        assert_eq!(
+            source_commit("me/foundry", &Source::Commit(commit.to_string())).unwrap(),
+            Some(commit.to_lowercase())
+        );
+        // Abbreviated commits and versions aren't cached.
+        assert_eq!(
+            source_commit("me/foundry", &Source::Commit("0123abc".to_string())).unwrap(),
+            None
+        );
+        assert_eq!(
+            source_commit("me/foundry", &Source::Version("stable".to_string())).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_foundryup_failure_stops_the_run() {
+        let dir = tempdir().unwrap();
+        fake_bin(dir.path(), "foundryup", "exit 1");
+
+        let branch = String::from("does-not-exist");
+        let source = Source::Branch(branch.clone());
+        let mut cmd =
+            Toolchain::default().foundryup(Path::new("foundryup"), "foundry-rs/foundry", &source);
+        cmd.env("PATH", dir.path());
+
+        let err = run_foundryup(cmd, "comparison", &source, &SystemRunner).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("comparison source (branch: does-not-exist)"));
+        assert!(msg.contains("exit status: 1"));
+    }
+
+    #[test]
+    fn test_foundryup_success() {
+        let dir = tempdir().unwrap();
+        fake_bin(dir.path(), "foundryup", "exit 0");
+
+        let version = String::from("stable");
+        let source = Source::Version(version.clone());
+        let mut cmd =
+            Toolchain::default().foundryup(Path::new("foundryup"), "foundry-rs/foundry", &source);
+        cmd.env("PATH", dir.path());
+
+        assert!(run_foundryup(cmd, "baseline", &source, &SystemRunner).is_ok());
+    }
+
+    #[test]
+    fn test_foundryup_not_found() {
+        let dir = tempdir().unwrap();
+        let branch = String::from("master");
+        let source = Source::Branch(branch.clone());
+        let mut cmd =
+            Toolchain::default().foundryup(Path::new("foundryup"), "foundry-rs/foundry", &source);
+        cmd.env("PATH", dir.path());
+
+        let msg = run_foundryup(cmd, "baseline", &source, &SystemRunner)
+            .unwrap_err()
+            .to_string();
+        assert!(msg.contains("binary not found"));
+        assert!(msg.contains("--foundryup-bin"));
+    }
+
+    #[test]
+    fn test_resolve_foundryup() {
+        let dir = tempdir().unwrap();
+        fake_bin(dir.path(), "foundryup", "exit 0");
+        let bin = dir.path().join("foundryup");
+        let bin_str = bin.to_str().unwrap();
+        assert_eq!(resolve_foundryup(Some(bin_str)).unwrap(), bin);
+
+        fs::set_permissions(&bin, fs::Permissions::from_mode(0o644)).unwrap();
+        let err = resolve_foundryup(Some(bin_str)).unwrap_err();
+        assert!(err.to_string().contains("is not executable"));
+
+        let missing = dir.path().join("missing");
+        let err = resolve_foundryup(Some(missing.to_str().unwrap())).unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[test]
+    fn test_find_in_path() {
+        let empty = tempdir().unwrap();
+        let dir = tempdir().unwrap();
+        fake_bin(dir.path(), "foundryup", "exit 0");
+        let path = env::join_paths([empty.path(), dir.path()]).unwrap();
+
+        assert_eq!(
+            find_in_path("foundryup", &path, Platform::Unix),
+            Some(dir.path().join("foundryup"))
+        );
+        assert_eq!(
+            find_in_path("foundryup", empty.path().as_os_str(), Platform::Unix),
+            None
+        );
+
+        // Windows prefers the programs with an extension.
+        fake_bin(dir.path(), "foundryup.cmd", "exit 0");
+        assert_eq!(
+            find_in_path("foundryup", &path, Platform::Windows),
+            Some(dir.path().join("foundryup.cmd"))
+        );
+    }
+
+    #[test]
+    fn test_windows_commands() {
+        let command = |path: &str| {
+            let cmd = Platform::Windows.command(Path::new(path));
+            command_line(&cmd)
+        };
+        assert_eq!(command("foundryup"), "bash foundryup");
+        assert_eq!(command("foundryup.CMD"), "cmd /C foundryup.CMD");
+        assert_eq!(command("forge.exe"), "forge.exe");
+        assert_eq!(
+            Platform::Unix.command(Path::new("foundryup")).get_program(),
+            "foundryup"
+        );
+        assert_eq!(
+            command_line(&Platform::Windows.shell("rm -rf cache")),
+            "cmd /C rm -rf cache"
+        );
+        assert_eq!(
+            command_line(&Platform::Unix.shell("rm -rf cache")),
+            "sh -c rm -rf cache"
+        );
+
+        // The shell script is installed with the same arguments as on Unix.
+        let runner = ScriptedRunner::default();
+        Toolchain::default()
+            .with_platform(Platform::Windows)
+            .install(
+                Path::new("foundryup"),
+                "foundry-rs/foundry",
+                "baseline",
+                &Source::Version("stable".to_string()),
+                &runner,
+            )
+            .unwrap();
+        assert_eq!(
+            runner.calls(),
+            ["bash foundryup -r foundry-rs/foundry -v stable"]
+        );
+    }
+}
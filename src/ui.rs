@@ -1,45 +1,1103 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+};
 use yansi::Paint;
 
-use crate::Benchmarks;
+use crate::{
+    Benchmarks,
+    anvil::{self, AnvilBench},
+    benchmark::{Measured, SourceResults, Tested},
+    fingerprint,
+    gate::{BudgetCheck, GateReport},
+    history::{HistoryEntry, Trend, TrendPoint},
+    output_size, redact,
+    utils::{self, ConfigOrigin, ProjectConfig},
+    warnings,
+};
 
-/// Assumes `ref_benchmarks[i]` corresponds to `vs_benchmarks[i]`
+/// Level of the tool's own log messages, set with `--log-level`. Independent of the verbosity of
+/// the `forge test` commands (`-v`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LogLevel {
+    /// Only errors, and the results.
+    Error,
+    /// Warnings and the summaries of the skipped and failed projects too.
+    Warn,
+    /// The progress of the pipeline too.
+    #[default]
+    Info,
+    /// Every command run by the pipeline, and the details of its steps too.
+    Debug,
+}
+
+/// Log level of the run, see [`set_log_level`].
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether messages of `level` are printed at the log level of the run.
+pub fn logs(level: LogLevel) -> bool {
+    level as u8 <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Whether the full output of the failed commands is printed, see [`set_full_errors`].
+static FULL_ERRORS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_full_errors(full: bool) {
+    FULL_ERRORS.store(full, Ordering::Relaxed);
+}
+
+/// Whether to print the full output of the failed commands (`--full-errors`), rather than the
+/// summary of the failing tests of a failed `forge test`, or the last lines of the output of the
+/// other commands (see [`log_cmd_error`]).
+pub fn full_errors() -> bool {
+    FULL_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Number of lines of the output of a failed command printed by default, see [`set_error_lines`].
+pub const DEFAULT_ERROR_LINES: usize = 50;
+
+/// Number of trailing lines of the output of a failed command printed without `--full-errors`.
+static ERROR_LINES: AtomicUsize = AtomicUsize::new(DEFAULT_ERROR_LINES);
+
+pub fn set_error_lines(lines: usize) {
+    ERROR_LINES.store(lines, Ordering::Relaxed);
+}
+
+/// Terminal width assumed when stdout is a terminal of unknown size.
+const DEFAULT_TERMINAL_WIDTH: usize = 100;
+/// Widest a project or source name gets in a terminal table, longer ones are truncated.
+const MAX_NAME_WIDTH: usize = 40;
+/// Narrowest the project column gets when a terminal table doesn't fit the terminal.
+const MIN_PROJECT_WIDTH: usize = 12;
+
+/// Prints a table with the test times of every source, and the relative diff of each comparison
+/// source against the baseline, followed by the same table for the build times.
+///
+/// The tables are aligned to the width of the terminal if stdout is one, and printed as markdown
+/// otherwise, e.g. when piped to a file.
+///
+/// Projects are matched by name, so a project that failed with some of the sources is still
+/// reported (with "n/a" for the missing values).
 pub fn log_test_table(b: &Benchmarks) {
-    println!("\n## benchmarks `forge test {}`\n", b.verbosity);
+    if io::stdout().is_terminal() {
+        let width = console::Term::stdout()
+            .size_checked()
+            .map_or(DEFAULT_TERMINAL_WIDTH, |(_, columns)| columns as usize);
+        print!("{}", render_terminal(b, width));
+    } else {
+        print!("{}", render_markdown(b));
+    }
+}
 
-    println!(
-        "| Project | Before [{}]({}) | After [{}]({}) | Relative Diff |",
-        b.ref_source.name(),
-        b.ref_source.github_url(b.foundry_repo),
-        b.vs_source.name(),
-        b.vs_source.github_url(b.foundry_repo),
-    );
-    println!("|--------|----------|------|-----------|");
+/// Renders the tables printed by [`log_test_table`] as markdown.
+pub fn render_markdown(b: &Benchmarks) -> String {
+    render_tables(b, markdown_table, true)
+}
 
-    for (before_project, after_project) in b.ref_tests.iter().zip(b.vs_tests.iter()) {
-        let project_link = format!("[{}]({})", before_project.name, before_project.url);
+/// Renders the tables printed by [`log_test_table`] for a terminal `width` columns wide.
+pub fn render_terminal(b: &Benchmarks, width: usize) -> String {
+    render_tables(b, |b, table| terminal_table(b, table, width), false)
+}
 
-        let before_time = before_project.avg_test_time;
-        let after_time = after_project.avg_test_time;
+/// Renders the tables of `b` (see [`diff_tables`]), along with their notes, with `render_table`,
+/// followed by the build warnings of a diff (see [`warnings::render`]).
+fn render_tables(
+    b: &Benchmarks,
+    render_table: impl Fn(&Benchmarks, &DiffTable) -> String,
+    collapsible: bool,
+) -> String {
+    let mut out = String::new();
+    let table = DiffTable::new(b, Metric::Test);
+    let fmt = DiffTable::new(b, Metric::Fmt);
+    // Without any test time, i.e. with `--stop-after build`, only the build table is rendered.
+    let tested = table
+        .rows
+        .iter()
+        .any(|row| row.times.iter().any(Option::is_some));
+    // Without any project measuring `forge test`, only the `forge fmt` table is.
+    let only_fmt = table.rows.is_empty() && !fmt.rows.is_empty();
+    let build_only = build_only_projects(b);
+    if tested || (table.rows.is_empty() && !only_fmt && build_only.is_empty()) {
+        out.push_str(&format!("\n## benchmarks `forge test {}`\n\n", b.verbosity));
+        out.push_str(&render_table(b, &table));
+        out.push_str(&format!(
+            "\nnote: the reported times are the average of {} runs.\n",
+            b.num_runs
+        ));
+    }
+    let footnotes = [
+        table.noise_note(b.noise_threshold),
+        table.noisy_note(b.noisy_threshold),
+        b.timing_source_note(),
+        b.unpinned_fork_note(),
+        b.hooks_note(),
+        b.network_note(),
+    ];
+    let config_notes = fingerprint::notes(b);
+    for note in b
+        .notes
+        .iter()
+        .chain(footnotes.iter().flatten())
+        .chain(&config_notes)
+    {
+        out.push_str(&format!("note: {note}\n"));
+    }
+    if !tested && !table.rows.is_empty() {
+        out.push_str("note: `forge test` didn't run (--stop-after build).\n");
+    }
+    if !build_only.is_empty() {
+        out.push_str(&format!(
+            "note: only built, without test times: {}.\n",
+            build_only.join(", ")
+        ));
+    }
 
-        let overhead = if before_time == 0.0 {
-            if after_time == 0.0 {
-                0.0
-            } else {
-                f64::INFINITY
+    let table = DiffTable::new(b, Metric::Script);
+    if !table.rows.is_empty() {
+        out.push_str("\n## benchmarks `forge script`\n\n");
+        out.push_str(&render_table(b, &table));
+        out.push_str(&format!(
+            "\nnote: the reported times are the average of {} runs, after the test runs.\n",
+            b.num_runs
+        ));
+        let notes = [
+            table.noise_note(b.noise_threshold),
+            table.noisy_note(b.noisy_threshold),
+        ];
+        for note in notes.iter().flatten() {
+            out.push_str(&format!("note: {note}\n"));
+        }
+    }
+
+    let table = DiffTable::new(b, Metric::Discovery);
+    if !table.rows.is_empty() {
+        out.push_str("\n## benchmarks `forge test --list`\n\n");
+        out.push_str(&render_table(b, &table));
+        out.push_str(
+            "\nnote: the reported times are the average of a few runs, after the build; n/a if unsupported by forge.\n",
+        );
+    }
+
+    if !fmt.rows.is_empty() {
+        out.push_str("\n## benchmarks `forge fmt --check`\n\n");
+        out.push_str(&render_table(b, &fmt));
+        out.push_str(&format!(
+            "\nnote: the reported times are the average of {} runs, without building the projects.\n",
+            b.num_runs
+        ));
+        let notes = [
+            fmt.noise_note(b.noise_threshold),
+            fmt.noisy_note(b.noisy_threshold),
+        ];
+        for note in notes.iter().flatten() {
+            out.push_str(&format!("note: {note}\n"));
+        }
+    }
+
+    if !only_fmt {
+        out.push_str("\n## benchmarks `forge build`\n\n");
+        let table = DiffTable::new(b, Metric::Build);
+        out.push_str(&render_table(b, &table));
+        out.push_str("\nnote: the reported times are from a single clean build.\n");
+        if let Some(note) = table.noise_note(b.noise_threshold) {
+            out.push_str(&format!("note: {note}\n"));
+        }
+    }
+
+    let table = DiffTable::new(b, Metric::Incremental);
+    if !table.rows.is_empty() {
+        out.push_str("\n## benchmarks incremental `forge build`\n\n");
+        out.push_str(&render_table(b, &table));
+        out.push_str(&format!("\nnote: {INCREMENTAL_NOTE}\n"));
+        let notes = [
+            table.noise_note(b.noise_threshold),
+            table.noisy_note(b.noisy_threshold),
+        ];
+        for note in notes.iter().flatten() {
+            out.push_str(&format!("note: {note}\n"));
+        }
+    }
+    out.push_str(&output_size::render(b));
+    out.push_str(&warnings::render(b, collapsible));
+    if let Some(reproduction) = &b.reproduction {
+        out.push_str(&reproduction.footer());
+    }
+    out
+}
+
+/// Metric compared by a [`DiffTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Metric {
+    /// The average test time.
+    Test,
+    /// The average time of the project's `forge script` runs.
+    Script,
+    /// The average time of the test discovery with `forge test --list`, for the projects
+    /// measuring it.
+    Discovery,
+    /// The average time of `forge fmt --check`, for the projects measuring it.
+    Fmt,
+    /// The time of a clean build.
+    Build,
+    /// The average time of a `forge build` after a trivial edit of a source, for the projects
+    /// measuring it.
+    Incremental,
+}
+
+impl Metric {
+    /// Whether `tested` has this metric: the tests (unless only built) and builds of the projects
+    /// measuring `forge test`, the scripts of the projects with one, the test discovery,
+    /// incremental builds and `forge fmt` of the projects measuring them.
+    fn applies(self, tested: &Tested) -> bool {
+        match self {
+            Self::Test => tested.command == Measured::Test && !tested.build_only,
+            Self::Build => tested.command == Measured::Test,
+            Self::Script => tested.avg_script_time.is_some(),
+            Self::Discovery => tested.discovery_time.is_some(),
+            Self::Incremental => tested.incremental_build_time.is_some(),
+            Self::Fmt => tested.command == Measured::Fmt,
+        }
+    }
+
+    /// The time of `tested`, if measured: the tests don't run with `--stop-after build`.
+    fn time(self, tested: &Tested) -> Option<f64> {
+        if !self.applies(tested) {
+            return None;
+        }
+        match self {
+            Self::Test | Self::Fmt => tested.test_time(),
+            Self::Script => tested.avg_script_time,
+            Self::Discovery => tested.discovery_time,
+            Self::Build => Some(tested.build_time),
+            Self::Incremental => tested.incremental_build_time,
+        }
+    }
+
+    /// The time of every run of `tested`, empty for builds which run once and for the test
+    /// discovery, of which only the average is kept.
+    fn samples(self, tested: &Tested) -> &[f64] {
+        match self {
+            Self::Test | Self::Fmt => &tested.samples,
+            Self::Script => &tested.script_samples,
+            Self::Incremental => &tested.incremental_samples,
+            Self::Discovery | Self::Build => &[],
+        }
+    }
+}
+
+/// The projects of `b` that were only built, in order, see [`Tested::build_only`].
+fn build_only_projects(b: &Benchmarks) -> Vec<&str> {
+    project_rows(b)
+        .into_iter()
+        .filter(|project| {
+            b.results
+                .iter()
+                .flat_map(|r| r.tests.iter().find(|t| t.name == project.name))
+                .any(|tested| tested.build_only)
+        })
+        .map(|project| project.name.as_str())
+        .collect()
+}
+
+/// The tables of `b`, in the order they are rendered: the test times, the script times, the test
+/// discovery times, the `forge fmt` times, the build times and the incremental build times. The
+/// script, test discovery, `forge fmt` and incremental build tables are left out without any
+/// project measuring them, and the test and build tables without any project measuring
+/// `forge test` while some measure `forge fmt`. The test table is also left out when every
+/// project measuring `forge test` was only built.
+pub fn diff_tables(b: &Benchmarks) -> Vec<DiffTable<'_>> {
+    let tables: Vec<DiffTable<'_>> = [
+        Metric::Test,
+        Metric::Script,
+        Metric::Discovery,
+        Metric::Fmt,
+        Metric::Build,
+        Metric::Incremental,
+    ]
+    .into_iter()
+    .map(|metric| DiffTable::new(b, metric))
+    .collect();
+    let only_fmt = tables[0].rows.is_empty() && !tables[3].rows.is_empty();
+    let only_built = tables[0].rows.is_empty() && !build_only_projects(b).is_empty();
+    tables
+        .into_iter()
+        .filter(|table| match table.metric {
+            Metric::Test => !only_fmt && !only_built,
+            Metric::Build => !only_fmt,
+            Metric::Script | Metric::Discovery | Metric::Fmt | Metric::Incremental => {
+                !table.rows.is_empty()
+            }
+        })
+        .collect()
+}
+
+/// Times of a metric for every project and source, along with the relative diff of each
+/// comparison source against the baseline. Every output format is rendered from it.
+#[derive(Debug, Serialize)]
+pub struct DiffTable<'a> {
+    pub metric: Metric,
+    /// Every source, the first one being the baseline.
+    pub sources: Vec<TableSource<'a>>,
+    pub rows: Vec<TableRow<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableSource<'a> {
+    pub name: Cow<'a, str>,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableRow<'a> {
+    pub project: &'a str,
+    pub url: &'a str,
+    /// Time (in seconds) with every source, if the project succeeded with it.
+    pub times: Vec<Option<f64>>,
+    /// Relative diff (in %) of every comparison source, if both times are known.
+    pub diffs: Vec<Option<f64>>,
+    /// Noise floor (in %) of every relative diff, if known. Diffs within it are reported as ~0%.
+    pub noise: Vec<Option<f64>>,
+    /// Coefficient of variation (in %) of the runs with every source, if it ran at least twice.
+    pub cvs: Vec<Option<f64>>,
+    /// Whether the runs vary by more than [`Benchmarks::noisy_threshold`] with some source, so
+    /// that its diffs should be read with suspicion.
+    pub noisy: bool,
+}
+
+impl TableRow<'_> {
+    /// The formatted values of the row: the baseline time, followed by the time and the relative
+    /// diff of every comparison source. Every table renders these same values.
+    pub fn cells(&self) -> Vec<String> {
+        let mut cells = vec![fmt_time(self.times[0])];
+        for (after, diff) in self.times[1..].iter().zip(&self.diffs) {
+            cells.push(fmt_time(*after));
+            cells.push(diff.map_or_else(|| "n/a".to_string(), |diff| format!("{diff:.1}%")));
+        }
+        for (i, cell) in cells.iter_mut().skip(2).step_by(2).enumerate() {
+            if self.is_noise(i) {
+                *cell = NOISE_MARKER.to_string();
             }
+        }
+        cells
+    }
+
+    /// Whether the relative diff of the `i`-th comparison source is within its noise floor.
+    pub fn is_noise(&self, i: usize) -> bool {
+        matches!((self.diffs[i], self.noise[i]), (Some(diff), Some(noise)) if diff.abs() <= noise)
+    }
+
+    /// Name of the project, followed by the [`NOISY_MARKER`] if its runs are noisy.
+    pub fn project_name(&self) -> Cow<'_, str> {
+        if self.noisy {
+            Cow::Owned(format!("{} {NOISY_MARKER}", self.project))
         } else {
-            (after_time - before_time) / before_time * 100.0
-        };
+            Cow::Borrowed(self.project)
+        }
+    }
+}
+
+impl<'a> DiffTable<'a> {
+    pub fn new(b: &'a Benchmarks, metric: Metric) -> Self {
+        let sources = b
+            .results
+            .iter()
+            .map(|r| TableSource {
+                name: r.display_name(),
+                url: r.github_url(),
+            })
+            .collect();
+        let rows = project_rows(b)
+            .into_iter()
+            // Only the projects measuring `forge test` have test and build times, only the ones
+            // with a script have script times, and so on.
+            .filter(|project| {
+                b.results
+                    .iter()
+                    .flat_map(|r| r.tests.iter().find(|t| t.name == project.name))
+                    .any(|tested| metric.applies(tested))
+            })
+            .map(|project| {
+                let times: Vec<Option<f64>> = b
+                    .results
+                    .iter()
+                    .map(|r| find_time(&r.tests, &project.name, |t| metric.time(t)))
+                    .collect();
+                let diffs = times[1..]
+                    .iter()
+                    .map(|after| match (times[0], after) {
+                        (Some(before), Some(after)) => Some(relative_diff(before, *after)),
+                        _ => None,
+                    })
+                    .collect();
+                let samples: Vec<Option<&[f64]>> = b
+                    .results
+                    .iter()
+                    .map(|r| {
+                        let tested = r.tests.iter().find(|t| t.name == project.name)?;
+                        Some(metric.samples(tested))
+                    })
+                    .collect();
+                let noise = samples[1..]
+                    .iter()
+                    .map(|after| match (b.noise_threshold, metric) {
+                        (Some(threshold), _) => Some(threshold),
+                        // Builds run once, and only the average test discovery is kept, so their
+                        // spread is unknown.
+                        (None, Metric::Build | Metric::Discovery) => None,
+                        (
+                            None,
+                            Metric::Test | Metric::Script | Metric::Fmt | Metric::Incremental,
+                        ) => noise_floor(samples[0]?, (*after)?),
+                    })
+                    .collect();
+                let cvs: Vec<Option<f64>> = samples
+                    .iter()
+                    .map(|samples| coefficient_of_variation((*samples)?))
+                    .collect();
+                let noisy = cvs.iter().flatten().any(|cv| *cv > b.noisy_threshold);
+                TableRow {
+                    project: &project.name,
+                    url: &project.url,
+                    times,
+                    diffs,
+                    noise,
+                    cvs,
+                    noisy,
+                }
+            })
+            .collect();
+        Self {
+            metric,
+            sources,
+            rows,
+        }
+    }
+
+    /// Note explaining the [`NOISE_MARKER`], if some diff of the table is within the noise.
+    pub fn noise_note(&self, threshold: Option<f64>) -> Option<String> {
+        let marked = self
+            .rows
+            .iter()
+            .any(|row| (0..row.diffs.len()).any(|i| row.is_noise(i)));
+        if !marked {
+            return None;
+        }
+        Some(match threshold {
+            Some(threshold) => {
+                format!("{NOISE_MARKER} marks the diffs within {threshold}% (--noise-threshold).")
+            }
+            None => format!(
+                "{NOISE_MARKER} marks the diffs within the noise of the test runs, i.e. twice the \
+                 standard error estimated from their pooled coefficient of variation."
+            ),
+        })
+    }
+
+    /// Note counting the projects marked with the [`NOISY_MARKER`], with the coefficient of
+    /// variation of their runs with every source, if any.
+    pub fn noisy_note(&self, threshold: f64) -> Option<String> {
+        let noisy: Vec<String> = self
+            .rows
+            .iter()
+            .filter(|row| row.noisy)
+            .map(|row| {
+                let cvs: Vec<String> = row
+                    .cvs
+                    .iter()
+                    .map(|cv| cv.map_or_else(|| "n/a".to_string(), |cv| format!("{cv:.1}%")))
+                    .collect();
+                format!("{} (CV {})", row.project, cvs.join(" / "))
+            })
+            .collect();
+        if noisy.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{NOISY_MARKER} marks {} of {} projects, whose runs vary by more than {threshold}% with some source (coefficient of variation, --noisy-threshold): {}.",
+            noisy.len(),
+            self.rows.len(),
+            noisy.join(", ")
+        ))
+    }
+}
+
+/// Note of the incremental build tables.
+pub const INCREMENTAL_NOTE: &str = "the reported times are the average of a few `forge build` after the clean one, each after appending a comment to a source of the project, restored after every build; n/a if a build failed.";
+
+/// Reported instead of a relative diff within the noise.
+pub const NOISE_MARKER: &str = "~0%";
+
+/// Coefficient of variation (in %) of the runs of a project above which it's marked as noisy, by
+/// default.
+pub const DEFAULT_NOISY_THRESHOLD: f64 = 5.0;
+
+/// Appended to the name of a project whose runs are noisy, see [`TableRow::noisy`].
+pub const NOISY_MARKER: &str = "(noisy)";
+
+/// Notes the projects of `results` whose sources ran different numbers of tests, e.g. two
+/// revisions of a project whose suites differ, so that their test times don't compare like with
+/// like. `None` if every source ran the same tests, or didn't report them.
+pub fn test_count_note(results: &[SourceResults]) -> Option<String> {
+    let mut projects: Vec<&str> = Vec::new();
+    for tested in results.iter().flat_map(|r| &r.tests) {
+        if !projects.contains(&tested.name.as_str()) {
+            projects.push(&tested.name);
+        }
+    }
+    let differing: Vec<String> = projects
+        .into_iter()
+        .filter_map(|project| {
+            let counts: Vec<Option<usize>> = results
+                .iter()
+                .map(|r| r.tests.iter().find(|t| t.name == project)?.num_tests)
+                .collect();
+            let mut known = counts.iter().flatten();
+            let first = known.next()?;
+            known.any(|count| count != first).then(|| {
+                let counts: Vec<String> = counts
+                    .iter()
+                    .map(|count| count.map_or_else(|| "n/a".to_string(), |c| c.to_string()))
+                    .collect();
+                format!("{project} ({} tests)", counts.join(" / "))
+            })
+        })
+        .collect();
+    (!differing.is_empty()).then(|| {
+        format!(
+            "the sources ran different numbers of tests, their test times compare different suites: {}.",
+            differing.join(", ")
+        )
+    })
+}
+
+/// Coefficient of variation (in %) of `samples`: their standard deviation relative to their mean.
+///
+/// Returns `None` with fewer than two samples, or a non-positive mean.
+pub fn coefficient_of_variation(samples: &[f64]) -> Option<f64> {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    if samples.len() < 2 || mean <= 0.0 {
+        return None;
+    }
+    let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    Some(variance.sqrt() / mean * 100.0)
+}
+
+/// Noise floor (in %) of the relative diff between the means of the `before` and `after` samples:
+/// twice its standard error, estimated from the pooled coefficient of variation of the samples.
+///
+/// Returns `None` if either side has fewer than two samples, or a non-positive mean.
+pub fn noise_floor(before: &[f64], after: &[f64]) -> Option<f64> {
+    // Squared coefficient of variation, weighted by the degrees of freedom of the samples.
+    let weighted_cv2 = |samples: &[f64]| {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        if samples.len() < 2 || mean <= 0.0 {
+            return None;
+        }
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        Some(variance / mean.powi(2) * (n - 1.0))
+    };
+    let pooled_cv2 =
+        (weighted_cv2(before)? + weighted_cv2(after)?) / (before.len() + after.len() - 2) as f64;
+    let standard_error =
+        (pooled_cv2 * (1.0 / before.len() as f64 + 1.0 / after.len() as f64)).sqrt();
+    Some(2.0 * standard_error * 100.0)
+}
+
+/// Renders the rows of a comparison table as markdown.
+fn markdown_table(b: &Benchmarks, table: &DiffTable) -> String {
+    let baseline = b.baseline();
+    let mut header = format!(
+        "| Project | Before [{}]({}) |",
+        header_name(baseline),
+        table.sources[0].url,
+    );
+    let mut separator = String::from("|--------|----------|");
+    for (vs, source) in b.comparisons().iter().zip(&table.sources[1..]) {
+        header.push_str(&format!(
+            " After [{}]({}) | Relative Diff |",
+            header_name(vs),
+            source.url,
+        ));
+        separator.push_str("------|-----------|");
+    }
+    let mut out = format!("{header}\n{separator}\n");
+
+    for row in &table.rows {
+        out.push_str(&format!("| [{}]({}) |", row.project_name(), row.url));
+        for cell in row.cells() {
+            out.push_str(&format!(" {cell} |"));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Renders the rows of a comparison table aligned in columns, fitting in `width` columns if the
+/// project names can be truncated enough. Times and diffs are right-aligned.
+fn terminal_table(b: &Benchmarks, table: &DiffTable, width: usize) -> String {
+    let mut header = vec![
+        "Project".to_string(),
+        format!("Before {}", b.baseline().display_name()),
+    ];
+    for vs in b.comparisons() {
+        header.push(format!("After {}", vs.display_name()));
+        header.push("Diff".to_string());
+    }
+    let mut rows: Vec<Vec<String>> = vec![header];
+    for row in &table.rows {
+        rows.push(
+            std::iter::once(row.project_name().into_owned())
+                .chain(row.cells())
+                .collect(),
+        );
+    }
+    for row in &mut rows {
+        for cell in row.iter_mut() {
+            *cell = truncate(cell, MAX_NAME_WIDTH);
+        }
+    }
+
+    let mut widths = vec![0; rows[0].len()];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    // Shrinks the project column to fit, the other ones being as narrow as their values.
+    let others: usize = widths[1..].iter().map(|w| w + 2).sum();
+    widths[0] = widths[0].min(width.saturating_sub(others).max(MIN_PROJECT_WIDTH));
+
+    let mut out = String::new();
+    let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    for (i, row) in rows.iter().enumerate() {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (cell, width))| {
+                if column == 0 {
+                    format!("{:<width$}", truncate(cell, *width))
+                } else {
+                    format!("{cell:>width$}")
+                }
+            })
+            .collect();
+        out.push_str(line.join("  ").trim_end());
+        out.push('\n');
+        if i == 0 {
+            out.push_str(&separator.join("  "));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// `text`, truncated with an ellipsis if it's longer than `width` characters.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Name of a source in the table headers. User-provided labels are escaped, as they could
+/// otherwise break the table.
+fn header_name<'a>(results: &'a SourceResults) -> Cow<'a, str> {
+    match &results.label {
+        Some(label) => Cow::Owned(escape_markdown(label)),
+        None => results.display_name(),
+    }
+}
+
+/// Escapes the characters of `text` that have a meaning in markdown (link) tables.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Returns every benchmarked project, in the order in which they first appear across sources.
+fn project_rows(b: &Benchmarks) -> Vec<&Tested> {
+    let mut rows: Vec<&Tested> = Vec::new();
+    for tested in b.results.iter().flat_map(|r| r.tests.iter()) {
+        if !rows.iter().any(|row| row.name == tested.name) {
+            rows.push(tested);
+        }
+    }
+    rows
+}
+
+fn find_time(tests: &[Tested], name: &str, time: impl Fn(&Tested) -> Option<f64>) -> Option<f64> {
+    tests.iter().find(|t| t.name == name).and_then(time)
+}
+
+fn fmt_time(time: Option<f64>) -> String {
+    time.map_or_else(|| "n/a".to_string(), |t| format!("{t:.2}s"))
+}
+
+/// Renders the `anvil` benchmarks of every source (see [`anvil::bench`]) as a markdown table, with
+/// the relative diff of each comparison source against the first one, the baseline. The sources
+/// without any (e.g. a recorded baseline, or a failed benchmark) show "n/a".
+pub fn render_anvil_table(sources: &[(String, Option<AnvilBench>)], num_runs: usize) -> String {
+    let mut header = format!("| Metric | Before {} |", sources[0].0);
+    let mut separator = String::from("|--------|----------|");
+    for (name, _) in &sources[1..] {
+        header.push_str(&format!(" After {name} | Relative Diff |"));
+        separator.push_str("------|-----------|");
+    }
+    let mut out = format!("\n## benchmarks `anvil`\n\n{header}\n{separator}\n");
+    let txs = format!("{} transactions", anvil::BENCH_TXS);
+    for (metric, time) in [
+        ("startup", AnvilBench::avg_startup as fn(&AnvilBench) -> f64),
+        (&txs, AnvilBench::avg_txs),
+    ] {
+        let times: Vec<Option<f64>> = sources
+            .iter()
+            .map(|(_, bench)| bench.as_ref().map(time))
+            .collect();
+        out.push_str(&format!("| {metric} | {} |", fmt_time(times[0])));
+        for after in &times[1..] {
+            let diff = times[0]
+                .zip(*after)
+                .map(|(before, after)| relative_diff(before, after));
+            out.push_str(&format!(
+                " {} | {} |",
+                fmt_time(*after),
+                diff.map_or_else(|| "n/a".to_string(), |diff| format!("{diff:.1}%"))
+            ));
+        }
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "\nnote: the reported times are the average of {num_runs} runs, each with a fresh node.\n"
+    ));
+    out
+}
+
+/// Relative difference (in %) of `after` with respect to `before`.
+pub fn relative_diff(before: f64, after: f64) -> f64 {
+    if before == 0.0 {
+        if after == 0.0 { 0.0 } else { f64::INFINITY }
+    } else {
+        (after - before) / before * 100.0
+    }
+}
 
+/// The budget of a summary line, e.g. ` (budget: 10.00s)`, highlighted if it's `exceeded`, or
+/// nothing without any.
+pub fn budget_suffix(check: Option<&BudgetCheck>) -> String {
+    match check {
+        None => String::new(),
+        Some(check) if check.exceeded => {
+            let suffix = format!(
+                " (budget: {:.2}s, {:+.1}% OVER BUDGET)",
+                check.expected,
+                check.relative_diff()
+            );
+            Paint::red(&suffix).bold().to_string()
+        }
+        Some(check) => {
+            let suffix = format!(" (budget: {:.2}s)", check.expected);
+            Paint::dim(&suffix).to_string()
+        }
+    }
+}
+
+/// Prints the projects slower than their budget, as errors if the budgets are `enforced`.
+pub fn log_over_budget(over_budget: &[&BudgetCheck], enforced: bool) {
+    if over_budget.is_empty() {
+        return;
+    }
+    let prefix = if enforced {
+        Paint::red("ERROR:").bold()
+    } else {
+        Paint::yellow("WARNING:").bold()
+    };
+    eprintln!(
+        "{prefix} {} over budget{}:",
+        match over_budget.len() {
+            1 => "1 project is".to_string(),
+            n => format!("{n} projects are"),
+        },
+        if enforced { " (--enforce-budgets)" } else { "" }
+    );
+    for check in over_budget {
+        eprintln!(
+            " * {}: {} time {:.2}s, expected {:.2}s ({:+.1}%)",
+            check.project,
+            check.metric,
+            check.time,
+            check.expected,
+            check.relative_diff()
+        );
+    }
+}
+
+/// Prints the outcome of the regression gate.
+pub fn log_gate_report(report: &GateReport) {
+    println!("\n## regression gate\n");
+    if !report.excluded.is_empty() {
+        println!("excluded: {}", report.excluded.join(", "));
+    }
+    if report.passed() {
         println!(
-            "| {project_link} | {before_time:.2}s | {after_time:.2}s | {overhead:.1}% |"
+            "{} no regression above {}%.",
+            Paint::green("PASS:").bold(),
+            report.threshold
         );
+        return;
     }
 
     println!(
-        "\nnote: the reported times are the average of {} runs.",
-        b.ref_tests[0].runs
+        "{} {} regression(s) above {}%:",
+        Paint::red("FAIL:").bold(),
+        report.violations.len(),
+        report.threshold
     );
+    for violation in &report.violations {
+        println!(
+            " - {}: {} {:+.1}%",
+            violation.source,
+            violation.project.as_deref().unwrap_or("geometric mean"),
+            violation.relative_diff
+        );
+    }
+}
+
+/// Prints the resolved configuration of every project, along with where each field came from.
+///
+/// Env var values are never printed, only their keys.
+pub fn log_project_configs(projects: &[ProjectConfig]) {
+    for (i, project) in projects.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", Paint::cyan(&project.name).bold());
+        println!("  url:          {}", project.url());
+        if project.tags.is_empty() {
+            println!("  tags:         {}", Paint::dim("none"));
+        } else {
+            println!("  tags:         {}", project.tags.join(", "));
+        }
+        if !project.required_env.is_empty() {
+            println!("  required_env: {}", project.required_env.join(", "));
+        }
+        if !project.test_args.is_empty() {
+            println!("  test_args:    {}", project.test_args.join(" "));
+        }
+        if project.command != Measured::Test {
+            println!("  command:      {}", project.command.name());
+        }
+        if let Some(script) = &project.script {
+            println!(
+                "  script:       {}",
+                [script.as_str()]
+                    .into_iter()
+                    .chain(project.script_args.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+        }
+        if let Some(anvil) = &project.anvil {
+            let port = match anvil.port {
+                0 => "a free port".to_string(),
+                port => format!("port {port}"),
+            };
+            println!("  anvil:        forks ${} on {port}", anvil.fork_url_env);
+        }
+        if let Some(cache) = &project.rpc_cache {
+            println!("  rpc_cache:    caches ${}", cache.url_env);
+        }
+        if let Some(budget) = &project.budget {
+            let times: Vec<String> = [("build", budget.build_time), ("test", budget.test_time)]
+                .into_iter()
+                .filter_map(|(metric, time)| Some(format!("{metric} {:.2}s", time?)))
+                .collect();
+            println!(
+                "  budget:       {} (+{}%)",
+                times.join(", "),
+                budget.tolerance
+            );
+        }
+        log_config_list(
+            "dependencies",
+            project.dependencies(),
+            project.origins.dependencies,
+        );
+        log_config_list(
+            "remappings",
+            project.remappings(),
+            project.origins.remappings,
+        );
+        if project.origins.env_vars.is_empty() {
+            println!("  env_vars:     {}", Paint::dim("none"));
+        } else {
+            println!("  env_vars:");
+            for (key, origin) in &project.origins.env_vars {
+                println!("    - {key}=*** {}", Paint::dim(&format!("({origin})")));
+            }
+        }
+    }
+}
+
+fn log_config_list(field: &str, values: Option<&Vec<String>>, origin: Option<ConfigOrigin>) {
+    match (values, origin) {
+        (Some(values), Some(origin)) => {
+            println!("  {field}: {}", Paint::dim(&format!("({origin})")));
+            for value in values {
+                println!("    - {value}");
+            }
+        }
+        _ => println!("  {:<13} {}", format!("{field}:"), Paint::dim("none")),
+    }
+}
+
+/// Prints the recorded runs, followed by the test time progression of every project across them.
+pub fn log_history(entries: &[HistoryEntry]) {
+    println!("\n## recorded runs\n");
+    println!("| Date | Host | Sources | Runs | Projects |");
+    println!("|------|------|---------|------|----------|");
+    for entry in entries {
+        let sources: Vec<String> = entry
+            .sources
+            .iter()
+            .map(|s| match &s.forge_version {
+                Some(forge) if forge.version != s.name => {
+                    format!("{} ({forge})", escape_markdown(&s.name))
+                }
+                _ => escape_markdown(&s.name),
+            })
+            .collect();
+        let projects = entry.sources.iter().map(|s| s.projects.len()).max();
+        println!(
+            "| {} | {} ({}) | {} | {} | {} |",
+            utils::format_timestamp(entry.timestamp),
+            entry.hostname.as_deref().unwrap_or("unknown"),
+            entry.host_fingerprint,
+            sources.join(", "),
+            entry.num_runs,
+            projects.unwrap_or_default()
+        );
+    }
+
+    let mut projects: Vec<&str> = entries
+        .iter()
+        .flat_map(|e| &e.sources)
+        .flat_map(|s| &s.projects)
+        .map(|p| p.name.as_str())
+        .collect();
+    projects.sort_unstable();
+    projects.dedup();
+
+    for project in projects {
+        println!("\n## `{project}`\n");
+        println!("| Date | Source | Test Time | Build Time | Relative Diff |");
+        println!("|------|--------|-----------|------------|---------------|");
+        let mut previous = None;
+        for entry in entries {
+            for source in &entry.sources {
+                let Some(timing) = source.projects.iter().find(|p| p.name == project) else {
+                    continue;
+                };
+                let diff = match (previous, timing.avg_test_time) {
+                    (Some(before), Some(after)) => format!("{:.1}%", relative_diff(before, after)),
+                    _ => "-".to_string(),
+                };
+                println!(
+                    "| {} | {} | {} | {} | {diff} |",
+                    utils::format_timestamp(entry.timestamp),
+                    escape_markdown(&source.name),
+                    fmt_time(timing.avg_test_time),
+                    fmt_time(Some(timing.build_time)),
+                );
+                previous = timing.avg_test_time;
+            }
+        }
+    }
+    println!("\nnote: relative diffs are against the previous row.");
+}
+
+/// Prints a sparkline of the test time of every project, along with its oldest and newest
+/// points, and the change between them. The columns are padded so that the sparklines line up.
+pub fn log_trends(trends: &[Trend], runs: usize) {
+    println!("\n## test time trend over the last {runs} run(s)\n");
+    let point = |point: &TrendPoint| {
+        let forge = point
+            .forge
+            .map_or_else(|| "unknown".to_string(), |forge| forge.version.clone());
+        format!("{} ({forge})", fmt_time(Some(point.time)))
+    };
+    let rows: Vec<[String; 5]> = trends
+        .iter()
+        .map(|trend| {
+            [
+                trend.project.to_string(),
+                sparkline(&trend.points),
+                point(&trend.oldest),
+                point(&trend.newest),
+                format!(
+                    "{:+.1}%",
+                    relative_diff(trend.oldest.time, trend.newest.time)
+                ),
+            ]
+        })
+        .collect();
+
+    let header = ["Project", "Trend", "Oldest", "Newest", "Change"].map(str::to_string);
+    let mut widths = [0; 5];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| {
+                let padding = width - cell.chars().count();
+                format!("{cell}{}", " ".repeat(padding))
+            })
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+/// Renders `points` as a unicode sparkline, scaled between their minimum and maximum. Missing
+/// points are left blank.
+pub fn sparkline(points: &[Option<f64>]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let measured = points.iter().flatten();
+    let min = measured.clone().copied().fold(f64::INFINITY, f64::min);
+    let max = measured.copied().fold(f64::NEG_INFINITY, f64::max);
+    points
+        .iter()
+        .map(|point| match point {
+            None => ' ',
+            Some(_) if max <= min => BARS[0],
+            Some(value) => {
+                let level = (value - min) / (max - min) * (BARS.len() - 1) as f64;
+                BARS[level.round() as usize]
+            }
+        })
+        .collect()
 }
 
 const BASE_BANNER: &str =
@@ -61,21 +1119,624 @@ fn print_banner(text: Option<&str>, with_line_break: bool) {
 }
 
 pub fn banner(text: Option<&str>) {
-    print_banner(text, true);
+    if logs(LogLevel::Info) {
+        print_banner(text, true);
+    }
 }
 
 pub fn big_banner(text: &str) {
+    if !logs(LogLevel::Info) {
+        return;
+    }
     print_banner(None, true);
     println!("{}", Paint::new(text).bold());
     print_banner(None, false);
 }
 
-/// Helper function to print output errors from external commands.
-pub fn log_cmd_error(bytes: &[u8], msg: &str) {
-    eprintln!("{msg}");
+/// Helper function to log the failure of an external command exiting with `exit_code`, along with
+/// its output, with the secrets redacted. Only the last lines of the output are kept, unless
+/// `--full-errors` is set (see [`truncate_output`]).
+pub fn log_cmd_error(bytes: &[u8], exit_code: Option<i32>, msg: &str) {
+    let output = redact::text(&String::from_utf8_lossy(bytes));
+    let output = if full_errors() {
+        output
+    } else {
+        truncate_output(&output, ERROR_LINES.load(Ordering::Relaxed))
+    };
+    tracing::error!(exit_code, output, "{}", redact::text(msg));
+}
+
+/// Keeps the last `max_lines` lines of `output`, preceded by a notice of the omitted ones. Whole
+/// lines are kept, so an ANSI escape sequence, which never spans lines, is never split.
+pub fn truncate_output(output: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = output.split_inclusive('\n').collect();
+    if lines.len() <= max_lines {
+        return output.to_string();
+    }
+    let omitted = lines.len() - max_lines;
+    format!(
+        "… {omitted} earlier lines omitted, re-run with --full-errors to print them\n{}",
+        lines[omitted..].concat()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::{NetworkAccess, Source, TimingSource};
+
+    #[test]
+    fn test_terminal_table() {
+        let (stable, branch) = ("stable".to_string(), "feat".to_string());
+        let long = "an-organization/a-project-with-a-very-long-name-indeed";
+        let results = [
+            (
+                Source::Version(stable.clone()),
+                vec![Tested::fixture("a/a", 2.0), Tested::fixture(long, 10.0)],
+            ),
+            (
+                Source::Branch(branch.clone()),
+                vec![Tested::fixture("a/a", 1.0)],
+            ),
+        ]
+        .into_iter()
+        .map(|(source, tests)| SourceResults {
+            source,
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        })
+        .collect();
+        let b = Benchmarks {
+            verbosity: "-vv".to_string(),
+            num_runs: 1,
+            results,
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+        let table = DiffTable::new(&b, Metric::Test);
+
+        assert_eq!(
+            terminal_table(&b, &table, 100),
+            "Project                                   Before stable  After feat    Diff\n\
+             ----------------------------------------  -------------  ----------  ------\n\
+             a/a                                               2.00s       1.00s  -50.0%\n\
+             an-organization/a-project-with-a-very-l…         10.00s         n/a     n/a\n"
+        );
+        // The project column shrinks to fit narrow terminals.
+        let narrow = terminal_table(&b, &table, 50);
+        assert!(narrow.lines().all(|line| line.chars().count() <= 50));
+        assert!(narrow.contains("an-organizatio…"));
 
-    let content = String::from_utf8_lossy(bytes);
-    content
-        .lines()
-        .for_each(|line| eprintln!("{}", Paint::red(line).dim()));
+        // The markdown table renders the same values.
+        let markdown = markdown_table(&b, &table);
+        assert!(markdown.contains("| [a/a](https://github.com/a/a) | 2.00s | 1.00s | -50.0% |"));
+        assert!(
+            render_terminal(&b, 100)
+                .contains("note: the reported times are the average of 1 runs.")
+        );
+    }
+
+    #[test]
+    fn test_fmt_tables() {
+        let fmt = |name: &str, avg_test_time: f64| Tested {
+            build_time: 0.0,
+            command: Measured::Fmt,
+            ..Tested::fixture(name, avg_test_time)
+        };
+        let results = [
+            vec![fmt("a/a", 2.0), Tested::fixture("b/b", 1.0)],
+            vec![fmt("a/a", 1.0), Tested::fixture("b/b", 1.0)],
+        ]
+        .into_iter()
+        .map(|tests| SourceResults {
+            source: Source::Version("stable".to_string()),
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        })
+        .collect();
+        let mut b = Benchmarks {
+            verbosity: "-vv".to_string(),
+            num_runs: 1,
+            results,
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+        // The projects measuring `forge fmt` only have a row in its table.
+        let metrics: Vec<_> = diff_tables(&b)
+            .iter()
+            .map(|table| (table.metric, table.rows.len()))
+            .collect();
+        assert_eq!(
+            metrics,
+            [(Metric::Test, 1), (Metric::Fmt, 1), (Metric::Build, 1)]
+        );
+        let markdown = render_markdown(&b);
+        assert!(markdown.contains("## benchmarks `forge fmt --check`"));
+        assert!(markdown.contains("| [a/a](https://github.com/a/a) | 2.00s | 1.00s | -50.0% |"));
+        assert!(markdown.contains(
+            "note: the reported times are the average of 1 runs, without building the projects."
+        ));
+
+        // Without any project measuring `forge test`, only the `forge fmt` table is rendered.
+        for results in &mut b.results {
+            results.tests.truncate(1);
+        }
+        let markdown = render_markdown(&b);
+        assert!(markdown.contains("## benchmarks `forge fmt --check`"));
+        assert!(!markdown.contains("## benchmarks `forge test"));
+        assert!(!markdown.contains("## benchmarks `forge build`"));
+        assert_eq!(diff_tables(&b).len(), 1);
+    }
+
+    #[test]
+    fn test_unpinned_fork_note() {
+        let unpinned = |name: &str| Tested {
+            unpinned_fork: true,
+            ..Tested::fixture(name, 1.0)
+        };
+        let results = [
+            vec![unpinned("b/b"), Tested::fixture("a/a", 1.0)],
+            vec![unpinned("b/b"), unpinned("a/a")],
+        ]
+        .into_iter()
+        .map(|tests| SourceResults {
+            source: Source::Version("stable".to_string()),
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        })
+        .collect();
+        let mut b = Benchmarks {
+            verbosity: "-vv".to_string(),
+            num_runs: 1,
+            results,
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+        assert!(render_markdown(&b).contains(
+            "note: unpinned fork: a/a, b/b forked the latest block, so the compared workloads may \
+             differ.\n"
+        ));
+
+        for results in &mut b.results {
+            results.tests[0].unpinned_fork = false;
+            results.tests[1].unpinned_fork = false;
+        }
+        assert_eq!(b.unpinned_fork_note(), None);
+        assert!(!render_markdown(&b).contains("unpinned fork"));
+    }
+
+    #[test]
+    fn test_hooks_note() {
+        let hooked = |name: &str, prepare: Option<&str>, conclude: Option<&str>| Tested {
+            prepare: prepare.map(String::from),
+            conclude: conclude.map(String::from),
+            ..Tested::fixture(name, 1.0)
+        };
+        let mut b = Benchmarks {
+            verbosity: "-vv".to_string(),
+            num_runs: 1,
+            results: vec![SourceResults {
+                source: Source::Version("stable".to_string()),
+                foundry_repo: "foundry-rs/foundry".to_string(),
+                label: None,
+                forge: None,
+                tests: vec![Tested::fixture("a/a", 1.0)],
+            }],
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+        assert_eq!(b.hooks_note(), None);
+
+        b.results[0].tests = vec![
+            hooked("a/a", Some("sync"), None),
+            hooked("b/b", Some("sync"), Some("rm -rf cache")),
+            hooked("c/c", Some("sync"), None),
+            Tested::fixture("d/d", 1.0),
+        ];
+        assert_eq!(
+            b.hooks_note().unwrap(),
+            "a/a, c/c ran `sync` before (--prepare) every test run; b/b ran `sync` before \
+             (--prepare) and `rm -rf cache` after (--conclude) every test run, excluded from the \
+             times."
+        );
+        assert!(render_markdown(&b).contains("note: a/a, c/c ran `sync` before"));
+    }
+
+    #[test]
+    fn test_network_note() {
+        let with_network = |name: &str, network| Tested {
+            network,
+            ..Tested::fixture(name, 1.0)
+        };
+        let mut b = Benchmarks {
+            verbosity: "-vv".to_string(),
+            num_runs: 1,
+            results: vec![SourceResults {
+                source: Source::Version("stable".to_string()),
+                foundry_repo: "foundry-rs/foundry".to_string(),
+                label: None,
+                forge: None,
+                tests: vec![Tested::fixture("a/a", 1.0)],
+            }],
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+        assert_eq!(b.network_note(), None);
+
+        b.results[0].tests = vec![
+            with_network("a/a", NetworkAccess::Isolated),
+            with_network("b/b", NetworkAccess::Isolated),
+        ];
+        assert_eq!(
+            b.network_note().unwrap(),
+            "the timed test runs had no network access (--no-network)."
+        );
+
+        b.results[0]
+            .tests
+            .push(with_network("c/c", NetworkAccess::Exempted));
+        assert_eq!(
+            b.network_note().unwrap(),
+            "the timed test runs had no network access (--no-network), except those of c/c, which \
+             need the network."
+        );
+        assert!(render_markdown(&b).contains("except those of c/c"));
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        let output = "\x1b[31merror[2314]\x1b[0m: first\nsecond\n\x1b[1mthird\x1b[0m\n";
+        assert_eq!(truncate_output(output, 3), output);
+        assert_eq!(
+            truncate_output(output, 2),
+            "… 1 earlier lines omitted, re-run with --full-errors to print them\nsecond\n\x1b[1mthird\x1b[0m\n"
+        );
+        // The last line is kept whole even without a trailing newline.
+        assert_eq!(
+            truncate_output("a\nb\x1b[0m", 1),
+            "… 1 earlier lines omitted, re-run with --full-errors to print them\nb\x1b[0m"
+        );
+        assert_eq!(truncate_output("", 0), "");
+    }
+
+    #[test]
+    fn test_timing_source_note() {
+        let mut b = Benchmarks {
+            verbosity: "-vv".to_string(),
+            num_runs: 1,
+            results: vec![SourceResults {
+                source: Source::Version("stable".to_string()),
+                foundry_repo: "foundry-rs/foundry".to_string(),
+                label: None,
+                forge: None,
+                tests: vec![Tested::fixture("a/a", 1.0)],
+            }],
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+        assert_eq!(b.timing_source_note(), None);
+        assert!(!render_markdown(&b).contains("timing source"));
+
+        b.results[0].tests[0].timing_source = TimingSource::Forge;
+        assert!(render_markdown(&b).contains(
+            "note: timing source: the test times are the durations reported by forge for running \
+             the test suites (--timing-source forge), without its startup.\n"
+        ));
+    }
+
+    #[test]
+    fn test_build_only_tables() {
+        let untested = |name: &str| Tested {
+            runs: 0,
+            samples: Vec::new(),
+            ..Tested::fixture(name, 0.0)
+        };
+        let results = [
+            Source::Version("stable".to_string()),
+            Source::Branch("feat".to_string()),
+        ]
+        .into_iter()
+        .map(|source| SourceResults {
+            source,
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests: vec![untested("a/a")],
+        })
+        .collect();
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 2,
+            results,
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+
+        // Only the build times are compared after `--stop-after build`.
+        let markdown = render_markdown(&b);
+        assert!(!markdown.contains("## benchmarks `forge test"));
+        assert!(markdown.contains("note: `forge test` didn't run (--stop-after build)."));
+        assert!(markdown.contains("| [a/a](https://github.com/a/a) | 1.00s | 1.00s | 0.0% |"));
+    }
+
+    #[test]
+    fn test_build_only_projects() {
+        let built = |name: &str| Tested {
+            runs: 0,
+            samples: Vec::new(),
+            build_only: true,
+            ..Tested::fixture(name, 0.0)
+        };
+        let benchmarks = |tests: Vec<Tested>| Benchmarks {
+            verbosity: String::new(),
+            num_runs: 1,
+            results: [
+                Source::Version("stable".to_string()),
+                Source::Branch("feat".to_string()),
+            ]
+            .into_iter()
+            .map(|source| SourceResults {
+                source,
+                foundry_repo: "foundry-rs/foundry".to_string(),
+                label: None,
+                forge: None,
+                tests: tests.clone(),
+            })
+            .collect(),
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+
+        // The build-only projects are only compared in the build table.
+        let b = benchmarks(vec![Tested::fixture("a/a", 2.0), built("b/b")]);
+        let tables = diff_tables(&b);
+        assert_eq!(tables[0].metric, Metric::Test);
+        assert_eq!(tables[0].rows.len(), 1);
+        let build = tables.iter().find(|t| t.metric == Metric::Build).unwrap();
+        assert_eq!(build.rows.len(), 2);
+        let markdown = render_markdown(&b);
+        assert!(markdown.contains("note: only built, without test times: b/b.\n"));
+        assert!(!markdown.contains("--stop-after build"));
+
+        // Without any tested project, there is no test table.
+        let b = benchmarks(vec![built("b/b")]);
+        assert!(!render_markdown(&b).contains("## benchmarks `forge test"));
+        assert!(diff_tables(&b).iter().all(|t| t.metric != Metric::Test));
+    }
+
+    #[test]
+    fn test_noise_floor() {
+        // Identical samples have no noise.
+        assert_eq!(noise_floor(&[2.0, 2.0], &[1.0, 1.0]), Some(0.0));
+        // The same relative spread on both sides (a squared coefficient of variation of 0.02),
+        // over 2 + 2 runs.
+        let floor = noise_floor(&[0.9, 1.1], &[1.8, 2.2]).unwrap();
+        assert!((floor - 2.0 * 0.02f64.sqrt() * 100.0).abs() < 1e-9);
+        assert_eq!(noise_floor(&[1.0], &[1.0, 2.0]), None);
+        assert_eq!(noise_floor(&[0.0, 0.0], &[1.0, 2.0]), None);
+    }
+
+    #[test]
+    fn test_noise_marker() {
+        let (stable, branch) = ("stable".to_string(), "feat".to_string());
+        let sample = |name: &str, samples: Vec<f64>| Tested {
+            avg_test_time: samples.iter().sum::<f64>() / samples.len() as f64,
+            samples,
+            ..Tested::fixture(name, 0.0)
+        };
+        let results = [
+            (
+                Source::Version(stable.clone()),
+                vec![
+                    sample("noisy/a", vec![1.0, 1.4, 0.6]),
+                    sample("stable/b", vec![1.0, 1.01, 0.99]),
+                ],
+            ),
+            (
+                Source::Branch(branch.clone()),
+                vec![
+                    sample("noisy/a", vec![0.9, 1.3, 0.5]),
+                    sample("stable/b", vec![0.9, 0.91, 0.89]),
+                ],
+            ),
+        ]
+        .into_iter()
+        .map(|(source, tests)| SourceResults {
+            source,
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        })
+        .collect();
+        let mut b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 3,
+            results,
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+
+        // The 10% diff of the noisy project is within its noise, the one of the other isn't.
+        let markdown = render_markdown(&b);
+        assert!(markdown.contains("| 1.00s | 0.90s | ~0% |"), "{markdown}");
+        assert!(
+            markdown.contains("| 1.00s | 0.90s | -10.0% |"),
+            "{markdown}"
+        );
+        assert!(markdown.contains("note: ~0% marks the diffs within the noise of the test runs"));
+        assert!(render_terminal(&b, 100).contains("~0%"));
+
+        // A fixed threshold applies to every project, and to the build times.
+        b.noise_threshold = Some(15.0);
+        let markdown = render_markdown(&b);
+        assert!(!markdown.contains("-10.0%"));
+        assert!(markdown.contains("note: ~0% marks the diffs within 15% (--noise-threshold)."));
+        b.noise_threshold = Some(5.0);
+        assert_eq!(render_markdown(&b).matches("-10.0%").count(), 2);
+    }
+
+    #[test]
+    fn test_noisy_marker() {
+        assert_eq!(coefficient_of_variation(&[1.0]), None);
+        assert_eq!(coefficient_of_variation(&[0.0, 0.0]), None);
+        assert!((coefficient_of_variation(&[1.0, 1.4, 0.6]).unwrap() - 40.0).abs() < 1e-9);
+
+        let sample = |name: &str, samples: Vec<f64>| Tested {
+            avg_test_time: samples.iter().sum::<f64>() / samples.len() as f64,
+            samples,
+            ..Tested::fixture(name, 0.0)
+        };
+        let results = [
+            (
+                Source::Version("stable".to_string()),
+                vec![
+                    sample("noisy/a", vec![1.0, 1.4, 0.6]),
+                    sample("stable/b", vec![1.0, 1.01, 0.99]),
+                ],
+            ),
+            (
+                Source::Branch("feat".to_string()),
+                vec![
+                    sample("noisy/a", vec![0.9, 1.3, 0.5]),
+                    sample("stable/b", vec![0.9, 0.91, 0.89]),
+                ],
+            ),
+        ]
+        .into_iter()
+        .map(|(source, tests)| SourceResults {
+            source,
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        })
+        .collect();
+        let mut b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 3,
+            results,
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: DEFAULT_NOISY_THRESHOLD,
+        };
+
+        let table = DiffTable::new(&b, Metric::Test);
+        assert!(table.rows[0].noisy && !table.rows[1].noisy);
+        let markdown = render_markdown(&b);
+        assert!(markdown.contains("| [noisy/a (noisy)](https://github.com/noisy/a) |"));
+        assert!(markdown.contains("| [stable/b](https://github.com/stable/b) |"));
+        assert!(markdown.contains(
+            "note: (noisy) marks 1 of 2 projects, whose runs vary by more than 5% with some source (coefficient of variation, --noisy-threshold): noisy/a (CV 40.0% / 44.4%).\n"
+        ));
+        assert!(render_terminal(&b, 100).contains("noisy/a (noisy)"));
+        // The build times, measured once, are never noisy.
+        assert!(!DiffTable::new(&b, Metric::Build).rows[0].noisy);
+
+        b.noisy_threshold = 50.0;
+        assert!(!render_markdown(&b).contains("(noisy)"));
+    }
+
+    #[test]
+    fn test_test_count_note() {
+        let counted = |name: &str, num_tests| Tested {
+            num_tests,
+            ..Tested::fixture(name, 1.0)
+        };
+        let results: Vec<SourceResults> = [
+            vec![
+                counted("a/a", Some(12)),
+                counted("b/b", Some(3)),
+                counted("c/c", None),
+            ],
+            vec![
+                counted("a/a", Some(15)),
+                counted("b/b", Some(3)),
+                counted("c/c", Some(4)),
+            ],
+        ]
+        .into_iter()
+        .map(|tests| SourceResults {
+            source: Source::Current { forge: None },
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        })
+        .collect();
+        assert_eq!(
+            test_count_note(&results).unwrap(),
+            "the sources ran different numbers of tests, their test times compare different suites: a/a (12 / 15 tests)."
+        );
+        assert_eq!(test_count_note(&results[..1]), None);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("a/a", 3), "a/a");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+    }
+
+    #[test]
+    fn test_escape_markdown() {
+        assert_eq!(escape_markdown("feat/perf-opt"), "feat/perf-opt");
+        assert_eq!(
+            escape_markdown("a|b [c]_*`<x>`\\"),
+            "a\\|b \\[c\\]\\_\\*\\`\\<x\\>\\`\\\\"
+        );
+    }
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[Some(1.0), Some(2.0), None, Some(8.0)]), "▁▂ █");
+        assert_eq!(sparkline(&[Some(3.0), Some(3.0)]), "▁▁");
+        assert_eq!(sparkline(&[None, Some(3.0)]), " ▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_render_anvil_table() {
+        let bench = |startup: f64, txs: f64| AnvilBench {
+            startup: vec![startup, startup],
+            txs: vec![txs],
+        };
+        let sources = [
+            ("stable".to_string(), Some(bench(0.5, 2.0))),
+            ("feat".to_string(), Some(bench(0.25, 2.0))),
+            ("nightly".to_string(), None),
+        ];
+        assert_eq!(
+            render_anvil_table(&sources, 2),
+            "\n## benchmarks `anvil`\n\n\
+             | Metric | Before stable | After feat | Relative Diff | After nightly | Relative Diff |\n\
+             |--------|----------|------|-----------|------|-----------|\n\
+             | startup | 0.50s | 0.25s | -50.0% | n/a | n/a |\n\
+             | 100 transactions | 2.00s | 2.00s | 0.0% | n/a | n/a |\n\
+             \nnote: the reported times are the average of 2 runs, each with a fresh node.\n"
+        );
+    }
 }
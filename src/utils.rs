@@ -1,18 +1,448 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::RwLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    benchmark::{Measured, NetworkAccess, TimingSource},
+    config,
+};
+
+/// GitHub instance the `owner/repo` names refer to, unless another one is set with
+/// [`set_github_url`].
 pub const GITHUB_URL: &str = "https://github.com";
 
+/// GitHub instance of the run, see [`set_github_url`].
+static GITHUB_BASE_URL: RwLock<Option<String>> = RwLock::new(None);
+
+/// Sets the GitHub instance the `owner/repo` names of the projects and Foundry repositories are
+/// cloned from and linked to, e.g. a GitHub Enterprise server (`--github-url`, or `github_base_url`
+/// in `[defaults]`). `None` restores [`GITHUB_URL`].
+pub fn set_github_url(url: Option<String>) {
+    *GITHUB_BASE_URL.write().unwrap() = url;
+}
+
+/// Base URL of the GitHub instance of the run, see [`set_github_url`].
+pub fn github_url() -> String {
+    GITHUB_BASE_URL
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| GITHUB_URL.to_string())
+}
+
+/// Validates a GitHub base URL: an absolute `https` URL without a trailing slash, e.g.
+/// `https://github.example.com`.
+pub fn parse_github_url(url: &str) -> Result<String, String> {
+    let Some(rest) = url.strip_prefix("https://") else {
+        return Err(format!(
+            "{url} isn't an absolute https URL, e.g. https://github.example.com"
+        ));
+    };
+    let host = rest.split('/').next().unwrap_or_default();
+    if host.is_empty() || url.contains(char::is_whitespace) || url.contains(['?', '#']) {
+        return Err(format!(
+            "{url} isn't an absolute https URL, e.g. https://github.example.com"
+        ));
+    }
+    if url.ends_with('/') {
+        return Err(format!("{url} must not end with a slash"));
+    }
+    Ok(url.to_string())
+}
+
+/// Returns the directory where the tool keeps its work files (e.g. isolated toolchain prefixes),
+/// creating it if needed.
+pub fn work_dir() -> io::Result<PathBuf> {
+    let dir = work_dir_path();
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Returns the path of the work directory, without creating it.
+pub fn work_dir_path() -> PathBuf {
+    std::env::temp_dir().join("foundry-benchmarks")
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Formats a Unix timestamp as a UTC date and time (e.g. `2025-06-16 12:30:00 UTC`).
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let (hh, mm, ss) = (secs % 86_400 / 3600, secs % 3600 / 60, secs % 60);
+
+    // Converts days since the epoch into a civil date. See:
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02} {hh:02}:{mm:02}:{ss:02} UTC")
+}
+
+/// Parses a `YYYY-MM-DD` date into the Unix timestamp of its midnight (UTC).
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Inverse of `format_timestamp`. See:
+    // https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    u64::try_from(days * 86_400).ok()
+}
+
 /// Represents the configuration for a benchmarkable project.
-#[derive(Debug, Clone)]
+///
+/// Built by [`ConfigFile::into_project_configs`](crate::ConfigFile::into_project_configs) for the
+/// projects of a config file, or in code:
+///
+/// ```
+/// use foundry_benchmarks::ProjectConfig;
+///
+/// let project = ProjectConfig::new("vectorized/solady")
+///     .with_deps(vec!["install", "foundry-rs/forge-std"])
+///     .with_env_vars(vec!["FOUNDRY_FUZZ_RUNS"], vec!["256"]);
+/// assert_eq!(project.url(), "https://github.com/vectorized/solady");
+/// assert_eq!(project.env_vars().unwrap()["FOUNDRY_FUZZ_RUNS"], "256");
+/// ```
+#[derive(Debug, Clone, Serialize)]
 pub struct ProjectConfig {
+    /// Name of the project: an `owner/repo` GitHub repository, or the URL of any git repository.
     pub name: String,
+    #[serde(flatten)]
     pub config: JsonProjectConfig,
+    /// Where each field of `config` was resolved from.
+    pub origins: ConfigOrigins,
+    /// Tags of the project's `[[project]]` entry, selected with `--tag`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Environment variables the project requires, checked before the run starts.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub required_env: Vec<String>,
+    /// Extra arguments of its `forge test` commands, set by `--preset`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub test_args: Vec<String>,
+    /// Local `anvil` node its fork tests run against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anvil: Option<AnvilConfig>,
+    /// Local proxy caching the RPC requests of its fork tests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_cache: Option<RpcCacheConfig>,
+    /// Command timed by its test stage, `forge test` unless set to `fmt` by its entry or by
+    /// `--command`.
+    #[serde(skip_serializing_if = "Measured::is_test")]
+    pub command: Measured,
+    /// Solidity script timed with `forge script` after its test runs, e.g. `script/Deploy.s.sol`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    /// Extra arguments of its `forge script` commands.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub script_args: Vec<String>,
+    /// Parameters of the project generated instead of cloned, see [`SyntheticConfig`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub synthetic: Option<SyntheticConfig>,
+    /// Times the project is expected to take, checked after a plain run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<Budget>,
+    /// Whether the project is only built, without any test or script run, set by its entry or by
+    /// `--build-only`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub build_only: bool,
+    /// Whether its test discovery is timed with `forge test --list` after the build, set by
+    /// `--measure-discovery`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub measure_discovery: bool,
+    /// Number of incremental builds timed after its build, set by `--incremental`. `None`
+    /// if they aren't measured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incremental_runs: Option<NonZeroUsize>,
+    /// Source edited before every incremental build, relative to the project, e.g. `src/Pool.sol`.
+    /// Defaults to the first Solidity file of its `src` directory, in path order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incremental_file: Option<String>,
+    /// Shell command run in the project directory before every timed test run, e.g. to drop the
+    /// OS caches, set by its entry or by `--prepare`. Its time isn't measured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prepare: Option<String>,
+    /// Shell command run in the project directory after every timed test run, set by its entry or
+    /// by `--conclude`. Its time isn't measured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclude: Option<String>,
+    /// Whether its configured remappings are merged into its `remappings.txt`, or replace it.
+    #[serde(skip_serializing_if = "RemappingsMode::is_merge")]
+    pub remappings_mode: RemappingsMode,
+    /// Clock its test runs are timed with, set by `--timing-source`.
+    #[serde(default, skip_serializing_if = "TimingSource::is_process")]
+    pub timing_source: TimingSource,
+    /// Number of threads its tests run on, set by `--forge-threads`. Defaults to forge's, one per
+    /// core.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forge_threads: Option<NonZeroUsize>,
+    /// Whether its fork tests must pin the block they fork, set by `--require-pinned-forks`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub require_pinned_fork: bool,
+    /// Whether the variables documented by its `.env.example` must be set, set by
+    /// `--require-env-example`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub require_env_example: bool,
+    /// Whether its timed runs need the network, e.g. for fork tests, exempting them from
+    /// `--no-network`. Set by its entry.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub needs_network: bool,
+    /// Whether its timed runs are isolated from the network, set by `--no-network`, unless it
+    /// [needs it](Self::network).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub no_network: bool,
+    /// Whether its `forge` commands run with a clean environment, instead of inheriting the
+    /// benchmark's, set by `--clean-env`: only `PATH`, `HOME` and its configured env vars are kept.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub clean_env: bool,
+    /// Revision (branch, tag or full commit hash) the project is checked out at, instead of the
+    /// head of its default branch, set by `project-diff`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// Whether it's cloned for a run outside the benchmark (the canary or the flamegraphs of a
+    /// diff), into a temporary directory that `--keep` and `--keep-failed` never keep.
+    #[serde(skip)]
+    pub scratch: bool,
+}
+
+/// Settings of the local `anvil` node forking the RPC endpoint of a project's fork tests, e.g.
+/// `anvil = { fork_url_env = "MAINNET_RPC_URL", port = 0 }`.
+///
+/// The node is started before the test runs of the project (outside of their timings) and killed
+/// after them.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnvilConfig {
+    /// Variable holding the endpoint to fork, configured or exported. `forge test` gets the
+    /// endpoint of the node in it instead.
+    pub fork_url_env: String,
+    /// Port of the node, or 0 for a free one.
+    #[serde(default)]
+    pub port: u16,
+}
+
+/// Settings of the local proxy caching the deterministic RPC requests of a project's fork tests on
+/// disk, e.g. `rpc_cache = { url_env = "MAINNET_RPC_URL" }`, so that their timings don't depend on
+/// the latency of the upstream endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RpcCacheConfig {
+    /// Variable holding the upstream endpoint, configured or exported. The tests (or the `anvil`
+    /// node forking it) get the endpoint of the proxy in it instead.
+    pub url_env: String,
+}
+
+/// How the configured remappings of a project are written to its `remappings.txt`, set by the
+/// `remappings_mode` of its entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemappingsMode {
+    /// Merged with the project's own remappings, the configured ones winning for the same prefix.
+    #[default]
+    Merge,
+    /// Written as the whole file, dropping the project's own remappings.
+    Replace,
+}
+
+impl RemappingsMode {
+    pub(crate) fn is_merge(&self) -> bool {
+        *self == Self::Merge
+    }
+}
+
+/// The prefix a remapping applies to, with its context if any, e.g. `src:@oz/` for
+/// `src:@oz/=lib/oz/`. The whole remapping without a `=`.
+fn remapping_prefix(remapping: &str) -> &str {
+    remapping
+        .split_once('=')
+        .map_or(remapping, |(prefix, _)| prefix)
+        .trim()
+}
+
+/// Merges the `configured` remappings of a project into its `existing` ones, e.g. the lines of its
+/// `remappings.txt`: an existing remapping of a configured prefix is replaced in place by the
+/// configured one, and the other configured remappings are appended. Blank lines and duplicates
+/// are left out.
+pub fn merge_remappings(existing: &[String], configured: &[String]) -> Vec<String> {
+    let mut merged: Vec<String> = Vec::new();
+    let mut push = |remapping: &str| {
+        if !remapping.is_empty() && !merged.iter().any(|r| r == remapping) {
+            merged.push(remapping.to_string());
+        }
+    };
+    for remapping in existing.iter().map(|r| r.trim()) {
+        let prefix = remapping_prefix(remapping);
+        match configured
+            .iter()
+            .find(|r| remapping_prefix(r) == prefix && !remapping.is_empty())
+        {
+            Some(replacement) => push(replacement.trim()),
+            None => push(remapping),
+        }
+    }
+    for remapping in configured {
+        push(remapping.trim());
+    }
+    merged
+}
+
+/// Parameters of a synthetic project, generated instead of cloned so that its size and shape are
+/// controlled, e.g. `synthetic = { contracts = 200, tests_per_contract = 10, fuzz = true }`.
+///
+/// The generated sources only depend on these parameters, so that every side of a diff benchmarks
+/// the same project.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyntheticConfig {
+    /// Number of contracts, each with its own test contract.
+    pub contracts: usize,
+    /// Number of tests of each contract.
+    pub tests_per_contract: usize,
+    /// Length of the inheritance chains of the contracts, 1 for no inheritance.
+    pub inheritance_depth: usize,
+    /// Whether every other test is a fuzz test.
+    pub fuzz: bool,
+    /// Seed of the generated sources, and of the fuzz tests.
+    pub seed: u64,
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            contracts: 100,
+            tests_per_contract: 5,
+            inheritance_depth: 1,
+            fuzz: false,
+            seed: 0,
+        }
+    }
+}
+
+impl std::fmt::Display for SyntheticConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "contracts={},tests_per_contract={},inheritance_depth={},fuzz={},seed={}",
+            self.contracts, self.tests_per_contract, self.inheritance_depth, self.fuzz, self.seed
+        )
+    }
+}
+
+/// Tolerance (in %) of a [`Budget`] without one set.
+pub const DEFAULT_BUDGET_TOLERANCE: f64 = 10.0;
+
+/// Times a project is expected to take, in seconds, set by the `expected_build_time` and
+/// `expected_test_time` of its entry. A plain run reports the projects slower than their budget,
+/// and fails with `--enforce-budgets`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Budget {
+    pub build_time: Option<f64>,
+    /// Expected time of the test runs, or of `forge fmt` for the projects measuring it.
+    pub test_time: Option<f64>,
+    /// How much slower (in %) than expected a project may be before it's over budget.
+    pub tolerance: f64,
+}
+
+impl Budget {
+    /// Whether `time` exceeds `expected`, beyond the tolerance.
+    pub fn exceeded(&self, time: f64, expected: f64) -> bool {
+        time > expected * (1.0 + self.tolerance / 100.0)
+    }
+}
+
+/// Where a configuration field was resolved from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigOrigin {
+    /// The project's `[[project]]` entry of the config file.
+    Project,
+    /// The `[defaults]` section of the config file.
+    Defaults,
+    /// The `[custom]` section of the config file.
+    Custom,
+    /// The global `--deps`, `--remappings` and `--env` flags.
+    Cli,
+    /// The project's JSON configuration passed to `--repo`.
+    Json,
+    /// The preset selected with `--preset`.
+    Preset,
+}
+
+impl std::fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Project => "file project",
+            Self::Defaults => "file defaults",
+            Self::Custom => "file custom",
+            Self::Cli => "CLI",
+            Self::Json => "--repo JSON",
+            Self::Preset => "preset",
+        })
+    }
+}
+
+/// Origins of the fields of a [`JsonProjectConfig`], `None` for unset fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConfigOrigins {
+    pub dependencies: Option<ConfigOrigin>,
+    pub remappings: Option<ConfigOrigin>,
+    /// Origin of every environment variable, by key.
+    pub env_vars: BTreeMap<String, ConfigOrigin>,
+}
+
+impl ConfigOrigins {
+    /// Origins of the fields set in `config`, all resolved from `origin`.
+    pub fn of(config: &JsonProjectConfig, origin: ConfigOrigin) -> Self {
+        Self {
+            dependencies: config.dependencies.as_ref().map(|_| origin),
+            remappings: config.remappings.as_ref().map(|_| origin),
+            env_vars: config
+                .env_vars
+                .iter()
+                .flatten()
+                .map(|(key, _)| (key.clone(), origin))
+                .collect(),
+        }
+    }
 }
 
 /// JSON configuration for a project (excludes `name`)
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct JsonProjectConfig {
+    /// Repository to clone instead of the one of the project's name, e.g. a fork or a mirror.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dependencies: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -21,27 +451,140 @@ pub struct JsonProjectConfig {
     pub env_vars: Option<HashMap<String, String>>,
 }
 
+impl JsonProjectConfig {
+    /// Expands the environment variables of its env vars, dependencies and remappings, like the
+    /// ones of a config file, see [`config::expand_env_value`].
+    pub fn expand_env_vars(&mut self) {
+        let lists = [&mut self.dependencies, &mut self.remappings];
+        for value in self
+            .env_vars
+            .iter_mut()
+            .flat_map(|env_vars| env_vars.values_mut())
+            .chain(lists.into_iter().flatten().flatten())
+        {
+            *value = config::expand_env_value(value);
+        }
+    }
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of characters and `?` any single
+/// character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` of the pattern, and of the text it matches up to.
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            // Backtrack, the last `*` matching one more character.
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether `name` is a glob pattern, see [`glob_match`].
+pub fn is_glob(name: &str) -> bool {
+    name.contains(['*', '?'])
+}
+
+/// Whether the project `name` matches the glob `pattern`. Like GitHub repositories, names are
+/// matched case-insensitively.
+pub fn name_matches(pattern: &str, name: &str) -> bool {
+    glob_match(&pattern.to_lowercase(), &name.to_lowercase())
+}
+
+/// Quotes `arg` for a POSIX shell, if needed.
+pub fn shell_quote(arg: &str) -> String {
+    if arg
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./:=,@".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Whether `name` looks like a URL rather than an `owner/repo` name.
+pub fn looks_like_url(name: &str) -> bool {
+    name.contains("://") || name.starts_with("git@")
+}
+
+/// Whether `url` is an `owner/repo` GitHub shorthand, rather than a URL or a local path.
+fn is_github_shorthand(url: &str) -> bool {
+    !looks_like_url(url)
+        && !url.starts_with(['.', '/', '~'])
+        && url.split('/').count() == 2
+        && url.split('/').all(|part| !part.is_empty())
+}
+
 impl ProjectConfig {
+    /// Configuration of the project `name`, without any settings.
     pub fn new(name: impl Into<String>) -> Self {
         Self {
             name: name.into(),
             config: JsonProjectConfig::default(),
+            origins: ConfigOrigins::default(),
+            tags: Vec::new(),
+            required_env: Vec::new(),
+            test_args: Vec::new(),
+            anvil: None,
+            rpc_cache: None,
+            command: Measured::Test,
+            script: None,
+            script_args: Vec::new(),
+            synthetic: None,
+            budget: None,
+            build_only: false,
+            measure_discovery: false,
+            incremental_runs: None,
+            incremental_file: None,
+            prepare: None,
+            conclude: None,
+            remappings_mode: RemappingsMode::Merge,
+            timing_source: TimingSource::Process,
+            forge_threads: None,
+            require_pinned_fork: false,
+            require_env_example: false,
+            needs_network: false,
+            no_network: false,
+            clean_env: false,
+            rev: None,
+            scratch: false,
         }
     }
 
-    #[cfg(test)]
+    /// Replaces the settings of the project.
     pub fn with_config(mut self, config: JsonProjectConfig) -> Self {
         self.config = config;
         self
     }
 
-    #[cfg(test)]
+    /// Sets the arguments of the `forge` command installing the dependencies of the project
+    /// before it's built, e.g. `["install", "foundry-rs/forge-std"]`.
     pub fn with_deps(mut self, deps: Vec<impl Into<String>>) -> Self {
         self.config.dependencies = Some(deps.into_iter().map(|d| d.into()).collect());
         self
     }
 
-    #[cfg(test)]
+    /// Sets the remappings written to the project's `remappings.txt`.
     pub fn with_remappings(mut self, remappings: Vec<impl Into<String>>) -> Self {
         self.config.remappings = Some(remappings.into_iter().map(|r| r.into()).collect());
         self
@@ -51,7 +594,6 @@ impl ProjectConfig {
     ///
     /// # Panics
     /// This method will panic if the number of names does not match the number of values.
-    #[cfg(test)]
     pub fn with_env_vars(
         mut self,
         names: Vec<impl Into<String>>,
@@ -73,12 +615,86 @@ impl ProjectConfig {
         self
     }
 
+    /// Runs the fork tests of the project against a local `anvil` node, see [`AnvilConfig`].
+    pub fn with_anvil(mut self, anvil: AnvilConfig) -> Self {
+        self.anvil = Some(anvil);
+        self
+    }
+
+    /// Caches the RPC requests of the project's fork tests, see [`RpcCacheConfig`].
+    pub fn with_rpc_cache(mut self, rpc_cache: RpcCacheConfig) -> Self {
+        self.rpc_cache = Some(rpc_cache);
+        self
+    }
+
+    /// Times `command` instead of `forge test` in the test stage of the project.
+    pub fn with_command(mut self, command: Measured) -> Self {
+        self.command = command;
+        self
+    }
+
+    /// Times `forge script <path>` with `args` after the test runs of the project.
+    pub fn with_script(mut self, path: impl Into<String>, args: Vec<&str>) -> Self {
+        self.script = Some(path.into());
+        self.script_args = args.into_iter().map(String::from).collect();
+        self
+    }
+
+    /// Generates the project from `synthetic` instead of cloning it.
+    pub fn with_synthetic(mut self, synthetic: SyntheticConfig) -> Self {
+        self.synthetic = Some(synthetic);
+        self
+    }
+
+    /// The non-empty value of the variable `key` for the project: its resolved env var, or else
+    /// the exported one.
+    pub fn env_value(&self, key: &str) -> Option<String> {
+        self.env_vars()
+            .and_then(|vars| vars.get(key).cloned())
+            .filter(|value| !value.is_empty())
+            .or_else(|| std::env::var(key).ok().filter(|value| !value.is_empty()))
+    }
+
+    /// Returns a copy of the configuration with the values of its env vars masked.
+    pub fn masked(&self) -> Self {
+        let mut masked = self.clone();
+        for value in masked
+            .config
+            .env_vars
+            .iter_mut()
+            .flat_map(|vars| vars.values_mut())
+        {
+            *value = "***".to_string();
+        }
+        masked
+    }
+
+    /// URL the project is cloned from and linked to: its `url` override if set (with `owner/repo`
+    /// standing for a GitHub repository), or else the GitHub repository of its name, on the
+    /// GitHub instance of the run (see [`github_url`]).
     pub fn url(&self) -> String {
-        format!("{GITHUB_URL}/{name}", name = self.name)
+        if let Some(synthetic) = &self.synthetic {
+            return format!("synthetic:{synthetic}");
+        }
+        match &self.config.url {
+            Some(url) if is_github_shorthand(url) => format!("{}/{url}", github_url()),
+            Some(url) => url.clone(),
+            None if looks_like_url(&self.name) => self.name.clone(),
+            None => format!("{}/{name}", github_url(), name = self.name),
+        }
     }
 
-    pub fn label(&self) -> String {
-        format!("[{name}]", name = self.name)
+    /// Network access of its timed runs: isolated with `--no-network`, unless it needs the network.
+    ///
+    /// A project reaching a local `anvil` node or `rpc_cache` proxy needs it too, since the
+    /// isolated runs don't share the loopback interface of the machine.
+    pub fn network(&self) -> NetworkAccess {
+        let needs_network = self.needs_network || self.anvil.is_some() || self.rpc_cache.is_some();
+        match (self.no_network, needs_network) {
+            (false, _) => NetworkAccess::Allowed,
+            (true, false) => NetworkAccess::Isolated,
+            (true, true) => NetworkAccess::Exempted,
+        }
     }
 
     // Convenience getters that delegate to config
@@ -99,6 +715,64 @@ impl ProjectConfig {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*KEY*", "ETHERSCAN_API_KEY"));
+        assert!(glob_match("*KEY*", "KEY"));
+        assert!(glob_match("RPC_*", "RPC_MAINNET"));
+        assert!(!glob_match("RPC_*", "MAINNET_RPC"));
+        assert!(glob_match("*_URL", "MAINNET_RPC_URL"));
+        assert!(glob_match("A*B*C", "AXXBYYC"));
+        assert!(!glob_match("A*B*C", "AXXCYYB"));
+        assert!(glob_match("EXACT", "EXACT"));
+        assert!(!glob_match("EXACT", "EXACTLY"));
+        // The last part can't overlap the previous ones.
+        assert!(!glob_match("AB*BC", "ABC"));
+        assert!(glob_match("a?c*", "abcdef"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(glob_match("*", ""));
+        assert!(name_matches("Uniswap/*", "uniswap/v4-core"));
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("owner/repo"), "owner/repo");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_750_077_000), "2025-06-16 12:30:00 UTC");
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2000-02-29"), Some(951_782_400));
+        assert_eq!(parse_date("2025-06-16"), Some(1_750_032_000));
+        assert_eq!(parse_date("2025-13-01"), None);
+        assert_eq!(parse_date("2025-06"), None);
+        assert_eq!(parse_date("1969-12-31"), None);
+    }
+
+    #[test]
+    fn test_project_config_masked() {
+        let config = ProjectConfig::new("test/repo")
+            .with_deps(vec!["dep1"])
+            .with_env_vars(vec!["RPC_URL"], vec!["https://secret.rpc"]);
+
+        let masked = config.masked();
+        assert_eq!(masked.dependencies(), config.dependencies());
+        assert_eq!(
+            masked.env_vars().unwrap().get("RPC_URL"),
+            Some(&"***".to_string())
+        );
+        assert!(!serde_json::to_string(&masked).unwrap().contains("secret"));
+    }
+
     #[test]
     fn test_project_config_new() {
         let config = ProjectConfig::new("test/repo");
@@ -115,6 +789,38 @@ mod tests {
         assert_eq!(config.dependencies().unwrap(), &vec!["dep1", "dep2"]);
     }
 
+    #[test]
+    fn test_merge_remappings() {
+        let lines = |remappings: &[&str]| -> Vec<String> {
+            remappings.iter().map(|r| r.to_string()).collect()
+        };
+        let existing = lines(&[
+            "forge-std/=lib/forge-std/src/",
+            "@oz/=lib/openzeppelin-contracts/contracts/",
+            "",
+            "src:@oz/=lib/oz-v4/",
+            "solady/=lib/solady/src/",
+            "solady/=lib/solady/src/",
+        ]);
+        let configured = lines(&["@oz/=lib/oz/", "@new/=lib/new/", "solady/=lib/solady/src/"]);
+        // The configured prefixes win in place, the others are appended, without any duplicate.
+        assert_eq!(
+            merge_remappings(&existing, &configured),
+            [
+                "forge-std/=lib/forge-std/src/",
+                "@oz/=lib/oz/",
+                "src:@oz/=lib/oz-v4/",
+                "solady/=lib/solady/src/",
+                "@new/=lib/new/",
+            ]
+        );
+        assert_eq!(merge_remappings(&[], &configured), configured);
+        assert_eq!(
+            merge_remappings(&existing[..1], &[]),
+            ["forge-std/=lib/forge-std/src/"]
+        );
+    }
+
     #[test]
     fn test_project_config_with_remappings() {
         let config =
@@ -162,19 +868,57 @@ mod tests {
 
     #[test]
     fn test_project_config_url() {
-        let config = ProjectConfig::new("owner/repo");
+        let mut config = ProjectConfig::new("owner/repo");
         assert_eq!(config.url(), "https://github.com/owner/repo");
+
+        for (url, expected) in [
+            ("me/repo-fork", "https://github.com/me/repo-fork"),
+            ("https://gitlab.com/me/repo", "https://gitlab.com/me/repo"),
+            ("git@github.com:me/repo.git", "git@github.com:me/repo.git"),
+            ("/srv/mirrors/repo", "/srv/mirrors/repo"),
+            ("./mirrors/repo", "./mirrors/repo"),
+        ] {
+            config.config.url = Some(url.to_string());
+            assert_eq!(config.url(), expected);
+        }
+        // The name stays the one of the project.
+        assert_eq!(config.name, "owner/repo");
     }
 
     #[test]
-    fn test_project_config_label() {
-        let config = ProjectConfig::new("owner/repo");
-        assert_eq!(config.label(), "[owner/repo]");
+    fn test_parse_github_url() {
+        assert_eq!(
+            parse_github_url("https://github.example.com").unwrap(),
+            "https://github.example.com"
+        );
+        assert_eq!(
+            parse_github_url("https://example.com/github").unwrap(),
+            "https://example.com/github"
+        );
+        assert_eq!(
+            parse_github_url("https://github.example.com/").unwrap_err(),
+            "https://github.example.com/ must not end with a slash"
+        );
+        for url in [
+            "http://github.example.com",
+            "github.example.com",
+            "https://",
+            "https:///owner",
+            "https://github.example.com?x=1",
+        ] {
+            assert!(
+                parse_github_url(url)
+                    .unwrap_err()
+                    .contains("isn't an absolute https URL"),
+                "{url}"
+            );
+        }
     }
 
     #[test]
     fn test_json_project_config_serde() {
         let json_config = JsonProjectConfig {
+            url: None,
             dependencies: Some(vec!["dep1".to_string()]),
             remappings: Some(vec!["@lib/=lib/".to_string()]),
             env_vars: Some(HashMap::from([("KEY".to_string(), "value".to_string())])),
@@ -214,6 +958,7 @@ mod tests {
     #[test]
     fn test_project_config_with_config() {
         let json_config = JsonProjectConfig {
+            url: None,
             dependencies: Some(vec!["dep1".to_string()]),
             remappings: Some(vec!["@lib/=lib/".to_string()]),
             env_vars: Some(HashMap::from([("KEY".to_string(), "value".to_string())])),
@@ -229,4 +974,31 @@ mod tests {
             Some(&"value".to_string())
         );
     }
+
+    #[test]
+    fn test_project_network() {
+        let mut config = ProjectConfig::new("test/repo");
+        assert_eq!(config.network(), NetworkAccess::Allowed);
+        config.no_network = true;
+        assert_eq!(config.network(), NetworkAccess::Isolated);
+
+        // The local node and proxy are only reachable with the network of the machine.
+        let anvil = AnvilConfig {
+            fork_url_env: "MAINNET_RPC_URL".to_string(),
+            port: 0,
+        };
+        assert_eq!(
+            config.clone().with_anvil(anvil).network(),
+            NetworkAccess::Exempted
+        );
+        let rpc_cache = RpcCacheConfig {
+            url_env: "MAINNET_RPC_URL".to_string(),
+        };
+        assert_eq!(
+            config.clone().with_rpc_cache(rpc_cache).network(),
+            NetworkAccess::Exempted
+        );
+        config.needs_network = true;
+        assert_eq!(config.network(), NetworkAccess::Exempted);
+    }
 }
@@ -0,0 +1,511 @@
+use eyre::Result;
+use std::{collections::HashSet, fmt, path::Path};
+use yansi::Paint;
+
+use crate::{
+    config::{self, ConfigFile, ConfigTable},
+    utils,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in a configuration file.
+#[derive(Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Project the finding belongs to, or `None` for global settings.
+    pub project: Option<String>,
+    /// Offending field, e.g. `remappings` or `defaults.env_vars`.
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self.severity {
+            Severity::Warning => Paint::yellow("WARNING:").bold(),
+            Severity::Error => Paint::red("ERROR:").bold(),
+        };
+        match &self.project {
+            Some(project) => write!(f, "{prefix} [{project}] {}: {}", self.field, self.message),
+            None => write!(f, "{prefix} {}: {}", self.field, self.message),
+        }
+    }
+}
+
+/// Findings of [`validate`], in the order they were found.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    fn push(
+        &mut self,
+        severity: Severity,
+        project: Option<&str>,
+        field: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.findings.push(Finding {
+            severity,
+            project: project.map(str::to_string),
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+}
+
+/// Loads the configuration file at `path`, and checks it for mistakes that would otherwise only
+/// surface once a run fails.
+///
+/// Errors if the file can't be read or parsed.
+pub fn validate(path: &str) -> Result<Report> {
+    let (config, unknown_keys) = ConfigFile::load_lenient(path)?;
+    let raw = config::read_raw(Path::new(path))?;
+
+    let mut report = Report::default();
+    // Unknown keys would be rejected by runs, unless passed --lenient-config.
+    for unknown in unknown_keys {
+        let (project, field) = match unknown.table {
+            ConfigTable::Root => (None, unknown.key),
+            ConfigTable::Preset(name) => (None, format!("preset.{name}.{}", unknown.key)),
+            ConfigTable::Global(section) => (None, format!("{section}.{}", unknown.key)),
+            ConfigTable::Project(name) => (name, unknown.key),
+            ConfigTable::Variant(name) => (
+                None,
+                format!("variant.{}.{}", name.as_deref().unwrap_or("?"), unknown.key),
+            ),
+        };
+        let mut message = match unknown.suggestion {
+            Some(suggestion) => format!("unknown key, did you mean `{suggestion}`?"),
+            None => "unknown key".to_string(),
+        };
+        if unknown.path != path {
+            message.push_str(&format!(" (in {})", unknown.path));
+        }
+        report.push(Severity::Error, project.as_deref(), field, message);
+    }
+    check_env_expansions(&raw, &mut report);
+
+    if config.project.is_empty() {
+        report.push(
+            Severity::Warning,
+            None,
+            "project",
+            "no projects are configured, runs will need --repos or --repo",
+        );
+    }
+
+    for project in &config.project {
+        let name = Some(project.name.as_str());
+        if project.url.is_some() && utils::looks_like_url(&project.name) {
+            report.push(
+                Severity::Error,
+                name,
+                "url",
+                "the name is already a URL, use an `owner/repo` name with `url`, or drop `url`",
+            );
+        }
+        check_lists(
+            &mut report,
+            name,
+            "",
+            project.dependencies.as_deref(),
+            project.remappings.as_deref(),
+        );
+    }
+
+    // Projects of included files can be overridden, so only duplicates within the file itself are
+    // reported.
+    let mut names = HashSet::new();
+    for project in raw
+        .get("project")
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if let Some(name) = project.get("name").and_then(|n| n.as_str())
+            && !names.insert(name)
+        {
+            report.push(
+                Severity::Error,
+                Some(name),
+                "name",
+                "duplicate project, only its last entry is used",
+            );
+        }
+    }
+
+    for (section, deps, remappings) in [
+        (
+            "custom.",
+            config.custom.dependencies.as_deref(),
+            config.custom.remappings.as_deref(),
+        ),
+        (
+            "defaults.",
+            config.defaults.dependencies.as_deref(),
+            config.defaults.remappings.as_deref(),
+        ),
+    ] {
+        check_lists(&mut report, None, section, deps, remappings);
+    }
+
+    let mut variants = HashSet::new();
+    for variant in &config.variant {
+        if !variants.insert(&variant.name) {
+            report.push(
+                Severity::Error,
+                None,
+                format!("variant.{}", variant.name),
+                "duplicate variant name",
+            );
+        }
+    }
+    if config.variant.len() == 1 {
+        report.push(
+            Severity::Warning,
+            None,
+            "variant",
+            "a single variant is configured, but matrix needs at least two",
+        );
+    }
+
+    Ok(report)
+}
+
+/// Checks the `dependencies` and `remappings` of a project, or of a global section if `project` is
+/// `None`. `prefix` is prepended to the reported fields.
+fn check_lists(
+    report: &mut Report,
+    project: Option<&str>,
+    prefix: &str,
+    dependencies: Option<&[String]>,
+    remappings: Option<&[String]>,
+) {
+    for remapping in remappings.into_iter().flatten() {
+        if !remapping.contains('=') {
+            report.push(
+                Severity::Error,
+                project,
+                format!("{prefix}remappings"),
+                format!("`{remapping}` is not a `prefix=target` remapping"),
+            );
+        }
+    }
+
+    if let Some(deps) = dependencies {
+        // Dependencies are the args of a `forge install` command.
+        let mut deps = deps.iter();
+        if deps.next().map(String::as_str) != Some("install") {
+            report.push(
+                Severity::Error,
+                project,
+                format!("{prefix}dependencies"),
+                "the first entry must be `install`",
+            );
+        }
+        for dep in deps.filter(|dep| !dep.starts_with('-')) {
+            if !is_dependency(dep) {
+                report.push(
+                    Severity::Warning,
+                    project,
+                    format!("{prefix}dependencies"),
+                    format!("`{dep}` doesn't look like `owner/repo[@ref]` or a git URL"),
+                );
+            }
+        }
+    }
+}
+
+/// Reports env vars, dependencies and remappings whose `${VAR}` expansion failed, as they are
+/// passed through unexpanded.
+fn check_env_expansions(raw: &toml::Table, report: &mut Report) {
+    let mut unset = |project: Option<&str>, field: String, value: &toml::Value| {
+        if let Some(var) = value.as_str().and_then(config::unset_env_var) {
+            report.push(
+                Severity::Warning,
+                project,
+                field,
+                format!("`{var}` is not set, the reference is used as is"),
+            );
+        }
+    };
+    let mut check = |project: Option<&str>, field: &str, values: Option<&toml::Value>| match values
+    {
+        Some(toml::Value::Table(env_vars)) => {
+            for (key, value) in env_vars {
+                unset(project, format!("{field}.{key}"), value);
+            }
+        }
+        Some(toml::Value::Array(list)) => {
+            for value in list {
+                unset(project, field.to_string(), value);
+            }
+        }
+        _ => {}
+    };
+
+    for section in ["custom", "defaults"] {
+        for field in ["env_vars", "dependencies", "remappings"] {
+            let values = raw.get(section).and_then(|s| s.get(field));
+            check(None, &format!("{section}.{field}"), values);
+        }
+    }
+    for project in raw
+        .get("project")
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let name = project.get("name").and_then(|n| n.as_str());
+        for field in ["env_vars", "dependencies", "remappings"] {
+            check(name, field, project.get(field));
+        }
+    }
+    for variant in raw
+        .get("variant")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let name = variant.get("name").and_then(|n| n.as_str()).unwrap_or("?");
+        check(
+            None,
+            &format!("variant.{name}.env_vars"),
+            variant.get("env_vars"),
+        );
+    }
+}
+
+/// Whether `dep` looks like a dependency accepted by `forge install`: `owner/repo[@ref]`, an
+/// aliased `alias=owner/repo[@ref]`, or a git URL.
+fn is_dependency(dep: &str) -> bool {
+    if dep.contains("://") || dep.starts_with("git@") {
+        return true;
+    }
+    let dep = dep.split_once('=').map_or(dep, |(_, dep)| dep);
+    let repo = dep.split_once('@').map_or(dep, |(repo, _)| repo);
+    matches!(
+        repo.split_once('/'),
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn validate_str(contents: &str) -> Report {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("benchmarks.toml");
+        std::fs::write(&path, contents).unwrap();
+        validate(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_validate_valid_config() {
+        let report = validate_str(
+            r#"
+[defaults]
+env_vars = { FOUNDRY_FUZZ_RUNS = "256" }
+
+[[project]]
+name = "sablier-labs/lockup"
+dependencies = ["install", "--no-git", "foundry-rs/forge-std@v1.8.0", "sol=vectorized/solady"]
+remappings = ["forge-std/=lib/forge-std/src/"]
+"#,
+        );
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+    }
+
+    #[test]
+    fn test_validate_findings() {
+        let report = validate_str(
+            r#"
+[defaults]
+env_var = { A = "b" }
+
+[[project]]
+name = "owner/repo"
+remapings = ["a=b"]
+remappings = ["forge-std/"]
+dependencies = ["forge-std"]
+env_vars = { RPC = "${FOUNDRY_BENCHMARKS_UNDEFINED_VAR}" }
+
+[[project]]
+name = "owner/repo"
+"#,
+        );
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|f| (f.severity, f.project.as_deref(), f.field.as_str()))
+            .collect();
+
+        assert_eq!(
+            findings,
+            vec![
+                (Severity::Error, None, "defaults.env_var"),
+                (Severity::Error, Some("owner/repo"), "remapings"),
+                (Severity::Warning, Some("owner/repo"), "env_vars.RPC"),
+                (Severity::Error, Some("owner/repo"), "remappings"),
+                (Severity::Error, Some("owner/repo"), "dependencies"),
+                (Severity::Error, Some("owner/repo"), "name"),
+            ]
+        );
+        assert_eq!(report.errors(), 5);
+        assert_eq!(report.warnings(), 1);
+    }
+
+    #[test]
+    fn test_validate_list_expansions() {
+        let report = validate_str(
+            r#"
+[defaults]
+remappings = ["shared/=${FOUNDRY_BENCHMARKS_UNDEFINED_VAR}/shared/"]
+
+[[project]]
+name = "owner/repo"
+dependencies = ["install", "owner/lib@${FOUNDRY_BENCHMARKS_UNDEFINED_VAR:-v1}"]
+remappings = ["@lib/=$FOUNDRY_BENCHMARKS_UNDEFINED_VAR/lib/"]
+"#,
+        );
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|f| (f.severity, f.project.as_deref(), f.field.as_str()))
+            .collect();
+        // A default makes the reference expandable.
+        assert_eq!(
+            findings,
+            vec![
+                (Severity::Warning, None, "defaults.remappings"),
+                (Severity::Warning, Some("owner/repo"), "remappings"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_global_lists() {
+        let report = validate_str(
+            r#"
+[defaults]
+dependencies = ["foundry-rs/forge-std"]
+remappings = ["@std/=lib/forge-std/src/"]
+
+[custom]
+remappings = ["@std/"]
+
+[[project]]
+name = "owner/repo"
+"#,
+        );
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|f| (f.severity, f.field.as_str()))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (Severity::Error, "custom.remappings"),
+                (Severity::Error, "defaults.dependencies"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_url() {
+        let report = validate_str(
+            r#"
+[[project]]
+name = "vectorized/solady"
+url = "me/solady-fork"
+
+[[project]]
+name = "https://github.com/me/repo"
+url = "https://gitlab.com/me/repo"
+"#,
+        );
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|f| (f.project.as_deref(), f.field.as_str()))
+            .collect();
+        assert_eq!(findings, vec![(Some("https://github.com/me/repo"), "url")]);
+    }
+
+    #[test]
+    fn test_validate_variants() {
+        let report = validate_str(
+            r#"
+[[project]]
+name = "owner/repo"
+
+[[variant]]
+name = "fuzz-256"
+env_vars = { FOUNDRY_FUZZ_RUNS = "256" }
+
+[[variant]]
+name = "fuzz-256"
+env = { FOUNDRY_FUZZ_RUNS = "1024" }
+"#,
+        );
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|f| (f.severity, f.field.as_str()))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (Severity::Error, "variant.fuzz-256.env"),
+                (Severity::Error, "variant.fuzz-256"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_empty_project_list() {
+        let report = validate_str("[defaults]\n");
+        assert_eq!(report.errors(), 0);
+        assert_eq!(report.warnings(), 1);
+        assert_eq!(report.findings[0].field, "project");
+    }
+
+    #[test]
+    fn test_is_dependency() {
+        assert!(is_dependency("foundry-rs/forge-std"));
+        assert!(is_dependency("foundry-rs/forge-std@v1.8.0"));
+        assert!(is_dependency(
+            "oz=OpenZeppelin/openzeppelin-contracts@v5.0.2"
+        ));
+        assert!(is_dependency("https://github.com/foundry-rs/forge-std"));
+        assert!(is_dependency("git@github.com:foundry-rs/forge-std.git"));
+        assert!(!is_dependency("forge-std"));
+        assert!(!is_dependency("a/b/c"));
+        assert!(!is_dependency("/forge-std"));
+    }
+}
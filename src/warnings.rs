@@ -0,0 +1,312 @@
+use std::{collections::BTreeMap, fmt::Write as _, path::Path};
+
+use serde::Serialize;
+
+use crate::{benchmark::Benchmarks, logging::strip_ansi, ui};
+
+/// The warnings printed by a `forge build` in `dir`, e.g.
+/// `Warning (2072): Unused local variable. --> src/A.sol:12:9`: the compiler's, and forge's lints.
+///
+/// Every warning is normalized into its first line, followed by its location relative to `dir`,
+/// so that the same warning compares equal between the sides of a diff, which build in different
+/// directories.
+pub fn parse(output: &str, dir: &Path) -> Vec<String> {
+    let dirs: Vec<String> = std::iter::once(dir.to_path_buf())
+        .chain(dir.canonicalize().ok())
+        .map(|dir| format!("{}/", dir.display()))
+        .collect();
+    let mut warnings = Vec::new();
+    let mut current: Option<String> = None;
+    for line in strip_ansi(output).lines() {
+        let trimmed = line.trim();
+        if is_warning(trimmed) {
+            warnings.extend(current.replace(trimmed.to_string()));
+        } else if let Some(location) = trimmed.strip_prefix("-->")
+            && let Some(warning) = &mut current
+        {
+            let mut location = location.trim().trim_end_matches(':');
+            for dir in &dirs {
+                location = location.strip_prefix(dir.as_str()).unwrap_or(location);
+            }
+            let _ = write!(warning, " --> {location}");
+            warnings.extend(current.take());
+        } else if trimmed.is_empty() || is_error(trimmed) {
+            warnings.extend(current.take());
+        }
+    }
+    warnings.extend(current);
+    warnings
+}
+
+/// Whether `line` starts a warning: `Warning (2072): ...` and `Warning: ...` from solc, or
+/// `warning[mixed-case-variable]: ...` from forge's lints.
+fn is_warning(line: &str) -> bool {
+    line.strip_prefix("Warning")
+        .is_some_and(|rest| rest.starts_with(": ") || rest.starts_with(" ("))
+        || line.starts_with("warning[")
+        || line.starts_with("warning: ")
+}
+
+fn is_error(line: &str) -> bool {
+    line.starts_with("Error") || line.starts_with("error")
+}
+
+/// The build warnings of a project with every source of a diff.
+#[derive(Debug, Serialize)]
+pub struct ProjectWarnings<'a> {
+    pub project: &'a str,
+    /// Number of warnings with every source, if it was built with it.
+    pub counts: Vec<Option<usize>>,
+    /// The warnings of every comparison source that its build and the baseline's don't share.
+    pub changes: Vec<WarningChanges<'a>>,
+}
+
+/// The warnings that only a comparison source, or only the baseline, printed.
+#[derive(Debug, Default, Serialize)]
+pub struct WarningChanges<'a> {
+    /// Printed with the comparison source, but not with the baseline.
+    pub new: Vec<&'a str>,
+    /// Printed with the baseline, but not with the comparison source.
+    pub gone: Vec<&'a str>,
+}
+
+impl ProjectWarnings<'_> {
+    fn changed(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|change| !change.new.is_empty() || !change.gone.is_empty())
+    }
+}
+
+/// The build warnings of every project of `b` built with some source, without the projects
+/// building without any warning.
+pub fn compare(b: &Benchmarks) -> Vec<ProjectWarnings<'_>> {
+    let mut projects: Vec<&str> = Vec::new();
+    for tested in b.results.iter().flat_map(|r| &r.tests) {
+        if !projects.contains(&tested.name.as_str()) {
+            projects.push(&tested.name);
+        }
+    }
+    projects
+        .into_iter()
+        .filter_map(|project| {
+            let warnings: Vec<Option<&[String]>> = b
+                .results
+                .iter()
+                .map(|r| {
+                    let tested = r.tests.iter().find(|t| t.name == project)?;
+                    tested.build_warnings.as_deref()
+                })
+                .collect();
+            if warnings.iter().flatten().all(|w| w.is_empty()) {
+                return None;
+            }
+            let changes = warnings[1..]
+                .iter()
+                .map(|after| match (warnings[0], after) {
+                    (Some(before), Some(after)) => WarningChanges {
+                        new: only_in(after, before),
+                        gone: only_in(before, after),
+                    },
+                    _ => WarningChanges::default(),
+                })
+                .collect();
+            Some(ProjectWarnings {
+                project,
+                counts: warnings.iter().map(|w| w.map(<[String]>::len)).collect(),
+                changes,
+            })
+        })
+        .collect()
+}
+
+/// The warnings of `a` that `b` doesn't have, as many times as `a` has them more than `b`.
+fn only_in<'a>(a: &'a [String], b: &[String]) -> Vec<&'a str> {
+    let mut left: BTreeMap<&str, usize> = BTreeMap::new();
+    for warning in b {
+        *left.entry(warning).or_default() += 1;
+    }
+    a.iter()
+        .filter(|warning| match left.get_mut(warning.as_str()) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                false
+            }
+            _ => true,
+        })
+        .map(String::as_str)
+        .collect()
+}
+
+/// Renders the build warnings of the projects of `b` with every source, and those printed with
+/// only one side of a comparison. With `collapsible`, the section is folded in a `<details>` block
+/// (for markdown), so that it doesn't drown the tables.
+///
+/// Empty without a comparison source, or when no project built with any warning.
+pub fn render(b: &Benchmarks, collapsible: bool) -> String {
+    let projects = compare(b);
+    if b.results.len() < 2 || projects.is_empty() {
+        return String::new();
+    }
+    let changed = projects.iter().filter(|p| p.changed()).count();
+    let title = format!("build warnings ({changed} project(s) with different warnings)");
+    let mut out = if collapsible {
+        format!("\n<details>\n<summary>{title}</summary>\n\n")
+    } else {
+        format!("\n## {title}\n\n")
+    };
+
+    let names: Vec<String> = b
+        .results
+        .iter()
+        .map(|r| ui::escape_markdown(&r.display_name()))
+        .collect();
+    out.push_str("| Project |");
+    for name in &names {
+        let _ = write!(out, " {name} |");
+    }
+    out.push_str("\n|---------|");
+    out.push_str(&"---|".repeat(names.len()));
+    out.push('\n');
+    for project in &projects {
+        let _ = write!(out, "| {} |", ui::escape_markdown(project.project));
+        for count in &project.counts {
+            match count {
+                Some(count) => {
+                    let _ = write!(out, " {count} |");
+                }
+                None => out.push_str(" n/a |"),
+            }
+        }
+        out.push('\n');
+    }
+
+    for project in projects.iter().filter(|p| p.changed()) {
+        for (name, change) in names[1..].iter().zip(&project.changes) {
+            if change.new.is_empty() && change.gone.is_empty() {
+                continue;
+            }
+            let _ = writeln!(
+                out,
+                "\n**{}** with {name}:\n",
+                ui::escape_markdown(project.project)
+            );
+            for (kind, warnings) in [("new", &change.new), ("gone", &change.gone)] {
+                for warning in warnings.iter() {
+                    let _ = writeln!(out, "- {kind}: {}", ui::escape_markdown(warning));
+                }
+            }
+        }
+    }
+    let _ = write!(
+        out,
+        "\nnote: the warnings printed by `forge build`. The new ones are only printed with the comparison source, the gone ones only with {}.\n",
+        names[0]
+    );
+    if collapsible {
+        out.push_str("\n</details>\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::{Source, SourceResults, Tested};
+
+    #[test]
+    fn test_parse() {
+        let dir = Path::new("/tmp/.tmpAbC/a__a");
+        let output = "Compiling 2 files with Solc 0.8.28
+Compiler run successful with warnings:
+\x1b[33mWarning (2072)\x1b[0m: Unused local variable.
+  --> /tmp/.tmpAbC/a__a/src/A.sol:12:9:
+   |
+12 |         uint256 x = 1;
+   |         ^^^^^^^^^
+
+Warning: SPDX license identifier not provided in source file.
+--> test/B.t.sol
+
+warning[mixed-case-variable]: mutable variables should use mixedCase
+  --> src/A.sol:5:20
+   |
+note: this is not a warning
+Warning (5667): Unused function parameter.
+";
+        assert_eq!(
+            parse(output, dir),
+            [
+                "Warning (2072): Unused local variable. --> src/A.sol:12:9",
+                "Warning: SPDX license identifier not provided in source file. --> test/B.t.sol",
+                "warning[mixed-case-variable]: mutable variables should use mixedCase --> src/A.sol:5:20",
+                "Warning (5667): Unused function parameter.",
+            ]
+        );
+        assert!(parse("Compiler run successful!\n", dir).is_empty());
+    }
+
+    fn tested(name: &str, warnings: Option<&[&str]>) -> Tested {
+        Tested {
+            build_warnings: warnings
+                .map(|warnings| warnings.iter().map(|warning| warning.to_string()).collect()),
+            ..Tested::fixture(name, 1.0)
+        }
+    }
+
+    #[test]
+    fn test_compare_and_render() {
+        let source = |source, tests| SourceResults {
+            source,
+            foundry_repo: "foundry-rs/foundry".to_string(),
+            label: None,
+            forge: None,
+            tests,
+        };
+        let b = Benchmarks {
+            verbosity: String::new(),
+            num_runs: 1,
+            results: vec![
+                source(
+                    Source::Version("stable".to_string()),
+                    vec![
+                        tested("a/a", Some(&["W1", "W2", "W2"])),
+                        tested("b/b", Some(&[])),
+                        tested("c/c", Some(&["W3"])),
+                    ],
+                ),
+                source(
+                    Source::Branch("feat".to_string()),
+                    vec![
+                        tested("a/a", Some(&["W2", "W4"])),
+                        tested("b/b", Some(&[])),
+                        tested("c/c", None),
+                    ],
+                ),
+            ],
+            notes: Vec::new(),
+            reproduction: None,
+            noise_threshold: None,
+            noisy_threshold: ui::DEFAULT_NOISY_THRESHOLD,
+        };
+
+        let projects = compare(&b);
+        // b/b has no warning with either source.
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects[0].counts, [Some(3), Some(2)]);
+        assert_eq!(projects[0].changes[0].new, ["W4"]);
+        assert_eq!(projects[0].changes[0].gone, ["W1", "W2"]);
+        // Without the warnings of a side, nothing is compared.
+        assert_eq!(projects[1].counts, [Some(1), None]);
+        assert!(!projects[1].changed());
+
+        let markdown = render(&b, true);
+        assert!(markdown.starts_with(
+            "\n<details>\n<summary>build warnings (1 project(s) with different warnings)</summary>\n\n"
+        ));
+        assert!(markdown.contains("| a/a | 3 | 2 |\n| c/c | 1 | n/a |\n"));
+        assert!(markdown.contains("**a/a** with feat:\n\n- new: W4\n- gone: W1\n- gone: W2\n"));
+        assert!(markdown.ends_with("</details>\n"));
+        assert!(!render(&b, false).contains("<details>"));
+    }
+}
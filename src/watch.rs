@@ -0,0 +1,145 @@
+use eyre::{Result, eyre};
+use std::{
+    process::Command,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use yansi::Paint;
+
+use crate::{anvil, utils};
+
+/// Granularity at which a sleeping watch checks for Ctrl-C.
+const POLL_STEP: Duration = Duration::from_millis(200);
+
+/// Ctrl-C handler of `diff --watch`. The first Ctrl-C asks the watch to stop once the current
+/// iteration is over, and a second one exits right away.
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl Interrupt {
+    pub fn install() -> Self {
+        let flag = Arc::new(AtomicBool::new(false));
+        let handler_flag = Arc::clone(&flag);
+        let handler = move || {
+            if handler_flag.swap(true, Ordering::SeqCst) {
+                anvil::kill_all();
+                std::process::exit(130);
+            }
+            eprintln!(
+                "\n{} Stopping after the current iteration, press Ctrl-C again to exit now",
+                Paint::yellow("WATCH:").bold()
+            );
+        };
+        if let Err(e) = ctrlc::set_handler(handler) {
+            eprintln!(
+                "{} Failed to handle Ctrl-C, stopping the watch may interrupt an iteration. {e}",
+                Paint::yellow("WARNING:").bold()
+            );
+        }
+        Self(flag)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Sleeps for `duration`, waking up early on Ctrl-C. Returns whether the watch should go on.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let start = Instant::now();
+        while !self.is_set() {
+            let Some(left) = duration.checked_sub(start.elapsed()) else {
+                return true;
+            };
+            thread::sleep(left.min(POLL_STEP));
+        }
+        false
+    }
+}
+
+/// Returns the commit at the head of `branch` in the repository `foundry_repo` of the configured
+/// GitHub instance (see [`utils::github_url`]), without cloning it.
+pub fn head_sha(foundry_repo: &str, branch: &str) -> Result<String> {
+    let output = Command::new("git")
+        .args([
+            "ls-remote",
+            &format!("{}/{foundry_repo}", utils::github_url()),
+        ])
+        .arg(format!("refs/heads/{branch}"))
+        .output()
+        .map_err(|e| eyre!("Failed to run 'git ls-remote'. Error: {e}"))?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{} git ls-remote failed: {}",
+            Paint::red("ERROR:").bold(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse_ls_remote(&String::from_utf8_lossy(&output.stdout), branch).ok_or_else(|| {
+        eyre!(
+            "{} Branch {branch} not found in {foundry_repo}",
+            Paint::red("ERROR:").bold()
+        )
+    })
+}
+
+/// Finds the commit of `branch` in the output of `git ls-remote`.
+fn parse_ls_remote(output: &str, branch: &str) -> Option<String> {
+    let reference = format!("refs/heads/{branch}");
+    output.lines().find_map(|line| {
+        let (sha, name) = line.split_once('\t')?;
+        (name == reference).then(|| sha.to_string())
+    })
+}
+
+/// Path the results of the iteration measuring `sha` are saved to: `path`, suffixed with the
+/// short commit hash (e.g. `results-0123abc.json`).
+pub fn iteration_path(path: &str, sha: &str) -> String {
+    let short = &sha[..sha.len().min(7)];
+    match path.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.contains('/') => {
+            format!("{stem}-{short}.{ext}")
+        }
+        _ => format!("{path}-{short}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ls_remote() {
+        let output = "1111111111111111111111111111111111111111\trefs/heads/feat/perf-2\n\
+                      2222222222222222222222222222222222222222\trefs/heads/feat/perf\n";
+        assert_eq!(
+            parse_ls_remote(output, "feat/perf").as_deref(),
+            Some("2222222222222222222222222222222222222222")
+        );
+        assert_eq!(parse_ls_remote(output, "master"), None);
+        assert_eq!(parse_ls_remote("", "master"), None);
+    }
+
+    #[test]
+    fn test_iteration_path() {
+        let sha = "0123abcdef0123abcdef0123abcdef0123abcdef";
+        assert_eq!(iteration_path("results.json", sha), "results-0123abc.json");
+        assert_eq!(
+            iteration_path("out/run.v2/results", sha),
+            "out/run.v2/results-0123abc"
+        );
+        assert_eq!(iteration_path(".results", sha), ".results-0123abc");
+    }
+
+    #[test]
+    fn test_interrupt_sleep() {
+        let interrupt = Interrupt(Arc::new(AtomicBool::new(false)));
+        assert!(interrupt.sleep(Duration::from_millis(10)));
+        interrupt.0.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+        assert!(!interrupt.sleep(Duration::from_secs(60)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}